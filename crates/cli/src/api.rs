@@ -0,0 +1,676 @@
+//! `td api serve`: an opt-in JSON API over HTTP/1.1 on 127.0.0.1 for scripts and
+//! dashboards. Every request needs `Authorization: Bearer <token>` matching the hashed
+//! `api.token` setting. Each connection is handled on its own thread with its own
+//! database connection, so a long run or transfer does not hold up other requests;
+//! `POST /v1/runs/stream` reports its progress as server-sent events.
+//!
+//! This is a small hand-rolled server rather than axum: the API is a handful of JSON
+//! routes on localhost, every handler calls the same blocking `tdcore` code as the CLI,
+//! and axum would bring tokio and hyper into an otherwise synchronous binary for little
+//! gain. Only what the routes need is parsed (one request per connection, no chunked
+//! bodies, header and body size limits).
+//!
+//! Critical profiles (and profiles covered by `confirm.token`) are only touched when the
+//! request body carries `"confirm": "<profile_id>"`, the API's stand-in for the prompt.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tdcore::cmdset::CmdSetStore;
+use tdcore::cmdset_runner;
+use tdcore::db;
+use tdcore::maintenance::{self, WindowMode};
+use tdcore::oplog::{self, OpLogQuery};
+use tdcore::policy::{self, PolicyAction};
+use tdcore::profile::{DangerLevel, Profile, ProfileFilters, ProfileStore, ProfileType};
+use tdcore::run_events::RunEventBus;
+use tdcore::settings;
+use tdcore::transfer::TransferDirection;
+use tdcore::util;
+use tracing::{info, warn};
+
+use crate::exit::{self, ExitStatus};
+
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+const MAX_HEADER_LINES: usize = 100;
+/// Longest request or header line read; a longer one is answered with 431.
+const MAX_HEADER_BYTES: usize = 8 * 1024;
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_OPLOG_LIMIT: usize = 100;
+/// Connections handled at once; more are answered with 503 right away.
+const MAX_CONNECTIONS: usize = 32;
+
+#[derive(Debug, Default)]
+pub struct ApiRequest {
+    pub method: String,
+    pub path: String,
+    pub query: BTreeMap<String, String>,
+    /// Header names are lowercased.
+    pub headers: BTreeMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// A request or header line longer than `MAX_HEADER_BYTES`.
+#[derive(Debug)]
+struct HeaderTooLarge;
+
+impl std::fmt::Display for HeaderTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "request header line longer than {MAX_HEADER_BYTES} bytes"
+        )
+    }
+}
+
+impl std::error::Error for HeaderTooLarge {}
+
+#[derive(Debug)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl ApiResponse {
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.into() }),
+        }
+    }
+}
+
+/// Accepts connections until the process is interrupted, handling each on its own thread.
+pub fn serve(listener: TcpListener) -> Result<()> {
+    let active = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("api accept failed: {err}");
+                continue;
+            }
+        };
+        if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            active.fetch_sub(1, Ordering::SeqCst);
+            let busy = ApiResponse::error(503, "too many concurrent requests");
+            if let Err(err) = write_response(&mut stream, &busy) {
+                warn!("api connection failed: {err:#}");
+            }
+            continue;
+        }
+        let worker_active = Arc::clone(&active);
+        let spawned = thread::Builder::new()
+            .name("td-api-conn".to_string())
+            .spawn(move || {
+                if let Err(err) = handle_connection(stream) {
+                    warn!("api connection failed: {err:#}");
+                }
+                worker_active.fetch_sub(1, Ordering::SeqCst);
+            });
+        if let Err(err) = spawned {
+            active.fetch_sub(1, Ordering::SeqCst);
+            warn!("api connection thread failed to start: {err}");
+        }
+    }
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream) -> Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let response = match read_request(&mut BufReader::new(stream)) {
+        Ok(request) if request.method == "POST" && request.path == "/v1/runs/stream" => {
+            let status = stream_run(&request, &mut writer)?;
+            info!("api {} {} -> {status}", request.method, request.path);
+            return Ok(());
+        }
+        Ok(request) => {
+            let response = handle(&request);
+            info!(
+                "api {} {} -> {}",
+                request.method, request.path, response.status
+            );
+            response
+        }
+        Err(err) if err.is::<HeaderTooLarge>() => ApiResponse::error(431, format!("{err:#}")),
+        Err(err) => ApiResponse::error(400, format!("{err:#}")),
+    };
+    write_response(&mut writer, &response)?;
+    Ok(())
+}
+
+pub fn read_request(reader: &mut impl BufRead) -> Result<ApiRequest> {
+    let mut line = String::new();
+    read_header_line(reader, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        anyhow::bail!("malformed request line");
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = ApiRequest {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (percent_decode(key), percent_decode(value))
+            })
+            .collect(),
+        ..ApiRequest::default()
+    };
+    for _ in 0..MAX_HEADER_LINES {
+        line.clear();
+        read_header_line(reader, &mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            request
+                .headers
+                .insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    let length = match request.headers.get("content-length") {
+        Some(raw) => raw.parse::<usize>().context("invalid Content-Length")?,
+        None => 0,
+    };
+    if length > MAX_BODY_BYTES {
+        anyhow::bail!("request body larger than {MAX_BODY_BYTES} bytes");
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body)?;
+    Ok(request)
+}
+
+/// `read_line` that stops after `MAX_HEADER_BYTES`, so a client cannot grow `line` without
+/// bound by never sending a newline.
+fn read_header_line(reader: &mut impl BufRead, line: &mut String) -> Result<()> {
+    let limit = MAX_HEADER_BYTES as u64 + 1;
+    if reader.by_ref().take(limit).read_line(line)? > MAX_HEADER_BYTES {
+        return Err(HeaderTooLarge.into());
+    }
+    Ok(())
+}
+
+fn write_response(writer: &mut impl Write, response: &ApiResponse) -> io::Result<()> {
+    let body = serde_json::to_vec_pretty(&response.body)?;
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        431 => "Request Header Fields Too Large",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        body.len()
+    )?;
+    writer.write_all(&body)?;
+    writer.flush()
+}
+
+/// Authenticates and routes one request.
+pub fn handle(request: &ApiRequest) -> ApiResponse {
+    if let Some(denied) = check_auth(request) {
+        return denied;
+    }
+    match route(request) {
+        Ok(Some(body)) => ApiResponse { status: 200, body },
+        Ok(None) => ApiResponse::error(
+            404,
+            format!("no route for {} {}", request.method, request.path),
+        ),
+        Err(err) => ApiResponse::error(status_for(&err), format!("{err:#}")),
+    }
+}
+
+fn check_auth(request: &ApiRequest) -> Option<ApiResponse> {
+    let result = db::init_connection()
+        .map_err(anyhow::Error::from)
+        .and_then(|conn| authorized(&conn, request));
+    match result {
+        Ok(true) => None,
+        Ok(false) => Some(ApiResponse::error(401, "missing or invalid bearer token")),
+        Err(err) => Some(ApiResponse::error(500, format!("{err:#}"))),
+    }
+}
+
+fn authorized(conn: &rusqlite::Connection, request: &ApiRequest) -> Result<bool> {
+    let Some(token) = request
+        .headers
+        .get("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return Ok(false);
+    };
+    Ok(settings::api_token_matches(conn, token.trim())?)
+}
+
+fn status_for(err: &anyhow::Error) -> u16 {
+    match exit::classify(err) {
+        ExitStatus::NotFound => 404,
+        ExitStatus::Invalid => 400,
+        ExitStatus::PolicyDenied => 403,
+        ExitStatus::RemoteFailure => 502,
+        _ => 500,
+    }
+}
+
+fn route(request: &ApiRequest) -> Result<Option<Value>> {
+    let segments = request
+        .path
+        .trim_matches('/')
+        .split('/')
+        .collect::<Vec<_>>();
+    let value = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["v1", "profiles"]) => {
+            let store = ProfileStore::new(db::init_connection()?);
+            serde_json::to_value(store.list_filtered(&ProfileFilters::default())?)?
+        }
+        ("GET", ["v1", "profiles", profile_id]) => {
+            let store = ProfileStore::new(db::init_connection()?);
+            serde_json::to_value(find_profile(&store, profile_id)?)?
+        }
+        ("GET", ["v1", "cmdsets"]) => {
+            let store = CmdSetStore::new(db::init_connection()?);
+            let cmdsets = store
+                .list()?
+                .into_iter()
+                .map(|cmdset| json!({ "cmdset_id": cmdset.cmdset_id, "name": cmdset.name }))
+                .collect::<Vec<_>>();
+            Value::Array(cmdsets)
+        }
+        ("GET", ["v1", "oplog"]) => oplog_json(request)?,
+        ("POST", ["v1", "runs"]) => run(parse_body(request)?)?,
+        ("POST", ["v1", "transfers"]) => transfer(parse_body(request)?)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(value))
+}
+
+fn oplog_json(request: &ApiRequest) -> Result<Value> {
+    let query = &request.query;
+    let parse_i64 = |key: &str| -> Result<Option<i64>> {
+        query
+            .get(key)
+            .map(|raw| {
+                raw.parse()
+                    .map_err(|_| exit::invalid(format!("{key} must be epoch milliseconds")))
+            })
+            .transpose()
+    };
+    let ok = match query.get("ok").map(String::as_str) {
+        None => None,
+        Some("true") => Some(true),
+        Some("false") => Some(false),
+        Some(other) => return Err(exit::invalid(format!("ok must be true or false: {other}"))),
+    };
+    let limit = match query.get("limit") {
+        Some(raw) => raw
+            .parse()
+            .map_err(|_| exit::invalid(format!("limit must be a number: {raw}")))?,
+        None => DEFAULT_OPLOG_LIMIT,
+    };
+    let offset = match query.get("offset") {
+        Some(raw) => raw
+            .parse()
+            .map_err(|_| exit::invalid(format!("offset must be a number: {raw}")))?,
+        None => 0,
+    };
+    let conn = db::init_connection()?;
+    let records = oplog::list_operations(
+        &conn,
+        &OpLogQuery {
+            profile_id: query.get("profile_id").cloned(),
+            op: query.get("op").cloned(),
+            ok,
+            since_ms: parse_i64("since")?,
+            until_ms: parse_i64("until")?,
+            operator: query.get("operator").cloned(),
+            limit,
+            offset,
+        },
+    )?;
+    Ok(serde_json::to_value(records)?)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RunBody {
+    profile_id: String,
+    cmdset_id: String,
+    #[serde(default)]
+    confirm: Option<String>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Stores and the checked profile for a run, shared by `/v1/runs` and its stream.
+type PreparedRun = (ProfileStore, CmdSetStore, Profile, String);
+
+fn prepare_run(body: RunBody) -> Result<PreparedRun> {
+    let profile_store = ProfileStore::new(db::init_connection()?);
+    let cmdset_store = CmdSetStore::new(db::init_connection()?);
+    let profile = find_profile(&profile_store, &body.profile_id)?;
+    if profile.profile_type != ProfileType::Ssh {
+        return Err(exit::invalid("run only supports SSH profiles for now"));
+    }
+    let commands = crate::cmdset_commands(&cmdset_store, &body.cmdset_id)?;
+    ensure_confirmed(
+        &profile_store,
+        &profile,
+        PolicyAction::Run {
+            cmdset_id: &body.cmdset_id,
+            commands: &commands,
+        },
+        body.confirm.as_deref(),
+        body.reason.as_deref(),
+    )?;
+    Ok((profile_store, cmdset_store, profile, body.cmdset_id))
+}
+
+fn run(body: RunBody) -> Result<Value> {
+    let (profile_store, cmdset_store, profile, cmdset_id) = prepare_run(body)?;
+    let env = cmdset_runner::daemon_secret_env(&cmdset_store, &cmdset_id)?;
+    let result = crate::run_profile_cmdset(
+        &profile_store,
+        &cmdset_store,
+        &profile,
+        &cmdset_id,
+        "api",
+        None,
+        false,
+        &env,
+        |_| Ok(()),
+    )?;
+    Ok(crate::run_result_json(&result))
+}
+
+/// `POST /v1/runs/stream`: the run's events as server-sent events, then a `result` (the
+/// `/v1/runs` document) or `error` event. The run finishes even if the client leaves.
+fn stream_run(request: &ApiRequest, writer: &mut impl Write) -> Result<u16> {
+    if let Some(denied) = check_auth(request) {
+        write_response(writer, &denied)?;
+        return Ok(denied.status);
+    }
+    let (profile_store, cmdset_store, profile, cmdset_id) =
+        match parse_body(request).and_then(prepare_run) {
+            Ok(prepared) => prepared,
+            Err(err) => {
+                let response = ApiResponse::error(status_for(&err), format!("{err:#}"));
+                write_response(writer, &response)?;
+                return Ok(response.status);
+            }
+        };
+    write!(
+        writer,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n"
+    )?;
+    writer.flush()?;
+    let bus = RunEventBus::new();
+    let events = bus.subscribe();
+    let worker = thread::spawn(move || {
+        let env = cmdset_runner::daemon_secret_env(&cmdset_store, &cmdset_id)?;
+        crate::run_profile_cmdset(
+            &profile_store,
+            &cmdset_store,
+            &profile,
+            &cmdset_id,
+            "api",
+            Some(&bus),
+            false,
+            &env,
+            |_| Ok(()),
+        )
+    });
+    let mut connected = true;
+    for event in events {
+        if connected {
+            connected = write_event(writer, event.name(), &serde_json::to_value(&event)?).is_ok();
+        }
+    }
+    let (name, data) = match worker.join() {
+        Ok(Ok(result)) => ("result", crate::run_result_json(&result)),
+        Ok(Err(err)) => (
+            "error",
+            json!({ "status": status_for(&err), "error": format!("{err:#}") }),
+        ),
+        Err(_) => (
+            "error",
+            json!({ "status": 500, "error": "run worker panicked" }),
+        ),
+    };
+    if connected {
+        write_event(writer, name, &data)?;
+    }
+    Ok(200)
+}
+
+fn write_event(writer: &mut impl Write, name: &str, data: &Value) -> io::Result<()> {
+    write!(writer, "event: {name}\ndata: {data}\n\n")?;
+    writer.flush()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TransferBody {
+    profile_id: String,
+    direction: String,
+    local_path: PathBuf,
+    remote_path: String,
+    #[serde(default = "default_via")]
+    via: String,
+    #[serde(default)]
+    confirm: Option<String>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+fn default_via() -> String {
+    "scp".to_string()
+}
+
+fn transfer(body: TransferBody) -> Result<Value> {
+    let store = ProfileStore::new(db::init_connection()?);
+    let profile = find_profile(&store, &body.profile_id)?;
+    if profile.profile_type != ProfileType::Ssh {
+        return Err(exit::invalid("transfers only support SSH profiles"));
+    }
+    let direction = TransferDirection::parse(&body.direction)?;
+    if body.via.eq_ignore_ascii_case("ftp") {
+        return Err(exit::policy_denied(
+            "ftp transfers are not available over the API; use scp or sftp",
+        ));
+    }
+    ensure_confirmed(
+        &store,
+        &profile,
+        PolicyAction::Transfer {
+            upload: direction == TransferDirection::Push,
+        },
+        body.confirm.as_deref(),
+        body.reason.as_deref(),
+    )?;
+    crate::transfer_for_profile(
+        &store,
+        &profile,
+        direction,
+        &crate::TransferArgs {
+            profile_id: body.profile_id,
+            local_path: body.local_path,
+            remote_path: body.remote_path,
+            via: body.via,
+            i_know_its_insecure: false,
+        },
+    )?;
+    Ok(json!({ "ok": true }))
+}
+
+/// The API's version of the danger prompt: maintenance windows and `policy.danger` apply,
+/// the body must name the profile in `confirm`, and `reason` must be set when the policy or
+/// an open maintenance window asks for one.
+fn ensure_confirmed(
+    store: &ProfileStore,
+    profile: &Profile,
+    action: PolicyAction<'_>,
+    confirm: Option<&str>,
+    reason: Option<&str>,
+) -> Result<()> {
+    let window = maintenance::active_for(store.conn(), profile, util::now_ms())?;
+    if let Some(window) = window
+        .as_ref()
+        .filter(|window| window.mode == WindowMode::Block)
+    {
+        return Err(window.denied(profile, action.as_str()).into());
+    }
+    let verdict = policy::check(store.conn(), profile, &action, util::now_ms())?;
+    let required = verdict.confirm
+        || profile.danger_level == DangerLevel::Critical
+        || settings::confirm_token_required(store.conn(), profile)?;
+    if required && confirm != Some(profile.profile_id.as_str()) {
+        return Err(exit::policy_denied(format!(
+            "{} profile not confirmed: set \"confirm\": \"{}\" in the request",
+            profile.danger_level, profile.profile_id
+        )));
+    }
+    if verdict.reason || window.is_some() {
+        let Some(reason) = reason.map(str::trim).filter(|reason| !reason.is_empty()) else {
+            return Err(exit::policy_denied(format!(
+                "{} on {} profile {} requires a reason: set \"reason\" in the request",
+                action.as_str(),
+                profile.danger_level,
+                profile.profile_id
+            )));
+        };
+        if verdict.reason {
+            policy::record_reason(store.conn(), profile, &action, reason)?;
+        }
+        if let Some(window) = &window {
+            maintenance::record_override(store.conn(), profile, window, action.as_str(), reason)?;
+        }
+    }
+    Ok(())
+}
+
+fn find_profile(store: &ProfileStore, profile_id: &str) -> Result<Profile> {
+    store
+        .get(profile_id)?
+        .ok_or_else(|| exit::not_found(format!("profile not found: {profile_id}")))
+}
+
+fn parse_body<T: for<'de> Deserialize<'de>>(request: &ApiRequest) -> Result<T> {
+    serde_json::from_slice(&request.body)
+        .map_err(|err| exit::invalid(format!("invalid request body: {err}")))
+}
+
+fn percent_decode(raw: &str) -> String {
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'+' => out.push(b' '),
+            b'%' => match bytes
+                .get(idx + 1..idx + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    out.push(byte);
+                    idx += 2;
+                }
+                None => out.push(b'%'),
+            },
+            byte => out.push(byte),
+        }
+        idx += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_request_line_headers_query_and_body() {
+        let raw = "POST /v1/oplog?op=run&profile_id=web%201 HTTP/1.1\r\n\
+                   Authorization: Bearer abc\r\n\
+                   Content-Length: 2\r\n\r\n{}";
+        let request = read_request(&mut raw.as_bytes()).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/v1/oplog");
+        assert_eq!(request.query["profile_id"], "web 1");
+        assert_eq!(request.headers["authorization"], "Bearer abc");
+        assert_eq!(request.body, b"{}");
+        assert_eq!(percent_decode("100%"), "100%");
+
+        let oversized = format!(
+            "GET / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_BYTES + 1
+        );
+        assert!(read_request(&mut oversized.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn rejects_header_lines_past_the_limit() {
+        let long_target = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_HEADER_BYTES));
+        let err = read_request(&mut long_target.as_bytes()).unwrap_err();
+        assert!(err.is::<HeaderTooLarge>());
+
+        let endless_header = format!("GET / HTTP/1.1\r\nX-Pad: {}", "a".repeat(1 << 20));
+        let err = read_request(&mut endless_header.as_bytes()).unwrap_err();
+        assert!(err.is::<HeaderTooLarge>());
+
+        let fits = format!(
+            "GET / HTTP/1.1\r\nX-Pad: {}\r\n\r\n",
+            "a".repeat(MAX_HEADER_BYTES - 16)
+        );
+        assert!(read_request(&mut fits.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn maps_error_categories_to_http_status() {
+        assert_eq!(status_for(&exit::not_found("x")), 404);
+        assert_eq!(status_for(&exit::policy_denied("x")), 403);
+        assert_eq!(status_for(&exit::remote_failure("x")), 502);
+        assert_eq!(status_for(&anyhow::anyhow!("x")), 500);
+    }
+
+    #[test]
+    fn a_stalled_connection_does_not_block_the_next_one() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener));
+
+        let _stalled = TcpStream::connect(addr).unwrap();
+        let mut client = TcpStream::connect(addr).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client.write_all(b"garbage\r\n\r\n").unwrap();
+        let mut status = String::new();
+        BufReader::new(client).read_line(&mut status).unwrap();
+        assert!(status.starts_with("HTTP/1.1 400 "), "{status}");
+    }
+}
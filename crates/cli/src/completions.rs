@@ -0,0 +1,207 @@
+//! Shell completions. `td completions <shell>` prints a registration script that calls
+//! back into `td` on every `Tab`, so subcommands and flags come from the clap definition
+//! and profile ids, CommandSet ids, and group names come from the database as they are
+//! typed. The database is opened read-only; when it cannot be read, only the static
+//! candidates are offered.
+
+use std::ffi::OsStr;
+use std::io::Write;
+
+use anyhow::Result;
+use clap::{Command, CommandFactory, ValueEnum};
+use clap_complete::engine::{
+    ArgValueCandidates, ArgValueCompleter, CompletionCandidate, ValueCompleter,
+};
+use clap_complete::env::{CompleteEnv, Shells};
+use tdcore::cmdset::CmdSetStore;
+use tdcore::db;
+use tdcore::profile::ProfileStore;
+
+use crate::Cli;
+
+/// Set by the registration scripts when they ask `td` for candidates.
+const COMPLETE_VAR: &str = "COMPLETE";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+impl CompletionShell {
+    fn name(self) -> &'static str {
+        match self {
+            CompletionShell::Bash => "bash",
+            CompletionShell::Zsh => "zsh",
+            CompletionShell::Fish => "fish",
+            CompletionShell::Powershell => "powershell",
+        }
+    }
+}
+
+/// Answers a completion request from a registered shell and exits; returns on a normal
+/// run. Must run before anything is printed.
+pub fn complete_if_requested() {
+    CompleteEnv::with_factory(command)
+        .var(COMPLETE_VAR)
+        .complete();
+}
+
+/// `td completions <shell>`: the script to source from the shell's startup file.
+pub fn write_registration(shell: CompletionShell, out: &mut dyn Write) -> Result<()> {
+    let shells = Shells::builtins();
+    let completer = shells.completer(shell.name()).expect("built-in shell");
+    completer.write_registration(COMPLETE_VAR, "td", "td", "td", out)?;
+    Ok(())
+}
+
+/// The CLI definition with database-backed candidates attached by argument id.
+fn command() -> Command {
+    with_candidates(Cli::command())
+}
+
+fn with_candidates(cmd: Command) -> Command {
+    let name = cmd.get_name().to_string();
+    cmd.mut_args(|arg| match arg.get_id().as_str() {
+        "profile_id" | "profile" | "profiles" | "src_profile_id" | "dst_profile_id" => {
+            arg.add(ArgValueCandidates::new(profile_candidates))
+        }
+        "cmdset_id" => arg.add(ArgValueCandidates::new(cmdset_candidates)),
+        "group" => arg.add(ArgValueCandidates::new(group_candidates)),
+        "targets" if name == "run" => arg.add(ArgValueCompleter::new(RunTargets)),
+        "targets" if name == "tail" => arg.add(ArgValueCompleter::new(TailTargets)),
+        _ => arg,
+    })
+    .mut_subcommands(with_candidates)
+}
+
+/// `td run <PROFILE_ID> <CMDSET_ID>`, where the first value is the CommandSet instead
+/// when targets come from `--profile`/`--group`/`--tag`.
+struct RunTargets;
+
+impl ValueCompleter for RunTargets {
+    fn complete(&self, current: &OsStr) -> Vec<CompletionCandidate> {
+        self.complete_at(0, current)
+    }
+
+    fn complete_at(&self, arg_index: usize, current: &OsStr) -> Vec<CompletionCandidate> {
+        let mut candidates = cmdset_candidates();
+        if arg_index == 0 {
+            candidates.extend(profile_candidates());
+        }
+        matching(candidates, current)
+    }
+}
+
+/// `td tail <PROFILE_ID> <PATH>`; the remote path is left to the user.
+struct TailTargets;
+
+impl ValueCompleter for TailTargets {
+    fn complete(&self, current: &OsStr) -> Vec<CompletionCandidate> {
+        self.complete_at(0, current)
+    }
+
+    fn complete_at(&self, arg_index: usize, current: &OsStr) -> Vec<CompletionCandidate> {
+        if arg_index == 0 {
+            matching(profile_candidates(), current)
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+fn matching(candidates: Vec<CompletionCandidate>, current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+    candidates
+        .into_iter()
+        .filter(|candidate| {
+            candidate
+                .get_value()
+                .to_string_lossy()
+                .starts_with(current.as_ref())
+        })
+        .collect()
+}
+
+fn profile_candidates() -> Vec<CompletionCandidate> {
+    let Ok(conn) = db::open_read_only() else {
+        return Vec::new();
+    };
+    ProfileStore::new(conn)
+        .list()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|profile| CompletionCandidate::new(profile.profile_id).help(Some(profile.name.into())))
+        .collect()
+}
+
+fn cmdset_candidates() -> Vec<CompletionCandidate> {
+    let Ok(conn) = db::open_read_only() else {
+        return Vec::new();
+    };
+    CmdSetStore::new(conn)
+        .list()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|cmdset| CompletionCandidate::new(cmdset.cmdset_id).help(Some(cmdset.name.into())))
+        .collect()
+}
+
+fn group_candidates() -> Vec<CompletionCandidate> {
+    let Ok(conn) = db::open_read_only() else {
+        return Vec::new();
+    };
+    let mut groups: Vec<String> = ProfileStore::new(conn)
+        .list()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|profile| profile.group)
+        .collect();
+    groups.sort();
+    groups.dedup();
+    groups.into_iter().map(CompletionCandidate::new).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'a>(cmd: &'a Command, path: &[&str], arg: &str) -> &'a clap::Arg {
+        let mut cmd = cmd;
+        for name in path {
+            cmd = cmd.find_subcommand(name).expect("subcommand");
+        }
+        cmd.get_arguments()
+            .find(|candidate| candidate.get_id() == arg)
+            .expect("argument")
+    }
+
+    #[test]
+    fn attaches_dynamic_candidates_and_writes_scripts() {
+        let cmd = command();
+        for (path, arg) in [
+            (&["connect"][..], "profile_id"),
+            (&["profile", "show"][..], "profile_id"),
+            (&["run"][..], "profiles"),
+            (&["run"][..], "group"),
+            (&["log", "list"][..], "profile"),
+        ] {
+            assert!(
+                find(&cmd, path, arg).get::<ArgValueCandidates>().is_some(),
+                "{path:?} {arg}"
+            );
+        }
+        assert!(find(&cmd, &["run"], "targets")
+            .get::<ArgValueCompleter>()
+            .is_some());
+
+        for shell in CompletionShell::value_variants() {
+            let mut script = Vec::new();
+            write_registration(*shell, &mut script).unwrap();
+            let script = String::from_utf8(script).unwrap();
+            assert!(script.contains(COMPLETE_VAR), "{shell:?}");
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Process exit codes shared by every subcommand so wrapper scripts can branch on them.
+//!
+//! Errors carry their category either as a [`CliError`] built with the helpers below or
+//! as a `tdcore` [`CoreError`]; anything else exits with the generic failure code.
+
+use std::fmt;
+use std::process::ExitCode;
+
+use tdcore::error::{CoreError, ProfileError, RunError, SecretError, TransferError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// 0: the command did what was asked.
+    Success,
+    /// 1: an unexpected or uncategorized error (I/O, database, spawn failures).
+    Failure,
+    /// 2: a referenced profile, CommandSet, session, favorite, or key does not exist.
+    NotFound,
+    /// 3: invalid arguments, values, or input documents (including CLI usage errors).
+    Invalid,
+    /// 4: the remote side or the external client (ssh, scp, telnet) failed.
+    RemoteFailure,
+    /// 5: refused by a safety policy (declined danger confirmation, missing `--yes`).
+    PolicyDenied,
+    /// 6: a bulk operation finished but some of its items failed.
+    PartialFailure,
+}
+
+impl ExitStatus {
+    pub fn code(self) -> u8 {
+        match self {
+            ExitStatus::Success => 0,
+            ExitStatus::Failure => 1,
+            ExitStatus::NotFound => 2,
+            ExitStatus::Invalid => 3,
+            ExitStatus::RemoteFailure => 4,
+            ExitStatus::PolicyDenied => 5,
+            ExitStatus::PartialFailure => 6,
+        }
+    }
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        ExitCode::from(status.code())
+    }
+}
+
+/// An error message tagged with the exit status it should produce.
+#[derive(Debug)]
+pub struct CliError {
+    pub status: ExitStatus,
+    message: String,
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+fn tagged(status: ExitStatus, message: impl Into<String>) -> anyhow::Error {
+    anyhow::Error::new(CliError {
+        status,
+        message: message.into(),
+    })
+}
+
+pub fn failure(message: impl Into<String>) -> anyhow::Error {
+    tagged(ExitStatus::Failure, message)
+}
+
+pub fn not_found(message: impl Into<String>) -> anyhow::Error {
+    tagged(ExitStatus::NotFound, message)
+}
+
+pub fn invalid(message: impl Into<String>) -> anyhow::Error {
+    tagged(ExitStatus::Invalid, message)
+}
+
+pub fn remote_failure(message: impl Into<String>) -> anyhow::Error {
+    tagged(ExitStatus::RemoteFailure, message)
+}
+
+pub fn policy_denied(message: impl Into<String>) -> anyhow::Error {
+    tagged(ExitStatus::PolicyDenied, message)
+}
+
+pub fn partial_failure(message: impl Into<String>) -> anyhow::Error {
+    tagged(ExitStatus::PartialFailure, message)
+}
+
+/// Picks the exit status for an error, using the outermost categorized cause.
+pub fn classify(err: &anyhow::Error) -> ExitStatus {
+    for cause in err.chain() {
+        if let Some(cli) = cause.downcast_ref::<CliError>() {
+            return cli.status;
+        }
+        if let Some(core) = cause.downcast_ref::<CoreError>() {
+            return classify_core(core);
+        }
+        if cause.downcast_ref::<serde_json::Error>().is_some() {
+            return ExitStatus::Invalid;
+        }
+    }
+    ExitStatus::Failure
+}
+
+fn classify_core(err: &CoreError) -> ExitStatus {
+    match err {
+        CoreError::NotFound(_) => ExitStatus::NotFound,
+        CoreError::Json(_)
+        | CoreError::InvalidId(_)
+        | CoreError::Conflict(_)
+        | CoreError::InvalidSetting(_)
+        | CoreError::Import(_) => ExitStatus::Invalid,
+        CoreError::Profile(err) => match err {
+            ProfileError::NotFound(_) => ExitStatus::NotFound,
+            _ => ExitStatus::Invalid,
+        },
+        CoreError::Secret(err) => match err {
+            SecretError::NotFound(_) => ExitStatus::NotFound,
+            SecretError::MasterAlreadySet => ExitStatus::Invalid,
+            _ => ExitStatus::Failure,
+        },
+        CoreError::Run(err) => match err {
+            RunError::CmdSetNotFound(_) | RunError::ParserNotFound(_) => ExitStatus::NotFound,
+            RunError::CommandExecution(_) => ExitStatus::RemoteFailure,
+            _ => ExitStatus::Invalid,
+        },
+        CoreError::PolicyDenied(_) => ExitStatus::PolicyDenied,
+        CoreError::Transfer(err) => match err {
+            TransferError::FavoriteNotFound(_) => ExitStatus::NotFound,
+            _ => ExitStatus::Invalid,
+        },
+        // I/O, database, and directory errors, plus variants added in later versions.
+        _ => ExitStatus::Failure,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn classifies_tagged_core_and_plain_errors() {
+        assert_eq!(
+            classify(&not_found("profile not found: p1")),
+            ExitStatus::NotFound
+        );
+        assert_eq!(
+            classify(&anyhow::Error::new(CoreError::InvalidSetting("x".into()))),
+            ExitStatus::Invalid
+        );
+        let wrapped = Err::<(), _>(remote_failure("ssh exited with code 255"))
+            .context("exec failed")
+            .unwrap_err();
+        assert_eq!(classify(&wrapped), ExitStatus::RemoteFailure);
+        assert_eq!(
+            classify(&anyhow::Error::new(CoreError::from(
+                ProfileError::NotFound("p1".into())
+            ))),
+            ExitStatus::NotFound
+        );
+        assert_eq!(
+            classify(&anyhow::Error::new(CoreError::from(
+                SecretError::MasterVerificationFailed
+            ))),
+            ExitStatus::Failure
+        );
+        assert_eq!(classify(&anyhow::anyhow!("boom")), ExitStatus::Failure);
+        assert_eq!(ExitStatus::PartialFailure.code(), 6);
+    }
+}
@@ -4,22 +4,29 @@ use clap::{ArgAction, Args, CommandFactory, Parser, Subcommand, ValueEnum};
 #[cfg(all(test, windows))]
 use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
-use rusqlite::Connection;
-use std::collections::BTreeMap;
-use std::fmt::Display;
+use regex::Regex;
+use rusqlite::{Connection, OpenFlags};
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
 use std::fs::OpenOptions;
-use std::io::{self, Read, Write};
+use std::io::{self, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Command, ExitCode, Stdio};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
-    Arc,
+    Arc, OnceLock,
 };
 use std::thread;
 use std::time::{Duration, Instant};
+use tdcore::active_session;
 use tdcore::agent;
-use tdcore::cmdset::{CmdSetStore, NewCmdSet, NewCmdStep, StepOnError};
-use tdcore::cmdset_runner::{run_cmdset_ssh, CmdSetRunRequest};
+use tdcore::audit::{self, AuditFormat};
+use tdcore::calendar;
+use tdcore::clipboard::{Clipboard, ClipboardExpiry};
+use tdcore::cmdset::{CmdSetStore, EnvValue, NewCmdSet, NewCmdStep, StepOnError};
+use tdcore::cmdset_runner::{
+    self, run_cmdset_ssh, CmdSetRunRequest, CmdSetRunResult, CmdStepRunResult, RunEnvVar,
+};
 use tdcore::configset::{ConfigFileWhen, ConfigSetStore, NewConfigFile, NewConfigSet};
 #[cfg(all(test, windows))]
 use tdcore::conpty::{
@@ -28,16 +35,33 @@ use tdcore::conpty::{
 };
 #[cfg(windows)]
 use tdcore::conpty::{run_conpty_ssh_child, ConptyLogSanitizer, ConptyRunOptions};
+use tdcore::daemon::{Daemon, DaemonClient, DaemonOptions};
 use tdcore::db;
+use tdcore::db_context;
 use tdcore::doctor::{self, ClientKind, ClientOverrides};
+use tdcore::forward_preset;
+use tdcore::guard;
+use tdcore::hooks::{self, HookContext, HookPoint, HookRun};
+use tdcore::host_import::{self, HostImportDefaults};
 use tdcore::import_export::{self, ConflictStrategy, ExportDocument, ImportReport};
+use tdcore::maintenance::{self, NewMaintenanceWindow, WindowMode};
+use tdcore::notify::{self, Notification};
 use tdcore::oplog;
 use tdcore::parser::parse_output;
 use tdcore::paths;
+use tdcore::policy::{self, PolicyAction};
 use tdcore::profile::{
-    DangerLevel, NewProfile, Profile, ProfileFilters, ProfileStore, ProfileType, UpdateProfile,
+    self, CloneProfile, DangerLevel, NewProfile, Profile, ProfileFilters, ProfileStore,
+    ProfileType, UpdateProfile,
 };
-use tdcore::secret::{NewSecret, SecretStore};
+use tdcore::remote_script::{self, ScriptRunRequest};
+use tdcore::retention;
+use tdcore::run_events::RunEventBus;
+use tdcore::run_output::{self, NewRunOutput};
+use tdcore::run_record;
+use tdcore::run_report::{ProfileRun, ReportFormat, RunReport};
+use tdcore::schedule::{self, NewSchedule, ScheduleTarget};
+use tdcore::secret::{NewSecret, SecretKind, SecretStore};
 use tdcore::session_log::{
     self, SessionLogFiles, SessionLogPlan, SessionLogReference,
     SESSION_LOG_REASON_METADATA_WRITE_FAILED, SESSION_LOG_REASON_POWERSHELL_LAUNCH_FAILED,
@@ -48,26 +72,82 @@ use tdcore::settings::SettingScope;
 use tdcore::settings_registry;
 use tdcore::ssh::{self, SshAuthContext, SshInvocation, SshInvocationMode, SshInvocationRequest};
 use tdcore::tester::{self, SshBatchCommand, TestOptions};
-use tdcore::transfer::{TransferDirection, TransferTempDir, TransferVia};
-use tdcore::tunnel::{ForwardKind, ForwardStore, NewSession, SessionKind, SessionStore};
-use tdcore::util::now_ms;
-use time::OffsetDateTime;
+use tdcore::transfer::{
+    NewTransferFavorite, TransferDirection, TransferFavorite, TransferFavoriteStore,
+    TransferTempDir, TransferVia,
+};
+use tdcore::tunnel::{
+    self, DestProbe, EnvShell, Forward, ForwardKind, ForwardStore, NewForward, NewSession,
+    SessionKind, SessionStore,
+};
+use tdcore::tunnel_manager::{TunnelManager, TunnelReport};
+use tdcore::util::{self, now_ms};
+use time::{OffsetDateTime, UtcOffset};
 use tracing::{info, warn};
 use tracing_subscriber::filter::EnvFilter;
 use tracing_subscriber::prelude::*;
+#[cfg(feature = "tui")]
 use tui as tdtui;
 use wait_timeout::ChildExt;
 use zeroize::Zeroizing;
 
+#[cfg(feature = "api")]
+mod api;
+mod completions;
+mod exit;
+mod output;
+mod picker;
+mod tail;
 mod transfer;
 
+use crate::output::Tone;
 use crate::transfer::{ensure_insecure_allowed, execute_transfer, run_transfer_with_log};
 
 const INITIAL_SEND_DELAY: Duration = Duration::from_millis(300);
+/// How long an `ssh -W` channel must stay open before a forward destination counts as reachable.
+const DEST_CHECK_WAIT: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Parser)]
 #[command(author, version, about = "TeraDock CLI", long_about = None)]
 struct Cli {
+    /// Disable colored output (also honours NO_COLOR)
+    #[arg(long, global = true, help_heading = "Global options")]
+    no_color: bool,
+    /// Print listings directly instead of through $PAGER
+    #[arg(long, global = true, help_heading = "Global options")]
+    no_pager: bool,
+    /// Reason recorded in the op log when a `policy.danger` rule asks for one
+    #[arg(
+        long,
+        global = true,
+        help_heading = "Global options",
+        value_name = "TEXT"
+    )]
+    reason: Option<String>,
+    /// Read-only observer mode: browse profiles, history, and doctor output, but refuse
+    /// runs, sessions, transfers, and edits (also set by `observer.enabled`)
+    #[arg(long, global = true, help_heading = "Global options")]
+    observer: bool,
+    /// Use this database file instead of the default (also set by TD_DB)
+    #[arg(
+        long,
+        global = true,
+        help_heading = "Global options",
+        value_name = "PATH",
+        conflicts_with = "context"
+    )]
+    db: Option<PathBuf>,
+    /// Use the database of this named context from contexts.json in the config dir
+    #[arg(
+        long,
+        global = true,
+        help_heading = "Global options",
+        value_name = "NAME"
+    )]
+    context: Option<String>,
+    /// Never open the profile picker; a missing profile id is an error
+    #[arg(long, global = true, help_heading = "Global options")]
+    no_interactive: bool,
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -102,16 +182,35 @@ enum Commands {
     },
     /// Check environment and required clients
     Doctor {
-        /// Output as JSON
-        #[arg(long)]
+        /// Output as JSON (one compact document per change with --watch)
+        #[arg(long, conflicts_with = "fix")]
         json: bool,
+        /// Apply safe remediations (~/.ssh permissions, default key, client overrides)
+        #[arg(long)]
+        fix: bool,
+        /// With --fix, apply fixes that normally ask for confirmation
+        #[arg(long, requires = "fix")]
+        yes: bool,
+        /// Keep re-checking until interrupted, printing only what changed
+        #[arg(long, conflicts_with = "fix")]
+        watch: bool,
+        /// Seconds between checks with --watch
+        #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+    /// Inspect the local TeraDock database
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
     },
     /// Initialize local TeraDock data and optionally install safe samples
     Init(InitArgs),
+    /// Suggest SSH profiles from ssh commands in shell history
+    Suggest(SuggestArgs),
     /// Execute a non-interactive command over SSH
     Exec {
-        /// Profile ID to use
-        profile_id: String,
+        /// Profile ID to use (picked interactively when left out on a terminal)
+        profile_id: Option<String>,
         /// Timeout in milliseconds
         #[arg(long)]
         timeout_ms: Option<u64>,
@@ -125,18 +224,26 @@ enum Commands {
         #[arg(last = true)]
         cmd: Vec<String>,
     },
+    /// Search stored td run/exec outputs across all profiles
+    SearchOutput(SearchOutputArgs),
+    /// Upload a local script, run it over SSH, and remove it afterwards
+    ExecScript(ExecScriptArgs),
     /// Execute a stored CommandSet over SSH
-    Run {
-        /// Profile ID to use
-        profile_id: String,
-        /// CommandSet ID to execute
-        cmdset_id: String,
-        /// Output as JSON
-        #[arg(long)]
-        json: bool,
+    Run(RunArgs),
+    /// Run CommandSets on a cron schedule (fired by `td daemon`)
+    Schedule {
+        #[command(subcommand)]
+        command: ScheduleCommands,
+    },
+    /// Block or gate connections to critical profiles during maintenance windows
+    Maintenance {
+        #[command(subcommand)]
+        command: MaintenanceCommands,
     },
     /// Connect to a profile (SSH/Telnet/Serial)
     Connect(ConnectArgs),
+    /// Tail a remote file over SSH (tail -F with reconnect, grep, and highlighting)
+    Tail(TailArgs),
     /// Show recently used interactive SSH session profiles
     Recent {
         /// Maximum number of profiles to show
@@ -151,21 +258,43 @@ enum Commands {
         #[command(subcommand)]
         command: SessionCommands,
     },
+    /// Maintain operation logs and stored run outputs
+    Log {
+        #[command(subcommand)]
+        command: LogCommands,
+    },
     /// Manage SSH tunnels
     Tunnel {
         #[command(subcommand)]
         command: TunnelCommands,
     },
+    /// Run or talk to the local daemon that shares tunnel state and the unlocked vault
+    Daemon {
+        #[command(subcommand)]
+        command: DaemonCommands,
+    },
+    /// Serve the opt-in localhost HTTP API for automation
+    Api {
+        #[command(subcommand)]
+        command: ApiCommands,
+    },
     /// Test connectivity to a profile
     Test {
         /// Profile ID to test
-        profile_id: String,
-        /// Output as JSON
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        profile_id: Option<String>,
+        /// Test every profile (archived profiles are skipped)
+        #[arg(long)]
+        all: bool,
+        /// Output as JSON (an array of reports with --all)
         #[arg(long)]
         json: bool,
         /// Include SSH BatchMode auth probe (SSH profiles only)
         #[arg(long)]
         ssh: bool,
+        /// Measure RTT and loss with this many extra TCP connects
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=100))]
+        samples: Option<u32>,
     },
     /// Upload a local file to a profile over SCP/SFTP
     Push(TransferArgs),
@@ -173,6 +302,11 @@ enum Commands {
     Pull(TransferArgs),
     /// Transfer a file between two profiles (pull -> local temp -> push)
     Xfer(XferArgs),
+    /// Manage saved transfer favorites (named local/remote path pairs)
+    Fav {
+        #[command(subcommand)]
+        command: FavCommands,
+    },
     /// Manage secrets (master password required for reveal)
     Secret {
         #[command(subcommand)]
@@ -183,7 +317,15 @@ enum Commands {
     /// Import profiles, command sets, configs, and secrets metadata from JSON
     Import(ImportArgs),
     /// Launch the terminal UI
-    Ui,
+    Ui(UiArgs),
+    /// Browse an export JSON read-only in the terminal UI (in-memory, no DB writes)
+    View(ViewArgs),
+    /// Print a shell completion script that completes profile ids, CommandSet ids, and
+    /// groups from the database
+    Completions {
+        #[arg(value_enum)]
+        shell: completions::CompletionShell,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -192,24 +334,118 @@ enum ProfileCommands {
     Add(ProfileAddArgs),
     /// Edit an existing profile
     Edit(ProfileEditArgs),
+    /// Partially update a profile (tag +/- edits, client override patches)
+    Set(ProfileSetArgs),
+    /// Duplicate a profile (fields, tags, forwards, jump host) under a new id
+    Clone(ProfileCloneArgs),
+    /// Create profiles from a host,user,port,group,tags CSV or a plain host list
+    ImportHosts(ProfileImportHostsArgs),
     /// List profiles
     List(ProfileListArgs),
+    /// Ranked full-text search over name, host, tags, group, and note
+    Search(ProfileSearchArgs),
     /// Show a profile in JSON
-    Show { profile_id: String },
-    /// Remove a profile
-    Rm { profile_id: String },
+    Show {
+        /// Picked interactively when left out on a terminal
+        profile_id: Option<String>,
+        /// Accepted for scripts; the profile is always printed as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Remove a profile (td profile restore brings it back unless --permanent)
+    Rm {
+        profile_id: String,
+        /// Delete without keeping a restorable copy
+        #[arg(long)]
+        permanent: bool,
+    },
+    /// Restore the most recently removed profile with this id
+    Restore { profile_id: String },
+    /// List removed profiles that can be restored
+    Deleted {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Permanently drop restorable copies of removed profiles
+    Purge(ProfilePurgeArgs),
+    /// Hide a profile from default lists while keeping its history
+    Archive { profile_id: String },
+    /// Restore an archived profile
+    Unarchive { profile_id: String },
+}
+
+#[derive(Debug, Args)]
+struct RunArgs {
+    /// Profile ID to use, then the CommandSet ID to execute; with --profile, --tag, or
+    /// --group only the CommandSet ID is given
+    #[arg(required = true, num_args = 1..=2, value_names = ["PROFILE_ID", "CMDSET_ID"])]
+    targets: Vec<String>,
+    /// Run on this profile (repeatable or comma-delimited)
+    #[arg(long = "profile", action = ArgAction::Append, value_delimiter = ',')]
+    profiles: Vec<String>,
+    /// Run on every SSH profile with this tag (comma-delimited, AND match)
+    #[arg(long, action = ArgAction::Append, value_delimiter = ',')]
+    tag: Vec<String>,
+    /// Run on every SSH profile in this group
+    #[arg(long)]
+    group: Option<String>,
+    /// Output as JSON
+    #[arg(long)]
+    json: bool,
+    /// Also write a timestamped report file under reports.dir
+    #[arg(long, value_enum)]
+    report: Option<ReportFormatArg>,
+    /// Run steps guard.commands blocks, after typing a confirmation code (logged)
+    #[arg(long)]
+    force: bool,
+    /// Export an api_token secret to every step as NAME (repeatable)
+    #[arg(long = "secret-env", value_name = "NAME=SECRET_ID")]
+    secret_env: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+struct TailArgs {
+    /// Profile ID to tail on, then the remote file path (e.g. /var/log/syslog); with
+    /// --tag/--group only the path is given
+    #[arg(required = true, num_args = 1..=2, value_names = ["PROFILE_ID", "PATH"])]
+    targets: Vec<String>,
+    /// Tail every SSH profile with this tag (comma-delimited, AND match)
+    #[arg(long, action = ArgAction::Append, value_delimiter = ',')]
+    tag: Vec<String>,
+    /// Tail every SSH profile in this group
+    #[arg(long)]
+    group: Option<String>,
+    /// Keep following (tail -F) and reconnect if the connection drops
+    #[arg(short, long)]
+    follow: bool,
+    /// Number of existing lines to show first
+    #[arg(short = 'n', long, default_value_t = 10)]
+    lines: u32,
+    /// Only show lines matching this regex (matches are highlighted)
+    #[arg(long)]
+    grep: Option<String>,
+    /// Highlight matches of this regex without filtering (repeatable)
+    #[arg(long, action = ArgAction::Append)]
+    highlight: Vec<String>,
+    /// Also append shown lines to a local file
+    #[arg(short, long)]
+    output: Option<PathBuf>,
 }
 
 #[derive(Debug, Args)]
 struct ConnectArgs {
-    /// Profile ID to connect to
-    profile_id: String,
+    /// Profile ID to connect to (picked interactively when left out on a terminal)
+    profile_id: Option<String>,
     /// One-time string to send right after connect (overrides profile)
     #[arg(long)]
     initial_send: Option<String>,
     /// Explicit session logging backend for this SSH connect (currently conpty)
     #[arg(long)]
     log_backend: Option<String>,
+    /// Print the client and command line that would be launched, without connecting
+    #[arg(long)]
+    dry_run: bool,
 }
 
 #[derive(Debug, Args)]
@@ -277,6 +513,81 @@ struct ProfileEditArgs {
     clear_client_overrides: bool,
 }
 
+#[derive(Debug, Args)]
+struct ProfileSetArgs {
+    /// Profile ID to update
+    profile_id: String,
+    #[arg(long)]
+    name: Option<String>,
+    #[arg(long)]
+    host: Option<String>,
+    #[arg(long)]
+    user: Option<String>,
+    #[arg(long)]
+    port: Option<u16>,
+    #[arg(long)]
+    r#type: Option<String>,
+    #[arg(long)]
+    danger: Option<String>,
+    #[arg(long)]
+    group: Option<String>,
+    #[arg(long)]
+    clear_group: bool,
+    /// Tag edit: `+tag` (or `tag`) adds, `-tag` removes; repeatable
+    #[arg(long, action = ArgAction::Append, allow_hyphen_values = true)]
+    tag: Vec<String>,
+    #[arg(long)]
+    note: Option<String>,
+    #[arg(long)]
+    clear_note: bool,
+    #[arg(long)]
+    initial_send: Option<String>,
+    #[arg(long)]
+    clear_initial_send: bool,
+    /// JSON merge patch for client overrides (`null` removes a key)
+    #[arg(long)]
+    client_overrides_patch: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct ProfileCloneArgs {
+    /// Profile ID to copy
+    src_id: String,
+    /// Explicit ID for the copy (auto-generated if omitted)
+    #[arg(long = "id")]
+    new_id: Option<String>,
+    /// Name for the copy (defaults to "<name> (copy)")
+    #[arg(long)]
+    name: Option<String>,
+    #[arg(long)]
+    host: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct ProfileImportHostsArgs {
+    /// CSV file (host,user,port,group,tags; header optional) or newline host list
+    path: PathBuf,
+    /// User for rows without one
+    #[arg(long)]
+    user: Option<String>,
+    /// Port for rows without one
+    #[arg(long, default_value_t = 22)]
+    port: u16,
+    #[arg(long, default_value = "ssh")]
+    r#type: String,
+    #[arg(long, default_value = "normal")]
+    danger: String,
+    /// Group for rows without one
+    #[arg(long)]
+    group: Option<String>,
+    /// Tags added to every created profile
+    #[arg(long, action = ArgAction::Append, value_delimiter = ',')]
+    tag: Vec<String>,
+    /// Print the summary as JSON
+    #[arg(long)]
+    json: bool,
+}
+
 #[derive(Debug, Args)]
 struct ProfileListArgs {
     /// Filter by group
@@ -294,6 +605,62 @@ struct ProfileListArgs {
     /// Free-text query over id/name/host/user
     #[arg(long)]
     query: Option<String>,
+    /// Include archived profiles
+    #[arg(long)]
+    archived: bool,
+    /// Output as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct ProfileSearchArgs {
+    /// Words to search for; each word matches as a prefix
+    #[arg(required = true, num_args = 1..)]
+    query: Vec<String>,
+    /// Include archived profiles
+    #[arg(long)]
+    archived: bool,
+    /// Show at most N results
+    #[arg(long)]
+    limit: Option<usize>,
+    /// Output as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum DbCommands {
+    /// Show database path and pragma state (journal mode, busy timeout, schema version)
+    Status {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// List pre-migration snapshots (<db>.v{N}.bak)
+    Snapshots {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Restore the snapshot taken before the most recent migration over the database
+    Rollback(DbRollbackArgs),
+    /// List the named database contexts in contexts.json (see --context)
+    Contexts {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+struct DbRollbackArgs {
+    /// Restore the snapshot of this schema version instead
+    #[arg(long)]
+    to: Option<u32>,
+    /// Confirm the restore without an interactive prompt
+    #[arg(long)]
+    yes: bool,
 }
 
 #[derive(Debug, Args)]
@@ -303,6 +670,63 @@ struct InitArgs {
     with_samples: bool,
 }
 
+#[derive(Debug, Args)]
+struct SearchOutputArgs {
+    /// Text to find (matched as a phrase)
+    query: String,
+    /// Only outputs newer than this age (e.g. 30d, 12h)
+    #[arg(long)]
+    since: Option<String>,
+    /// Only outputs from this profile
+    #[arg(long)]
+    profile: Option<String>,
+    /// Maximum number of matches
+    #[arg(long, default_value_t = 50)]
+    limit: usize,
+    /// Output as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct ExecScriptArgs {
+    /// Profile ID to use
+    profile_id: String,
+    /// Local script to upload and run
+    script: PathBuf,
+    /// Arguments passed to the script (interpreted by the remote shell)
+    #[arg(long, allow_hyphen_values = true)]
+    args: Option<String>,
+    /// Always allocate a remote pty (default: only when attached to a terminal)
+    #[arg(long, conflicts_with = "no_tty")]
+    tty: bool,
+    /// Never allocate a remote pty
+    #[arg(long)]
+    no_tty: bool,
+    /// Print the result summary as JSON after the script output
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct SuggestArgs {
+    /// History file to scan (repeatable; defaults to bash, zsh, and PowerShell history)
+    #[arg(long = "from-history", value_name = "PATH", action = ArgAction::Append)]
+    from_history: Vec<PathBuf>,
+    /// User for commands without one (defaults to $USER / %USERNAME%)
+    #[arg(long)]
+    user: Option<String>,
+    /// Tags added to every created profile
+    #[arg(long, action = ArgAction::Append, value_delimiter = ',')]
+    tag: Vec<String>,
+    /// Create every suggestion without asking
+    #[arg(long, conflicts_with = "json")]
+    yes: bool,
+    /// List suggestions as JSON without creating profiles
+    #[arg(long)]
+    json: bool,
+}
+
 #[derive(Debug, Subcommand)]
 enum SessionCommands {
     /// Diagnose interactive SSH session logging
@@ -319,6 +743,14 @@ enum SessionCommands {
     Path { session_id: String },
     /// Prune saved interactive SSH session logs
     Prune(SessionPruneArgs),
+    /// List interactive SSH sessions running now (from td connect or the TUI)
+    Active {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// End a running interactive SSH session by its id from td session active
+    Terminate { id: i64 },
 }
 
 #[derive(Debug, Args)]
@@ -368,44 +800,293 @@ struct SessionShowArgs {
     tail: Option<usize>,
 }
 
+#[derive(Debug, Subcommand)]
+enum LogCommands {
+    /// Delete op logs and stored outputs past their profile's retention.<level>_days
+    Prune(LogPruneArgs),
+    /// Attach a handoff note to an op log entry (the newest one unless --id is given)
+    Note(LogNoteArgs),
+    /// Summarize one day's operations and notes for a shift handoff
+    Summary(LogSummaryArgs),
+    /// List op log entries, newest first
+    List(LogListArgs),
+    /// Print op log entries as audit events (JSON lines or CEF), oldest first
+    Export(LogExportArgs),
+    /// Show one op log entry with its stored step outputs
+    Show {
+        /// Op log entry id (see td log list)
+        id: i64,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print only the path of the run's output recording (see run.record)
+        #[arg(long, conflicts_with = "json")]
+        output: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ScheduleCommands {
+    /// Schedule a CommandSet to run on matching profiles
+    Add(ScheduleAddArgs),
+    /// List schedules with their next run
+    List {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Delete a schedule
+    Rm {
+        /// Schedule ID
+        id: i64,
+    },
+    /// Resume a paused schedule from its next firing
+    Enable {
+        /// Schedule ID
+        id: i64,
+    },
+    /// Pause a schedule
+    Disable {
+        /// Schedule ID
+        id: i64,
+    },
+    /// Run every due schedule now, as `td daemon` does
+    RunDue {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Export upcoming scheduled runs as a calendar
+    Export {
+        /// Write iCalendar (.ics); the only format so far
+        #[arg(long, required = true)]
+        ics: bool,
+        /// How many days ahead to list firings
+        #[arg(long, default_value_t = 14)]
+        days: u32,
+        /// Write output to a file instead of stdout
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+    },
+}
+
 #[derive(Debug, Args)]
-struct SessionPruneArgs {
-    /// Delete sessions older than an age such as 30d, 12h, 60m, or 3600s
+struct ScheduleAddArgs {
+    /// CommandSet ID to run
+    cmdset_id: String,
+    /// Profiles to run on: <profile_id>, group:<name>, or tag:<a>,<b>
     #[arg(long)]
-    older_than: Option<String>,
-    /// Keep the newest N sessions and select older entries for pruning
+    target: String,
+    /// Cron expression (minute hour day month weekday) or @hourly/@daily/@weekly/@monthly
     #[arg(long)]
-    keep_last: Option<usize>,
-    /// Show matching sessions without deleting files
+    cron: String,
+    /// Create the schedule paused
     #[arg(long)]
-    dry_run: bool,
-    /// Confirm deletion without an interactive prompt
+    disabled: bool,
+    /// Also run on critical profiles, which scheduled runs skip otherwise
     #[arg(long)]
-    yes: bool,
-    /// Output as JSON
+    allow_critical: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum MaintenanceCommands {
+    /// Add a maintenance window for critical profiles
+    Add(MaintenanceAddArgs),
+    /// List maintenance windows
+    List {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Delete a maintenance window
+    Rm {
+        /// Window ID
+        id: i64,
+    },
+    /// Export maintenance windows as a calendar
+    Export {
+        /// Write iCalendar (.ics); the only format so far
+        #[arg(long, required = true)]
+        ics: bool,
+        /// Write output to a file instead of stdout
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Args)]
+struct MaintenanceAddArgs {
+    /// Profiles covered: <profile_id>, group:<name>, or tag:<a>,<b>
     #[arg(long)]
-    json: bool,
+    target: String,
+    /// Weekly span (e.g. "sat 22:00..sun 06:00") or dates (YYYY-MM-DD..YYYY-MM-DD)
+    #[arg(long)]
+    window: String,
+    /// block refuses connections; reason lets them through once a reason is given
+    #[arg(long, value_enum, default_value = "block")]
+    mode: WindowModeArg,
+    /// Shown when the window stops a connection
+    #[arg(long)]
+    note: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum WindowModeArg {
+    Block,
+    Reason,
+}
+
+impl From<WindowModeArg> for WindowMode {
+    fn from(value: WindowModeArg) -> Self {
+        match value {
+            WindowModeArg::Block => WindowMode::Block,
+            WindowModeArg::Reason => WindowMode::Reason,
+        }
+    }
 }
 
 #[derive(Debug, Args)]
-struct TransferArgs {
-    /// Profile ID to transfer to/from
-    profile_id: String,
-    /// Local path (source for push, destination for pull)
-    local_path: PathBuf,
-    /// Remote path (destination for push, source for pull)
-    remote_path: String,
-    /// Transfer client (scp, sftp, or ftp)
-    #[arg(long, default_value = "scp")]
-    via: String,
-    /// Acknowledge FTP is insecure when using --via ftp
+struct LogExportArgs {
+    /// Only entries newer than an age (e.g. 1d) or since a day (YYYY-MM-DD, today)
     #[arg(long)]
-    i_know_its_insecure: bool,
+    since: Option<String>,
+    /// Only entries older than an age or before a day, in the same forms as --since
+    #[arg(long)]
+    until: Option<String>,
+    /// Event format
+    #[arg(long, value_enum, default_value = "jsonl")]
+    format: AuditFormatArg,
 }
 
 #[derive(Debug, Args)]
-struct XferArgs {
-    /// Source profile ID
+struct LogListArgs {
+    /// Only entries for this profile
+    #[arg(long)]
+    profile: Option<String>,
+    /// Only this operation (e.g. run, exec, connect)
+    #[arg(long)]
+    op: Option<String>,
+    /// Only entries by this operator name or OS user
+    #[arg(long)]
+    operator: Option<String>,
+    /// Only successful entries
+    #[arg(long, conflicts_with = "failed")]
+    ok: bool,
+    /// Only failed entries
+    #[arg(long)]
+    failed: bool,
+    /// Only entries newer than an age (e.g. 7d, 12h) or since a day (YYYY-MM-DD, today)
+    #[arg(long)]
+    since: Option<String>,
+    /// Only entries older than an age or before a day, in the same forms as --since
+    #[arg(long)]
+    until: Option<String>,
+    /// Maximum number of entries (0 lists all)
+    #[arg(long, default_value_t = 50)]
+    limit: usize,
+    /// Skip this many matching entries first (next page: --offset <limit>)
+    #[arg(long, default_value_t = 0)]
+    offset: usize,
+    /// Output as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct LogNoteArgs {
+    /// Note text; an empty string removes the entry's note
+    note: String,
+    /// Op log entry id (see td api or the summary's notes)
+    #[arg(long)]
+    id: Option<i64>,
+    /// Without --id, note the newest entry for this profile
+    #[arg(long, conflicts_with = "id")]
+    profile: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct LogSummaryArgs {
+    /// Day to summarize: today, yesterday, or YYYY-MM-DD (in ui.timezone)
+    #[arg(long, default_value = "today")]
+    day: String,
+    /// Output as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct UiArgs {
+    /// Print how long each startup step took after the UI exits
+    #[arg(long)]
+    profile_startup: bool,
+}
+
+#[derive(Debug, Args)]
+struct ProfilePurgeArgs {
+    /// Only copies of this profile id (default: every removed profile)
+    profile_id: Option<String>,
+    /// Only copies removed longer ago than an age such as 30d, 12h, 60m, or 3600s
+    #[arg(long)]
+    older_than: Option<String>,
+    /// List matching copies without dropping them
+    #[arg(long)]
+    dry_run: bool,
+    /// Confirm dropping without an interactive prompt
+    #[arg(long)]
+    yes: bool,
+}
+
+#[derive(Debug, Args)]
+struct LogPruneArgs {
+    /// Count matching rows without deleting them
+    #[arg(long)]
+    dry_run: bool,
+    /// Confirm deletion without an interactive prompt
+    #[arg(long)]
+    yes: bool,
+    /// Output as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct SessionPruneArgs {
+    /// Delete sessions older than an age such as 30d, 12h, 60m, or 3600s
+    #[arg(long)]
+    older_than: Option<String>,
+    /// Keep the newest N sessions and select older entries for pruning
+    #[arg(long)]
+    keep_last: Option<usize>,
+    /// Show matching sessions without deleting files
+    #[arg(long)]
+    dry_run: bool,
+    /// Confirm deletion without an interactive prompt
+    #[arg(long)]
+    yes: bool,
+    /// Output as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct TransferArgs {
+    /// Profile ID to transfer to/from
+    profile_id: String,
+    /// Local path (source for push, destination for pull)
+    local_path: PathBuf,
+    /// Remote path (destination for push, source for pull)
+    remote_path: String,
+    /// Transfer client (scp, sftp, or ftp)
+    #[arg(long, default_value = "scp")]
+    via: String,
+    /// Acknowledge FTP is insecure when using --via ftp
+    #[arg(long)]
+    i_know_its_insecure: bool,
+}
+
+#[derive(Debug, Args)]
+struct XferArgs {
+    /// Source profile ID
     src_profile_id: String,
     /// Source remote path
     src_path: String,
@@ -421,6 +1102,58 @@ struct XferArgs {
     i_know_its_insecure: bool,
 }
 
+#[derive(Debug, Subcommand)]
+enum FavCommands {
+    /// Save a named local/remote path pair for a profile
+    Add(FavAddArgs),
+    /// List saved transfer favorites
+    List {
+        /// Only show favorites for this profile
+        profile_id: Option<String>,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Remove a saved transfer favorite
+    Rm {
+        /// Profile ID the favorite belongs to
+        profile_id: String,
+        /// Favorite name
+        name: String,
+    },
+    /// Re-run a saved transfer favorite (prompts for a choice when name is omitted)
+    Run(FavRunArgs),
+}
+
+#[derive(Debug, Args)]
+struct FavAddArgs {
+    /// Profile ID the favorite belongs to
+    profile_id: String,
+    /// Favorite name (unique per profile)
+    name: String,
+    /// Local path (source for push, destination for pull)
+    local_path: String,
+    /// Remote path (destination for push, source for pull)
+    remote_path: String,
+    /// Transfer direction (push or pull)
+    #[arg(long, default_value = "push")]
+    direction: String,
+    /// Transfer client (scp, sftp, or ftp)
+    #[arg(long, default_value = "scp")]
+    via: String,
+}
+
+#[derive(Debug, Args)]
+struct FavRunArgs {
+    /// Profile ID the favorite belongs to
+    profile_id: String,
+    /// Favorite name (omit to pick interactively)
+    name: Option<String>,
+    /// Acknowledge FTP is insecure when the favorite uses ftp
+    #[arg(long)]
+    i_know_its_insecure: bool,
+}
+
 #[derive(Debug, Subcommand)]
 enum TunnelCommands {
     /// Start a tunnel for a profile
@@ -432,6 +1165,192 @@ enum TunnelCommands {
     },
     /// Show tunnel session status
     Status(TunnelStatusArgs),
+    /// Print shell exports (host/port) for a profile's running forwards
+    Env(TunnelEnvArgs),
+    /// Manage a profile's saved forwards
+    Forward {
+        #[command(subcommand)]
+        command: ForwardCommands,
+    },
+    /// Save a profile's forwards as a named preset and add them to other profiles
+    Preset {
+        #[command(subcommand)]
+        command: PresetCommands,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum PresetCommands {
+    /// Save a profile's forwards as a preset
+    Save {
+        /// Preset name
+        name: String,
+        /// Profile whose forwards to copy
+        #[arg(long)]
+        from: String,
+    },
+    /// List presets and their forwards
+    List {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Add a preset's forwards to one or more profiles
+    Apply {
+        /// Preset name
+        name: String,
+        /// Profiles to add the forwards to
+        #[arg(required = true)]
+        profile_ids: Vec<String>,
+    },
+    /// Remove a preset (profiles keep the forwards already added)
+    Rm {
+        /// Preset name
+        name: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ForwardCommands {
+    /// Save a named forward for a profile
+    Add(ForwardAddArgs),
+    /// List a profile's forwards
+    List {
+        /// Profile ID whose forwards to list
+        profile_id: String,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Remove a saved forward
+    Rm {
+        /// Profile ID the forward belongs to
+        profile_id: String,
+        /// Forward name
+        name: String,
+    },
+    /// Ask the profile's server whether a local forward's destination is reachable
+    Check {
+        /// Profile ID the forward belongs to
+        profile_id: String,
+        /// Forward name
+        name: String,
+    },
+}
+
+#[derive(Debug, Args)]
+struct ForwardAddArgs {
+    /// Profile ID the forward belongs to
+    profile_id: String,
+    /// Forward name (unique per profile)
+    name: String,
+    /// Forward type
+    #[arg(long, value_enum, default_value = "local")]
+    kind: ForwardKindArg,
+    /// Listen address ([host:]port); a bare port binds to 127.0.0.1
+    #[arg(long)]
+    listen: String,
+    /// Destination host:port (required for local and remote forwards)
+    #[arg(long)]
+    dest: Option<String>,
+    /// For local forwards, check over ssh that the server can reach --dest and warn if not
+    #[arg(long)]
+    check_dest: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ForwardKindArg {
+    Local,
+    Remote,
+    Dynamic,
+}
+
+#[derive(Debug, Subcommand)]
+enum DaemonCommands {
+    /// Run the daemon in the foreground until `td daemon stop`
+    Start(DaemonStartArgs),
+    /// Show whether a daemon is running
+    Status {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Ask the running daemon to exit
+    Stop,
+    /// Forget the master key held by the daemon
+    Lock,
+}
+
+#[derive(Debug, Subcommand)]
+enum ApiCommands {
+    /// Serve the API on 127.0.0.1 until interrupted (requires `td api token` first)
+    Serve {
+        /// Port to listen on
+        #[arg(long, default_value_t = 7878)]
+        port: u16,
+    },
+    /// Generate a new bearer token, store its hash, and print it once
+    Token,
+}
+
+#[derive(Debug, Args)]
+struct DaemonStartArgs {
+    /// Seconds between tunnel health checks
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    interval: u64,
+    /// Restart dead tunnels within their tunnel.reconnect policy
+    #[arg(long)]
+    restart_tunnels: bool,
+    /// Do not fire due `td schedule` entries
+    #[arg(long)]
+    no_schedules: bool,
+}
+
+#[derive(Debug, Args)]
+struct TunnelEnvArgs {
+    /// Profile ID whose active tunnels to describe
+    profile_id: String,
+    /// Shell syntax for the exports
+    #[arg(long, value_enum, default_value = "bash")]
+    format: EnvFormatArg,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ReportFormatArg {
+    Json,
+    Text,
+    Html,
+}
+
+impl From<ReportFormatArg> for ReportFormat {
+    fn from(value: ReportFormatArg) -> Self {
+        match value {
+            ReportFormatArg::Json => ReportFormat::Json,
+            ReportFormatArg::Text => ReportFormat::Text,
+            ReportFormatArg::Html => ReportFormat::Html,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum AuditFormatArg {
+    Jsonl,
+    Cef,
+}
+
+impl From<AuditFormatArg> for AuditFormat {
+    fn from(value: AuditFormatArg) -> Self {
+        match value {
+            AuditFormatArg::Jsonl => AuditFormat::Jsonl,
+            AuditFormatArg::Cef => AuditFormat::Cef,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum EnvFormatArg {
+    Bash,
+    Powershell,
 }
 
 #[derive(Debug, Args)]
@@ -445,9 +1364,19 @@ struct TunnelStartArgs {
 
 #[derive(Debug, Args)]
 struct TunnelStatusArgs {
-    /// Output as JSON
+    /// Output as JSON (one compact document per check with --watch)
     #[arg(long)]
     json: bool,
+    /// Keep re-checking sessions until interrupted
+    #[arg(long)]
+    watch: bool,
+    /// Seconds between checks with --watch
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    interval: u64,
+    /// Restart sessions whose ssh process or forwards are dead, following the
+    /// tunnel.reconnect policy, instead of dropping sessions whose process exited
+    #[arg(long)]
+    restart: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -457,11 +1386,55 @@ enum SecretCommands {
     /// Add a secret (requires master password)
     Add(SecretAddArgs),
     /// List secrets (metadata only)
-    List,
+    #[command(alias = "ls")]
+    List {
+        /// Only secrets linked to this profile, marking the one `td connect` uses
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Link a secret to a profile so `td connect` can fetch it for that host
+    Link {
+        secret_id: String,
+        #[arg(long)]
+        profile: String,
+    },
+    /// Remove a secret's link to a profile
+    Unlink {
+        secret_id: String,
+        #[arg(long)]
+        profile: String,
+    },
     /// Reveal a secret value (requires master password)
     Reveal { secret_id: String },
+    /// Copy a secret value to the clipboard without printing it; cleared after
+    /// clipboard.clear_after_secs
+    Copy { secret_id: String },
     /// Remove a secret
     Rm { secret_id: String },
+    /// Set a PIN that can resume the TUI idle lock instead of the master password
+    SetLockPin,
+    /// Remove the idle lock PIN
+    ClearLockPin,
+    /// Change the master password, re-encrypting every secret in one transaction
+    RotateMaster,
+    /// Re-encrypt every secret under a new key derived from the same master password
+    Rekey,
+    /// Hand the master password to td daemon so vault commands stop prompting
+    Unlock {
+        /// Lock again after this many minutes (0 never; default vault.auto_lock_minutes, 15)
+        #[arg(long, value_parser = clap::value_parser!(u64).range(0..=1440))]
+        minutes: Option<u64>,
+    },
+    /// Forget the master key held by td daemon
+    Lock,
+    /// Print the current TOTP code of an otp_seed secret
+    Totp { secret_id: String },
+    /// Show whether the vault is unlocked and when it locks again
+    Status {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 #[derive(Debug, Args)]
@@ -469,6 +1442,7 @@ struct SecretAddArgs {
     /// Explicit secret ID (auto-generated if omitted)
     #[arg(long)]
     secret_id: Option<String>,
+    /// password, ssh_key_passphrase, api_token, or otp_seed (other names get no typed behavior)
     #[arg(long)]
     kind: String,
     #[arg(long)]
@@ -501,18 +1475,40 @@ enum ConfigCommands {
     ClearAuthOrder,
     /// Apply a config set to a profile
     Apply(ConfigApplyArgs),
+    /// Turn off observer mode set by `observer.enabled`, after a typed confirmation on a
+    /// terminal (refused with --observer)
+    LeaveObserver,
 }
 
 #[derive(Debug, Subcommand)]
 enum EnvCommands {
     /// List available env presets
     List,
+    /// Print the current env preset, or nothing when none is in use (for shell prompts)
+    Current,
+    /// Create an empty env preset
+    Create { name: String },
     /// Set the current env preset
-    Use { name: String },
+    Use {
+        #[arg(required_unless_present = "clear")]
+        name: Option<String>,
+        /// Stop using an env preset; global settings apply again
+        #[arg(long, conflicts_with = "name")]
+        clear: bool,
+    },
     /// Show settings for an env preset
     Show { name: String },
     /// Set a configuration value in an env preset (NAME.KEY VALUE)
     Set(EnvSetArgs),
+    /// Remove a configuration value from an env preset (NAME.KEY)
+    Unset { name_key: String },
+    /// Delete an env preset and all of its settings
+    Rm {
+        name: String,
+        /// Skip the confirmation prompt
+        #[arg(long)]
+        yes: bool,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -529,10 +1525,35 @@ enum AgentCommands {
         #[arg(long)]
         json: bool,
     },
-    /// Add a key to ssh-agent
-    Add { key_path: PathBuf },
+    /// Add a key to ssh-agent (ssh-add prompts for the passphrase)
+    Add {
+        key_path: PathBuf,
+        /// Skip the confirmation prompt
+        #[arg(long)]
+        yes: bool,
+        /// Output the result as JSON (requires --yes)
+        #[arg(long, requires = "yes")]
+        json: bool,
+    },
+    /// Remove one key from ssh-agent
+    Remove {
+        key_path: PathBuf,
+        /// Skip the confirmation prompt
+        #[arg(long)]
+        yes: bool,
+        /// Output the result as JSON (requires --yes)
+        #[arg(long, requires = "yes")]
+        json: bool,
+    },
     /// Remove all keys from ssh-agent
-    Clear,
+    Clear {
+        /// Skip the confirmation prompts
+        #[arg(long)]
+        yes: bool,
+        /// Output the result as JSON (requires --yes)
+        #[arg(long, requires = "yes")]
+        json: bool,
+    },
 }
 
 #[derive(Debug, Args)]
@@ -582,7 +1603,7 @@ struct ConfigSchemaArgs {
 struct ConfigGetArgs {
     /// Setting key
     key: String,
-    /// Setting scope (global, env:NAME, or profile:ID)
+    /// Setting scope (global, env:NAME, group:NAME, or profile:ID)
     #[arg(long, default_value = "global")]
     scope: String,
     /// Resolve the value from the scope and fall back to global if unset
@@ -597,7 +1618,7 @@ struct ConfigSetArgs {
     key: Option<String>,
     /// Setting value
     value: Option<String>,
-    /// Setting scope (global, env:NAME, or profile:ID)
+    /// Setting scope (global, env:NAME, group:NAME, or profile:ID)
     #[arg(long, default_value = "global")]
     scope: String,
     /// Resolve the value after setting (falls back to global if unset)
@@ -613,7 +1634,11 @@ enum ConfigSetCommands {
     /// Add a config set
     Add(ConfigSetAddArgs),
     /// List config sets
-    List,
+    List {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
     /// Show a config set in JSON
     Show { config_id: String },
     /// Remove a config set
@@ -679,62 +1704,366 @@ struct ExportArgs {
     /// Write output to a file instead of stdout
     #[arg(long, short = 'o')]
     output: Option<PathBuf>,
+    /// Export only these profiles, without command sets, configs, or secrets (repeatable)
+    #[arg(long = "profile", conflicts_with = "include_secrets")]
+    profiles: Vec<String>,
 }
 
 #[derive(Debug, Args)]
 struct ImportArgs {
-    /// Conflict strategy for name collisions (reject or rename)
+    /// Conflict strategy: reject or rename name collisions, or skip or overwrite existing ids
     #[arg(long, default_value = "reject")]
     conflict: ConflictArg,
     /// Path to an export JSON file (reads stdin if omitted)
     path: Option<PathBuf>,
 }
 
+#[derive(Debug, Args)]
+struct ViewArgs {
+    /// Path to an export JSON file (from td export)
+    path: PathBuf,
+    /// Allow CommandSet runs and SSH sessions against the snapshot's hosts
+    #[arg(long)]
+    allow_run: bool,
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 enum ConflictArg {
     Reject,
     Rename,
+    Skip,
+    Overwrite,
 }
 
-fn main() -> Result<()> {
-    let _guard = init_logging()?;
-    let cli = Cli::parse();
-    match cli.command {
-        Some(Commands::Profile { command }) => handle_profile(command),
-        Some(Commands::ConfigSet { command }) => handle_configset(command),
-        Some(Commands::Config { command }) => handle_config(command),
-        Some(Commands::Env { command }) => handle_env(command),
-        Some(Commands::Agent { command }) => handle_agent(command),
-        Some(Commands::Doctor { json }) => handle_doctor(json),
-        Some(Commands::Init(args)) => handle_init(args),
-        Some(Commands::Exec {
-            profile_id,
-            timeout_ms,
-            json,
-            parser,
-            cmd,
-        }) => handle_exec(profile_id, timeout_ms, json, parser, cmd),
-        Some(Commands::Run {
-            profile_id,
-            cmdset_id,
-            json,
-        }) => handle_run(profile_id, cmdset_id, json),
-        Some(Commands::Connect(args)) => handle_connect(args),
-        Some(Commands::Recent { limit, json }) => handle_recent(limit, json),
-        Some(Commands::Session { command }) => handle_session(command),
-        Some(Commands::Tunnel { command }) => handle_tunnel(command),
-        Some(Commands::Test {
-            profile_id,
-            json,
-            ssh,
-        }) => handle_test(profile_id, json, ssh),
-        Some(Commands::Push(args)) => handle_push(args),
+fn main() -> ExitCode {
+    completions::complete_if_requested();
+    if let Some(reply) = agent::askpass_reply(std::env::args().nth(1).as_deref()) {
+        println!("{reply}");
+        return exit::ExitStatus::Success.into();
+    }
+    let cli = match parse_cli() {
+        Ok(cli) => cli,
+        Err(err) if err.use_stderr() => {
+            let _ = err.print();
+            return exit::ExitStatus::Invalid.into();
+        }
+        Err(err) => err.exit(),
+    };
+    match run(cli) {
+        Ok(()) => exit::ExitStatus::Success.into(),
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            if err
+                .chain()
+                .filter_map(|cause| cause.downcast_ref::<tdcore::error::CoreError>())
+                .any(db::is_busy)
+            {
+                eprintln!("{}", db::describe_lock());
+            }
+            exit::classify(&err).into()
+        }
+    }
+}
+
+/// Applies `--db` or `--context` before anything opens the database.
+fn select_database(db: Option<PathBuf>, context: Option<&str>) -> Result<()> {
+    let path = match (db, context) {
+        (Some(path), _) => path,
+        (None, Some(name)) => db_context::resolve(name)?,
+        (None, None) => return Ok(()),
+    };
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    if !parent.is_dir() {
+        return Err(exit::invalid(format!(
+            "database directory does not exist: {}",
+            parent.display()
+        )));
+    }
+    paths::set_database_path(path);
+    Ok(())
+}
+
+/// Parses the command line. With `TD_OUTPUT=json`, a subcommand that has `--json` gets
+/// it as if it had been typed; when that does not parse (e.g. `td log show --output`),
+/// the command line is used as given.
+fn parse_cli() -> clap::error::Result<Cli> {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    if output::json_from_env() {
+        if let Some(with_json) = with_json_flag(&args) {
+            if let Ok(cli) = Cli::try_parse_from(with_json) {
+                return Ok(cli);
+            }
+        }
+    }
+    Cli::try_parse_from(args)
+}
+
+/// `args` with `--json` added for the selected subcommand, or `None` when it has no
+/// such flag or already sets it.
+fn with_json_flag(args: &[OsString]) -> Option<Vec<OsString>> {
+    let matches = Cli::command().try_get_matches_from(args).ok()?;
+    let mut command = Cli::command();
+    let mut current = &matches;
+    while let Some((name, sub)) = current.subcommand() {
+        command = command.find_subcommand(name)?.clone();
+        current = sub;
+    }
+    let has_json = command
+        .get_arguments()
+        .any(|arg| arg.get_long() == Some("json"));
+    if !has_json || matches!(current.try_get_one::<bool>("json"), Ok(Some(true))) {
+        return None;
+    }
+    // Before `--`, so trailing remote commands (`td exec <id> -- ...`) are left alone.
+    let at = args
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(args.len());
+    let mut args = args.to_vec();
+    args.insert(at, OsString::from("--json"));
+    Some(args)
+}
+
+/// Whether `command` only reads, so observer mode lets it through. Anything that runs on
+/// a host, opens a session or transfer, or changes stored data is refused.
+fn observer_allows(command: &Commands) -> bool {
+    match command {
+        Commands::Profile { command } => matches!(
+            command,
+            ProfileCommands::List(_)
+                | ProfileCommands::Search(_)
+                | ProfileCommands::Show { .. }
+                | ProfileCommands::Deleted { .. }
+        ),
+        Commands::ConfigSet { command } => matches!(
+            command,
+            ConfigSetCommands::List { .. } | ConfigSetCommands::Show { .. }
+        ),
+        Commands::Config { command } => match command {
+            ConfigCommands::Schema(_)
+            | ConfigCommands::Keys
+            | ConfigCommands::Get(_)
+            | ConfigCommands::ShowClient
+            | ConfigCommands::ShowAuthOrder => true,
+            // `config set` and `config leave-observer` stay refused; `run` lets the latter
+            // through only when observer mode comes from the setting, not `--observer`.
+            _ => false,
+        },
+        Commands::Env { command } => {
+            matches!(
+                command,
+                EnvCommands::List | EnvCommands::Current | EnvCommands::Show { .. }
+            )
+        }
+        Commands::Agent { command } => {
+            matches!(
+                command,
+                AgentCommands::Status { .. } | AgentCommands::List { .. }
+            )
+        }
+        Commands::Doctor { fix, .. } => !fix,
+        Commands::Db { command } => {
+            matches!(
+                command,
+                DbCommands::Status { .. }
+                    | DbCommands::Snapshots { .. }
+                    | DbCommands::Contexts { .. }
+            )
+        }
+        Commands::Suggest(args) => args.json,
+        Commands::SearchOutput(_) | Commands::Recent { .. } => true,
+        Commands::Session { command } => !matches!(
+            command,
+            SessionCommands::ConptyTest(_)
+                | SessionCommands::Prune(_)
+                | SessionCommands::Terminate { .. }
+        ),
+        Commands::Schedule { command } => matches!(
+            command,
+            ScheduleCommands::List { .. } | ScheduleCommands::Export { .. }
+        ),
+        Commands::Maintenance { command } => {
+            matches!(
+                command,
+                MaintenanceCommands::List { .. } | MaintenanceCommands::Export { .. }
+            )
+        }
+        Commands::Log { command } => {
+            !matches!(command, LogCommands::Prune(_) | LogCommands::Note(_))
+        }
+        Commands::Tunnel { command } => {
+            matches!(
+                command,
+                TunnelCommands::Status(_)
+                    | TunnelCommands::Env(_)
+                    | TunnelCommands::Preset {
+                        command: PresetCommands::List { .. }
+                    }
+            )
+        }
+        Commands::Daemon { command } => {
+            matches!(
+                command,
+                DaemonCommands::Status { .. } | DaemonCommands::Lock
+            )
+        }
+        Commands::Test { ssh, .. } => !ssh,
+        Commands::Fav { command } => matches!(command, FavCommands::List { .. }),
+        Commands::Secret { command } => matches!(
+            command,
+            SecretCommands::List { .. } | SecretCommands::Lock | SecretCommands::Status { .. }
+        ),
+        Commands::Export(args) => !args.include_secrets,
+        Commands::Ui(_) | Commands::Completions { .. } => true,
+        Commands::View(args) => !args.allow_run,
+        Commands::Init(_)
+        | Commands::Exec { .. }
+        | Commands::ExecScript(_)
+        | Commands::Run(_)
+        | Commands::Connect(_)
+        | Commands::Tail(_)
+        | Commands::Api { .. }
+        | Commands::Push(_)
+        | Commands::Pull(_)
+        | Commands::Xfer(_)
+        | Commands::Import(_) => false,
+    }
+}
+
+/// `td` plus the subcommand path (e.g. `td log prune`) for `lockinfo`; arguments and
+/// option values are left out so nothing sensitive is stored.
+fn command_label() -> String {
+    let mut words = vec!["td".to_string()];
+    if let Ok(matches) = Cli::command().try_get_matches() {
+        let mut current = &matches;
+        while let Some((name, sub)) = current.subcommand() {
+            words.push(name.to_string());
+            current = sub;
+        }
+    }
+    words.join(" ")
+}
+
+/// Opens the database; with `read_only`, a list/show command falls back to a read-only
+/// connection when another process holds the lock.
+fn open_db(read_only: bool) -> Result<Connection> {
+    match db::init_connection() {
+        Ok(conn) => Ok(conn),
+        Err(err) if read_only && db::is_busy(&err) => {
+            let conn = db::open_read_only().map_err(|_| err)?;
+            eprintln!("Warning: {}", db::describe_lock());
+            eprintln!("Showing data through a read-only connection.");
+            Ok(conn)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let _guard = init_logging()?;
+    output::configure(output::OutputOptions {
+        no_color: cli.no_color,
+        no_pager: cli.no_pager,
+    });
+    let _ = POLICY_REASON.set(cli.reason);
+    let _ = INTERACTIVE.set(!cli.no_interactive);
+    select_database(cli.db, cli.context.as_deref())?;
+    // Rollback replaces the database file, so it must not hold (or migrate) a connection.
+    let rolling_back = matches!(
+        cli.command,
+        Some(Commands::Db {
+            command: DbCommands::Rollback(_)
+        })
+    );
+    let leaving_observer = matches!(
+        cli.command,
+        Some(Commands::Config {
+            command: ConfigCommands::LeaveObserver
+        })
+    ) && !cli.observer;
+    if cli
+        .command
+        .as_ref()
+        .is_some_and(|command| !observer_allows(command))
+        && !leaving_observer
+        && (cli.observer
+            || if rolling_back {
+                observer_enabled_unmigrated()
+            } else {
+                settings::get_observer_enabled(&db::init_connection()?)?
+            })
+    {
+        return Err(exit::policy_denied(format!(
+            "observer mode: `{}` is disabled; observers can only browse",
+            command_label()
+        )));
+    }
+    let _registration = cli
+        .command
+        .as_ref()
+        .filter(|_| !rolling_back)
+        .and_then(|_| db::register_process(&command_label()));
+    match cli.command {
+        Some(Commands::Profile { command }) => handle_profile(command),
+        Some(Commands::ConfigSet { command }) => handle_configset(command),
+        Some(Commands::Config { command }) => handle_config(command),
+        Some(Commands::Env { command }) => handle_env(command),
+        Some(Commands::Agent { command }) => handle_agent(command),
+        Some(Commands::Doctor {
+            json,
+            watch: true,
+            interval,
+            ..
+        }) => handle_doctor_watch(json, interval),
+        Some(Commands::Doctor { json, fix, yes, .. }) => handle_doctor(json, fix, yes),
+        Some(Commands::Db { command }) => handle_db(command),
+        Some(Commands::Init(args)) => handle_init(args),
+        Some(Commands::Suggest(args)) => handle_suggest(args),
+        Some(Commands::ExecScript(args)) => handle_exec_script(args),
+        Some(Commands::SearchOutput(args)) => handle_search_output(args),
+        Some(Commands::Exec {
+            profile_id,
+            timeout_ms,
+            json,
+            parser,
+            cmd,
+        }) => handle_exec(profile_id, timeout_ms, json, parser, cmd),
+        Some(Commands::Run(args)) => handle_run(args),
+        Some(Commands::Connect(args)) => handle_connect(args),
+        Some(Commands::Tail(args)) => handle_tail(args),
+        Some(Commands::Recent { limit, json }) => handle_recent(limit, json),
+        Some(Commands::Session { command }) => handle_session(command),
+        Some(Commands::Schedule { command }) => handle_schedule(command),
+        Some(Commands::Maintenance { command }) => handle_maintenance(command),
+        Some(Commands::Log { command }) => handle_log(command),
+        Some(Commands::Tunnel { command }) => handle_tunnel(command),
+        Some(Commands::Daemon { command }) => handle_daemon(command),
+        Some(Commands::Api { command }) => handle_api(command),
+        Some(Commands::Test {
+            profile_id,
+            all,
+            json,
+            ssh,
+            samples,
+        }) => match profile_id {
+            Some(profile_id) if !all => handle_test(profile_id, json, ssh, samples),
+            _ => handle_test_all(json, ssh, samples),
+        },
+        Some(Commands::Push(args)) => handle_push(args),
         Some(Commands::Pull(args)) => handle_pull(args),
         Some(Commands::Xfer(args)) => handle_xfer(args),
+        Some(Commands::Fav { command }) => handle_fav(command),
         Some(Commands::Secret { command }) => handle_secret(command),
         Some(Commands::Export(args)) => handle_export(args),
         Some(Commands::Import(args)) => handle_import(args),
-        Some(Commands::Ui) => handle_ui(),
+        Some(Commands::Ui(args)) => handle_ui(args, cli.observer),
+        Some(Commands::View(args)) => handle_view(args),
+        Some(Commands::Completions { shell }) => {
+            completions::write_registration(shell, &mut io::stdout())
+        }
         None => {
             Cli::command().print_help()?;
             println!();
@@ -751,7 +2080,11 @@ fn handle_export(args: ExportArgs) -> Result<()> {
         None
     };
     let conn = db::init_connection()?;
-    let json = import_export::export_to_json(&conn, args.include_secrets, master.as_ref())?;
+    let json = if args.profiles.is_empty() {
+        import_export::export_to_json(&conn, args.include_secrets, master.as_ref())?
+    } else {
+        serde_json::to_string_pretty(&import_export::export_profiles(&conn, &args.profiles)?)?
+    };
     if let Some(path) = args.output {
         std::fs::write(&path, json)?;
         info!("export written to {}", path.display());
@@ -778,6 +2111,8 @@ fn handle_import(args: ImportArgs) -> Result<()> {
         match args.conflict {
             ConflictArg::Reject => ConflictStrategy::Reject,
             ConflictArg::Rename => ConflictStrategy::Rename,
+            ConflictArg::Skip => ConflictStrategy::Skip,
+            ConflictArg::Overwrite => ConflictStrategy::Overwrite,
         },
         master.as_ref(),
     )?;
@@ -785,8 +2120,52 @@ fn handle_import(args: ImportArgs) -> Result<()> {
     Ok(())
 }
 
-fn handle_ui() -> Result<()> {
-    tdtui::run()
+#[cfg(feature = "tui")]
+fn handle_ui(args: UiArgs, observer: bool) -> Result<()> {
+    tdtui::run(args.profile_startup, observer)
+}
+
+#[cfg(not(feature = "tui"))]
+fn handle_ui(_args: UiArgs, _observer: bool) -> Result<()> {
+    Err(feature_disabled("tui"))
+}
+
+#[cfg(not(feature = "tui"))]
+fn handle_view(_args: ViewArgs) -> Result<()> {
+    Err(feature_disabled("tui"))
+}
+
+/// Commands stay in the CLI of slim builds and explain what is missing instead.
+#[cfg_attr(all(feature = "tui", feature = "api"), allow(dead_code))]
+fn feature_disabled(feature: &str) -> anyhow::Error {
+    exit::invalid(format!(
+        "this td was built without the `{feature}` feature; rebuild with `--features {feature}`"
+    ))
+}
+
+#[cfg(feature = "tui")]
+fn handle_view(args: ViewArgs) -> Result<()> {
+    let json = std::fs::read_to_string(&args.path)
+        .with_context(|| format!("failed to read {}", args.path.display()))?;
+    let document: ExportDocument = serde_json::from_str(&json)
+        .with_context(|| format!("{} is not a TeraDock export", args.path.display()))?;
+    let name = tdcore::id::generate_id("td_view_");
+    let mut conn = db::init_shared_memory(&name)?;
+    let report = import_export::load_snapshot(&mut conn, document)?;
+    info!(
+        "snapshot loaded: profiles={} cmdsets={}",
+        report.profiles, report.cmdsets
+    );
+    let cmdset_conn = db::init_shared_memory(&name)?;
+    if !args.allow_run {
+        db::set_query_only(&conn)?;
+        db::set_query_only(&cmdset_conn)?;
+    }
+    tdtui::run_snapshot(
+        ProfileStore::new(conn),
+        CmdSetStore::new(cmdset_conn),
+        args.allow_run,
+    )
 }
 
 fn read_import_payload(path: Option<&Path>) -> Result<String> {
@@ -808,6 +2187,12 @@ fn print_import_report(report: &ImportReport) {
         report.secrets,
         report.secrets_skipped
     );
+    if report.skipped > 0 || report.overwritten > 0 {
+        println!(
+            "existing: skipped={}, overwritten={}",
+            report.skipped, report.overwritten
+        );
+    }
 }
 
 fn handle_init(args: InitArgs) -> Result<()> {
@@ -907,8 +2292,111 @@ fn linux_basic_check_sample() -> NewCmdSet {
     }
 }
 
-fn handle_profile(cmd: ProfileCommands) -> Result<()> {
+fn handle_suggest(args: SuggestArgs) -> Result<()> {
+    let paths = if args.from_history.is_empty() {
+        let home = directories::BaseDirs::new()
+            .map(|dirs| dirs.home_dir().to_path_buf())
+            .ok_or_else(|| exit::failure("failed to resolve home directory"))?;
+        host_import::default_history_paths(&home)
+            .into_iter()
+            .filter(|path| path.is_file())
+            .collect()
+    } else {
+        args.from_history
+    };
+    if paths.is_empty() {
+        return Err(exit::not_found(
+            "no shell history found; pass --from-history <PATH>",
+        ));
+    }
+    let mut raw = String::new();
+    for path in &paths {
+        let bytes =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        // zsh and PowerShell history may contain non-UTF-8 bytes.
+        raw.push_str(&String::from_utf8_lossy(&bytes));
+        raw.push('\n');
+    }
     let store = ProfileStore::new(db::init_connection()?);
+    let defaults = HostImportDefaults {
+        user: args
+            .user
+            .or_else(|| std::env::var("USER").ok())
+            .or_else(|| std::env::var("USERNAME").ok()),
+        port: 22,
+        profile_type: ProfileType::Ssh,
+        danger_level: DangerLevel::Normal,
+        group: None,
+        tags: args.tag,
+    };
+    let suggestions = host_import::unknown_history_hosts(
+        &store,
+        host_import::parse_ssh_history(&raw),
+        &defaults,
+    )?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&suggestions)?);
+        return Ok(());
+    }
+    if suggestions.is_empty() {
+        println!("No new ssh destinations found.");
+        return Ok(());
+    }
+    let mut rows = Vec::new();
+    for suggestion in &suggestions {
+        if args.yes || confirm_suggestion(suggestion, &defaults)? {
+            rows.push(suggestion.to_row());
+        }
+    }
+    let report = host_import::import_hosts(&store, &rows, &defaults)?;
+    info!(
+        "history suggestions imported: created={} skipped={}",
+        report.created.len(),
+        report.skipped.len()
+    );
+    for profile_id in &report.created {
+        println!("created {profile_id}");
+    }
+    for skipped in &report.skipped {
+        println!("skipped {}: {}", skipped.host, skipped.reason);
+    }
+    println!(
+        "created: {}, skipped: {}",
+        report.created.len(),
+        report.skipped.len()
+    );
+    Ok(())
+}
+
+fn confirm_suggestion(
+    suggestion: &host_import::HistoryHost,
+    defaults: &HostImportDefaults,
+) -> Result<bool> {
+    let user = suggestion
+        .user
+        .as_deref()
+        .or(defaults.user.as_deref())
+        .unwrap_or("?");
+    print!(
+        "Create profile for {user}@{}:{} (used {} time{})? [y/N] ",
+        suggestion.host,
+        suggestion.port.unwrap_or(defaults.port),
+        suggestion.count,
+        if suggestion.count == 1 { "" } else { "s" }
+    );
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let answer = input.trim();
+    Ok(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"))
+}
+
+fn handle_profile(cmd: ProfileCommands) -> Result<()> {
+    let read_only = matches!(
+        cmd,
+        ProfileCommands::List(_) | ProfileCommands::Search(_) | ProfileCommands::Show { .. }
+    );
+    let store = ProfileStore::new(open_db(read_only)?);
     match cmd {
         ProfileCommands::Add(args) => {
             let profile_type = parse_profile_type(&args.r#type)?;
@@ -928,6 +2416,7 @@ fn handle_profile(cmd: ProfileCommands) -> Result<()> {
                 initial_send: args.initial_send,
                 client_overrides: overrides,
             })?;
+            warn_invalid_client_paths(created.client_overrides.as_ref());
             info!("profile created: {}", created.profile_id);
             println!("{}", created.profile_id);
             Ok(())
@@ -977,10 +2466,126 @@ fn handle_profile(cmd: ProfileCommands) -> Result<()> {
                     client_overrides: overrides,
                 },
             )?;
+            warn_invalid_client_paths(updated.client_overrides.as_ref());
+            info!("profile updated: {}", updated.profile_id);
+            println!("{}", updated.profile_id);
+            Ok(())
+        }
+        ProfileCommands::Set(args) => {
+            let current = store.get(&args.profile_id)?.ok_or_else(|| {
+                exit::not_found(format!("profile not found: {}", args.profile_id))
+            })?;
+            let profile_type = match args.r#type {
+                Some(ref t) => Some(parse_profile_type(t)?),
+                None => None,
+            };
+            let danger = match args.danger {
+                Some(ref d) => Some(parse_danger(d)?),
+                None => None,
+            };
+            let tags = if args.tag.is_empty() {
+                None
+            } else {
+                Some(profile::apply_tag_edits(&current.tags, &args.tag))
+            };
+            let overrides = match args.client_overrides_patch {
+                Some(raw) => {
+                    let patch: serde_json::Value = serde_json::from_str(&raw)
+                        .with_context(|| "invalid --client-overrides-patch JSON")?;
+                    Some(profile::patch_client_overrides(
+                        current.client_overrides.as_ref(),
+                        &patch,
+                    )?)
+                }
+                None => None,
+            };
+            let group = if args.clear_group {
+                Some(None)
+            } else {
+                args.group.map(Some)
+            };
+            let note = if args.clear_note {
+                Some(None)
+            } else {
+                args.note.map(Some)
+            };
+            let initial_send = if args.clear_initial_send {
+                Some(None)
+            } else {
+                args.initial_send.map(Some)
+            };
+            let updated = store.update(
+                &args.profile_id,
+                UpdateProfile {
+                    name: args.name,
+                    profile_type,
+                    host: args.host,
+                    port: args.port,
+                    user: args.user,
+                    danger_level: danger,
+                    group,
+                    tags,
+                    note,
+                    initial_send,
+                    client_overrides: overrides,
+                },
+            )?;
+            warn_invalid_client_paths(updated.client_overrides.as_ref());
             info!("profile updated: {}", updated.profile_id);
             println!("{}", updated.profile_id);
             Ok(())
         }
+        ProfileCommands::Clone(args) => {
+            let cloned = store.clone_profile(
+                &args.src_id,
+                CloneProfile {
+                    profile_id: args.new_id,
+                    name: args.name,
+                    host: args.host,
+                },
+            )?;
+            info!("profile cloned: {} -> {}", args.src_id, cloned.profile_id);
+            println!("{}", cloned.profile_id);
+            Ok(())
+        }
+        ProfileCommands::ImportHosts(args) => {
+            let raw = std::fs::read_to_string(&args.path)
+                .with_context(|| format!("failed to read {}", args.path.display()))?;
+            let rows = host_import::parse_host_rows(&raw);
+            let defaults = HostImportDefaults {
+                user: args.user,
+                port: args.port,
+                profile_type: parse_profile_type(&args.r#type)?,
+                danger_level: parse_danger(&args.danger)?,
+                group: args.group,
+                tags: args.tag,
+            };
+            let report = host_import::import_hosts(&store, &rows, &defaults)?;
+            info!(
+                "hosts imported: created={} skipped={}",
+                report.created.len(),
+                report.skipped.len()
+            );
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                return Ok(());
+            }
+            for profile_id in &report.created {
+                println!("created {profile_id}");
+            }
+            for skipped in &report.skipped {
+                println!(
+                    "skipped line {} ({}): {}",
+                    skipped.line, skipped.host, skipped.reason
+                );
+            }
+            println!(
+                "created: {}, skipped: {}",
+                report.created.len(),
+                report.skipped.len()
+            );
+            Ok(())
+        }
         ProfileCommands::List(args) => {
             let profile_type = match args.r#type {
                 Some(ref t) => Some(parse_profile_type(t)?),
@@ -996,41 +2601,159 @@ fn handle_profile(cmd: ProfileCommands) -> Result<()> {
                 profile_type,
                 danger,
                 query: args.query,
+                include_archived: args.archived,
             };
             let profiles = store.list_filtered(&filters)?;
-            if profiles.is_empty() {
-                println!("(no profiles)");
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&profiles)?);
                 return Ok(());
             }
-            for p in profiles {
-                println!(
-                    "{:<16} {:<10} {:<5} {:<15} {:<12} {:<8} {}",
-                    p.profile_id, p.name, p.profile_type, p.host, p.user, p.port, p.danger_level
-                );
+            print_profile_rows(&profiles)
+        }
+        ProfileCommands::Search(args) => {
+            let filters = ProfileFilters {
+                query: Some(args.query.join(" ")),
+                include_archived: args.archived,
+                ..ProfileFilters::default()
+            };
+            let mut profiles = store.search(&filters)?;
+            if let Some(limit) = args.limit {
+                profiles.truncate(limit);
             }
-            Ok(())
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&profiles)?);
+                return Ok(());
+            }
+            print_profile_rows(&profiles)
         }
-        ProfileCommands::Show { profile_id } => {
+        ProfileCommands::Show { profile_id, .. } => {
+            let profile_id = profile_id_or_pick(&store, profile_id)?;
             match store.get(&profile_id)? {
                 Some(profile) => {
                     let serialized = serde_json::to_string_pretty(&profile)?;
                     println!("{serialized}");
                 }
-                None => return Err(anyhow!("profile not found: {profile_id}")),
+                None => return Err(exit::not_found(format!("profile not found: {profile_id}"))),
             }
             Ok(())
         }
-        ProfileCommands::Rm { profile_id } => {
-            if store.delete(&profile_id)? {
-                info!("removed profile {}", profile_id);
+        ProfileCommands::Rm {
+            profile_id,
+            permanent,
+        } => {
+            let removed = if permanent {
+                store.delete(&profile_id)?
             } else {
+                store.delete_restorable(&profile_id)?
+            };
+            if !removed {
                 warn!("profile not found: {}", profile_id);
+                return Ok(());
+            }
+            info!("removed profile {}", profile_id);
+            if !permanent {
+                eprintln!("Removed {profile_id}; td profile restore {profile_id} brings it back.");
+            }
+            Ok(())
+        }
+        ProfileCommands::Purge(args) => {
+            let deleted_before = args
+                .older_than
+                .as_deref()
+                .map(parse_prune_age_ms)
+                .transpose()?
+                .map(|age| now_ms() - age);
+            let preview = args.dry_run || !args.yes;
+            let purged =
+                store.purge_deleted(args.profile_id.as_deref(), deleted_before, preview)?;
+            let verb = if preview { "would drop" } else { "dropped" };
+            for entry in &purged {
+                println!(
+                    "{verb} {} ({})",
+                    entry.profile.profile_id, entry.profile.name
+                );
+            }
+            println!("{verb} {} removed profile copies", purged.len());
+            if !args.dry_run && !args.yes && !purged.is_empty() {
+                return Err(exit::policy_denied(
+                    "refusing to drop removed profiles without --yes; rerun with --dry-run to preview or --yes to drop",
+                ));
+            }
+            Ok(())
+        }
+        ProfileCommands::Restore { profile_id } => {
+            let profile = store.restore_deleted(&profile_id)?;
+            info!("profile restored: {}", profile.profile_id);
+            println!("{}", profile.profile_id);
+            Ok(())
+        }
+        ProfileCommands::Deleted { json } => {
+            let deleted = store.list_deleted()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&deleted)?);
+                return Ok(());
+            }
+            if deleted.is_empty() {
+                println!("(no removed profiles)");
+                return Ok(());
             }
+            let offset = settings::get_display_offset(store.conn())?;
+            println!("{:<16}  {:<24}  {:<28}  REMOVED", "PROFILE", "NAME", "HOST");
+            for entry in deleted {
+                println!(
+                    "{:<16}  {:<24}  {:<28}  {}",
+                    entry.profile.profile_id,
+                    entry.profile.name,
+                    entry.profile.host,
+                    util::format_timestamp_ms(entry.deleted_at, offset)
+                );
+            }
+            Ok(())
+        }
+        ProfileCommands::Archive { profile_id } => {
+            let profile = store.set_archived(&profile_id, true)?;
+            info!("profile archived: {}", profile.profile_id);
+            println!("{}", profile.profile_id);
+            Ok(())
+        }
+        ProfileCommands::Unarchive { profile_id } => {
+            let profile = store.set_archived(&profile_id, false)?;
+            info!("profile unarchived: {}", profile.profile_id);
+            println!("{}", profile.profile_id);
             Ok(())
         }
     }
 }
 
+fn print_profile_rows(profiles: &[Profile]) -> Result<()> {
+    if profiles.is_empty() {
+        println!("(no profiles)");
+        return Ok(());
+    }
+    output::page(|out| {
+        for p in profiles {
+            writeln!(
+                out,
+                "{:<16} {} {:<5} {:<15} {:<12} {:<8} {}{}",
+                p.profile_id,
+                util::pad_display(&p.name, 10),
+                p.profile_type,
+                p.host,
+                p.user,
+                p.port,
+                output::danger_cell(&p.danger_level.to_string(), 0),
+                if p.archived_at.is_some() {
+                    output::paint(" (archived)", Tone::Dim)
+                } else {
+                    String::new()
+                }
+            )?;
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
 fn handle_config(cmd: ConfigCommands) -> Result<()> {
     let conn = db::init_connection()?;
     match cmd {
@@ -1038,10 +2761,14 @@ fn handle_config(cmd: ConfigCommands) -> Result<()> {
         ConfigCommands::Keys => handle_config_keys(),
         ConfigCommands::Get(args) => handle_config_get(&conn, args),
         ConfigCommands::Set(args) => handle_config_set(&conn, args),
+        ConfigCommands::LeaveObserver => handle_config_leave_observer(&conn),
+        #[cfg(feature = "tui")]
         ConfigCommands::Ui => {
             tdtui::run_settings_ui()?;
             Ok(())
         }
+        #[cfg(not(feature = "tui"))]
+        ConfigCommands::Ui => Err(feature_disabled("tui")),
         ConfigCommands::SetClient(args) => {
             let mut overrides = if args.clear_all {
                 ClientOverrides::default()
@@ -1064,6 +2791,7 @@ fn handle_config(cmd: ConfigCommands) -> Result<()> {
                 overrides.telnet = Some(path);
             }
             settings::set_client_overrides(&conn, &overrides)?;
+            warn_invalid_client_paths(Some(&overrides));
             info!("updated client overrides");
             println!(
                 "{}",
@@ -1150,31 +2878,61 @@ fn handle_agent(cmd: AgentCommands) -> Result<()> {
             }
             Ok(())
         }
-        AgentCommands::Add { key_path } => {
+        AgentCommands::Add {
+            key_path,
+            yes,
+            json,
+        } => {
             ensure_agent_socket_available()?;
-            if !confirm_agent_add(&key_path)? {
+            if !yes && !confirm_agent_add(&key_path)? {
                 println!("aborted");
                 return Ok(());
             }
             let output = agent::run_add(&key_path)?;
             handle_ssh_add_output(output, "ssh-add add")?;
-            println!("ssh-add: key added");
-            Ok(())
+            print_agent_change(json, "add", Some(&key_path), "key added")
         }
-        AgentCommands::Clear => {
+        AgentCommands::Remove {
+            key_path,
+            yes,
+            json,
+        } => {
             ensure_agent_socket_available()?;
-            if !confirm_agent_clear()? {
+            if !yes && !confirm_agent_remove(&key_path)? {
                 println!("aborted");
                 return Ok(());
             }
-            let output = agent::run_clear()?;
+            let output = agent::run_remove(&key_path)?;
+            handle_ssh_add_output(output, "ssh-add remove")?;
+            print_agent_change(json, "remove", Some(&key_path), "key removed")
+        }
+        AgentCommands::Clear { yes, json } => {
+            ensure_agent_socket_available()?;
+            if !yes && !confirm_agent_clear()? {
+                println!("aborted");
+                return Ok(());
+            }
+            let output = agent::run_clear()?;
             handle_ssh_add_output(output, "ssh-add clear")?;
-            println!("ssh-add: keys cleared");
-            Ok(())
+            print_agent_change(json, "clear", None, "keys cleared")
         }
     }
 }
 
+fn print_agent_change(json: bool, action: &str, key: Option<&Path>, message: &str) -> Result<()> {
+    if json {
+        let value = serde_json::json!({
+            "ok": true,
+            "action": action,
+            "key_path": key.map(|path| path.display().to_string()),
+        });
+        println!("{}", serde_json::to_string_pretty(&value)?);
+    } else {
+        println!("ssh-add: {message}");
+    }
+    Ok(())
+}
+
 fn handle_env(cmd: EnvCommands) -> Result<()> {
     let conn = db::init_connection()?;
     match cmd {
@@ -1198,12 +2956,65 @@ fn handle_env(cmd: EnvCommands) -> Result<()> {
             }
             Ok(())
         }
-        EnvCommands::Use { name } => {
+        EnvCommands::Current => {
+            if let Some(current) = settings::get_current_env(&conn)? {
+                println!("{current}");
+            }
+            Ok(())
+        }
+        EnvCommands::Create { name } => {
             let name = normalize_env_name(&name)?;
+            if !settings::create_env(&conn, &name)? {
+                return Err(exit::invalid(format!("env already exists: {name}")));
+            }
+            println!("Created env {name}; switch to it with `td env use {name}`.");
+            Ok(())
+        }
+        EnvCommands::Use { clear: true, .. } => {
+            settings::clear_current_env(&conn)?;
+            println!("No env in use; global settings apply.");
+            Ok(())
+        }
+        EnvCommands::Use { name, .. } => {
+            let name = normalize_env_name(name.as_deref().unwrap_or_default())?;
+            if !settings::env_exists(&conn, &name)? {
+                eprintln!("Warning: env {name} does not exist yet; create it with `td env create {name}`.");
+            }
             settings::set_current_env(&conn, &name)?;
             println!("{name}");
             Ok(())
         }
+        EnvCommands::Unset { name_key } => {
+            let (name, key) = parse_env_key(&name_key)?;
+            let name = normalize_env_name(&name)?;
+            ensure_known_setting(&key)?;
+            let scope = SettingScope::Env(name);
+            settings::clear_setting_scoped(&conn, &scope, &key)?;
+            println!("{key}=");
+            Ok(())
+        }
+        EnvCommands::Rm { name, yes } => {
+            let name = normalize_env_name(&name)?;
+            if !settings::env_exists(&conn, &name)?
+                && settings::get_current_env(&conn)?.as_deref() != Some(name.as_str())
+            {
+                return Err(exit::not_found(format!("env not found: {name}")));
+            }
+            if !yes {
+                println!("About to delete env {name} and all of its settings.");
+                print!("Type 'yes' to continue: ");
+                io::stdout().flush()?;
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+                if !input.trim().eq_ignore_ascii_case("yes") {
+                    println!("Aborted by user.");
+                    return Ok(());
+                }
+            }
+            let removed = settings::remove_env(&conn, &name)?;
+            println!("Removed env {name} ({removed} setting(s)).");
+            Ok(())
+        }
         EnvCommands::Show { name } => {
             let name = normalize_env_name(&name)?;
             let scope = SettingScope::Env(name);
@@ -1226,9 +3037,13 @@ fn handle_env(cmd: EnvCommands) -> Result<()> {
                 Ok(normalized) => normalized,
                 Err(err) => {
                     let schema = schema_output_for_key(&key)?;
-                    return Err(anyhow!("invalid value for '{key}': {err}\n\n{schema}"));
+                    return Err(exit::invalid(format!(
+                        "invalid value for '{key}': {err}\n\n{schema}"
+                    )));
                 }
             };
+            // Keeps the env listed after its last setting is unset.
+            settings::create_env(&conn, &name)?;
             let scope = SettingScope::Env(name);
             settings::set_setting_scoped(&conn, &scope, &key, &normalized)?;
             println!("{key}={normalized}");
@@ -1258,7 +3073,7 @@ fn handle_config_keys() -> Result<()> {
 fn handle_config_get(conn: &Connection, args: ConfigGetArgs) -> Result<()> {
     ensure_known_setting(&args.key)?;
     let scope = SettingScope::parse(&args.scope)
-        .map_err(|err| anyhow!("invalid scope '{}': {err}", args.scope))?;
+        .map_err(|err| exit::invalid(format!("invalid scope '{}': {err}", args.scope)))?;
     ensure_scope_supported(&args.key, scope.kind())?;
     let mut value = if args.resolved {
         settings::get_setting_resolved(conn, &scope, &args.key)?
@@ -1276,6 +3091,39 @@ fn handle_config_get(conn: &Connection, args: ConfigGetArgs) -> Result<()> {
     Ok(())
 }
 
+/// Clears `observer.enabled` globally and for the active env once the phrase is typed;
+/// scripts and piped input cannot confirm it.
+fn handle_config_leave_observer(conn: &Connection) -> Result<()> {
+    if !settings::get_observer_enabled(conn)? {
+        println!("Observer mode is not enabled.");
+        return Ok(());
+    }
+    if !io::stdin().is_terminal() {
+        return Err(exit::invalid(
+            "leaving observer mode needs a typed confirmation on a terminal",
+        ));
+    }
+    const PHRASE: &str = "leave observer mode";
+    print!("Type '{PHRASE}' to continue: ");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if input.trim() != PHRASE {
+        println!("Aborted by user.");
+        return Err(exit::policy_denied("observer mode not left"));
+    }
+    settings::clear_setting_scoped(conn, &SettingScope::Global, settings::OBSERVER_ENABLED_KEY)?;
+    if let Some(env) = settings::get_current_env(conn)? {
+        settings::clear_setting_scoped(
+            conn,
+            &SettingScope::Env(env),
+            settings::OBSERVER_ENABLED_KEY,
+        )?;
+    }
+    println!("Observer mode is off.");
+    Ok(())
+}
+
 fn default_resolved_config_value(conn: &Connection, key: &str) -> Result<Option<String>> {
     Ok(session_log::default_value_for_key(conn, key)?)
 }
@@ -1292,19 +3140,21 @@ fn handle_config_set(conn: &Connection, args: ConfigSetArgs) -> Result<()> {
     }
     let key = args
         .key
-        .ok_or_else(|| anyhow!("missing config key (use --help for schema)"))?;
+        .ok_or_else(|| exit::invalid("missing config key (use --help for schema)"))?;
     ensure_known_setting(&key)?;
     let value = args
         .value
-        .ok_or_else(|| anyhow!("missing value for config key '{key}'"))?;
+        .ok_or_else(|| exit::invalid(format!("missing value for config key '{key}'")))?;
     let scope = SettingScope::parse(&args.scope)
-        .map_err(|err| anyhow!("invalid scope '{}': {err}", args.scope))?;
+        .map_err(|err| exit::invalid(format!("invalid scope '{}': {err}", args.scope)))?;
     ensure_scope_supported(&key, scope.kind())?;
     let normalized = match settings_registry::validate_setting_value(&key, &value) {
         Ok(normalized) => normalized,
         Err(err) => {
             let schema = schema_output_for_key(&key)?;
-            return Err(anyhow!("invalid value for '{key}': {err}\n\n{schema}"));
+            return Err(exit::invalid(format!(
+                "invalid value for '{key}': {err}\n\n{schema}"
+            )));
         }
     };
     settings::set_setting_scoped(conn, &scope, &key, &normalized)?;
@@ -1319,20 +3169,20 @@ fn handle_config_set(conn: &Connection, args: ConfigSetArgs) -> Result<()> {
 
 fn ensure_known_setting(key: &str) -> Result<()> {
     if settings_registry::schema_for_key(key).is_none() {
-        return Err(anyhow!(
+        return Err(exit::invalid(format!(
             "unknown config key: {key}\nknown keys: {}",
             settings_registry::list_keys().join(", ")
-        ));
+        )));
     }
     Ok(())
 }
 
 fn ensure_scope_supported(key: &str, scope: settings::SettingScopeKind) -> Result<()> {
     if !settings_registry::scope_supported(key, scope)? {
-        return Err(anyhow!(
+        return Err(exit::invalid(format!(
             "config key '{key}' does not support {} scope",
             format_scope_kind(scope)
-        ));
+        )));
     }
     Ok(())
 }
@@ -1341,20 +3191,21 @@ fn format_scope_kind(scope: settings::SettingScopeKind) -> &'static str {
     match scope {
         settings::SettingScopeKind::Global => "global",
         settings::SettingScopeKind::Env => "env",
+        settings::SettingScopeKind::Group => "group",
         settings::SettingScopeKind::Profile => "profile",
     }
 }
 
 fn schema_output_for_key(key: &str) -> Result<String> {
     let schema = settings_registry::schema_for_key(key)
-        .ok_or_else(|| anyhow!("unknown config key: {key}"))?;
+        .ok_or_else(|| exit::invalid(format!("unknown config key: {key}")))?;
     Ok(serde_json::to_string_pretty(schema)?)
 }
 
 fn normalize_env_name(name: &str) -> Result<String> {
     let trimmed = name.trim();
     if trimmed.is_empty() {
-        return Err(anyhow!("env name cannot be empty"));
+        return Err(exit::invalid("env name cannot be empty"));
     }
     Ok(trimmed.to_string())
 }
@@ -1364,18 +3215,24 @@ fn parse_env_key(raw: &str) -> Result<(String, String)> {
     let env = parts.next().unwrap_or("").trim();
     let key = parts.next().unwrap_or("").trim();
     if env.is_empty() || key.is_empty() {
-        return Err(anyhow!("env setting must be in the form NAME.KEY"));
+        return Err(exit::invalid("env setting must be in the form NAME.KEY"));
     }
     Ok((env.to_string(), key.to_string()))
 }
 
 fn handle_configset(cmd: ConfigSetCommands) -> Result<()> {
-    let mut store = ConfigSetStore::new(db::init_connection()?);
+    let read_only = matches!(
+        cmd,
+        ConfigSetCommands::List { .. } | ConfigSetCommands::Show { .. }
+    );
+    let mut store = ConfigSetStore::new(open_db(read_only)?);
     match cmd {
         ConfigSetCommands::Add(args) => {
             let files = parse_config_file_specs(&args.file)?;
             if files.is_empty() {
-                return Err(anyhow!("config set must include at least one --file entry"));
+                return Err(exit::invalid(
+                    "config set must include at least one --file entry",
+                ));
             }
             let created = store.insert(NewConfigSet {
                 config_id: args.config_id,
@@ -1387,8 +3244,12 @@ fn handle_configset(cmd: ConfigSetCommands) -> Result<()> {
             println!("{}", created.config.config_id);
             Ok(())
         }
-        ConfigSetCommands::List => {
+        ConfigSetCommands::List { json } => {
             let sets = store.list()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&sets)?);
+                return Ok(());
+            }
             if sets.is_empty() {
                 println!("(no config sets)");
                 return Ok(());
@@ -1405,7 +3266,11 @@ fn handle_configset(cmd: ConfigSetCommands) -> Result<()> {
                     let serialized = serde_json::to_string_pretty(&details)?;
                     println!("{serialized}");
                 }
-                None => return Err(anyhow!("config set not found: {config_id}")),
+                None => {
+                    return Err(exit::not_found(format!(
+                        "config set not found: {config_id}"
+                    )))
+                }
             }
             Ok(())
         }
@@ -1425,31 +3290,21 @@ fn handle_config_apply(args: ConfigApplyArgs) -> Result<()> {
     let config_store = ConfigSetStore::new(db::init_connection()?);
     let profile = profile_store
         .get(&args.profile_id)?
-        .ok_or_else(|| anyhow!("profile not found: {}", args.profile_id))?;
+        .ok_or_else(|| exit::not_found(format!("profile not found: {}", args.profile_id)))?;
     ensure_ssh_profile(&profile, "config apply")?;
-    if profile.danger_level == DangerLevel::Critical && !confirm_danger(&profile)? {
-        println!("Aborted by user.");
-        return Ok(());
-    }
+    ensure_allowed(&profile, PolicyAction::ConfigApply)?;
     let config = config_store
         .get(&args.config_id)?
-        .ok_or_else(|| anyhow!("config set not found: {}", args.config_id))?;
+        .ok_or_else(|| exit::not_found(format!("config set not found: {}", args.config_id)))?;
 
     let started = Instant::now();
     let via = TransferVia::parse(&args.via)?;
-    let allow_insecure_transfers = settings::get_allow_insecure_transfers(profile_store.conn())?;
+    let allow_insecure_transfers =
+        settings::get_allow_insecure_transfers_for(profile_store.conn(), &profile.profile_id)?;
     ensure_insecure_allowed(via, allow_insecure_transfers, args.i_know_its_insecure)?;
-    let ssh = resolve_client_for(
-        ClientKind::Ssh,
-        profile.client_overrides.as_ref(),
-        &profile_store,
-    )?;
-    let transfer_client = resolve_client_for(
-        via.client_kind(),
-        profile.client_overrides.as_ref(),
-        &profile_store,
-    )?;
-    let auth = ssh_auth_context(profile_store.conn())?;
+    let ssh = resolve_client_for(ClientKind::Ssh, &profile, &profile_store)?;
+    let transfer_client = resolve_client_for(via.client_kind(), &profile, &profile_store)?;
+    let auth = ssh_profile_auth_context(profile_store.conn(), &profile)?;
     emit_ssh_auth_messages(&auth);
 
     let needs_home = config.files.iter().any(|file| file.dest.starts_with("~/"));
@@ -1464,7 +3319,10 @@ fn handle_config_apply(args: ConfigApplyArgs) -> Result<()> {
     for file in &config.files {
         let local_path = PathBuf::from(&file.src);
         if !local_path.exists() {
-            return Err(anyhow!("local file not found: {}", local_path.display()));
+            return Err(exit::not_found(format!(
+                "local file not found: {}",
+                local_path.display()
+            )));
         }
         let dest = resolve_remote_dest(&file.dest, remote_home.as_deref())?;
         let status = remote_file_status(&ssh, &profile, &auth, &dest, file.when)?;
@@ -1524,10 +3382,10 @@ fn handle_config_apply(args: ConfigApplyArgs) -> Result<()> {
             args.i_know_its_insecure,
         )?;
         if !transfer.ok {
-            return Err(anyhow!(
+            return Err(exit::remote_failure(format!(
                 "config apply transfer failed with exit code {}",
                 transfer.exit_code
-            ));
+            )));
         }
         run_remote_command(
             &ssh,
@@ -1590,19 +3448,19 @@ fn parse_config_file_spec(spec: &str) -> Result<NewConfigFile> {
         let key = parts.next().unwrap_or("").trim();
         let value = parts
             .next()
-            .ok_or_else(|| anyhow!("invalid file spec (missing '='): {spec}"))?
+            .ok_or_else(|| exit::invalid(format!("invalid file spec (missing '='): {spec}")))?
             .trim();
         match key {
             "src" => src = Some(value.to_string()),
             "dest" => dest = Some(value.to_string()),
             "mode" => mode = Some(value.to_string()),
             "when" => when = ConfigFileWhen::parse(value)?,
-            _ => return Err(anyhow!("unknown file spec key: {key}")),
+            _ => return Err(exit::invalid(format!("unknown file spec key: {key}"))),
         }
     }
 
-    let src = src.ok_or_else(|| anyhow!("file spec missing src: {spec}"))?;
-    let dest = dest.ok_or_else(|| anyhow!("file spec missing dest: {spec}"))?;
+    let src = src.ok_or_else(|| exit::invalid(format!("file spec missing src: {spec}")))?;
+    let dest = dest.ok_or_else(|| exit::invalid(format!("file spec missing dest: {spec}")))?;
     Ok(NewConfigFile {
         src,
         dest,
@@ -1613,7 +3471,7 @@ fn parse_config_file_spec(spec: &str) -> Result<NewConfigFile> {
 
 fn resolve_remote_dest(dest: &str, remote_home: Option<&str>) -> Result<String> {
     if dest.starts_with("~/") {
-        let home = remote_home.ok_or_else(|| anyhow!("remote home not resolved"))?;
+        let home = remote_home.ok_or_else(|| exit::remote_failure("remote home not resolved"))?;
         let suffix = dest.trim_start_matches("~/");
         Ok(format!("{home}/{suffix}"))
     } else {
@@ -1637,6 +3495,10 @@ fn ssh_auth_context(conn: &Connection) -> Result<SshAuthContext> {
     ssh::ssh_auth_context(conn).map_err(Into::into)
 }
 
+fn ssh_profile_auth_context(conn: &Connection, profile: &Profile) -> Result<SshAuthContext> {
+    ssh::ssh_profile_auth_context(conn, &profile.profile_id).map_err(Into::into)
+}
+
 fn emit_ssh_auth_messages(auth: &SshAuthContext) {
     if let Some(hint) = &auth.hint {
         eprintln!("{hint}");
@@ -1710,7 +3572,9 @@ fn remote_exists(ssh: &Path, profile: &Profile, auth: &SshAuthContext, dest: &st
     match stdout.as_str() {
         "EXISTS" => Ok(true),
         "MISSING" => Ok(false),
-        _ => Err(anyhow!("unexpected exists response: {stdout}")),
+        _ => Err(exit::remote_failure(format!(
+            "unexpected exists response: {stdout}"
+        ))),
     }
 }
 
@@ -1746,7 +3610,7 @@ fn fetch_remote_home(ssh: &Path, profile: &Profile, auth: &SshAuthContext) -> Re
     let output = run_remote_command(ssh, profile, auth, "printf %s \"$HOME\"")?;
     let home = String::from_utf8_lossy(&output.stdout).trim().to_string();
     if home.is_empty() {
-        return Err(anyhow!("failed to resolve remote home"));
+        return Err(exit::remote_failure("failed to resolve remote home"));
     }
     Ok(home)
 }
@@ -1771,11 +3635,13 @@ fn run_remote_command(
         Ok(output)
     } else {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(anyhow!("remote command failed: {stderr}"))
+        Err(exit::remote_failure(format!(
+            "remote command failed: {stderr}"
+        )))
     }
 }
 
-fn shell_quote(value: &str) -> String {
+pub(crate) fn shell_quote(value: &str) -> String {
     let escaped = value.replace('\'', "'\\''");
     format!("'{escaped}'")
 }
@@ -1789,37 +3655,40 @@ fn sha256_file(path: &Path) -> Result<String> {
         .context("failed to execute sha256sum")?;
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow!("sha256sum failed: {stderr}"));
+        return Err(exit::remote_failure(format!("sha256sum failed: {stderr}")));
     }
     let stdout = String::from_utf8_lossy(&output.stdout);
     let hash = stdout
         .split_whitespace()
         .next()
-        .ok_or_else(|| anyhow!("sha256sum output missing hash"))?;
+        .ok_or_else(|| exit::remote_failure("sha256sum output missing hash"))?;
     Ok(hash.to_string())
 }
 
 fn handle_exec(
-    profile_id: String,
+    profile_id: Option<String>,
     timeout_ms: Option<u64>,
     json_output: bool,
     parser: Option<String>,
     cmd: Vec<String>,
 ) -> Result<()> {
     if cmd.is_empty() {
-        return Err(anyhow!("no command provided; pass after --"));
+        return Err(exit::invalid("no command provided; pass after --"));
     }
     let store = ProfileStore::new(db::init_connection()?);
+    let profile_id = profile_id_or_pick(&store, profile_id)?;
     let profile = store
         .get(&profile_id)?
-        .ok_or_else(|| anyhow!("profile not found: {profile_id}"))?;
+        .ok_or_else(|| exit::not_found(format!("profile not found: {profile_id}")))?;
     if profile.profile_type != ProfileType::Ssh {
-        return Err(anyhow!("exec only supports SSH profiles for now"));
-    }
-    if profile.danger_level == DangerLevel::Critical && !confirm_danger(&profile)? {
-        println!("Aborted by user.");
-        return Ok(());
+        return Err(exit::invalid("exec only supports SSH profiles for now"));
     }
+    ensure_allowed(
+        &profile,
+        PolicyAction::Exec {
+            command: &cmd.join(" "),
+        },
+    )?;
 
     let invocation = ssh::build_ssh_invocation(
         &store,
@@ -1837,17 +3706,34 @@ fn handle_exec(
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
 
-    let started = Instant::now();
-    let output = match timeout_ms {
-        Some(ms) => run_with_timeout(command, Duration::from_millis(ms))
-            .map_err(|e| anyhow!("exec timed out after {ms}ms: {e}"))?,
-        None => command.output().context("failed to execute ssh")?,
+    let cmdset_store = CmdSetStore::new(db::init_connection()?);
+    let context = HookContext {
+        op: "exec",
+        profile: &profile,
+        cmdset_id: None,
     };
-    let duration_ms = started.elapsed().as_millis() as i64;
+    let ((output, duration_ms), failed_hooks) = hooks::around(
+        &store,
+        &cmdset_store,
+        &context,
+        || -> Result<_> {
+            let started = Instant::now();
+            let output = match timeout_ms {
+                Some(ms) => run_with_timeout(command, Duration::from_millis(ms)).map_err(|e| {
+                    exit::remote_failure(format!("exec timed out after {ms}ms: {e}"))
+                })?,
+                None => command.output().context("failed to execute ssh")?,
+            };
+            Ok((output, started.elapsed().as_millis() as i64))
+        },
+        |(output, _)| output.status.success(),
+    )?;
+    warn_hook_failures(&failed_hooks);
     let exit_code = output.status.code().unwrap_or_default();
     let ok = output.status.success();
 
     store.touch_last_used(&profile.profile_id)?;
+    let cmd_line = cmd.join(" ");
     let entry = oplog::OpLogEntry {
         op: "exec".into(),
         profile_id: Some(profile.profile_id.clone()),
@@ -1855,19 +3741,37 @@ fn handle_exec(
         ok,
         exit_code: Some(exit_code),
         duration_ms: Some(duration_ms),
-        meta_json: None,
+        meta_json: Some(serde_json::json!({
+            "command_sha256": remote_script::sha256_hex(cmd_line.as_bytes()),
+        })),
     };
-    oplog::log_operation(store.conn(), entry)?;
+    let op_log_id = oplog::log_operation(store.conn(), entry)?;
+    let (stdout, stderr) = (
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr),
+    );
+    if settings::get_run_store_outputs(store.conn(), &profile.profile_id)? {
+        run_output::record(
+            store.conn(),
+            &NewRunOutput {
+                profile_id: &profile.profile_id,
+                source: "exec",
+                ord: None,
+                cmd: &cmd_line,
+                exit_code: Some(exit_code),
+                stdout: &stdout,
+                stderr: &stderr,
+                op_log_id: Some(op_log_id),
+            },
+        )?;
+    }
 
     if json_output {
         let stdout_text = String::from_utf8_lossy(&output.stdout);
         let parsed = if let Some(parser_spec) = parser {
             let spec = tdcore::parser::ParserSpec::parse(&parser_spec)?;
             let parser_def = match &spec {
-                tdcore::parser::ParserSpec::Regex(id) => {
-                    let cmdset_store = CmdSetStore::new(db::init_connection()?);
-                    cmdset_store.get_parser(id)?
-                }
+                tdcore::parser::ParserSpec::Regex(id) => cmdset_store.get_parser(id)?,
                 _ => None,
             };
             parse_output(&spec, &stdout_text, parser_def.as_ref())?
@@ -1888,171 +3792,895 @@ fn handle_exec(
         io::stdout().write_all(&output.stdout)?;
         io::stderr().write_all(&output.stderr)?;
         if !ok {
-            return Err(anyhow!("ssh exited with code {exit_code}"));
+            return Err(exit::remote_failure(format!(
+                "ssh exited with code {exit_code}"
+            )));
         }
     }
     Ok(())
 }
 
-fn handle_run(profile_id: String, cmdset_id: String, json_output: bool) -> Result<()> {
-    let profile_store = ProfileStore::new(db::init_connection()?);
-    let cmdset_store = CmdSetStore::new(db::init_connection()?);
-    let profile = profile_store
-        .get(&profile_id)?
-        .ok_or_else(|| anyhow!("profile not found: {profile_id}"))?;
-    if profile.profile_type != ProfileType::Ssh {
-        return Err(anyhow!("run only supports SSH profiles for now"));
+fn handle_search_output(args: SearchOutputArgs) -> Result<()> {
+    if args.query.trim().is_empty() {
+        return Err(exit::invalid("search query must not be empty"));
     }
-    if profile.danger_level == DangerLevel::Critical && !confirm_danger(&profile)? {
-        println!("Aborted by user.");
+    let since_ms = args
+        .since
+        .as_deref()
+        .map(|raw| parse_age_ms("--since", raw))
+        .transpose()?
+        .map(|age| now_ms() - age);
+    let conn = db::init_connection()?;
+    let matches = run_output::search(
+        &conn,
+        &run_output::OutputSearch {
+            query: &args.query,
+            since_ms,
+            profile_id: args.profile.as_deref(),
+            limit: args.limit,
+        },
+    )?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&matches)?);
+        return Ok(());
+    }
+    if matches.is_empty() {
+        println!("No stored outputs match (outputs are kept only when run.store_outputs is true).");
         return Ok(());
     }
+    let offset = settings::get_display_offset(&conn)?;
+    output::page(|out| {
+        for found in &matches {
+            let profile = found.profile_id.as_deref().unwrap_or("-");
+            let step = found
+                .ord
+                .map(|ord| format!(" step {ord}"))
+                .unwrap_or_default();
+            let exit_code = found
+                .exit_code
+                .map(|code| code.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            writeln!(
+                out,
+                "{}  {}  {}{step}  exit={exit_code}  {}",
+                util::format_timestamp_ms(found.ts, offset),
+                output::paint(profile, Tone::Highlight),
+                found.source,
+                found.cmd
+            )?;
+            for line in &found.lines {
+                writeln!(out, "    {line}")?;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+fn handle_exec_script(args: ExecScriptArgs) -> Result<()> {
+    if !args.script.is_file() {
+        return Err(exit::not_found(format!(
+            "script not found: {}",
+            args.script.display()
+        )));
+    }
+    let store = ProfileStore::new(db::init_connection()?);
+    let profile = store
+        .get(&args.profile_id)?
+        .ok_or_else(|| exit::not_found(format!("profile not found: {}", args.profile_id)))?;
+    if profile.profile_type != ProfileType::Ssh {
+        return Err(exit::invalid("exec-script only supports SSH profiles"));
+    }
+    ensure_allowed(&profile, PolicyAction::Script)?;
     let invocation = ssh::build_ssh_invocation(
-        &profile_store,
+        &store,
         SshInvocationRequest {
-            profile_id: &profile_id,
+            profile_id: &args.profile_id,
             source: "cli",
-            mode: SshInvocationMode::CommandSet,
+            mode: SshInvocationMode::Exec,
         },
     )?;
     emit_ssh_auth_messages(&invocation.auth_context);
-    let result = run_cmdset_ssh(
-        &profile_store,
-        &cmdset_store,
-        CmdSetRunRequest {
-            profile_id: &profile_id,
-            cmdset_id: &cmdset_id,
+    let scp = resolve_client_for(ClientKind::Scp, &profile, &store)?;
+    let tty = args.tty || (!args.no_tty && io::stdin().is_terminal() && io::stdout().is_terminal());
+    let result = remote_script::run_script(
+        &store,
+        ScriptRunRequest {
+            profile_id: &args.profile_id,
+            script: &args.script,
+            args: args.args.as_deref(),
             ssh: &invocation.client_path,
             ssh_auth_args: &invocation.auth_context.args,
-        },
-        |step| -> tdcore::error::Result<()> {
-            if !json_output {
-                io::stdout().write_all(step.stdout.as_bytes())?;
-                io::stderr().write_all(step.stderr.as_bytes())?;
-            }
-            Ok(())
+            scp: &scp,
+            tty,
         },
     )?;
-
-    if json_output {
-        let json = serde_json::json!({
-            "ok": result.ok,
-            "exit_code": result.exit_code,
-            "stdout": result.stdout,
-            "stderr": result.stderr,
-            "duration_ms": result.duration_ms,
-            "parsed": {
-                "steps": result.steps,
-            }
-        });
-        println!("{}", serde_json::to_string_pretty(&json)?);
-        return Ok(());
+    info!(
+        "script executed: profile={} sha256={} exit_code={}",
+        args.profile_id, result.script_sha256, result.exit_code
+    );
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        eprintln!("script sha256: {}", result.script_sha256);
     }
-
     if !result.ok {
-        return Err(anyhow!("run failed with exit code {}", result.exit_code));
+        return Err(exit::remote_failure(format!(
+            "script exited with code {}",
+            result.exit_code
+        )));
     }
     Ok(())
 }
 
-fn handle_connect(args: ConnectArgs) -> Result<()> {
-    let store = ProfileStore::new(db::init_connection()?);
-    let profile_id = args.profile_id;
-    let profile = store
-        .get(&profile_id)?
-        .ok_or_else(|| anyhow!("profile not found: {profile_id}"))?;
-    if profile.danger_level == DangerLevel::Critical && !confirm_danger(&profile)? {
-        println!("Aborted by user.");
-        return Ok(());
+fn handle_run(args: RunArgs) -> Result<()> {
+    let profile_store = ProfileStore::new(db::init_connection()?);
+    let cmdset_store = CmdSetStore::new(db::init_connection()?);
+    let (profiles, cmdset_id) = run_targets(&profile_store, &args)?;
+    if cmdset_store.get(&cmdset_id)?.is_none() {
+        return Err(exit::not_found(format!("unknown command set: {cmdset_id}")));
+    }
+    let json_output = args.json;
+    // Every policy, maintenance, and guard check is answered before the first step runs,
+    // so a refusal on one target never leaves the others half done.
+    let mut force_guards = Vec::with_capacity(profiles.len());
+    for profile in &profiles {
+        ensure_allowed(
+            profile,
+            PolicyAction::Run {
+                cmdset_id: &cmdset_id,
+                commands: &cmdset_commands(&cmdset_store, &cmdset_id)?,
+            },
+        )?;
+        force_guards.push(
+            args.force
+                && confirm_guard_override(&profile_store, profile, &cmdset_store, &cmdset_id)?,
+        );
     }
-    let log_backend = parse_connect_log_backend(args.log_backend)?;
-    let initial_send = args.initial_send.or_else(|| profile.initial_send.clone());
-    match profile.profile_type {
-        ProfileType::Ssh => {
-            let invocation = ssh::build_ssh_invocation(
-                &store,
-                SshInvocationRequest {
-                    profile_id: &profile.profile_id,
-                    source: "cli",
-                    mode: SshInvocationMode::Interactive,
-                },
-            )?;
-            emit_ssh_auth_messages(&invocation.auth_context);
-            connect_ssh(&store, invocation, log_backend)
+    let env = secret_run_env(&cmdset_store, &cmdset_id, &args.secret_env)?;
+    let mut results = Vec::with_capacity(profiles.len());
+    for (profile, force_guard) in profiles.iter().zip(force_guards) {
+        if profiles.len() > 1 && !json_output {
+            println!(
+                "{}",
+                output::paint(&format!("== {} ==", profile.profile_id), Tone::Highlight)
+            );
         }
-        ProfileType::Telnet => {
-            reject_non_ssh_log_backend(log_backend)?;
-            let telnet = resolve_client_for(
-                ClientKind::Telnet,
-                profile.client_overrides.as_ref(),
-                &store,
-            )?;
-            connect_telnet(&store, profile, telnet, initial_send)
+        let result = run_on_profile(
+            &profile_store,
+            &cmdset_store,
+            profile,
+            &cmdset_id,
+            force_guard,
+            &env,
+            json_output,
+        );
+        results.push((profile, result));
+    }
+    if let Some(format) = args.report {
+        let runs = results
+            .iter()
+            .map(|(profile, result)| match result {
+                Ok(result) => ProfileRun::from_result(&profile.profile_id, result.clone()),
+                Err(err) => ProfileRun::from_error(&profile.profile_id, format!("{err:#}")),
+            })
+            .collect();
+        let conn = profile_store.conn();
+        let path = RunReport::new(&cmdset_id, runs).write(
+            &settings::get_reports_dir(conn)?,
+            format.into(),
+            settings::get_display_offset(conn)?,
+        )?;
+        eprintln!("report: {}", path.display());
+    }
+
+    if results.len() == 1 {
+        let result = results.pop().map(|(_, result)| result).expect("one run")?;
+        if json_output {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&run_result_json(&result))?
+            );
+            return Ok(());
         }
-        ProfileType::Serial => {
-            reject_non_ssh_log_backend(log_backend)?;
-            connect_serial(&store, profile, initial_send)
+        if !result.ok {
+            return Err(exit::remote_failure(format!(
+                "run failed with exit code {}",
+                result.exit_code
+            )));
         }
+        return Ok(());
     }
-}
 
-fn parse_connect_log_backend(
-    raw: Option<String>,
-) -> Result<Option<session_log::SessionLogBackendSetting>> {
-    let Some(raw) = raw else {
-        return Ok(None);
+    let failed: Vec<&str> = results
+        .iter()
+        .filter(|(_, result)| !result.as_ref().is_ok_and(|result| result.ok))
+        .map(|(profile, _)| profile.profile_id.as_str())
+        .collect();
+    if json_output {
+        let runs: Vec<serde_json::Value> = results
+            .iter()
+            .map(|(profile, result)| {
+                let mut run = match result {
+                    Ok(result) => run_result_json(result),
+                    Err(err) => serde_json::json!({ "ok": false, "error": format!("{err:#}") }),
+                };
+                run["profile_id"] = profile.profile_id.clone().into();
+                run
+            })
+            .collect();
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "cmdset_id": cmdset_id,
+                "ok": failed.is_empty(),
+                "runs": runs,
+            }))?
+        );
+    } else {
+        for (profile, result) in &results {
+            let status = match result {
+                Ok(result) if result.ok => output::paint("ok", Tone::Ok),
+                Ok(result) => output::paint(&format!("exit {}", result.exit_code), Tone::Fail),
+                Err(err) => output::paint(&format!("{err:#}"), Tone::Fail),
+            };
+            println!("{}: {status}", profile.profile_id);
+        }
+    }
+    match failed.len() {
+        0 => Ok(()),
+        n if n == results.len() => Err(exit::remote_failure(format!(
+            "run failed on every profile: {}",
+            failed.join(", ")
+        ))),
+        n => Err(exit::partial_failure(format!(
+            "run failed on {n} of {} profiles: {}",
+            results.len(),
+            failed.join(", ")
+        ))),
+    }
+}
+
+/// The profiles and CommandSet of a `td run`: `<profile> <cmdset>`, or `<cmdset>` with the
+/// union of `--profile` ids and the SSH profiles matching `--group`/`--tag`, in that order.
+fn run_targets(store: &ProfileStore, args: &RunArgs) -> Result<(Vec<Profile>, String)> {
+    let by_selector = !args.profiles.is_empty() || !args.tag.is_empty() || args.group.is_some();
+    let (profile_ids, cmdset_id) = match (by_selector, args.targets.as_slice()) {
+        (false, [profile_id, cmdset_id]) => (vec![profile_id.clone()], cmdset_id.clone()),
+        (true, [cmdset_id]) => (args.profiles.clone(), cmdset_id.clone()),
+        (false, _) => return Err(exit::invalid("run needs a profile id and a CommandSet id")),
+        (true, _) => {
+            return Err(exit::invalid(
+                "with --profile/--tag/--group, pass only the CommandSet id",
+            ))
+        }
     };
-    let backend = session_log::SessionLogBackendSetting::parse(&raw)?;
-    if backend != session_log::SessionLogBackendSetting::Conpty {
-        return Err(anyhow!(
-            "--log-backend currently supports only explicit conpty"
-        ));
+    let mut profiles: Vec<Profile> = Vec::new();
+    for profile_id in &profile_ids {
+        let profile = store
+            .get(profile_id)?
+            .ok_or_else(|| exit::not_found(format!("profile not found: {profile_id}")))?;
+        if profile.profile_type != ProfileType::Ssh {
+            return Err(exit::invalid("run only supports SSH profiles for now"));
+        }
+        profiles.push(profile);
+    }
+    if !args.tag.is_empty() || args.group.is_some() {
+        let matched = store
+            .list_filtered(&ProfileFilters {
+                group: args.group.clone(),
+                tags: args.tag.clone(),
+                ..ProfileFilters::default()
+            })?
+            .into_iter()
+            .filter(|profile| profile.profile_type == ProfileType::Ssh)
+            .collect::<Vec<_>>();
+        if matched.is_empty() {
+            return Err(exit::not_found("no SSH profiles match the given tag/group"));
+        }
+        profiles.extend(matched);
     }
-    Ok(Some(backend))
+    let mut seen = HashSet::new();
+    profiles.retain(|profile| seen.insert(profile.profile_id.clone()));
+    Ok((profiles, cmdset_id))
 }
 
-fn reject_non_ssh_log_backend(
-    backend: Option<session_log::SessionLogBackendSetting>,
-) -> Result<()> {
-    if backend.is_some() {
-        Err(anyhow!("--log-backend only supports SSH profiles"))
-    } else {
-        Ok(())
-    }
+/// One profile of a `td run`, with its hooks and notification; step output is streamed
+/// unless the caller prints JSON.
+fn run_on_profile(
+    profile_store: &ProfileStore,
+    cmdset_store: &CmdSetStore,
+    profile: &Profile,
+    cmdset_id: &str,
+    force_guard: bool,
+    env: &[RunEnvVar],
+    json_output: bool,
+) -> Result<CmdSetRunResult> {
+    let context = HookContext {
+        op: "run",
+        profile,
+        cmdset_id: Some(cmdset_id),
+    };
+    let result = hooks::around(
+        profile_store,
+        cmdset_store,
+        &context,
+        || {
+            run_profile_cmdset(
+                profile_store,
+                cmdset_store,
+                profile,
+                cmdset_id,
+                "cli",
+                None,
+                force_guard,
+                env,
+                |step| -> tdcore::error::Result<()> {
+                    if !json_output {
+                        io::stdout().write_all(step.stdout.as_bytes())?;
+                        io::stderr().write_all(step.stderr.as_bytes())?;
+                    }
+                    Ok(())
+                },
+            )
+        },
+        |result| result.ok,
+    )
+    .map(|(result, failed)| {
+        warn_hook_failures(&failed);
+        result
+    });
+    let notification = Notification::run(
+        &profile.profile_id,
+        cmdset_id,
+        result.as_ref().map_err(|err| format!("{err:#}")),
+    );
+    warn_notify_failures(
+        notify::dispatch(profile_store.conn(), &notification)
+            .unwrap_or_else(|err| vec![format!("notification skipped: {err}")]),
+    );
+    result
 }
 
-fn handle_recent(limit: usize, json: bool) -> Result<()> {
-    if limit == 0 {
-        return Err(anyhow!("--limit must be greater than 0"));
+/// For `td run --force`: shows the steps `guard.commands` blocks, asks for a typed code,
+/// and logs the override. Returns whether the guard has to be bypassed at all.
+fn confirm_guard_override(
+    profile_store: &ProfileStore,
+    profile: &Profile,
+    cmdset_store: &CmdSetStore,
+    cmdset_id: &str,
+) -> Result<bool> {
+    let steps = cmdset_store.list_steps(cmdset_id)?;
+    let violations = guard::violations(profile_store.conn(), &profile.profile_id, &steps)?;
+    if violations.is_empty() {
+        return Ok(false);
     }
-    let conn = db::init_connection()?;
-    let recent = oplog::recent_ssh_sessions(&conn, limit)?;
-    if json {
-        println!("{}", serde_json::to_string_pretty(&recent)?);
-        return Ok(());
+    if !io::stdin().is_terminal() {
+        return Err(exit::policy_denied(
+            "--force needs a terminal to confirm the command guard override",
+        ));
     }
-    if recent.is_empty() {
-        println!("(no recent SSH sessions)");
-        return Ok(());
+    println!("The command guard blocks these steps of {cmdset_id}:");
+    for violation in &violations {
+        println!("  {violation}");
     }
-    println!(
-        "{:<16} {:<20} {:<28} {:<6} {:<8} {:<20} status",
-        "profile_id", "name", "endpoint", "type", "danger", "last_connected"
+    let code = util::confirmation_token();
+    print!(
+        "Type '{code}' to run them on {} anyway (the override is logged): ",
+        profile.profile_id
     );
-    for item in recent {
-        let endpoint = format!("{}@{}:{}", item.user, item.host, item.port);
-        println!(
-            "{:<16} {:<20} {:<28} {:<6} {:<8} {:<20} {}",
-            item.profile_id,
-            item.name,
-            endpoint,
-            item.profile_type,
-            item.danger_level,
-            format_unix_ms_utc(item.last_connected_at),
-            format_recent_status(item.last_ok, item.last_exit_code.as_ref())
-        );
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if !input.trim().eq_ignore_ascii_case(&code) {
+        println!("Aborted by user.");
+        return Err(guard::denied(&violations).into());
     }
-    Ok(())
+    guard::record_override(
+        profile_store.conn(),
+        &profile.profile_id,
+        cmdset_id,
+        &violations,
+    )?;
+    Ok(true)
+}
+
+/// Runs `cmdset_id` on an SSH profile whose danger confirmation the caller handled;
+/// `force_guard` is only set after [`confirm_guard_override`].
+#[allow(clippy::too_many_arguments)]
+fn run_profile_cmdset(
+    profile_store: &ProfileStore,
+    cmdset_store: &CmdSetStore,
+    profile: &Profile,
+    cmdset_id: &str,
+    source: &str,
+    events: Option<&RunEventBus>,
+    force_guard: bool,
+    env: &[RunEnvVar],
+    on_step: impl FnMut(&CmdStepRunResult) -> tdcore::error::Result<()>,
+) -> Result<CmdSetRunResult> {
+    let invocation = ssh::build_ssh_invocation(
+        profile_store,
+        SshInvocationRequest {
+            profile_id: &profile.profile_id,
+            source,
+            mode: SshInvocationMode::CommandSet,
+        },
+    )?;
+    emit_ssh_auth_messages(&invocation.auth_context);
+    // Only needed when the CommandSet stages files; the runner reports a missing client.
+    let scp = resolve_client_for(ClientKind::Scp, profile, profile_store).ok();
+    let ssh_password = native_ssh_password(profile_store, profile)?;
+    Ok(run_cmdset_ssh(
+        profile_store,
+        cmdset_store,
+        CmdSetRunRequest {
+            profile_id: &profile.profile_id,
+            cmdset_id,
+            ssh: &invocation.client_path,
+            ssh_auth_args: &invocation.auth_context.args,
+            scp: scp.as_deref(),
+            ssh_password: ssh_password.as_deref().map(String::as_str),
+            events,
+            force_guard,
+            env,
+        },
+        on_step,
+    )?)
+}
+
+/// Reveals the secrets the CommandSet's `vars.env_vars` references and the
+/// `td run --secret-env NAME=SECRET_ID` tokens, asking for the master password at most once.
+fn secret_run_env(
+    cmdset_store: &CmdSetStore,
+    cmdset_id: &str,
+    specs: &[String],
+) -> Result<Vec<RunEnvVar>> {
+    let Some(cmdset) = cmdset_store.get(cmdset_id)? else {
+        return Ok(Vec::new());
+    };
+    let needs_secrets = cmdset
+        .env_vars()?
+        .iter()
+        .any(|decl| matches!(decl.value, EnvValue::Secret(_)));
+    if specs.is_empty() && !needs_secrets {
+        return Ok(Vec::new());
+    }
+    let secrets = SecretStore::new(db::init_connection()?);
+    let master = load_master_prompt(&secrets)?;
+    let mut env = cmdset_runner::secret_env(&cmdset, &secrets, &master)?;
+    for spec in specs {
+        let (name, secret_id) = spec.split_once('=').ok_or_else(|| {
+            exit::invalid(format!("--secret-env expects NAME=SECRET_ID (got {spec})"))
+        })?;
+        let value = secrets.reveal_kind(&master, secret_id.trim(), SecretKind::ApiToken)?;
+        let var = RunEnvVar::new(name.trim(), value)?;
+        env.retain(|existing| existing.name != var.name);
+        env.push(var);
+    }
+    Ok(env)
+}
+
+/// The `td run --json` document.
+fn run_result_json(result: &CmdSetRunResult) -> serde_json::Value {
+    serde_json::json!({
+        "ok": result.ok,
+        "exit_code": result.exit_code,
+        "stdout": result.stdout,
+        "stderr": result.stderr,
+        "duration_ms": result.duration_ms,
+        "parsed": {
+            "steps": result.steps,
+        }
+    })
+}
+
+fn handle_tail(args: TailArgs) -> Result<()> {
+    let compile = |pattern: &str| {
+        Regex::new(pattern)
+            .map_err(|err| exit::invalid(format!("invalid regex '{pattern}': {err}")))
+    };
+    let grep = args.grep.as_deref().map(compile).transpose()?;
+    let highlight = args
+        .highlight
+        .iter()
+        .map(|pattern| compile(pattern))
+        .collect::<Result<Vec<_>>>()?;
+    let by_selector = !args.tag.is_empty() || args.group.is_some();
+    let (profile_id, path) = match (by_selector, args.targets.as_slice()) {
+        (false, [profile_id, path]) => (Some(profile_id.clone()), path.clone()),
+        (true, [path]) => (None, path.clone()),
+        (false, _) => return Err(exit::invalid("tail needs a profile id and a remote path")),
+        (true, _) => {
+            return Err(exit::invalid(
+                "with --tag/--group, pass only the remote path (no profile id)",
+            ))
+        }
+    };
+    let store = ProfileStore::new(db::init_connection()?);
+    let profiles = match profile_id {
+        Some(profile_id) => {
+            let profile = store
+                .get(&profile_id)?
+                .ok_or_else(|| exit::not_found(format!("profile not found: {profile_id}")))?;
+            ensure_ssh_profile(&profile, "tail")?;
+            vec![profile]
+        }
+        None => {
+            let profiles = store
+                .list_filtered(&ProfileFilters {
+                    group: args.group,
+                    tags: args.tag,
+                    ..ProfileFilters::default()
+                })?
+                .into_iter()
+                .filter(|profile| profile.profile_type == ProfileType::Ssh)
+                .collect::<Vec<_>>();
+            if profiles.is_empty() {
+                return Err(exit::not_found("no SSH profiles match the given tag/group"));
+            }
+            profiles
+        }
+    };
+    let mut targets = Vec::new();
+    for profile in profiles {
+        ensure_allowed(&profile, PolicyAction::Connect)?;
+        let invocation = ssh::build_ssh_invocation(
+            &store,
+            SshInvocationRequest {
+                profile_id: &profile.profile_id,
+                source: "cli",
+                mode: SshInvocationMode::Exec,
+            },
+        )?;
+        emit_ssh_auth_messages(&invocation.auth_context);
+        targets.push(tail::TailTarget {
+            profile,
+            invocation,
+        });
+    }
+    tail::run_tail(
+        &store,
+        &targets,
+        &tail::TailOptions {
+            path,
+            lines: args.lines,
+            follow: args.follow,
+            grep,
+            highlight,
+            output: args.output,
+        },
+    )
+}
+
+fn handle_connect(args: ConnectArgs) -> Result<()> {
+    let store = ProfileStore::new(db::init_connection()?);
+    let profile_id = profile_id_or_pick(&store, args.profile_id)?;
+    let profile = store
+        .get(&profile_id)?
+        .ok_or_else(|| exit::not_found(format!("profile not found: {profile_id}")))?;
+    if args.dry_run {
+        return print_connect_preview(&store, &profile);
+    }
+    ensure_allowed(&profile, PolicyAction::Connect)?;
+    ensure_preflight_passed(&store, &profile)?;
+    warn_hook_failures(&hooks::run(
+        &store,
+        &CmdSetStore::new(db::init_connection()?),
+        HookPoint::PreConnect,
+        &HookContext {
+            op: "connect",
+            profile: &profile,
+            cmdset_id: None,
+        },
+        None,
+    )?);
+    if profile.profile_type == ProfileType::Ssh {
+        autoload_agent_key(&store, &profile)?;
+    }
+    let log_backend = parse_connect_log_backend(args.log_backend)?;
+    // Held until the session ends; dropping it clears the password from the clipboard.
+    let _login_secret = copy_login_secret(&store, &profile)?;
+    let initial_send = args.initial_send.or_else(|| profile.initial_send.clone());
+    match profile.profile_type {
+        ProfileType::Ssh => {
+            let invocation = ssh::build_ssh_invocation(
+                &store,
+                SshInvocationRequest {
+                    profile_id: &profile.profile_id,
+                    source: "cli",
+                    mode: SshInvocationMode::Interactive,
+                },
+            )?;
+            emit_ssh_auth_messages(&invocation.auth_context);
+            connect_ssh(&store, invocation, log_backend)
+        }
+        ProfileType::Telnet => {
+            reject_non_ssh_log_backend(log_backend)?;
+            let telnet = resolve_client_for(ClientKind::Telnet, &profile, &store)?;
+            connect_telnet(&store, profile, telnet, initial_send)
+        }
+        ProfileType::Serial => {
+            reject_non_ssh_log_backend(log_backend)?;
+            connect_serial(&store, profile, initial_send)
+        }
+    }
+}
+
+/// `td connect --dry-run`: which client would run, where it came from, and its command line.
+/// Policy, preflight, and hooks are not evaluated.
+fn print_connect_preview(store: &ProfileStore, profile: &Profile) -> Result<()> {
+    warn_invalid_client_paths(profile.client_overrides.as_ref());
+    let kind = match profile.profile_type {
+        ProfileType::Ssh => ClientKind::Ssh,
+        ProfileType::Telnet => ClientKind::Telnet,
+        ProfileType::Serial => {
+            println!(
+                "serial port {} at {} baud (built in, no external client)",
+                profile.host, profile.port
+            );
+            return Ok(());
+        }
+    };
+    let resolved = ssh::resolve_client_source_for(kind, profile, store.conn())?;
+    let Some(client) = resolved.path else {
+        return Err(exit::not_found(format!(
+            "{} client not found (no usable override and not in PATH)",
+            kind.as_str()
+        )));
+    };
+    println!("client:  {} ({})", client.display(), resolved.source);
+    let command = if kind == ClientKind::Ssh {
+        let invocation = ssh::build_ssh_invocation(
+            store,
+            SshInvocationRequest {
+                profile_id: &profile.profile_id,
+                source: "cli",
+                mode: SshInvocationMode::Interactive,
+            },
+        )?;
+        ssh::format_command_line(&invocation.client_path, &invocation.args)
+    } else {
+        ssh::format_command_line(
+            &client,
+            &[profile.host.clone().into(), profile.port.to_string().into()],
+        )
+    };
+    println!("command: {command}");
+    Ok(())
+}
+
+/// Client override paths that are not files are skipped at connect time in favour of the
+/// global override or PATH; say so when they are saved or previewed.
+fn warn_invalid_client_paths(overrides: Option<&ClientOverrides>) {
+    for (kind, path) in overrides
+        .map(ClientOverrides::invalid_paths)
+        .unwrap_or_default()
+    {
+        eprintln!(
+            "{} {} override {path} is not a file; it will be ignored",
+            output::paint("warning:", Tone::Warn),
+            kind.as_str()
+        );
+    }
+}
+
+/// Copies the profile's linked `password` secret to the clipboard for the login prompt.
+/// The returned handle clears it after `clipboard.clear_after_secs` or when dropped.
+fn copy_login_secret(store: &ProfileStore, profile: &Profile) -> Result<Option<ClipboardExpiry>> {
+    let secrets = SecretStore::new(db::init_connection()?);
+    let Some(secret) = secrets.login_secret_for(&profile.profile_id)? else {
+        return Ok(None);
+    };
+    let Some(clipboard) = Clipboard::detect() else {
+        eprintln!(
+            "{} secret {} is linked to {}, but no clipboard tool was found (wl-copy, xclip, xsel, pbcopy, clip)",
+            output::paint("warning:", Tone::Warn),
+            secret.secret_id,
+            profile.profile_id
+        );
+        return Ok(None);
+    };
+    let master = load_master_prompt(&secrets)?;
+    let clear_after = settings::get_clipboard_clear_after(store.conn())?;
+    let expiry = secrets.copy_to_clipboard(
+        &master,
+        &secret.secret_id,
+        &clipboard,
+        "connect",
+        Some(&profile.profile_id),
+    )?;
+    eprintln!(
+        "password from secret {} ({}) copied to the clipboard{}",
+        secret.secret_id,
+        secret.label,
+        clear_after
+            .map(|after| format!("; it is cleared after {}s", after.as_secs()))
+            .unwrap_or_default()
+    );
+    Ok(expiry)
+}
+
+/// Keeps `td secret copy` running until its clear is due, with a countdown; any key,
+/// Ctrl+C included, clears the clipboard early. Returns whether it was cleared.
+fn wait_for_clipboard_clear(expiry: ClipboardExpiry) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Ok(expiry.wait());
+    }
+    let raw = RawModeGuard::enter()?;
+    let mut early = false;
+    while !expiry.is_done() {
+        eprint!(
+            "\rclearing in {}s (press any key to clear now) ",
+            expiry.remaining().as_secs() + 1
+        );
+        if crossterm::event::poll(Duration::from_millis(250))? {
+            if let crossterm::event::Event::Key(key) = crossterm::event::read()? {
+                if key.kind == crossterm::event::KeyEventKind::Press {
+                    early = true;
+                    break;
+                }
+            }
+        }
+    }
+    drop(raw);
+    eprintln!();
+    Ok(if early {
+        expiry.clear_now()
+    } else {
+        expiry.wait()
+    })
+}
+
+/// Reports advisory hook failures; blocking ones already stopped the command.
+fn warn_hook_failures(failed: &[HookRun]) {
+    for hook in failed {
+        eprintln!(
+            "{} {}",
+            output::paint("warning:", Tone::Warn),
+            hook.describe()
+        );
+    }
+}
+
+fn warn_notify_failures(failed: Vec<String>) {
+    for message in failed {
+        eprintln!("{} {message}", output::paint("warning:", Tone::Warn));
+    }
+}
+
+/// With `ssh.agent_autoload` on, loads the profile's `ssh.agent_key` into ssh-agent
+/// unless it is already there. Failures only warn: ssh can still fall back to other auth.
+fn autoload_agent_key(store: &ProfileStore, profile: &Profile) -> Result<()> {
+    let Some(key_path) = settings::get_ssh_agent_autoload_key(store.conn(), &profile.profile_id)?
+    else {
+        return Ok(());
+    };
+    if agent::status().auth_sock.is_none() {
+        eprintln!(
+            "ssh-agent: SSH_AUTH_SOCK is not set; not loading {}",
+            key_path.display()
+        );
+        return Ok(());
+    }
+    if agent::is_key_loaded(&key_path) == Some(true) {
+        return Ok(());
+    }
+    let secrets = SecretStore::new(db::init_connection()?);
+    let added = match secrets.linked_of_kind(&profile.profile_id, SecretKind::SshKeyPassphrase)? {
+        Some(secret) => {
+            let master = load_master_prompt(&secrets)?;
+            let passphrase =
+                secrets.reveal_kind(&master, &secret.secret_id, SecretKind::SshKeyPassphrase)?;
+            agent::run_add_with_passphrase(&key_path, &std::env::current_exe()?, &passphrase)
+        }
+        None => agent::run_add(&key_path),
+    };
+    match added {
+        Ok(output) if output.status.success() => {
+            info!("agent key loaded for profile {}", profile.profile_id);
+            eprintln!("ssh-agent: loaded {}", key_path.display());
+        }
+        Ok(output) => eprintln!(
+            "ssh-agent: failed to load {}: {}",
+            key_path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(err) => eprintln!("ssh-agent: failed to run ssh-add: {err}"),
+    }
+    Ok(())
+}
+
+/// With `connect.preflight` enabled, probes the host first and asks before launching a
+/// client that would otherwise hang on an unreachable host.
+fn ensure_preflight_passed(store: &ProfileStore, profile: &Profile) -> Result<()> {
+    if !settings::get_connect_preflight(store.conn(), &profile.profile_id)? {
+        return Ok(());
+    }
+    let Some(reason) = tester::preflight(profile) else {
+        return Ok(());
+    };
+    eprintln!(
+        "{} {}:{} looks unreachable ({reason})",
+        output::paint("warning:", Tone::Warn),
+        profile.host,
+        profile.port
+    );
+    eprint!("Launch anyway? [y/N]: ");
+    io::stderr().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if matches!(input.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+        Ok(())
+    } else {
+        Err(exit::remote_failure(format!("host unreachable: {reason}")))
+    }
+}
+
+fn parse_connect_log_backend(
+    raw: Option<String>,
+) -> Result<Option<session_log::SessionLogBackendSetting>> {
+    let Some(raw) = raw else {
+        return Ok(None);
+    };
+    let backend = session_log::SessionLogBackendSetting::parse(&raw)?;
+    if backend != session_log::SessionLogBackendSetting::Conpty {
+        return Err(exit::invalid(
+            "--log-backend currently supports only explicit conpty",
+        ));
+    }
+    Ok(Some(backend))
+}
+
+fn reject_non_ssh_log_backend(
+    backend: Option<session_log::SessionLogBackendSetting>,
+) -> Result<()> {
+    if backend.is_some() {
+        Err(exit::invalid("--log-backend only supports SSH profiles"))
+    } else {
+        Ok(())
+    }
+}
+
+fn handle_recent(limit: usize, json: bool) -> Result<()> {
+    if limit == 0 {
+        return Err(exit::invalid("--limit must be greater than 0"));
+    }
+    let conn = open_db(true)?;
+    let recent = oplog::recent_ssh_sessions(&conn, limit)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&recent)?);
+        return Ok(());
+    }
+    if recent.is_empty() {
+        println!("(no recent SSH sessions)");
+        return Ok(());
+    }
+    output::page(|out| {
+        writeln!(
+            out,
+            "{:<16} {:<20} {:<28} {:<6} {:<8} {:<20} status",
+            "profile_id", "name", "endpoint", "type", "danger", "last_connected"
+        )?;
+        for item in &recent {
+            let endpoint = format!("{}@{}:{}", item.user, item.host, item.port);
+            let status = format_recent_status(item.last_ok, item.last_exit_code.as_ref());
+            let status = output::paint(&status, if item.last_ok { Tone::Ok } else { Tone::Fail });
+            writeln!(
+                out,
+                "{:<16} {:<20} {:<28} {:<6} {} {:<20} {}",
+                item.profile_id,
+                item.name,
+                endpoint,
+                item.profile_type,
+                output::danger_cell(&item.danger_level, 8),
+                format_unix_ms_utc(item.last_connected_at),
+                status
+            )?;
+        }
+        Ok(())
+    })?;
+    Ok(())
 }
 
 fn handle_session(cmd: SessionCommands) -> Result<()> {
@@ -2081,7 +4709,9 @@ fn handle_session(cmd: SessionCommands) -> Result<()> {
             let conn = db::init_connection()?;
             let metadata = session_log::get_session_log(&conn, &session_id)?;
             let Some(log_path) = metadata.log_path else {
-                return Err(anyhow!("session has no terminal log path: {session_id}"));
+                return Err(exit::not_found(format!(
+                    "session has no terminal log path: {session_id}"
+                )));
             };
             println!("{}", log_path.display());
             Ok(())
@@ -2090,13 +4720,45 @@ fn handle_session(cmd: SessionCommands) -> Result<()> {
             let conn = db::init_connection()?;
             handle_session_prune(&conn, args)
         }
+        SessionCommands::Active { json } => {
+            let conn = db::init_connection()?;
+            let sessions = active_session::list(&conn)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&sessions)?);
+                return Ok(());
+            }
+            if sessions.is_empty() {
+                println!("(no active sessions)");
+                return Ok(());
+            }
+            println!("{:>5}  {:<16}  {:>8}  STARTED", "ID", "PROFILE", "PID");
+            for session in sessions {
+                println!(
+                    "{:>5}  {:<16}  {:>8}  {}",
+                    session.id,
+                    session.profile_id,
+                    session.pid,
+                    util::format_relative_ms(session.started_at, now_ms())
+                );
+            }
+            Ok(())
+        }
+        SessionCommands::Terminate { id } => {
+            let conn = db::init_connection()?;
+            let session = active_session::terminate(&conn, id)?;
+            println!(
+                "Ended session {id} on {} (pid {}).",
+                session.profile_id, session.pid
+            );
+            Ok(())
+        }
     }
 }
 
 #[cfg(not(windows))]
 fn handle_session_conpty_test(_store: &ProfileStore, _args: SessionConptyTestArgs) -> Result<()> {
-    Err(anyhow!(
-        "unsupported: ConPTY session logging is only available on Windows"
+    Err(exit::invalid(
+        "unsupported: ConPTY session logging is only available on Windows",
     ))
 }
 
@@ -2106,12 +4768,9 @@ fn handle_session_conpty_test(store: &ProfileStore, args: SessionConptyTestArgs)
     let profile_id = args.profile_id;
     let profile = store
         .get(&profile_id)?
-        .ok_or_else(|| anyhow!("profile not found: {profile_id}"))?;
+        .ok_or_else(|| exit::not_found(format!("profile not found: {profile_id}")))?;
     ensure_ssh_profile(&profile, "session conpty-test")?;
-    if profile.danger_level == DangerLevel::Critical && !confirm_danger(&profile)? {
-        println!("Aborted by user.");
-        return Ok(());
-    }
+    ensure_allowed(&profile, PolicyAction::Connect)?;
     let invocation = build_conpty_test_invocation(store, &profile_id)?;
     emit_ssh_auth_messages(&invocation.auth_context);
     println!("ConPTY session logging PoC is experimental.");
@@ -2184,9 +4843,9 @@ fn handle_session_conpty_test(store: &ProfileStore, args: SessionConptyTestArgs)
             if outcome.ok {
                 Ok(())
             } else if let Some(code) = outcome.exit_code {
-                Err(anyhow!("ssh exited with code {code}"))
+                Err(exit::remote_failure(format!("ssh exited with code {code}")))
             } else {
-                Err(anyhow!("ssh ended without exit code"))
+                Err(exit::remote_failure("ssh ended without exit code"))
             }
         }
         CliSshRunResult::LaunchFailed {
@@ -2215,6 +4874,7 @@ fn handle_session_conpty_test(store: &ProfileStore, args: SessionConptyTestArgs)
     }
 }
 
+#[cfg(any(windows, test))]
 fn build_conpty_test_invocation(store: &ProfileStore, profile_id: &str) -> Result<SshInvocation> {
     ssh::build_ssh_invocation(
         store,
@@ -2237,12 +4897,14 @@ fn teradock_debug_enabled() -> bool {
     }
 }
 
-fn conpty_debug(enabled: bool, message: impl Display) {
+#[cfg(windows)]
+fn conpty_debug(enabled: bool, message: impl std::fmt::Display) {
     if enabled {
         eprintln!("debug: {message}");
     }
 }
 
+#[cfg(any(windows, test))]
 fn conpty_startup_timeout(seconds: u64) -> Option<Duration> {
     (seconds > 0).then(|| Duration::from_secs(seconds))
 }
@@ -2366,7 +5028,7 @@ fn print_session_diagnostics(diagnostics: &session_log::SessionLogDiagnostics) {
 
 fn handle_session_list(conn: &Connection, args: SessionListArgs) -> Result<()> {
     if args.limit == 0 {
-        return Err(anyhow!("--limit must be greater than 0"));
+        return Err(exit::invalid("--limit must be greater than 0"));
     }
     let mut sessions = session_log::list_session_logs(conn)?;
     sessions.truncate(args.limit);
@@ -2549,10 +5211,10 @@ fn handle_session_show(conn: &Connection, args: SessionShowArgs) -> Result<()> {
 
     if let Some(tail) = args.tail {
         if tail == 0 {
-            return Err(anyhow!("--tail must be greater than 0"));
+            return Err(exit::invalid("--tail must be greater than 0"));
         }
         let Some(log_path) = metadata.log_path.as_ref() else {
-            return Err(anyhow!("session has no terminal log path"));
+            return Err(exit::not_found("session has no terminal log path"));
         };
         print_log_tail(log_path, tail)?;
     }
@@ -2561,7 +5223,7 @@ fn handle_session_show(conn: &Connection, args: SessionShowArgs) -> Result<()> {
 
 fn handle_session_prune(conn: &Connection, args: SessionPruneArgs) -> Result<()> {
     if args.keep_last == Some(0) {
-        return Err(anyhow!("--keep-last must be greater than 0"));
+        return Err(exit::invalid("--keep-last must be greater than 0"));
     }
     let older_than_ms = args
         .older_than
@@ -2605,7 +5267,7 @@ fn handle_session_prune(conn: &Connection, args: SessionPruneArgs) -> Result<()>
         } else {
             print_session_prune_plan(&plan, false);
         }
-        return Err(anyhow!(
+        return Err(exit::policy_denied(
             "refusing to delete session logs without --yes; rerun with --dry-run to preview or --yes to delete"
         ));
     }
@@ -2617,10 +5279,10 @@ fn handle_session_prune(conn: &Connection, args: SessionPruneArgs) -> Result<()>
             serde_json::to_string_pretty(&session_prune_report_json(&plan, &args, &report))?
         );
         if !report.failures.is_empty() {
-            return Err(anyhow!(
+            return Err(exit::partial_failure(format!(
                 "session prune completed with {} deletion failures",
                 report.failed_deletions
-            ));
+            )));
         }
         return Ok(());
     }
@@ -2648,10 +5310,10 @@ fn handle_session_prune(conn: &Connection, args: SessionPruneArgs) -> Result<()>
                 failure.error
             );
         }
-        return Err(anyhow!(
+        return Err(exit::partial_failure(format!(
             "session prune completed with {} deletion failures",
             report.failed_deletions
-        ));
+        )));
     }
     Ok(())
 }
@@ -2809,18 +5471,23 @@ fn print_session_prune_plan(plan: &session_log::SessionPrunePlan, dry_run: bool)
 }
 
 fn parse_prune_age_ms(raw: &str) -> Result<i64> {
+    parse_age_ms("--older-than", raw)
+}
+
+/// Parses an age such as `30d`, `12h`, `15m`, or `90s` given to `flag`.
+fn parse_age_ms(flag: &str, raw: &str) -> Result<i64> {
     let value = raw.trim();
     if value.len() < 2 {
-        return Err(anyhow!(
-            "--older-than must use a positive number with suffix d, h, m, or s"
-        ));
+        return Err(exit::invalid(format!(
+            "{flag} must use a positive number with suffix d, h, m, or s"
+        )));
     }
     let (digits, suffix) = value.split_at(value.len() - 1);
     let amount = digits
         .parse::<i64>()
-        .map_err(|_| anyhow!("invalid --older-than value: {raw}"))?;
+        .map_err(|_| exit::invalid(format!("invalid {flag} value: {raw}")))?;
     if amount <= 0 {
-        return Err(anyhow!("--older-than must be greater than 0"));
+        return Err(exit::invalid(format!("{flag} must be greater than 0")));
     }
     let multiplier = match suffix {
         "d" | "D" => 24_i64 * 60 * 60 * 1000,
@@ -2828,14 +5495,14 @@ fn parse_prune_age_ms(raw: &str) -> Result<i64> {
         "m" | "M" => 60_i64 * 1000,
         "s" | "S" => 1000,
         _ => {
-            return Err(anyhow!(
-                "--older-than must use suffix d, h, m, or s, for example 30d"
-            ))
+            return Err(exit::invalid(format!(
+                "{flag} must use suffix d, h, m, or s, for example 30d"
+            )))
         }
     };
     amount
         .checked_mul(multiplier)
-        .ok_or_else(|| anyhow!("--older-than value is too large"))
+        .ok_or_else(|| exit::invalid(format!("{flag} value is too large")))
 }
 
 fn session_capture_lines(metadata: &session_log::SessionLogMetadata) -> Vec<String> {
@@ -2898,10 +5565,16 @@ fn sanitize_conpty_log_bytes(raw: &[u8]) -> Vec<u8> {
     sanitized
 }
 
-fn handle_doctor(json: bool) -> Result<()> {
+fn handle_doctor(json: bool, fix: bool, yes: bool) -> Result<()> {
     let conn = db::init_connection()?;
     let global_overrides = settings::get_client_overrides(&conn)?;
-    let report = doctor::check_clients_with_overrides(None, global_overrides.as_ref());
+    let mut report = doctor::check_clients_with_overrides(None, global_overrides.as_ref());
+    let mut failed_fixes = 0;
+    if fix {
+        failed_fixes = apply_doctor_fixes(&conn, &report, yes)?;
+        let global_overrides = settings::get_client_overrides(&conn)?;
+        report = doctor::check_clients_with_overrides(None, global_overrides.as_ref());
+    }
     let meta_json = serde_json::to_value(&report)?;
     let entry = oplog::OpLogEntry {
         op: "doctor".into(),
@@ -2916,465 +5589,1972 @@ fn handle_doctor(json: bool) -> Result<()> {
 
     if json {
         println!("{}", serde_json::to_string_pretty(&report)?);
-        return Ok(());
-    }
-
-    println!("Doctor report:");
-    for client in &report.clients {
-        let path = client
-            .path
-            .as_ref()
-            .map(|value| value.display().to_string())
-            .unwrap_or_else(|| "MISSING".to_string());
-        println!("{:<6} {:<14} {}", client.name, client.source, path);
-    }
-    if let Some(sock) = &report.agent.auth_sock {
-        println!("SSH_AUTH_SOCK: {sock}");
     } else {
-        println!("SSH_AUTH_SOCK: (not set)");
+        print_doctor_report(&report)?;
     }
-    if let Some(count) = report.agent.key_count {
-        println!("ssh-agent keys: {count}");
+    // Warnings alone do not fail the command; errors exit 1 so scripts can gate on it.
+    if !report.errors.is_empty() {
+        return Err(exit::failure(format!(
+            "doctor found {} error(s)",
+            report.errors.len()
+        )));
     }
-    if let Some(error) = &report.agent.error {
-        println!("ssh-add: {error}");
+    if failed_fixes > 0 {
+        return Err(exit::failure(format!("{failed_fixes} fix(es) failed")));
     }
-    if !report.warnings.is_empty() {
-        println!("Warnings:");
-        for warning in &report.warnings {
-            println!("- {}: {}", warning.code, warning.message);
-        }
+    Ok(())
+}
+
+/// `td doctor --watch`: prints the first report in full, then a timestamped list of what
+/// changed whenever a later check differs. Only the first check goes to the op log.
+fn handle_doctor_watch(json: bool, interval: u64) -> Result<()> {
+    let conn = db::init_connection()?;
+    let offset = settings::get_display_offset(&conn)?;
+    let check = || -> Result<doctor::DoctorReport> {
+        let global_overrides = settings::get_client_overrides(&conn)?;
+        Ok(doctor::check_clients_with_overrides(
+            None,
+            global_overrides.as_ref(),
+        ))
+    };
+    let mut previous = check()?;
+    oplog::log_operation(
+        &conn,
+        oplog::OpLogEntry {
+            op: "doctor".into(),
+            profile_id: None,
+            client_used: None,
+            ok: previous.errors.is_empty()
+                && previous.clients.iter().all(|client| client.path.is_some()),
+            exit_code: None,
+            duration_ms: None,
+            meta_json: Some(serde_json::to_value(&previous)?),
+        },
+    )?;
+    if json {
+        println!("{}", serde_json::to_string(&previous)?);
+    } else {
+        print_doctor_report(&previous)?;
+        println!("Watching every {interval}s; only changes are printed. Press Ctrl-C to stop.");
     }
-    if !report.errors.is_empty() {
-        println!("Errors:");
-        for error in &report.errors {
-            println!("- {}: {}", error.code, error.message);
+    loop {
+        io::stdout().flush()?;
+        thread::sleep(Duration::from_secs(interval));
+        let current = check()?;
+        let changes = doctor::changes(&previous, &current);
+        previous = current;
+        if changes.is_empty() {
+            continue;
+        }
+        let at = now_ms();
+        if json {
+            let payload = serde_json::json!({ "at": at, "changes": changes });
+            println!("{}", serde_json::to_string(&payload)?);
+            continue;
+        }
+        println!("-- {} --", util::format_timestamp_ms(at, offset));
+        for change in &changes {
+            match (&change.before, &change.after) {
+                (Some(before), Some(after)) => {
+                    println!("{}: {before} -> {after}", change.item)
+                }
+                (None, Some(after)) => println!(
+                    "{} {}: {after}",
+                    output::paint("new", Tone::Warn),
+                    change.item
+                ),
+                (Some(before), None) => println!(
+                    "{} {}: {before}",
+                    output::paint("resolved", Tone::Ok),
+                    change.item
+                ),
+                (None, None) => {}
+            }
         }
     }
-    Ok(())
 }
 
-fn format_recent_status(ok: bool, exit_code: Option<&i32>) -> String {
-    match (ok, exit_code) {
-        (true, Some(code)) => format!("ok exit {code}"),
-        (false, Some(code)) => format!("failed exit {code}"),
-        (true, None) => "ok without exit code".to_string(),
-        (false, None) => "failed without exit code".to_string(),
+/// Applies the planned doctor fixes in order, asking before the ones that create keys or
+/// change services. Returns the number of fixes that failed.
+fn apply_doctor_fixes(
+    conn: &Connection,
+    report: &doctor::DoctorReport,
+    yes: bool,
+) -> Result<usize> {
+    let fixes = doctor::plan_fixes(report);
+    if fixes.is_empty() {
+        println!("Nothing to fix.");
+        return Ok(0);
+    }
+    let mut failed = 0;
+    for fix in &fixes {
+        if fix.needs_confirmation() && !yes && !confirm_doctor_fix(fix)? {
+            println!("skipped: {}", fix.description());
+            continue;
+        }
+        match doctor::apply_fix(conn, fix) {
+            Ok(summary) => println!("{} {summary}", output::paint("fixed:", Tone::Ok)),
+            Err(err) => {
+                failed += 1;
+                println!(
+                    "{} {}: {err}",
+                    output::paint("failed:", Tone::Fail),
+                    fix.description()
+                );
+            }
+        }
     }
+    println!();
+    Ok(failed)
 }
 
-fn format_unix_ms_utc(ts_ms: i64) -> String {
-    let secs = ts_ms.div_euclid(1000);
-    let Ok(dt) = OffsetDateTime::from_unix_timestamp(secs) else {
-        return ts_ms.to_string();
-    };
-    format!(
-        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
-        dt.year(),
-        u8::from(dt.month()),
-        dt.day(),
-        dt.hour(),
-        dt.minute(),
-        dt.second()
-    )
+fn confirm_doctor_fix(fix: &doctor::DoctorFix) -> Result<bool> {
+    println!("About to {}.", fix.description());
+    print!("Type 'yes' to continue: ");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().eq_ignore_ascii_case("yes"))
 }
 
-fn format_duration_ms(duration_ms: i64) -> String {
-    if duration_ms >= 1000 {
-        format!("{:.1}s", duration_ms as f64 / 1000.0)
-    } else {
-        format!("{duration_ms}ms")
-    }
+fn print_doctor_report(report: &doctor::DoctorReport) -> Result<()> {
+    output::page(|out| {
+        writeln!(out, "Doctor report:")?;
+        for client in &report.clients {
+            let path = match &client.path {
+                Some(value) => value.display().to_string(),
+                None => output::paint("MISSING", Tone::Fail),
+            };
+            writeln!(out, "{:<6} {:<14} {}", client.name, client.source, path)?;
+        }
+        if let Some(sock) = &report.agent.auth_sock {
+            writeln!(out, "SSH_AUTH_SOCK: {sock}")?;
+        } else {
+            writeln!(out, "SSH_AUTH_SOCK: (not set)")?;
+        }
+        if let Some(count) = report.agent.key_count {
+            writeln!(out, "ssh-agent keys: {count}")?;
+        }
+        if let Some(error) = &report.agent.error {
+            writeln!(out, "ssh-add: {error}")?;
+        }
+        if !report.warnings.is_empty() {
+            writeln!(out, "{}", output::paint("Warnings:", Tone::Warn))?;
+            for warning in &report.warnings {
+                writeln!(out, "- {}: {}", warning.code, warning.message)?;
+            }
+        }
+        if !report.errors.is_empty() {
+            writeln!(out, "{}", output::paint("Errors:", Tone::Fail))?;
+            for error in &report.errors {
+                writeln!(out, "- {}: {}", error.code, error.message)?;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(())
 }
 
-fn format_session_status(metadata: &session_log::SessionLogMetadata) -> String {
-    match metadata.exit_code {
-        Some(code) => format!("{} exit {}", metadata.status, code),
-        None => metadata.status.clone(),
+fn handle_schedule(cmd: ScheduleCommands) -> Result<()> {
+    let conn = db::init_connection()?;
+    let offset = settings::get_display_offset(&conn)?;
+    let when = |ts: Option<i64>| {
+        ts.map(|ts| util::format_timestamp_ms(ts, offset))
+            .unwrap_or_else(|| "-".to_string())
+    };
+    match cmd {
+        ScheduleCommands::Add(args) => {
+            let target = ScheduleTarget::parse(&args.target)?;
+            let profiles = target.resolve(&ProfileStore::new(db::init_connection()?))?;
+            let critical = profiles
+                .iter()
+                .filter(|profile| profile.danger_level == DangerLevel::Critical)
+                .collect::<Vec<_>>();
+            if args.allow_critical {
+                for profile in &critical {
+                    ensure_danger_confirmed(profile)?;
+                }
+            }
+            let created = schedule::add(
+                &conn,
+                NewSchedule {
+                    cmdset_id: args.cmdset_id,
+                    target,
+                    cron: args.cron,
+                    enabled: !args.disabled,
+                    allow_critical: args.allow_critical,
+                },
+                util::now_ms(),
+            )?;
+            println!(
+                "Added schedule {}: {} on {} ({}), next run {}",
+                created.id,
+                created.cmdset_id,
+                created.target,
+                created.cron,
+                when(created.next_run_at)
+            );
+            if profiles.is_empty() {
+                eprintln!("Warning: {} matches no SSH profiles yet.", created.target);
+            } else if !critical.is_empty() && !created.allow_critical {
+                eprintln!(
+                    "Note: {} critical profile(s) will be skipped; use --allow-critical to include them.",
+                    critical.len()
+                );
+            }
+            Ok(())
+        }
+        ScheduleCommands::List { json } => {
+            let schedules = schedule::list(&conn)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&schedules)?);
+                return Ok(());
+            }
+            if schedules.is_empty() {
+                println!("No schedules. Add one with `td schedule add`.");
+                return Ok(());
+            }
+            println!(
+                "{:>4}  {:<16}  {:<20}  {:<18}  {:<26}  LAST",
+                "ID", "CMDSET", "TARGET", "CRON", "NEXT RUN"
+            );
+            for entry in schedules {
+                let last = match (entry.last_run_at, entry.last_ok) {
+                    (None, _) => "-".to_string(),
+                    (Some(ts), ok) => format!(
+                        "{} {}",
+                        when(Some(ts)),
+                        if ok == Some(true) { "ok" } else { "failed" }
+                    ),
+                };
+                let next = if entry.enabled {
+                    when(entry.next_run_at)
+                } else {
+                    "paused".to_string()
+                };
+                println!(
+                    "{:>4}  {:<16}  {:<20}  {:<18}  {:<26}  {}",
+                    entry.id,
+                    table_cell(&entry.cmdset_id, 16),
+                    table_cell(&entry.target, 20),
+                    table_cell(&entry.cron, 18),
+                    next,
+                    last
+                );
+            }
+            Ok(())
+        }
+        ScheduleCommands::Rm { id } => {
+            schedule::remove(&conn, id)?;
+            println!("Removed schedule {id}");
+            Ok(())
+        }
+        ScheduleCommands::Enable { id } | ScheduleCommands::Disable { id } => {
+            let enabled = matches!(cmd, ScheduleCommands::Enable { .. });
+            let updated = schedule::set_enabled(&conn, id, enabled, util::now_ms())?;
+            if updated.enabled {
+                println!(
+                    "Enabled schedule {id}, next run {}",
+                    when(updated.next_run_at)
+                );
+            } else {
+                println!("Paused schedule {id}");
+            }
+            Ok(())
+        }
+        ScheduleCommands::Export { days, output, .. } => write_calendar(
+            calendar::schedules_ics(&conn, util::now_ms(), days)?,
+            output,
+        ),
+        ScheduleCommands::RunDue { json } => {
+            let runs = schedule::run_due(
+                &ProfileStore::new(conn),
+                &CmdSetStore::new(db::init_connection()?),
+                util::now_ms(),
+            )?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&runs)?);
+                return Ok(());
+            }
+            if runs.is_empty() {
+                println!("No schedules are due.");
+            }
+            for run in &runs {
+                let tone = if run.failed.is_empty() {
+                    Tone::Ok
+                } else {
+                    Tone::Fail
+                };
+                println!(
+                    "{}",
+                    output::paint(
+                        &format!(
+                            "schedule {} ({}): {} ok, {} failed, {} skipped",
+                            run.schedule_id,
+                            run.cmdset_id,
+                            run.ok.len(),
+                            run.failed.len(),
+                            run.skipped.len()
+                        ),
+                        tone
+                    )
+                );
+                for failure in &run.failed {
+                    println!("  {}: {}", failure.profile_id, failure.error);
+                }
+            }
+            Ok(())
+        }
     }
 }
 
-fn table_cell(value: &str, width: usize) -> String {
-    let char_count = value.chars().count();
-    if char_count <= width {
-        return value.to_string();
-    }
-    if width == 0 {
-        return String::new();
+fn handle_maintenance(cmd: MaintenanceCommands) -> Result<()> {
+    let conn = db::init_connection()?;
+    match cmd {
+        MaintenanceCommands::Add(args) => {
+            let created = maintenance::add(
+                &conn,
+                NewMaintenanceWindow {
+                    target: ScheduleTarget::parse(&args.target)?,
+                    span: args.window,
+                    mode: args.mode.into(),
+                    note: args.note,
+                },
+                util::now_ms(),
+            )?;
+            println!(
+                "Added maintenance window {}: {} on {} ({})",
+                created.id,
+                created.span,
+                created.target,
+                created.mode.as_str()
+            );
+            let profiles = ScheduleTarget::parse(&created.target)?
+                .resolve(&ProfileStore::new(db::init_connection()?))?;
+            if !profiles
+                .iter()
+                .any(|profile| profile.danger_level == DangerLevel::Critical)
+            {
+                eprintln!(
+                    "Warning: {} matches no critical profiles yet; windows only apply to critical profiles.",
+                    created.target
+                );
+            }
+            Ok(())
+        }
+        MaintenanceCommands::List { json } => {
+            let windows = maintenance::list(&conn)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&windows)?);
+                return Ok(());
+            }
+            if windows.is_empty() {
+                println!("No maintenance windows. Add one with `td maintenance add`.");
+                return Ok(());
+            }
+            println!(
+                "{:>4}  {:<20}  {:<26}  {:<6}  NOTE",
+                "ID", "TARGET", "WINDOW", "MODE"
+            );
+            for window in windows {
+                println!(
+                    "{:>4}  {:<20}  {:<26}  {:<6}  {}",
+                    window.id,
+                    table_cell(&window.target, 20),
+                    table_cell(&window.span, 26),
+                    window.mode.as_str(),
+                    window.note.as_deref().unwrap_or("-")
+                );
+            }
+            Ok(())
+        }
+        MaintenanceCommands::Rm { id } => {
+            maintenance::remove(&conn, id)?;
+            println!("Removed maintenance window {id}");
+            Ok(())
+        }
+        MaintenanceCommands::Export { output, .. } => {
+            write_calendar(calendar::maintenance_ics(&conn, util::now_ms())?, output)
+        }
     }
-    let mut truncated = value
-        .chars()
-        .take(width.saturating_sub(1))
-        .collect::<String>();
-    truncated.push('~');
-    truncated
 }
 
-fn format_session_log_path(path: Option<&Path>) -> String {
-    let Some(path) = path else {
-        return "<none>".to_string();
-    };
-    if !is_displayable_session_log_path(path) {
-        return "<none>".to_string();
+fn write_calendar(ics: String, output: Option<PathBuf>) -> Result<()> {
+    match output {
+        Some(path) => {
+            std::fs::write(&path, ics)?;
+            info!("calendar written to {}", path.display());
+        }
+        None => print!("{ics}"),
     }
-    path.display().to_string()
+    Ok(())
 }
 
-fn is_displayable_session_log_path(path: &Path) -> bool {
-    let raw = path.as_os_str().to_string_lossy();
-    if raw.trim().is_empty() || raw.contains('\n') || raw.contains('\r') {
-        return false;
+fn handle_log(cmd: LogCommands) -> Result<()> {
+    let conn = db::init_connection()?;
+    match cmd {
+        LogCommands::Prune(args) => handle_log_prune(&conn, args),
+        LogCommands::Note(args) => handle_log_note(&conn, args),
+        LogCommands::Summary(args) => handle_log_summary(&conn, args),
+        LogCommands::List(args) => handle_log_list(&conn, args),
+        LogCommands::Export(args) => handle_log_export(&conn, args),
+        LogCommands::Show { id, json, output } => handle_log_show(&conn, id, json, output),
     }
-    path.is_absolute()
-        || path
-            .extension()
-            .is_some_and(|extension| extension.to_string_lossy().eq_ignore_ascii_case("log"))
 }
 
-fn handle_test(profile_id: String, json: bool, include_ssh: bool) -> Result<()> {
-    let store = ProfileStore::new(db::init_connection()?);
-    let profile = store
-        .get(&profile_id)?
-        .ok_or_else(|| anyhow!("profile not found: {profile_id}"))?;
-    if !tester::is_network_profile(&profile) {
-        return Err(anyhow!("test only supports SSH or telnet profiles"));
-    }
-
-    let mut options = TestOptions::default();
-    let mut client_used = None;
-    if include_ssh {
-        if profile.profile_type != ProfileType::Ssh {
-            return Err(anyhow!("--ssh is only supported for SSH profiles"));
-        }
-        let auth = ssh_auth_context(store.conn())?;
-        emit_ssh_auth_messages(&auth);
-        let ssh = resolve_client_for(ClientKind::Ssh, profile.client_overrides.as_ref(), &store)?;
-        client_used = Some(ssh.to_string_lossy().into_owned());
-        let batch = SshBatchCommand::new(
-            ssh,
-            profile.user.clone(),
-            profile.host.clone(),
-            profile.port,
-            auth.args,
-            Duration::from_secs(5),
-        );
-        options = options.with_ssh(batch);
-    }
-
-    let report = tester::run_profile_test(&profile, &options);
-    store.touch_last_used(&profile.profile_id)?;
-    let meta_json = serde_json::to_value(&report)?;
-    let entry = oplog::OpLogEntry {
-        op: "test".into(),
-        profile_id: Some(profile.profile_id.clone()),
-        client_used,
-        ok: report.ok,
-        exit_code: report.ssh_exit_code(),
-        duration_ms: Some(report.duration_ms),
-        meta_json: Some(meta_json),
+fn handle_log_list(conn: &Connection, args: LogListArgs) -> Result<()> {
+    let offset = settings::get_display_offset(conn)?;
+    let since_ms = args
+        .since
+        .as_deref()
+        .map(|raw| time_bound_ms("--since", raw, offset))
+        .transpose()?;
+    let until_ms = args
+        .until
+        .as_deref()
+        .map(|raw| time_bound_ms("--until", raw, offset))
+        .transpose()?;
+    let ok = match (args.ok, args.failed) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
     };
-    oplog::log_operation(store.conn(), entry)?;
-
-    if json {
-        println!("{}", serde_json::to_string_pretty(&report)?);
+    let records = oplog::list_operations(
+        conn,
+        &oplog::OpLogQuery {
+            profile_id: args.profile,
+            op: args.op,
+            ok,
+            since_ms,
+            until_ms,
+            operator: args.operator,
+            limit: args.limit,
+            offset: args.offset,
+        },
+    )?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&records)?);
         return Ok(());
     }
-
-    println!(
-        "Test results for {} ({}): {}:{}",
-        report.profile_id, report.profile_type, report.host, report.port
-    );
-    for check in &report.checks {
-        let status = if check.skipped {
-            "SKIPPED"
-        } else if check.ok {
-            "OK"
-        } else {
-            "FAIL"
-        };
-        let duration = check
-            .duration_ms
-            .map(|ms| format!("{ms}ms"))
-            .unwrap_or_else(|| "-".to_string());
-        let detail = check.detail.as_deref().unwrap_or("");
-        if detail.is_empty() {
-            println!("{:<8} {:<7} ({duration})", check.name, status);
-        } else {
-            println!("{:<8} {:<7} ({duration}) {}", check.name, status, detail);
-        }
+    if records.is_empty() {
+        println!("No op log entries match.");
+        return Ok(());
     }
-    if report.ok {
+    output::page(|out| {
+        writeln!(
+            out,
+            "{:>6}  {:<26}  {:<12}  {:<16}  {:<12}  {:<8}  {:>8}",
+            "ID", "TIME", "OP", "PROFILE", "USER", "RESULT", "DURATION"
+        )?;
+        for record in &records {
+            let result = match (record.ok, record.exit_code) {
+                (true, _) => "ok".to_string(),
+                (false, Some(code)) => format!("exit {code}"),
+                (false, None) => "failed".to_string(),
+            };
+            let tone = if record.ok { Tone::Ok } else { Tone::Fail };
+            writeln!(
+                out,
+                "{:>6}  {:<26}  {:<12}  {:<16}  {:<12}  {}  {:>8}{}",
+                record.id,
+                util::format_timestamp_ms(record.ts, offset),
+                table_cell(&record.op, 12),
+                table_cell(record.profile_id.as_deref().unwrap_or("-"), 16),
+                table_cell(record.who().unwrap_or("-"), 12),
+                output::paint(&format!("{:<8}", table_cell(&result, 8)), tone),
+                record
+                    .duration_ms
+                    .map(format_duration_ms)
+                    .unwrap_or_else(|| "-".to_string()),
+                if record.note.is_some() {
+                    "  [note]"
+                } else {
+                    ""
+                }
+            )?;
+        }
         Ok(())
-    } else {
-        Err(anyhow!("test failed"))
-    }
+    })?;
+    Ok(())
 }
 
-fn handle_tunnel(cmd: TunnelCommands) -> Result<()> {
-    match cmd {
-        TunnelCommands::Start(args) => handle_tunnel_start(args),
-        TunnelCommands::Stop { session_id } => handle_tunnel_stop(&session_id),
-        TunnelCommands::Status(args) => handle_tunnel_status(args),
+fn handle_log_export(conn: &Connection, args: LogExportArgs) -> Result<()> {
+    let offset = settings::get_display_offset(conn)?;
+    let since_ms = args
+        .since
+        .as_deref()
+        .map(|raw| time_bound_ms("--since", raw, offset))
+        .transpose()?;
+    let until_ms = args
+        .until
+        .as_deref()
+        .map(|raw| time_bound_ms("--until", raw, offset))
+        .transpose()?;
+    let format = AuditFormat::from(args.format);
+    let mut out = io::stdout().lock();
+    for event in audit::events(conn, since_ms, until_ms)? {
+        writeln!(out, "{}", event.render(format)?)?;
     }
+    Ok(())
 }
 
-fn handle_tunnel_start(args: TunnelStartArgs) -> Result<()> {
-    if args.forward.is_empty() {
-        return Err(anyhow!("tunnel start requires at least one --forward"));
-    }
-    let profile_store = ProfileStore::new(db::init_connection()?);
-    let forward_store = ForwardStore::new(db::init_connection()?);
-    let session_store = SessionStore::new(db::init_connection()?);
-    let profile = profile_store
-        .get(&args.profile_id)?
-        .ok_or_else(|| anyhow!("profile not found: {}", args.profile_id))?;
-    ensure_ssh_profile(&profile, "tunnel")?;
-    if profile.danger_level == DangerLevel::Critical && !confirm_danger(&profile)? {
-        println!("Aborted by user.");
+fn handle_log_show(conn: &Connection, id: i64, json: bool, output: bool) -> Result<()> {
+    let record = oplog::get_operation(conn, id)?
+        .ok_or_else(|| exit::not_found(format!("op log entry not found: {id}")))?;
+    if output {
+        let path = run_record::recording_path(&record).ok_or_else(|| {
+            exit::not_found(format!(
+                "op log entry {id} has no output recording (set run.record to log or cast)"
+            ))
+        })?;
+        println!("{}", path.display());
         return Ok(());
     }
-
-    let mut forwards = Vec::new();
-    for name in &args.forward {
-        let forward = forward_store
-            .get_by_name(&profile.profile_id, name)?
-            .ok_or_else(|| anyhow!("forward not found: {name}"))?;
-        forwards.push(forward);
+    let outputs = run_output::outputs_for_operation(conn, &record)?;
+    if json {
+        let mut view = serde_json::to_value(&record)?;
+        view["outputs"] = serde_json::to_value(&outputs)?;
+        println!("{}", serde_json::to_string_pretty(&view)?);
+        return Ok(());
     }
+    let offset = settings::get_display_offset(conn)?;
+    output::page(|out| {
+        writeln!(out, "Op log entry {}", record.id)?;
+        writeln!(
+            out,
+            "  time:     {}",
+            util::format_timestamp_ms(record.ts, offset)
+        )?;
+        writeln!(out, "  op:       {}", record.op)?;
+        writeln!(
+            out,
+            "  profile:  {}",
+            record.profile_id.as_deref().unwrap_or("-")
+        )?;
+        if let Some(operator) = &record.operator {
+            writeln!(out, "  operator: {operator}")?;
+        }
+        if let Some(os_user) = &record.os_user {
+            writeln!(out, "  os user:  {os_user}")?;
+        }
+        if let Some(client) = &record.client_used {
+            writeln!(out, "  client:   {client}")?;
+        }
+        let result = match record.exit_code {
+            Some(code) => format!("{} (exit {code})", if record.ok { "ok" } else { "failed" }),
+            None => (if record.ok { "ok" } else { "failed" }).to_string(),
+        };
+        writeln!(out, "  result:   {result}")?;
+        if let Some(duration_ms) = record.duration_ms {
+            writeln!(out, "  duration: {}", format_duration_ms(duration_ms))?;
+        }
+        if let Some(note) = &record.note {
+            writeln!(out, "  note:     {note}")?;
+        }
+        if let Some(meta) = &record.meta {
+            writeln!(out, "  meta:     {meta}")?;
+        }
+        if let Some(path) = run_record::recording_path(&record) {
+            writeln!(out, "  recording: {}", path.display())?;
+        }
+        if !matches!(record.op.as_str(), "run" | "exec") {
+            return Ok(());
+        }
+        if outputs.is_empty() {
+            writeln!(
+                out,
+                "No stored output for this entry (outputs are kept only when run.store_outputs is true)."
+            )?;
+            return Ok(());
+        }
+        for stored in &outputs {
+            let step = stored
+                .ord
+                .map(|ord| format!("step {ord}: "))
+                .unwrap_or_default();
+            let exit_code = stored
+                .exit_code
+                .map(|code| code.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}",
+                output::paint(
+                    &format!("== {step}{} (exit={exit_code})", stored.cmd),
+                    Tone::Highlight
+                )
+            )?;
+            if !stored.stdout.is_empty() {
+                write!(out, "{}", stored.stdout)?;
+                if !stored.stdout.ends_with('\n') {
+                    writeln!(out)?;
+                }
+            }
+            if !stored.stderr.is_empty() {
+                writeln!(out, "-- stderr --")?;
+                write!(out, "{}", stored.stderr)?;
+                if !stored.stderr.ends_with('\n') {
+                    writeln!(out)?;
+                }
+            }
+            if stored.truncated_bytes > 0 {
+                writeln!(
+                    out,
+                    "[{} bytes not kept: over run.output_max_bytes]",
+                    stored.truncated_bytes
+                )?;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
 
-    let ssh = resolve_client_for(
-        ClientKind::Ssh,
-        profile.client_overrides.as_ref(),
-        &profile_store,
-    )?;
-    let auth = ssh_auth_context(profile_store.conn())?;
-    emit_ssh_auth_messages(&auth);
+/// Resolves a `--since`/`--until` bound given as an age (`7d`) or a day (`YYYY-MM-DD`).
+fn time_bound_ms(flag: &str, raw: &str, offset: UtcOffset) -> Result<i64> {
+    let looks_like_age = raw
+        .trim()
+        .chars()
+        .last()
+        .is_some_and(|suffix| "dDhHmMsS".contains(suffix));
+    if looks_like_age {
+        return Ok(now_ms() - parse_age_ms(flag, raw)?);
+    }
+    Ok(day_start_ms(flag, raw, offset)?.1)
+}
 
-    let mut cmd = Command::new(&ssh);
-    cmd.arg("-N")
-        .arg("-p")
-        .arg(profile.port.to_string())
-        .args(&auth.args);
-    for forward in &forwards {
-        let spec = match forward.kind {
-            ForwardKind::Dynamic => forward.listen.clone(),
-            ForwardKind::Local | ForwardKind::Remote => format!(
-                "{}:{}",
-                forward.listen,
-                forward
-                    .dest
-                    .as_ref()
-                    .ok_or_else(|| anyhow!("forward {} missing destination", forward.name))?
-            ),
-        };
-        cmd.arg(forward.kind.as_flag()).arg(spec);
+fn handle_log_note(conn: &Connection, args: LogNoteArgs) -> Result<()> {
+    let id = match args.id {
+        Some(id) => id,
+        None => oplog::latest_operation_id(conn, args.profile.as_deref())?.ok_or_else(|| {
+            exit::not_found(match &args.profile {
+                Some(profile_id) => format!("no op log entries for profile: {profile_id}"),
+                None => "no op log entries yet".to_string(),
+            })
+        })?,
+    };
+    oplog::set_note(conn, id, &args.note)?;
+    if args.note.trim().is_empty() {
+        println!("removed note from op log entry {id}");
+    } else {
+        println!("noted op log entry {id}");
     }
-    cmd.arg(format!("{}@{}", profile.user, profile.host))
-        .stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null());
+    Ok(())
+}
 
-    let child = cmd.spawn().context("failed to launch ssh tunnel")?;
-    let session = session_store.insert(NewSession {
-        kind: SessionKind::Tunnel,
-        profile_id: profile.profile_id.clone(),
-        pid: Some(child.id()),
-        forwards: forwards
-            .iter()
-            .map(|forward| forward.name.clone())
-            .collect(),
-    })?;
+fn handle_log_summary(conn: &Connection, args: LogSummaryArgs) -> Result<()> {
+    let offset = settings::get_display_offset(conn)?;
+    let (date, since_ms) = day_start_ms("--day", &args.day, offset)?;
+    let summary = oplog::summarize(conn, since_ms, since_ms + 24 * 60 * 60 * 1000)?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
     println!(
-        "started tunnel session {} (pid {})",
-        session.session_id,
-        session.pid.unwrap_or_default()
+        "Op log summary for {date}: {} operations, {} failed",
+        summary.total, summary.failed
     );
+    for (op, counts) in &summary.by_op {
+        println!("  {op:<20} {} ok, {} failed", counts.ok, counts.failed);
+    }
+    if summary.notes.is_empty() {
+        println!("Notes: (none)");
+        return Ok(());
+    }
+    println!("Notes:");
+    for record in &summary.notes {
+        let stamp = util::format_timestamp_ms(record.ts, offset);
+        println!(
+            "  {} #{} {}{}: {}",
+            stamp.get(11..16).unwrap_or(&stamp),
+            record.id,
+            record.op,
+            record
+                .profile_id
+                .as_deref()
+                .map(|profile_id| format!(" {profile_id}"))
+                .unwrap_or_default(),
+            record.note.as_deref().unwrap_or_default()
+        );
+    }
     Ok(())
 }
 
-fn handle_tunnel_stop(session_id: &str) -> Result<()> {
-    let session_store = SessionStore::new(db::init_connection()?);
-    let session = session_store
-        .get(session_id)?
-        .ok_or_else(|| anyhow!("session not found: {session_id}"))?;
-    if let Some(pid) = session.pid {
-        terminate_pid(pid)?;
+/// Resolves `today`, `yesterday`, or `YYYY-MM-DD` to the date and its local midnight in ms.
+fn day_start_ms(flag: &str, day: &str, offset: UtcOffset) -> Result<(time::Date, i64)> {
+    let today = OffsetDateTime::now_utc().to_offset(offset).date();
+    let invalid = || {
+        exit::invalid(format!(
+            "invalid {flag} {day}; use today, yesterday, or YYYY-MM-DD"
+        ))
+    };
+    let date = match day.trim() {
+        "today" => today,
+        "yesterday" => today.previous_day().ok_or_else(invalid)?,
+        raw => {
+            let mut parts = raw.splitn(3, '-').map(str::parse::<i32>);
+            let (Some(Ok(year)), Some(Ok(month)), Some(Ok(day))) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                return Err(invalid());
+            };
+            let month = u8::try_from(month)
+                .ok()
+                .and_then(|month| time::Month::try_from(month).ok())
+                .ok_or_else(invalid)?;
+            let day = u8::try_from(day).map_err(|_| invalid())?;
+            time::Date::from_calendar_date(year, month, day).map_err(|_| invalid())?
+        }
+    };
+    let start = date.midnight().assume_offset(offset).unix_timestamp() * 1000;
+    Ok((date, start))
+}
+
+fn handle_log_prune(conn: &Connection, args: LogPruneArgs) -> Result<()> {
+    let preview = args.dry_run || !args.yes;
+    let report = retention::prune(conn, now_ms(), preview)?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_log_prune_report(&report);
+    }
+    if !args.dry_run && !args.yes && report.op_logs() + report.run_outputs() > 0 {
+        return Err(exit::policy_denied(
+            "refusing to delete op logs without --yes; rerun with --dry-run to preview or --yes to delete",
+        ));
     }
-    session_store.remove(session_id)?;
-    println!("stopped tunnel session {session_id}");
     Ok(())
 }
 
-fn handle_tunnel_status(args: TunnelStatusArgs) -> Result<()> {
-    let session_store = SessionStore::new(db::init_connection()?);
-    let cleaned = session_store.cleanup_dead()?;
-    let sessions = session_store.list()?;
+fn print_log_prune_report(report: &retention::RetentionReport) {
+    let verb = if report.dry_run {
+        "would delete"
+    } else {
+        "deleted"
+    };
+    println!("Log prune{}", if report.dry_run { " dry-run" } else { "" });
+    for level in &report.levels {
+        if level.days == 0 {
+            println!("- {}: kept forever", level.danger_level);
+            if level.run_outputs > 0 {
+                println!(
+                    "  {verb} {} outputs past retention.output_days",
+                    level.run_outputs
+                );
+            }
+        } else {
+            println!(
+                "- {} (older than {} days): {verb} {} op logs, {} outputs",
+                level.danger_level, level.days, level.op_logs, level.run_outputs
+            );
+        }
+    }
+    if report.max_rows > 0 {
+        println!(
+            "- over oplog.max_rows ({}): {verb} {} oldest op logs",
+            report.max_rows, report.over_max_rows
+        );
+    }
+}
 
-    if args.json {
-        let payload = serde_json::json!({
-            "cleaned": cleaned.iter().map(|session| session.session_id.clone()).collect::<Vec<_>>(),
-            "sessions": sessions.iter().map(|session| serde_json::json!({
-                "session_id": session.session_id,
-                "profile_id": session.profile_id,
-                "pid": session.pid,
-                "started_at": session.started_at,
-                "forwards": session.forwards,
-            })).collect::<Vec<_>>(),
-        });
-        println!("{}", serde_json::to_string_pretty(&payload)?);
+fn handle_db(cmd: DbCommands) -> Result<()> {
+    match cmd {
+        DbCommands::Status { json } => {
+            let conn = db::init_connection()?;
+            handle_db_status(&conn, json)
+        }
+        DbCommands::Snapshots { json } => handle_db_snapshots(&paths::database_path()?, json),
+        DbCommands::Rollback(args) => handle_db_rollback(&paths::database_path()?, args),
+        DbCommands::Contexts { json } => handle_db_contexts(json),
+    }
+}
+
+fn handle_db_contexts(json: bool) -> Result<()> {
+    let contexts = db_context::list()?;
+    let active = paths::database_path()?;
+    if json {
+        let contexts: Vec<serde_json::Value> = contexts
+            .iter()
+            .map(|context| {
+                serde_json::json!({
+                    "name": context.name,
+                    "db": context.db,
+                    "active": context.db == active,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&contexts)?);
+        return Ok(());
+    }
+    if contexts.is_empty() {
+        println!(
+            "(no contexts in {})",
+            db_context::contexts_path()?.display()
+        );
         return Ok(());
     }
+    for context in contexts {
+        let marker = if context.db == active { " *" } else { "" };
+        println!("{:<16} {}{marker}", context.name, context.db.display());
+    }
+    Ok(())
+}
 
-    if sessions.is_empty() {
-        println!("(no tunnel sessions)");
+fn handle_db_snapshots(db_path: &Path, json: bool) -> Result<()> {
+    let snapshots = db::list_snapshots(db_path)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&snapshots)?);
         return Ok(());
     }
-    if !cleaned.is_empty() {
-        println!("cleaned {} dead session(s)", cleaned.len());
+    if snapshots.is_empty() {
+        println!("(no snapshots for {})", db_path.display());
+        return Ok(());
     }
-    for session in sessions {
-        let pid = session
-            .pid
-            .map(|value| value.to_string())
-            .unwrap_or_else(|| "-".to_string());
+    for snapshot in snapshots {
         println!(
-            "{:<12} {:<10} {:<8} {:?}",
-            session.session_id, session.profile_id, pid, session.forwards
+            "v{:<4} {:>10} bytes  {}",
+            snapshot.version,
+            snapshot.size_bytes,
+            snapshot.path.display()
         );
     }
     Ok(())
 }
 
-fn terminate_pid(pid: u32) -> Result<()> {
-    #[cfg(unix)]
-    {
-        let status = Command::new("kill")
-            .arg(pid.to_string())
-            .status()
-            .context("failed to execute kill")?;
-        if !status.success() {
-            return Err(anyhow!("failed to terminate pid {pid}"));
-        }
-    }
-    #[cfg(windows)]
-    {
-        let status = Command::new("taskkill")
-            .args(["/PID", &pid.to_string(), "/T", "/F"])
-            .status()
-            .context("failed to execute taskkill")?;
-        if !status.success() {
-            return Err(anyhow!("failed to terminate pid {pid}"));
+/// Reads `observer.enabled` from the raw database file without migrating it; the
+/// connection is closed on return. A file that cannot be read counts as not enabled.
+fn observer_enabled_unmigrated() -> bool {
+    paths::database_path()
+        .ok()
+        .and_then(|path| Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).ok())
+        .and_then(|conn| settings::get_observer_enabled(&conn).ok())
+        .unwrap_or(false)
+}
+
+// Works on the raw file: opening through db::init_connection would migrate (and snapshot) again.
+fn handle_db_rollback(db_path: &Path, args: DbRollbackArgs) -> Result<()> {
+    let snapshot = match args.to {
+        Some(version) => db::list_snapshots(db_path)?
+            .into_iter()
+            .find(|snapshot| snapshot.version == version)
+            .ok_or_else(|| exit::not_found(format!("no snapshot for schema v{version}")))?,
+        None => db::latest_snapshot(db_path)?.ok_or_else(|| {
+            exit::not_found(format!("no snapshots found for {}", db_path.display()))
+        })?,
+    };
+    println!(
+        "Restore {} (schema v{}) over {}?",
+        snapshot.path.display(),
+        snapshot.version,
+        db_path.display()
+    );
+    if !args.yes {
+        print!("Type 'yes' to continue: ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("yes") {
+            println!("Aborted by user.");
+            return Ok(());
         }
     }
+    let backup = db::restore_snapshot(db_path, &snapshot)?;
+    println!("restored schema v{} snapshot", snapshot.version);
+    println!("previous database saved to {}", backup.display());
+    println!(
+        "note: this release migrates the database again on next use; run an older release to stay on v{}",
+        snapshot.version
+    );
     Ok(())
 }
 
-fn handle_push(args: TransferArgs) -> Result<()> {
-    let store = ProfileStore::new(db::init_connection()?);
-    let profile = store
-        .get(&args.profile_id)?
-        .ok_or_else(|| anyhow!("profile not found: {}", args.profile_id))?;
-    ensure_ssh_profile(&profile, "push")?;
-    if profile.danger_level == DangerLevel::Critical && !confirm_danger(&profile)? {
-        println!("Aborted by user.");
+fn handle_db_status(conn: &Connection, json: bool) -> Result<()> {
+    let status = db::status(conn)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&status)?);
         return Ok(());
     }
-    let via = TransferVia::parse(&args.via)?;
-    let allow_insecure_transfers = settings::get_allow_insecure_transfers(store.conn())?;
-    let auth = ssh_auth_context(store.conn())?;
-    emit_ssh_auth_messages(&auth);
-    let client = resolve_client_for(via.client_kind(), profile.client_overrides.as_ref(), &store)?;
-    run_transfer_with_log(
-        &store,
-        &profile,
-        TransferDirection::Push,
-        &args.local_path,
-        &args.remote_path,
-        via,
-        client,
-        &auth.args,
-        allow_insecure_transfers,
-        args.i_know_its_insecure,
-        "push",
-    )
+    let path = status
+        .path
+        .as_ref()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| "(in-memory)".to_string());
+    println!("path:          {path}");
+    println!("schema:        v{}", status.user_version);
+    println!("journal_mode:  {}", status.journal_mode);
+    println!("synchronous:   {}", status.synchronous);
+    println!(
+        "busy_timeout:  {}ms ({})",
+        status.busy_timeout_ms,
+        db::BUSY_TIMEOUT_KEY
+    );
+    println!(
+        "foreign_keys:  {}",
+        if status.foreign_keys { "on" } else { "off" }
+    );
+    println!(
+        "size:          {} pages x {} bytes",
+        status.page_count, status.page_size
+    );
+    Ok(())
 }
 
-fn handle_pull(args: TransferArgs) -> Result<()> {
-    let store = ProfileStore::new(db::init_connection()?);
-    let profile = store
-        .get(&args.profile_id)?
-        .ok_or_else(|| anyhow!("profile not found: {}", args.profile_id))?;
-    ensure_ssh_profile(&profile, "pull")?;
-    if profile.danger_level == DangerLevel::Critical && !confirm_danger(&profile)? {
-        println!("Aborted by user.");
-        return Ok(());
+fn format_recent_status(ok: bool, exit_code: Option<&i32>) -> String {
+    match (ok, exit_code) {
+        (true, Some(code)) => format!("ok exit {code}"),
+        (false, Some(code)) => format!("failed exit {code}"),
+        (true, None) => "ok without exit code".to_string(),
+        (false, None) => "failed without exit code".to_string(),
     }
-    let via = TransferVia::parse(&args.via)?;
-    let allow_insecure_transfers = settings::get_allow_insecure_transfers(store.conn())?;
-    let auth = ssh_auth_context(store.conn())?;
-    emit_ssh_auth_messages(&auth);
-    let client = resolve_client_for(via.client_kind(), profile.client_overrides.as_ref(), &store)?;
-    run_transfer_with_log(
-        &store,
-        &profile,
-        TransferDirection::Pull,
-        &args.local_path,
-        &args.remote_path,
-        via,
-        client,
-        &auth.args,
-        allow_insecure_transfers,
-        args.i_know_its_insecure,
-        "pull",
+}
+
+fn format_unix_ms_utc(ts_ms: i64) -> String {
+    let secs = ts_ms.div_euclid(1000);
+    let Ok(dt) = OffsetDateTime::from_unix_timestamp(secs) else {
+        return ts_ms.to_string();
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        dt.year(),
+        u8::from(dt.month()),
+        dt.day(),
+        dt.hour(),
+        dt.minute(),
+        dt.second()
     )
 }
 
-fn handle_xfer(args: XferArgs) -> Result<()> {
-    let store = ProfileStore::new(db::init_connection()?);
-    let src_profile = store
-        .get(&args.src_profile_id)?
-        .ok_or_else(|| anyhow!("profile not found: {}", args.src_profile_id))?;
-    let dst_profile = store
-        .get(&args.dst_profile_id)?
-        .ok_or_else(|| anyhow!("profile not found: {}", args.dst_profile_id))?;
-    ensure_ssh_profile(&src_profile, "xfer")?;
-    ensure_ssh_profile(&dst_profile, "xfer")?;
-    if src_profile.danger_level == DangerLevel::Critical && !confirm_danger(&src_profile)? {
-        println!("Aborted by user.");
-        return Ok(());
+fn format_duration_ms(duration_ms: i64) -> String {
+    if duration_ms >= 1000 {
+        format!("{:.1}s", duration_ms as f64 / 1000.0)
+    } else {
+        format!("{duration_ms}ms")
     }
-    if dst_profile.danger_level == DangerLevel::Critical && !confirm_danger(&dst_profile)? {
-        println!("Aborted by user.");
-        return Ok(());
+}
+
+fn format_session_status(metadata: &session_log::SessionLogMetadata) -> String {
+    match metadata.exit_code {
+        Some(code) => format!("{} exit {}", metadata.status, code),
+        None => metadata.status.clone(),
     }
+}
 
-    let via = TransferVia::parse(&args.via)?;
-    let allow_insecure_transfers = settings::get_allow_insecure_transfers(store.conn())?;
-    let auth = ssh_auth_context(store.conn())?;
-    emit_ssh_auth_messages(&auth);
-    let src_client = resolve_client_for(
-        via.client_kind(),
-        src_profile.client_overrides.as_ref(),
-        &store,
-    )?;
-    let dst_client = resolve_client_for(
-        via.client_kind(),
-        dst_profile.client_overrides.as_ref(),
-        &store,
-    )?;
-    let temp_dir = TransferTempDir::new("xfer")?;
-    let temp_file = temp_dir.path().join(filename_from_remote(&args.src_path));
+fn table_cell(value: &str, width: usize) -> String {
+    util::truncate_display(value, width, '~')
+}
 
-    let started = Instant::now();
-    let pull = execute_transfer(
-        &src_profile,
-        TransferDirection::Pull,
-        &temp_file,
-        &args.src_path,
-        via,
-        src_client,
-        &auth.args,
-        allow_insecure_transfers,
+fn format_session_log_path(path: Option<&Path>) -> String {
+    let Some(path) = path else {
+        return "<none>".to_string();
+    };
+    if !is_displayable_session_log_path(path) {
+        return "<none>".to_string();
+    }
+    path.display().to_string()
+}
+
+fn is_displayable_session_log_path(path: &Path) -> bool {
+    let raw = path.as_os_str().to_string_lossy();
+    if raw.trim().is_empty() || raw.contains('\n') || raw.contains('\r') {
+        return false;
+    }
+    path.is_absolute()
+        || path
+            .extension()
+            .is_some_and(|extension| extension.to_string_lossy().eq_ignore_ascii_case("log"))
+}
+
+fn handle_test(
+    profile_id: String,
+    json: bool,
+    include_ssh: bool,
+    samples: Option<u32>,
+) -> Result<()> {
+    let store = ProfileStore::new(db::init_connection()?);
+    let profile = store
+        .get(&profile_id)?
+        .ok_or_else(|| exit::not_found(format!("profile not found: {profile_id}")))?;
+    if include_ssh && profile.profile_type != ProfileType::Ssh {
+        return Err(exit::invalid("--ssh is only supported for SSH profiles"));
+    }
+    let auth = if include_ssh {
+        let auth = ssh_auth_context(store.conn())?;
+        emit_ssh_auth_messages(&auth);
+        Some(auth)
+    } else {
+        None
+    };
+    let (options, client_used) = test_options_for(&store, &profile, auth.as_ref())?;
+    let options = options.with_latency_samples(samples);
+
+    let report = tester::run_profile_test(&profile, &options);
+    log_test_report(&store, &report, client_used)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!(
+        "Test results for {} ({}): {}:{}",
+        report.profile_id, report.profile_type, report.host, report.port
+    );
+    for check in &report.checks {
+        let status = if check.skipped {
+            "SKIPPED"
+        } else if check.ok {
+            "OK"
+        } else {
+            "FAIL"
+        };
+        let duration = check
+            .duration_ms
+            .map(|ms| format!("{ms}ms"))
+            .unwrap_or_else(|| "-".to_string());
+        let detail = check.detail.as_deref().unwrap_or("");
+        if detail.is_empty() {
+            println!("{:<8} {:<7} ({duration})", check.name, status);
+        } else {
+            println!("{:<8} {:<7} ({duration}) {}", check.name, status, detail);
+        }
+    }
+    if report.ok {
+        Ok(())
+    } else {
+        Err(exit::remote_failure("test failed"))
+    }
+}
+
+/// Parallel probes used by `td test --all`.
+const TEST_ALL_WORKERS: usize = 8;
+
+fn handle_test_all(json: bool, include_ssh: bool, samples: Option<u32>) -> Result<()> {
+    let store = ProfileStore::new(db::init_connection()?);
+    let profiles = store.list_filtered(&ProfileFilters::default())?;
+    if profiles.is_empty() {
+        return Err(exit::not_found("no profiles to test"));
+    }
+    let auth = if include_ssh {
+        let auth = ssh_auth_context(store.conn())?;
+        emit_ssh_auth_messages(&auth);
+        Some(auth)
+    } else {
+        None
+    };
+    let mut jobs = Vec::with_capacity(profiles.len());
+    let mut clients = Vec::with_capacity(profiles.len());
+    for profile in profiles {
+        // The SSH probe only applies to SSH profiles; telnet and serial profiles get their
+        // own reachability checks.
+        let auth = auth
+            .as_ref()
+            .filter(|_| profile.profile_type == ProfileType::Ssh);
+        let (options, client_used) = test_options_for(&store, &profile, auth)?;
+        jobs.push((profile, options.with_latency_samples(samples)));
+        clients.push(client_used);
+    }
+
+    let reports = tester::run_profile_tests(&jobs, TEST_ALL_WORKERS);
+    for (report, client_used) in reports.iter().zip(clients) {
+        log_test_report(&store, report, client_used)?;
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        output::page(|out| {
+            writeln!(
+                out,
+                "{:<16} {:<7} {:<28} {:<6} {:>8}  FAILED CHECKS",
+                "PROFILE", "TYPE", "ADDRESS", "RESULT", "TIME"
+            )?;
+            for report in &reports {
+                let failed = report
+                    .checks
+                    .iter()
+                    .filter(|check| check.is_failed())
+                    .map(|check| check.name.as_str())
+                    .collect::<Vec<_>>();
+                let result = if report.ok {
+                    output::paint(&format!("{:<6}", "OK"), Tone::Ok)
+                } else {
+                    output::paint(&format!("{:<6}", "FAIL"), Tone::Fail)
+                };
+                writeln!(
+                    out,
+                    "{:<16} {:<7} {:<28} {} {:>6}ms  {}",
+                    report.profile_id,
+                    report.profile_type,
+                    format!("{}:{}", report.host, report.port),
+                    result,
+                    report.duration_ms,
+                    if failed.is_empty() {
+                        "-".to_string()
+                    } else {
+                        failed.join(",")
+                    }
+                )?;
+            }
+            Ok(())
+        })?;
+    }
+
+    let failed = reports
+        .iter()
+        .filter(|report| !report.ok)
+        .map(|report| report.profile_id.as_str())
+        .collect::<Vec<_>>();
+    match failed.len() {
+        0 => Ok(()),
+        n if n == reports.len() => Err(exit::remote_failure(format!(
+            "test failed for every profile: {}",
+            failed.join(", ")
+        ))),
+        n => Err(exit::partial_failure(format!(
+            "test failed for {n} of {} profiles: {}",
+            reports.len(),
+            failed.join(", ")
+        ))),
+    }
+}
+
+/// Test options for one profile; `auth` enables the SSH BatchMode probe.
+fn test_options_for(
+    store: &ProfileStore,
+    profile: &Profile,
+    auth: Option<&SshAuthContext>,
+) -> Result<(TestOptions, Option<String>)> {
+    let options = TestOptions::default();
+    let Some(auth) = auth else {
+        return Ok((options, None));
+    };
+    let ssh = resolve_client_for(ClientKind::Ssh, profile, store)?;
+    let client_used = Some(ssh.to_string_lossy().into_owned());
+    let batch = SshBatchCommand::new(
+        ssh,
+        profile.user.clone(),
+        profile.host.clone(),
+        profile.port,
+        auth.args.clone(),
+        Duration::from_secs(5),
+    );
+    Ok((options.with_ssh(batch), client_used))
+}
+
+fn log_test_report(
+    store: &ProfileStore,
+    report: &tester::TestReport,
+    client_used: Option<String>,
+) -> Result<()> {
+    store.touch_last_used(&report.profile_id)?;
+    let entry = oplog::OpLogEntry {
+        op: "test".into(),
+        profile_id: Some(report.profile_id.clone()),
+        client_used,
+        ok: report.ok,
+        exit_code: report.ssh_exit_code(),
+        duration_ms: Some(report.duration_ms),
+        meta_json: Some(serde_json::to_value(report)?),
+    };
+    oplog::log_operation(store.conn(), entry)?;
+    Ok(())
+}
+
+fn handle_tunnel(cmd: TunnelCommands) -> Result<()> {
+    match cmd {
+        TunnelCommands::Start(args) => handle_tunnel_start(args),
+        TunnelCommands::Stop { session_id } => handle_tunnel_stop(&session_id),
+        TunnelCommands::Status(args) => handle_tunnel_status(args),
+        TunnelCommands::Env(args) => handle_tunnel_env(args),
+        TunnelCommands::Forward { command } => handle_forward(command),
+        TunnelCommands::Preset { command } => handle_forward_preset(command),
+    }
+}
+
+fn handle_api(cmd: ApiCommands) -> Result<()> {
+    let conn = db::init_connection()?;
+    match cmd {
+        #[cfg(not(feature = "api"))]
+        ApiCommands::Serve { .. } => Err(feature_disabled("api")),
+        #[cfg(feature = "api")]
+        ApiCommands::Serve { port } => {
+            if settings::get_setting(&conn, settings::API_TOKEN_KEY)?.is_none() {
+                return Err(exit::policy_denied(
+                    "api.token is not set; run `td api token` first",
+                ));
+            }
+            let listener = std::net::TcpListener::bind(("127.0.0.1", port))
+                .with_context(|| format!("failed to bind 127.0.0.1:{port}"))?;
+            println!("td api listening on http://{}", listener.local_addr()?);
+            api::serve(listener)
+        }
+        ApiCommands::Token => {
+            let token: String = tdcore::crypto::random_bytes::<24>()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect();
+            let stored =
+                settings_registry::validate_setting_value(settings::API_TOKEN_KEY, &token)?;
+            settings::set_setting(&conn, settings::API_TOKEN_KEY, &stored)?;
+            println!("{token}");
+            eprintln!("Only a hash is stored; this token replaces any previous one.");
+            Ok(())
+        }
+    }
+}
+
+fn handle_daemon(cmd: DaemonCommands) -> Result<()> {
+    match cmd {
+        DaemonCommands::Start(args) => {
+            let mut daemon = Daemon::new(
+                ProfileStore::new(db::init_connection()?),
+                SecretStore::new(db::init_connection()?),
+                DaemonOptions {
+                    interval: Duration::from_secs(args.interval),
+                    restart_tunnels: args.restart_tunnels,
+                    run_schedules: !args.no_schedules,
+                },
+            );
+            let listener = daemon.bind()?;
+            println!(
+                "td daemon listening on {} (pid {})",
+                listener.info().endpoint,
+                listener.info().pid
+            );
+            daemon.serve(&listener)?;
+            println!("td daemon stopped");
+            Ok(())
+        }
+        DaemonCommands::Status { json } => {
+            let status = match DaemonClient::connect()? {
+                Some(client) => {
+                    let mut status = client.call("ping", serde_json::Value::Null)?;
+                    status["endpoint"] = client.info().endpoint.clone().into();
+                    Some(status)
+                }
+                None => None,
+            };
+            if json {
+                let payload = serde_json::json!({ "running": status.is_some(), "daemon": status });
+                println!("{}", serde_json::to_string_pretty(&payload)?);
+                return Ok(());
+            }
+            match status {
+                Some(status) => {
+                    println!(
+                        "running: pid {} at {}",
+                        status["pid"],
+                        status["endpoint"].as_str().unwrap_or_default()
+                    );
+                    println!(
+                        "vault: {}",
+                        if status["unlocked"] == true {
+                            "unlocked"
+                        } else {
+                            "locked"
+                        }
+                    );
+                    println!("tunnel sessions: {}", status["sessions"]);
+                    println!("restart tunnels: {}", status["restart_tunnels"]);
+                }
+                None => println!("not running"),
+            }
+            Ok(())
+        }
+        DaemonCommands::Stop => {
+            require_daemon()?.call("shutdown", serde_json::Value::Null)?;
+            println!("daemon stopped");
+            Ok(())
+        }
+        DaemonCommands::Lock => {
+            require_daemon()?.call("vault.lock", serde_json::Value::Null)?;
+            println!("vault locked in daemon");
+            Ok(())
+        }
+    }
+}
+
+fn unlock_daemon_vault(minutes: Option<u64>) -> Result<()> {
+    let client = require_daemon()?;
+    let password = Zeroizing::new(prompt_password("Master password: ")?);
+    let mut params = serde_json::json!({ "password": password.as_str() });
+    if let Some(minutes) = minutes {
+        params["minutes"] = minutes.into();
+    }
+    client.call("vault.unlock", params)?;
+    let status = client.call("vault.status", serde_json::Value::Null)?;
+    println!("vault unlocked in daemon{}", auto_lock_suffix(&status));
+    Ok(())
+}
+
+/// `" (auto-locks in 14m 59s)"` when the daemon's `vault.status` carries a timeout.
+fn auto_lock_suffix(status: &serde_json::Value) -> String {
+    status["locks_in_secs"]
+        .as_u64()
+        .map(|secs| format!(" (auto-locks in {}m {}s)", secs / 60, secs % 60))
+        .unwrap_or_default()
+}
+
+fn require_daemon() -> Result<DaemonClient> {
+    DaemonClient::connect()?
+        .ok_or_else(|| exit::not_found("td daemon is not running; start it with `td daemon start`"))
+}
+
+fn handle_tunnel_start(args: TunnelStartArgs) -> Result<()> {
+    if args.forward.is_empty() {
+        return Err(exit::invalid(
+            "tunnel start requires at least one --forward",
+        ));
+    }
+    let profile_store = ProfileStore::new(db::init_connection()?);
+    let forward_store = ForwardStore::new(db::init_connection()?);
+    let session_store = SessionStore::new(db::init_connection()?);
+    let profile = profile_store
+        .get(&args.profile_id)?
+        .ok_or_else(|| exit::not_found(format!("profile not found: {}", args.profile_id)))?;
+    ensure_ssh_profile(&profile, "tunnel")?;
+    ensure_allowed(&profile, PolicyAction::Tunnel)?;
+
+    let mut forwards = Vec::new();
+    for name in &args.forward {
+        let forward = forward_store
+            .get_by_name(&profile.profile_id, name)?
+            .ok_or_else(|| exit::not_found(format!("forward not found: {name}")))?;
+        forwards.push(forward);
+    }
+
+    let ssh = resolve_client_for(ClientKind::Ssh, &profile, &profile_store)?;
+    let auth = ssh_profile_auth_context(profile_store.conn(), &profile)?;
+    emit_ssh_auth_messages(&auth);
+
+    let mut cmd = tunnel::tunnel_command(&ssh, &profile, &auth.args, &forwards)?;
+    let child = cmd.spawn().context("failed to launch ssh tunnel")?;
+    let session = session_store.insert(NewSession {
+        kind: SessionKind::Tunnel,
+        profile_id: profile.profile_id.clone(),
+        pid: Some(child.id()),
+        forwards: forwards
+            .iter()
+            .map(|forward| forward.name.clone())
+            .collect(),
+    })?;
+    println!(
+        "started tunnel session {} (pid {})",
+        session.session_id,
+        session.pid.unwrap_or_default()
+    );
+    Ok(())
+}
+
+fn handle_tunnel_stop(session_id: &str) -> Result<()> {
+    let session_store = SessionStore::new(db::init_connection()?);
+    let session = session_store
+        .get(session_id)?
+        .ok_or_else(|| exit::not_found(format!("session not found: {session_id}")))?;
+    if let Some(pid) = session.pid {
+        tunnel::terminate_pid(pid)?;
+    }
+    session_store.remove(session_id)?;
+    println!("stopped tunnel session {session_id}");
+    Ok(())
+}
+
+fn handle_tunnel_status(args: TunnelStatusArgs) -> Result<()> {
+    let profile_store = ProfileStore::new(db::init_connection()?);
+    let session_store = SessionStore::new(db::init_connection()?);
+    let offset = settings::get_display_offset(profile_store.conn())?;
+    loop {
+        let report = check_tunnel_sessions(&profile_store, &session_store, args.restart)?;
+        if args.json {
+            let payload = report.to_json();
+            if args.watch {
+                println!("{}", serde_json::to_string(&payload)?);
+            } else {
+                println!("{}", serde_json::to_string_pretty(&payload)?);
+            }
+        } else {
+            if args.watch {
+                println!("-- {} --", util::format_timestamp_ms(now_ms(), offset));
+            }
+            report.print();
+        }
+        if !args.watch {
+            return Ok(());
+        }
+        io::stdout().flush()?;
+        thread::sleep(Duration::from_secs(args.interval));
+    }
+}
+
+struct TunnelStatusReport {
+    cleaned: Vec<String>,
+    supervised: TunnelReport,
+}
+
+/// One health pass. Without `restart`, sessions whose process exited are dropped and the
+/// rest are probed; with it, the tunnel manager restarts dead sessions per
+/// `tunnel.reconnect`.
+fn check_tunnel_sessions(
+    profile_store: &ProfileStore,
+    session_store: &SessionStore,
+    restart: bool,
+) -> Result<TunnelStatusReport> {
+    if restart {
+        let supervised = TunnelManager::new(profile_store).supervise()?;
+        for (session_id, error) in &supervised.failed {
+            warn!("failed to restart tunnel {session_id}: {error}");
+        }
+        return Ok(TunnelStatusReport {
+            cleaned: Vec::new(),
+            supervised,
+        });
+    }
+    let cleaned = session_store
+        .cleanup_dead()?
+        .into_iter()
+        .map(|session| session.session_id)
+        .collect();
+    Ok(TunnelStatusReport {
+        cleaned,
+        supervised: TunnelReport {
+            sessions: tunnel::check_tunnel_health(session_store.conn())?,
+            ..TunnelReport::default()
+        },
+    })
+}
+
+impl TunnelStatusReport {
+    fn to_json(&self) -> serde_json::Value {
+        let supervised = &self.supervised;
+        serde_json::json!({
+            "cleaned": self.cleaned,
+            "restarted": supervised.restarted,
+            "restart_failed": supervised.failed.iter().map(|(session_id, error)| serde_json::json!({
+                "session_id": session_id,
+                "error": error,
+            })).collect::<Vec<_>>(),
+            "retries_exhausted": supervised.exhausted,
+            "sessions": supervised.sessions.iter().map(|session| serde_json::json!({
+                "session_id": session.session_id,
+                "profile_id": session.profile_id,
+                "pid": session.pid,
+                "started_at": session.started_at,
+                "forwards": session.forwards,
+                "health": session.health,
+                "restarts": session.restarts,
+                "restarted_at": session.restarted_at,
+                "retry_at": session.retry_at,
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    fn print(&self) {
+        let supervised = &self.supervised;
+        if supervised.sessions.is_empty() {
+            println!("(no tunnel sessions)");
+            return;
+        }
+        if !self.cleaned.is_empty() {
+            println!("cleaned {} dead session(s)", self.cleaned.len());
+        }
+        for session in &supervised.sessions {
+            let pid = session
+                .pid
+                .map(|value| value.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            let mut health = session.health_summary();
+            if supervised.restarted.contains(&session.session_id) {
+                health.push_str(" (restarted)");
+            } else if let Some((_, error)) = supervised
+                .failed
+                .iter()
+                .find(|(session_id, _)| *session_id == session.session_id)
+            {
+                health.push_str(&format!(" (restart failed: {error})"));
+            } else if supervised.exhausted.contains(&session.session_id) {
+                health.push_str(" (retries exhausted)");
+            }
+            if session.restarts > 0 {
+                health.push_str(&format!(" restarts:{}", session.restarts));
+            }
+            println!(
+                "{:<12} {:<10} {:<8} {:?} {}",
+                session.session_id, session.profile_id, pid, session.forwards, health
+            );
+        }
+    }
+}
+
+fn handle_forward(cmd: ForwardCommands) -> Result<()> {
+    match cmd {
+        ForwardCommands::Add(args) => handle_forward_add(args),
+        ForwardCommands::List { profile_id, json } => handle_forward_list(&profile_id, json),
+        ForwardCommands::Rm { profile_id, name } => {
+            let forward_store = ForwardStore::new(db::init_connection()?);
+            forward_store.remove(&profile_id, &name)?;
+            println!("removed forward '{name}' from {profile_id}");
+            Ok(())
+        }
+        ForwardCommands::Check { profile_id, name } => {
+            let profile_store = ProfileStore::new(db::init_connection()?);
+            let forward_store = ForwardStore::new(db::init_connection()?);
+            let forward = forward_store
+                .get_by_name(&profile_id, &name)?
+                .ok_or_else(|| exit::not_found(format!("forward not found: {name}")))?;
+            match check_forward_dest(&profile_store, &forward)? {
+                DestProbe::Unreachable { error } => Err(exit::remote_failure(format!(
+                    "{} cannot reach {}: {error}",
+                    forward.profile_id,
+                    forward.dest.unwrap_or_default()
+                ))),
+                DestProbe::Unknown { error } => Err(exit::remote_failure(format!(
+                    "could not check {}: {error}",
+                    forward.name
+                ))),
+                DestProbe::Reachable => {
+                    println!(
+                        "{} reaches {}",
+                        forward.profile_id,
+                        forward.dest.unwrap_or_default()
+                    );
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+fn handle_forward_add(args: ForwardAddArgs) -> Result<()> {
+    let profile_store = ProfileStore::new(db::init_connection()?);
+    let profile = profile_store
+        .get(&args.profile_id)?
+        .ok_or_else(|| exit::not_found(format!("profile not found: {}", args.profile_id)))?;
+    ensure_ssh_profile(&profile, "forward")?;
+    let kind = match args.kind {
+        ForwardKindArg::Local => ForwardKind::Local,
+        ForwardKindArg::Remote => ForwardKind::Remote,
+        ForwardKindArg::Dynamic => ForwardKind::Dynamic,
+    };
+    if args.check_dest && kind != ForwardKind::Local {
+        return Err(exit::invalid("--check-dest only applies to local forwards"));
+    }
+    let forward_store = ForwardStore::new(db::init_connection()?);
+    let forward = forward_store.insert(NewForward {
+        profile_id: profile.profile_id,
+        name: args.name,
+        kind,
+        listen: args.listen,
+        dest: args.dest,
+    })?;
+    println!(
+        "saved forward '{}' for {} ({})",
+        forward.name,
+        forward.profile_id,
+        describe_forward(&forward)
+    );
+    if args.check_dest {
+        let dest = forward.dest.clone().unwrap_or_default();
+        match check_forward_dest(&profile_store, &forward)? {
+            DestProbe::Reachable => println!("{} reaches {dest}", forward.profile_id),
+            DestProbe::Unreachable { error } => {
+                eprintln!(
+                    "warning: {} cannot reach {dest}: {error}",
+                    forward.profile_id
+                );
+            }
+            DestProbe::Unknown { error } => {
+                eprintln!("warning: could not check {dest}: {error}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_forward_list(profile_id: &str, json: bool) -> Result<()> {
+    let forward_store = ForwardStore::new(db::init_connection()?);
+    let forwards = forward_store.list_for_profile(profile_id)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&forwards)?);
+        return Ok(());
+    }
+    if forwards.is_empty() {
+        println!("(no forwards)");
+        return Ok(());
+    }
+    for forward in forwards {
+        println!("{:<20} {}", forward.name, describe_forward(&forward));
+    }
+    Ok(())
+}
+
+fn handle_forward_preset(cmd: PresetCommands) -> Result<()> {
+    let conn = db::init_connection()?;
+    match cmd {
+        PresetCommands::Save { name, from } => {
+            let preset = forward_preset::save_from_profile(&conn, &name, &from)?;
+            println!(
+                "saved preset '{}' with {} forwards from {from}",
+                preset.name,
+                preset.forwards.len()
+            );
+        }
+        PresetCommands::List { json } => {
+            let presets = forward_preset::list(&conn)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&presets)?);
+                return Ok(());
+            }
+            if presets.is_empty() {
+                println!("(no presets)");
+            }
+            for preset in presets {
+                println!("{}", preset.name);
+                for forward in &preset.forwards {
+                    let rule = match &forward.dest {
+                        Some(dest) => format!("{} {} -> {dest}", forward.kind, forward.listen),
+                        None => format!("{} {}", forward.kind, forward.listen),
+                    };
+                    println!("  {:<18} {rule}", forward.name);
+                }
+            }
+        }
+        PresetCommands::Apply { name, profile_ids } => {
+            let report = forward_preset::apply(&conn, &name, &profile_ids)?;
+            println!(
+                "applied preset '{name}' to {} profiles: {} forwards added, {} already present",
+                profile_ids.len(),
+                report.added,
+                report.unchanged
+            );
+        }
+        PresetCommands::Rm { name } => {
+            forward_preset::remove(&conn, &name)?;
+            println!("removed preset '{name}'");
+        }
+    }
+    Ok(())
+}
+
+fn describe_forward(forward: &Forward) -> String {
+    match &forward.dest {
+        Some(dest) => format!("{} {} -> {dest}", forward.kind, forward.listen),
+        None => format!("{} {}", forward.kind, forward.listen),
+    }
+}
+
+/// Probes a local forward's destination from the profile's server with `ssh -W`.
+fn check_forward_dest(profile_store: &ProfileStore, forward: &Forward) -> Result<DestProbe> {
+    let Some(dest) = forward
+        .dest
+        .as_deref()
+        .filter(|_| forward.kind == ForwardKind::Local)
+    else {
+        return Err(exit::invalid(format!(
+            "forward {} is not a local forward",
+            forward.name
+        )));
+    };
+    let profile = profile_store
+        .get(&forward.profile_id)?
+        .ok_or_else(|| exit::not_found(format!("profile not found: {}", forward.profile_id)))?;
+    ensure_allowed(&profile, PolicyAction::Tunnel)?;
+    let ssh = resolve_client_for(ClientKind::Ssh, &profile, profile_store)?;
+    let auth = ssh_profile_auth_context(profile_store.conn(), &profile)?;
+    emit_ssh_auth_messages(&auth);
+    Ok(tunnel::probe_forward_dest(
+        &ssh,
+        &profile,
+        &auth.args,
+        dest,
+        DEST_CHECK_WAIT,
+    )?)
+}
+
+fn handle_tunnel_env(args: TunnelEnvArgs) -> Result<()> {
+    let session_store = SessionStore::new(db::init_connection()?);
+    let forward_store = ForwardStore::new(db::init_connection()?);
+    session_store.cleanup_dead()?;
+    let mut names = Vec::new();
+    for session in session_store.list()? {
+        if session.kind == SessionKind::Tunnel && session.profile_id == args.profile_id {
+            for name in session.forwards {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+    }
+    if names.is_empty() {
+        return Err(exit::not_found(format!(
+            "no running tunnel for profile: {}",
+            args.profile_id
+        )));
+    }
+    let templates = settings::get_tunnel_env_templates(&db::init_connection()?, &args.profile_id)?;
+    let mut vars = Vec::new();
+    for name in &names {
+        let Some(forward) = forward_store.get_by_name(&args.profile_id, name)? else {
+            warn!("forward {name} of a running tunnel no longer exists");
+            continue;
+        };
+        vars.extend(tunnel::forward_env_vars(
+            &forward,
+            templates.get(name).map(String::as_str),
+        )?);
+    }
+    let shell = match args.format {
+        EnvFormatArg::Bash => EnvShell::Bash,
+        EnvFormatArg::Powershell => EnvShell::Powershell,
+    };
+    print!("{}", tunnel::format_env_exports(&vars, shell));
+    Ok(())
+}
+
+fn handle_push(args: TransferArgs) -> Result<()> {
+    let store = ProfileStore::new(db::init_connection()?);
+    let profile = store
+        .get(&args.profile_id)?
+        .ok_or_else(|| exit::not_found(format!("profile not found: {}", args.profile_id)))?;
+    ensure_ssh_profile(&profile, "push")?;
+    ensure_allowed(&profile, PolicyAction::Transfer { upload: true })?;
+    transfer_for_profile(&store, &profile, TransferDirection::Push, &args)
+}
+
+/// Runs a push or pull for a profile whose danger confirmation the caller handled.
+fn transfer_for_profile(
+    store: &ProfileStore,
+    profile: &Profile,
+    direction: TransferDirection,
+    args: &TransferArgs,
+) -> Result<()> {
+    let via = TransferVia::parse(&args.via)?;
+    let allow_insecure_transfers =
+        settings::get_allow_insecure_transfers_for(store.conn(), &profile.profile_id)?;
+    let auth = ssh_profile_auth_context(store.conn(), profile)?;
+    emit_ssh_auth_messages(&auth);
+    let client = resolve_client_for(via.client_kind(), profile, store)?;
+    run_transfer_with_log(
+        store,
+        profile,
+        direction,
+        &args.local_path,
+        &args.remote_path,
+        via,
+        client,
+        &auth.args,
+        allow_insecure_transfers,
+        args.i_know_its_insecure,
+        direction.as_str(),
+    )
+}
+
+fn handle_pull(args: TransferArgs) -> Result<()> {
+    let store = ProfileStore::new(db::init_connection()?);
+    let profile = store
+        .get(&args.profile_id)?
+        .ok_or_else(|| exit::not_found(format!("profile not found: {}", args.profile_id)))?;
+    ensure_ssh_profile(&profile, "pull")?;
+    ensure_allowed(&profile, PolicyAction::Transfer { upload: false })?;
+    transfer_for_profile(&store, &profile, TransferDirection::Pull, &args)
+}
+
+fn handle_fav(cmd: FavCommands) -> Result<()> {
+    match cmd {
+        FavCommands::Add(args) => handle_fav_add(args),
+        FavCommands::List { profile_id, json } => handle_fav_list(profile_id, json),
+        FavCommands::Rm { profile_id, name } => {
+            let fav_store = TransferFavoriteStore::new(db::init_connection()?);
+            fav_store.remove(&profile_id, &name)?;
+            println!("removed favorite '{name}' from {profile_id}");
+            Ok(())
+        }
+        FavCommands::Run(args) => handle_fav_run(args),
+    }
+}
+
+fn handle_fav_add(args: FavAddArgs) -> Result<()> {
+    let store = ProfileStore::new(db::init_connection()?);
+    let profile = store
+        .get(&args.profile_id)?
+        .ok_or_else(|| exit::not_found(format!("profile not found: {}", args.profile_id)))?;
+    ensure_ssh_profile(&profile, "fav")?;
+    let direction = TransferDirection::parse(&args.direction)?;
+    let via = TransferVia::parse(&args.via)?;
+    let fav_store = TransferFavoriteStore::new(db::init_connection()?);
+    let favorite = fav_store.insert(NewTransferFavorite {
+        profile_id: profile.profile_id,
+        name: args.name,
+        direction,
+        local_path: args.local_path,
+        remote_path: args.remote_path,
+        via,
+    })?;
+    println!(
+        "saved favorite '{}' for {} ({})",
+        favorite.name,
+        favorite.profile_id,
+        describe_favorite(&favorite)
+    );
+    Ok(())
+}
+
+fn handle_fav_list(profile_id: Option<String>, json: bool) -> Result<()> {
+    let fav_store = TransferFavoriteStore::new(db::init_connection()?);
+    let favorites = match &profile_id {
+        Some(profile_id) => fav_store.list_for_profile(profile_id)?,
+        None => fav_store.list()?,
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&favorites)?);
+        return Ok(());
+    }
+    if favorites.is_empty() {
+        println!("(no transfer favorites)");
+        return Ok(());
+    }
+    for favorite in favorites {
+        println!(
+            "{:<16} {:<20} {}",
+            favorite.profile_id,
+            favorite.name,
+            describe_favorite(&favorite)
+        );
+    }
+    Ok(())
+}
+
+fn handle_fav_run(args: FavRunArgs) -> Result<()> {
+    let store = ProfileStore::new(db::init_connection()?);
+    let profile = store
+        .get(&args.profile_id)?
+        .ok_or_else(|| exit::not_found(format!("profile not found: {}", args.profile_id)))?;
+    let fav_store = TransferFavoriteStore::new(db::init_connection()?);
+    let favorite = match &args.name {
+        Some(name) => fav_store
+            .get_by_name(&profile.profile_id, name)?
+            .ok_or_else(|| exit::not_found(format!("favorite not found: {name}")))?,
+        None => {
+            let favorites = fav_store.list_for_profile(&profile.profile_id)?;
+            if favorites.is_empty() {
+                return Err(exit::not_found(format!(
+                    "no transfer favorites for {}; add one with td fav add",
+                    profile.profile_id
+                )));
+            }
+            prompt_favorite(favorites)?
+        }
+    };
+    ensure_ssh_profile(&profile, favorite.direction.as_str())?;
+    ensure_allowed(
+        &profile,
+        PolicyAction::Transfer {
+            upload: favorite.direction == TransferDirection::Push,
+        },
+    )?;
+    let allow_insecure_transfers =
+        settings::get_allow_insecure_transfers_for(store.conn(), &profile.profile_id)?;
+    let auth = ssh_profile_auth_context(store.conn(), &profile)?;
+    emit_ssh_auth_messages(&auth);
+    let client = resolve_client_for(favorite.via.client_kind(), &profile, &store)?;
+    let result = run_transfer_with_log(
+        &store,
+        &profile,
+        favorite.direction,
+        Path::new(&favorite.local_path),
+        &favorite.remote_path,
+        favorite.via,
+        client,
+        &auth.args,
+        allow_insecure_transfers,
+        args.i_know_its_insecure,
+        favorite.direction.as_str(),
+    );
+    fav_store.touch_last_used(favorite.id)?;
+    result
+}
+
+fn prompt_favorite(favorites: Vec<TransferFavorite>) -> Result<TransferFavorite> {
+    for (idx, favorite) in favorites.iter().enumerate() {
+        println!(
+            "{:>3}) {:<20} {}",
+            idx + 1,
+            favorite.name,
+            describe_favorite(favorite)
+        );
+    }
+    print!("Select favorite (number or name): ");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let idx = parse_favorite_choice(&input, &favorites)?;
+    Ok(favorites.into_iter().nth(idx).expect("choice is in range"))
+}
+
+fn parse_favorite_choice(input: &str, favorites: &[TransferFavorite]) -> Result<usize> {
+    let input = input.trim();
+    if let Ok(number) = input.parse::<usize>() {
+        if number >= 1 && number <= favorites.len() {
+            return Ok(number - 1);
+        }
+        return Err(exit::invalid(format!("choice out of range: {number}")));
+    }
+    favorites
+        .iter()
+        .position(|favorite| favorite.name == input)
+        .ok_or_else(|| exit::not_found(format!("favorite not found: {input}")))
+}
+
+fn describe_favorite(favorite: &TransferFavorite) -> String {
+    let arrow = match favorite.direction {
+        TransferDirection::Push => "->",
+        TransferDirection::Pull => "<-",
+    };
+    format!(
+        "{} {} {} {} via {}",
+        favorite.direction.as_str(),
+        favorite.local_path,
+        arrow,
+        favorite.remote_path,
+        favorite.via.as_str()
+    )
+}
+
+fn handle_xfer(args: XferArgs) -> Result<()> {
+    let store = ProfileStore::new(db::init_connection()?);
+    let src_profile = store
+        .get(&args.src_profile_id)?
+        .ok_or_else(|| exit::not_found(format!("profile not found: {}", args.src_profile_id)))?;
+    let dst_profile = store
+        .get(&args.dst_profile_id)?
+        .ok_or_else(|| exit::not_found(format!("profile not found: {}", args.dst_profile_id)))?;
+    ensure_ssh_profile(&src_profile, "xfer")?;
+    ensure_ssh_profile(&dst_profile, "xfer")?;
+    ensure_allowed(&src_profile, PolicyAction::Transfer { upload: false })?;
+    ensure_allowed(&dst_profile, PolicyAction::Transfer { upload: true })?;
+
+    let via = TransferVia::parse(&args.via)?;
+    // Both ends must allow an insecure transfer, whichever scope allows it for each.
+    let allow_insecure_transfers =
+        settings::get_allow_insecure_transfers_for(store.conn(), &src_profile.profile_id)?
+            && settings::get_allow_insecure_transfers_for(store.conn(), &dst_profile.profile_id)?;
+    let auth = ssh_auth_context(store.conn())?;
+    emit_ssh_auth_messages(&auth);
+    let src_client = resolve_client_for(via.client_kind(), &src_profile, &store)?;
+    let dst_client = resolve_client_for(via.client_kind(), &dst_profile, &store)?;
+    let temp_dir = TransferTempDir::new("xfer")?;
+    let temp_file = temp_dir.path().join(filename_from_remote(&args.src_path));
+
+    let started = Instant::now();
+    let pull = execute_transfer(
+        &src_profile,
+        TransferDirection::Pull,
+        &temp_file,
+        &args.src_path,
+        via,
+        src_client,
+        &auth.args,
+        allow_insecure_transfers,
         args.i_know_its_insecure,
     )?;
     let mut push = None;
@@ -3429,7 +7609,9 @@ fn handle_xfer(args: XferArgs) -> Result<()> {
     if ok {
         Ok(())
     } else {
-        Err(anyhow!("xfer failed with exit code {exit_code}"))
+        Err(exit::remote_failure(format!(
+            "xfer failed with exit code {exit_code}"
+        )))
     }
 }
 
@@ -3524,9 +7706,9 @@ fn connect_ssh(
             if outcome.ok {
                 Ok(())
             } else if let Some(code) = outcome.exit_code {
-                Err(anyhow!("ssh exited with code {code}"))
+                Err(exit::remote_failure(format!("ssh exited with code {code}")))
             } else {
-                Err(anyhow!("ssh ended without exit code"))
+                Err(exit::remote_failure("ssh ended without exit code"))
             }
         }
         CliSshRunResult::LaunchFailed {
@@ -3562,7 +7744,8 @@ fn run_plain_cli_ssh(
     let mut cmd = Command::new(&invocation.client_path);
     cmd.args(&invocation.args);
     let started = Instant::now();
-    let status = cmd.status().context("failed to launch ssh");
+    let status = active_session::run_tracked(&mut cmd, &invocation.target.profile_id)
+        .context("failed to launch ssh");
     let duration_ms = started.elapsed().as_millis() as i64;
 
     match status {
@@ -3594,10 +7777,11 @@ fn run_script_logged_cli_ssh(
     );
     let log_started_at = now_ms();
     let started = Instant::now();
-    let status = Command::new(&script.executable)
-        .args(&script.args)
-        .status()
-        .context("failed to launch script");
+    let status = active_session::run_tracked(
+        Command::new(&script.executable).args(&script.args),
+        &invocation.target.profile_id,
+    )
+    .context("failed to launch script");
     let duration_ms = started.elapsed().as_millis() as i64;
 
     match status {
@@ -3659,10 +7843,11 @@ fn run_powershell_transcript_cli_ssh(
     );
     let log_started_at = now_ms();
     let started = Instant::now();
-    let status = Command::new(&powershell.executable)
-        .args(&powershell.args)
-        .status()
-        .context("failed to launch PowerShell");
+    let status = active_session::run_tracked(
+        Command::new(&powershell.executable).args(&powershell.args),
+        &invocation.target.profile_id,
+    )
+    .context("failed to launch PowerShell");
     let duration_ms = started.elapsed().as_millis() as i64;
 
     match status {
@@ -3891,7 +8076,10 @@ fn connect_telnet(
     if ok {
         Ok(())
     } else {
-        Err(anyhow!("telnet exited with code {}", exit_code))
+        Err(exit::remote_failure(format!(
+            "telnet exited with code {}",
+            exit_code
+        )))
     }
 }
 
@@ -4028,21 +8216,42 @@ fn spawn_serial_initial_send(port: &mut Box<dyn serialport::SerialPort>, initial
 }
 
 fn handle_secret(cmd: SecretCommands) -> Result<()> {
-    let store = SecretStore::new(db::init_connection()?);
+    let store = SecretStore::new(open_db(matches!(
+        cmd,
+        SecretCommands::List { .. } | SecretCommands::Status { .. }
+    ))?);
     match cmd {
         SecretCommands::SetMaster => {
             if store.is_master_set()? {
-                return Err(anyhow!("master password already set"));
+                return Err(exit::invalid("master password already set"));
             }
             let first = prompt_password("Enter new master password: ")?;
             let second = prompt_password("Confirm master password: ")?;
             if first != second {
-                return Err(anyhow!("passwords did not match"));
+                return Err(exit::invalid("passwords did not match"));
             }
             store.set_master(&first)?;
             info!("master password set");
             Ok(())
         }
+        SecretCommands::SetLockPin => {
+            let first = prompt_password("Enter new lock PIN: ")?;
+            let second = prompt_password("Confirm lock PIN: ")?;
+            if first != second {
+                return Err(exit::invalid("PINs did not match"));
+            }
+            store.set_lock_pin(&first)?;
+            info!("lock PIN set");
+            Ok(())
+        }
+        SecretCommands::ClearLockPin => {
+            if store.clear_lock_pin()? {
+                println!("lock PIN cleared");
+            } else {
+                println!("(no lock PIN set)");
+            }
+            Ok(())
+        }
         SecretCommands::Add(args) => {
             let master = load_master_prompt(&store)?;
             let value = prompt_password("Secret value (input hidden): ")?;
@@ -4050,7 +8259,7 @@ fn handle_secret(cmd: SecretCommands) -> Result<()> {
                 &master,
                 NewSecret {
                     secret_id: args.secret_id,
-                    kind: args.kind,
+                    kind: SecretKind::parse(&args.kind),
                     label: args.label,
                     value: Zeroizing::new(value),
                     meta: None,
@@ -4059,7 +8268,59 @@ fn handle_secret(cmd: SecretCommands) -> Result<()> {
             println!("{}", created.secret_id);
             Ok(())
         }
-        SecretCommands::List => {
+        SecretCommands::List {
+            profile: Some(profile_id),
+        } => {
+            let profiles = ProfileStore::new(open_db(true)?);
+            if profiles.get(&profile_id)?.is_none() {
+                return Err(exit::not_found(format!("profile not found: {profile_id}")));
+            }
+            let linked = store.list_for_profile(&profile_id)?;
+            let login = store.login_secret_for(&profile_id)?;
+            let native = settings::get_ssh_password_secret(profiles.conn(), &profile_id)?;
+            if linked.is_empty() && native.is_none() {
+                println!("(no secrets linked to {profile_id})");
+                return Ok(());
+            }
+            for s in &linked {
+                let used = if login
+                    .as_ref()
+                    .is_some_and(|login| login.secret_id == s.secret_id)
+                {
+                    "  <- td connect"
+                } else {
+                    ""
+                };
+                println!("{:<16} {:<12} {:<20}{used}", s.secret_id, s.kind, s.label);
+            }
+            if let Some(secret_id) = native {
+                println!(
+                    "{secret_id:<16} (set by {} for the native ssh backend)",
+                    settings::SSH_PASSWORD_SECRET_KEY
+                );
+            }
+            Ok(())
+        }
+        SecretCommands::Link { secret_id, profile } => {
+            if ProfileStore::new(db::init_connection()?)
+                .get(&profile)?
+                .is_none()
+            {
+                return Err(exit::not_found(format!("profile not found: {profile}")));
+            }
+            store.link(&profile, &secret_id)?;
+            println!("linked {secret_id} to {profile}");
+            Ok(())
+        }
+        SecretCommands::Unlink { secret_id, profile } => {
+            if store.unlink(&profile, &secret_id)? {
+                println!("unlinked {secret_id} from {profile}");
+            } else {
+                println!("({secret_id} is not linked to {profile})");
+            }
+            Ok(())
+        }
+        SecretCommands::List { profile: None } => {
             let secrets = store.list()?;
             if secrets.is_empty() {
                 println!("(no secrets)");
@@ -4079,6 +8340,121 @@ fn handle_secret(cmd: SecretCommands) -> Result<()> {
             println!("{value}");
             Ok(())
         }
+        SecretCommands::Copy { secret_id } => {
+            let secret = store.get(&secret_id)?;
+            let clipboard = Clipboard::detect().ok_or_else(|| {
+                exit::invalid("no clipboard tool found (wl-copy, xclip, xsel, pbcopy, clip)")
+            })?;
+            let master = load_master_prompt(&store)?;
+            let expiry = store.copy_to_clipboard(&master, &secret_id, &clipboard, "cli", None)?;
+            eprintln!(
+                "secret {} ({}) copied to the clipboard",
+                secret.secret_id, secret.label
+            );
+            let Some(expiry) = expiry else {
+                eprintln!(
+                    "{} is 0, so it stays there",
+                    settings::CLIPBOARD_CLEAR_AFTER_KEY
+                );
+                return Ok(());
+            };
+            if wait_for_clipboard_clear(expiry)? {
+                eprintln!("clipboard cleared");
+            } else {
+                eprintln!("clipboard changed since the copy; left as is");
+            }
+            Ok(())
+        }
+        SecretCommands::RotateMaster | SecretCommands::Rekey => {
+            let password_changed = matches!(cmd, SecretCommands::RotateMaster);
+            let current = prompt_password("Current master password: ")?;
+            let count = if password_changed {
+                let first = prompt_password("Enter new master password: ")?;
+                let second = prompt_password("Confirm new master password: ")?;
+                if first != second {
+                    return Err(exit::invalid("passwords did not match"));
+                }
+                store.rotate_master(&current, &first)?
+            } else {
+                store.rekey(&current)?
+            };
+            oplog::log_operation(
+                &db::init_connection()?,
+                oplog::OpLogEntry {
+                    op: "secret_rotate".to_string(),
+                    profile_id: None,
+                    client_used: None,
+                    ok: true,
+                    exit_code: None,
+                    duration_ms: None,
+                    meta_json: Some(serde_json::json!({
+                        "secrets": count,
+                        "password_changed": password_changed,
+                    })),
+                },
+            )?;
+            println!("re-encrypted {count} secret(s)");
+            // The daemon still holds the old key; make it ask again.
+            if let Some(client) = DaemonClient::connect().ok().flatten() {
+                client.call("vault.lock", serde_json::Value::Null)?;
+                println!("vault locked in daemon; run `td secret unlock` to unlock it again");
+            }
+            Ok(())
+        }
+        SecretCommands::Totp { secret_id } => {
+            let master = load_master_prompt(&store)?;
+            let (code, remaining) = store.totp(&master, &secret_id, now_ms() as u64 / 1000)?;
+            println!("{code}");
+            eprintln!("valid for {remaining}s");
+            Ok(())
+        }
+        SecretCommands::Unlock { minutes } => unlock_daemon_vault(minutes),
+        SecretCommands::Lock => {
+            let locked = require_daemon()?.call("vault.lock", serde_json::Value::Null)?;
+            if locked == true {
+                println!("vault locked in daemon");
+            } else {
+                println!("(vault was not unlocked)");
+            }
+            Ok(())
+        }
+        SecretCommands::Status { json } => {
+            let master_set = store.is_master_set()?;
+            let status = match DaemonClient::connect()? {
+                Some(client) => Some(client.call("vault.status", serde_json::Value::Null)?),
+                None => None,
+            };
+            let unlocked = status
+                .as_ref()
+                .is_some_and(|status| status["unlocked"] == true);
+            if json {
+                let payload = serde_json::json!({
+                    "master_set": master_set,
+                    "daemon_running": status.is_some(),
+                    "unlocked": unlocked,
+                    "locks_in_secs": status.as_ref().map(|status| status["locks_in_secs"].clone()),
+                });
+                println!("{}", serde_json::to_string_pretty(&payload)?);
+                return Ok(());
+            }
+            println!(
+                "master password: {}",
+                if master_set { "set" } else { "not set" }
+            );
+            match status {
+                None => println!("vault: locked (td daemon is not running)"),
+                Some(status) if unlocked => {
+                    let until = auto_lock_suffix(&status);
+                    if until.is_empty() {
+                        println!("vault: unlocked (until `td secret lock`)");
+                    } else {
+                        println!("vault: unlocked{until}");
+                    }
+                }
+                Some(_) => println!("vault: locked"),
+            }
+            Ok(())
+        }
         SecretCommands::Rm { secret_id } => {
             if store.delete(&secret_id)? {
                 info!("removed secret {}", secret_id);
@@ -4095,7 +8471,7 @@ fn parse_profile_type(value: &str) -> Result<ProfileType> {
         "ssh" => Ok(ProfileType::Ssh),
         "telnet" => Ok(ProfileType::Telnet),
         "serial" => Ok(ProfileType::Serial),
-        _ => Err(anyhow!("invalid profile type: {value}")),
+        _ => Err(exit::invalid(format!("invalid profile type: {value}"))),
     }
 }
 
@@ -4104,7 +8480,7 @@ fn parse_danger(value: &str) -> Result<DangerLevel> {
         "normal" => Ok(DangerLevel::Normal),
         "high" => Ok(DangerLevel::High),
         "critical" => Ok(DangerLevel::Critical),
-        _ => Err(anyhow!("invalid danger level: {value}")),
+        _ => Err(exit::invalid(format!("invalid danger level: {value}"))),
     }
 }
 
@@ -4117,15 +8493,17 @@ fn parse_client_overrides(raw: Option<String>) -> Result<Option<ClientOverrides>
 
 fn resolve_client_for(
     kind: ClientKind,
-    profile_overrides: Option<&ClientOverrides>,
+    profile: &Profile,
     store: &ProfileStore,
 ) -> Result<PathBuf> {
-    ssh::resolve_client_for(kind, profile_overrides, store.conn()).map_err(Into::into)
+    ssh::resolve_client_for(kind, profile, store.conn()).map_err(Into::into)
 }
 
 fn ensure_ssh_profile(profile: &Profile, op: &str) -> Result<()> {
     if profile.profile_type != ProfileType::Ssh {
-        Err(anyhow!("{op} only supports SSH profiles for now"))
+        Err(exit::invalid(format!(
+            "{op} only supports SSH profiles for now"
+        )))
     } else {
         Ok(())
     }
@@ -4158,6 +8536,15 @@ fn confirm_agent_add(key_path: &Path) -> Result<bool> {
     Ok(input.trim().eq_ignore_ascii_case("yes"))
 }
 
+fn confirm_agent_remove(key_path: &Path) -> Result<bool> {
+    println!("About to remove key from ssh-agent: {}", key_path.display());
+    print!("Type 'yes' to continue: ");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().eq_ignore_ascii_case("yes"))
+}
+
 fn confirm_agent_clear() -> Result<bool> {
     println!("About to remove all keys from ssh-agent.");
     print!("Type 'yes' to continue: ");
@@ -4196,16 +8583,132 @@ fn handle_ssh_add_output(output: std::process::Output, label: &str) -> Result<()
     Err(anyhow!("{label} failed: {detail}"))
 }
 
-fn confirm_danger(profile: &Profile) -> Result<bool> {
+/// The global `--reason`, given to every policy or maintenance window that asks for one.
+static POLICY_REASON: OnceLock<Option<String>> = OnceLock::new();
+
+/// Cleared by the global `--no-interactive`.
+static INTERACTIVE: OnceLock<bool> = OnceLock::new();
+
+/// `profile_id`, or one picked with [`picker`] when it was left out.
+fn profile_id_or_pick(store: &ProfileStore, profile_id: Option<String>) -> Result<String> {
+    picker::profile_id_or_pick(
+        store,
+        profile_id,
+        INTERACTIVE.get().copied().unwrap_or(true),
+    )
+}
+
+/// Applies maintenance windows and `policy.danger` to `action`, then the danger
+/// confirmation, then asks for (or takes `--reason`) a reason when the policy or an open
+/// maintenance window wants one and logs it.
+fn ensure_allowed(profile: &Profile, action: PolicyAction<'_>) -> Result<()> {
+    let conn = db::init_connection()?;
+    let window = maintenance::active_for(&conn, profile, now_ms())?;
+    if let Some(window) = window
+        .as_ref()
+        .filter(|window| window.mode == WindowMode::Block)
+    {
+        return Err(window.denied(profile, action.as_str()).into());
+    }
+    let verdict = policy::check(&conn, profile, &action, now_ms())?;
+    confirm_profile(profile, verdict.confirm)?;
+    if verdict.reason || window.is_some() {
+        if let Some(window) = &window {
+            eprintln!(
+                "{}",
+                output::paint(
+                    &format!(
+                        "Maintenance window {} ({}) covers {}.",
+                        window.id, window.span, profile.profile_id
+                    ),
+                    Tone::Warn
+                )
+            );
+        }
+        let reason = policy_reason(profile, &action)?;
+        if verdict.reason {
+            policy::record_reason(&conn, profile, &action, &reason)?;
+        }
+        if let Some(window) = &window {
+            maintenance::record_override(&conn, profile, window, action.as_str(), &reason)?;
+        }
+    }
+    Ok(())
+}
+
+fn policy_reason(profile: &Profile, action: &PolicyAction<'_>) -> Result<String> {
+    if let Some(reason) = POLICY_REASON
+        .get()
+        .and_then(Option::as_deref)
+        .map(str::trim)
+        .filter(|reason| !reason.is_empty())
+    {
+        return Ok(reason.to_string());
+    }
+    if !io::stdin().is_terminal() {
+        return Err(exit::policy_denied(format!(
+            "{} on {} profile {} requires a reason; pass --reason",
+            action.as_str(),
+            profile.danger_level,
+            profile.profile_id
+        )));
+    }
+    print!("Reason for {} on {}: ", action.as_str(), profile.profile_id);
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let reason = input.trim();
+    if reason.is_empty() {
+        return Err(exit::policy_denied("a reason is required"));
+    }
+    Ok(reason.to_string())
+}
+
+/// Step commands of `cmdset_id` as stored, for `policy.danger` command checks.
+pub(crate) fn cmdset_commands(cmdset_store: &CmdSetStore, cmdset_id: &str) -> Result<Vec<String>> {
+    Ok(cmdset_store
+        .list_steps(cmdset_id)?
+        .into_iter()
+        .map(|step| step.cmd)
+        .collect())
+}
+
+fn ensure_danger_confirmed(profile: &Profile) -> Result<()> {
+    confirm_profile(profile, false)
+}
+
+/// Asks for confirmation on critical profiles, on those `confirm.token` covers, and on
+/// any profile when `always` is set by a policy.
+fn confirm_profile(profile: &Profile, always: bool) -> Result<()> {
+    let token = settings::confirm_token_required(&db::init_connection()?, profile)?
+        .then(util::confirmation_token);
+    if (always || profile.danger_level == DangerLevel::Critical || token.is_some())
+        && !confirm_danger(profile, token.as_deref())?
+    {
+        println!("Aborted by user.");
+        return Err(exit::policy_denied(format!(
+            "{} profile not confirmed: {}",
+            profile.danger_level, profile.profile_id
+        )));
+    }
+    Ok(())
+}
+
+/// Asks for `yes`, or for `token` when `confirm.token` covers the profile's danger level.
+fn confirm_danger(profile: &Profile, token: Option<&str>) -> Result<bool> {
+    let env = settings::get_current_env(&db::init_connection()?)?
+        .map(|env| format!(" [env: {env}]"))
+        .unwrap_or_default();
     println!(
-        "Profile '{}' is marked critical. Proceed with connect to {}@{}:{} ?",
-        profile.profile_id, profile.user, profile.host, profile.port
+        "Profile '{}' is marked {}{env}. Proceed with connect to {}@{}:{} ?",
+        profile.profile_id, profile.danger_level, profile.user, profile.host, profile.port
     );
-    print!("Type 'yes' to continue: ");
+    let expected = token.unwrap_or("yes");
+    print!("Type '{expected}' to continue: ");
     io::stdout().flush()?;
     let mut input = String::new();
     io::stdin().read_line(&mut input)?;
-    Ok(input.trim().eq_ignore_ascii_case("yes"))
+    Ok(input.trim().eq_ignore_ascii_case(expected))
 }
 
 fn run_with_timeout(mut cmd: Command, timeout: Duration) -> Result<std::process::Output> {
@@ -4216,7 +8719,10 @@ fn run_with_timeout(mut cmd: Command, timeout: Duration) -> Result<std::process:
     if status.is_none() {
         let _ = child.kill();
         let _ = child.wait();
-        return Err(anyhow!("timeout after {}ms", timeout.as_millis()));
+        return Err(exit::remote_failure(format!(
+            "timeout after {}ms",
+            timeout.as_millis()
+        )));
     }
     child
         .wait_with_output()
@@ -4263,12 +8769,48 @@ fn prompt_password(prompt: &str) -> Result<String> {
     Ok(pw)
 }
 
+/// The master key from a running, unlocked `td daemon`, else from a password prompt.
 fn load_master_prompt(store: &SecretStore) -> Result<tdcore::crypto::MasterKey> {
+    if let Some(master) = DaemonClient::connect()
+        .ok()
+        .flatten()
+        .and_then(|client| client.master_key().ok().flatten())
+    {
+        return Ok(master);
+    }
     let password = prompt_password("Master password: ")?;
     let master = store.load_master(&password)?;
     Ok(master)
 }
 
+/// Reveals the profile's `ssh.password_secret`, or else its linked `password` secret, when
+/// `td run` will use the native backend.
+fn native_ssh_password(
+    store: &ProfileStore,
+    profile: &Profile,
+) -> Result<Option<Zeroizing<String>>> {
+    let backend = settings::get_ssh_backend(store.conn(), &profile.profile_id)?;
+    if backend != settings::SshBackend::Native {
+        return Ok(None);
+    }
+    if !ssh::NATIVE_BACKEND_AVAILABLE {
+        return Err(exit::invalid(format!(
+            "{} is native, but this td was built without the ssh-native feature",
+            settings::SSH_BACKEND_KEY
+        )));
+    }
+    let secrets = SecretStore::new(db::init_connection()?);
+    let secret_id = match settings::get_ssh_password_secret(store.conn(), &profile.profile_id)? {
+        Some(secret_id) => secret_id,
+        None => match secrets.login_secret_for(&profile.profile_id)? {
+            Some(secret) => secret.secret_id,
+            None => return Ok(None),
+        },
+    };
+    let master = load_master_prompt(&secrets)?;
+    Ok(Some(Zeroizing::new(secrets.reveal(&master, &secret_id)?)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -4332,6 +8874,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parses_secret_ls_for_a_profile() {
+        let cli = Cli::try_parse_from(["td", "secret", "ls", "--profile", "p_db"])
+            .expect("parses secret ls");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Secret {
+                command: SecretCommands::List { profile: Some(ref id) },
+            }) if id == "p_db"
+        ));
+    }
+
     #[test]
     fn parse_helpers_validate_known_values() {
         assert!(parse_profile_type("ssh").is_ok());
@@ -4363,995 +8917,1673 @@ mod tests {
             Some(Commands::Profile {
                 command: ProfileCommands::Edit(args),
             }) => {
-                assert_eq!(args.profile_id, "p1");
-                assert_eq!(args.name.as_deref(), Some("new"));
-                assert_eq!(args.host.as_deref(), Some("example.net"));
-                assert!(args.clear_group);
-                assert!(args.clear_note);
-                assert!(args.clear_client_overrides);
-                assert_eq!(
-                    args.tags,
-                    Some(vec!["alpha".to_string(), "beta".to_string()])
-                );
+                assert_eq!(args.profile_id, "p1");
+                assert_eq!(args.name.as_deref(), Some("new"));
+                assert_eq!(args.host.as_deref(), Some("example.net"));
+                assert!(args.clear_group);
+                assert!(args.clear_note);
+                assert!(args.clear_client_overrides);
+                assert_eq!(
+                    args.tags,
+                    Some(vec!["alpha".to_string(), "beta".to_string()])
+                );
+            }
+            _ => panic!("expected profile edit command"),
+        }
+    }
+
+    #[test]
+    fn parses_config_set_client() {
+        let cli = Cli::try_parse_from([
+            "td",
+            "config",
+            "set-client",
+            "--ssh",
+            "/usr/bin/ssh",
+            "--clear-all",
+        ])
+        .expect("parses config set-client");
+
+        match cli.command {
+            Some(Commands::Config {
+                command: ConfigCommands::SetClient(args),
+            }) => {
+                assert_eq!(args.ssh.as_deref(), Some("/usr/bin/ssh"));
+                assert!(args.clear_all);
+            }
+            _ => panic!("expected config set-client command"),
+        }
+    }
+
+    #[test]
+    fn parses_config_set_allow_insecure_transfers() {
+        let cli = Cli::try_parse_from(["td", "config", "set", "allow_insecure_transfers", "true"])
+            .expect("parses config set");
+
+        match cli.command {
+            Some(Commands::Config {
+                command: ConfigCommands::Set(args),
+            }) => {
+                assert_eq!(args.key.as_deref(), Some("allow_insecure_transfers"));
+                assert_eq!(args.value.as_deref(), Some("true"));
+            }
+            _ => panic!("expected config set command"),
+        }
+    }
+
+    #[test]
+    fn parses_config_ui() {
+        let cli = Cli::try_parse_from(["td", "config", "ui"]).expect("parses config ui");
+
+        match cli.command {
+            Some(Commands::Config {
+                command: ConfigCommands::Ui,
+            }) => {}
+            _ => panic!("expected config ui command"),
+        }
+    }
+
+    #[test]
+    fn parses_configset_add_with_file() {
+        let cli = Cli::try_parse_from([
+            "td",
+            "configset",
+            "add",
+            "--name",
+            "dotfiles",
+            "--file",
+            "src=./.bashrc,dest=~/.bashrc,mode=644,when=changed",
+        ])
+        .expect("parses configset add");
+
+        match cli.command {
+            Some(Commands::ConfigSet {
+                command: ConfigSetCommands::Add(args),
+            }) => {
+                assert_eq!(args.name, "dotfiles");
+                assert_eq!(args.file.len(), 1);
+            }
+            _ => panic!("expected configset add command"),
+        }
+    }
+
+    #[test]
+    fn parses_config_apply() {
+        let cli = Cli::try_parse_from([
+            "td", "config", "apply", "p1", "cfg_main", "--plan", "--backup", "--via", "sftp",
+        ])
+        .expect("parses config apply");
+
+        match cli.command {
+            Some(Commands::Config {
+                command: ConfigCommands::Apply(args),
+            }) => {
+                assert_eq!(args.profile_id, "p1");
+                assert_eq!(args.config_id, "cfg_main");
+                assert!(args.plan);
+                assert!(args.backup);
+                assert_eq!(args.via, "sftp");
+                assert!(!args.i_know_its_insecure);
+            }
+            _ => panic!("expected config apply command"),
+        }
+    }
+
+    #[test]
+    fn parses_env_set() {
+        let cli = Cli::try_parse_from(["td", "env", "set", "work.ssh_auth_order", "agent,keys"])
+            .expect("parses env set");
+
+        match cli.command {
+            Some(Commands::Env {
+                command: EnvCommands::Set(args),
+            }) => {
+                assert_eq!(args.name_key, "work.ssh_auth_order");
+                assert_eq!(args.value, "agent,keys");
+            }
+            _ => panic!("expected env set command"),
+        }
+    }
+
+    #[test]
+    fn parses_env_use_clear_and_rm() {
+        let cli = Cli::try_parse_from(["td", "env", "use", "--clear"]).expect("parses env use");
+        match cli.command {
+            Some(Commands::Env {
+                command: EnvCommands::Use { name, clear },
+            }) => assert!(name.is_none() && clear),
+            _ => panic!("expected env use command"),
+        }
+        assert!(Cli::try_parse_from(["td", "env", "use"]).is_err());
+        assert!(Cli::try_parse_from(["td", "env", "use", "work", "--clear"]).is_err());
+        let cli = Cli::try_parse_from(["td", "env", "rm", "work", "--yes"]).expect("parses env rm");
+        match cli.command {
+            Some(Commands::Env {
+                command: EnvCommands::Rm { name, yes },
+            }) => assert!(name == "work" && yes),
+            _ => panic!("expected env rm command"),
+        }
+    }
+
+    #[test]
+    fn parses_exec_command() {
+        let cli = Cli::try_parse_from([
+            "td",
+            "exec",
+            "p1",
+            "--timeout-ms",
+            "5000",
+            "--json",
+            "--parser",
+            "json",
+            "--",
+            "echo",
+            "hello",
+        ])
+        .expect("parses exec");
+
+        match cli.command {
+            Some(Commands::Exec {
+                profile_id,
+                timeout_ms,
+                json,
+                parser,
+                cmd,
+            }) => {
+                assert_eq!(profile_id.as_deref(), Some("p1"));
+                assert_eq!(timeout_ms, Some(5000));
+                assert!(json);
+                assert_eq!(parser.as_deref(), Some("json"));
+                assert_eq!(cmd, vec!["echo".to_string(), "hello".to_string()]);
+            }
+            _ => panic!("expected exec command"),
+        }
+    }
+
+    #[test]
+    fn parses_run_command() {
+        let cli = Cli::try_parse_from(["td", "run", "p1", "c_main", "--json"]).expect("parses run");
+
+        match cli.command {
+            Some(Commands::Run(args)) => {
+                assert_eq!(args.targets, ["p1", "c_main"]);
+                assert!(args.profiles.is_empty());
+                assert!(args.json);
+                assert!(args.report.is_none());
+                assert!(!args.force);
+                assert!(args.secret_env.is_empty());
+            }
+            _ => panic!("expected run command"),
+        }
+
+        let cli = Cli::try_parse_from([
+            "td",
+            "run",
+            "c_main",
+            "--profile",
+            "p1,p2",
+            "--profile",
+            "p3",
+            "--group",
+            "web",
+            "--tag",
+            "prod",
+        ])
+        .expect("parses run with selectors");
+        match cli.command {
+            Some(Commands::Run(args)) => {
+                assert_eq!(args.targets, ["c_main"]);
+                assert_eq!(args.profiles, ["p1", "p2", "p3"]);
+                assert_eq!(args.group.as_deref(), Some("web"));
+                assert_eq!(args.tag, ["prod"]);
+            }
+            _ => panic!("expected run command"),
+        }
+    }
+
+    #[test]
+    fn parses_profile_export_and_import_conflict_choices() {
+        let cli = Cli::try_parse_from(["td", "export", "--profile", "p1", "--profile", "p2"])
+            .expect("parses export --profile");
+        match cli.command {
+            Some(Commands::Export(args)) => assert_eq!(args.profiles, ["p1", "p2"]),
+            _ => panic!("expected export command"),
+        }
+        let cli = Cli::try_parse_from(["td", "import", "--conflict", "overwrite", "a.json"])
+            .expect("parses import --conflict overwrite");
+        match cli.command {
+            Some(Commands::Import(args)) => {
+                assert!(matches!(args.conflict, ConflictArg::Overwrite))
+            }
+            _ => panic!("expected import command"),
+        }
+        assert!(
+            Cli::try_parse_from(["td", "export", "--profile", "p1", "--include-secrets"]).is_err()
+        );
+    }
+
+    #[test]
+    fn parses_connect_log_backend_conpty() {
+        let cli = Cli::try_parse_from(["td", "connect", "p1", "--log-backend", "conpty"])
+            .expect("parses connect --log-backend conpty");
+
+        match cli.command {
+            Some(Commands::Connect(args)) => {
+                assert_eq!(args.profile_id.as_deref(), Some("p1"));
+                assert_eq!(args.log_backend.as_deref(), Some("conpty"));
+                assert!(!args.dry_run);
+            }
+            _ => panic!("expected connect command"),
+        }
+    }
+
+    #[test]
+    fn connect_log_backend_rejects_non_conpty() {
+        let err = parse_connect_log_backend(Some("script".to_string())).unwrap_err();
+
+        assert!(err
+            .to_string()
+            .contains("--log-backend currently supports only explicit conpty"));
+    }
+
+    #[test]
+    fn parses_recent_command() {
+        let cli =
+            Cli::try_parse_from(["td", "recent", "--limit", "5", "--json"]).expect("parses recent");
+
+        match cli.command {
+            Some(Commands::Recent { limit, json }) => {
+                assert_eq!(limit, 5);
+                assert!(json);
+            }
+            _ => panic!("expected recent command"),
+        }
+    }
+
+    #[test]
+    fn parses_session_list_command() {
+        let cli = Cli::try_parse_from(["td", "session", "list", "--limit", "5", "--json"])
+            .expect("parses session list");
+
+        match cli.command {
+            Some(Commands::Session {
+                command: SessionCommands::List(args),
+            }) => {
+                assert_eq!(args.limit, 5);
+                assert!(args.json);
             }
-            _ => panic!("expected profile edit command"),
+            _ => panic!("expected session list command"),
         }
     }
 
     #[test]
-    fn parses_config_set_client() {
-        let cli = Cli::try_parse_from([
-            "td",
-            "config",
-            "set-client",
-            "--ssh",
-            "/usr/bin/ssh",
-            "--clear-all",
-        ])
-        .expect("parses config set-client");
+    fn parses_session_stats_command() {
+        let cli = Cli::try_parse_from(["td", "session", "stats", "--json"]).expect("parses stats");
 
         match cli.command {
-            Some(Commands::Config {
-                command: ConfigCommands::SetClient(args),
+            Some(Commands::Session {
+                command: SessionCommands::Stats(args),
             }) => {
-                assert_eq!(args.ssh.as_deref(), Some("/usr/bin/ssh"));
-                assert!(args.clear_all);
+                assert!(args.json);
             }
-            _ => panic!("expected config set-client command"),
+            _ => panic!("expected session stats command"),
         }
     }
 
     #[test]
-    fn parses_config_set_allow_insecure_transfers() {
-        let cli = Cli::try_parse_from(["td", "config", "set", "allow_insecure_transfers", "true"])
-            .expect("parses config set");
+    fn parses_session_doctor_command() {
+        let cli = Cli::try_parse_from(["td", "session", "doctor", "--json"])
+            .expect("parses session doctor");
 
         match cli.command {
-            Some(Commands::Config {
-                command: ConfigCommands::Set(args),
+            Some(Commands::Session {
+                command: SessionCommands::Doctor(args),
             }) => {
-                assert_eq!(args.key.as_deref(), Some("allow_insecure_transfers"));
-                assert_eq!(args.value.as_deref(), Some("true"));
+                assert!(args.json);
             }
-            _ => panic!("expected config set command"),
+            _ => panic!("expected session doctor command"),
         }
     }
 
     #[test]
-    fn parses_config_ui() {
-        let cli = Cli::try_parse_from(["td", "config", "ui"]).expect("parses config ui");
+    fn parses_session_path_command() {
+        let cli = Cli::try_parse_from(["td", "session", "path", "sl_abc123"])
+            .expect("parses session path");
 
         match cli.command {
-            Some(Commands::Config {
-                command: ConfigCommands::Ui,
-            }) => {}
-            _ => panic!("expected config ui command"),
+            Some(Commands::Session {
+                command: SessionCommands::Path { session_id },
+            }) => {
+                assert_eq!(session_id, "sl_abc123");
+            }
+            _ => panic!("expected session path command"),
         }
     }
 
     #[test]
-    fn parses_configset_add_with_file() {
+    fn parses_session_prune_command() {
         let cli = Cli::try_parse_from([
             "td",
-            "configset",
-            "add",
-            "--name",
-            "dotfiles",
-            "--file",
-            "src=./.bashrc,dest=~/.bashrc,mode=644,when=changed",
+            "session",
+            "prune",
+            "--older-than",
+            "30d",
+            "--keep-last",
+            "100",
+            "--dry-run",
         ])
-        .expect("parses configset add");
+        .expect("parses session prune");
 
         match cli.command {
-            Some(Commands::ConfigSet {
-                command: ConfigSetCommands::Add(args),
+            Some(Commands::Session {
+                command: SessionCommands::Prune(args),
             }) => {
-                assert_eq!(args.name, "dotfiles");
-                assert_eq!(args.file.len(), 1);
+                assert_eq!(args.older_than.as_deref(), Some("30d"));
+                assert_eq!(args.keep_last, Some(100));
+                assert!(args.dry_run);
+                assert!(!args.yes);
+                assert!(!args.json);
             }
-            _ => panic!("expected configset add command"),
+            _ => panic!("expected session prune command"),
         }
-    }
 
-    #[test]
-    fn parses_config_apply() {
         let cli = Cli::try_parse_from([
-            "td", "config", "apply", "p1", "cfg_main", "--plan", "--backup", "--via", "sftp",
+            "td",
+            "session",
+            "prune",
+            "--keep-last",
+            "100",
+            "--yes",
+            "--json",
         ])
-        .expect("parses config apply");
+        .expect("parses session prune --yes --json");
+        match cli.command {
+            Some(Commands::Session {
+                command: SessionCommands::Prune(args),
+            }) => {
+                assert_eq!(args.older_than, None);
+                assert_eq!(args.keep_last, Some(100));
+                assert!(!args.dry_run);
+                assert!(args.yes);
+                assert!(args.json);
+            }
+            _ => panic!("expected session prune command"),
+        }
+    }
+
+    #[test]
+    fn parses_session_conpty_test_command() {
+        let cli = Cli::try_parse_from(["td", "session", "conpty-test", "p_test"])
+            .expect("parses session conpty-test");
 
         match cli.command {
-            Some(Commands::Config {
-                command: ConfigCommands::Apply(args),
+            Some(Commands::Session {
+                command: SessionCommands::ConptyTest(args),
             }) => {
-                assert_eq!(args.profile_id, "p1");
-                assert_eq!(args.config_id, "cfg_main");
-                assert!(args.plan);
-                assert!(args.backup);
-                assert_eq!(args.via, "sftp");
-                assert!(!args.i_know_its_insecure);
+                assert_eq!(args.profile_id, "p_test");
+                assert_eq!(args.startup_timeout_sec, 10);
+                assert!(!args.debug);
             }
-            _ => panic!("expected config apply command"),
+            _ => panic!("expected session conpty-test command"),
         }
     }
 
     #[test]
-    fn parses_env_set() {
-        let cli = Cli::try_parse_from(["td", "env", "set", "work.ssh_auth_order", "agent,keys"])
-            .expect("parses env set");
+    fn parses_session_conpty_test_debug_command() {
+        let cli = Cli::try_parse_from(["td", "session", "conpty-test", "p_test", "--debug"])
+            .expect("parses session conpty-test --debug");
 
         match cli.command {
-            Some(Commands::Env {
-                command: EnvCommands::Set(args),
+            Some(Commands::Session {
+                command: SessionCommands::ConptyTest(args),
             }) => {
-                assert_eq!(args.name_key, "work.ssh_auth_order");
-                assert_eq!(args.value, "agent,keys");
+                assert_eq!(args.profile_id, "p_test");
+                assert_eq!(args.startup_timeout_sec, 10);
+                assert!(args.debug);
             }
-            _ => panic!("expected env set command"),
+            _ => panic!("expected session conpty-test command"),
         }
     }
 
     #[test]
-    fn parses_exec_command() {
+    fn parses_session_conpty_test_startup_timeout_command() {
         let cli = Cli::try_parse_from([
             "td",
-            "exec",
-            "p1",
-            "--timeout-ms",
-            "5000",
-            "--json",
-            "--parser",
-            "json",
-            "--",
-            "echo",
-            "hello",
+            "session",
+            "conpty-test",
+            "p_test",
+            "--startup-timeout-sec",
+            "0",
         ])
-        .expect("parses exec");
+        .expect("parses session conpty-test --startup-timeout-sec");
 
         match cli.command {
-            Some(Commands::Exec {
-                profile_id,
-                timeout_ms,
-                json,
-                parser,
-                cmd,
+            Some(Commands::Session {
+                command: SessionCommands::ConptyTest(args),
             }) => {
-                assert_eq!(profile_id, "p1");
-                assert_eq!(timeout_ms, Some(5000));
-                assert!(json);
-                assert_eq!(parser.as_deref(), Some("json"));
-                assert_eq!(cmd, vec!["echo".to_string(), "hello".to_string()]);
+                assert_eq!(args.profile_id, "p_test");
+                assert_eq!(args.startup_timeout_sec, 0);
+                assert_eq!(conpty_startup_timeout(args.startup_timeout_sec), None);
             }
-            _ => panic!("expected exec command"),
+            _ => panic!("expected session conpty-test command"),
         }
     }
 
     #[test]
-    fn parses_run_command() {
-        let cli = Cli::try_parse_from(["td", "run", "p1", "c_main", "--json"]).expect("parses run");
+    fn conpty_test_invocation_rejects_non_ssh_profile() {
+        let store = ProfileStore::new(db::init_in_memory().unwrap());
+        store
+            .insert(NewProfile {
+                profile_id: Some("p_telnet".to_string()),
+                name: "Telnet".to_string(),
+                profile_type: ProfileType::Telnet,
+                host: "example.com".to_string(),
+                port: 23,
+                user: "alice".to_string(),
+                danger_level: DangerLevel::Normal,
+                group: None,
+                tags: Vec::new(),
+                note: None,
+                initial_send: None,
+                client_overrides: None,
+            })
+            .unwrap();
+
+        let err = build_conpty_test_invocation(&store, "p_telnet").unwrap_err();
+
+        assert!(err.to_string().contains("SSH requires an SSH profile"));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn conpty_test_is_unsupported_on_non_windows() {
+        let store = ProfileStore::new(db::init_in_memory().unwrap());
+
+        let err = handle_session_conpty_test(
+            &store,
+            SessionConptyTestArgs {
+                profile_id: "p_test".to_string(),
+                startup_timeout_sec: 10,
+                debug: false,
+            },
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("unsupported"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn conpty_key_mapping_handles_text_arrows_and_ctrl_c() {
+        use crossterm::event::KeyEventState;
+
+        assert_eq!(
+            key_event_to_pty_bytes(KeyEvent {
+                code: KeyCode::Char('x'),
+                modifiers: KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                state: KeyEventState::NONE,
+            }),
+            Some(b"x".to_vec())
+        );
+        assert_eq!(
+            key_event_to_pty_bytes(KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: KeyEventState::NONE,
+            }),
+            Some(vec![0x03])
+        );
+        assert_eq!(
+            key_event_to_pty_bytes(KeyEvent {
+                code: KeyCode::Left,
+                modifiers: KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                state: KeyEventState::NONE,
+            }),
+            Some(b"\x1b[D".to_vec())
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn conpty_pty_size_clamps_zero_dimensions() {
+        let size = pty_size(0, 0);
 
-        match cli.command {
-            Some(Commands::Run {
-                profile_id,
-                cmdset_id,
-                json,
-            }) => {
-                assert_eq!(profile_id, "p1");
-                assert_eq!(cmdset_id, "c_main");
-                assert!(json);
-            }
-            _ => panic!("expected run command"),
-        }
+        assert_eq!(size.cols, 1);
+        assert_eq!(size.rows, 1);
+        assert_eq!(size.pixel_width, 0);
+        assert_eq!(size.pixel_height, 0);
     }
 
+    #[cfg(windows)]
     #[test]
-    fn parses_connect_log_backend_conpty() {
-        let cli = Cli::try_parse_from(["td", "connect", "p1", "--log-backend", "conpty"])
-            .expect("parses connect --log-backend conpty");
-
-        match cli.command {
-            Some(Commands::Connect(args)) => {
-                assert_eq!(args.profile_id, "p1");
-                assert_eq!(args.log_backend.as_deref(), Some("conpty"));
-            }
-            _ => panic!("expected connect command"),
-        }
+    fn conpty_exit_code_saturates_to_metadata_range() {
+        assert_eq!(
+            conpty_exit_code(&portable_pty::ExitStatus::with_exit_code(0)),
+            0
+        );
+        assert_eq!(
+            conpty_exit_code(&portable_pty::ExitStatus::with_exit_code(255)),
+            255
+        );
+        assert_eq!(
+            conpty_exit_code(&portable_pty::ExitStatus::with_exit_code(u32::MAX)),
+            i32::MAX
+        );
     }
 
+    #[cfg(windows)]
     #[test]
-    fn connect_log_backend_rejects_non_conpty() {
-        let err = parse_connect_log_backend(Some("script".to_string())).unwrap_err();
+    fn conpty_output_inspector_ignores_cursor_position_query_as_startup_output() {
+        let mut inspector = ConptyOutputInspector::default();
 
-        assert!(err
-            .to_string()
-            .contains("--log-backend currently supports only explicit conpty"));
+        let inspection = inspector.inspect(b"\x1b[6n");
+
+        assert!(!inspection.has_visible_output);
+        assert_eq!(
+            inspection.synthetic_responses,
+            vec![ConptySyntheticResponse::CursorPosition]
+        );
     }
 
+    #[cfg(windows)]
     #[test]
-    fn parses_recent_command() {
-        let cli =
-            Cli::try_parse_from(["td", "recent", "--limit", "5", "--json"]).expect("parses recent");
+    fn conpty_output_inspector_handles_split_cursor_position_query() {
+        let mut inspector = ConptyOutputInspector::default();
 
-        match cli.command {
-            Some(Commands::Recent { limit, json }) => {
-                assert_eq!(limit, 5);
-                assert!(json);
-            }
-            _ => panic!("expected recent command"),
-        }
+        let first = inspector.inspect(b"\x1b[");
+        let second = inspector.inspect(b"6n");
+
+        assert!(!first.has_visible_output);
+        assert!(first.synthetic_responses.is_empty());
+        assert!(!second.has_visible_output);
+        assert_eq!(
+            second.synthetic_responses,
+            vec![ConptySyntheticResponse::CursorPosition]
+        );
     }
 
+    #[cfg(windows)]
     #[test]
-    fn parses_session_list_command() {
-        let cli = Cli::try_parse_from(["td", "session", "list", "--limit", "5", "--json"])
-            .expect("parses session list");
+    fn conpty_output_inspector_treats_prompt_text_as_startup_output() {
+        let mut inspector = ConptyOutputInspector::default();
 
-        match cli.command {
-            Some(Commands::Session {
-                command: SessionCommands::List(args),
-            }) => {
-                assert_eq!(args.limit, 5);
-                assert!(args.json);
-            }
-            _ => panic!("expected session list command"),
-        }
+        let inspection = inspector.inspect(b"\x1b[?2004hPassword: ");
+
+        assert!(inspection.has_visible_output);
+        assert!(inspection.synthetic_responses.is_empty());
     }
 
+    #[cfg(windows)]
     #[test]
-    fn parses_session_stats_command() {
-        let cli = Cli::try_parse_from(["td", "session", "stats", "--json"]).expect("parses stats");
+    fn conpty_loop_receive_synthesizes_startup_timeout_without_worker_event() {
+        let (_tx, rx) = mpsc::channel();
+        let first_output_received = AtomicBool::new(false);
 
-        match cli.command {
-            Some(Commands::Session {
-                command: SessionCommands::Stats(args),
-            }) => {
-                assert!(args.json);
-            }
-            _ => panic!("expected session stats command"),
-        }
+        let message = recv_conpty_loop_message(
+            &rx,
+            &first_output_received,
+            Some(Instant::now() + Duration::from_millis(1)),
+        )
+        .expect("startup timeout message");
+
+        assert!(matches!(
+            message,
+            ConptyLoopMessage::Event(ConptyEvent::StartupTimeout)
+        ));
     }
 
+    #[cfg(windows)]
     #[test]
-    fn parses_session_doctor_command() {
-        let cli = Cli::try_parse_from(["td", "session", "doctor", "--json"])
-            .expect("parses session doctor");
+    fn conpty_log_sanitizer_strips_color_and_clear_sequences() {
+        let mut sanitizer = ConptyLogSanitizer::default();
 
-        match cli.command {
-            Some(Commands::Session {
-                command: SessionCommands::Doctor(args),
-            }) => {
-                assert!(args.json);
-            }
-            _ => panic!("expected session doctor command"),
-        }
+        let mut out = sanitizer.push(b"drwx <ESC>");
+        out.extend(sanitizer.push(b"\x1b[34m.\x1b[m/\x1b[K\n"));
+
+        assert_eq!(String::from_utf8(out).unwrap(), "drwx <ESC>./\n");
     }
 
+    #[cfg(windows)]
     #[test]
-    fn parses_session_path_command() {
-        let cli = Cli::try_parse_from(["td", "session", "path", "sl_abc123"])
-            .expect("parses session path");
+    fn conpty_log_sanitizer_turns_cursor_forward_into_spacing() {
+        let mut sanitizer = ConptyLogSanitizer::default();
 
-        match cli.command {
-            Some(Commands::Session {
-                command: SessionCommands::Path { session_id },
-            }) => {
-                assert_eq!(session_id, "sl_abc123");
-            }
-            _ => panic!("expected session path command"),
-        }
+        let out = sanitizer.push(b"nico@ao03:~$\x1b[1Cdf -h\r\n");
+
+        assert_eq!(String::from_utf8(out).unwrap(), "nico@ao03:~$ df -h\n");
     }
 
+    #[cfg(windows)]
     #[test]
-    fn parses_session_prune_command() {
-        let cli = Cli::try_parse_from([
-            "td",
-            "session",
-            "prune",
-            "--older-than",
-            "30d",
-            "--keep-last",
-            "100",
-            "--dry-run",
-        ])
-        .expect("parses session prune");
+    fn conpty_log_sanitizer_applies_prompt_line_clear_redraw() {
+        let mut sanitizer = ConptyLogSanitizer::with_row(18);
 
-        match cli.command {
-            Some(Commands::Session {
-                command: SessionCommands::Prune(args),
-            }) => {
-                assert_eq!(args.older_than.as_deref(), Some("30d"));
-                assert_eq!(args.keep_last, Some(100));
-                assert!(args.dry_run);
-                assert!(!args.yes);
-                assert!(!args.json);
-            }
-            _ => panic!("expected session prune command"),
-        }
+        let mut out = sanitizer.push("nico@ao03:~$ ｌｌ".as_bytes());
+        out.extend(sanitizer.push(b"\x1b[18;16H\x1b[K\x1b[18;14H\x1b[K\x07ll\r"));
+        out.extend(sanitizer.push(b"\x1b[?2004l\n"));
 
-        let cli = Cli::try_parse_from([
-            "td",
-            "session",
-            "prune",
-            "--keep-last",
-            "100",
-            "--yes",
-            "--json",
-        ])
-        .expect("parses session prune --yes --json");
-        match cli.command {
-            Some(Commands::Session {
-                command: SessionCommands::Prune(args),
-            }) => {
-                assert_eq!(args.older_than, None);
-                assert_eq!(args.keep_last, Some(100));
-                assert!(!args.dry_run);
-                assert!(args.yes);
-                assert!(args.json);
-            }
-            _ => panic!("expected session prune command"),
-        }
+        assert_eq!(String::from_utf8(out).unwrap(), "nico@ao03:~$ ll\n");
     }
 
+    #[cfg(windows)]
     #[test]
-    fn parses_session_conpty_test_command() {
-        let cli = Cli::try_parse_from(["td", "session", "conpty-test", "p_test"])
-            .expect("parses session conpty-test");
+    fn conpty_log_sanitizer_splits_absolute_cursor_rows_into_lines() {
+        let mut sanitizer = ConptyLogSanitizer::default();
 
-        match cli.command {
-            Some(Commands::Session {
-                command: SessionCommands::ConptyTest(args),
-            }) => {
-                assert_eq!(args.profile_id, "p_test");
-                assert_eq!(args.startup_timeout_sec, 10);
-                assert!(!args.debug);
-            }
-            _ => panic!("expected session conpty-test command"),
-        }
+        let mut out = sanitizer.push(b"Welcome\x1b[3;1H * Documentation\n");
+        out.extend(sanitizer.finish());
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Welcome\n * Documentation\n"
+        );
     }
 
-    #[test]
-    fn parses_session_conpty_test_debug_command() {
-        let cli = Cli::try_parse_from(["td", "session", "conpty-test", "p_test", "--debug"])
-            .expect("parses session conpty-test --debug");
+    #[cfg(windows)]
+    #[test]
+    fn conpty_log_sanitizer_preserves_utf8_japanese_text() {
+        let mut sanitizer = ConptyLogSanitizer::default();
+
+        let out = sanitizer.push("6月 15日 日本語\n".as_bytes());
 
-        match cli.command {
-            Some(Commands::Session {
-                command: SessionCommands::ConptyTest(args),
-            }) => {
-                assert_eq!(args.profile_id, "p_test");
-                assert_eq!(args.startup_timeout_sec, 10);
-                assert!(args.debug);
-            }
-            _ => panic!("expected session conpty-test command"),
-        }
+        assert_eq!(String::from_utf8(out).unwrap(), "6月 15日 日本語\n");
     }
 
+    #[cfg(windows)]
     #[test]
-    fn parses_session_conpty_test_startup_timeout_command() {
-        let cli = Cli::try_parse_from([
-            "td",
-            "session",
-            "conpty-test",
-            "p_test",
-            "--startup-timeout-sec",
-            "0",
-        ])
-        .expect("parses session conpty-test --startup-timeout-sec");
+    fn session_log_display_text_sanitizes_existing_raw_conpty_logs() {
+        let raw = "Welcome\x1b[18;1H\
+                   nico@ao03:~$ ｌｌ\x1b[18;16H\x1b[K\x1b[18;14H\x1b[K\x07ll\r\x1b[?2004l\n\
+                   nico@ao03:~$\x1b[1Cdf -h\r\n\
+                   drwx \x1b[34m.\x1b[m/\x1b[K\n";
 
-        match cli.command {
-            Some(Commands::Session {
-                command: SessionCommands::ConptyTest(args),
-            }) => {
-                assert_eq!(args.profile_id, "p_test");
-                assert_eq!(args.startup_timeout_sec, 0);
-                assert_eq!(conpty_startup_timeout(args.startup_timeout_sec), None);
-            }
-            _ => panic!("expected session conpty-test command"),
-        }
+        let display = session_log_display_text(raw.as_bytes());
+
+        assert_eq!(
+            display,
+            "Welcome\nnico@ao03:~$ ll\nnico@ao03:~$ df -h\ndrwx ./\n"
+        );
     }
 
     #[test]
-    fn conpty_test_invocation_rejects_non_ssh_profile() {
-        let store = ProfileStore::new(db::init_in_memory().unwrap());
-        store
-            .insert(NewProfile {
-                profile_id: Some("p_telnet".to_string()),
-                name: "Telnet".to_string(),
-                profile_type: ProfileType::Telnet,
-                host: "example.com".to_string(),
-                port: 23,
-                user: "alice".to_string(),
-                danger_level: DangerLevel::Normal,
-                group: None,
-                tags: Vec::new(),
-                note: None,
-                initial_send: None,
-                client_overrides: None,
-            })
-            .unwrap();
+    fn session_show_capture_lines_include_host_only_warning() {
+        let metadata = session_log::SessionLogMetadata {
+            session_id: "sl_abc123".to_string(),
+            profile_id: "p_test".to_string(),
+            user: "alice".to_string(),
+            host: "example.com".to_string(),
+            port: 22,
+            started_at: 1000,
+            ended_at: 2000,
+            duration_ms: 1000,
+            exit_code: Some(0),
+            backend: session_log::SESSION_LOG_BACKEND_POWERSHELL_TRANSCRIPT.to_string(),
+            log_path: Some(PathBuf::from("sl_abc123.log")),
+            metadata_path: PathBuf::from("sl_abc123.json"),
+            status: "completed".to_string(),
+            reason: None,
+            failure_phase: None,
+            failure_reason: None,
+            content_capture: Some(session_log::SESSION_LOG_CONTENT_CAPTURE_BEST_EFFORT.to_string()),
+            content_capture_reliable: Some(false),
+            backend_status: None,
+            backend_warning: Some(
+                session_log::SESSION_LOG_BACKEND_WARNING_POWERSHELL_TRANSCRIPT.to_string(),
+            ),
+            content_capture_status: Some(
+                session_log::SESSION_LOG_CAPTURE_STATUS_HOST_ONLY_OR_EMPTY.to_string(),
+            ),
+            content_capture_warning: Some(
+                session_log::SESSION_LOG_CAPTURE_WARNING_NO_SSH_CONTENT.to_string(),
+            ),
+        };
 
-        let err = build_conpty_test_invocation(&store, "p_telnet").unwrap_err();
+        let lines = session_capture_lines(&metadata);
 
-        assert!(err.to_string().contains("SSH requires an SSH profile"));
+        assert!(lines.iter().any(|line| line == "backend_status: degraded"));
+        assert!(lines
+            .iter()
+            .any(|line| line == "content_capture: best_effort"));
+        assert!(lines
+            .iter()
+            .any(|line| line == "content_capture_reliable: false"));
+        assert!(lines
+            .iter()
+            .any(|line| line == "Content capture: host_only_or_empty"));
+        assert!(lines
+            .iter()
+            .any(|line| line == "Warning: No SSH terminal content appears to have been captured."));
     }
 
-    #[cfg(not(windows))]
     #[test]
-    fn conpty_test_is_unsupported_on_non_windows() {
-        let store = ProfileStore::new(db::init_in_memory().unwrap());
+    fn session_show_capture_lines_include_conpty_degraded_warning() {
+        let metadata = session_log::SessionLogMetadata {
+            session_id: "sl_abc123".to_string(),
+            profile_id: "p_test".to_string(),
+            user: "alice".to_string(),
+            host: "example.com".to_string(),
+            port: 22,
+            started_at: 100,
+            ended_at: 200,
+            duration_ms: 100,
+            exit_code: Some(0),
+            backend: session_log::SESSION_LOG_BACKEND_CONPTY.to_string(),
+            log_path: Some(PathBuf::from("sl_abc123.log")),
+            metadata_path: PathBuf::from("sl_abc123.json"),
+            status: "completed".to_string(),
+            reason: None,
+            failure_phase: None,
+            failure_reason: None,
+            content_capture: Some(session_log::SESSION_LOG_CONTENT_CAPTURE_TERMINAL_IO.to_string()),
+            content_capture_reliable: Some(true),
+            backend_status: Some(
+                session_log::SESSION_LOG_BACKEND_STATUS_EXPLICIT_READY.to_string(),
+            ),
+            backend_warning: Some(
+                session_log::SESSION_LOG_BACKEND_WARNING_CONPTY_EXPLICIT_NOT_AUTO.to_string(),
+            ),
+            content_capture_status: None,
+            content_capture_warning: None,
+        };
 
-        let err = handle_session_conpty_test(
-            &store,
-            SessionConptyTestArgs {
-                profile_id: "p_test".to_string(),
-                startup_timeout_sec: 10,
-                debug: false,
-            },
-        )
-        .unwrap_err();
+        let lines = session_capture_lines(&metadata);
 
-        assert!(err.to_string().contains("unsupported"));
+        assert!(lines
+            .iter()
+            .any(|line| line == "backend_status: explicit_ready"));
+        assert!(lines
+            .iter()
+            .any(|line| line == "content_capture: terminal_io"));
+        assert!(lines
+            .iter()
+            .any(|line| line == "content_capture_reliable: true"));
+        assert!(lines
+            .iter()
+            .any(|line| line
+                == "backend_warning: conpty_backend_is_explicit_and_not_selected_by_auto"));
     }
 
-    #[cfg(windows)]
     #[test]
-    fn conpty_key_mapping_handles_text_arrows_and_ctrl_c() {
-        use crossterm::event::KeyEventState;
-
-        assert_eq!(
-            key_event_to_pty_bytes(KeyEvent {
-                code: KeyCode::Char('x'),
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: KeyEventState::NONE,
-            }),
-            Some(b"x".to_vec())
-        );
+    fn session_log_path_display_ignores_warning_text() {
         assert_eq!(
-            key_event_to_pty_bytes(KeyEvent {
-                code: KeyCode::Char('c'),
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                state: KeyEventState::NONE,
-            }),
-            Some(vec![0x03])
+            format_session_log_path(Some(Path::new(
+                "not selected by auto; use session.log.backend=conpty explicitly."
+            ))),
+            "<none>"
         );
         assert_eq!(
-            key_event_to_pty_bytes(KeyEvent {
-                code: KeyCode::Left,
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: KeyEventState::NONE,
-            }),
-            Some(b"\x1b[D".to_vec())
+            format_session_log_path(Some(Path::new("sl_abc123.log"))),
+            "sl_abc123.log"
         );
+        assert_eq!(format_session_log_path(None), "<none>");
     }
 
-    #[cfg(windows)]
     #[test]
-    fn conpty_pty_size_clamps_zero_dimensions() {
-        let size = pty_size(0, 0);
-
-        assert_eq!(size.cols, 1);
-        assert_eq!(size.rows, 1);
-        assert_eq!(size.pixel_width, 0);
-        assert_eq!(size.pixel_height, 0);
+    fn session_list_table_cells_are_bounded() {
+        assert_eq!(table_cell("completed exit 0", 18), "completed exit 0");
+        assert_eq!(table_cell("abcdefghijklmnopqrstuvwxyz", 8), "abcdefg~");
     }
 
-    #[cfg(windows)]
     #[test]
-    fn conpty_exit_code_saturates_to_metadata_range() {
+    fn session_log_config_defaults_are_resolved() {
+        let conn = db::init_in_memory().unwrap();
+
         assert_eq!(
-            conpty_exit_code(&portable_pty::ExitStatus::with_exit_code(0)),
-            0
+            default_resolved_config_value(&conn, session_log::SESSION_LOG_ENABLED_KEY).unwrap(),
+            Some("false".to_string())
+        );
+        assert_eq!(
+            default_resolved_config_value(&conn, session_log::SESSION_LOG_BACKEND_KEY).unwrap(),
+            Some("auto".to_string())
         );
+    }
+
+    #[test]
+    fn session_stats_json_summary_uses_safe_fields() {
+        let stats = session_log::SessionLogStats {
+            log_dir: PathBuf::from("session-logs"),
+            total_sessions: 2,
+            total_log_bytes: 1234,
+            skipped_metadata: 1,
+            by_backend: BTreeMap::from([
+                (session_log::SESSION_LOG_BACKEND_CONPTY.to_string(), 1),
+                (
+                    session_log::SESSION_LOG_BACKEND_POWERSHELL_TRANSCRIPT.to_string(),
+                    1,
+                ),
+            ]),
+            by_status: BTreeMap::from([
+                ("completed".to_string(), 1),
+                ("completed_nonzero".to_string(), 1),
+            ]),
+            oldest_session: Some(session_log::SessionLogStatsSession {
+                session_id: "sl_old".to_string(),
+                started_at: 1000,
+            }),
+            newest_session: Some(session_log::SessionLogStatsSession {
+                session_id: "sl_new".to_string(),
+                started_at: 2000,
+            }),
+        };
+
+        let payload = session_stats_json(&stats);
+
+        assert_eq!(payload["log_directory"], "session-logs");
+        assert_eq!(payload["total_sessions"], 2);
+        assert_eq!(payload["total_log_bytes"], 1234);
+        assert_eq!(payload["skipped_metadata"], 1);
+        assert_eq!(payload["by_backend"]["conpty"], 1);
+        assert_eq!(payload["by_backend"]["powershell-transcript"], 1);
+        assert_eq!(payload["by_status"]["completed"], 1);
+        assert_eq!(payload["by_status"]["completed_nonzero"], 1);
+        assert_eq!(payload["oldest_session"]["session_id"], "sl_old");
         assert_eq!(
-            conpty_exit_code(&portable_pty::ExitStatus::with_exit_code(255)),
-            255
+            payload["oldest_session"]["started_at"],
+            "1970-01-01T00:00:01Z"
         );
+        assert_eq!(payload["newest_session"]["session_id"], "sl_new");
         assert_eq!(
-            conpty_exit_code(&portable_pty::ExitStatus::with_exit_code(u32::MAX)),
-            i32::MAX
+            payload["newest_session"]["started_at"],
+            "1970-01-01T00:00:02Z"
         );
+
+        let serialized = serde_json::to_string(&payload).unwrap();
+        assert!(!serialized.contains("terminal body with password"));
+        assert!(!serialized.contains("auth_args"));
+        assert!(!serialized.contains("command"));
+        assert!(!serialized.contains("private_key_path"));
+        assert!(!serialized.contains("password"));
+        assert!(!serialized.contains("secret"));
+        assert!(!serialized.contains("token"));
     }
 
-    #[cfg(windows)]
     #[test]
-    fn conpty_output_inspector_ignores_cursor_position_query_as_startup_output() {
-        let mut inspector = ConptyOutputInspector::default();
+    fn session_prune_dry_run_json_summary_uses_safe_fields() {
+        let candidate = test_prune_candidate("sl_old", 1000, "completed", 4096);
+        let plan = session_log::SessionPrunePlan {
+            log_dir: PathBuf::from("session-logs"),
+            candidates: vec![candidate],
+            skipped: vec![session_log::SessionPruneSkipped {
+                metadata_path: PathBuf::from("session-logs").join("sl_bad.json"),
+                reason: "malformed metadata".to_string(),
+            }],
+            planned_size_bytes: 4096,
+        };
+        let args = SessionPruneArgs {
+            older_than: Some("30d".to_string()),
+            keep_last: None,
+            dry_run: true,
+            yes: false,
+            json: true,
+        };
 
-        let inspection = inspector.inspect(b"\x1b[6n");
+        let payload = session_prune_plan_json(&plan, &args, true, false);
 
-        assert!(!inspection.has_visible_output);
-        assert_eq!(
-            inspection.synthetic_responses,
-            vec![ConptySyntheticResponse::CursorPosition]
-        );
+        assert_eq!(payload["dry_run"], true);
+        assert_eq!(payload["criteria"]["older_than"], "30d");
+        assert!(payload["criteria"]["keep_last"].is_null());
+        assert_eq!(payload["selected_sessions"], 1);
+        assert_eq!(payload["deleted_sessions"], 0);
+        assert_eq!(payload["planned_bytes"], 4096);
+        assert_eq!(payload["skipped_metadata"], 1);
+        assert_eq!(payload["failed_deletions"], 0);
+        assert_eq!(payload["sessions"][0]["session_id"], "sl_old");
+        assert_eq!(payload["sessions"][0]["started_at"], "1970-01-01T00:00:01Z");
+        assert_eq!(payload["sessions"][0]["status"], "completed");
+        assert_eq!(payload["sessions"][0]["backend"], "conpty");
+        assert_eq!(payload["sessions"][0]["planned_bytes"], 4096);
+        assert_eq!(payload["sessions"][0]["action"], "would_delete");
+
+        let serialized = serde_json::to_string(&payload).unwrap();
+        assert!(!serialized.contains("terminal body with password"));
+        assert!(!serialized.contains("auth_args"));
+        assert!(!serialized.contains("command"));
+        assert!(!serialized.contains("private_key_path"));
+        assert!(!serialized.contains("password"));
+        assert!(!serialized.contains("secret"));
+        assert!(!serialized.contains("token"));
     }
 
-    #[cfg(windows)]
     #[test]
-    fn conpty_output_inspector_handles_split_cursor_position_query() {
-        let mut inspector = ConptyOutputInspector::default();
+    fn session_prune_actual_json_summary_includes_failure_details() {
+        let deleted = test_prune_candidate("sl_deleted", 1000, "completed", 100);
+        let failed = test_prune_candidate("sl_failed", 2000, "completed_nonzero", 200);
+        let failure_path = failed.log_path.clone().unwrap();
+        let plan = session_log::SessionPrunePlan {
+            log_dir: PathBuf::from("session-logs"),
+            candidates: vec![deleted, failed],
+            skipped: Vec::new(),
+            planned_size_bytes: 300,
+        };
+        let args = SessionPruneArgs {
+            older_than: None,
+            keep_last: Some(100),
+            dry_run: false,
+            yes: true,
+            json: true,
+        };
+        let report = session_log::SessionPruneApplyReport {
+            sessions_deleted: 1,
+            planned_size_bytes: 300,
+            failed_deletions: 1,
+            failures: vec![session_log::SessionPruneFailure {
+                session_id: "sl_failed".to_string(),
+                path: failure_path.clone(),
+                operation: "remove_log".to_string(),
+                error: "access denied".to_string(),
+            }],
+        };
 
-        let first = inspector.inspect(b"\x1b[");
-        let second = inspector.inspect(b"6n");
+        let payload = session_prune_report_json(&plan, &args, &report);
 
-        assert!(!first.has_visible_output);
-        assert!(first.synthetic_responses.is_empty());
-        assert!(!second.has_visible_output);
+        assert_eq!(payload["dry_run"], false);
+        assert!(payload["criteria"]["older_than"].is_null());
+        assert_eq!(payload["criteria"]["keep_last"], 100);
+        assert_eq!(payload["selected_sessions"], 2);
+        assert_eq!(payload["deleted_sessions"], 1);
+        assert_eq!(payload["planned_bytes"], 300);
+        assert_eq!(payload["skipped_metadata"], 0);
+        assert_eq!(payload["failed_deletions"], 1);
+        assert_eq!(payload["sessions"][0]["session_id"], "sl_deleted");
+        assert_eq!(payload["sessions"][0]["action"], "deleted");
+        assert_eq!(payload["sessions"][1]["session_id"], "sl_failed");
+        assert_eq!(payload["sessions"][1]["action"], "failed");
+        assert_eq!(payload["failures"][0]["session_id"], "sl_failed");
+        assert_eq!(payload["failures"][0]["operation"], "remove_log");
         assert_eq!(
-            second.synthetic_responses,
-            vec![ConptySyntheticResponse::CursorPosition]
+            payload["failures"][0]["path"],
+            failure_path.display().to_string()
         );
+        assert_eq!(payload["failures"][0]["error"], "access denied");
     }
 
-    #[cfg(windows)]
-    #[test]
-    fn conpty_output_inspector_treats_prompt_text_as_startup_output() {
-        let mut inspector = ConptyOutputInspector::default();
+    fn test_prune_candidate(
+        session_id: &str,
+        started_at: i64,
+        status: &str,
+        planned_size_bytes: u64,
+    ) -> session_log::SessionPruneCandidate {
+        let metadata_path = PathBuf::from("session-logs").join(format!("{session_id}.json"));
+        let log_path = PathBuf::from("session-logs").join(format!("{session_id}.log"));
+        session_log::SessionPruneCandidate {
+            metadata: session_log::SessionLogMetadata {
+                session_id: session_id.to_string(),
+                profile_id: "p_test".to_string(),
+                user: "alice".to_string(),
+                host: "example.com".to_string(),
+                port: 22,
+                started_at,
+                ended_at: started_at + 1000,
+                duration_ms: 1000,
+                exit_code: Some(0),
+                backend: session_log::SESSION_LOG_BACKEND_CONPTY.to_string(),
+                log_path: Some(log_path.clone()),
+                metadata_path: metadata_path.clone(),
+                status: status.to_string(),
+                reason: None,
+                failure_phase: None,
+                failure_reason: None,
+                content_capture: Some(
+                    session_log::SESSION_LOG_CONTENT_CAPTURE_TERMINAL_IO.to_string(),
+                ),
+                content_capture_reliable: Some(true),
+                backend_status: Some(
+                    session_log::SESSION_LOG_BACKEND_STATUS_EXPLICIT_READY.to_string(),
+                ),
+                backend_warning: Some(
+                    session_log::SESSION_LOG_BACKEND_WARNING_CONPTY_EXPLICIT_NOT_AUTO.to_string(),
+                ),
+                content_capture_status: None,
+                content_capture_warning: None,
+            },
+            metadata_path,
+            log_path: Some(log_path),
+            log_exists: true,
+            metadata_size_bytes: planned_size_bytes,
+            log_size_bytes: 0,
+            planned_size_bytes,
+        }
+    }
 
-        let inspection = inspector.inspect(b"\x1b[?2004hPassword: ");
+    #[test]
+    fn parses_init_with_samples() {
+        let cli = Cli::try_parse_from(["td", "init", "--with-samples"]).expect("parses init");
 
-        assert!(inspection.has_visible_output);
-        assert!(inspection.synthetic_responses.is_empty());
+        match cli.command {
+            Some(Commands::Init(args)) => {
+                assert!(args.with_samples);
+            }
+            _ => panic!("expected init command"),
+        }
     }
 
-    #[cfg(windows)]
     #[test]
-    fn conpty_loop_receive_synthesizes_startup_timeout_without_worker_event() {
-        let (_tx, rx) = mpsc::channel();
-        let first_output_received = AtomicBool::new(false);
+    fn sample_cmdset_install_is_idempotent() {
+        let conn = db::init_in_memory().unwrap();
+        let mut store = CmdSetStore::new(conn);
 
-        let message = recv_conpty_loop_message(
-            &rx,
-            &first_output_received,
-            Some(Instant::now() + Duration::from_millis(1)),
-        )
-        .expect("startup timeout message");
+        let first = install_sample_cmdsets(&mut store).unwrap();
+        let second = install_sample_cmdsets(&mut store).unwrap();
 
-        assert!(matches!(
-            message,
-            ConptyLoopMessage::Event(ConptyEvent::StartupTimeout)
-        ));
+        assert_eq!(first[0].status, SampleInstallStatus::Created);
+        assert_eq!(second[0].status, SampleInstallStatus::Skipped);
+        let steps = store.list_steps("linux-basic-check").unwrap();
+        assert_eq!(steps.len(), 5);
     }
 
-    #[cfg(windows)]
     #[test]
-    fn conpty_log_sanitizer_strips_color_and_clear_sequences() {
-        let mut sanitizer = ConptyLogSanitizer::default();
+    fn parses_fav_add_with_direction_and_via() {
+        let cli = Cli::try_parse_from([
+            "td",
+            "fav",
+            "add",
+            "p_web",
+            "deploy bundle",
+            "./target/app.tar",
+            "/opt/app/",
+            "--via",
+            "sftp",
+        ])
+        .expect("parses fav add");
 
-        let mut out = sanitizer.push(b"drwx <ESC>");
-        out.extend(sanitizer.push(b"\x1b[34m.\x1b[m/\x1b[K\n"));
+        match cli.command {
+            Some(Commands::Fav {
+                command: FavCommands::Add(args),
+            }) => {
+                assert_eq!(args.profile_id, "p_web");
+                assert_eq!(args.name, "deploy bundle");
+                assert_eq!(args.local_path, "./target/app.tar");
+                assert_eq!(args.remote_path, "/opt/app/");
+                assert_eq!(args.direction, "push");
+                assert_eq!(args.via, "sftp");
+            }
+            _ => panic!("expected fav add command"),
+        }
+    }
 
-        assert_eq!(String::from_utf8(out).unwrap(), "drwx <ESC>./\n");
+    #[test]
+    fn parses_fav_run_without_name_for_picker() {
+        let cli = Cli::try_parse_from(["td", "fav", "run", "p_web"]).expect("parses fav run");
+
+        match cli.command {
+            Some(Commands::Fav {
+                command: FavCommands::Run(args),
+            }) => {
+                assert_eq!(args.profile_id, "p_web");
+                assert!(args.name.is_none());
+            }
+            _ => panic!("expected fav run command"),
+        }
     }
 
-    #[cfg(windows)]
     #[test]
-    fn conpty_log_sanitizer_turns_cursor_forward_into_spacing() {
-        let mut sanitizer = ConptyLogSanitizer::default();
+    fn favorite_choice_accepts_number_or_name() {
+        let favorite = |name: &str| TransferFavorite {
+            id: 1,
+            profile_id: "p_web".to_string(),
+            name: name.to_string(),
+            direction: TransferDirection::Push,
+            local_path: "./app.tar".to_string(),
+            remote_path: "/opt/app/".to_string(),
+            via: TransferVia::Scp,
+            created_at: 0,
+            last_used_at: None,
+        };
+        let favorites = vec![favorite("deploy"), favorite("logs")];
 
-        let out = sanitizer.push(b"nico@ao03:~$\x1b[1Cdf -h\r\n");
+        assert_eq!(parse_favorite_choice("2\n", &favorites).unwrap(), 1);
+        assert_eq!(parse_favorite_choice("deploy", &favorites).unwrap(), 0);
+        assert!(parse_favorite_choice("3", &favorites).is_err());
+        assert!(parse_favorite_choice("missing", &favorites).is_err());
+    }
 
-        assert_eq!(String::from_utf8(out).unwrap(), "nico@ao03:~$ df -h\n");
+    #[test]
+    fn parses_db_status_json() {
+        let cli = Cli::try_parse_from(["td", "db", "status", "--json"]).expect("parses db status");
+
+        match cli.command {
+            Some(Commands::Db {
+                command: DbCommands::Status { json },
+            }) => assert!(json),
+            _ => panic!("expected db status command"),
+        }
     }
 
-    #[cfg(windows)]
     #[test]
-    fn conpty_log_sanitizer_applies_prompt_line_clear_redraw() {
-        let mut sanitizer = ConptyLogSanitizer::with_row(18);
-
-        let mut out = sanitizer.push("nico@ao03:~$ ｌｌ".as_bytes());
-        out.extend(sanitizer.push(b"\x1b[18;16H\x1b[K\x1b[18;14H\x1b[K\x07ll\r"));
-        out.extend(sanitizer.push(b"\x1b[?2004l\n"));
+    fn parses_db_rollback_with_version() {
+        let cli = Cli::try_parse_from(["td", "db", "rollback", "--to", "4", "--yes"])
+            .expect("parses db rollback");
 
-        assert_eq!(String::from_utf8(out).unwrap(), "nico@ao03:~$ ll\n");
+        match cli.command {
+            Some(Commands::Db {
+                command: DbCommands::Rollback(args),
+            }) => {
+                assert_eq!(args.to, Some(4));
+                assert!(args.yes);
+            }
+            _ => panic!("expected db rollback command"),
+        }
     }
 
-    #[cfg(windows)]
     #[test]
-    fn conpty_log_sanitizer_splits_absolute_cursor_rows_into_lines() {
-        let mut sanitizer = ConptyLogSanitizer::default();
-
-        let mut out = sanitizer.push(b"Welcome\x1b[3;1H * Documentation\n");
-        out.extend(sanitizer.finish());
+    fn parses_profile_set_with_tag_edits() {
+        let cli = Cli::try_parse_from([
+            "td",
+            "profile",
+            "set",
+            "p1",
+            "--host",
+            "example.net",
+            "--tag",
+            "+new",
+            "--tag",
+            "-old",
+            "--danger",
+            "critical",
+            "--client-overrides-patch",
+            r#"{"ssh":null}"#,
+        ])
+        .expect("parses profile set");
 
-        assert_eq!(
-            String::from_utf8(out).unwrap(),
-            "Welcome\n * Documentation\n"
-        );
+        match cli.command {
+            Some(Commands::Profile {
+                command: ProfileCommands::Set(args),
+            }) => {
+                assert_eq!(args.profile_id, "p1");
+                assert_eq!(args.host.as_deref(), Some("example.net"));
+                assert_eq!(args.tag, vec!["+new".to_string(), "-old".to_string()]);
+                assert_eq!(args.danger.as_deref(), Some("critical"));
+                assert_eq!(
+                    args.client_overrides_patch.as_deref(),
+                    Some(r#"{"ssh":null}"#)
+                );
+            }
+            _ => panic!("expected profile set command"),
+        }
     }
 
-    #[cfg(windows)]
     #[test]
-    fn conpty_log_sanitizer_preserves_utf8_japanese_text() {
-        let mut sanitizer = ConptyLogSanitizer::default();
-
-        let out = sanitizer.push("6月 15日 日本語\n".as_bytes());
+    fn parses_profile_clone() {
+        let cli = Cli::try_parse_from([
+            "td", "profile", "clone", "p1", "--id", "p2", "--host", "other",
+        ])
+        .expect("parses profile clone");
 
-        assert_eq!(String::from_utf8(out).unwrap(), "6月 15日 日本語\n");
+        match cli.command {
+            Some(Commands::Profile {
+                command: ProfileCommands::Clone(args),
+            }) => {
+                assert_eq!(args.src_id, "p1");
+                assert_eq!(args.new_id.as_deref(), Some("p2"));
+                assert_eq!(args.host.as_deref(), Some("other"));
+                assert!(args.name.is_none());
+            }
+            _ => panic!("expected profile clone command"),
+        }
     }
 
-    #[cfg(windows)]
     #[test]
-    fn session_log_display_text_sanitizes_existing_raw_conpty_logs() {
-        let raw = "Welcome\x1b[18;1H\
-                   nico@ao03:~$ ｌｌ\x1b[18;16H\x1b[K\x1b[18;14H\x1b[K\x07ll\r\x1b[?2004l\n\
-                   nico@ao03:~$\x1b[1Cdf -h\r\n\
-                   drwx \x1b[34m.\x1b[m/\x1b[K\n";
-
-        let display = session_log_display_text(raw.as_bytes());
+    fn parses_profile_import_hosts() {
+        let cli = Cli::try_parse_from([
+            "td",
+            "profile",
+            "import-hosts",
+            "hosts.csv",
+            "--user",
+            "ops",
+            "--tag",
+            "lab,imported",
+        ])
+        .expect("parses profile import-hosts");
 
-        assert_eq!(
-            display,
-            "Welcome\nnico@ao03:~$ ll\nnico@ao03:~$ df -h\ndrwx ./\n"
-        );
+        match cli.command {
+            Some(Commands::Profile {
+                command: ProfileCommands::ImportHosts(args),
+            }) => {
+                assert_eq!(args.path, PathBuf::from("hosts.csv"));
+                assert_eq!(args.user.as_deref(), Some("ops"));
+                assert_eq!(args.port, 22);
+                assert_eq!(args.tag, vec!["lab".to_string(), "imported".to_string()]);
+            }
+            _ => panic!("expected profile import-hosts command"),
+        }
     }
 
     #[test]
-    fn session_show_capture_lines_include_host_only_warning() {
-        let metadata = session_log::SessionLogMetadata {
-            session_id: "sl_abc123".to_string(),
-            profile_id: "p_test".to_string(),
-            user: "alice".to_string(),
-            host: "example.com".to_string(),
-            port: 22,
-            started_at: 1000,
-            ended_at: 2000,
-            duration_ms: 1000,
-            exit_code: Some(0),
-            backend: session_log::SESSION_LOG_BACKEND_POWERSHELL_TRANSCRIPT.to_string(),
-            log_path: Some(PathBuf::from("sl_abc123.log")),
-            metadata_path: PathBuf::from("sl_abc123.json"),
-            status: "completed".to_string(),
-            reason: None,
-            failure_phase: None,
-            failure_reason: None,
-            content_capture: Some(session_log::SESSION_LOG_CONTENT_CAPTURE_BEST_EFFORT.to_string()),
-            content_capture_reliable: Some(false),
-            backend_status: None,
-            backend_warning: Some(
-                session_log::SESSION_LOG_BACKEND_WARNING_POWERSHELL_TRANSCRIPT.to_string(),
-            ),
-            content_capture_status: Some(
-                session_log::SESSION_LOG_CAPTURE_STATUS_HOST_ONLY_OR_EMPTY.to_string(),
-            ),
-            content_capture_warning: Some(
-                session_log::SESSION_LOG_CAPTURE_WARNING_NO_SSH_CONTENT.to_string(),
-            ),
-        };
-
-        let lines = session_capture_lines(&metadata);
+    fn parses_view_with_allow_run() {
+        let cli =
+            Cli::try_parse_from(["td", "view", "export.json", "--allow-run"]).expect("parses view");
 
-        assert!(lines.iter().any(|line| line == "backend_status: degraded"));
-        assert!(lines
-            .iter()
-            .any(|line| line == "content_capture: best_effort"));
-        assert!(lines
-            .iter()
-            .any(|line| line == "content_capture_reliable: false"));
-        assert!(lines
-            .iter()
-            .any(|line| line == "Content capture: host_only_or_empty"));
-        assert!(lines
-            .iter()
-            .any(|line| line == "Warning: No SSH terminal content appears to have been captured."));
+        match cli.command {
+            Some(Commands::View(args)) => {
+                assert_eq!(args.path, PathBuf::from("export.json"));
+                assert!(args.allow_run);
+            }
+            _ => panic!("expected view command"),
+        }
     }
 
     #[test]
-    fn session_show_capture_lines_include_conpty_degraded_warning() {
-        let metadata = session_log::SessionLogMetadata {
-            session_id: "sl_abc123".to_string(),
-            profile_id: "p_test".to_string(),
-            user: "alice".to_string(),
-            host: "example.com".to_string(),
-            port: 22,
-            started_at: 100,
-            ended_at: 200,
-            duration_ms: 100,
-            exit_code: Some(0),
-            backend: session_log::SESSION_LOG_BACKEND_CONPTY.to_string(),
-            log_path: Some(PathBuf::from("sl_abc123.log")),
-            metadata_path: PathBuf::from("sl_abc123.json"),
-            status: "completed".to_string(),
-            reason: None,
-            failure_phase: None,
-            failure_reason: None,
-            content_capture: Some(session_log::SESSION_LOG_CONTENT_CAPTURE_TERMINAL_IO.to_string()),
-            content_capture_reliable: Some(true),
-            backend_status: Some(
-                session_log::SESSION_LOG_BACKEND_STATUS_EXPLICIT_READY.to_string(),
-            ),
-            backend_warning: Some(
-                session_log::SESSION_LOG_BACKEND_WARNING_CONPTY_EXPLICIT_NOT_AUTO.to_string(),
-            ),
-            content_capture_status: None,
-            content_capture_warning: None,
-        };
-
-        let lines = session_capture_lines(&metadata);
+    fn parses_profile_archive_and_archived_list() {
+        let cli = Cli::try_parse_from(["td", "profile", "archive", "p1"]).expect("parses archive");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Profile {
+                command: ProfileCommands::Archive { ref profile_id },
+            }) if profile_id == "p1"
+        ));
 
-        assert!(lines
-            .iter()
-            .any(|line| line == "backend_status: explicit_ready"));
-        assert!(lines
-            .iter()
-            .any(|line| line == "content_capture: terminal_io"));
-        assert!(lines
-            .iter()
-            .any(|line| line == "content_capture_reliable: true"));
-        assert!(lines
-            .iter()
-            .any(|line| line
-                == "backend_warning: conpty_backend_is_explicit_and_not_selected_by_auto"));
+        let cli = Cli::try_parse_from(["td", "profile", "list", "--archived"])
+            .expect("parses list --archived");
+        match cli.command {
+            Some(Commands::Profile {
+                command: ProfileCommands::List(args),
+            }) => assert!(args.archived),
+            _ => panic!("expected profile list command"),
+        }
     }
 
     #[test]
-    fn session_log_path_display_ignores_warning_text() {
+    fn td_output_json_adds_the_flag_only_where_it_exists() {
+        let args = |words: &[&str]| words.iter().map(OsString::from).collect::<Vec<_>>();
         assert_eq!(
-            format_session_log_path(Some(Path::new(
-                "not selected by auto; use session.log.backend=conpty explicitly."
-            ))),
-            "<none>"
+            with_json_flag(&args(&["td", "profile", "list", "--group", "web"])),
+            Some(args(&["td", "profile", "list", "--group", "web", "--json"]))
         );
         assert_eq!(
-            format_session_log_path(Some(Path::new("sl_abc123.log"))),
-            "sl_abc123.log"
+            with_json_flag(&args(&["td", "exec", "p1", "--", "uname", "-a"])),
+            Some(args(&["td", "exec", "p1", "--json", "--", "uname", "-a"]))
         );
-        assert_eq!(format_session_log_path(None), "<none>");
+        assert_eq!(with_json_flag(&args(&["td", "doctor", "--json"])), None);
+        assert_eq!(with_json_flag(&args(&["td", "connect", "p1"])), None);
+        assert_eq!(with_json_flag(&args(&["td", "profile", "bogus"])), None);
     }
 
     #[test]
-    fn session_list_table_cells_are_bounded() {
-        assert_eq!(table_cell("completed exit 0", 18), "completed exit 0");
-        assert_eq!(table_cell("abcdefghijklmnopqrstuvwxyz", 8), "abcdefg~");
+    fn parses_database_selection_flags() {
+        let cli = Cli::try_parse_from(["td", "profile", "list", "--db", "/tmp/work.db"])
+            .expect("parses --db");
+        assert_eq!(cli.db, Some(PathBuf::from("/tmp/work.db")));
+        let cli = Cli::try_parse_from(["td", "--context", "home", "ui"]).expect("parses --context");
+        assert_eq!(cli.context.as_deref(), Some("home"));
+        assert!(
+            Cli::try_parse_from(["td", "--db", "a.db", "--context", "home", "recent"]).is_err()
+        );
     }
 
     #[test]
-    fn session_log_config_defaults_are_resolved() {
-        let conn = db::init_in_memory().unwrap();
-
-        assert_eq!(
-            default_resolved_config_value(&conn, session_log::SESSION_LOG_ENABLED_KEY).unwrap(),
-            Some("false".to_string())
-        );
-        assert_eq!(
-            default_resolved_config_value(&conn, session_log::SESSION_LOG_BACKEND_KEY).unwrap(),
-            Some("auto".to_string())
-        );
+    fn parses_global_output_flags_after_subcommand() {
+        let cli = Cli::try_parse_from(["td", "profile", "list", "--no-color", "--no-pager"])
+            .expect("parses output flags");
+        assert!(cli.no_color);
+        assert!(cli.no_pager);
     }
 
     #[test]
-    fn session_stats_json_summary_uses_safe_fields() {
-        let stats = session_log::SessionLogStats {
-            log_dir: PathBuf::from("session-logs"),
-            total_sessions: 2,
-            total_log_bytes: 1234,
-            skipped_metadata: 1,
-            by_backend: BTreeMap::from([
-                (session_log::SESSION_LOG_BACKEND_CONPTY.to_string(), 1),
-                (
-                    session_log::SESSION_LOG_BACKEND_POWERSHELL_TRANSCRIPT.to_string(),
-                    1,
-                ),
-            ]),
-            by_status: BTreeMap::from([
-                ("completed".to_string(), 1),
-                ("completed_nonzero".to_string(), 1),
-            ]),
-            oldest_session: Some(session_log::SessionLogStatsSession {
-                session_id: "sl_old".to_string(),
-                started_at: 1000,
-            }),
-            newest_session: Some(session_log::SessionLogStatsSession {
-                session_id: "sl_new".to_string(),
-                started_at: 2000,
-            }),
-        };
-
-        let payload = session_stats_json(&stats);
+    fn parses_global_reason_after_subcommand() {
+        let cli = Cli::try_parse_from(["td", "connect", "p_db", "--reason", "INC-42 disk full"])
+            .expect("parses --reason");
+        assert_eq!(cli.reason.as_deref(), Some("INC-42 disk full"));
+    }
 
-        assert_eq!(payload["log_directory"], "session-logs");
-        assert_eq!(payload["total_sessions"], 2);
-        assert_eq!(payload["total_log_bytes"], 1234);
-        assert_eq!(payload["skipped_metadata"], 1);
-        assert_eq!(payload["by_backend"]["conpty"], 1);
-        assert_eq!(payload["by_backend"]["powershell-transcript"], 1);
-        assert_eq!(payload["by_status"]["completed"], 1);
-        assert_eq!(payload["by_status"]["completed_nonzero"], 1);
-        assert_eq!(payload["oldest_session"]["session_id"], "sl_old");
-        assert_eq!(
-            payload["oldest_session"]["started_at"],
-            "1970-01-01T00:00:01Z"
-        );
-        assert_eq!(payload["newest_session"]["session_id"], "sl_new");
-        assert_eq!(
-            payload["newest_session"]["started_at"],
-            "1970-01-01T00:00:02Z"
-        );
+    #[test]
+    fn parses_maintenance_add_with_reason_mode() {
+        let cli = Cli::try_parse_from([
+            "td",
+            "maintenance",
+            "add",
+            "--target",
+            "group:billing",
+            "--window",
+            "sat 22:00..sun 06:00",
+            "--mode",
+            "reason",
+        ])
+        .expect("parses maintenance add");
+        match cli.command {
+            Some(Commands::Maintenance {
+                command: MaintenanceCommands::Add(args),
+            }) => {
+                assert_eq!(args.target, "group:billing");
+                assert_eq!(args.window, "sat 22:00..sun 06:00");
+                assert!(matches!(args.mode, WindowModeArg::Reason));
+                assert!(args.note.is_none());
+            }
+            _ => panic!("expected maintenance add command"),
+        }
+    }
 
-        let serialized = serde_json::to_string(&payload).unwrap();
-        assert!(!serialized.contains("terminal body with password"));
-        assert!(!serialized.contains("auth_args"));
-        assert!(!serialized.contains("command"));
-        assert!(!serialized.contains("private_key_path"));
-        assert!(!serialized.contains("password"));
-        assert!(!serialized.contains("secret"));
-        assert!(!serialized.contains("token"));
+    #[test]
+    fn global_flags_share_a_help_heading() {
+        let command = Cli::command();
+        for id in ["db", "context", "observer", "no_interactive"] {
+            let arg = command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .expect(id);
+            assert!(arg.is_global_set(), "{id}");
+            assert_eq!(arg.get_help_heading(), Some("Global options"), "{id}");
+        }
     }
 
     #[test]
-    fn session_prune_dry_run_json_summary_uses_safe_fields() {
-        let candidate = test_prune_candidate("sl_old", 1000, "completed", 4096);
-        let plan = session_log::SessionPrunePlan {
-            log_dir: PathBuf::from("session-logs"),
-            candidates: vec![candidate],
-            skipped: vec![session_log::SessionPruneSkipped {
-                metadata_path: PathBuf::from("session-logs").join("sl_bad.json"),
-                reason: "malformed metadata".to_string(),
-            }],
-            planned_size_bytes: 4096,
-        };
-        let args = SessionPruneArgs {
-            older_than: Some("30d".to_string()),
-            keep_last: None,
-            dry_run: true,
-            yes: false,
-            json: true,
+    fn observer_mode_allows_only_reads() {
+        let allows = |args: &[&str]| {
+            let cli = Cli::try_parse_from(args).expect("parses");
+            assert!(cli.observer);
+            observer_allows(&cli.command.expect("has a command"))
         };
+        assert!(allows(&["td", "--observer", "profile", "list"]));
+        assert!(allows(&["td", "log", "list", "--observer"]));
+        assert!(allows(&["td", "--observer", "doctor"]));
+        assert!(allows(&["td", "--observer", "schedule", "export", "--ics"]));
+        assert!(Cli::try_parse_from(["td", "maintenance", "export"]).is_err());
+        assert!(!allows(&[
+            "td",
+            "--observer",
+            "config",
+            "set",
+            "observer.enabled",
+            "false"
+        ]));
+        assert!(!allows(&["td", "--observer", "config", "leave-observer"]));
+        assert!(!allows(&[
+            "td",
+            "--observer",
+            "tail",
+            "p_web",
+            "/var/log/syslog"
+        ]));
+        assert!(!allows(&["td", "--observer", "doctor", "--fix"]));
+        assert!(!allows(&[
+            "td",
+            "--observer",
+            "exec",
+            "p_db",
+            "--",
+            "uptime"
+        ]));
+        assert!(!allows(&["td", "--observer", "profile", "rm", "p_db"]));
+        assert!(!allows(&[
+            "td",
+            "--observer",
+            "config",
+            "set",
+            "ui.timezone",
+            "UTC"
+        ]));
+    }
 
-        let payload = session_prune_plan_json(&plan, &args, true, false);
+    #[test]
+    fn parses_profile_search_words() {
+        let cli = Cli::try_parse_from(["td", "profile", "search", "billing", "db", "--limit", "5"])
+            .expect("parses profile search");
+        match cli.command {
+            Some(Commands::Profile {
+                command: ProfileCommands::Search(args),
+            }) => {
+                assert_eq!(args.query, vec!["billing", "db"]);
+                assert_eq!(args.limit, Some(5));
+                assert!(!args.archived);
+            }
+            _ => panic!("expected profile search command"),
+        }
+    }
 
-        assert_eq!(payload["dry_run"], true);
-        assert_eq!(payload["criteria"]["older_than"], "30d");
-        assert!(payload["criteria"]["keep_last"].is_null());
-        assert_eq!(payload["selected_sessions"], 1);
-        assert_eq!(payload["deleted_sessions"], 0);
-        assert_eq!(payload["planned_bytes"], 4096);
-        assert_eq!(payload["skipped_metadata"], 1);
-        assert_eq!(payload["failed_deletions"], 0);
-        assert_eq!(payload["sessions"][0]["session_id"], "sl_old");
-        assert_eq!(payload["sessions"][0]["started_at"], "1970-01-01T00:00:01Z");
-        assert_eq!(payload["sessions"][0]["status"], "completed");
-        assert_eq!(payload["sessions"][0]["backend"], "conpty");
-        assert_eq!(payload["sessions"][0]["planned_bytes"], 4096);
-        assert_eq!(payload["sessions"][0]["action"], "would_delete");
+    #[test]
+    fn parses_tail_follow_grep_and_output() {
+        let cli = Cli::try_parse_from([
+            "td",
+            "tail",
+            "web1",
+            "/var/log/syslog",
+            "-f",
+            "--grep",
+            "ERROR",
+            "-o",
+            "triage.log",
+        ])
+        .expect("parses tail");
+        match cli.command {
+            Some(Commands::Tail(args)) => {
+                assert_eq!(args.targets, vec!["web1", "/var/log/syslog"]);
+                assert!(args.follow);
+                assert_eq!(args.lines, 10);
+                assert_eq!(args.grep.as_deref(), Some("ERROR"));
+                assert_eq!(args.output, Some(PathBuf::from("triage.log")));
+            }
+            _ => panic!("expected tail command"),
+        }
+    }
 
-        let serialized = serde_json::to_string(&payload).unwrap();
-        assert!(!serialized.contains("terminal body with password"));
-        assert!(!serialized.contains("auth_args"));
-        assert!(!serialized.contains("command"));
-        assert!(!serialized.contains("private_key_path"));
-        assert!(!serialized.contains("password"));
-        assert!(!serialized.contains("secret"));
-        assert!(!serialized.contains("token"));
+    #[test]
+    fn parses_tail_by_tag_with_only_a_path() {
+        let cli = Cli::try_parse_from(["td", "tail", "--tag", "web", "/var/log/nginx/error.log"])
+            .expect("parses tail --tag");
+        match cli.command {
+            Some(Commands::Tail(args)) => {
+                assert_eq!(args.tag, vec!["web"]);
+                assert_eq!(args.targets, vec!["/var/log/nginx/error.log"]);
+            }
+            _ => panic!("expected tail command"),
+        }
     }
 
     #[test]
-    fn session_prune_actual_json_summary_includes_failure_details() {
-        let deleted = test_prune_candidate("sl_deleted", 1000, "completed", 100);
-        let failed = test_prune_candidate("sl_failed", 2000, "completed_nonzero", 200);
-        let failure_path = failed.log_path.clone().unwrap();
-        let plan = session_log::SessionPrunePlan {
-            log_dir: PathBuf::from("session-logs"),
-            candidates: vec![deleted, failed],
-            skipped: Vec::new(),
-            planned_size_bytes: 300,
-        };
-        let args = SessionPruneArgs {
-            older_than: None,
-            keep_last: Some(100),
-            dry_run: false,
-            yes: true,
-            json: true,
-        };
-        let report = session_log::SessionPruneApplyReport {
-            sessions_deleted: 1,
-            planned_size_bytes: 300,
-            failed_deletions: 1,
-            failures: vec![session_log::SessionPruneFailure {
-                session_id: "sl_failed".to_string(),
-                path: failure_path.clone(),
-                operation: "remove_log".to_string(),
-                error: "access denied".to_string(),
-            }],
-        };
+    fn parses_test_all_without_profile_id() {
+        let cli =
+            Cli::try_parse_from(["td", "test", "--all", "--json"]).expect("parses test --all");
+        match cli.command {
+            Some(Commands::Test {
+                profile_id,
+                all,
+                json,
+                ..
+            }) => {
+                assert!(profile_id.is_none());
+                assert!(all);
+                assert!(json);
+            }
+            _ => panic!("expected test command"),
+        }
+        assert!(Cli::try_parse_from(["td", "test"]).is_err());
+        assert!(Cli::try_parse_from(["td", "test", "p1", "--all"]).is_err());
+    }
 
-        let payload = session_prune_report_json(&plan, &args, &report);
+    #[test]
+    fn parses_test_latency_samples() {
+        let cli = Cli::try_parse_from(["td", "test", "p1", "--samples", "5"])
+            .expect("parses test --samples");
+        match cli.command {
+            Some(Commands::Test { samples, .. }) => assert_eq!(samples, Some(5)),
+            _ => panic!("expected test command"),
+        }
+        assert!(Cli::try_parse_from(["td", "test", "p1", "--samples", "0"]).is_err());
+    }
 
-        assert_eq!(payload["dry_run"], false);
-        assert!(payload["criteria"]["older_than"].is_null());
-        assert_eq!(payload["criteria"]["keep_last"], 100);
-        assert_eq!(payload["selected_sessions"], 2);
-        assert_eq!(payload["deleted_sessions"], 1);
-        assert_eq!(payload["planned_bytes"], 300);
-        assert_eq!(payload["skipped_metadata"], 0);
-        assert_eq!(payload["failed_deletions"], 1);
-        assert_eq!(payload["sessions"][0]["session_id"], "sl_deleted");
-        assert_eq!(payload["sessions"][0]["action"], "deleted");
-        assert_eq!(payload["sessions"][1]["session_id"], "sl_failed");
-        assert_eq!(payload["sessions"][1]["action"], "failed");
-        assert_eq!(payload["failures"][0]["session_id"], "sl_failed");
-        assert_eq!(payload["failures"][0]["operation"], "remove_log");
-        assert_eq!(
-            payload["failures"][0]["path"],
-            failure_path.display().to_string()
-        );
-        assert_eq!(payload["failures"][0]["error"], "access denied");
+    #[test]
+    fn parses_doctor_fix_flags() {
+        let cli =
+            Cli::try_parse_from(["td", "doctor", "--fix", "--yes"]).expect("parses doctor --fix");
+        match cli.command {
+            Some(Commands::Doctor { json, fix, yes, .. }) => assert!(!json && fix && yes),
+            _ => panic!("expected doctor command"),
+        }
+        assert!(Cli::try_parse_from(["td", "doctor", "--yes"]).is_err());
+        assert!(Cli::try_parse_from(["td", "doctor", "--fix", "--json"]).is_err());
+        let cli = Cli::try_parse_from(["td", "doctor", "--watch", "--interval", "5"])
+            .expect("parses doctor --watch");
+        match cli.command {
+            Some(Commands::Doctor {
+                watch, interval, ..
+            }) => assert!(watch && interval == 5),
+            _ => panic!("expected doctor command"),
+        }
+        assert!(Cli::try_parse_from(["td", "doctor", "--watch", "--fix"]).is_err());
     }
 
-    fn test_prune_candidate(
-        session_id: &str,
-        started_at: i64,
-        status: &str,
-        planned_size_bytes: u64,
-    ) -> session_log::SessionPruneCandidate {
-        let metadata_path = PathBuf::from("session-logs").join(format!("{session_id}.json"));
-        let log_path = PathBuf::from("session-logs").join(format!("{session_id}.log"));
-        session_log::SessionPruneCandidate {
-            metadata: session_log::SessionLogMetadata {
-                session_id: session_id.to_string(),
-                profile_id: "p_test".to_string(),
-                user: "alice".to_string(),
-                host: "example.com".to_string(),
-                port: 22,
-                started_at,
-                ended_at: started_at + 1000,
-                duration_ms: 1000,
-                exit_code: Some(0),
-                backend: session_log::SESSION_LOG_BACKEND_CONPTY.to_string(),
-                log_path: Some(log_path.clone()),
-                metadata_path: metadata_path.clone(),
-                status: status.to_string(),
-                reason: None,
-                failure_phase: None,
-                failure_reason: None,
-                content_capture: Some(
-                    session_log::SESSION_LOG_CONTENT_CAPTURE_TERMINAL_IO.to_string(),
-                ),
-                content_capture_reliable: Some(true),
-                backend_status: Some(
-                    session_log::SESSION_LOG_BACKEND_STATUS_EXPLICIT_READY.to_string(),
-                ),
-                backend_warning: Some(
-                    session_log::SESSION_LOG_BACKEND_WARNING_CONPTY_EXPLICIT_NOT_AUTO.to_string(),
-                ),
-                content_capture_status: None,
-                content_capture_warning: None,
-            },
-            metadata_path,
-            log_path: Some(log_path),
-            log_exists: true,
-            metadata_size_bytes: planned_size_bytes,
-            log_size_bytes: 0,
-            planned_size_bytes,
+    #[test]
+    fn parses_agent_remove_and_clear_flags() {
+        let cli = Cli::try_parse_from([
+            "td",
+            "agent",
+            "remove",
+            "/tmp/id_ed25519",
+            "--yes",
+            "--json",
+        ])
+        .expect("parses agent remove");
+        match cli.command {
+            Some(Commands::Agent {
+                command:
+                    AgentCommands::Remove {
+                        key_path,
+                        yes,
+                        json,
+                    },
+            }) => {
+                assert_eq!(key_path, PathBuf::from("/tmp/id_ed25519"));
+                assert!(yes && json);
+            }
+            _ => panic!("expected agent remove command"),
         }
+        assert!(Cli::try_parse_from(["td", "agent", "clear", "--json"]).is_err());
+        assert!(Cli::try_parse_from(["td", "agent", "clear"]).is_ok());
     }
 
     #[test]
-    fn parses_init_with_samples() {
-        let cli = Cli::try_parse_from(["td", "init", "--with-samples"]).expect("parses init");
+    fn parses_exec_script_with_hyphen_args() {
+        let cli = Cli::try_parse_from([
+            "td",
+            "exec-script",
+            "lab1",
+            "./diagnose.sh",
+            "--args",
+            "-v --since 1h",
+            "--no-tty",
+        ])
+        .expect("parses exec-script");
+        match cli.command {
+            Some(Commands::ExecScript(args)) => {
+                assert_eq!(args.profile_id, "lab1");
+                assert_eq!(args.script, PathBuf::from("./diagnose.sh"));
+                assert_eq!(args.args.as_deref(), Some("-v --since 1h"));
+                assert!(args.no_tty && !args.tty);
+            }
+            _ => panic!("expected exec-script command"),
+        }
+        assert!(
+            Cli::try_parse_from(["td", "exec-script", "lab1", "a.sh", "--tty", "--no-tty"])
+                .is_err()
+        );
+    }
 
+    #[test]
+    fn parses_search_output_since() {
+        let cli = Cli::try_parse_from(["td", "search-output", "ORA-00600", "--since", "30d"])
+            .expect("parses search-output");
         match cli.command {
-            Some(Commands::Init(args)) => {
-                assert!(args.with_samples);
+            Some(Commands::SearchOutput(args)) => {
+                assert_eq!(args.query, "ORA-00600");
+                assert_eq!(
+                    parse_age_ms("--since", args.since.as_deref().unwrap()).unwrap(),
+                    30 * 24 * 60 * 60 * 1000
+                );
+                assert_eq!(args.limit, 50);
             }
-            _ => panic!("expected init command"),
+            _ => panic!("expected search-output command"),
         }
     }
 
     #[test]
-    fn sample_cmdset_install_is_idempotent() {
-        let conn = db::init_in_memory().unwrap();
-        let mut store = CmdSetStore::new(conn);
+    fn parses_suggest_from_history() {
+        let cli = Cli::try_parse_from([
+            "td",
+            "suggest",
+            "--from-history",
+            "/tmp/a",
+            "--from-history",
+            "/tmp/b",
+            "--yes",
+        ])
+        .expect("parses suggest");
+        match cli.command {
+            Some(Commands::Suggest(args)) => {
+                assert_eq!(args.from_history.len(), 2);
+                assert!(args.yes && !args.json);
+            }
+            _ => panic!("expected suggest command"),
+        }
+        assert!(Cli::try_parse_from(["td", "suggest", "--yes", "--json"]).is_err());
+    }
 
-        let first = install_sample_cmdsets(&mut store).unwrap();
-        let second = install_sample_cmdsets(&mut store).unwrap();
+    #[test]
+    fn resolves_summary_day_in_display_offset() {
+        let offset = UtcOffset::from_hms(9, 0, 0).unwrap();
+        let (date, start) = day_start_ms("--day", "2026-03-01", offset).unwrap();
+        assert_eq!(date.to_string(), "2026-03-01");
+        // 2026-02-28T15:00:00Z
+        assert_eq!(start, 1_772_290_800_000);
+        assert!(day_start_ms("--day", "2026-13-01", offset).is_err());
+        assert!(day_start_ms("--day", "last week", offset).is_err());
+        let (today, _) = day_start_ms("--day", "today", offset).unwrap();
+        let (yesterday, _) = day_start_ms("--day", "yesterday", offset).unwrap();
+        assert_eq!(yesterday.next_day(), Some(today));
+        assert_eq!(
+            time_bound_ms("--since", "2026-03-01", offset).unwrap(),
+            start
+        );
+        let week_ago = time_bound_ms("--since", "7d", offset).unwrap();
+        assert!((now_ms() - week_ago - 7 * 24 * 60 * 60 * 1000).abs() < 60_000);
+        assert!(time_bound_ms("--until", "soon", offset).is_err());
+    }
 
-        assert_eq!(first[0].status, SampleInstallStatus::Created);
-        assert_eq!(second[0].status, SampleInstallStatus::Skipped);
-        let steps = store.list_steps("linux-basic-check").unwrap();
-        assert_eq!(steps.len(), 5);
+    #[test]
+    fn parses_tunnel_env_format() {
+        let cli = Cli::try_parse_from(["td", "tunnel", "env", "db1", "--format", "powershell"])
+            .expect("parses tunnel env");
+        match cli.command {
+            Some(Commands::Tunnel {
+                command: TunnelCommands::Env(args),
+            }) => {
+                assert_eq!(args.profile_id, "db1");
+                assert!(matches!(args.format, EnvFormatArg::Powershell));
+            }
+            _ => panic!("expected tunnel env command"),
+        }
+    }
+
+    #[test]
+    fn parses_tunnel_forward_add_with_dest_check() {
+        let cli = Cli::try_parse_from([
+            "td",
+            "tunnel",
+            "forward",
+            "add",
+            "db1",
+            "pg",
+            "--listen",
+            "15432",
+            "--dest",
+            "db:5432",
+            "--check-dest",
+        ])
+        .expect("parses tunnel forward add");
+        match cli.command {
+            Some(Commands::Tunnel {
+                command:
+                    TunnelCommands::Forward {
+                        command: ForwardCommands::Add(args),
+                    },
+            }) => {
+                assert!(matches!(args.kind, ForwardKindArg::Local));
+                assert_eq!(args.dest.as_deref(), Some("db:5432"));
+                assert!(args.check_dest);
+            }
+            _ => panic!("expected tunnel forward add command"),
+        }
     }
 }
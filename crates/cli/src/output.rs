@@ -0,0 +1,211 @@
+//! Terminal presentation for human-readable listings: ANSI color and paging.
+//!
+//! Both are only used when stdout is a terminal. Color honours `--no-color`, `NO_COLOR`,
+//! and `TERM=dumb`; paging honours `--no-pager` and `PAGER` (default `less -FRX`).
+//! `TD_OUTPUT=json` asks every command that has `--json` for JSON instead.
+
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::OnceLock;
+
+static OPTIONS: OnceLock<OutputOptions> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputOptions {
+    pub no_color: bool,
+    pub no_pager: bool,
+}
+
+/// Records the global `--no-color` / `--no-pager` flags; call once at startup.
+pub fn configure(options: OutputOptions) {
+    let _ = OPTIONS.set(options);
+}
+
+fn options() -> OutputOptions {
+    OPTIONS.get().copied().unwrap_or_default()
+}
+
+pub fn color_enabled() -> bool {
+    color_allowed(
+        options().no_color,
+        env::var_os("NO_COLOR").as_deref(),
+        env::var("TERM").ok().as_deref(),
+        io::stdout().is_terminal(),
+    )
+}
+
+fn color_allowed(
+    no_color_flag: bool,
+    no_color_env: Option<&std::ffi::OsStr>,
+    term: Option<&str>,
+    is_tty: bool,
+) -> bool {
+    // https://no-color.org: any non-empty NO_COLOR value disables color.
+    let env_disabled = no_color_env.is_some_and(|value| !value.is_empty());
+    is_tty && !no_color_flag && !env_disabled && term != Some("dumb")
+}
+
+/// Whether `TD_OUTPUT` selects JSON output; `text` or an unset variable keep tables.
+pub fn json_from_env() -> bool {
+    json_requested(env::var("TD_OUTPUT").ok().as_deref())
+}
+
+fn json_requested(td_output: Option<&str>) -> bool {
+    td_output.is_some_and(|value| value.trim().eq_ignore_ascii_case("json"))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Tone {
+    Ok,
+    Warn,
+    Fail,
+    Dim,
+    Highlight,
+    /// One of six distinguishable colors, e.g. per host; the index wraps around.
+    Palette(usize),
+}
+
+const PALETTE: [&str; 6] = ["36", "35", "34", "33", "32", "31"];
+
+impl Tone {
+    fn code(self) -> &'static str {
+        match self {
+            Tone::Ok => "32",
+            Tone::Warn => "33",
+            Tone::Fail => "31",
+            Tone::Dim => "2",
+            Tone::Highlight => "7",
+            Tone::Palette(index) => PALETTE[index % PALETTE.len()],
+        }
+    }
+}
+
+/// Wraps `text` in an ANSI color when color output is enabled. Pad before painting so
+/// escape codes do not count towards column widths.
+pub fn paint(text: &str, tone: Tone) -> String {
+    paint_with(text, tone, color_enabled())
+}
+
+pub fn paint_with(text: &str, tone: Tone, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[{}m{text}\x1b[0m", tone.code())
+    } else {
+        text.to_string()
+    }
+}
+
+/// Pads a danger level name to `width` and colors high (yellow) and critical (red).
+pub fn danger_cell(level: &str, width: usize) -> String {
+    let cell = format!("{level:<width$}");
+    match level {
+        "high" => paint(&cell, Tone::Warn),
+        "critical" => paint(&cell, Tone::Fail),
+        _ => cell,
+    }
+}
+
+/// Destination for a listing: a pager when stdout is a terminal, otherwise stdout.
+struct Pager {
+    child: Option<Child>,
+}
+
+impl Pager {
+    fn start() -> Self {
+        if options().no_pager || !io::stdout().is_terminal() {
+            return Self { child: None };
+        }
+        Self {
+            child: spawn_pager(),
+        }
+    }
+
+    fn writer(&mut self) -> Box<dyn Write + '_> {
+        match self.child.as_mut().and_then(|child| child.stdin.as_mut()) {
+            Some(stdin) => Box::new(stdin),
+            None => Box::new(io::stdout().lock()),
+        }
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        if let Some(mut child) = self.child.take() {
+            drop(child.stdin.take());
+            child.wait()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            drop(child.stdin.take());
+            let _ = child.wait();
+        }
+    }
+}
+
+fn spawn_pager() -> Option<Child> {
+    let configured = env::var("PAGER")
+        .ok()
+        .filter(|value| !value.trim().is_empty());
+    let (program, args) = match configured.as_deref() {
+        Some("cat") => return None,
+        Some(raw) => {
+            let mut parts = raw.split_whitespace().map(str::to_string);
+            (parts.next()?, parts.collect::<Vec<_>>())
+        }
+        None if cfg!(windows) => return None,
+        None => ("less".to_string(), vec!["-FRX".to_string()]),
+    };
+    Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .ok()
+}
+
+/// Renders a listing through the pager (or straight to stdout). A closed pager pipe is
+/// treated as success so quitting `less` early is not an error.
+pub fn page(render: impl FnOnce(&mut dyn Write) -> io::Result<()>) -> io::Result<()> {
+    let mut pager = Pager::start();
+    let result = {
+        let mut out = pager.writer();
+        render(&mut out).and_then(|()| out.flush())
+    };
+    match result {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+        other => other?,
+    }
+    pager.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn color_respects_flag_env_and_tty() {
+        assert!(color_allowed(false, None, Some("xterm"), true));
+        assert!(!color_allowed(true, None, Some("xterm"), true));
+        assert!(!color_allowed(false, Some(OsStr::new("1")), None, true));
+        assert!(color_allowed(false, Some(OsStr::new("")), None, true));
+        assert!(!color_allowed(false, None, Some("dumb"), true));
+        assert!(!color_allowed(false, None, Some("xterm"), false));
+    }
+
+    #[test]
+    fn td_output_selects_json() {
+        assert!(json_requested(Some("json")));
+        assert!(json_requested(Some(" JSON ")));
+        assert!(!json_requested(Some("text")));
+        assert!(!json_requested(None));
+    }
+
+    #[test]
+    fn paint_wraps_only_when_enabled() {
+        assert_eq!(paint_with("up", Tone::Ok, false), "up");
+        assert_eq!(paint_with("down", Tone::Fail, true), "\x1b[31mdown\x1b[0m");
+    }
+}
@@ -0,0 +1,258 @@
+//! Inline fuzzy picker for commands that take a profile id: when it is left out on a
+//! terminal, `td connect`, `td exec`, and `td profile show` list the profiles below the
+//! prompt and narrow them as you type. Each word of the query must appear, in order, in
+//! the id, name, host, group, or tags. `--no-interactive`, or stdin or stderr not being a
+//! terminal, turns the picker off so scripts get an error instead of a prompt.
+
+use std::io::{self, IsTerminal, Write};
+
+use anyhow::Result;
+use crossterm::cursor::{MoveToColumn, MoveUp};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::queue;
+use crossterm::terminal::{Clear, ClearType};
+use tdcore::profile::{Profile, ProfileStore};
+
+use crate::exit;
+use crate::output::{self, Tone};
+use crate::RawModeGuard;
+
+/// Rows shown below the prompt.
+const VISIBLE_ROWS: usize = 10;
+
+/// The profile id given on the command line, or one picked interactively.
+pub fn profile_id_or_pick(
+    store: &ProfileStore,
+    profile_id: Option<String>,
+    interactive: bool,
+) -> Result<String> {
+    if let Some(profile_id) = profile_id {
+        return Ok(profile_id);
+    }
+    if !interactive || !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Err(exit::invalid(
+            "a profile id is required (the picker only runs on a terminal without --no-interactive)",
+        ));
+    }
+    let profiles = store.list()?;
+    if profiles.is_empty() {
+        return Err(exit::not_found("no profiles to pick from"));
+    }
+    pick(&profiles)?.ok_or_else(|| exit::invalid("no profile picked"))
+}
+
+/// Scores `text` against one query word as a case-insensitive subsequence; `None` when a
+/// character is missing. Consecutive characters and characters at the start of a word
+/// count extra, and an earlier first match beats a later one.
+fn fuzzy_score(word: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut at = 0;
+    let mut previous: Option<usize> = None;
+    let mut first = None;
+    for wanted in word.chars().flat_map(char::to_lowercase) {
+        let found = (at..text.len()).find(|&index| text[index] == wanted)?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        first.get_or_insert(found);
+        previous = Some(found);
+        at = found + 1;
+    }
+    Some(score - first.unwrap_or(0).min(20) as i64)
+}
+
+fn haystack(profile: &Profile) -> String {
+    let mut text = format!("{} {} {}", profile.profile_id, profile.name, profile.host);
+    if let Some(group) = &profile.group {
+        text.push(' ');
+        text.push_str(group);
+    }
+    for tag in &profile.tags {
+        text.push(' ');
+        text.push_str(tag);
+    }
+    text
+}
+
+/// Indexes of the profiles matching every word of `query`, best first; an empty query
+/// keeps the list order.
+fn rank(profiles: &[Profile], query: &str) -> Vec<usize> {
+    let words: Vec<&str> = query.split_whitespace().collect();
+    let mut scored: Vec<(i64, usize)> = profiles
+        .iter()
+        .enumerate()
+        .filter_map(|(index, profile)| {
+            let text = haystack(profile);
+            words
+                .iter()
+                .map(|word| fuzzy_score(word, &text))
+                .sum::<Option<i64>>()
+                .map(|score| (score, index))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    scored.into_iter().map(|(_, index)| index).collect()
+}
+
+fn pick(profiles: &[Profile]) -> Result<Option<String>> {
+    let mut query = String::new();
+    let mut selected = 0usize;
+    let mut drawn = 0usize;
+    let mut err = io::stderr();
+    let _raw = RawModeGuard::enter()?;
+    let picked = loop {
+        let matches = rank(profiles, &query);
+        selected = selected.min(matches.len().saturating_sub(1));
+        drawn = draw(&mut err, profiles, &matches, &query, selected, drawn)?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key {
+            KeyEvent {
+                code: KeyCode::Char('c' | 'd'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Esc, ..
+            } => break None,
+            KeyEvent {
+                code: KeyCode::Enter,
+                ..
+            } => break matches.get(selected).copied(),
+            KeyEvent {
+                code: KeyCode::Up, ..
+            }
+            | KeyEvent {
+                code: KeyCode::Char('p'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => selected = selected.saturating_sub(1),
+            KeyEvent {
+                code: KeyCode::Down,
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Char('n'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => selected += 1,
+            KeyEvent {
+                code: KeyCode::Backspace,
+                ..
+            } => {
+                query.pop();
+                selected = 0;
+            }
+            KeyEvent {
+                code: KeyCode::Char(ch),
+                modifiers,
+                ..
+            } if !modifiers.contains(KeyModifiers::CONTROL) => {
+                query.push(ch);
+                selected = 0;
+            }
+            _ => {}
+        }
+    };
+    clear(&mut err, drawn)?;
+    Ok(picked.map(|index| profiles[index].profile_id.clone()))
+}
+
+/// Redraws the prompt and the visible matches over the previous frame of `drawn` lines;
+/// returns the number of lines drawn.
+fn draw(
+    out: &mut impl Write,
+    profiles: &[Profile],
+    matches: &[usize],
+    query: &str,
+    selected: usize,
+    drawn: usize,
+) -> Result<usize> {
+    clear(out, drawn)?;
+    let start = selected.saturating_sub(VISIBLE_ROWS - 1);
+    let prompt = format!("profile ({}/{})> {query}", matches.len(), profiles.len());
+    let mut lines = 1;
+    write!(out, "{prompt}")?;
+    for (offset, &index) in matches.iter().skip(start).take(VISIBLE_ROWS).enumerate() {
+        let profile = &profiles[index];
+        let row = format!(
+            "{:<16} {:<20} {}@{}",
+            profile.profile_id, profile.name, profile.user, profile.host
+        );
+        let row = if start + offset == selected {
+            output::paint(&format!("> {row}"), Tone::Highlight)
+        } else {
+            format!("  {row}")
+        };
+        write!(out, "\r\n{row}")?;
+        lines += 1;
+    }
+    // Back to the end of the prompt line.
+    if lines > 1 {
+        queue!(out, MoveUp((lines - 1) as u16))?;
+    }
+    queue!(out, MoveToColumn(prompt.chars().count() as u16))?;
+    out.flush()?;
+    Ok(lines)
+}
+
+fn clear(out: &mut impl Write, drawn: usize) -> Result<()> {
+    if drawn > 0 {
+        queue!(out, MoveToColumn(0), Clear(ClearType::FromCursorDown))?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tdcore::profile::{DangerLevel, ProfileType};
+
+    fn profile(profile_id: &str, name: &str, host: &str, tags: &[&str]) -> Profile {
+        Profile {
+            profile_id: profile_id.to_string(),
+            name: name.to_string(),
+            profile_type: ProfileType::Ssh,
+            host: host.to_string(),
+            port: 22,
+            user: "ops".to_string(),
+            danger_level: DangerLevel::Normal,
+            group: None,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            note: None,
+            initial_send: None,
+            client_overrides: None,
+            created_at: 0,
+            updated_at: 0,
+            last_used_at: None,
+            archived_at: None,
+        }
+    }
+
+    #[test]
+    fn ranks_profiles_by_fuzzy_match() {
+        let profiles = vec![
+            profile("p_db1", "Database primary", "db1.example.com", &["prod"]),
+            profile("p_web1", "Web frontend", "web1.example.com", &["prod"]),
+            profile("p_web2", "Web staging", "web2.example.com", &["staging"]),
+        ];
+        assert_eq!(rank(&profiles, ""), [0, 1, 2]);
+        assert_eq!(rank(&profiles, "web"), [1, 2]);
+        assert_eq!(rank(&profiles, "wb stag"), [2]);
+        assert_eq!(rank(&profiles, "prod db").first(), Some(&0));
+        assert!(rank(&profiles, "zzz").is_empty());
+
+        assert!(fuzzy_score("web", "p_web1") > fuzzy_score("web", "w_e_b"));
+        assert_eq!(fuzzy_score("bew", "web"), None);
+    }
+}
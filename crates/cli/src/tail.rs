@@ -0,0 +1,410 @@
+use crate::exit;
+use crate::output::{self, Tone};
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::ffi::OsString;
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tdcore::oplog;
+use tdcore::profile::{Profile, ProfileStore};
+use tdcore::ssh::SshInvocation;
+use tdcore::util;
+use tracing::warn;
+
+/// Longest wait between reconnect attempts while following.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Keepalives make ssh notice a dropped network within ~45s instead of hanging.
+const KEEPALIVE_OPTIONS: [&str; 4] = [
+    "-o",
+    "ServerAliveInterval=15",
+    "-o",
+    "ServerAliveCountMax=3",
+];
+
+pub struct TailOptions {
+    pub path: String,
+    pub lines: u32,
+    pub follow: bool,
+    pub grep: Option<Regex>,
+    pub highlight: Vec<Regex>,
+    pub output: Option<PathBuf>,
+}
+
+/// Builds the remote `tail` command. `-F` keeps following across log rotation.
+pub fn remote_tail_command(path: &str, lines: u32, follow: bool) -> String {
+    let mut command = format!("tail -n {lines}");
+    if follow {
+        command.push_str(" -F");
+    }
+    command.push_str(" -- ");
+    command.push_str(&crate::shell_quote(path));
+    command
+}
+
+/// Ssh arguments for a tail: the profile invocation arguments with keepalive options placed
+/// before the destination (the last argument).
+pub fn tail_ssh_args(invocation_args: &[OsString], remote_command: &str) -> Vec<OsString> {
+    let mut args = invocation_args.to_vec();
+    let destination = args.pop();
+    args.extend(KEEPALIVE_OPTIONS.iter().map(OsString::from));
+    args.extend(destination);
+    args.push(OsString::from(remote_command));
+    args
+}
+
+/// Wraps every match of the grep and highlight patterns in reverse video.
+pub fn highlight_line(line: &str, patterns: &[&Regex], color: bool) -> String {
+    if !color || patterns.is_empty() {
+        return line.to_string();
+    }
+    let mut ranges = patterns
+        .iter()
+        .flat_map(|pattern| pattern.find_iter(line).map(|m| (m.start(), m.end())))
+        .filter(|(start, end)| start < end)
+        .collect::<Vec<_>>();
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    let mut rendered = String::with_capacity(line.len());
+    let mut cursor = 0;
+    for (start, end) in merged {
+        rendered.push_str(&line[cursor..start]);
+        rendered.push_str(&output::paint_with(
+            &line[start..end],
+            Tone::Highlight,
+            true,
+        ));
+        cursor = end;
+    }
+    rendered.push_str(&line[cursor..]);
+    rendered
+}
+
+/// One profile to tail, with its prepared ssh invocation.
+pub struct TailTarget {
+    pub profile: Profile,
+    pub invocation: SshInvocation,
+}
+
+enum TailEvent {
+    Line {
+        target: usize,
+        line: String,
+    },
+    Ended {
+        target: usize,
+        exit_code: Option<i32>,
+        duration_ms: i64,
+        reconnect_in: Option<Duration>,
+    },
+}
+
+/// A host that has been silent this long is listed in the lag indicator line.
+const LAG_THRESHOLD: Duration = Duration::from_secs(30);
+
+/// Streams the remote file from every target until ssh exits. With `follow`, a dropped
+/// connection is retried with exponential backoff; reconnects start at the end of the
+/// file so lines are not printed twice. With several targets, lines are interleaved as
+/// they arrive, prefixed with the profile name in a stable per-host color, and hosts
+/// that fall silent are reported on stderr. Each ssh attempt is logged as `tail`.
+pub fn run_tail(store: &ProfileStore, targets: &[TailTarget], options: &TailOptions) -> Result<()> {
+    let mut write_through = match &options.output {
+        Some(path) => Some(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("failed to open {}", path.display()))?,
+        ),
+        None => None,
+    };
+    let color = output::color_enabled();
+    let mut patterns = options.highlight.iter().collect::<Vec<_>>();
+    patterns.extend(options.grep.as_ref());
+    let multi = targets.len() > 1;
+    let name_width = targets
+        .iter()
+        .map(|target| util::display_width(&target.profile.name))
+        .max()
+        .unwrap_or(0);
+
+    let (tx, rx) = mpsc::channel();
+    for (index, target) in targets.iter().enumerate() {
+        let tx = tx.clone();
+        let client = target.invocation.client_path.clone();
+        let args = target.invocation.args.clone();
+        let (path, lines, follow) = (options.path.clone(), options.lines, options.follow);
+        thread::spawn(move || tail_worker(index, client, args, path, lines, follow, tx));
+    }
+    drop(tx);
+
+    let mut last_line_at = vec![Instant::now(); targets.len()];
+    let mut last_lag_report = Instant::now();
+    let mut failures = Vec::new();
+    loop {
+        let event = match rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(event) => event,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                if multi && options.follow && last_lag_report.elapsed() >= LAG_THRESHOLD {
+                    if let Some(report) = lag_report(targets, &last_line_at) {
+                        eprintln!("{}", output::paint(&report, Tone::Dim));
+                    }
+                    last_lag_report = Instant::now();
+                }
+                continue;
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        match event {
+            TailEvent::Line { target, line } => {
+                last_line_at[target] = Instant::now();
+                if options
+                    .grep
+                    .as_ref()
+                    .is_some_and(|grep| !grep.is_match(&line))
+                {
+                    continue;
+                }
+                let profile = &targets[target].profile;
+                let prefix = if multi {
+                    format!("{} | ", util::pad_display(&profile.name, name_width))
+                } else {
+                    String::new()
+                };
+                let shown = format!(
+                    "{}{}",
+                    if multi {
+                        output::paint(&prefix, Tone::Palette(host_color(profile)))
+                    } else {
+                        String::new()
+                    },
+                    highlight_line(&line, &patterns, color)
+                );
+                match writeln!(io::stdout().lock(), "{shown}") {
+                    Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+                    other => other?,
+                }
+                if let Some(file) = write_through.as_mut() {
+                    writeln!(file, "{prefix}{line}")?;
+                }
+            }
+            TailEvent::Ended {
+                target,
+                exit_code,
+                duration_ms,
+                reconnect_in,
+            } => {
+                let profile = &targets[target].profile;
+                log_tail(
+                    store,
+                    profile,
+                    &targets[target].invocation,
+                    options,
+                    exit_code,
+                    duration_ms,
+                )?;
+                if let Some(delay) = reconnect_in {
+                    eprintln!(
+                        "{}",
+                        output::paint(
+                            &format!(
+                                "-- connection to {} lost (exit {}); reconnecting in {}s --",
+                                profile.profile_id,
+                                exit_code
+                                    .map_or_else(|| "none".to_string(), |code| code.to_string()),
+                                delay.as_secs()
+                            ),
+                            Tone::Warn,
+                        )
+                    );
+                } else if exit_code != Some(0) {
+                    failures.push(format!(
+                        "{} ({})",
+                        profile.profile_id,
+                        exit_code.map_or_else(
+                            || "no exit code".to_string(),
+                            |code| format!("exit {code}")
+                        )
+                    ));
+                }
+            }
+        }
+    }
+    match failures.len() {
+        0 => Ok(()),
+        n if n == targets.len() && n == 1 => Err(exit::remote_failure(format!(
+            "tail failed: {}",
+            failures.join(", ")
+        ))),
+        n if n == targets.len() => Err(exit::remote_failure(format!(
+            "tail failed on every host: {}",
+            failures.join(", ")
+        ))),
+        _ => Err(exit::partial_failure(format!(
+            "tail failed on {} of {} hosts: {}",
+            failures.len(),
+            targets.len(),
+            failures.join(", ")
+        ))),
+    }
+}
+
+fn tail_worker(
+    target: usize,
+    client: PathBuf,
+    args: Vec<OsString>,
+    path: String,
+    mut lines: u32,
+    follow: bool,
+    tx: mpsc::Sender<TailEvent>,
+) {
+    let mut delay = Duration::from_secs(1);
+    loop {
+        let started = Instant::now();
+        let remote_command = remote_tail_command(&path, lines, follow);
+        let exit_code = stream_once(&client, &args, &remote_command, |line| {
+            tx.send(TailEvent::Line { target, line }).is_ok()
+        });
+        if started.elapsed() > MAX_RECONNECT_DELAY {
+            delay = Duration::from_secs(1);
+        }
+        let reconnect_in = follow.then_some(delay);
+        let ended = TailEvent::Ended {
+            target,
+            exit_code,
+            duration_ms: started.elapsed().as_millis() as i64,
+            reconnect_in,
+        };
+        if tx.send(ended).is_err() || reconnect_in.is_none() {
+            return;
+        }
+        thread::sleep(delay);
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        lines = 0;
+    }
+}
+
+/// Runs ssh once and forwards each stdout line; stops early when `on_line` returns false.
+/// Returns the ssh exit code, or `None` if ssh could not be started or was killed.
+fn stream_once(
+    client: &Path,
+    args: &[OsString],
+    remote_command: &str,
+    mut on_line: impl FnMut(String) -> bool,
+) -> Option<i32> {
+    let mut child = match Command::new(client)
+        .args(tail_ssh_args(args, remote_command))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) => {
+            warn!("failed to execute ssh for tail: {err}");
+            return None;
+        }
+    };
+    let stdout = child.stdout.take()?;
+    let mut reader = BufReader::new(stdout);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let line = String::from_utf8_lossy(&buf)
+            .trim_end_matches(['\n', '\r'])
+            .to_string();
+        if !on_line(line) {
+            let _ = child.kill();
+            break;
+        }
+    }
+    child.wait().ok().and_then(|status| status.code())
+}
+
+/// Stable color for a host prefix, derived from the profile id.
+fn host_color(profile: &Profile) -> usize {
+    profile.profile_id.bytes().fold(0usize, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(byte as usize)
+    })
+}
+
+fn lag_report(targets: &[TailTarget], last_line_at: &[Instant]) -> Option<String> {
+    let lagging = targets
+        .iter()
+        .zip(last_line_at)
+        .filter(|(_, at)| at.elapsed() >= LAG_THRESHOLD)
+        .map(|(target, at)| format!("{} {}s", target.profile.name, at.elapsed().as_secs()))
+        .collect::<Vec<_>>();
+    (!lagging.is_empty()).then(|| format!("-- no output since: {} --", lagging.join(", ")))
+}
+
+fn log_tail(
+    store: &ProfileStore,
+    profile: &Profile,
+    invocation: &SshInvocation,
+    options: &TailOptions,
+    exit_code: Option<i32>,
+    duration_ms: i64,
+) -> Result<()> {
+    store.touch_last_used(&profile.profile_id)?;
+    let entry = oplog::OpLogEntry {
+        op: "tail".into(),
+        profile_id: Some(profile.profile_id.clone()),
+        client_used: Some(invocation.client_path.to_string_lossy().into_owned()),
+        ok: exit_code == Some(0),
+        exit_code,
+        duration_ms: Some(duration_ms),
+        meta_json: Some(serde_json::json!({
+            "path": options.path,
+            "follow": options.follow,
+            "grep": options.grep.is_some(),
+        })),
+    };
+    if let Err(err) = oplog::log_operation(store.conn(), entry) {
+        warn!("failed to record tail in op_logs: {err}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_quoted_remote_tail_command() {
+        assert_eq!(
+            remote_tail_command("/var/log/syslog", 10, true),
+            "tail -n 10 -F -- '/var/log/syslog'"
+        );
+        assert_eq!(
+            remote_tail_command("/tmp/it's here.log", 0, false),
+            r"tail -n 0 -- '/tmp/it'\''s here.log'"
+        );
+    }
+
+    #[test]
+    fn highlights_overlapping_matches_once() {
+        let error = Regex::new("ERROR").unwrap();
+        let code = Regex::new("ERR").unwrap();
+        assert_eq!(
+            highlight_line("x ERROR y", &[&error, &code], true),
+            "x \x1b[7mERROR\x1b[0m y"
+        );
+        assert_eq!(highlight_line("x ERROR y", &[&error], false), "x ERROR y");
+    }
+}
@@ -1,8 +1,11 @@
+use std::cell::RefCell;
 use std::collections::HashSet;
+use std::marker::PhantomData;
 
 use data_encoding::BASE32_NOPAD;
 use once_cell::sync::Lazy;
-use rand::{thread_rng, RngCore};
+use rand::rngs::StdRng;
+use rand::{thread_rng, RngCore, SeedableRng};
 use regex::Regex;
 
 const MIN_ID_LEN: usize = 3;
@@ -54,12 +57,47 @@ pub fn validate_id(candidate: &str) -> Result<(), IdError> {
     Ok(())
 }
 
+thread_local! {
+    static SEEDED_RNG: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// Makes [`generate_id`] deterministic on the current thread until the returned guard is
+/// dropped, so fixtures and documentation examples get the same IDs on every run.
+/// Guards nest: dropping one restores the previous generator.
+pub fn seed_ids(seed: u64) -> SeededIds {
+    let previous = SEEDED_RNG.with(|rng| rng.replace(Some(StdRng::seed_from_u64(seed))));
+    SeededIds {
+        previous,
+        _not_send: PhantomData,
+    }
+}
+
+/// Guard returned by [`seed_ids`]; tied to the thread that created it.
+#[must_use = "IDs are only deterministic while the guard is alive"]
+pub struct SeededIds {
+    previous: Option<StdRng>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for SeededIds {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        SEEDED_RNG.with(|rng| rng.replace(previous));
+    }
+}
+
+fn fill_random(bytes: &mut [u8]) {
+    SEEDED_RNG.with(|rng| match rng.borrow_mut().as_mut() {
+        Some(seeded) => seeded.fill_bytes(bytes),
+        None => thread_rng().fill_bytes(bytes),
+    });
+}
+
 /// Generate a random ID with the given prefix using lowercase base32 characters.
 pub fn generate_id(prefix: &str) -> String {
-    let mut rng = thread_rng();
     let mut bytes = [0u8; 5];
     loop {
-        rng.fill_bytes(&mut bytes);
+        fill_random(&mut bytes);
         let encoded = BASE32_NOPAD.encode(&bytes).to_lowercase();
         let candidate = format!("{prefix}{encoded}");
         if validate_id(&candidate).is_ok() {
@@ -104,4 +142,28 @@ mod tests {
         assert!(id.len() <= 64);
         assert!(validate_id(&id).is_ok());
     }
+
+    #[test]
+    fn seeded_ids_repeat_and_restore() {
+        let first = {
+            let _ids = seed_ids(7);
+            vec![generate_id("p_"), generate_id("c_")]
+        };
+        let second = {
+            let _ids = seed_ids(7);
+            vec![generate_id("p_"), generate_id("c_")]
+        };
+        assert_eq!(first, second);
+        assert_ne!(first[0][2..], first[1][2..]);
+
+        let outer = seed_ids(1);
+        let expected = generate_id("p_");
+        drop(outer);
+        let _outer = seed_ids(1);
+        {
+            let _inner = seed_ids(2);
+            generate_id("p_");
+        }
+        assert_eq!(generate_id("p_"), expected);
+    }
 }
@@ -0,0 +1,177 @@
+//! Interactive SSH sessions that are running right now, so other td processes and the TUI
+//! can show which profiles are connected and end a session that was left open.
+//!
+//! `td connect` and the TUI's `s` record the pid of the process they start (ssh, or the
+//! `script`/PowerShell wrapper when session logging is on) in `active_sessions` while it
+//! runs, along with the process start time. Rows whose process is gone, or whose pid now
+//! belongs to a process started later, are dropped the next time the list is read, so a
+//! td that was killed does not leave a profile marked as connected and `terminate` never
+//! kills an unrelated process that reused the pid.
+
+use std::process::{Command, ExitStatus};
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use crate::db;
+use crate::error::{CoreError, Result};
+use crate::tunnel;
+use crate::util::now_ms;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActiveSession {
+    pub id: i64,
+    pub profile_id: String,
+    pub pid: u32,
+    pub started_at: i64,
+    /// `tunnel::process_start_marker` of `pid` at registration.
+    #[serde(skip)]
+    proc_start: Option<String>,
+}
+
+impl ActiveSession {
+    /// Whether `pid` is still the process that was registered. Rows registered before the
+    /// start marker was recorded only check that the pid is alive.
+    fn is_running(&self) -> bool {
+        match &self.proc_start {
+            Some(recorded) => tunnel::process_start_marker(self.pid).as_ref() == Some(recorded),
+            None => tunnel::is_pid_alive(self.pid),
+        }
+    }
+}
+
+pub fn register(conn: &Connection, profile_id: &str, pid: u32, started_at: i64) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO active_sessions (profile_id, pid, started_at, proc_start) \
+         VALUES (?1, ?2, ?3, ?4)",
+        params![
+            profile_id,
+            pid as i64,
+            started_at,
+            tunnel::process_start_marker(pid)
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn unregister(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute("DELETE FROM active_sessions WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+/// Sessions whose process is still running, oldest first. Stale rows are deleted.
+pub fn list(conn: &Connection) -> Result<Vec<ActiveSession>> {
+    let mut alive = Vec::new();
+    for session in all(conn)? {
+        if session.is_running() {
+            alive.push(session);
+        } else {
+            unregister(conn, session.id)?;
+        }
+    }
+    Ok(alive)
+}
+
+/// Ends the session's process. The td that started it logs the `connect` result as usual.
+/// A session whose pid has exited or been reused is dropped as stale instead.
+pub fn terminate(conn: &Connection, id: i64) -> Result<ActiveSession> {
+    let session = all(conn)?
+        .into_iter()
+        .find(|session| session.id == id)
+        .ok_or_else(|| CoreError::NotFound(format!("no active session {id}")))?;
+    if !session.is_running() {
+        unregister(conn, session.id)?;
+        return Err(CoreError::NotFound(format!(
+            "active session {id} is stale: pid {} is no longer its process",
+            session.pid
+        )));
+    }
+    tunnel::terminate_pid(session.pid)?;
+    unregister(conn, session.id)?;
+    Ok(session)
+}
+
+fn all(conn: &Connection) -> Result<Vec<ActiveSession>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, profile_id, pid, started_at, proc_start FROM active_sessions ORDER BY id",
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut sessions = Vec::new();
+    while let Some(row) = rows.next()? {
+        sessions.push(ActiveSession {
+            id: row.get(0)?,
+            profile_id: row.get(1)?,
+            pid: row.get::<_, i64>(2)? as u32,
+            started_at: row.get(3)?,
+            proc_start: row.get(4)?,
+        });
+    }
+    Ok(sessions)
+}
+
+/// Runs `command` to completion like `Command::status`, recording it as an active session
+/// of `profile_id` meanwhile. Tracking is best effort: a database error is logged and the
+/// session still runs.
+pub fn run_tracked(command: &mut Command, profile_id: &str) -> std::io::Result<ExitStatus> {
+    let mut child = command.spawn()?;
+    let tracked = db::init_connection().and_then(|conn| {
+        let id = register(&conn, profile_id, child.id(), now_ms())?;
+        Ok((conn, id))
+    });
+    if let Err(err) = &tracked {
+        tracing::warn!("failed to record active session for {profile_id}: {err}");
+    }
+    let status = child.wait();
+    if let Ok((conn, id)) = tracked {
+        if let Err(err) = unregister(&conn, id) {
+            tracing::warn!("failed to clear active session {id}: {err}");
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_in_memory;
+
+    #[test]
+    fn lists_live_sessions_and_drops_stale_ones() {
+        let conn = init_in_memory().unwrap();
+        let live = register(&conn, "p_web", std::process::id(), 10).unwrap();
+        // Far above any pid_max, so never a live process.
+        register(&conn, "p_db", 999_999_999, 20).unwrap();
+
+        let sessions = list(&conn).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, live);
+        assert_eq!(sessions[0].profile_id, "p_web");
+        let stale: i64 = conn
+            .query_row("SELECT COUNT(*) FROM active_sessions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stale, 1, "the dead row is gone");
+
+        unregister(&conn, live).unwrap();
+        assert!(list(&conn).unwrap().is_empty());
+        assert!(terminate(&conn, live).is_err());
+    }
+
+    #[test]
+    fn a_reused_pid_is_stale_and_never_terminated() {
+        let conn = init_in_memory().unwrap();
+        let id = register(&conn, "p_web", std::process::id(), 10).unwrap();
+        assert_eq!(list(&conn).unwrap().len(), 1);
+
+        // As if this pid had belonged to another process when the session was registered.
+        conn.execute(
+            "UPDATE active_sessions SET proc_start = 'earlier' WHERE id = ?1",
+            params![id],
+        )
+        .unwrap();
+        assert!(matches!(terminate(&conn, id), Err(CoreError::NotFound(_))));
+        let rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM active_sessions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 0, "the stale row is gone");
+    }
+}
@@ -1,7 +1,7 @@
 use serde::Serialize;
 use std::env;
 use std::path::Path;
-use std::process::{Command, Output};
+use std::process::{Command, Output, Stdio};
 
 #[derive(Debug, Clone, Serialize)]
 pub struct AgentStatus {
@@ -75,14 +75,88 @@ pub fn list() -> AgentList {
     }
 }
 
+/// Adds a key; ssh-add asks for the passphrase on the terminal when the key has one.
 pub fn run_add(key_path: &Path) -> std::io::Result<Output> {
-    Command::new("ssh-add").arg(key_path).output()
+    Command::new("ssh-add")
+        .arg(key_path)
+        .stdin(Stdio::inherit())
+        .output()
+}
+
+/// Set in ssh-add's environment by [`run_add_with_passphrase`]; td started as that
+/// ssh-add's askpass program prints it back (see [`askpass_reply`]).
+pub const ASKPASS_REPLY_ENV: &str = "TD_ASKPASS_REPLY";
+
+/// Adds a key with a passphrase td already holds. ssh-add runs `askpass` (the td binary)
+/// instead of prompting; the passphrase only lives in that ssh-add's environment.
+pub fn run_add_with_passphrase(
+    key_path: &Path,
+    askpass: &Path,
+    passphrase: &str,
+) -> std::io::Result<Output> {
+    let mut command = Command::new("ssh-add");
+    command
+        .arg(key_path)
+        .env("SSH_ASKPASS", askpass)
+        .env("SSH_ASKPASS_REQUIRE", "force")
+        .env(ASKPASS_REPLY_ENV, passphrase)
+        .stdin(Stdio::null());
+    // ssh-add before OpenSSH 8.4 ignores SSH_ASKPASS_REQUIRE and wants DISPLAY set.
+    if env::var_os("DISPLAY").is_none() {
+        command.env("DISPLAY", ":0");
+    }
+    command.output()
+}
+
+/// The answer when td runs as ssh-add's askpass program: the passphrase, or an empty
+/// line once ssh-add reports it wrong so ssh-add gives up instead of asking forever.
+pub fn askpass_reply(prompt: Option<&str>) -> Option<String> {
+    let reply = env::var(ASKPASS_REPLY_ENV).ok()?;
+    if prompt.is_some_and(|prompt| prompt.starts_with("Bad passphrase")) {
+        return Some(String::new());
+    }
+    Some(reply)
+}
+
+/// Removes one key; ssh-add looks up the matching `.pub` file next to `key_path`.
+pub fn run_remove(key_path: &Path) -> std::io::Result<Output> {
+    Command::new("ssh-add").arg("-d").arg(key_path).output()
 }
 
 pub fn run_clear() -> std::io::Result<Output> {
     Command::new("ssh-add").arg("-D").output()
 }
 
+/// Whether the agent already holds `key_path`, matched by fingerprint. `None` when the
+/// fingerprint or the agent's key list cannot be read.
+pub fn is_key_loaded(key_path: &Path) -> Option<bool> {
+    let output = Command::new("ssh-keygen")
+        .arg("-lf")
+        .arg(key_path)
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let fingerprint = key_fingerprint(&String::from_utf8_lossy(&output.stdout))?;
+    let list = list();
+    if list.error.is_some() {
+        return None;
+    }
+    Some(
+        list.keys
+            .iter()
+            .any(|line| key_fingerprint(line).as_deref() == Some(fingerprint.as_str())),
+    )
+}
+
+/// The fingerprint field of an `ssh-add -l` / `ssh-keygen -l` line
+/// (`256 SHA256:... comment (ED25519)`).
+fn key_fingerprint(line: &str) -> Option<String> {
+    line.split_whitespace().nth(1).map(str::to_string)
+}
+
 fn parse_list_output(output: &Output) -> AgentList {
     let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
     let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
@@ -127,3 +201,17 @@ fn contains_no_identities(text: &str) -> bool {
 fn run_ssh_add(args: &[&str]) -> std::io::Result<Output> {
     Command::new("ssh-add").args(args).output()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_fingerprint_from_list_line() {
+        assert_eq!(
+            key_fingerprint("256 SHA256:abcDEF alice@laptop (ED25519)").as_deref(),
+            Some("SHA256:abcDEF")
+        );
+        assert_eq!(key_fingerprint(""), None);
+    }
+}
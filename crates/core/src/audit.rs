@@ -0,0 +1,279 @@
+//! Op log entries as audit events for SIEM ingestion: `td log export` renders a time range
+//! as JSON lines or ArcSight CEF, and with `audit.file` set every logged operation is also
+//! appended to that file as one JSON line.
+//!
+//! Events carry what the op log already knows (profile, host, outcome) plus a SHA-256 of
+//! the command when the operation recorded one; command text, output, and auth arguments
+//! are never included.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use rusqlite::{params, Connection, Row};
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::error::{CoreError, Result};
+use crate::settings;
+
+const CEF_VENDOR: &str = "TeraDock";
+const CEF_PRODUCT: &str = "td";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditFormat {
+    Jsonl,
+    Cef,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub ts: i64,
+    /// `ts` as RFC 3339 in UTC.
+    pub time: String,
+    /// OS user that ran the operation, when known.
+    pub user: Option<String>,
+    /// `operator.name` when one was configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
+    pub host: Option<String>,
+    pub profile_id: Option<String>,
+    pub danger_level: Option<String>,
+    pub op: String,
+    pub command_sha256: Option<String>,
+    pub ok: bool,
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<i64>,
+}
+
+impl AuditEvent {
+    pub fn render(&self, format: AuditFormat) -> Result<String> {
+        match format {
+            AuditFormat::Jsonl => Ok(serde_json::to_string(self)?),
+            AuditFormat::Cef => Ok(self.cef()),
+        }
+    }
+
+    fn cef(&self) -> String {
+        let severity = match self.danger_level.as_deref() {
+            Some("critical") => 9,
+            Some("high") => 6,
+            _ => 3,
+        };
+        let mut extension = vec![
+            format!("rt={}", self.ts),
+            format!("outcome={}", if self.ok { "success" } else { "failure" }),
+            format!("externalId={}", self.id),
+        ];
+        let optional = [
+            ("suser", self.user.as_deref()),
+            ("dhost", self.host.as_deref()),
+            ("cs1Label", self.profile_id.as_ref().map(|_| "profileId")),
+            ("cs1", self.profile_id.as_deref()),
+            (
+                "cs2Label",
+                self.command_sha256.as_ref().map(|_| "commandSha256"),
+            ),
+            ("cs2", self.command_sha256.as_deref()),
+            ("cs3Label", self.operator.as_ref().map(|_| "operator")),
+            ("cs3", self.operator.as_deref()),
+        ];
+        for (key, value) in optional {
+            if let Some(value) = value {
+                extension.push(format!("{key}={}", cef_value(value)));
+            }
+        }
+        if let Some(code) = self.exit_code {
+            extension.push(format!("cn1Label=exitCode cn1={code}"));
+        }
+        if let Some(duration_ms) = self.duration_ms {
+            extension.push(format!("cn2Label=durationMs cn2={duration_ms}"));
+        }
+        format!(
+            "CEF:0|{CEF_VENDOR}|{CEF_PRODUCT}|{}|{}|{}|{severity}|{}",
+            env!("CARGO_PKG_VERSION"),
+            cef_header(&self.op),
+            cef_header(&format!("td {}", self.op)),
+            extension.join(" ")
+        )
+    }
+}
+
+/// Audit events logged in `[since_ms, until_ms)`, oldest first.
+pub fn events(
+    conn: &Connection,
+    since_ms: Option<i64>,
+    until_ms: Option<i64>,
+) -> Result<Vec<AuditEvent>> {
+    let mut stmt = conn.prepare(&format!(
+        "{SELECT_EVENT} WHERE (?1 IS NULL OR o.ts >= ?1) AND (?2 IS NULL OR o.ts < ?2) \
+         ORDER BY o.ts, o.id"
+    ))?;
+    let mut rows = stmt.query(params![since_ms, until_ms])?;
+    let mut events = Vec::new();
+    while let Some(row) = rows.next()? {
+        events.push(event_from_row(row)?);
+    }
+    Ok(events)
+}
+
+/// Appends op log entry `op_log_id` to `audit.file` as a JSON line when that is set.
+pub fn append(conn: &Connection, op_log_id: i64) -> Result<()> {
+    let Some(path) = settings::get_audit_file(conn)? else {
+        return Ok(());
+    };
+    let event = conn.query_row(
+        &format!("{SELECT_EVENT} WHERE o.id = ?1"),
+        params![op_log_id],
+        |row| Ok(event_from_row(row)),
+    )??;
+    append_line(&path, &event.render(AuditFormat::Jsonl)?)
+}
+
+const SELECT_EVENT: &str = "SELECT o.id, o.ts, o.op, o.profile_id, o.ok, o.exit_code, \
+     o.duration_ms, o.meta_json, o.os_user, o.operator, p.host, p.danger_level \
+     FROM op_logs o LEFT JOIN profiles p ON p.profile_id = o.profile_id";
+
+fn event_from_row(row: &Row<'_>) -> Result<AuditEvent> {
+    let ts: i64 = row.get("ts")?;
+    let meta: Option<String> = row.get("meta_json")?;
+    let meta = meta
+        .as_deref()
+        .map(serde_json::from_str::<serde_json::Value>)
+        .transpose()?;
+    // `exec`/`run` record `command_sha256`; `exec_script` records the script's `sha256`.
+    let command_sha256 = meta.as_ref().and_then(|meta| {
+        meta.get("command_sha256")
+            .or_else(|| meta.get("sha256"))
+            .and_then(|hash| hash.as_str())
+            .map(str::to_string)
+    });
+    Ok(AuditEvent {
+        id: row.get("id")?,
+        ts,
+        time: OffsetDateTime::from_unix_timestamp_nanos(i128::from(ts) * 1_000_000)
+            .ok()
+            .and_then(|time| time.format(&Rfc3339).ok())
+            .unwrap_or_else(|| ts.to_string()),
+        user: row.get("os_user")?,
+        operator: row.get("operator")?,
+        host: row.get("host")?,
+        profile_id: row.get("profile_id")?,
+        danger_level: row.get("danger_level")?,
+        op: row.get("op")?,
+        command_sha256,
+        ok: row.get::<_, i64>("ok")? != 0,
+        exit_code: row.get("exit_code")?,
+        duration_ms: row.get("duration_ms")?,
+    })
+}
+
+fn append_line(path: &Path, line: &str) -> Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|err| {
+        CoreError::InvalidSetting(format!("cannot open audit.file {}: {err}", path.display()))
+    })?;
+    writeln!(file, "{line}")?;
+    Ok(())
+}
+
+/// CEF header fields escape `\` and `|`.
+fn cef_header(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+/// CEF extension values escape `\` and `=`, and encode line breaks.
+fn cef_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_in_memory;
+    use crate::oplog::{self, OpLogEntry};
+    use crate::profile::{DangerLevel, NewProfile, ProfileStore, ProfileType};
+    use crate::util;
+
+    #[test]
+    fn renders_events_as_jsonl_and_cef_and_appends_to_the_audit_file() {
+        let store = ProfileStore::new(init_in_memory().unwrap());
+        store
+            .insert(NewProfile {
+                profile_id: Some("p_web".to_string()),
+                name: "web".to_string(),
+                profile_type: ProfileType::Ssh,
+                host: "web=1.example.com".to_string(),
+                port: 22,
+                user: "deploy".to_string(),
+                danger_level: DangerLevel::Critical,
+                group: None,
+                tags: Vec::new(),
+                note: None,
+                initial_send: None,
+                client_overrides: None,
+            })
+            .unwrap();
+        let conn = store.conn();
+        let path = std::env::temp_dir().join(format!(
+            "teradock-audit-{}-{}.jsonl",
+            std::process::id(),
+            util::now_ms()
+        ));
+        settings::set_setting(conn, settings::AUDIT_FILE_KEY, path.to_str().unwrap()).unwrap();
+        oplog::log_operation(
+            conn,
+            OpLogEntry {
+                op: "exec".to_string(),
+                profile_id: Some("p_web".to_string()),
+                client_used: Some("/usr/bin/ssh".to_string()),
+                ok: false,
+                exit_code: Some(2),
+                duration_ms: Some(15),
+                meta_json: Some(serde_json::json!({ "command_sha256": "ab12" })),
+            },
+        )
+        .unwrap();
+
+        let logged = events(conn, None, None).unwrap();
+        assert_eq!(logged.len(), 1);
+        let cef = logged[0].render(AuditFormat::Cef).unwrap();
+        assert!(cef.starts_with("CEF:0|TeraDock|td|"), "{cef}");
+        assert!(cef.contains("|exec|td exec|9|"), "{cef}");
+        assert!(cef.contains("outcome=failure"), "{cef}");
+        assert!(cef.contains("dhost=web\\=1.example.com"), "{cef}");
+        assert!(
+            cef.contains("cs1=p_web cs2Label=commandSha256 cs2=ab12"),
+            "{cef}"
+        );
+        assert!(cef.contains("cn1Label=exitCode cn1=2"), "{cef}");
+        assert!(events(conn, Some(util::now_ms() + 60_000), None)
+            .unwrap()
+            .is_empty());
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let line: serde_json::Value = serde_json::from_str(written.trim()).unwrap();
+        assert_eq!(line["op"], "exec");
+        assert_eq!(line["command_sha256"], "ab12");
+        assert_eq!(line["exit_code"], 2);
+    }
+}
@@ -0,0 +1,272 @@
+//! iCalendar (RFC 5545) export of scheduled CommandSet runs and maintenance windows, so a
+//! team calendar can show when TeraDock will touch production systems.
+//!
+//! Cron schedules have no exact RRULE equivalent, so their firings are listed one by one
+//! up to a horizon. Weekly maintenance windows become one weekly recurring event; dated
+//! ones become a single event. Times are written in UTC, converted from `ui.timezone`.
+
+use rusqlite::Connection;
+use time::{Duration, OffsetDateTime, UtcOffset};
+
+use crate::error::Result;
+use crate::maintenance::{self, WindowMode};
+use crate::policy::TimeWindow;
+use crate::schedule::{self, CronExpr};
+use crate::settings;
+
+/// Upper bound on the firings listed per schedule, so `* * * * *` cannot produce a
+/// calendar no client will load.
+const MAX_FIRINGS_PER_SCHEDULE: usize = 500;
+
+const MINUTES_PER_WEEK: i64 = 7 * 24 * 60;
+
+struct Event {
+    uid: String,
+    start_ms: i64,
+    end_ms: Option<i64>,
+    weekly: bool,
+    summary: String,
+    description: String,
+}
+
+/// Firings of every enabled schedule from `now_ms` through the next `days` days.
+pub fn schedules_ics(conn: &Connection, now_ms: i64, days: u32) -> Result<String> {
+    let offset = settings::get_display_offset(conn)?;
+    let until_ms = now_ms + i64::from(days) * 86_400_000;
+    let mut events = Vec::new();
+    for schedule in schedule::list(conn)? {
+        if !schedule.enabled {
+            continue;
+        }
+        let cron = CronExpr::parse(&schedule.cron)?;
+        let mut at_ms = now_ms;
+        for _ in 0..MAX_FIRINGS_PER_SCHEDULE {
+            match cron.next_after(at_ms, offset) {
+                Some(next) if next <= until_ms => at_ms = next,
+                _ => break,
+            }
+            events.push(Event {
+                uid: format!("schedule-{}-{}@teradock", schedule.id, at_ms / 1000),
+                start_ms: at_ms,
+                end_ms: None,
+                weekly: false,
+                summary: format!("td run {} on {}", schedule.cmdset_id, schedule.target),
+                description: format!(
+                    "Schedule {} ({}). {}",
+                    schedule.id,
+                    schedule.cron,
+                    if schedule.allow_critical {
+                        "Includes critical profiles."
+                    } else {
+                        "Critical profiles are skipped."
+                    }
+                ),
+            });
+        }
+    }
+    events.sort_by_key(|event| event.start_ms);
+    Ok(render(&events, now_ms))
+}
+
+/// Every maintenance window; weekly ones recur from the current week.
+pub fn maintenance_ics(conn: &Connection, now_ms: i64) -> Result<String> {
+    let offset = settings::get_display_offset(conn)?;
+    let mut events = Vec::new();
+    for window in maintenance::list(conn)? {
+        let (start_ms, end_ms, weekly) = match TimeWindow::parse(&window.span)? {
+            TimeWindow::Weekly { start, end } => {
+                let start_ms = week_start_ms(now_ms, offset) + i64::from(start) * 60_000;
+                let minutes = (i64::from(end) - i64::from(start)).rem_euclid(MINUTES_PER_WEEK);
+                (start_ms, start_ms + minutes * 60_000, true)
+            }
+            TimeWindow::Dates { start, end } => (
+                start.assume_offset(offset).unix_timestamp() * 1000,
+                end.assume_offset(offset).unix_timestamp() * 1000,
+                false,
+            ),
+        };
+        let effect = match window.mode {
+            WindowMode::Block => "Connections to critical profiles are blocked.",
+            WindowMode::Reason => "Connections to critical profiles need a reason.",
+        };
+        events.push(Event {
+            uid: format!("maintenance-{}@teradock", window.id),
+            start_ms,
+            end_ms: Some(end_ms),
+            weekly,
+            summary: format!(
+                "Maintenance ({}) on {}",
+                window.mode.as_str(),
+                window.target
+            ),
+            description: match &window.note {
+                Some(note) => format!("{note}\n{effect}"),
+                None => effect.to_string(),
+            },
+        });
+    }
+    Ok(render(&events, now_ms))
+}
+
+/// Monday 00:00 of the week containing `now_ms`, in `offset`.
+fn week_start_ms(now_ms: i64, offset: UtcOffset) -> i64 {
+    let now = OffsetDateTime::from_unix_timestamp(now_ms.div_euclid(1000))
+        .unwrap_or(OffsetDateTime::UNIX_EPOCH)
+        .to_offset(offset);
+    let monday = now.date() - Duration::days(i64::from(now.weekday().number_days_from_monday()));
+    monday.midnight().assume_offset(offset).unix_timestamp() * 1000
+}
+
+fn render(events: &[Event], now_ms: i64) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//TeraDock//td//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", event.uid));
+        lines.push(format!("DTSTAMP:{}", utc_stamp(now_ms)));
+        lines.push(format!("DTSTART:{}", utc_stamp(event.start_ms)));
+        if let Some(end_ms) = event.end_ms {
+            lines.push(format!("DTEND:{}", utc_stamp(end_ms)));
+        }
+        if event.weekly {
+            lines.push("RRULE:FREQ=WEEKLY".to_string());
+        }
+        lines.push(format!("SUMMARY:{}", escape_text(&event.summary)));
+        lines.push(format!("DESCRIPTION:{}", escape_text(&event.description)));
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines
+        .iter()
+        .map(|line| fold(line))
+        .collect::<Vec<_>>()
+        .join("")
+}
+
+/// `20261016T190000Z`.
+fn utc_stamp(ts_ms: i64) -> String {
+    let at = OffsetDateTime::from_unix_timestamp(ts_ms.div_euclid(1000))
+        .unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        at.year(),
+        u8::from(at.month()),
+        at.day(),
+        at.hour(),
+        at.minute(),
+        at.second()
+    )
+}
+
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Ends `line` with CRLF, folding it at 75 octets without splitting a UTF-8 character.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut width = 0;
+    for ch in line.chars() {
+        if width + ch.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(ch);
+        width += ch.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_in_memory;
+    use crate::maintenance::NewMaintenanceWindow;
+    use crate::schedule::{NewSchedule, ScheduleTarget};
+
+    // 2026-10-16 (a Friday) 19:00 UTC.
+    const FRIDAY_EVENING_MS: i64 = 1_792_177_200_000;
+
+    #[test]
+    fn exports_schedule_firings_and_maintenance_windows() {
+        let conn = &init_in_memory().unwrap();
+        conn.execute(
+            "INSERT INTO cmdsets (cmdset_id, name) VALUES ('c_health', 'health')",
+            [],
+        )
+        .unwrap();
+        schedule::add(
+            conn,
+            NewSchedule {
+                cmdset_id: "c_health".to_string(),
+                target: ScheduleTarget::parse("group:web").unwrap(),
+                cron: "0 6 * * *".to_string(),
+                enabled: true,
+                allow_critical: false,
+            },
+            0,
+        )
+        .unwrap();
+        let ics = schedules_ics(conn, FRIDAY_EVENING_MS, 3).unwrap();
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 3);
+        assert!(ics.contains("DTSTART:20261017T060000Z\r\n"));
+        assert!(ics.contains("SUMMARY:td run c_health on group:web\r\n"));
+
+        maintenance::add(
+            conn,
+            NewMaintenanceWindow {
+                target: ScheduleTarget::parse("tag:db").unwrap(),
+                span: "sat 22:00..sun 06:00".to_string(),
+                mode: WindowMode::Block,
+                note: Some("storage migration, phase 2".to_string()),
+            },
+            0,
+        )
+        .unwrap();
+        maintenance::add(
+            conn,
+            NewMaintenanceWindow {
+                target: ScheduleTarget::parse("p_db").unwrap(),
+                span: "2026-12-24..2026-12-26".to_string(),
+                mode: WindowMode::Reason,
+                note: None,
+            },
+            0,
+        )
+        .unwrap();
+        let ics = maintenance_ics(conn, FRIDAY_EVENING_MS).unwrap();
+        assert!(ics.contains(
+            "DTSTART:20261017T220000Z\r\nDTEND:20261018T060000Z\r\nRRULE:FREQ=WEEKLY\r\n"
+        ));
+        assert!(ics.contains("DESCRIPTION:storage migration\\, phase 2\\nConnections"));
+        assert!(ics.contains("DTSTART:20261224T000000Z\r\nDTEND:20261227T000000Z\r\n"));
+    }
+
+    #[test]
+    fn folds_long_lines_on_character_boundaries() {
+        let folded = fold(&format!("SUMMARY:{}", "日本語".repeat(10)));
+        assert!(folded.split("\r\n").all(|line| line.len() <= 75));
+        assert_eq!(
+            folded.replace("\r\n ", ""),
+            format!("SUMMARY:{}\r\n", "日本語".repeat(10))
+        );
+    }
+}
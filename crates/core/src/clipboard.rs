@@ -0,0 +1,261 @@
+//! Clipboard access through the platform's clipboard tools (wl-copy, xclip, xsel,
+//! pbcopy, clip.exe), plus expiry for sensitive values.
+//!
+//! Everything that copies a secret or a command line carrying credentials goes through
+//! [`copy_sensitive`], which clears the clipboard again after `clipboard.clear_after_secs`
+//! unless the user has copied something else in the meantime.
+
+use std::env;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use zeroize::Zeroizing;
+
+use crate::doctor::resolve_client;
+use crate::error::{CoreError, Result, RunError};
+
+/// A copy command and, when the platform has one, a matching paste command.
+#[derive(Debug, Clone)]
+pub struct Clipboard {
+    copy: Vec<OsString>,
+    paste: Option<Vec<OsString>>,
+}
+
+impl Clipboard {
+    /// Uses explicit commands, e.g. `["xclip", "-selection", "clipboard"]`.
+    pub fn with_commands(copy: Vec<OsString>, paste: Option<Vec<OsString>>) -> Self {
+        Self { copy, paste }
+    }
+
+    /// Finds a clipboard tool for the current session, or `None` when there is none.
+    pub fn detect() -> Option<Self> {
+        let tool = |name: &str| resolve_client(&[name]);
+        let build = |copy: (PathBuf, &[&str]), paste: Option<(PathBuf, &[&str])>| {
+            let argv = |(program, args): (PathBuf, &[&str])| {
+                std::iter::once(program.into_os_string())
+                    .chain(args.iter().map(OsString::from))
+                    .collect::<Vec<_>>()
+            };
+            Self::with_commands(argv(copy), paste.map(argv))
+        };
+        if cfg!(windows) {
+            let copy = tool("clip")?;
+            let paste = tool("powershell")
+                .map(|ps| (ps, &["-NoProfile", "-Command", "Get-Clipboard -Raw"][..]));
+            return Some(build((copy, &[]), paste));
+        }
+        if cfg!(target_os = "macos") {
+            return Some(build(
+                (tool("pbcopy")?, &[]),
+                tool("pbpaste").map(|p| (p, &[][..])),
+            ));
+        }
+        if env::var_os("WAYLAND_DISPLAY").is_some() {
+            if let Some(copy) = tool("wl-copy") {
+                let paste = tool("wl-paste").map(|p| (p, &["--no-newline"][..]));
+                return Some(build((copy, &[]), paste));
+            }
+        }
+        if env::var_os("DISPLAY").is_some() {
+            if let Some(xclip) = tool("xclip") {
+                return Some(build(
+                    (xclip.clone(), &["-selection", "clipboard"]),
+                    Some((xclip, &["-selection", "clipboard", "-o"])),
+                ));
+            }
+            if let Some(xsel) = tool("xsel") {
+                return Some(build(
+                    (xsel.clone(), &["--clipboard", "--input"]),
+                    Some((xsel, &["--clipboard", "--output"])),
+                ));
+            }
+        }
+        None
+    }
+
+    pub fn copy(&self, text: &str) -> Result<()> {
+        let (program, args) = split_argv(&self.copy)?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(CoreError::Run(RunError::CommandExecution(format!(
+                "clipboard copy failed with {status}"
+            ))));
+        }
+        Ok(())
+    }
+
+    /// Current clipboard text; `None` when the platform has no paste tool.
+    pub fn paste(&self) -> Result<Option<Zeroizing<String>>> {
+        let Some(paste) = &self.paste else {
+            return Ok(None);
+        };
+        let (program, args) = split_argv(paste)?;
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            return Err(CoreError::Run(RunError::CommandExecution(format!(
+                "clipboard paste failed with {}",
+                output.status
+            ))));
+        }
+        Ok(Some(Zeroizing::new(
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        )))
+    }
+
+    /// Empties the clipboard if it still holds `expected`. Without a paste tool the
+    /// clipboard is cleared unconditionally. Returns whether it was cleared.
+    pub fn clear_if_unchanged(&self, expected: &str) -> Result<bool> {
+        if let Some(current) = self.paste()? {
+            // Some tools append a trailing newline on paste.
+            if current.trim_end_matches(['\r', '\n']) != expected.trim_end_matches(['\r', '\n']) {
+                return Ok(false);
+            }
+        }
+        self.copy("")?;
+        Ok(true)
+    }
+}
+
+fn split_argv(argv: &[OsString]) -> Result<(&OsString, &[OsString])> {
+    argv.split_first()
+        .ok_or_else(|| CoreError::Run(RunError::CommandExecution("empty clipboard command".into())))
+}
+
+/// A pending clear of a sensitive clipboard value. The clear runs on a background
+/// thread at the deadline; dropping the handle clears immediately, so a process that
+/// exits early never leaves the value behind.
+pub struct ClipboardExpiry {
+    deadline: Instant,
+    trigger: Option<mpsc::Sender<()>>,
+    worker: Option<JoinHandle<bool>>,
+}
+
+impl ClipboardExpiry {
+    /// Time left before the clipboard is cleared, for countdown displays.
+    pub fn remaining(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+
+    /// Whether the clear has already run.
+    pub fn is_done(&self) -> bool {
+        self.worker
+            .as_ref()
+            .is_none_or(|worker| worker.is_finished())
+    }
+
+    /// Blocks until the deadline passes and the clear has run. Returns whether the
+    /// clipboard was cleared (false if it had been overwritten or clearing failed).
+    pub fn wait(mut self) -> bool {
+        self.join()
+    }
+
+    /// Clears now instead of waiting for the deadline.
+    pub fn clear_now(mut self) -> bool {
+        self.trigger.take();
+        self.join()
+    }
+
+    fn join(&mut self) -> bool {
+        self.worker
+            .take()
+            .and_then(|worker| worker.join().ok())
+            .unwrap_or(false)
+    }
+}
+
+impl Drop for ClipboardExpiry {
+    fn drop(&mut self) {
+        self.trigger.take();
+        self.join();
+    }
+}
+
+/// Copies `text` and schedules it to be cleared after `clear_after` (see
+/// `settings::get_clipboard_clear_after`). With `None` the value stays on the clipboard.
+pub fn copy_sensitive(
+    clipboard: &Clipboard,
+    text: &str,
+    clear_after: Option<Duration>,
+) -> Result<Option<ClipboardExpiry>> {
+    clipboard.copy(text)?;
+    let Some(after) = clear_after else {
+        return Ok(None);
+    };
+    let (trigger, cancelled) = mpsc::channel::<()>();
+    let clipboard = clipboard.clone();
+    let expected = Zeroizing::new(text.to_string());
+    let worker = thread::spawn(move || {
+        // Returns early when the handle is dropped or `clear_now` is called.
+        let _ = cancelled.recv_timeout(after);
+        match clipboard.clear_if_unchanged(&expected) {
+            Ok(cleared) => cleared,
+            Err(err) => {
+                tracing::warn!("failed to clear clipboard: {err}");
+                false
+            }
+        }
+    });
+    Ok(Some(ClipboardExpiry {
+        deadline: Instant::now() + after,
+        trigger: Some(trigger),
+        worker: Some(worker),
+    }))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn file_clipboard(path: &std::path::Path) -> Clipboard {
+        let sh = |script: String| vec!["sh".into(), "-c".into(), script.into()];
+        Clipboard::with_commands(
+            sh(format!("cat > '{}'", path.display())),
+            Some(sh(format!("cat '{}'", path.display()))),
+        )
+    }
+
+    #[test]
+    fn sensitive_copy_clears_only_when_unchanged() {
+        let dir = std::env::temp_dir().join(format!("td-clip-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("clipboard");
+        let clipboard = file_clipboard(&path);
+
+        let expiry = copy_sensitive(&clipboard, "hunter2", Some(Duration::from_millis(50)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hunter2");
+        assert!(expiry.wait());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+
+        let expiry = copy_sensitive(&clipboard, "hunter2", Some(Duration::from_secs(60)))
+            .unwrap()
+            .unwrap();
+        clipboard.copy("something else").unwrap();
+        assert!(!expiry.clear_now());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "something else");
+
+        assert!(copy_sensitive(&clipboard, "kept", None).unwrap().is_none());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "kept");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
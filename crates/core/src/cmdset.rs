@@ -1,10 +1,15 @@
+use std::path::PathBuf;
+
 use common::id::{generate_id, normalize_id, validate_id};
 use rusqlite::{params, Connection, Row};
 use serde_json::Value;
 
-use crate::error::{CoreError, Result};
+use crate::error::{CoreError, Result, RunError};
 use crate::parser::{ParserDefinition, ParserSpec, ParserType};
 
+/// Placeholder in step commands replaced with the remote staging directory.
+pub const STAGING_DIR_PLACEHOLDER: &str = "{{staging_dir}}";
+
 #[derive(Debug, Clone)]
 pub struct CmdSet {
     pub cmdset_id: String,
@@ -12,6 +17,124 @@ pub struct CmdSet {
     pub vars: Option<Value>,
 }
 
+/// A local file uploaded to the run's staging directory before any step runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StagedFile {
+    pub local: PathBuf,
+    /// File name inside the staging directory.
+    pub name: String,
+}
+
+/// A value in `vars.env_vars`: a literal, or `{"secret": "<secret_id>"}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvValue {
+    Static(String),
+    Secret(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvVarDecl {
+    pub name: String,
+    pub value: EnvValue,
+}
+
+/// Whether `name` can be exported by a POSIX shell.
+pub fn is_env_name(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl CmdSet {
+    /// Variables listed in `vars.env_vars`, an object mapping names to literals or
+    /// `{"secret": "<secret_id>"}` references.
+    pub fn env_vars(&self) -> Result<Vec<EnvVarDecl>> {
+        let Some(env) = self.vars.as_ref().and_then(|vars| vars.get("env_vars")) else {
+            return Ok(Vec::new());
+        };
+        let invalid = |message: String| CoreError::Run(RunError::InvalidCommandSpec(message));
+        let entries = env
+            .as_object()
+            .ok_or_else(|| invalid("vars.env_vars must be an object".to_string()))?;
+        let mut decls = Vec::with_capacity(entries.len());
+        for (name, value) in entries {
+            if !is_env_name(name) {
+                return Err(invalid(format!(
+                    "invalid environment variable name in vars.env_vars: {name}"
+                )));
+            }
+            let value = match value {
+                Value::String(value) => EnvValue::Static(value.clone()),
+                Value::Number(_) | Value::Bool(_) => EnvValue::Static(value.to_string()),
+                Value::Object(map) => match map.get("secret").and_then(Value::as_str) {
+                    Some(secret_id) if map.len() == 1 => EnvValue::Secret(secret_id.to_string()),
+                    _ => {
+                        return Err(invalid(format!(
+                        "vars.env_vars.{name} must be a value or {{\"secret\": \"<secret_id>\"}}"
+                    )))
+                    }
+                },
+                _ => return Err(invalid(format!("invalid vars.env_vars.{name}: {value}"))),
+            };
+            decls.push(EnvVarDecl {
+                name: name.clone(),
+                value,
+            });
+        }
+        Ok(decls)
+    }
+
+    /// Files listed in `vars.stage`, each either a local path or
+    /// `{"local": "<path>", "name": "<remote file name>"}`.
+    pub fn staged_files(&self) -> Result<Vec<StagedFile>> {
+        let Some(stage) = self.vars.as_ref().and_then(|vars| vars.get("stage")) else {
+            return Ok(Vec::new());
+        };
+        let invalid = |message: String| CoreError::Run(RunError::InvalidCommandSpec(message));
+        let entries = stage
+            .as_array()
+            .ok_or_else(|| invalid("vars.stage must be an array".to_string()))?;
+        let mut files: Vec<StagedFile> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let (local, name) = match entry {
+                Value::String(local) => (local.as_str(), None),
+                Value::Object(map) => (
+                    map.get("local").and_then(Value::as_str).ok_or_else(|| {
+                        invalid("vars.stage entries need a \"local\" path".to_string())
+                    })?,
+                    map.get("name").and_then(Value::as_str),
+                ),
+                _ => return Err(invalid(format!("invalid vars.stage entry: {entry}"))),
+            };
+            let local = PathBuf::from(local);
+            let name = match name {
+                Some(name) => name.to_string(),
+                None => local
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            };
+            // Names end up in remote shell commands, so keep them to a safe alphabet.
+            let safe = !name.is_empty()
+                && !name.starts_with('.')
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+            if !safe {
+                return Err(invalid(format!(
+                    "invalid staged file name {name:?} (use letters, digits, '.', '-', '_')"
+                )));
+            }
+            if files.iter().any(|file| file.name == name) {
+                return Err(invalid(format!("duplicate staged file name: {name}")));
+            }
+            files.push(StagedFile { local, name });
+        }
+        Ok(files)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StepOnError {
     Stop,
@@ -30,9 +153,9 @@ impl StepOnError {
         match value {
             "stop" => Ok(Self::Stop),
             "continue" => Ok(Self::Continue),
-            _ => Err(CoreError::InvalidCommandSpec(format!(
+            _ => Err(CoreError::Run(RunError::InvalidCommandSpec(format!(
                 "unknown on_error: {value}"
-            ))),
+            )))),
         }
     }
 }
@@ -75,6 +198,17 @@ pub struct NewCmdStep {
     pub parser_spec: ParserSpec,
 }
 
+impl From<&CmdStep> for NewCmdStep {
+    fn from(step: &CmdStep) -> Self {
+        Self {
+            cmd: step.cmd.clone(),
+            timeout_ms: step.timeout_ms,
+            on_error: step.on_error,
+            parser_spec: step.parser_spec.clone(),
+        }
+    }
+}
+
 pub struct CmdSetStore {
     conn: Connection,
 }
@@ -86,9 +220,9 @@ impl CmdSetStore {
 
     pub fn insert(&mut self, input: NewCmdSet) -> Result<CmdSet> {
         if input.steps.is_empty() {
-            return Err(CoreError::InvalidCommandSpec(
+            return Err(CoreError::Run(RunError::InvalidCommandSpec(
                 "cmdset must include at least one step".to_string(),
-            ));
+            )));
         }
         let cmdset_id = input.normalize_id()?;
         let vars_json = input.vars.as_ref().map(serde_json::to_string).transpose()?;
@@ -100,26 +234,43 @@ impl CmdSetStore {
             "#,
             params![cmdset_id, input.name, vars_json],
         )?;
-        for (idx, step) in input.steps.into_iter().enumerate() {
-            let timeout_ms = step.timeout_ms.map(|value| value as i64);
-            tx.execute(
-                r#"
-                INSERT INTO cmdsteps (cmdset_id, ord, cmd, timeout_ms, on_error, parser_spec)
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-                "#,
-                params![
-                    cmdset_id,
-                    (idx + 1) as i64,
-                    step.cmd,
-                    timeout_ms,
-                    step.on_error.as_str(),
-                    step.parser_spec.to_string()
-                ],
-            )?;
-        }
+        insert_steps(&tx, &cmdset_id, input.steps)?;
         tx.commit()?;
         self.get(&cmdset_id)?
-            .ok_or_else(|| CoreError::NotFound(cmdset_id))
+            .ok_or_else(|| CoreError::Run(RunError::CmdSetNotFound(cmdset_id)))
+    }
+
+    /// Renames a CommandSet and replaces all of its steps in one transaction; `vars` is
+    /// kept. Steps are numbered again from 1 in the given order.
+    pub fn update(
+        &mut self,
+        cmdset_id: &str,
+        name: &str,
+        steps: Vec<NewCmdStep>,
+    ) -> Result<CmdSet> {
+        if steps.is_empty() {
+            return Err(CoreError::Run(RunError::InvalidCommandSpec(
+                "cmdset must include at least one step".to_string(),
+            )));
+        }
+        let tx = self.conn.transaction()?;
+        if tx.execute(
+            "UPDATE cmdsets SET name = ?2 WHERE cmdset_id = ?1",
+            params![cmdset_id, name],
+        )? == 0
+        {
+            return Err(CoreError::Run(RunError::CmdSetNotFound(
+                cmdset_id.to_string(),
+            )));
+        }
+        tx.execute(
+            "DELETE FROM cmdsteps WHERE cmdset_id = ?1",
+            params![cmdset_id],
+        )?;
+        insert_steps(&tx, cmdset_id, steps)?;
+        tx.commit()?;
+        self.get(cmdset_id)?
+            .ok_or_else(|| CoreError::Run(RunError::CmdSetNotFound(cmdset_id.to_string())))
     }
 
     pub fn get(&self, cmdset_id: &str) -> Result<Option<CmdSet>> {
@@ -191,6 +342,27 @@ impl CmdSetStore {
     }
 }
 
+fn insert_steps(conn: &Connection, cmdset_id: &str, steps: Vec<NewCmdStep>) -> Result<()> {
+    for (idx, step) in steps.into_iter().enumerate() {
+        let timeout_ms = step.timeout_ms.map(|value| value as i64);
+        conn.execute(
+            r#"
+            INSERT INTO cmdsteps (cmdset_id, ord, cmd, timeout_ms, on_error, parser_spec)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            params![
+                cmdset_id,
+                (idx + 1) as i64,
+                step.cmd,
+                timeout_ms,
+                step.on_error.as_str(),
+                step.parser_spec.to_string()
+            ],
+        )?;
+    }
+    Ok(())
+}
+
 fn deserialize_cmdset(row: &Row<'_>) -> Result<CmdSet> {
     let vars_json: Option<String> = row.get("vars_json")?;
     Ok(CmdSet {
@@ -301,6 +473,28 @@ mod tests {
         assert_eq!(steps[0].ord, 1);
         assert_eq!(steps[1].ord, 2);
         assert_eq!(steps[0].parser_spec, ParserSpec::Raw);
+
+        let uptime = steps[1].clone();
+        let updated = store
+            .update(
+                "linux-basic-check",
+                "Linux quick check",
+                vec![NewCmdStep {
+                    cmd: uptime.cmd,
+                    timeout_ms: None,
+                    on_error: StepOnError::Stop,
+                    parser_spec: ParserSpec::Json,
+                }],
+            )
+            .unwrap();
+        assert_eq!(updated.name, "Linux quick check");
+        let steps = store.list_steps("linux-basic-check").unwrap();
+        assert_eq!(steps.len(), 1);
+        assert_eq!((steps[0].ord, steps[0].cmd.as_str()), (1, "uptime"));
+        assert!(store.update("linux-basic-check", "x", Vec::new()).is_err());
+        assert!(store
+            .update("missing", "x", vec![NewCmdStep::from(&steps[0])])
+            .is_err());
     }
 
     #[test]
@@ -316,6 +510,91 @@ mod tests {
             })
             .unwrap_err();
 
-        assert!(matches!(err, CoreError::InvalidCommandSpec(_)));
+        assert!(matches!(
+            err,
+            CoreError::Run(RunError::InvalidCommandSpec(_))
+        ));
+    }
+
+    #[test]
+    fn reads_env_vars_with_secret_references() {
+        let cmdset = |vars: Value| CmdSet {
+            cmdset_id: "c1".to_string(),
+            name: "env".to_string(),
+            vars: Some(vars),
+        };
+
+        let decls = cmdset(serde_json::json!({
+            "env": "prod",
+            "env_vars": {"LANG": "C", "RETRIES": 3, "API_TOKEN": {"secret": "s_api"}}
+        }))
+        .env_vars()
+        .unwrap();
+        assert_eq!(
+            decls,
+            vec![
+                EnvVarDecl {
+                    name: "API_TOKEN".to_string(),
+                    value: EnvValue::Secret("s_api".to_string()),
+                },
+                EnvVarDecl {
+                    name: "LANG".to_string(),
+                    value: EnvValue::Static("C".to_string()),
+                },
+                EnvVarDecl {
+                    name: "RETRIES".to_string(),
+                    value: EnvValue::Static("3".to_string()),
+                },
+            ]
+        );
+        for bad in [
+            serde_json::json!({"env_vars": ["LANG=C"]}),
+            serde_json::json!({"env_vars": {"1X": "a"}}),
+            serde_json::json!({"env_vars": {"X": {"secret": "s", "kind": "x"}}}),
+        ] {
+            assert!(cmdset(bad).env_vars().is_err());
+        }
+    }
+
+    #[test]
+    fn reads_staged_files_from_vars() {
+        let cmdset = |vars: Value| CmdSet {
+            cmdset_id: "c1".to_string(),
+            name: "stage".to_string(),
+            vars: Some(vars),
+        };
+
+        let files = cmdset(serde_json::json!({
+            "stage": ["scripts/check.sh", {"local": "/tmp/x.conf", "name": "app.conf"}]
+        }))
+        .staged_files()
+        .unwrap();
+        assert_eq!(
+            files,
+            vec![
+                StagedFile {
+                    local: PathBuf::from("scripts/check.sh"),
+                    name: "check.sh".to_string(),
+                },
+                StagedFile {
+                    local: PathBuf::from("/tmp/x.conf"),
+                    name: "app.conf".to_string(),
+                },
+            ]
+        );
+        assert!(cmdset(serde_json::json!({}))
+            .staged_files()
+            .unwrap()
+            .is_empty());
+        assert!(
+            cmdset(serde_json::json!({"stage": [{"local": "a", "name": "../a"}]}))
+                .staged_files()
+                .is_err()
+        );
+        assert!(
+            cmdset(serde_json::json!({"stage": ["a/run.sh", "b/run.sh"]}))
+                .staged_files()
+                .is_err()
+        );
     }
 }
@@ -1,22 +1,162 @@
 use std::ffi::OsString;
+use std::io::{Read, Write};
 use std::path::Path;
-use std::process::{Command, Output, Stdio};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
 use std::time::{Duration, Instant};
 
 use serde::Serialize;
 use wait_timeout::ChildExt;
+use zeroize::Zeroizing;
 
-use crate::cmdset::{CmdSetStore, StepOnError};
-use crate::error::{CoreError, Result};
+use crate::cmdset::{
+    is_env_name, CmdSet, CmdSetStore, CmdStep, EnvValue, StagedFile, StepOnError,
+    STAGING_DIR_PLACEHOLDER,
+};
+use crate::crypto::MasterKey;
+use crate::daemon::DaemonClient;
+use crate::db;
+use crate::doctor::ClientKind;
+use crate::error::{CoreError, ProfileError, Result, RunError};
+use crate::guard;
 use crate::oplog::{self, OpLogEntry};
 use crate::parser::{parse_output, ParserSpec};
 use crate::profile::{Profile, ProfileStore, ProfileType};
+use crate::remote_script;
+use crate::run_events::{OutputStream, RunEvent, RunEventBus};
+use crate::run_output::{self, NewRunOutput};
+use crate::run_record;
+use crate::secret::SecretStore;
+use crate::settings::{self, RunEnvMode, RunRecordFormat, SshBackend};
+use crate::ssh;
+#[cfg(feature = "ssh-native")]
+use crate::ssh_native::{NativeAuth, NativeSession};
+use crate::transfer::{build_scp_args, TransferDirection};
 
 pub struct CmdSetRunRequest<'a> {
     pub profile_id: &'a str,
     pub cmdset_id: &'a str,
     pub ssh: &'a Path,
     pub ssh_auth_args: &'a [OsString],
+    /// scp client used to upload `vars.stage` files; only required when the CommandSet
+    /// stages files.
+    pub scp: Option<&'a Path>,
+    /// Password for the native backend's password and keyboard-interactive auth
+    /// (`ssh.password_secret`); ignored by the external client.
+    pub ssh_password: Option<&'a str>,
+    /// Receives lifecycle events while the run progresses.
+    pub events: Option<&'a RunEventBus>,
+    /// Runs steps `guard.commands` rejects; the caller confirmed and logged the override.
+    pub force_guard: bool,
+    /// Set for every step on top of the CommandSet's `vars.env_vars`, and the values of
+    /// its secret references; the values never reach logged or stored commands.
+    pub env: &'a [RunEnvVar],
+}
+
+/// An environment variable for the steps of a run, such as an `api_token` secret.
+#[derive(Clone)]
+pub struct RunEnvVar {
+    pub name: String,
+    pub value: Zeroizing<String>,
+}
+
+impl RunEnvVar {
+    pub fn new(name: &str, value: Zeroizing<String>) -> Result<Self> {
+        if !is_env_name(name) {
+            return Err(CoreError::Run(RunError::InvalidCommandSpec(format!(
+                "invalid environment variable name: {name}"
+            ))));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            value,
+        })
+    }
+}
+
+/// The values of the CommandSet's `{"secret": "<id>"}` references in `vars.env_vars`.
+pub fn secret_env(
+    cmdset: &CmdSet,
+    secrets: &SecretStore,
+    master: &MasterKey,
+) -> Result<Vec<RunEnvVar>> {
+    cmdset
+        .env_vars()?
+        .into_iter()
+        .filter_map(|decl| match decl.value {
+            EnvValue::Secret(secret_id) => Some((decl.name, secret_id)),
+            EnvValue::Static(_) => None,
+        })
+        .map(|(name, secret_id)| {
+            RunEnvVar::new(&name, Zeroizing::new(secrets.reveal(master, &secret_id)?))
+        })
+        .collect()
+}
+
+/// [`secret_env`] with the key of an unlocked `td daemon`; empty when no daemon holds one,
+/// in which case a run that needs secrets fails with a hint to unlock.
+pub fn daemon_secret_env(cmdset_store: &CmdSetStore, cmdset_id: &str) -> Result<Vec<RunEnvVar>> {
+    let Some(cmdset) = cmdset_store.get(cmdset_id)? else {
+        return Ok(Vec::new());
+    };
+    if !cmdset
+        .env_vars()?
+        .iter()
+        .any(|decl| matches!(decl.value, EnvValue::Secret(_)))
+    {
+        return Ok(Vec::new());
+    }
+    let master = DaemonClient::connect()
+        .ok()
+        .flatten()
+        .and_then(|client| client.master_key().ok().flatten());
+    match master {
+        Some(master) => secret_env(&cmdset, &SecretStore::new(db::init_connection()?), &master),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The CommandSet's `vars.env_vars` with `provided` on top; every secret reference must be
+/// among `provided`.
+fn run_env(cmdset: &CmdSet, provided: &[RunEnvVar]) -> Result<Vec<RunEnvVar>> {
+    let mut env = Vec::new();
+    for decl in cmdset.env_vars()? {
+        if provided.iter().any(|var| var.name == decl.name) {
+            continue;
+        }
+        match decl.value {
+            EnvValue::Static(value) => env.push(RunEnvVar::new(&decl.name, Zeroizing::new(value))?),
+            EnvValue::Secret(secret_id) => {
+                return Err(CoreError::Run(RunError::InvalidCommandSpec(format!(
+                    "{} needs secret {secret_id}; run it with td run or unlock the daemon \
+                     (td secret unlock)",
+                    decl.name
+                ))))
+            }
+        }
+    }
+    env.extend(provided.iter().cloned());
+    Ok(env)
+}
+
+/// Remote command for a step whose environment is exported: the login shell evaluates the
+/// script read from stdin, so no value appears on a local or remote command line.
+const EVAL_STDIN: &str = "eval \"$(cat)\"";
+
+/// `cmd` behind `export NAME='value';` for each variable, for a POSIX remote shell. Only
+/// ever sent as a step's stdin (see [`EVAL_STDIN`]).
+fn with_env(cmd: &str, env: &[RunEnvVar]) -> Zeroizing<String> {
+    let mut remote = Zeroizing::new(String::new());
+    for var in env {
+        remote.push_str(&format!(
+            "export {}='{}'; ",
+            var.name,
+            var.value.replace('\'', "'\\''")
+        ));
+    }
+    remote.push_str(cmd);
+    remote
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -47,47 +187,431 @@ pub fn run_cmdset_ssh(
     request: CmdSetRunRequest<'_>,
     mut on_step: impl FnMut(&CmdStepRunResult) -> Result<()>,
 ) -> Result<CmdSetRunResult> {
-    let profile = profile_store
-        .get(request.profile_id)?
-        .ok_or_else(|| CoreError::NotFound(request.profile_id.to_string()))?;
+    let profile = profile_store.get(request.profile_id)?.ok_or_else(|| {
+        CoreError::Profile(ProfileError::NotFound(request.profile_id.to_string()))
+    })?;
     if profile.profile_type != ProfileType::Ssh {
-        return Err(CoreError::InvalidCommandSpec(
+        return Err(CoreError::Run(RunError::InvalidCommandSpec(
             "run only supports SSH profiles for now".to_string(),
-        ));
-    }
-    if cmdset_store.get(request.cmdset_id)?.is_none() {
-        return Err(CoreError::NotFound(request.cmdset_id.to_string()));
+        )));
     }
+    let cmdset = cmdset_store
+        .get(request.cmdset_id)?
+        .ok_or_else(|| CoreError::Run(RunError::CmdSetNotFound(request.cmdset_id.to_string())))?;
     let steps = cmdset_store.list_steps(request.cmdset_id)?;
     if steps.is_empty() {
-        return Err(CoreError::InvalidCommandSpec(format!(
+        return Err(CoreError::Run(RunError::InvalidCommandSpec(format!(
             "cmdset has no steps: {}",
             request.cmdset_id
-        )));
+        ))));
     }
+    if !request.force_guard {
+        let violations = guard::violations(profile_store.conn(), &profile.profile_id, &steps)?;
+        if !violations.is_empty() {
+            return Err(guard::denied(&violations));
+        }
+    }
+    let staged = cmdset.staged_files()?;
+    let env = run_env(&cmdset, request.env)?;
+    let scp = if staged.is_empty() {
+        None
+    } else {
+        if let Some(missing) = staged.iter().find(|file| !file.local.is_file()) {
+            return Err(CoreError::Run(RunError::InvalidCommandSpec(format!(
+                "staged file not found: {}",
+                missing.local.display()
+            ))));
+        }
+        Some(request.scp.ok_or_else(|| {
+            CoreError::Run(RunError::CommandExecution(
+                "scp client is required to stage files".to_string(),
+            ))
+        })?)
+    };
+
+    // A recorded run needs events even when the caller did not ask for them.
+    let record_format = settings::get_run_record(profile_store.conn(), &profile.profile_id)?;
+    let record_bus = RunEventBus::new();
+    let events = match (request.events, record_format) {
+        (None, RunRecordFormat::Log | RunRecordFormat::Cast) => Some(&record_bus),
+        (events, _) => events,
+    };
+    let recorder = match record_format {
+        RunRecordFormat::Off => None,
+        RunRecordFormat::Log | RunRecordFormat::Cast => events.map(RunEventBus::subscribe_timed),
+    };
+    let emit = |event: RunEvent| {
+        if let Some(bus) = events {
+            bus.publish(event);
+        }
+    };
+    let backend = settings::get_ssh_backend(profile_store.conn(), &profile.profile_id)?;
+    let transport = StepTransport::open(backend, profile_store, &request, &profile)?;
+    let env_mode = settings::get_run_env_mode(profile_store.conn(), &profile.profile_id)?;
+    let send_env =
+        env_mode == RunEnvMode::SendEnv && matches!(transport, StepTransport::External { .. });
 
     let run_started = Instant::now();
-    let mut stdout_all = String::new();
-    let mut stderr_all = String::new();
+    let started_at_ms = crate::util::now_ms();
+    emit(RunEvent::RunStarted {
+        profile_id: profile.profile_id.clone(),
+        cmdset_id: request.cmdset_id.to_string(),
+        steps: steps.len(),
+    });
+    let staging_dir = match scp {
+        Some(scp) => Some(create_staging_dir(
+            request.ssh,
+            request.ssh_auth_args,
+            &profile,
+            scp,
+            &staged,
+        )?),
+        None => None,
+    };
+    let outcome = run_steps(
+        cmdset_store,
+        &transport,
+        steps,
+        staging_dir.as_deref(),
+        &env,
+        send_env,
+        events,
+        &mut on_step,
+    );
+    if let Some(dir) = &staging_dir {
+        remove_staging_dir(request.ssh, request.ssh_auth_args, &profile, dir);
+    }
+    let (step_results, overall_ok, last_exit_code) = outcome?;
+    // The run already happened, so a recording that cannot be written only warns.
+    let recording = recorder.and_then(|recorder| {
+        let events = recorder.try_iter().collect::<Vec<_>>();
+        run_record::runs_dir()
+            .and_then(|dir| {
+                run_record::write(
+                    &dir,
+                    record_format,
+                    &profile.profile_id,
+                    request.cmdset_id,
+                    started_at_ms,
+                    &events,
+                )
+            })
+            .unwrap_or_else(|err| {
+                tracing::warn!("failed to write run recording: {err}");
+                None
+            })
+    });
+    let stdout_all = step_results
+        .iter()
+        .map(|step| step.stdout.as_str())
+        .collect::<String>();
+    let stderr_all = step_results
+        .iter()
+        .map(|step| step.stderr.as_str())
+        .collect::<String>();
+
+    let duration_ms = run_started.elapsed().as_millis() as i64;
+    profile_store.touch_last_used(&profile.profile_id)?;
+    let mut meta = serde_json::json!({
+        "cmdset_id": request.cmdset_id,
+        "steps_executed": step_results.len(),
+        "staged_files": staged.len(),
+        "backend": transport.backend_name(),
+        "command_sha256": remote_script::sha256_hex(
+            step_results
+                .iter()
+                .map(|step| step.cmd.as_str())
+                .collect::<Vec<_>>()
+                .join("\n")
+                .as_bytes()
+        ),
+    });
+    if let Some(path) = &recording {
+        meta["recording"] = path.display().to_string().into();
+    }
+    let op_log_id = oplog::log_operation(
+        profile_store.conn(),
+        OpLogEntry {
+            op: "run".into(),
+            profile_id: Some(profile.profile_id.clone()),
+            client_used: Some(transport.client_label()),
+            ok: overall_ok,
+            exit_code: Some(last_exit_code),
+            duration_ms: Some(duration_ms),
+            meta_json: Some(meta),
+        },
+    )?;
+    if settings::get_run_store_outputs(profile_store.conn(), &profile.profile_id)? {
+        let source = format!("run:{}", request.cmdset_id);
+        for step in &step_results {
+            run_output::record(
+                profile_store.conn(),
+                &NewRunOutput {
+                    profile_id: &profile.profile_id,
+                    source: &source,
+                    ord: Some(step.ord),
+                    cmd: &step.cmd,
+                    exit_code: Some(step.exit_code),
+                    stdout: &step.stdout,
+                    stderr: &step.stderr,
+                    op_log_id: Some(op_log_id),
+                },
+            )?;
+        }
+    }
+    emit(RunEvent::RunFinished {
+        ok: overall_ok,
+        exit_code: last_exit_code,
+        duration_ms,
+    });
+
+    Ok(CmdSetRunResult {
+        ok: overall_ok,
+        exit_code: last_exit_code,
+        duration_ms,
+        stdout: stdout_all,
+        stderr: stderr_all,
+        steps: step_results,
+    })
+}
+
+/// How steps reach the host: one ssh client process per step, or a single native
+/// session kept open for the whole run.
+enum StepTransport<'a> {
+    External {
+        ssh: &'a Path,
+        auth_args: &'a [OsString],
+        profile: &'a Profile,
+    },
+    #[cfg(feature = "ssh-native")]
+    Native(NativeSession),
+}
+
+struct StepOutput {
+    ok: bool,
+    exit_code: i32,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl<'a> StepTransport<'a> {
+    fn open(
+        backend: SshBackend,
+        profile_store: &ProfileStore,
+        request: &CmdSetRunRequest<'a>,
+        profile: &'a Profile,
+    ) -> Result<Self> {
+        match backend {
+            SshBackend::External => Ok(Self::External {
+                ssh: request.ssh,
+                auth_args: request.ssh_auth_args,
+                profile,
+            }),
+            #[cfg(feature = "ssh-native")]
+            SshBackend::Native => {
+                let auth = NativeAuth::from_settings(
+                    profile_store.conn(),
+                    &profile.profile_id,
+                    request.ssh_password,
+                )?;
+                Ok(Self::Native(NativeSession::connect(profile, &auth)?))
+            }
+            #[cfg(not(feature = "ssh-native"))]
+            SshBackend::Native => {
+                let _ = profile_store;
+                Err(CoreError::InvalidSetting(format!(
+                    "{} is native, but this td was built without the ssh-native feature",
+                    settings::SSH_BACKEND_KEY
+                )))
+            }
+        }
+    }
+
+    fn backend_name(&self) -> &'static str {
+        match self {
+            Self::External { .. } => "external",
+            #[cfg(feature = "ssh-native")]
+            Self::Native(_) => "native",
+        }
+    }
+
+    fn client_label(&self) -> String {
+        match self {
+            Self::External { ssh, .. } => ssh.to_string_lossy().into_owned(),
+            #[cfg(feature = "ssh-native")]
+            Self::Native(_) => "libssh2".to_string(),
+        }
+    }
+
+    /// Runs one step; with `events`, output is published as `ord`'s chunks. `exported`
+    /// variables travel with the command over stdin; `send_env` is passed with
+    /// `ssh -o SendEnv` and only used by the external client.
+    fn run(
+        &self,
+        cmd: &str,
+        exported: &[RunEnvVar],
+        send_env: &[RunEnvVar],
+        timeout: Option<Duration>,
+        events: Option<(&RunEventBus, i64)>,
+    ) -> Result<StepOutput> {
+        let (remote, input) = if exported.is_empty() {
+            (cmd, None)
+        } else {
+            (EVAL_STDIN, Some(with_env(cmd, exported)))
+        };
+        match self {
+            Self::External {
+                ssh,
+                auth_args,
+                profile,
+            } => {
+                let command = build_ssh_command_with_env(ssh, profile, auth_args, remote, send_env);
+                let output = match (events, timeout, input) {
+                    (Some((bus, ord)), _, input) => {
+                        run_streaming(command, input, timeout, |stream, chunk| {
+                            bus.publish(RunEvent::Output {
+                                ord,
+                                stream,
+                                chunk: String::from_utf8_lossy(chunk).into_owned(),
+                            })
+                        })?
+                    }
+                    (None, Some(timeout), input) => {
+                        run_with_input_timeout(command, input, timeout)?
+                    }
+                    (None, None, Some(input)) => {
+                        spawn_with_input(command, Some(input))?.wait_with_output()?
+                    }
+                    (None, None, None) => command_output(command)?,
+                };
+                Ok(StepOutput {
+                    ok: output.status.success(),
+                    exit_code: output.status.code().unwrap_or_default(),
+                    stdout: output.stdout,
+                    stderr: output.stderr,
+                })
+            }
+            #[cfg(feature = "ssh-native")]
+            Self::Native(session) => {
+                let output =
+                    session.exec(remote, input.as_deref().map(String::as_bytes), timeout)?;
+                if let Some((bus, ord)) = events {
+                    for (stream, bytes) in [
+                        (OutputStream::Stdout, &output.stdout),
+                        (OutputStream::Stderr, &output.stderr),
+                    ] {
+                        if !bytes.is_empty() {
+                            bus.publish(RunEvent::Output {
+                                ord,
+                                stream,
+                                chunk: String::from_utf8_lossy(bytes).into_owned(),
+                            });
+                        }
+                    }
+                }
+                Ok(StepOutput {
+                    ok: output.exit_code == 0,
+                    exit_code: output.exit_code,
+                    stdout: output.stdout,
+                    stderr: output.stderr,
+                })
+            }
+        }
+    }
+}
+
+type StepsOutcome = (Vec<CmdStepRunResult>, bool, i32);
+
+/// Runs `cmdset_id` with the profile's configured ssh client and agent/key auth, for runs
+/// nobody is at a terminal for (schedules, remote hooks): no password is available.
+pub fn run_cmdset_unattended(
+    profile_store: &ProfileStore,
+    cmdset_store: &CmdSetStore,
+    profile: &Profile,
+    cmdset_id: &str,
+) -> Result<CmdSetRunResult> {
+    let conn = profile_store.conn();
+    let ssh_error = |err: ssh::SshBuildError| CoreError::InvalidSetting(err.to_string());
+    let client = ssh::resolve_client_for(ClientKind::Ssh, profile, conn).map_err(ssh_error)?;
+    // Only needed when the CommandSet stages files; the runner reports a missing client.
+    let scp = ssh::resolve_client_for(ClientKind::Scp, profile, conn).ok();
+    let auth = ssh::ssh_profile_auth_context(conn, &profile.profile_id).map_err(ssh_error)?;
+    run_cmdset_ssh(
+        profile_store,
+        cmdset_store,
+        CmdSetRunRequest {
+            profile_id: &profile.profile_id,
+            cmdset_id,
+            ssh: &client,
+            ssh_auth_args: &auth.args,
+            scp: scp.as_deref(),
+            ssh_password: None,
+            events: None,
+            force_guard: false,
+            env: &[],
+        },
+        |_| Ok(()),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_steps(
+    cmdset_store: &CmdSetStore,
+    transport: &StepTransport<'_>,
+    steps: Vec<CmdStep>,
+    staging_dir: Option<&str>,
+    env: &[RunEnvVar],
+    send_env: bool,
+    events: Option<&RunEventBus>,
+    on_step: &mut impl FnMut(&CmdStepRunResult) -> Result<()>,
+) -> Result<StepsOutcome> {
+    // With SendEnv, ssh passes the variables itself and the command stays as stored.
+    let (exported, sent) = if send_env {
+        (&[][..], env)
+    } else {
+        (env, &[][..])
+    };
     let mut step_results = Vec::new();
     let mut overall_ok = true;
     let mut last_exit_code = 0;
 
     for step in steps {
-        let command = build_ssh_command(request.ssh, &profile, request.ssh_auth_args, &step.cmd);
+        let cmd = match staging_dir {
+            Some(dir) => step.cmd.replace(STAGING_DIR_PLACEHOLDER, dir),
+            None => step.cmd,
+        };
+        if let Some(bus) = events {
+            bus.publish(RunEvent::StepStarted {
+                ord: step.ord,
+                cmd: cmd.clone(),
+            });
+        }
+        let step_events = events.map(|bus| (bus, step.ord));
         let step_started = Instant::now();
         let output = match step.timeout_ms {
-            Some(ms) => run_with_timeout(command, Duration::from_millis(ms)).map_err(|err| {
-                CoreError::CommandExecution(format!(
-                    "step {} timed out after {ms}ms: {err}",
-                    step.ord
-                ))
-            })?,
-            None => command_output(command)?,
+            Some(ms) => transport
+                .run(
+                    &cmd,
+                    exported,
+                    sent,
+                    Some(Duration::from_millis(ms)),
+                    step_events,
+                )
+                .map_err(|err| {
+                    let detail = match err {
+                        CoreError::Io(err) => err.to_string(),
+                        other => other.to_string(),
+                    };
+                    CoreError::Run(RunError::CommandExecution(format!(
+                        "step {} timed out after {ms}ms: {detail}",
+                        step.ord
+                    )))
+                })?,
+            None => transport.run(&cmd, exported, sent, None, step_events)?,
         };
         let duration_ms = step_started.elapsed().as_millis() as i64;
-        let exit_code = output.status.code().unwrap_or_default();
-        let ok = output.status.success();
+        let exit_code = output.exit_code;
+        let ok = output.ok;
         last_exit_code = exit_code;
         if !ok {
             overall_ok = false;
@@ -95,18 +619,28 @@ pub fn run_cmdset_ssh(
 
         let stdout_text = String::from_utf8_lossy(&output.stdout).to_string();
         let stderr_text = String::from_utf8_lossy(&output.stderr).to_string();
-        stdout_all.push_str(&stdout_text);
-        stderr_all.push_str(&stderr_text);
 
         let parser_def = match &step.parser_spec {
             ParserSpec::Regex(id) => cmdset_store.get_parser(id)?,
             _ => None,
         };
         let parsed = parse_output(&step.parser_spec, &stdout_text, parser_def.as_ref())?;
+        if let Some(bus) = events {
+            bus.publish(RunEvent::StepFinished {
+                ord: step.ord,
+                ok,
+                exit_code,
+                duration_ms,
+            });
+            bus.publish(RunEvent::StepParsed {
+                ord: step.ord,
+                parsed: parsed.clone(),
+            });
+        }
 
         let step_result = CmdStepRunResult {
             ord: step.ord,
-            cmd: step.cmd,
+            cmd,
             ok,
             exit_code,
             stdout: stdout_text,
@@ -121,40 +655,97 @@ pub fn run_cmdset_ssh(
             break;
         }
     }
+    Ok((step_results, overall_ok, last_exit_code))
+}
 
-    let duration_ms = run_started.elapsed().as_millis() as i64;
-    profile_store.touch_last_used(&profile.profile_id)?;
-    oplog::log_operation(
-        profile_store.conn(),
-        OpLogEntry {
-            op: "run".into(),
-            profile_id: Some(profile.profile_id),
-            client_used: Some(request.ssh.to_string_lossy().into_owned()),
-            ok: overall_ok,
-            exit_code: Some(last_exit_code),
-            duration_ms: Some(duration_ms),
-            meta_json: Some(serde_json::json!({
-                "cmdset_id": request.cmdset_id,
-                "steps_executed": step_results.len(),
-            })),
-        },
-    )?;
+/// Creates a private temp directory on the remote host and uploads the staged files
+/// into it. The directory is removed again if an upload fails.
+pub(crate) fn create_staging_dir(
+    ssh: &Path,
+    auth_args: &[OsString],
+    profile: &Profile,
+    scp: &Path,
+    staged: &[StagedFile],
+) -> Result<String> {
+    let command = build_ssh_command(
+        ssh,
+        profile,
+        auth_args,
+        "mktemp -d \"${TMPDIR:-/tmp}/teradock-stage.XXXXXX\"",
+    );
+    let output = command_output(command)?;
+    let dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    // The path is substituted into step commands, so only accept a plain absolute path.
+    let plain = dir.starts_with('/')
+        && dir
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '.' | '-' | '_'));
+    if !output.status.success() || !plain {
+        return Err(CoreError::Run(RunError::CommandExecution(format!(
+            "failed to create remote staging directory: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))));
+    }
+    for file in staged {
+        let remote = format!("{dir}/{}", file.name);
+        let output = Command::new(scp)
+            .args(auth_args)
+            .args(build_scp_args(
+                profile,
+                TransferDirection::Push,
+                &file.local,
+                &remote,
+            ))
+            .stdin(Stdio::null())
+            .output();
+        let failure = match output {
+            Ok(output) if output.status.success() => continue,
+            Ok(output) => String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            Err(err) => err.to_string(),
+        };
+        remove_staging_dir(ssh, auth_args, profile, &dir);
+        return Err(CoreError::Run(RunError::CommandExecution(format!(
+            "failed to stage {}: {failure}",
+            file.local.display()
+        ))));
+    }
+    Ok(dir)
+}
 
-    Ok(CmdSetRunResult {
-        ok: overall_ok,
-        exit_code: last_exit_code,
-        duration_ms,
-        stdout: stdout_all,
-        stderr: stderr_all,
-        steps: step_results,
-    })
+pub(crate) fn remove_staging_dir(ssh: &Path, auth_args: &[OsString], profile: &Profile, dir: &str) {
+    let command = build_ssh_command(ssh, profile, auth_args, &format!("rm -rf -- '{dir}'"));
+    match command_output(command) {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => tracing::warn!(
+            "failed to remove staging dir {dir}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(err) => tracing::warn!("failed to remove staging dir {dir}: {err}"),
+    }
 }
 
 fn build_ssh_command(ssh: &Path, profile: &Profile, auth_args: &[OsString], cmd: &str) -> Command {
+    build_ssh_command_with_env(ssh, profile, auth_args, cmd, &[])
+}
+
+/// Sets `env` on the ssh client and asks it to forward each variable; the server only
+/// accepts names its `AcceptEnv` lists.
+fn build_ssh_command_with_env(
+    ssh: &Path,
+    profile: &Profile,
+    auth_args: &[OsString],
+    cmd: &str,
+    env: &[RunEnvVar],
+) -> Command {
     let mut command = Command::new(ssh);
+    command.arg("-p").arg(profile.port.to_string());
+    for var in env {
+        command
+            .arg("-o")
+            .arg(format!("SendEnv={}", var.name))
+            .env(&var.name, var.value.as_str());
+    }
     command
-        .arg("-p")
-        .arg(profile.port.to_string())
         .args(auth_args)
         .arg(format!("{}@{}", profile.user, profile.host))
         .arg(cmd)
@@ -167,8 +758,34 @@ fn command_output(mut command: Command) -> Result<Output> {
     command.output().map_err(CoreError::Io)
 }
 
-fn run_with_timeout(mut command: Command, timeout: Duration) -> std::io::Result<Output> {
+pub(crate) fn run_with_timeout(command: Command, timeout: Duration) -> std::io::Result<Output> {
+    run_with_input_timeout(command, None, timeout)
+}
+
+/// Spawns `command` and, with `input`, writes it to the child's stdin from a helper thread
+/// and closes it, so a large input cannot stall against unread output.
+fn spawn_with_input(
+    mut command: Command,
+    input: Option<Zeroizing<String>>,
+) -> std::io::Result<Child> {
+    if input.is_some() {
+        command.stdin(Stdio::piped());
+    }
     let mut child = command.spawn()?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
+    }
+    Ok(child)
+}
+
+fn run_with_input_timeout(
+    command: Command,
+    input: Option<Zeroizing<String>>,
+    timeout: Duration,
+) -> std::io::Result<Output> {
+    let mut child = spawn_with_input(command, input)?;
     let status = child.wait_timeout(timeout)?;
     if status.is_none() {
         let _ = child.kill();
@@ -181,6 +798,93 @@ fn run_with_timeout(mut command: Command, timeout: Duration) -> std::io::Result<
     child.wait_with_output()
 }
 
+/// Like `run_with_timeout`, but hands stdout/stderr to `on_chunk` as they are read.
+fn run_streaming(
+    command: Command,
+    input: Option<Zeroizing<String>>,
+    timeout: Option<Duration>,
+    mut on_chunk: impl FnMut(OutputStream, &[u8]),
+) -> std::io::Result<Output> {
+    let mut child = spawn_with_input(command, input)?;
+    let (tx, rx) = mpsc::channel();
+    spawn_pipe_reader(child.stdout.take(), OutputStream::Stdout, tx.clone());
+    spawn_pipe_reader(child.stderr.take(), OutputStream::Stderr, tx);
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let timed_out = || {
+        std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!(
+                "timeout after {}ms",
+                timeout.unwrap_or_default().as_millis()
+            ),
+        )
+    };
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    loop {
+        let received = match deadline {
+            Some(deadline) => rx.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok((stream, chunk)) => {
+                on_chunk(stream, &chunk);
+                match stream {
+                    OutputStream::Stdout => stdout.extend_from_slice(&chunk),
+                    OutputStream::Stderr => stderr.extend_from_slice(&chunk),
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(timed_out());
+            }
+        }
+    }
+    let status = match deadline {
+        Some(deadline) => {
+            match child.wait_timeout(deadline.saturating_duration_since(Instant::now()))? {
+                Some(status) => status,
+                None => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(timed_out());
+                }
+            }
+        }
+        None => child.wait()?,
+    };
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+fn spawn_pipe_reader(
+    pipe: Option<impl Read + Send + 'static>,
+    stream: OutputStream,
+    tx: Sender<(OutputStream, Vec<u8>)>,
+) {
+    let Some(mut pipe) = pipe else {
+        return;
+    };
+    thread::spawn(move || {
+        let mut buf = [0u8; 8192];
+        loop {
+            match pipe.read(&mut buf) {
+                Ok(0) | Err(_) => return,
+                Ok(read) => {
+                    if tx.send((stream, buf[..read].to_vec())).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -250,16 +954,31 @@ mod tests {
     }
 
     fn insert_cmdset(store: &mut CmdSetStore, steps: Vec<NewCmdStep>) {
+        insert_cmdset_with_vars(store, None, steps);
+    }
+
+    fn insert_cmdset_with_vars(
+        store: &mut CmdSetStore,
+        vars: Option<serde_json::Value>,
+        steps: Vec<NewCmdStep>,
+    ) {
         store
             .insert(NewCmdSet {
                 cmdset_id: Some("c_test".to_string()),
                 name: "Test commands".to_string(),
-                vars: None,
+                vars,
                 steps,
             })
             .unwrap();
     }
 
+    #[cfg(unix)]
+    fn write_script(path: &Path, script: &str) {
+        use std::os::unix::fs::PermissionsExt;
+        fs::write(path, script).expect("write script");
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).expect("set executable");
+    }
+
     #[test]
     fn runs_steps_and_applies_parser() {
         let db_path = temp_db_path("cmdset-run");
@@ -284,6 +1003,11 @@ mod tests {
                 cmdset_id: "c_test",
                 ssh: &fake_ssh,
                 ssh_auth_args: &[],
+                scp: None,
+                ssh_password: None,
+                events: None,
+                force_guard: false,
+                env: &[],
             },
             |_| Ok(()),
         )
@@ -336,6 +1060,11 @@ mod tests {
                 cmdset_id: "c_test",
                 ssh: &fake_ssh,
                 ssh_auth_args: &[],
+                scp: None,
+                ssh_password: None,
+                events: None,
+                force_guard: false,
+                env: &[],
             },
             |_| Ok(()),
         )
@@ -381,6 +1110,11 @@ mod tests {
                 cmdset_id: "c_test",
                 ssh: &fake_ssh,
                 ssh_auth_args: &[],
+                scp: None,
+                ssh_password: None,
+                events: None,
+                force_guard: false,
+                env: &[],
             },
             |_| Ok(()),
         )
@@ -393,4 +1127,301 @@ mod tests {
         let _ = fs::remove_file(fake_ssh);
         cleanup();
     }
+
+    #[test]
+    fn publishes_lifecycle_events_in_order() {
+        let db_path = temp_db_path("cmdset-events");
+        let (profile_store, mut cmdset_store, cleanup) = stores(&db_path);
+        insert_profile(&profile_store);
+        insert_cmdset(
+            &mut cmdset_store,
+            vec![
+                NewCmdStep {
+                    cmd: "fail".to_string(),
+                    timeout_ms: Some(5_000),
+                    on_error: StepOnError::Continue,
+                    parser_spec: ParserSpec::Raw,
+                },
+                NewCmdStep {
+                    cmd: "ok-json".to_string(),
+                    timeout_ms: None,
+                    on_error: StepOnError::Stop,
+                    parser_spec: ParserSpec::Json,
+                },
+            ],
+        );
+        let fake_ssh = fake_ssh_path("events");
+        let bus = RunEventBus::new();
+        let events = bus.subscribe();
+
+        let result = run_cmdset_ssh(
+            &profile_store,
+            &cmdset_store,
+            CmdSetRunRequest {
+                profile_id: "p_test",
+                cmdset_id: "c_test",
+                ssh: &fake_ssh,
+                ssh_auth_args: &[],
+                scp: None,
+                ssh_password: None,
+                events: Some(&bus),
+                force_guard: false,
+                env: &[],
+            },
+            |_| Ok(()),
+        )
+        .unwrap();
+
+        let events = events.try_iter().collect::<Vec<_>>();
+        let names = events.iter().map(RunEvent::name).collect::<Vec<_>>();
+        assert_eq!(names.first(), Some(&"run_started"));
+        assert_eq!(names.last(), Some(&"run_finished"));
+        assert_eq!(
+            names
+                .iter()
+                .filter(|name| !matches!(**name, "output"))
+                .copied()
+                .collect::<Vec<_>>()[1..5],
+            [
+                "step_started",
+                "step_finished",
+                "step_parsed",
+                "step_started"
+            ]
+        );
+        let output = |wanted: OutputStream| {
+            events
+                .iter()
+                .filter_map(|event| match event {
+                    RunEvent::Output { stream, chunk, .. } if *stream == wanted => {
+                        Some(chunk.as_str())
+                    }
+                    _ => None,
+                })
+                .collect::<String>()
+        };
+        assert_eq!(output(OutputStream::Stdout), result.stdout);
+        assert_eq!(output(OutputStream::Stderr), "err\n");
+        assert!(events.contains(&RunEvent::StepParsed {
+            ord: 2,
+            parsed: serde_json::json!({ "ok": true }),
+        }));
+
+        let _ = fs::remove_file(fake_ssh);
+        cleanup();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exports_run_env_without_recording_values() {
+        let db_path = temp_db_path("cmdset-env");
+        let (profile_store, mut cmdset_store, cleanup) = stores(&db_path);
+        insert_profile(&profile_store);
+        let fake_ssh =
+            std::env::temp_dir().join(format!("teradock-env-ssh-{}", std::process::id()));
+        write_script(&fake_ssh, "#!/bin/sh\nexec sh -c \"$4\"\n");
+        insert_cmdset(
+            &mut cmdset_store,
+            vec![NewCmdStep {
+                cmd: "printf '%s' \"$API_TOKEN\"".to_string(),
+                timeout_ms: Some(5_000),
+                on_error: StepOnError::Stop,
+                parser_spec: ParserSpec::Raw,
+            }],
+        );
+        let env = [RunEnvVar::new("API_TOKEN", Zeroizing::new("it's-secret".into())).unwrap()];
+        assert!(RunEnvVar::new("1TOKEN", Zeroizing::new(String::new())).is_err());
+
+        let result = run_cmdset_ssh(
+            &profile_store,
+            &cmdset_store,
+            CmdSetRunRequest {
+                profile_id: "p_test",
+                cmdset_id: "c_test",
+                ssh: &fake_ssh,
+                ssh_auth_args: &[],
+                scp: None,
+                ssh_password: None,
+                events: None,
+                force_guard: false,
+                env: &env,
+            },
+            |_| Ok(()),
+        )
+        .unwrap();
+
+        assert_eq!(result.steps[0].stdout, "it's-secret");
+        assert_eq!(result.steps[0].cmd, "printf '%s' \"$API_TOKEN\"");
+        let _ = fs::remove_file(&fake_ssh);
+        cleanup();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cmdset_env_vars_need_their_secrets_and_can_use_send_env() {
+        let db_path = temp_db_path("cmdset-send-env");
+        let (profile_store, mut cmdset_store, cleanup) = stores(&db_path);
+        insert_profile(&profile_store);
+        let fake_ssh =
+            std::env::temp_dir().join(format!("teradock-send-env-ssh-{}", std::process::id()));
+        // Prints its options, then runs the remote command (the last argument) locally.
+        write_script(
+            &fake_ssh,
+            "#!/bin/sh
+for last; do :; done
+printf '%s ' \"$@\" >&2
+exec sh -c \"$last\"\n",
+        );
+        insert_cmdset_with_vars(
+            &mut cmdset_store,
+            Some(serde_json::json!({
+                "env_vars": {"APP_ENV": "prod", "API_TOKEN": {"secret": "s_api"}}
+            })),
+            vec![NewCmdStep {
+                cmd: "printf '%s %s' \"$APP_ENV\" \"$API_TOKEN\"".to_string(),
+                timeout_ms: Some(5_000),
+                on_error: StepOnError::Stop,
+                parser_spec: ParserSpec::Raw,
+            }],
+        );
+        let run = |env: &[RunEnvVar]| {
+            run_cmdset_ssh(
+                &profile_store,
+                &cmdset_store,
+                CmdSetRunRequest {
+                    profile_id: "p_test",
+                    cmdset_id: "c_test",
+                    ssh: &fake_ssh,
+                    ssh_auth_args: &[],
+                    scp: None,
+                    ssh_password: None,
+                    events: None,
+                    force_guard: false,
+                    env,
+                },
+                |_| Ok(()),
+            )
+        };
+
+        let err = run(&[]).unwrap_err().to_string();
+        assert!(err.contains("API_TOKEN needs secret s_api"), "{err}");
+
+        let env = [RunEnvVar::new("API_TOKEN", Zeroizing::new("t0k".into())).unwrap()];
+        let result = run(&env).unwrap();
+        assert_eq!(result.steps[0].stdout, "prod t0k");
+        assert!(!result.steps[0].stderr.contains("SendEnv"));
+        assert!(
+            !result.steps[0].stderr.contains("t0k") && !result.steps[0].stderr.contains("prod"),
+            "exported values stay off the ssh command line: {}",
+            result.steps[0].stderr
+        );
+
+        settings::set_setting(profile_store.conn(), settings::RUN_ENV_MODE_KEY, "send_env")
+            .unwrap();
+        let result = run(&env).unwrap();
+        assert_eq!(result.steps[0].stdout, "prod t0k");
+        assert!(result.steps[0]
+            .stderr
+            .contains("-o SendEnv=APP_ENV -o SendEnv=API_TOKEN"));
+        assert!(!result.steps[0].stderr.contains("t0k"));
+        let _ = fs::remove_file(&fake_ssh);
+        cleanup();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stages_files_and_cleans_up_staging_dir() {
+        let db_path = temp_db_path("cmdset-stage");
+        let (profile_store, mut cmdset_store, cleanup) = stores(&db_path);
+        insert_profile(&profile_store);
+        let dir = std::env::temp_dir().join(format!("teradock-stage-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("check.sh");
+        fs::write(&script, "echo hi\n").unwrap();
+        let log = dir.join("calls.log");
+        let fake_ssh = dir.join("ssh");
+        write_script(
+            &fake_ssh,
+            &format!(
+                "#!/bin/sh\nprintf 'ssh %s\\n' \"$4\" >> '{log}'\n\
+                 case \"$4\" in mktemp*) echo /tmp/teradock-stage.abc123 ;; *) printf '%s\\n' \"$4\" ;; esac\n",
+                log = log.display()
+            ),
+        );
+        let fake_scp = dir.join("scp");
+        write_script(
+            &fake_scp,
+            &format!(
+                "#!/bin/sh\nprintf 'scp %s %s\\n' \"$3\" \"$4\" >> '{}'\n",
+                log.display()
+            ),
+        );
+        insert_cmdset_with_vars(
+            &mut cmdset_store,
+            Some(serde_json::json!({
+                "stage": [{ "local": script.to_string_lossy(), "name": "run.sh" }]
+            })),
+            vec![NewCmdStep {
+                cmd: "sh {{staging_dir}}/run.sh".to_string(),
+                timeout_ms: Some(5_000),
+                on_error: StepOnError::Stop,
+                parser_spec: ParserSpec::Raw,
+            }],
+        );
+
+        let result = run_cmdset_ssh(
+            &profile_store,
+            &cmdset_store,
+            CmdSetRunRequest {
+                profile_id: "p_test",
+                cmdset_id: "c_test",
+                ssh: &fake_ssh,
+                ssh_auth_args: &[],
+                scp: Some(&fake_scp),
+                ssh_password: None,
+                events: None,
+                force_guard: false,
+                env: &[],
+            },
+            |_| Ok(()),
+        )
+        .unwrap();
+
+        assert!(result.ok);
+        assert_eq!(result.steps[0].cmd, "sh /tmp/teradock-stage.abc123/run.sh");
+        let calls = fs::read_to_string(&log).unwrap();
+        let calls = calls.lines().collect::<Vec<_>>();
+        assert_eq!(calls.len(), 4);
+        assert!(calls[0].starts_with("ssh mktemp -d"));
+        assert_eq!(
+            calls[1],
+            format!(
+                "scp {} alice@example.com:/tmp/teradock-stage.abc123/run.sh",
+                script.display()
+            )
+        );
+        assert_eq!(calls[2], "ssh sh /tmp/teradock-stage.abc123/run.sh");
+        assert_eq!(calls[3], "ssh rm -rf -- '/tmp/teradock-stage.abc123'");
+
+        let missing_scp = run_cmdset_ssh(
+            &profile_store,
+            &cmdset_store,
+            CmdSetRunRequest {
+                profile_id: "p_test",
+                cmdset_id: "c_test",
+                ssh: &fake_ssh,
+                ssh_auth_args: &[],
+                scp: None,
+                ssh_password: None,
+                events: None,
+                force_guard: false,
+                env: &[],
+            },
+            |_| Ok(()),
+        );
+        assert!(missing_scp.is_err());
+
+        let _ = fs::remove_dir_all(dir);
+        cleanup();
+    }
 }
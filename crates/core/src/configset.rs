@@ -1,7 +1,7 @@
 use rusqlite::{params, Connection, Row};
 use serde::{Deserialize, Serialize};
 
-use crate::error::{CoreError, Result};
+use crate::error::{CoreError, Result, RunError};
 use common::id::{generate_id, normalize_id, validate_id};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,9 +24,9 @@ impl ConfigFileWhen {
             "always" => Ok(Self::Always),
             "missing" => Ok(Self::Missing),
             "changed" => Ok(Self::Changed),
-            _ => Err(CoreError::InvalidCommandSpec(format!(
+            _ => Err(CoreError::Run(RunError::InvalidCommandSpec(format!(
                 "invalid config when: {value}"
-            ))),
+            )))),
         }
     }
 
@@ -111,7 +111,7 @@ impl ConfigSetStore {
         }
         tx.commit()?;
         self.get(&config_id)?
-            .ok_or_else(|| CoreError::NotFound(config_id))
+            .ok_or_else(|| CoreError::NotFound(format!("config set not found: {config_id}")))
     }
 
     pub fn list(&self) -> Result<Vec<ConfigSet>> {
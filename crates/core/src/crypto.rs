@@ -6,7 +6,7 @@ use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use zeroize::Zeroizing;
 
-use crate::error::{CoreError, Result};
+use crate::error::{CoreError, Result, SecretError};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KdfParams {
@@ -34,12 +34,12 @@ pub fn derive_key(password: &[u8], salt: &[u8], params: &KdfParams) -> Result<Ma
         params.parallelism,
         None,
     )
-    .map_err(|e| CoreError::Crypto(e.to_string()))?;
+    .map_err(|e| CoreError::Secret(SecretError::Crypto(e.to_string())))?;
     let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, argon_params);
     let mut key = Zeroizing::new([0u8; 32]);
     argon2
         .hash_password_into(password, salt, key.as_mut())
-        .map_err(|e| CoreError::Crypto(e.to_string()))?;
+        .map_err(|e| CoreError::Secret(SecretError::Crypto(e.to_string())))?;
     Ok(key)
 }
 
@@ -60,7 +60,7 @@ pub fn encrypt(key: &[u8], nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> Result
                 aad,
             },
         )
-        .map_err(|e| CoreError::Crypto(e.to_string()))
+        .map_err(|e| CoreError::Secret(SecretError::Crypto(e.to_string())))
 }
 
 pub fn decrypt(key: &[u8], nonce: &[u8], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
@@ -74,7 +74,7 @@ pub fn decrypt(key: &[u8], nonce: &[u8], aad: &[u8], ciphertext: &[u8]) -> Resul
                 aad,
             },
         )
-        .map_err(|_| CoreError::DecryptionFailed)
+        .map_err(|_| CoreError::Secret(SecretError::DecryptionFailed))
 }
 
 #[cfg(test)]
@@ -102,6 +102,9 @@ mod tests {
         let nonce = random_bytes::<24>();
         let ct = encrypt(key.as_ref(), &nonce, b"aad1", b"plain").unwrap();
         let err = decrypt(key.as_ref(), &nonce, b"aad2", &ct).unwrap_err();
-        assert!(matches!(err, CoreError::DecryptionFailed));
+        assert!(matches!(
+            err,
+            CoreError::Secret(SecretError::DecryptionFailed)
+        ));
     }
 }
@@ -0,0 +1,719 @@
+//! `td daemon`: one long-running process that keeps the database open, supervises tunnel
+//! sessions, and can hold the unlocked master key, so the CLI and TUI share that state
+//! instead of each probing tunnels and prompting for the master password on their own.
+//!
+//! Requests are JSON-RPC 2.0, one object per line. On Unix the daemon listens on
+//! `<config>/daemon.sock` (mode 0600). std has no named pipes, so on Windows it listens on
+//! a loopback TCP port instead. Either way it writes its endpoint and a random token to
+//! `<config>/daemon.json`, readable only by the current user, and every connection must
+//! start with an `auth` request carrying that token.
+//!
+//! The daemon also fires due `td schedule` entries, on a worker thread with its own
+//! database connections so a long CommandSet run does not hold up clients.
+
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use zeroize::Zeroizing;
+
+use crate::cmdset::CmdSetStore;
+use crate::crypto::{self, MasterKey};
+use crate::db;
+use crate::error::{CoreError, Result};
+use crate::paths;
+use crate::profile::ProfileStore;
+use crate::schedule;
+use crate::secret::SecretStore;
+use crate::settings;
+use crate::tunnel::{self, Session};
+use crate::tunnel_manager::TunnelManager;
+use crate::util::now_ms;
+
+#[cfg(not(unix))]
+use std::net::{TcpListener as Listener, TcpStream as Stream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener as Listener, UnixStream as Stream};
+
+const INFO_FILE: &str = "daemon.json";
+#[cfg(unix)]
+const SOCKET_FILE: &str = "daemon.sock";
+const ACCEPT_POLL: Duration = Duration::from_millis(100);
+/// How often the schedules table is checked for due runs.
+const SCHEDULE_POLL: Duration = Duration::from_secs(15);
+/// A client that stops talking is dropped so it cannot stall other clients.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const FAILED: i64 = -32000;
+const UNAUTHORIZED: i64 = -32001;
+
+/// Where a running daemon listens; written to `daemon.json` while it runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonInfo {
+    pub pid: u32,
+    pub endpoint: String,
+    pub token: String,
+    pub started_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    pub id: Value,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl RpcResponse {
+    fn ok(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".into(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn err(id: Value, code: i64, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: "2.0".into(),
+            id,
+            result: None,
+            error: Some(RpcError {
+                code,
+                message: message.into(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DaemonOptions {
+    /// How often tunnels are health-checked.
+    pub interval: Duration,
+    /// Restart dead tunnels within their `tunnel.reconnect` policy; off only reports them.
+    pub restart_tunnels: bool,
+    /// Fire due `td schedule` entries.
+    pub run_schedules: bool,
+}
+
+pub fn info_path() -> Result<PathBuf> {
+    Ok(paths::config_dir()?.join(INFO_FILE))
+}
+
+/// The running daemon's endpoint, if `daemon.json` exists.
+pub fn read_info() -> Result<Option<DaemonInfo>> {
+    match std::fs::read_to_string(info_path()?) {
+        Ok(raw) => Ok(Some(serde_json::from_str(&raw)?)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub struct Daemon {
+    store: ProfileStore,
+    secrets: SecretStore,
+    options: DaemonOptions,
+    token: String,
+    started_at: i64,
+    master: Option<MasterKey>,
+    /// When `master` is dropped again, per `vault.auto_lock_minutes` at unlock time.
+    master_expires_at: Option<Instant>,
+    sessions: Vec<Session>,
+    checked_at: Option<Instant>,
+    schedules_checked_at: Option<Instant>,
+    schedule_worker: Option<JoinHandle<()>>,
+    stopping: bool,
+}
+
+impl Daemon {
+    pub fn new(store: ProfileStore, secrets: SecretStore, options: DaemonOptions) -> Self {
+        Self {
+            store,
+            secrets,
+            options,
+            token: hex_token(),
+            started_at: now_ms(),
+            master: None,
+            master_expires_at: None,
+            sessions: Vec::new(),
+            checked_at: None,
+            schedules_checked_at: None,
+            schedule_worker: None,
+            stopping: false,
+        }
+    }
+
+    /// Binds the local endpoint and writes `daemon.json`. Fails when another daemon is
+    /// already answering; a stale socket or info file left by a crash is replaced.
+    pub fn bind(&self) -> Result<DaemonListener> {
+        if let Some(info) = read_info()? {
+            if DaemonClient::new(info.clone())
+                .call("ping", Value::Null)
+                .is_ok()
+            {
+                return Err(CoreError::Conflict(format!(
+                    "daemon already running (pid {}) at {}",
+                    info.pid, info.endpoint
+                )));
+            }
+        }
+        self.bind_at(&paths::config_dir()?)
+    }
+
+    fn bind_at(&self, dir: &Path) -> Result<DaemonListener> {
+        #[cfg(unix)]
+        let (listener, endpoint) = {
+            use std::os::unix::fs::PermissionsExt;
+            let path = dir.join(SOCKET_FILE);
+            let _ = std::fs::remove_file(&path);
+            let listener = Listener::bind(&path)?;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+            (listener, path.to_string_lossy().into_owned())
+        };
+        #[cfg(not(unix))]
+        let (listener, endpoint) = {
+            let listener = Listener::bind("127.0.0.1:0")?;
+            let endpoint = listener.local_addr()?.to_string();
+            (listener, endpoint)
+        };
+        listener.set_nonblocking(true)?;
+        let info = DaemonInfo {
+            pid: std::process::id(),
+            endpoint,
+            token: self.token.clone(),
+            started_at: self.started_at,
+        };
+        let info_path = dir.join(INFO_FILE);
+        write_private(&info_path, &serde_json::to_string_pretty(&info)?)?;
+        Ok(DaemonListener {
+            listener,
+            info,
+            info_path,
+        })
+    }
+
+    /// Serves clients one at a time and checks tunnels every `interval` until a
+    /// `shutdown` request arrives.
+    pub fn serve(&mut self, listener: &DaemonListener) -> Result<()> {
+        while !self.stopping {
+            self.tick();
+            match listener.listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(err) = self.handle_connection(stream) {
+                        tracing::debug!("daemon client dropped: {err}");
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        // Let a scheduled run finish and log its results before the process exits.
+        if let Some(worker) = self.schedule_worker.take() {
+            let _ = worker.join();
+        }
+        Ok(())
+    }
+
+    fn tick(&mut self) {
+        self.expire_master();
+        self.tick_schedules();
+        if self
+            .checked_at
+            .is_some_and(|at| at.elapsed() < self.options.interval)
+        {
+            return;
+        }
+        self.checked_at = Some(Instant::now());
+        if let Err(err) = self.refresh_sessions() {
+            tracing::warn!("daemon tunnel check failed: {err}");
+        }
+    }
+
+    fn expire_master(&mut self) {
+        if self
+            .master_expires_at
+            .is_some_and(|at| Instant::now() >= at)
+        {
+            self.master = None;
+            self.master_expires_at = None;
+            tracing::info!("daemon vault auto-locked");
+        }
+    }
+
+    fn lock_master(&mut self) -> bool {
+        self.master_expires_at = None;
+        self.master.take().is_some()
+    }
+
+    /// Starts a worker for due schedules unless one is still running.
+    fn tick_schedules(&mut self) {
+        if !self.options.run_schedules {
+            return;
+        }
+        if let Some(worker) = self.schedule_worker.take() {
+            if !worker.is_finished() {
+                self.schedule_worker = Some(worker);
+                return;
+            }
+            if worker.join().is_err() {
+                tracing::warn!("daemon schedule worker panicked");
+            }
+        }
+        if self
+            .schedules_checked_at
+            .is_some_and(|at| at.elapsed() < SCHEDULE_POLL)
+        {
+            return;
+        }
+        self.schedules_checked_at = Some(Instant::now());
+        match schedule::due(self.store.conn(), now_ms()) {
+            Ok(due) if due.is_empty() => return,
+            Ok(_) => {}
+            Err(err) => {
+                tracing::warn!("daemon schedule check failed: {err}");
+                return;
+            }
+        }
+        let Some(db_path) = self
+            .store
+            .conn()
+            .path()
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+        else {
+            tracing::warn!("daemon schedules need a database file");
+            return;
+        };
+        self.schedule_worker = Some(thread::spawn(move || {
+            let result = db::init_connection_at(&db_path).and_then(|profiles| {
+                let cmdsets = db::init_connection_at(&db_path)?;
+                schedule::run_due(
+                    &ProfileStore::new(profiles),
+                    &CmdSetStore::new(cmdsets),
+                    now_ms(),
+                )
+            });
+            match result {
+                Ok(runs) => {
+                    for run in runs {
+                        tracing::info!(
+                            "schedule {} ran {}: {} ok, {} failed, {} skipped",
+                            run.schedule_id,
+                            run.cmdset_id,
+                            run.ok.len(),
+                            run.failed.len(),
+                            run.skipped.len()
+                        );
+                    }
+                }
+                Err(err) => tracing::warn!("daemon schedule run failed: {err}"),
+            }
+        }));
+    }
+
+    fn refresh_sessions(&mut self) -> Result<()> {
+        self.sessions = if self.options.restart_tunnels {
+            TunnelManager::new(&self.store).supervise()?.sessions
+        } else {
+            tunnel::check_tunnel_health(self.store.conn())?
+        };
+        Ok(())
+    }
+
+    fn handle_connection(&mut self, stream: Stream) -> Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        let mut writer = stream.try_clone()?;
+        let mut authenticated = false;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<RpcRequest>(&line) {
+                Err(err) => RpcResponse::err(Value::Null, PARSE_ERROR, err.to_string()),
+                Ok(request) if request.method == "auth" => {
+                    authenticated = request.params["token"].as_str() == Some(self.token.as_str());
+                    if authenticated {
+                        RpcResponse::ok(request.id, Value::Bool(true))
+                    } else {
+                        RpcResponse::err(request.id, UNAUTHORIZED, "invalid daemon token")
+                    }
+                }
+                Ok(request) if !authenticated => {
+                    RpcResponse::err(request.id, UNAUTHORIZED, "send auth first")
+                }
+                Ok(request) => self.handle(request),
+            };
+            writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+            if self.stopping {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers one authenticated request.
+    pub fn handle(&mut self, request: RpcRequest) -> RpcResponse {
+        let id = request.id.clone();
+        match self.dispatch(&request.method, &request.params) {
+            Ok(Some(result)) => RpcResponse::ok(id, result),
+            Ok(None) => RpcResponse::err(
+                id,
+                METHOD_NOT_FOUND,
+                format!("unknown method: {}", request.method),
+            ),
+            Err(CoreError::InvalidSetting(message)) => {
+                RpcResponse::err(id, INVALID_PARAMS, message)
+            }
+            Err(err) => RpcResponse::err(id, FAILED, err.to_string()),
+        }
+    }
+
+    fn dispatch(&mut self, method: &str, params: &Value) -> Result<Option<Value>> {
+        // Between ticks a client could still catch a key that should be gone.
+        self.expire_master();
+        let result = match method {
+            "ping" => json!({
+                "pid": std::process::id(),
+                "version": env!("CARGO_PKG_VERSION"),
+                "started_at": self.started_at,
+                "unlocked": self.master.is_some(),
+                "restart_tunnels": self.options.restart_tunnels,
+                "run_schedules": self.options.run_schedules,
+                "schedule_running": self
+                    .schedule_worker
+                    .as_ref()
+                    .is_some_and(|worker| !worker.is_finished()),
+                "sessions": self.sessions.len(),
+            }),
+            "sessions.list" => {
+                if params["refresh"].as_bool() == Some(true) {
+                    self.checked_at = Some(Instant::now());
+                    self.refresh_sessions()?;
+                }
+                serde_json::to_value(&self.sessions)?
+            }
+            "vault.unlock" => {
+                let password = params["password"].as_str().ok_or_else(|| {
+                    CoreError::InvalidSetting("vault.unlock needs a password".into())
+                })?;
+                let auto_lock = match params["minutes"].as_u64() {
+                    Some(minutes) => {
+                        settings::parse_vault_auto_lock_minutes(&minutes.to_string())?;
+                        (minutes > 0).then(|| Duration::from_secs(minutes * 60))
+                    }
+                    None => settings::get_vault_auto_lock_after(self.store.conn())?,
+                };
+                self.master = Some(self.secrets.load_master(password)?);
+                self.master_expires_at = auto_lock.map(|after| Instant::now() + after);
+                Value::Bool(true)
+            }
+            "vault.lock" => Value::Bool(self.lock_master()),
+            "vault.status" => json!({
+                "unlocked": self.master.is_some(),
+                "locks_in_secs": self
+                    .master_expires_at
+                    .map(|at| at.saturating_duration_since(Instant::now()).as_secs()),
+            }),
+            "vault.key" => match &self.master {
+                Some(key) => Value::String(B64.encode(key.as_slice())),
+                None => Value::Null,
+            },
+            "shutdown" => {
+                self.stopping = true;
+                Value::Bool(true)
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(result))
+    }
+}
+
+/// The bound endpoint; removes the socket and `daemon.json` when dropped.
+pub struct DaemonListener {
+    listener: Listener,
+    info: DaemonInfo,
+    info_path: PathBuf,
+}
+
+impl DaemonListener {
+    pub fn info(&self) -> &DaemonInfo {
+        &self.info
+    }
+}
+
+impl Drop for DaemonListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.info_path);
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&self.info.endpoint);
+    }
+}
+
+pub struct DaemonClient {
+    info: DaemonInfo,
+}
+
+impl DaemonClient {
+    pub fn new(info: DaemonInfo) -> Self {
+        Self { info }
+    }
+
+    /// A client for the running daemon, or `None` when none answers.
+    pub fn connect() -> Result<Option<Self>> {
+        let Some(info) = read_info()? else {
+            return Ok(None);
+        };
+        let client = Self::new(info);
+        Ok(client.call("ping", Value::Null).is_ok().then_some(client))
+    }
+
+    pub fn info(&self) -> &DaemonInfo {
+        &self.info
+    }
+
+    pub fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let stream = Stream::connect(&self.info.endpoint)?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+        let auth = json!({"token": self.info.token});
+        for (id, method, params) in [(0, "auth", auth), (1, method, params)] {
+            let request = RpcRequest {
+                jsonrpc: "2.0".into(),
+                id: id.into(),
+                method: method.into(),
+                params,
+            };
+            writeln!(writer, "{}", serde_json::to_string(&request)?)?;
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            let response: RpcResponse = serde_json::from_str(&line)?;
+            if let Some(error) = response.error {
+                return Err(CoreError::Daemon(error.message));
+            }
+            if id == 1 {
+                return Ok(response.result.unwrap_or(Value::Null));
+            }
+        }
+        unreachable!("the loop returns on the second response")
+    }
+
+    /// Tunnel sessions with the daemon's latest health results.
+    pub fn sessions(&self, refresh: bool) -> Result<Vec<Session>> {
+        let value = self.call("sessions.list", json!({ "refresh": refresh }))?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// The master key, if the daemon has been unlocked.
+    pub fn master_key(&self) -> Result<Option<MasterKey>> {
+        let Some(encoded) = self
+            .call("vault.key", Value::Null)?
+            .as_str()
+            .map(str::to_owned)
+        else {
+            return Ok(None);
+        };
+        let bytes = Zeroizing::new(
+            B64.decode(encoded)
+                .map_err(|err| CoreError::Daemon(format!("bad master key: {err}")))?,
+        );
+        let key: [u8; 32] = bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_| CoreError::Daemon("bad master key length".into()))?;
+        Ok(Some(Zeroizing::new(key)))
+    }
+}
+
+fn hex_token() -> String {
+    crypto::random_bytes::<16>()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_in_memory;
+
+    fn daemon() -> Daemon {
+        let secrets = SecretStore::new(init_in_memory().unwrap());
+        secrets.set_master("correct horse").unwrap();
+        Daemon::new(
+            ProfileStore::new(init_in_memory().unwrap()),
+            secrets,
+            DaemonOptions {
+                interval: Duration::from_secs(60),
+                restart_tunnels: false,
+                run_schedules: false,
+            },
+        )
+    }
+
+    fn request(method: &str, params: Value) -> RpcRequest {
+        RpcRequest {
+            jsonrpc: "2.0".into(),
+            id: 7.into(),
+            method: method.into(),
+            params,
+        }
+    }
+
+    #[test]
+    fn unlock_shares_master_key_until_locked() {
+        let mut daemon = daemon();
+        let response = daemon.handle(request("vault.key", Value::Null));
+        assert_eq!(response.result, Some(Value::Null));
+
+        let response = daemon.handle(request("vault.unlock", json!({"password": "wrong"})));
+        assert_eq!(response.error.unwrap().code, FAILED);
+        let response = daemon.handle(request("vault.unlock", json!({})));
+        assert_eq!(response.error.unwrap().code, INVALID_PARAMS);
+
+        daemon.handle(request(
+            "vault.unlock",
+            json!({"password": "correct horse"}),
+        ));
+        let key = daemon
+            .handle(request("vault.key", Value::Null))
+            .result
+            .unwrap();
+        assert_eq!(B64.decode(key.as_str().unwrap()).unwrap().len(), 32);
+        assert_eq!(
+            daemon.handle(request("ping", Value::Null)).result.unwrap()["unlocked"],
+            true
+        );
+
+        daemon.handle(request("vault.lock", Value::Null));
+        assert_eq!(
+            daemon.handle(request("vault.key", Value::Null)).result,
+            Some(Value::Null)
+        );
+        let response = daemon.handle(request("profiles.drop", Value::Null));
+        assert_eq!(response.id, 7);
+        assert_eq!(response.error.unwrap().code, METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn unlocked_key_expires_after_the_auto_lock_timeout() {
+        let mut daemon = daemon();
+        daemon.handle(request(
+            "vault.unlock",
+            json!({"password": "correct horse"}),
+        ));
+        let status = daemon
+            .handle(request("vault.status", Value::Null))
+            .result
+            .unwrap();
+        let locks_in = status["locks_in_secs"].as_u64().unwrap();
+        assert!(
+            (14 * 60..=15 * 60).contains(&locks_in),
+            "15 minutes by default"
+        );
+
+        settings::set_setting(
+            daemon.store.conn(),
+            settings::VAULT_AUTO_LOCK_MINUTES_KEY,
+            "0",
+        )
+        .unwrap();
+        daemon.handle(request(
+            "vault.unlock",
+            json!({"password": "correct horse"}),
+        ));
+        let status = daemon.handle(request("vault.status", Value::Null)).result;
+        assert_eq!(
+            status.unwrap()["locks_in_secs"],
+            Value::Null,
+            "0 opts out of the timeout"
+        );
+        let response = daemon.handle(request(
+            "vault.unlock",
+            json!({"password": "correct horse", "minutes": 100_000}),
+        ));
+        assert_eq!(response.error.unwrap().code, INVALID_PARAMS);
+
+        daemon.master_expires_at = Some(Instant::now());
+        assert_eq!(
+            daemon.handle(request("vault.key", Value::Null)).result,
+            Some(Value::Null)
+        );
+        assert_eq!(
+            daemon
+                .handle(request("vault.status", Value::Null))
+                .result
+                .unwrap()["unlocked"],
+            false
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn serves_authenticated_clients_over_the_socket() {
+        let dir = std::env::temp_dir().join(format!("td-daemon-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut daemon = daemon();
+        let listener = daemon.bind_at(&dir).unwrap();
+        let info = listener.info().clone();
+        let server = thread::spawn(move || {
+            daemon.serve(&listener).unwrap();
+        });
+
+        let intruder = DaemonClient::new(DaemonInfo {
+            token: "guess".into(),
+            ..info.clone()
+        });
+        let err = intruder.call("ping", Value::Null).unwrap_err();
+        assert!(err.to_string().contains("invalid daemon token"));
+
+        let client = DaemonClient::new(info);
+        assert_eq!(client.call("ping", Value::Null).unwrap()["sessions"], 0);
+        assert!(client.sessions(true).unwrap().is_empty());
+        assert!(client.master_key().unwrap().is_none());
+        client.call("shutdown", Value::Null).unwrap();
+        server.join().unwrap();
+        assert!(!dir.join(INFO_FILE).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
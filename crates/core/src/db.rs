@@ -1,10 +1,48 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use rusqlite::{Connection, TransactionBehavior};
-use tracing::info;
+use rand::Rng;
+use rusqlite::{params, Connection, ErrorCode, OpenFlags, TransactionBehavior};
+use serde::Serialize;
+use tracing::{info, warn};
 
-use crate::error::Result;
+use crate::error::{CoreError, Result};
 use crate::paths::database_path;
+use crate::settings;
+use crate::util::now_ms;
+
+pub const BUSY_TIMEOUT_KEY: &str = "db.busy_timeout_ms";
+pub const DEFAULT_BUSY_TIMEOUT_MS: u64 = 5_000;
+/// Schema version written by the last migration in `apply_migrations`.
+pub const SCHEMA_VERSION: u32 = 23;
+const BUSY_RETRIES: u32 = 3;
+const BUSY_RETRY_BASE_MS: u64 = 100;
+/// A busy error returned faster than this skipped the busy handler (SQLite does that to
+/// avoid deadlocks), so `retry_busy` tries again; slower ones already waited it out.
+const BUSY_FAST_FAILURE: Duration = Duration::from_millis(50);
+const LOCK_PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DbStatus {
+    pub path: Option<PathBuf>,
+    pub journal_mode: String,
+    pub synchronous: String,
+    pub busy_timeout_ms: u64,
+    pub foreign_keys: bool,
+    pub user_version: u32,
+    pub page_size: u64,
+    pub page_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DbSnapshot {
+    pub version: u32,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    /// When the snapshot was written (file modification time, ms).
+    pub created_at: i64,
+}
 
 pub fn init_connection() -> Result<Connection> {
     let path = database_path()?;
@@ -14,24 +52,407 @@ pub fn init_connection() -> Result<Connection> {
 pub fn init_connection_at(path: &Path) -> Result<Connection> {
     let mut conn = Connection::open(path)?;
     configure_connection(&mut conn)?;
-    apply_migrations(&mut conn)?;
+    retry_busy(|| apply_migrations(&mut conn, Some(path)))?;
+    apply_configured_busy_timeout(&conn)?;
     Ok(conn)
 }
 
+/// Opens the database without migrating or writing, for list/show commands while another
+/// process holds the write lock. The schema must already be current.
+pub fn open_read_only() -> Result<Connection> {
+    let conn = open_read_only_with_timeout(Duration::from_millis(DEFAULT_BUSY_TIMEOUT_MS))?;
+    apply_configured_busy_timeout(&conn)?;
+    Ok(conn)
+}
+
+fn open_read_only_with_timeout(timeout: Duration) -> Result<Connection> {
+    let path = database_path()?;
+    let conn = Connection::open_with_flags(
+        &path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    conn.busy_timeout(timeout)?;
+    let version: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version != SCHEMA_VERSION {
+        return Err(CoreError::InvalidSetting(format!(
+            "read-only fallback needs schema v{SCHEMA_VERSION}, found v{version}"
+        )));
+    }
+    Ok(conn)
+}
+
+/// True for SQLite "database is locked" / "database table is locked" failures.
+pub fn is_busy(err: &CoreError) -> bool {
+    matches!(
+        err,
+        CoreError::Database(rusqlite::Error::SqliteFailure(failure, _))
+            if matches!(failure.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
+
+/// Runs `op` again (up to `BUSY_RETRIES` times, with jittered backoff) when it fails
+/// with a busy error the busy timeout did not wait for. Only use it for idempotent work
+/// such as a single statement or a transaction.
+pub fn retry_busy<T>(mut op: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut attempt = 0;
+    loop {
+        let started = Instant::now();
+        match op() {
+            Err(err)
+                if is_busy(&err)
+                    && attempt < BUSY_RETRIES
+                    && started.elapsed() < BUSY_FAST_FAILURE =>
+            {
+                let backoff = BUSY_RETRY_BASE_MS << attempt;
+                let jitter = rand::thread_rng().gen_range(0..=backoff);
+                warn!("database is locked; retrying in {}ms", backoff + jitter);
+                thread::sleep(Duration::from_millis(backoff + jitter));
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// A TeraDock process that registered itself in `lockinfo`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LockHolder {
+    pub pid: u32,
+    /// `td` and its subcommand words; never option values.
+    pub command: String,
+    pub started_at: i64,
+}
+
+/// Keeps this process listed in `lockinfo` until dropped.
+pub struct ProcessRegistration {
+    conn: Connection,
+}
+
+impl Drop for ProcessRegistration {
+    fn drop(&mut self) {
+        let _ = self.conn.execute(
+            "DELETE FROM lockinfo WHERE pid = ?1",
+            params![std::process::id()],
+        );
+    }
+}
+
+/// Records this process in `lockinfo` so a process that later finds the database locked
+/// can name it. Best effort: returns `None` when the row cannot be written.
+pub fn register_process(command: &str) -> Option<ProcessRegistration> {
+    let conn = init_connection().ok()?;
+    conn.busy_timeout(Duration::from_millis(250)).ok()?;
+    let pid = std::process::id();
+    let registered = conn.execute(
+        "INSERT OR REPLACE INTO lockinfo (pid, command, started_at) VALUES (?1, ?2, ?3)",
+        params![pid, command, now_ms()],
+    );
+    if let Err(err) = registered {
+        tracing::debug!("could not register in lockinfo: {err}");
+        return None;
+    }
+    for holder in lock_holders(&conn).unwrap_or_default() {
+        if process_alive(holder.pid) == Some(false) {
+            let _ = conn.execute("DELETE FROM lockinfo WHERE pid = ?1", params![holder.pid]);
+        }
+    }
+    Some(ProcessRegistration { conn })
+}
+
+/// Other registered processes, oldest first; rows of processes known to have exited
+/// are skipped.
+pub fn lock_holders(conn: &Connection) -> Result<Vec<LockHolder>> {
+    let mut stmt =
+        conn.prepare("SELECT pid, command, started_at FROM lockinfo ORDER BY started_at")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(LockHolder {
+            pid: row.get(0)?,
+            command: row.get(1)?,
+            started_at: row.get(2)?,
+        })
+    })?;
+    let own_pid = std::process::id();
+    let mut holders = Vec::new();
+    for holder in rows {
+        let holder = holder?;
+        if holder.pid != own_pid && process_alive(holder.pid) != Some(false) {
+            holders.push(holder);
+        }
+    }
+    Ok(holders)
+}
+
+/// Explains a busy error by naming the other TeraDock processes using the database.
+pub fn describe_lock() -> String {
+    let holders =
+        open_read_only_with_timeout(LOCK_PROBE_TIMEOUT).and_then(|conn| lock_holders(&conn));
+    match holders {
+        Ok(holders) if !holders.is_empty() => {
+            let names = holders
+                .iter()
+                .map(|holder| format!("pid {} ({})", holder.pid, holder.command))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                "The TeraDock database is locked by another process. Running TeraDock processes: {names}. \
+                 Wait for them to finish or raise {BUSY_TIMEOUT_KEY}."
+            )
+        }
+        Ok(_) => format!(
+            "The TeraDock database is locked by another process (no other td process is registered). \
+             Retry, or raise {BUSY_TIMEOUT_KEY}."
+        ),
+        Err(_) => format!(
+            "The TeraDock database is locked by another process, which also blocks reading the list of \
+             running td processes. Retry, or raise {BUSY_TIMEOUT_KEY}."
+        ),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn process_alive(pid: u32) -> Option<bool> {
+    Some(Path::new("/proc").join(pid.to_string()).exists())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_alive(_pid: u32) -> Option<bool> {
+    None
+}
+
 pub fn init_in_memory() -> Result<Connection> {
     let mut conn = Connection::open_in_memory()?;
     configure_connection(&mut conn)?;
-    apply_migrations(&mut conn)?;
+    apply_migrations(&mut conn, None)?;
+    apply_configured_busy_timeout(&conn)?;
+    Ok(conn)
+}
+
+/// Opens an in-memory database shared by every connection opened with the same `name`;
+/// it is discarded once the last of them is closed.
+pub fn init_shared_memory(name: &str) -> Result<Connection> {
+    let uri = format!("file:{name}?mode=memory&cache=shared");
+    let mut conn = Connection::open_with_flags(
+        uri,
+        OpenFlags::SQLITE_OPEN_READ_WRITE
+            | OpenFlags::SQLITE_OPEN_CREATE
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    configure_connection(&mut conn)?;
+    apply_migrations(&mut conn, None)?;
     Ok(conn)
 }
 
+/// Rejects any further writes through `conn` (`PRAGMA query_only`).
+pub fn set_query_only(conn: &Connection) -> Result<()> {
+    conn.pragma_update(None, "query_only", true)?;
+    Ok(())
+}
+
+/// Reports the pragma state of an open connection (see `td db status`).
+pub fn status(conn: &Connection) -> Result<DbStatus> {
+    let journal_mode: String = conn.pragma_query_value(None, "journal_mode", |row| row.get(0))?;
+    let synchronous: i64 = conn.pragma_query_value(None, "synchronous", |row| row.get(0))?;
+    let busy_timeout_ms: i64 = conn.pragma_query_value(None, "busy_timeout", |row| row.get(0))?;
+    let foreign_keys: bool = conn.pragma_query_value(None, "foreign_keys", |row| row.get(0))?;
+    let user_version: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let page_size: i64 = conn.pragma_query_value(None, "page_size", |row| row.get(0))?;
+    let page_count: i64 = conn.pragma_query_value(None, "page_count", |row| row.get(0))?;
+    let path = conn
+        .path()
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from);
+    Ok(DbStatus {
+        path,
+        journal_mode: journal_mode.to_lowercase(),
+        synchronous: synchronous_name(synchronous).to_string(),
+        busy_timeout_ms: busy_timeout_ms.max(0) as u64,
+        foreign_keys,
+        user_version,
+        page_size: page_size.max(0) as u64,
+        page_count: page_count.max(0) as u64,
+    })
+}
+
+/// Path of the snapshot taken before migrating an existing database up from schema
+/// `version`.
+pub fn snapshot_path(db_path: &Path, version: u32) -> PathBuf {
+    let mut raw = db_path.as_os_str().to_owned();
+    raw.push(format!(".v{version}.bak"));
+    PathBuf::from(raw)
+}
+
+/// Lists pre-migration snapshots for `db_path`, oldest schema version first.
+pub fn list_snapshots(db_path: &Path) -> Result<Vec<DbSnapshot>> {
+    let Some(dir) = db_path.parent() else {
+        return Ok(Vec::new());
+    };
+    let Some(file_name) = db_path.file_name().and_then(|name| name.to_str()) else {
+        return Ok(Vec::new());
+    };
+    let prefix = format!("{file_name}.v");
+    let mut snapshots = Vec::new();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        let Some(version) = name
+            .strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix(".bak"))
+            .and_then(|version| version.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let metadata = entry.metadata()?;
+        let created_at = metadata
+            .modified()
+            .ok()
+            .and_then(|at| at.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |age| age.as_millis() as i64);
+        snapshots.push(DbSnapshot {
+            version,
+            path: entry.path(),
+            size_bytes: metadata.len(),
+            created_at,
+        });
+    }
+    snapshots.sort_by_key(|snapshot| snapshot.version);
+    Ok(snapshots)
+}
+
+/// The snapshot taken before the most recent migration: the newest file, which after a
+/// rollback and re-migration need not be the highest version.
+pub fn latest_snapshot(db_path: &Path) -> Result<Option<DbSnapshot>> {
+    Ok(list_snapshots(db_path)?
+        .into_iter()
+        .max_by_key(|snapshot| (snapshot.created_at, snapshot.version)))
+}
+
+/// Replaces the database at `db_path` with `snapshot`.
+///
+/// Refused while another registered td process uses the database. The current database
+/// is first copied to `<db>.pre-rollback.bak` and its connection closed, then stale
+/// WAL/SHM files are removed so they are not replayed onto the restored file. The caller
+/// must hold no connection of its own. The next `init_connection` migrates the restored
+/// file again unless an older release opens it.
+pub fn restore_snapshot(db_path: &Path, snapshot: &DbSnapshot) -> Result<PathBuf> {
+    let backup = pre_rollback_path(db_path);
+    if db_path.exists() {
+        let conn = Connection::open(db_path)?;
+        conn.busy_timeout(Duration::from_millis(DEFAULT_BUSY_TIMEOUT_MS))?;
+        // A database too broken to list holders is exactly what gets rolled back.
+        let holders = lock_holders(&conn).unwrap_or_default();
+        if !holders.is_empty() {
+            let names = holders
+                .iter()
+                .map(|holder| format!("pid {} ({})", holder.pid, holder.command))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(CoreError::Conflict(format!(
+                "database is in use by {names}; close them before rolling back"
+            )));
+        }
+        vacuum_into(&conn, &backup)?;
+        conn.close().map_err(|(_, err)| err)?;
+    }
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar = db_path.as_os_str().to_owned();
+        sidecar.push(suffix);
+        match std::fs::remove_file(PathBuf::from(sidecar)) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    std::fs::copy(&snapshot.path, db_path)?;
+    Ok(backup)
+}
+
+pub fn pre_rollback_path(db_path: &Path) -> PathBuf {
+    let mut raw = db_path.as_os_str().to_owned();
+    raw.push(".pre-rollback.bak");
+    PathBuf::from(raw)
+}
+
+fn snapshot_before_migration(
+    conn: &Connection,
+    snapshot_base: Option<&Path>,
+    current: u32,
+) -> Result<()> {
+    let Some(db_path) = snapshot_base else {
+        return Ok(());
+    };
+    let target = snapshot_path(db_path, current);
+    info!("snapshotting schema v{current} to {}", target.display());
+    vacuum_into(conn, &target)
+}
+
+fn vacuum_into(conn: &Connection, target: &Path) -> Result<()> {
+    match std::fs::remove_file(target) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+    let target = target.to_str().ok_or_else(|| {
+        CoreError::InvalidSetting(format!("non UTF-8 snapshot path: {}", target.display()))
+    })?;
+    conn.execute("VACUUM INTO ?1", [target])?;
+    Ok(())
+}
+
 fn configure_connection(conn: &mut Connection) -> Result<()> {
     conn.pragma_update(None, "foreign_keys", true)?;
+    conn.busy_timeout(Duration::from_millis(DEFAULT_BUSY_TIMEOUT_MS))?;
+    // In-memory databases keep journal_mode=memory; only file databases switch to WAL.
+    let _mode: String =
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    Ok(())
+}
+
+fn apply_configured_busy_timeout(conn: &Connection) -> Result<()> {
+    let Some(raw) = settings::get_setting(conn, BUSY_TIMEOUT_KEY)? else {
+        return Ok(());
+    };
+    match parse_busy_timeout(&raw) {
+        Ok(timeout_ms) => conn.busy_timeout(Duration::from_millis(timeout_ms))?,
+        Err(err) => warn!("ignoring {BUSY_TIMEOUT_KEY}: {err}"),
+    }
     Ok(())
 }
 
-fn apply_migrations(conn: &mut Connection) -> Result<()> {
+pub fn parse_busy_timeout(raw: &str) -> Result<u64> {
+    raw.trim().parse::<u64>().map_err(|_| {
+        CoreError::InvalidSetting(format!(
+            "invalid busy timeout '{raw}' (expected milliseconds)"
+        ))
+    })
+}
+
+fn synchronous_name(value: i64) -> &'static str {
+    match value {
+        0 => "off",
+        1 => "normal",
+        2 => "full",
+        3 => "extra",
+        _ => "unknown",
+    }
+}
+
+fn apply_migrations(conn: &mut Connection, snapshot_base: Option<&Path>) -> Result<()> {
     let mut current: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    // One snapshot of the starting version covers every pending step; a fresh database
+    // has nothing worth restoring.
+    if current > 0 && current < SCHEMA_VERSION {
+        snapshot_before_migration(conn, snapshot_base, current)?;
+    }
     if current < 1 {
         info!("applying schema v1");
         let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
@@ -200,6 +621,595 @@ fn apply_migrations(conn: &mut Connection) -> Result<()> {
             "#,
         )?;
         tx.commit()?;
+        current = 4;
+    }
+    if current < 5 {
+        info!("applying schema v5");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS transfer_favorites (
+                id INTEGER PRIMARY KEY,
+                profile_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                direction TEXT NOT NULL,
+                local_path TEXT NOT NULL,
+                remote_path TEXT NOT NULL,
+                via TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                last_used_at INTEGER,
+                UNIQUE(profile_id, name),
+                FOREIGN KEY(profile_id) REFERENCES profiles(profile_id) ON DELETE CASCADE
+            );
+
+            PRAGMA user_version = 5;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 5;
+    }
+    if current < 6 {
+        info!("applying schema v6");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            ALTER TABLE profiles ADD COLUMN archived_at INTEGER;
+            PRAGMA user_version = 6;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 6;
+    }
+    if current < 7 {
+        info!("applying schema v7");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            CREATE VIRTUAL TABLE IF NOT EXISTS profiles_fts USING fts5(
+                profile_id UNINDEXED,
+                name,
+                host,
+                tags,
+                grp,
+                note
+            );
+
+            INSERT INTO profiles_fts (rowid, profile_id, name, host, tags, grp, note)
+            SELECT rowid, profile_id, name, host, tags_json, "group", note FROM profiles;
+
+            CREATE TRIGGER IF NOT EXISTS profiles_fts_insert AFTER INSERT ON profiles BEGIN
+                INSERT INTO profiles_fts (rowid, profile_id, name, host, tags, grp, note)
+                VALUES (new.rowid, new.profile_id, new.name, new.host, new.tags_json, new."group", new.note);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS profiles_fts_delete AFTER DELETE ON profiles BEGIN
+                DELETE FROM profiles_fts WHERE rowid = old.rowid;
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS profiles_fts_update AFTER UPDATE ON profiles BEGIN
+                DELETE FROM profiles_fts WHERE rowid = old.rowid;
+                INSERT INTO profiles_fts (rowid, profile_id, name, host, tags, grp, note)
+                VALUES (new.rowid, new.profile_id, new.name, new.host, new.tags_json, new."group", new.note);
+            END;
+
+            PRAGMA user_version = 7;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 7;
+    }
+    if current < 8 {
+        info!("applying schema v8");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS run_outputs (
+                id INTEGER PRIMARY KEY,
+                ts INTEGER NOT NULL,
+                profile_id TEXT,
+                source TEXT NOT NULL,
+                ord INTEGER,
+                cmd TEXT NOT NULL,
+                exit_code INTEGER,
+                stdout TEXT NOT NULL,
+                stderr TEXT NOT NULL,
+                FOREIGN KEY(profile_id) REFERENCES profiles(profile_id) ON DELETE SET NULL
+            );
+            CREATE INDEX IF NOT EXISTS run_outputs_ts ON run_outputs(ts);
+
+            CREATE VIRTUAL TABLE IF NOT EXISTS run_outputs_fts USING fts5(
+                stdout,
+                stderr,
+                content = 'run_outputs',
+                content_rowid = 'id'
+            );
+
+            CREATE TRIGGER IF NOT EXISTS run_outputs_fts_insert AFTER INSERT ON run_outputs BEGIN
+                INSERT INTO run_outputs_fts (rowid, stdout, stderr)
+                VALUES (new.id, new.stdout, new.stderr);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS run_outputs_fts_delete AFTER DELETE ON run_outputs BEGIN
+                INSERT INTO run_outputs_fts (run_outputs_fts, rowid, stdout, stderr)
+                VALUES ('delete', old.id, old.stdout, old.stderr);
+            END;
+
+            PRAGMA user_version = 8;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 8;
+    }
+    if current < 9 {
+        info!("applying schema v9");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            ALTER TABLE sessions ADD COLUMN health_json TEXT;
+            ALTER TABLE sessions ADD COLUMN restarts INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE sessions ADD COLUMN restarted_at INTEGER;
+            PRAGMA user_version = 9;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 9;
+    }
+    if current < 10 {
+        info!("applying schema v10");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            ALTER TABLE sessions ADD COLUMN retry_at INTEGER;
+            PRAGMA user_version = 10;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 10;
+    }
+    if current < 11 {
+        info!("applying schema v11");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS lockinfo (
+                pid INTEGER PRIMARY KEY,
+                command TEXT NOT NULL,
+                started_at INTEGER NOT NULL
+            );
+            PRAGMA user_version = 11;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 11;
+    }
+    if current < 12 {
+        info!("applying schema v12");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            ALTER TABLE op_logs ADD COLUMN note TEXT;
+            PRAGMA user_version = 12;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 12;
+    }
+    if current < 13 {
+        info!("applying schema v13");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS op_log_artifacts (
+                id INTEGER PRIMARY KEY,
+                op_log_id INTEGER NOT NULL,
+                ord INTEGER,
+                cmd TEXT NOT NULL,
+                exit_code INTEGER,
+                stdout TEXT NOT NULL,
+                stderr TEXT NOT NULL,
+                truncated_bytes INTEGER NOT NULL DEFAULT 0,
+                FOREIGN KEY(op_log_id) REFERENCES op_logs(id) ON DELETE CASCADE
+            );
+            CREATE INDEX IF NOT EXISTS op_log_artifacts_op_log ON op_log_artifacts(op_log_id);
+            PRAGMA user_version = 13;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 13;
+    }
+    if current < 14 {
+        info!("applying schema v14");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS schedules (
+                id INTEGER PRIMARY KEY,
+                cmdset_id TEXT NOT NULL,
+                target TEXT NOT NULL,
+                cron TEXT NOT NULL,
+                enabled INTEGER NOT NULL DEFAULT 1,
+                allow_critical INTEGER NOT NULL DEFAULT 0,
+                next_run_at INTEGER,
+                last_run_at INTEGER,
+                last_ok INTEGER,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY(cmdset_id) REFERENCES cmdsets(cmdset_id) ON DELETE CASCADE
+            );
+            CREATE INDEX IF NOT EXISTS schedules_next_run ON schedules(enabled, next_run_at);
+            PRAGMA user_version = 14;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 14;
+    }
+    if current < 15 {
+        info!("applying schema v15");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS maintenance_windows (
+                id INTEGER PRIMARY KEY,
+                target TEXT NOT NULL,
+                span TEXT NOT NULL,
+                mode TEXT NOT NULL,
+                note TEXT,
+                created_at INTEGER NOT NULL
+            );
+            PRAGMA user_version = 15;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 15;
+    }
+    if current < 16 {
+        info!("applying schema v16");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            ALTER TABLE op_logs ADD COLUMN os_user TEXT;
+            ALTER TABLE op_logs ADD COLUMN operator TEXT;
+            PRAGMA user_version = 16;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 16;
+    }
+    if current < 17 {
+        info!("applying schema v17");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS profile_secrets (
+                profile_id TEXT NOT NULL,
+                secret_id TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY(profile_id, secret_id),
+                FOREIGN KEY(profile_id) REFERENCES profiles(profile_id) ON DELETE CASCADE,
+                FOREIGN KEY(secret_id) REFERENCES secrets(secret_id) ON DELETE CASCADE
+            );
+            PRAGMA user_version = 17;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 17;
+    }
+    if current < 18 {
+        info!("applying schema v18");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS deleted_profiles (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                profile_id TEXT NOT NULL,
+                deleted_at INTEGER NOT NULL,
+                profile_json TEXT NOT NULL
+            );
+            PRAGMA user_version = 18;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 18;
+    }
+    if current < 19 {
+        info!("applying schema v19");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS active_sessions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                profile_id TEXT NOT NULL,
+                pid INTEGER NOT NULL,
+                started_at INTEGER NOT NULL
+            );
+            PRAGMA user_version = 19;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 19;
+    }
+    if current < 20 {
+        info!("applying schema v20");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS forward_presets (
+                preset TEXT NOT NULL,
+                name TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                listen TEXT NOT NULL,
+                dest TEXT NOT NULL,
+                PRIMARY KEY (preset, name)
+            );
+            PRAGMA user_version = 20;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 20;
+    }
+    if current < 21 {
+        info!("applying schema v21");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            ALTER TABLE deleted_profiles ADD COLUMN related_json TEXT;
+            PRAGMA user_version = 21;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 21;
+    }
+    if current < 22 {
+        info!("applying schema v22");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        // Step output kept for review lives in run_outputs, linked to its op log entry;
+        // the rows of the v13 op_log_artifacts table move there.
+        tx.execute_batch(
+            r#"
+            ALTER TABLE run_outputs
+                ADD COLUMN op_log_id INTEGER REFERENCES op_logs(id) ON DELETE SET NULL;
+            ALTER TABLE run_outputs ADD COLUMN truncated_bytes INTEGER NOT NULL DEFAULT 0;
+            CREATE INDEX IF NOT EXISTS run_outputs_op_log ON run_outputs(op_log_id);
+
+            INSERT INTO run_outputs
+                (ts, profile_id, source, ord, cmd, exit_code, stdout, stderr, op_log_id,
+                 truncated_bytes)
+            SELECT o.ts, o.profile_id,
+                   CASE WHEN o.op = 'run'
+                        THEN 'run:' || COALESCE(json_extract(o.meta_json, '$.cmdset_id'), '')
+                        ELSE o.op END,
+                   a.ord, a.cmd, a.exit_code, a.stdout, a.stderr, a.op_log_id, a.truncated_bytes
+            FROM op_log_artifacts a
+            JOIN op_logs o ON o.id = a.op_log_id
+            ORDER BY a.id;
+            DROP TABLE op_log_artifacts;
+
+            INSERT OR IGNORE INTO settings (scope, key, value)
+            SELECT scope, 'run.store_outputs', value FROM settings WHERE key = 'oplog.artifacts';
+            DELETE FROM settings WHERE key = 'oplog.artifacts';
+            UPDATE settings SET key = 'run.output_max_bytes' WHERE key = 'oplog.artifact_max_bytes';
+            UPDATE settings SET key = 'retention.output_days' WHERE key = 'retention.artifact_days';
+
+            PRAGMA user_version = 22;
+            "#,
+        )?;
+        tx.commit()?;
+        current = 22;
+    }
+    if current < 23 {
+        info!("applying schema v23");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+        tx.execute_batch(
+            r#"
+            ALTER TABLE active_sessions ADD COLUMN proc_start TEXT;
+            PRAGMA user_version = 23;
+            "#,
+        )?;
+        tx.commit()?;
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "teradock-db-{name}-{}-{}.db",
+            std::process::id(),
+            crate::util::now_ms()
+        ))
+    }
+
+    fn remove_db_files(path: &Path) {
+        let _ = std::fs::remove_file(path);
+        for suffix in ["-wal", "-shm"] {
+            let mut sidecar = path.as_os_str().to_owned();
+            sidecar.push(suffix);
+            let _ = std::fs::remove_file(PathBuf::from(sidecar));
+        }
+    }
+
+    #[test]
+    fn migrating_existing_database_snapshots_previous_version() {
+        let db_path = temp_db_path("snapshot");
+        {
+            let conn = init_connection_at(&db_path).unwrap();
+            conn.execute_batch(
+                "DROP TRIGGER profiles_fts_insert; DROP TRIGGER profiles_fts_delete; \
+                 DROP TRIGGER profiles_fts_update; DROP TABLE profiles_fts; \
+                 ALTER TABLE profiles DROP COLUMN archived_at; \
+                 ALTER TABLE sessions DROP COLUMN health_json; \
+                 ALTER TABLE sessions DROP COLUMN restarts; \
+                 ALTER TABLE sessions DROP COLUMN restarted_at; \
+                 ALTER TABLE sessions DROP COLUMN retry_at; \
+                 ALTER TABLE op_logs DROP COLUMN note; \
+                 ALTER TABLE op_logs DROP COLUMN os_user; \
+                 ALTER TABLE op_logs DROP COLUMN operator; \
+                 DROP TABLE transfer_favorites; DROP TABLE deleted_profiles; \
+                 DROP TABLE run_outputs_fts; DROP TABLE run_outputs; \
+                 DROP TABLE active_sessions; PRAGMA user_version = 4;",
+            )
+            .unwrap();
+        }
+
+        let conn = init_connection_at(&db_path).unwrap();
+        drop(conn);
+
+        let snapshots = list_snapshots(&db_path).unwrap();
+        let versions = snapshots.iter().map(|s| s.version).collect::<Vec<_>>();
+        assert_eq!(versions, vec![4], "one snapshot, of the starting version");
+        assert_eq!(snapshots[0].path, snapshot_path(&db_path, 4));
+
+        // A higher-versioned snapshot left by an earlier upgrade is not the default.
+        let earlier = snapshot_path(&db_path, 9);
+        std::fs::copy(&snapshots[0].path, &earlier).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&earlier)
+            .unwrap()
+            .set_modified(std::time::UNIX_EPOCH + Duration::from_secs(86_400))
+            .unwrap();
+        assert_eq!(latest_snapshot(&db_path).unwrap().unwrap().version, 4);
+        std::fs::remove_file(&earlier).unwrap();
+
+        let holder = Connection::open(&db_path).unwrap();
+        holder
+            .execute(
+                "INSERT INTO lockinfo (pid, command, started_at) VALUES (1, 'td ui', 1)",
+                [],
+            )
+            .unwrap();
+        drop(holder);
+        let err = restore_snapshot(&db_path, &snapshots[0]).unwrap_err();
+        assert!(err.to_string().contains("pid 1 (td ui)"), "{err}");
+        let holder = Connection::open(&db_path).unwrap();
+        holder.execute("DELETE FROM lockinfo", []).unwrap();
+        drop(holder);
+
+        let backup = restore_snapshot(&db_path, &snapshots[0]).unwrap();
+        assert_eq!(
+            std::fs::read(&db_path).unwrap(),
+            std::fs::read(&snapshots[0].path).unwrap(),
+            "restored file is byte-identical to the snapshot"
+        );
+        let restored = Connection::open(&db_path).unwrap();
+        let version: u32 = restored
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, 4);
+        drop(restored);
+        assert!(backup.exists());
+
+        for snapshot in &snapshots {
+            let _ = std::fs::remove_file(&snapshot.path);
+        }
+        let _ = std::fs::remove_file(backup);
+        remove_db_files(&db_path);
+    }
+
+    #[test]
+    fn retries_busy_writes_and_names_lock_holders() {
+        let db_path = temp_db_path("busy");
+        let holder = init_connection_at(&db_path).unwrap();
+        let writer = init_connection_at(&db_path).unwrap();
+        writer.busy_timeout(Duration::from_millis(1)).unwrap();
+        let insert = || -> Result<usize> {
+            Ok(writer.execute("INSERT INTO op_logs (ts, op, ok) VALUES (1, 'run', 1)", [])?)
+        };
+
+        holder.execute_batch("BEGIN IMMEDIATE").unwrap();
+        let err = retry_busy(insert).unwrap_err();
+        assert!(is_busy(&err), "{err}");
+        holder.execute_batch("COMMIT").unwrap();
+        assert_eq!(retry_busy(insert).unwrap(), 1);
+
+        holder
+            .execute(
+                "INSERT INTO lockinfo (pid, command, started_at) VALUES (1, 'td ui', 1), (?1, 'td list', 2)",
+                params![std::process::id()],
+            )
+            .unwrap();
+        let holders = lock_holders(&holder).unwrap();
+        assert_eq!(
+            holders,
+            vec![LockHolder {
+                pid: 1,
+                command: "td ui".to_string(),
+                started_at: 1,
+            }]
+        );
+
+        drop((holder, writer));
+        remove_db_files(&db_path);
+    }
+
+    #[test]
+    fn fresh_database_has_no_snapshots() {
+        let db_path = temp_db_path("fresh");
+        let conn = init_connection_at(&db_path).unwrap();
+        drop(conn);
+
+        assert!(list_snapshots(&db_path).unwrap().is_empty());
+        remove_db_files(&db_path);
+    }
+
+    #[test]
+    fn file_connections_use_wal_and_default_busy_timeout() {
+        let db_path = temp_db_path("wal");
+        let conn = init_connection_at(&db_path).unwrap();
+
+        let status = status(&conn).unwrap();
+
+        assert_eq!(status.journal_mode, "wal");
+        assert_eq!(status.synchronous, "normal");
+        assert_eq!(status.busy_timeout_ms, DEFAULT_BUSY_TIMEOUT_MS);
+        assert!(status.foreign_keys);
+        assert_eq!(status.path.as_deref(), Some(db_path.as_path()));
+        drop(conn);
+        remove_db_files(&db_path);
+    }
+
+    #[test]
+    fn busy_timeout_setting_applies_to_new_connections() {
+        let db_path = temp_db_path("busy");
+        let conn = init_connection_at(&db_path).unwrap();
+        settings::set_setting(&conn, BUSY_TIMEOUT_KEY, "1234").unwrap();
+
+        let reopened = init_connection_at(&db_path).unwrap();
+
+        assert_eq!(status(&reopened).unwrap().busy_timeout_ms, 1234);
+        drop(conn);
+        drop(reopened);
+        remove_db_files(&db_path);
+    }
+
+    #[test]
+    fn shared_memory_snapshot_is_visible_and_read_only() {
+        use crate::import_export::{self, ExportDocument};
+
+        let source = init_in_memory().unwrap();
+        source
+            .execute(
+                "INSERT INTO profiles (profile_id, name, type, host, port, user, danger_level, \
+                 tags_json, created_at, updated_at) \
+                 VALUES ('p_view', 'view', 'ssh', 'example.com', 22, 'ops', 'normal', '[]', 0, 0)",
+                [],
+            )
+            .unwrap();
+        let document: ExportDocument =
+            import_export::export_document(&source, false, None).unwrap();
+
+        let mut writer = init_shared_memory("td-view-test").unwrap();
+        let report = import_export::load_snapshot(&mut writer, document).unwrap();
+        assert_eq!(report.profiles, 1);
+
+        let reader = init_shared_memory("td-view-test").unwrap();
+        set_query_only(&reader).unwrap();
+        let count: i64 = reader
+            .query_row("SELECT COUNT(*) FROM profiles", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+        assert!(reader.execute("DELETE FROM profiles", []).is_err());
+    }
+}
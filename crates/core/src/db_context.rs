@@ -0,0 +1,124 @@
+//! Named database contexts: `contexts.json` in the config directory maps a name to a
+//! database file, so separate inventories (work, home) are picked with `td --context <name>`
+//! instead of typing `--db <path>` each time.
+//!
+//! ```json
+//! { "work": { "db": "~/work/teradock.db" }, "home": { "db": "home.db" } }
+//! ```
+//!
+//! A leading `~/` is the home directory and relative paths are taken from the config
+//! directory. The file is only read, never written by td.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use directories::BaseDirs;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CoreError, Result};
+use crate::paths;
+
+pub const CONTEXTS_FILE: &str = "contexts.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+struct ContextEntry {
+    db: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DbContext {
+    pub name: String,
+    /// The database file, with `~/` and relative paths resolved.
+    pub db: PathBuf,
+}
+
+pub fn contexts_path() -> Result<PathBuf> {
+    Ok(paths::config_dir()?.join(CONTEXTS_FILE))
+}
+
+/// Every context in `contexts.json`, by name; an absent file has none.
+pub fn list() -> Result<Vec<DbContext>> {
+    let path = contexts_path()?;
+    let base = paths::config_dir()?;
+    let home = BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+    load(&path, &base, home.as_deref())
+}
+
+/// The database file of context `name`.
+pub fn resolve(name: &str) -> Result<PathBuf> {
+    list()?
+        .into_iter()
+        .find(|context| context.name == name)
+        .map(|context| context.db)
+        .ok_or_else(|| {
+            CoreError::NotFound(format!(
+                "database context not found: {name} (see {CONTEXTS_FILE})"
+            ))
+        })
+}
+
+fn load(path: &Path, base: &Path, home: Option<&Path>) -> Result<Vec<DbContext>> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let entries: BTreeMap<String, ContextEntry> = serde_json::from_str(&text)
+        .map_err(|err| CoreError::InvalidSetting(format!("{}: {err}", path.display())))?;
+    entries
+        .into_iter()
+        .map(|(name, entry)| {
+            let db = entry.db.trim();
+            if db.is_empty() {
+                return Err(CoreError::InvalidSetting(format!(
+                    "{}: context {name} has an empty db path",
+                    path.display()
+                )));
+            }
+            let db = match (db.strip_prefix("~/"), home) {
+                (Some(rest), Some(home)) => home.join(rest),
+                _ => base.join(db),
+            };
+            Ok(DbContext { name, db })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_contexts_and_resolves_paths() {
+        let dir = std::env::temp_dir().join(format!("td-db-context-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CONTEXTS_FILE);
+        let base = Path::new("/cfg");
+        let home = Path::new("/home/ops");
+        assert!(load(&path, base, Some(home)).unwrap().is_empty());
+
+        std::fs::write(
+            &path,
+            r#"{ "work": { "db": "~/work/td.db" }, "home": { "db": "home.db" },
+                 "abs": { "db": "/srv/td.db" } }"#,
+        )
+        .unwrap();
+        let contexts = load(&path, base, Some(home)).unwrap();
+        let db = |name: &str| {
+            contexts
+                .iter()
+                .find(|context| context.name == name)
+                .map(|context| context.db.clone())
+                .unwrap()
+        };
+        assert_eq!(db("work"), home.join("work/td.db"));
+        assert_eq!(db("home"), base.join("home.db"));
+        assert_eq!(db("abs"), PathBuf::from("/srv/td.db"));
+
+        std::fs::write(&path, r#"{ "work": { "db": " " } }"#).unwrap();
+        assert!(load(&path, base, Some(home)).is_err());
+        std::fs::write(&path, "not json").unwrap();
+        assert!(load(&path, base, Some(home)).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
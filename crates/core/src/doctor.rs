@@ -2,9 +2,15 @@ use directories::BaseDirs;
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::ffi::OsStr;
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use rusqlite::Connection;
 
 use crate::agent::{self, AgentStatus};
+use crate::error::{CoreError, Result, RunError};
+use crate::settings;
 
 #[derive(Debug, Clone, Serialize, PartialEq, Eq)]
 pub enum ClientSource {
@@ -46,7 +52,7 @@ pub struct DoctorMessage {
     pub message: String,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ClientKind {
     Ssh,
     Scp,
@@ -56,6 +62,14 @@ pub enum ClientKind {
 }
 
 impl ClientKind {
+    pub const ALL: [ClientKind; 5] = [
+        ClientKind::Ssh,
+        ClientKind::Scp,
+        ClientKind::Sftp,
+        ClientKind::Ftp,
+        ClientKind::Telnet,
+    ];
+
     pub fn as_str(&self) -> &'static str {
         match self {
             ClientKind::Ssh => "ssh",
@@ -96,6 +110,15 @@ impl ClientOverrides {
             ClientKind::Telnet => self.telnet.as_deref(),
         }
     }
+
+    /// Overrides whose path is not a file; resolution skips them and falls back.
+    pub fn invalid_paths(&self) -> Vec<(ClientKind, &str)> {
+        ClientKind::ALL
+            .into_iter()
+            .filter_map(|kind| Some((kind, self.path_for(kind)?)))
+            .filter(|(_, path)| valid_override(path).is_none())
+            .collect()
+    }
 }
 
 /// Check for required external clients (ssh/scp/sftp/ftp/telnet) in PATH.
@@ -109,13 +132,7 @@ pub fn check_clients_with_overrides(
     global_overrides: Option<&ClientOverrides>,
 ) -> DoctorReport {
     let mut clients = Vec::new();
-    for kind in [
-        ClientKind::Ssh,
-        ClientKind::Scp,
-        ClientKind::Sftp,
-        ClientKind::Ftp,
-        ClientKind::Telnet,
-    ] {
+    for kind in ClientKind::ALL {
         let resolved = resolve_client_with_source(kind, profile_overrides, global_overrides);
         clients.push(ClientStatus {
             name: kind.as_str().to_string(),
@@ -153,6 +170,87 @@ pub fn check_clients_with_overrides(
     }
 }
 
+/// One difference between two doctor reports, for `td doctor --watch`. `before` is `None`
+/// for a new warning or error and `after` is `None` for one that went away.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DoctorChange {
+    pub item: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// What changed from `previous` to `current`: client resolution, the agent socket and key
+/// count, and warnings or errors that appeared or went away.
+pub fn changes(previous: &DoctorReport, current: &DoctorReport) -> Vec<DoctorChange> {
+    let mut changes = Vec::new();
+    let mut changed = |item: &str, before: String, after: String| {
+        if before != after {
+            changes.push(DoctorChange {
+                item: item.to_string(),
+                before: Some(before),
+                after: Some(after),
+            });
+        }
+    };
+    let client = |status: Option<&ClientStatus>| match status {
+        Some(ClientStatus {
+            path: Some(path),
+            source,
+            ..
+        }) => format!("{} ({source})", path.display()),
+        _ => "MISSING".to_string(),
+    };
+    for status in &current.clients {
+        let before = previous
+            .clients
+            .iter()
+            .find(|previous| previous.name == status.name);
+        changed(&status.name, client(before), client(Some(status)));
+    }
+    let sock = |agent: &AgentStatus| {
+        agent
+            .auth_sock
+            .clone()
+            .unwrap_or_else(|| "(not set)".to_string())
+    };
+    changed("SSH_AUTH_SOCK", sock(&previous.agent), sock(&current.agent));
+    let keys = |agent: &AgentStatus| {
+        agent
+            .key_count
+            .map_or_else(|| "-".to_string(), |count| count.to_string())
+    };
+    changed(
+        "ssh-agent keys",
+        keys(&previous.agent),
+        keys(&current.agent),
+    );
+    for (label, before, after) in [
+        ("warning", &previous.warnings, &current.warnings),
+        ("error", &previous.errors, &current.errors),
+    ] {
+        let has = |messages: &[DoctorMessage], message: &DoctorMessage| {
+            messages
+                .iter()
+                .any(|other| other.code == message.code && other.message == message.message)
+        };
+        for message in before.iter().filter(|message| !has(after, message)) {
+            changes.push(DoctorChange {
+                item: format!("{label} {}", message.code),
+                before: Some(message.message.clone()),
+                after: None,
+            });
+        }
+        for message in after.iter().filter(|message| !has(before, message)) {
+            changes.push(DoctorChange {
+                item: format!("{label} {}", message.code),
+                before: None,
+                after: Some(message.message.clone()),
+            });
+        }
+    }
+    changes
+}
+
 /// Resolve the first matching client executable from PATH using common extensions.
 pub fn resolve_client(candidates: &[&str]) -> Option<PathBuf> {
     let path_env = env::var_os("PATH")?;
@@ -174,7 +272,8 @@ pub struct ResolvedClient {
     pub source: ClientSource,
 }
 
-fn resolve_client_with_source(
+/// Like `resolve_client_with_overrides`, also saying which override or PATH won.
+pub fn resolve_client_with_source(
     kind: ClientKind,
     profile_overrides: Option<&ClientOverrides>,
     global_overrides: Option<&ClientOverrides>,
@@ -375,6 +474,230 @@ fn pathext() -> Vec<String> {
     }
 }
 
+/// A remediation `td doctor --fix` can apply. Creating keys and changing system
+/// services only happens after explicit confirmation (see [`DoctorFix::needs_confirmation`]).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "fix", rename_all = "snake_case")]
+pub enum DoctorFix {
+    /// Create `~/.ssh` with owner-only permissions.
+    CreateSshDir { path: PathBuf },
+    /// Restrict an existing `~/.ssh` to owner-only permissions (Unix).
+    RestrictSshDir { path: PathBuf },
+    /// Run `ssh-keygen -t ed25519`; ssh-keygen prompts for the passphrase itself.
+    GenerateKey { path: PathBuf, keygen: PathBuf },
+    /// Set the Windows OpenSSH agent service to start automatically and start it.
+    EnableAgentService,
+    /// Record a client found outside PATH as a global client override.
+    SetClientOverride { client: String, path: PathBuf },
+}
+
+impl DoctorFix {
+    pub fn description(&self) -> String {
+        match self {
+            DoctorFix::CreateSshDir { path } => {
+                format!("create {} (mode 700)", path.display())
+            }
+            DoctorFix::RestrictSshDir { path } => {
+                format!("restrict {} to mode 700", path.display())
+            }
+            DoctorFix::GenerateKey { path, .. } => {
+                format!("generate an ed25519 key at {}", path.display())
+            }
+            DoctorFix::EnableAgentService => {
+                "enable and start the Windows ssh-agent service (needs an elevated shell)"
+                    .to_string()
+            }
+            DoctorFix::SetClientOverride { client, path } => {
+                format!("set global {client} client override to {}", path.display())
+            }
+        }
+    }
+
+    pub fn needs_confirmation(&self) -> bool {
+        matches!(
+            self,
+            DoctorFix::GenerateKey { .. } | DoctorFix::EnableAgentService
+        )
+    }
+}
+
+const KEY_FILES: [&str; 4] = ["id_ed25519", "id_ecdsa", "id_rsa", "id_ed25519_sk"];
+
+/// Lists the safe remediations for the current environment, based on `report`.
+pub fn plan_fixes(report: &DoctorReport) -> Vec<DoctorFix> {
+    let base_dirs = BaseDirs::new();
+    let home = base_dirs.as_ref().map(|dirs| dirs.home_dir());
+    let keygen = resolve_client(&["ssh-keygen", "ssh-keygen.exe"]);
+    plan_fixes_in(report, home, keygen, &fallback_client_dirs())
+}
+
+fn plan_fixes_in(
+    report: &DoctorReport,
+    home: Option<&Path>,
+    keygen: Option<PathBuf>,
+    fallback_dirs: &[PathBuf],
+) -> Vec<DoctorFix> {
+    let mut fixes = Vec::new();
+    if let Some(home) = home {
+        let ssh_dir = home.join(".ssh");
+        if !ssh_dir.exists() {
+            fixes.push(DoctorFix::CreateSshDir {
+                path: ssh_dir.clone(),
+            });
+        } else if ssh_dir_too_open(&ssh_dir) {
+            fixes.push(DoctorFix::RestrictSshDir {
+                path: ssh_dir.clone(),
+            });
+        }
+        let has_key = KEY_FILES.iter().any(|name| ssh_dir.join(name).is_file());
+        let agent_has_keys = report.agent.key_count.is_some_and(|count| count > 0);
+        if let (false, false, Some(keygen)) = (has_key, agent_has_keys, keygen) {
+            fixes.push(DoctorFix::GenerateKey {
+                path: ssh_dir.join("id_ed25519"),
+                keygen,
+            });
+        }
+    }
+    if cfg!(windows) && report.agent.key_count.is_none() {
+        fixes.push(DoctorFix::EnableAgentService);
+    }
+    if let Ok(search) = env::join_paths(fallback_dirs) {
+        for kind in [
+            ClientKind::Ssh,
+            ClientKind::Scp,
+            ClientKind::Sftp,
+            ClientKind::Ftp,
+            ClientKind::Telnet,
+        ] {
+            let missing = report
+                .clients
+                .iter()
+                .any(|client| client.name == kind.as_str() && client.path.is_none());
+            if let Some(path) = missing
+                .then(|| find_in_path(&search, kind.candidates()))
+                .flatten()
+            {
+                fixes.push(DoctorFix::SetClientOverride {
+                    client: kind.as_str().to_string(),
+                    path,
+                });
+            }
+        }
+    }
+    fixes
+}
+
+/// Applies one fix and returns a short summary of what was done.
+pub fn apply_fix(conn: &Connection, fix: &DoctorFix) -> Result<String> {
+    match fix {
+        DoctorFix::CreateSshDir { path } => {
+            fs::create_dir_all(path)?;
+            restrict_to_owner(path)?;
+            Ok(format!("created {}", path.display()))
+        }
+        DoctorFix::RestrictSshDir { path } => {
+            restrict_to_owner(path)?;
+            Ok(format!("set {} to mode 700", path.display()))
+        }
+        DoctorFix::GenerateKey { path, keygen } => {
+            // stdio is inherited so ssh-keygen can ask for the passphrase.
+            let status = Command::new(keygen)
+                .args(["-t", "ed25519", "-f"])
+                .arg(path)
+                .status()?;
+            if !status.success() {
+                return Err(
+                    RunError::CommandExecution(format!("ssh-keygen exited with {status}")).into(),
+                );
+            }
+            Ok(format!("generated {}", path.display()))
+        }
+        DoctorFix::EnableAgentService => {
+            let status = Command::new("powershell")
+                .args([
+                    "-NoProfile",
+                    "-Command",
+                    "Set-Service -Name ssh-agent -StartupType Automatic; Start-Service ssh-agent",
+                ])
+                .status()?;
+            if !status.success() {
+                return Err(RunError::CommandExecution(format!(
+                    "enabling the ssh-agent service failed with {status}"
+                ))
+                .into());
+            }
+            Ok("ssh-agent service enabled and started".to_string())
+        }
+        DoctorFix::SetClientOverride { client, path } => {
+            let mut overrides = settings::get_client_overrides(conn)?.unwrap_or_default();
+            let value = Some(path.to_string_lossy().into_owned());
+            match client.as_str() {
+                "ssh" => overrides.ssh = value,
+                "scp" => overrides.scp = value,
+                "sftp" => overrides.sftp = value,
+                "ftp" => overrides.ftp = value,
+                "telnet" => overrides.telnet = value,
+                other => {
+                    return Err(CoreError::InvalidSetting(format!(
+                        "unknown client: {other}"
+                    )))
+                }
+            }
+            settings::set_client_overrides(conn, &overrides)?;
+            Ok(format!("{client} override set to {}", path.display()))
+        }
+    }
+}
+
+/// Install locations that are often missing from PATH.
+fn fallback_client_dirs() -> Vec<PathBuf> {
+    if cfg!(windows) {
+        let mut dirs = Vec::new();
+        if let Some(root) = env::var_os("SystemRoot") {
+            dirs.push(PathBuf::from(root).join("System32").join("OpenSSH"));
+        }
+        if let Some(program_files) = env::var_os("ProgramFiles") {
+            let program_files = PathBuf::from(program_files);
+            dirs.push(program_files.join("OpenSSH"));
+            dirs.push(program_files.join("Git").join("usr").join("bin"));
+        }
+        dirs
+    } else {
+        [
+            "/usr/local/bin",
+            "/opt/homebrew/bin",
+            "/opt/local/bin",
+            "/usr/bin",
+            "/bin",
+        ]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect()
+    }
+}
+
+#[cfg(unix)]
+fn ssh_dir_too_open(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|meta| meta.permissions().mode() & 0o077 != 0)
+}
+
+#[cfg(not(unix))]
+fn ssh_dir_too_open(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn restrict_to_owner(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o700))
+}
+
+#[cfg(not(unix))]
+fn restrict_to_owner(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -483,4 +806,108 @@ mod tests {
             env::remove_var("PATH");
         }
     }
+
+    fn empty_report(clients: Vec<ClientStatus>) -> DoctorReport {
+        DoctorReport {
+            clients,
+            agent: AgentStatus {
+                auth_sock: None,
+                key_count: None,
+                keys: Vec::new(),
+                error: None,
+            },
+            warnings: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reports_changes_between_checks() {
+        let mut previous = empty_report(vec![ClientStatus {
+            name: "ssh".to_string(),
+            path: None,
+            source: ClientSource::Missing,
+        }]);
+        previous.warnings.push(DoctorMessage {
+            code: "ssh_agent_missing".to_string(),
+            message: "SSH_AUTH_SOCK is not set".to_string(),
+        });
+        assert!(changes(&previous, &previous).is_empty());
+
+        let mut current = empty_report(vec![ClientStatus {
+            name: "ssh".to_string(),
+            path: Some(PathBuf::from("/usr/bin/ssh")),
+            source: ClientSource::Path,
+        }]);
+        current.agent.auth_sock = Some("/tmp/agent.sock".to_string());
+        current.agent.key_count = Some(1);
+        current.errors.push(DoctorMessage {
+            code: "ssh_config_identity_missing".to_string(),
+            message: "IdentityFile not found".to_string(),
+        });
+        let items = changes(&previous, &current);
+        let item = |name: &str| items.iter().find(|change| change.item == name).unwrap();
+        assert_eq!(items.len(), 5);
+        assert_eq!(item("ssh").before.as_deref(), Some("MISSING"));
+        assert_eq!(item("ssh").after.as_deref(), Some("/usr/bin/ssh (path)"));
+        assert_eq!(item("ssh-agent keys").after.as_deref(), Some("1"));
+        assert_eq!(item("warning ssh_agent_missing").after, None);
+        assert_eq!(item("error ssh_config_identity_missing").before, None);
+        assert_eq!(item("SSH_AUTH_SOCK").before.as_deref(), Some("(not set)"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn plans_ssh_dir_key_and_override_fixes() {
+        let temp = env::temp_dir().join(format!("teradock-doctor-fix-{}", std::process::id()));
+        let home = temp.join("home");
+        let tools = temp.join("tools");
+        let _ = fs::remove_dir_all(&temp);
+        fs::create_dir_all(&home).unwrap();
+        fs::create_dir_all(&tools).unwrap();
+        fs::write(tools.join("scp"), "").unwrap();
+        let report = empty_report(vec![ClientStatus {
+            name: "scp".into(),
+            path: None,
+            source: ClientSource::Missing,
+        }]);
+
+        let fixes = plan_fixes_in(
+            &report,
+            Some(&home),
+            Some(PathBuf::from("ssh-keygen")),
+            std::slice::from_ref(&tools),
+        );
+
+        assert_eq!(
+            fixes[0],
+            DoctorFix::CreateSshDir {
+                path: home.join(".ssh")
+            }
+        );
+        assert!(fixes[1].needs_confirmation());
+        assert!(fixes.contains(&DoctorFix::SetClientOverride {
+            client: "scp".into(),
+            path: tools.join("scp"),
+        }));
+
+        let conn = crate::db::init_in_memory().unwrap();
+        apply_fix(&conn, &fixes[0]).unwrap();
+        assert!(home.join(".ssh").is_dir());
+        fs::write(home.join(".ssh").join("id_ed25519"), "").unwrap();
+        let again = plan_fixes_in(&report, Some(&home), Some(PathBuf::from("ssh-keygen")), &[]);
+        assert!(again.is_empty(), "{again:?}");
+
+        let override_fix = DoctorFix::SetClientOverride {
+            client: "scp".into(),
+            path: tools.join("scp"),
+        };
+        apply_fix(&conn, &override_fix).unwrap();
+        let overrides = settings::get_client_overrides(&conn).unwrap().unwrap();
+        assert_eq!(
+            overrides.scp,
+            Some(tools.join("scp").to_string_lossy().into_owned())
+        );
+        let _ = fs::remove_dir_all(&temp);
+    }
 }
@@ -5,7 +5,11 @@ use thiserror::Error;
 
 pub type Result<T> = result::Result<T, CoreError>;
 
+/// Errors returned by every `tdcore` API. Storage and validation failures shared by all
+/// modules live here directly; failures specific to profiles, secrets, CommandSet runs,
+/// and transfers are grouped in their own enums so callers can match one domain at a time.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum CoreError {
     #[error("failed to resolve application directories")]
     DirectoryResolution,
@@ -15,19 +19,10 @@ pub enum CoreError {
     Database(#[from] rusqlite::Error),
     #[error("json error: {0}")]
     Json(#[from] serde_json::Error),
-    #[error("crypto error: {0}")]
-    Crypto(String),
     #[error("invalid id: {0:?}")]
     InvalidId(IdError),
-    #[error("invalid command spec: {0}")]
-    InvalidCommandSpec(String),
-    #[error("command execution error: {0}")]
-    CommandExecution(String),
-    #[error("parser not found: {0}")]
-    ParserNotFound(String),
-    #[error("regex error: {0}")]
-    Regex(String),
-    #[error("unknown profile: {0}")]
+    /// The message names what is missing, e.g. `forward not found: web`.
+    #[error("{0}")]
     NotFound(String),
     #[error("conflict: {0}")]
     Conflict(String),
@@ -35,6 +30,42 @@ pub enum CoreError {
     InvalidSetting(String),
     #[error("import error: {0}")]
     Import(String),
+    /// A `td daemon` request failed; the message is the daemon's error text.
+    #[error("daemon error: {0}")]
+    Daemon(String),
+    /// A `policy.danger` rule forbids the action; the message names the rule.
+    #[error("policy denied: {0}")]
+    PolicyDenied(String),
+    #[error(transparent)]
+    Profile(#[from] ProfileError),
+    #[error(transparent)]
+    Secret(#[from] SecretError),
+    #[error(transparent)]
+    Run(#[from] RunError),
+    #[error(transparent)]
+    Transfer(#[from] TransferError),
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ProfileError {
+    #[error("unknown profile: {0}")]
+    NotFound(String),
+    #[error("conflict: {0}")]
+    Conflict(String),
+    #[error("unknown profile type: {0}")]
+    InvalidType(String),
+    #[error("unknown danger level: {0}")]
+    InvalidDangerLevel(String),
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum SecretError {
+    #[error("unknown secret: {0}")]
+    NotFound(String),
+    #[error("crypto error: {0}")]
+    Crypto(String),
     #[error("master password not set")]
     MasterNotSet,
     #[error("master password already set")]
@@ -43,4 +74,38 @@ pub enum CoreError {
     MasterVerificationFailed,
     #[error("decryption failed")]
     DecryptionFailed,
+    #[error("secret {secret_id} has kind {found}, expected {expected}")]
+    WrongKind {
+        secret_id: String,
+        expected: String,
+        found: String,
+    },
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RunError {
+    #[error("unknown command set: {0}")]
+    CmdSetNotFound(String),
+    #[error("invalid command spec: {0}")]
+    InvalidCommandSpec(String),
+    #[error("command execution error: {0}")]
+    CommandExecution(String),
+    #[error("parser not found: {0}")]
+    ParserNotFound(String),
+    #[error("regex error: {0}")]
+    Regex(String),
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum TransferError {
+    #[error("invalid transfer direction: {0}")]
+    InvalidDirection(String),
+    #[error("invalid transfer client: {0}")]
+    InvalidClient(String),
+    #[error("favorite not found: {0}")]
+    FavoriteNotFound(String),
+    #[error("favorite name already exists: {0}")]
+    FavoriteConflict(String),
 }
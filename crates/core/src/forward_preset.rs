@@ -0,0 +1,293 @@
+//! Forwarding presets: a named set of forwards (kind, listen, destination) saved from one
+//! profile and added to others in one step, so a group of hosts shares the same tunnels.
+//!
+//! A preset is a copy; editing the source profile's forwards later does not change it.
+//! A profile "matches" a preset when it has every forward of the preset, compared by kind,
+//! listen address, and destination (names may differ).
+
+use std::collections::BTreeMap;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::error::{CoreError, Result};
+use crate::tunnel::{self, Forward, ForwardKind};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PresetForward {
+    pub name: String,
+    pub kind: ForwardKind,
+    pub listen: String,
+    pub dest: Option<String>,
+}
+
+impl PresetForward {
+    fn same_rule(&self, forward: &Forward) -> bool {
+        self.kind == forward.kind && self.listen == forward.listen && self.dest == forward.dest
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ForwardPreset {
+    pub name: String,
+    pub forwards: Vec<PresetForward>,
+}
+
+impl ForwardPreset {
+    pub fn matches(&self, forwards: &[Forward]) -> bool {
+        self.forwards
+            .iter()
+            .all(|rule| forwards.iter().any(|forward| rule.same_rule(forward)))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ApplyReport {
+    /// Forwards added across all profiles.
+    pub added: usize,
+    /// Forwards a profile already had, left as they are.
+    pub unchanged: usize,
+}
+
+/// Saves `profile_id`'s forwards as preset `name`.
+pub fn save_from_profile(conn: &Connection, name: &str, profile_id: &str) -> Result<ForwardPreset> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CoreError::InvalidSetting("preset name is required".into()));
+    }
+    if get(conn, name)?.is_some() {
+        return Err(CoreError::Conflict(format!(
+            "forwarding preset already exists: {name}"
+        )));
+    }
+    let forwards = profile_forwards(conn, profile_id)?;
+    if forwards.is_empty() {
+        return Err(CoreError::InvalidSetting(format!(
+            "profile {profile_id} has no forwards to save"
+        )));
+    }
+    let tx = conn.unchecked_transaction()?;
+    for forward in &forwards {
+        tx.execute(
+            "INSERT INTO forward_presets (preset, name, kind, listen, dest) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                name,
+                forward.name,
+                forward.kind.to_string(),
+                forward.listen,
+                forward.dest.clone().unwrap_or_default()
+            ],
+        )?;
+    }
+    tx.commit()?;
+    get(conn, name)?
+        .ok_or_else(|| CoreError::NotFound(format!("forwarding preset not found: {name}")))
+}
+
+pub fn list(conn: &Connection) -> Result<Vec<ForwardPreset>> {
+    let mut stmt = conn.prepare(
+        "SELECT preset, name, kind, listen, dest FROM forward_presets ORDER BY preset, name",
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut presets: BTreeMap<String, Vec<PresetForward>> = BTreeMap::new();
+    while let Some(row) = rows.next()? {
+        let dest: String = row.get(4)?;
+        presets.entry(row.get(0)?).or_default().push(PresetForward {
+            name: row.get(1)?,
+            kind: ForwardKind::from_str(&row.get::<_, String>(2)?)?,
+            listen: row.get(3)?,
+            dest: (!dest.trim().is_empty()).then_some(dest),
+        });
+    }
+    Ok(presets
+        .into_iter()
+        .map(|(name, forwards)| ForwardPreset { name, forwards })
+        .collect())
+}
+
+pub fn get(conn: &Connection, name: &str) -> Result<Option<ForwardPreset>> {
+    Ok(list(conn)?.into_iter().find(|preset| preset.name == name))
+}
+
+pub fn remove(conn: &Connection, name: &str) -> Result<()> {
+    if conn.execute(
+        "DELETE FROM forward_presets WHERE preset = ?1",
+        params![name],
+    )? == 0
+    {
+        return Err(CoreError::NotFound(format!(
+            "forwarding preset not found: {name}"
+        )));
+    }
+    Ok(())
+}
+
+/// Adds the preset's forwards to every profile in one transaction. Forwards a profile
+/// already has are left alone; a forward name already used for a different rule stops
+/// the whole apply and nothing is written.
+pub fn apply(conn: &Connection, name: &str, profile_ids: &[String]) -> Result<ApplyReport> {
+    let preset = get(conn, name)?
+        .ok_or_else(|| CoreError::NotFound(format!("forwarding preset not found: {name}")))?;
+    let tx = conn.unchecked_transaction()?;
+    let mut report = ApplyReport::default();
+    for profile_id in profile_ids {
+        let exists = tx
+            .query_row(
+                "SELECT 1 FROM profiles WHERE profile_id = ?1",
+                params![profile_id],
+                |_| Ok(()),
+            )
+            .optional()?;
+        if exists.is_none() {
+            return Err(CoreError::NotFound(format!(
+                "profile not found: {profile_id}"
+            )));
+        }
+        let existing = profile_forwards(&tx, profile_id)?;
+        for rule in &preset.forwards {
+            if existing.iter().any(|forward| rule.same_rule(forward)) {
+                report.unchanged += 1;
+                continue;
+            }
+            if existing.iter().any(|forward| forward.name == rule.name) {
+                return Err(CoreError::Conflict(format!(
+                    "profile {profile_id} already has a different forward named {}",
+                    rule.name
+                )));
+            }
+            tx.execute(
+                "INSERT INTO ssh_forwards (profile_id, name, kind, listen, dest) \
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    profile_id,
+                    rule.name,
+                    rule.kind.to_string(),
+                    rule.listen,
+                    rule.dest.clone().unwrap_or_default()
+                ],
+            )?;
+            report.added += 1;
+        }
+    }
+    tx.commit()?;
+    Ok(report)
+}
+
+/// Names of the presets each profile matches, for list indicators. Profiles without a
+/// match are left out.
+pub fn matches_by_profile(conn: &Connection) -> Result<BTreeMap<String, Vec<String>>> {
+    let presets = list(conn)?;
+    let mut matches = BTreeMap::new();
+    if presets.is_empty() {
+        return Ok(matches);
+    }
+    let mut stmt = conn.prepare(
+        "SELECT id, profile_id, name, kind, listen, dest FROM ssh_forwards ORDER BY profile_id",
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut by_profile: BTreeMap<String, Vec<Forward>> = BTreeMap::new();
+    while let Some(row) = rows.next()? {
+        let forward = tunnel::deserialize_forward(row)?;
+        by_profile
+            .entry(forward.profile_id.clone())
+            .or_default()
+            .push(forward);
+    }
+    for (profile_id, forwards) in by_profile {
+        let names: Vec<String> = presets
+            .iter()
+            .filter(|preset| preset.matches(&forwards))
+            .map(|preset| preset.name.clone())
+            .collect();
+        if !names.is_empty() {
+            matches.insert(profile_id, names);
+        }
+    }
+    Ok(matches)
+}
+
+fn profile_forwards(conn: &Connection, profile_id: &str) -> Result<Vec<Forward>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, profile_id, name, kind, listen, dest FROM ssh_forwards \
+         WHERE profile_id = ?1 ORDER BY name",
+    )?;
+    let mut rows = stmt.query(params![profile_id])?;
+    let mut forwards = Vec::new();
+    while let Some(row) = rows.next()? {
+        forwards.push(tunnel::deserialize_forward(row)?);
+    }
+    Ok(forwards)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_in_memory;
+    use crate::profile::{DangerLevel, NewProfile, ProfileStore, ProfileType};
+
+    #[test]
+    fn presets_apply_to_many_profiles_and_show_matches() {
+        let store = ProfileStore::new(init_in_memory().unwrap());
+        for profile_id in ["p_dev1", "p_dev2", "p_dev3"] {
+            store
+                .insert(NewProfile {
+                    profile_id: Some(profile_id.to_string()),
+                    name: profile_id.to_string(),
+                    profile_type: ProfileType::Ssh,
+                    host: format!("{profile_id}.example.com"),
+                    port: 22,
+                    user: "ops".to_string(),
+                    danger_level: DangerLevel::Normal,
+                    group: None,
+                    tags: Vec::new(),
+                    note: None,
+                    initial_send: None,
+                    client_overrides: None,
+                })
+                .unwrap();
+        }
+        let conn = store.conn();
+        conn.execute(
+            "INSERT INTO ssh_forwards (profile_id, name, kind, listen, dest) \
+             VALUES ('p_dev1', 'db', 'local', '127.0.0.1:5432', 'db.internal:5432'), \
+                    ('p_dev1', 'socks', 'dynamic', '127.0.0.1:1080', ''), \
+                    ('p_dev3', 'db', 'local', '127.0.0.1:6543', 'other:5432')",
+            [],
+        )
+        .unwrap();
+
+        let preset = save_from_profile(conn, "dev-tunnels", "p_dev1").unwrap();
+        assert_eq!(preset.forwards.len(), 2);
+        assert!(save_from_profile(conn, "dev-tunnels", "p_dev1").is_err());
+        assert!(save_from_profile(conn, "empty", "p_dev2").is_err());
+
+        let report = apply(
+            conn,
+            "dev-tunnels",
+            &["p_dev1".to_string(), "p_dev2".to_string()],
+        )
+        .unwrap();
+        assert_eq!(
+            report,
+            ApplyReport {
+                added: 2,
+                unchanged: 2
+            }
+        );
+        assert!(
+            apply(conn, "dev-tunnels", &["p_dev3".to_string()]).is_err(),
+            "name clash with a different rule"
+        );
+        assert_eq!(profile_forwards(conn, "p_dev3").unwrap().len(), 1);
+
+        let matches = matches_by_profile(conn).unwrap();
+        assert_eq!(matches["p_dev1"], ["dev-tunnels"]);
+        assert_eq!(matches["p_dev2"], ["dev-tunnels"]);
+        assert!(!matches.contains_key("p_dev3"));
+
+        remove(conn, "dev-tunnels").unwrap();
+        assert!(list(conn).unwrap().is_empty());
+        assert!(remove(conn, "dev-tunnels").is_err());
+    }
+}
@@ -0,0 +1,228 @@
+//! The `guard.commands` setting: regex allow and deny patterns every CommandSet step is
+//! checked against before a run starts, so a denied step aborts the run before anything
+//! executes.
+//!
+//! ```json
+//! { "deny": ["rm\\s+-rf\\s+/", "\\bshutdown\\b"], "allow": ["^(systemctl status|df|uptime)"] }
+//! ```
+//!
+//! A deny match always wins; with `allow` set, a step must also match one allow pattern.
+//! Steps are checked as stored, before `{{staging_dir}}` is filled in. `td run --force`
+//! overrides the guard after an elevated confirmation and logs a `guard_override` entry.
+
+use regex::Regex;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::cmdset::CmdStep;
+use crate::error::{CoreError, Result};
+use crate::oplog::{self, OpLogEntry};
+use crate::settings::{self, SettingScope};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GuardRules {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+}
+
+impl GuardRules {
+    pub fn parse(raw: &str) -> Result<Self> {
+        let rules: Self = serde_json::from_str(raw)
+            .map_err(|err| CoreError::InvalidSetting(format!("invalid command guard: {err}")))?;
+        rules.compile()?;
+        Ok(rules)
+    }
+
+    pub fn compile(&self) -> Result<CommandGuard> {
+        Ok(CommandGuard {
+            allow: compile_all(&self.allow)?,
+            deny: compile_all(&self.deny)?,
+        })
+    }
+}
+
+fn compile_all(patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern).map_err(|err| {
+                CoreError::InvalidSetting(format!("invalid guard pattern '{pattern}': {err}"))
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandGuard {
+    allow: Vec<Regex>,
+    deny: Vec<Regex>,
+}
+
+/// A step the guard rejects, with the rule it broke.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GuardViolation {
+    pub ord: i64,
+    pub cmd: String,
+    pub rule: String,
+}
+
+impl std::fmt::Display for GuardViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "step {} `{}` {}", self.ord, self.cmd, self.rule)
+    }
+}
+
+impl CommandGuard {
+    /// The guard configured for the profile, or `None` when `guard.commands` is unset.
+    pub fn load(conn: &Connection, profile_id: &str) -> Result<Option<Self>> {
+        let scope = SettingScope::Profile(profile_id.to_string());
+        settings::get_setting_resolved(conn, &scope, settings::GUARD_COMMANDS_KEY)?
+            .map(|raw| GuardRules::parse(&raw)?.compile())
+            .transpose()
+    }
+
+    /// Why `cmd` may not run, if it may not.
+    pub fn check(&self, cmd: &str) -> Option<String> {
+        if let Some(pattern) = self.deny.iter().find(|pattern| pattern.is_match(cmd)) {
+            return Some(format!("matches deny pattern {}", pattern.as_str()));
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|pattern| pattern.is_match(cmd)) {
+            return Some("matches no allow pattern".to_string());
+        }
+        None
+    }
+
+    pub fn violations(&self, steps: &[CmdStep]) -> Vec<GuardViolation> {
+        steps
+            .iter()
+            .filter_map(|step| {
+                self.check(&step.cmd).map(|rule| GuardViolation {
+                    ord: step.ord,
+                    cmd: step.cmd.clone(),
+                    rule,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The steps of a run the profile's guard rejects; empty when no guard is set.
+pub fn violations(
+    conn: &Connection,
+    profile_id: &str,
+    steps: &[CmdStep],
+) -> Result<Vec<GuardViolation>> {
+    Ok(CommandGuard::load(conn, profile_id)?
+        .map(|guard| guard.violations(steps))
+        .unwrap_or_default())
+}
+
+/// The error a run stops with when `violations` is not empty.
+pub fn denied(violations: &[GuardViolation]) -> CoreError {
+    let list = violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ");
+    CoreError::PolicyDenied(format!(
+        "command guard blocked {list} (td run --force overrides it)"
+    ))
+}
+
+/// Logs a `--force` past the guard. Only step numbers and rules are kept, not commands.
+pub fn record_override(
+    conn: &Connection,
+    profile_id: &str,
+    cmdset_id: &str,
+    violations: &[GuardViolation],
+) -> Result<i64> {
+    oplog::log_operation(
+        conn,
+        OpLogEntry {
+            op: "guard_override".to_string(),
+            profile_id: Some(profile_id.to_string()),
+            client_used: None,
+            ok: true,
+            exit_code: None,
+            duration_ms: None,
+            meta_json: Some(serde_json::json!({
+                "cmdset_id": cmdset_id,
+                "steps": violations
+                    .iter()
+                    .map(|violation| serde_json::json!({
+                        "ord": violation.ord,
+                        "rule": violation.rule,
+                    }))
+                    .collect::<Vec<_>>(),
+                "user": crate::util::os_user(),
+            })),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmdset::StepOnError;
+    use crate::db::init_in_memory;
+    use crate::parser::ParserSpec;
+
+    fn step(ord: i64, cmd: &str) -> CmdStep {
+        CmdStep {
+            id: ord,
+            cmdset_id: "c_ops".to_string(),
+            ord,
+            cmd: cmd.to_string(),
+            timeout_ms: None,
+            on_error: StepOnError::Stop,
+            parser_spec: ParserSpec::Raw,
+        }
+    }
+
+    #[test]
+    fn deny_wins_over_allow_and_profiles_can_relax_the_guard() {
+        let conn = init_in_memory().unwrap();
+        let steps = [
+            step(1, "systemctl status nginx"),
+            step(2, "systemctl restart nginx"),
+            step(3, "rm -rf /var/cache/app"),
+        ];
+        assert!(violations(&conn, "p_web", &steps).unwrap().is_empty());
+
+        settings::set_setting(
+            &conn,
+            settings::GUARD_COMMANDS_KEY,
+            r#"{"allow": ["^systemctl ", "^rm "], "deny": ["rm\\s+-rf\\s+/"]}"#,
+        )
+        .unwrap();
+        let found = violations(&conn, "p_web", &steps).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            found[0].to_string(),
+            r"step 3 `rm -rf /var/cache/app` matches deny pattern rm\s+-rf\s+/"
+        );
+        let message = denied(&found).to_string();
+        assert!(message.starts_with("policy denied: command guard blocked step 3"));
+
+        settings::set_setting_scoped(
+            &conn,
+            &SettingScope::Profile("p_lab".to_string()),
+            settings::GUARD_COMMANDS_KEY,
+            r#"{"allow": ["^systemctl status "]}"#,
+        )
+        .unwrap();
+        let found = violations(&conn, "p_lab", &steps).unwrap();
+        assert_eq!(
+            found
+                .iter()
+                .map(|violation| violation.ord)
+                .collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert_eq!(found[0].rule, "matches no allow pattern");
+        assert!(GuardRules::parse(r#"{"deny": ["("]}"#).is_err());
+    }
+}
@@ -0,0 +1,673 @@
+//! Hook CommandSets run around connections and runs: `pre_connect` before `td connect`,
+//! `td exec`, and `td run`; `post_run` after every exec or run; `on_failure` after one that
+//! failed.
+//!
+//! Hooks come from the profile's `hooks.<point>` setting and then from the main
+//! CommandSet's `vars.hooks.<point>`. Each is a comma-separated list of CommandSet ids, run
+//! on the profile over ssh, or prefixed with `local:` to run each step through the local
+//! shell with `TD_HOOK`, `TD_OP`, `TD_PROFILE_ID`, `TD_HOST`, `TD_CMDSET_ID`, and `TD_OK`
+//! set. With `hooks.failure = blocking` a failed hook aborts the action (or fails it,
+//! after the fact); the default `advisory` only reports it.
+//!
+//! Remote hooks go through the profile's `policy.danger` rules and blocking maintenance
+//! windows like any other CommandSet run. A hook they refuse is not run and aborts the
+//! action under either failure mode.
+
+use std::fmt;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use rusqlite::Connection;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::cmdset::{CmdSetStore, StepOnError};
+use crate::cmdset_runner::{run_cmdset_unattended, run_with_timeout};
+use crate::error::{CoreError, Result, RunError};
+use crate::maintenance::{self, WindowMode};
+use crate::oplog::{self, OpLogEntry};
+use crate::policy::{self, PolicyAction};
+use crate::profile::{Profile, ProfileStore};
+use crate::settings;
+use crate::util;
+
+/// Timeout for a local hook step without its own `timeout_ms`.
+const LOCAL_STEP_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookPoint {
+    PreConnect,
+    PostRun,
+    OnFailure,
+}
+
+impl HookPoint {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PreConnect => "pre_connect",
+            Self::PostRun => "post_run",
+            Self::OnFailure => "on_failure",
+        }
+    }
+
+    pub fn setting_key(self) -> &'static str {
+        match self {
+            Self::PreConnect => settings::HOOKS_PRE_CONNECT_KEY,
+            Self::PostRun => settings::HOOKS_POST_RUN_KEY,
+            Self::OnFailure => settings::HOOKS_ON_FAILURE_KEY,
+        }
+    }
+}
+
+impl fmt::Display for HookPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One hook: a CommandSet run on the profile, or locally with `local:`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookRef {
+    pub cmdset_id: String,
+    pub local: bool,
+}
+
+impl HookRef {
+    /// Parses a comma-separated hook list such as `local:c_notify,c_snapshot`.
+    pub fn parse_list(raw: &str) -> Result<Vec<Self>> {
+        raw.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (local, cmdset_id) = match entry.split_once(':') {
+                    Some(("local", cmdset_id)) => (true, cmdset_id.trim()),
+                    Some(("remote", cmdset_id)) => (false, cmdset_id.trim()),
+                    Some(_) => {
+                        return Err(CoreError::InvalidSetting(format!(
+                            "invalid hook '{entry}': use <cmdset_id>, local:<cmdset_id>, or remote:<cmdset_id>"
+                        )))
+                    }
+                    None => (false, entry),
+                };
+                if cmdset_id.is_empty() {
+                    return Err(CoreError::InvalidSetting(format!(
+                        "invalid hook '{entry}': missing CommandSet id"
+                    )));
+                }
+                Ok(Self {
+                    cmdset_id: cmdset_id.to_string(),
+                    local,
+                })
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for HookRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.local {
+            write!(f, "local:{}", self.cmdset_id)
+        } else {
+            write!(f, "{}", self.cmdset_id)
+        }
+    }
+}
+
+/// What a failed hook does to the action it belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HookFailure {
+    /// Report the failure and carry on.
+    #[default]
+    Advisory,
+    /// Abort the action, or fail it when the hook ran afterwards.
+    Blocking,
+}
+
+/// The action hooks run around.
+#[derive(Debug, Clone, Copy)]
+pub struct HookContext<'a> {
+    /// `connect`, `exec`, `run`, or `schedule`.
+    pub op: &'a str,
+    pub profile: &'a Profile,
+    pub cmdset_id: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HookRun {
+    pub point: HookPoint,
+    pub cmdset_id: String,
+    pub local: bool,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+impl HookRun {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} hook {}{} failed: {}",
+            self.point,
+            if self.local { "local:" } else { "" },
+            self.cmdset_id,
+            self.error.as_deref().unwrap_or("unknown error")
+        )
+    }
+}
+
+/// Hooks configured for `point`: the profile's `hooks.<point>`, then `vars.hooks.<point>`
+/// of the main CommandSet.
+pub fn configured(
+    conn: &Connection,
+    point: HookPoint,
+    profile_id: &str,
+    cmdset_vars: Option<&Value>,
+) -> Result<Vec<HookRef>> {
+    let mut hooks = settings::get_hooks(conn, profile_id, point.setting_key())?;
+    if let Some(raw) = cmdset_vars
+        .and_then(|vars| vars.get("hooks"))
+        .and_then(|hooks| hooks.get(point.as_str()))
+    {
+        let raw = raw.as_str().ok_or_else(|| {
+            CoreError::Run(RunError::InvalidCommandSpec(format!(
+                "vars.hooks.{point} must be a string"
+            )))
+        })?;
+        hooks.extend(HookRef::parse_list(raw)?);
+    }
+    Ok(hooks)
+}
+
+/// Runs the `point` hooks in order and returns the failed ones. Under `blocking`, the
+/// first failure stops the rest and is returned as an error instead.
+pub fn run(
+    profile_store: &ProfileStore,
+    cmdset_store: &CmdSetStore,
+    point: HookPoint,
+    context: &HookContext<'_>,
+    run_ok: Option<bool>,
+) -> Result<Vec<HookRun>> {
+    let conn = profile_store.conn();
+    let vars = match context.cmdset_id {
+        Some(cmdset_id) => cmdset_store.get(cmdset_id)?.and_then(|cmdset| cmdset.vars),
+        None => None,
+    };
+    let hooks = configured(conn, point, &context.profile.profile_id, vars.as_ref())?;
+    if hooks.is_empty() {
+        return Ok(Vec::new());
+    }
+    let failure = settings::get_hooks_failure(conn, &context.profile.profile_id)?;
+    let mut failed = Vec::new();
+    for hook in hooks {
+        let started = Instant::now();
+        let allowed = if hook.local {
+            Ok(())
+        } else {
+            check_remote(cmdset_store, context.profile, &hook.cmdset_id, conn)
+        };
+        let refused = allowed.as_ref().err().map(ToString::to_string);
+        let outcome = if let Err(err) = allowed {
+            Err(err)
+        } else if hook.local {
+            run_local(cmdset_store, &hook.cmdset_id, point, context, run_ok)
+        } else {
+            run_cmdset_unattended(
+                profile_store,
+                cmdset_store,
+                context.profile,
+                &hook.cmdset_id,
+            )
+            .and_then(|result| match result.ok {
+                true => Ok(()),
+                false => Err(CoreError::Run(RunError::CommandExecution(format!(
+                    "exit code {}",
+                    result.exit_code
+                )))),
+            })
+        };
+        let hook_run = HookRun {
+            point,
+            cmdset_id: hook.cmdset_id,
+            local: hook.local,
+            ok: outcome.is_ok(),
+            error: outcome.err().map(|err| err.to_string()),
+        };
+        oplog::log_operation(
+            conn,
+            OpLogEntry {
+                op: "hook".into(),
+                profile_id: Some(context.profile.profile_id.clone()),
+                client_used: None,
+                ok: hook_run.ok,
+                exit_code: None,
+                duration_ms: Some(started.elapsed().as_millis() as i64),
+                meta_json: Some(serde_json::json!({
+                    "hook": point,
+                    "cmdset_id": hook_run.cmdset_id,
+                    "local": hook_run.local,
+                    "for_op": context.op,
+                    "for_cmdset_id": context.cmdset_id,
+                })),
+            },
+        )?;
+        if hook_run.ok {
+            continue;
+        }
+        if let Some(refused) = refused {
+            return Err(CoreError::PolicyDenied(format!(
+                "{point} hook {} refused: {refused}",
+                hook_run.cmdset_id
+            )));
+        }
+        if failure == HookFailure::Blocking {
+            return Err(CoreError::Run(RunError::CommandExecution(format!(
+                "blocking {}",
+                hook_run.describe()
+            ))));
+        }
+        failed.push(hook_run);
+    }
+    Ok(failed)
+}
+
+/// Applies the checks a `td run` of `cmdset_id` on `profile` would get: a blocking
+/// maintenance window and the `policy.danger` cmdsets, command allowlist, and freeze
+/// windows. Confirmations and reasons were already settled for the main action.
+fn check_remote(
+    cmdset_store: &CmdSetStore,
+    profile: &Profile,
+    cmdset_id: &str,
+    conn: &Connection,
+) -> Result<()> {
+    let now_ms = util::now_ms();
+    if let Some(window) = maintenance::active_for(conn, profile, now_ms)? {
+        if window.mode == WindowMode::Block {
+            return Err(window.denied(profile, "hook"));
+        }
+    }
+    let commands: Vec<String> = cmdset_store
+        .list_steps(cmdset_id)?
+        .into_iter()
+        .map(|step| step.cmd)
+        .collect();
+    policy::check(
+        conn,
+        profile,
+        &PolicyAction::Run {
+            cmdset_id,
+            commands: &commands,
+        },
+        now_ms,
+    )?;
+    Ok(())
+}
+
+/// Runs `action` between the `pre_connect` hooks and the `post_run` (and, when it failed,
+/// `on_failure`) hooks. Returns the action's value with the advisory hook failures; a
+/// blocking hook failure becomes the error. An error from the action itself wins over
+/// hook failures, which are then only logged.
+pub fn around<T, E: From<CoreError>>(
+    profile_store: &ProfileStore,
+    cmdset_store: &CmdSetStore,
+    context: &HookContext<'_>,
+    action: impl FnOnce() -> std::result::Result<T, E>,
+    succeeded: impl Fn(&T) -> bool,
+) -> std::result::Result<(T, Vec<HookRun>), E> {
+    let mut failed = run(
+        profile_store,
+        cmdset_store,
+        HookPoint::PreConnect,
+        context,
+        None,
+    )?;
+    let value = match action() {
+        Ok(value) => value,
+        Err(err) => {
+            for point in [HookPoint::PostRun, HookPoint::OnFailure] {
+                if let Err(hook_err) = run(profile_store, cmdset_store, point, context, Some(false))
+                {
+                    tracing::warn!("{hook_err}");
+                }
+            }
+            return Err(err);
+        }
+    };
+    let ok = succeeded(&value);
+    failed.extend(run(
+        profile_store,
+        cmdset_store,
+        HookPoint::PostRun,
+        context,
+        Some(ok),
+    )?);
+    if !ok {
+        failed.extend(run(
+            profile_store,
+            cmdset_store,
+            HookPoint::OnFailure,
+            context,
+            Some(ok),
+        )?);
+    }
+    Ok((value, failed))
+}
+
+fn run_local(
+    cmdset_store: &CmdSetStore,
+    cmdset_id: &str,
+    point: HookPoint,
+    context: &HookContext<'_>,
+    run_ok: Option<bool>,
+) -> Result<()> {
+    if cmdset_store.get(cmdset_id)?.is_none() {
+        return Err(CoreError::Run(RunError::CmdSetNotFound(
+            cmdset_id.to_string(),
+        )));
+    }
+    let mut first_error = None;
+    for step in cmdset_store.list_steps(cmdset_id)? {
+        let mut command = shell_command(&step.cmd);
+        command
+            .env("TD_HOOK", point.as_str())
+            .env("TD_OP", context.op)
+            .env("TD_PROFILE_ID", &context.profile.profile_id)
+            .env("TD_HOST", &context.profile.host)
+            .env("TD_CMDSET_ID", context.cmdset_id.unwrap_or_default())
+            .env("TD_OK", run_ok.map(|ok| ok.to_string()).unwrap_or_default())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let timeout = step
+            .timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(LOCAL_STEP_TIMEOUT);
+        let error = match run_with_timeout(command, timeout) {
+            Ok(output) if output.status.success() => continue,
+            Ok(output) => format!(
+                "step {} exited with {}: {}",
+                step.ord,
+                output.status.code().unwrap_or(-1),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(err) => format!("step {}: {err}", step.ord),
+        };
+        first_error.get_or_insert(error);
+        if step.on_error == StepOnError::Stop {
+            break;
+        }
+    }
+    match first_error {
+        Some(error) => Err(CoreError::Run(RunError::CommandExecution(error))),
+        None => Ok(()),
+    }
+}
+
+fn shell_command(cmd: &str) -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(cmd);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(cmd);
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmdset::{NewCmdSet, NewCmdStep};
+    use crate::db;
+    use crate::parser::ParserSpec;
+    use crate::profile::{DangerLevel, NewProfile, ProfileType};
+
+    #[test]
+    fn parses_hook_lists() {
+        assert_eq!(
+            HookRef::parse_list(" local:c_notify , c_snapshot,").unwrap(),
+            vec![
+                HookRef {
+                    cmdset_id: "c_notify".to_string(),
+                    local: true,
+                },
+                HookRef {
+                    cmdset_id: "c_snapshot".to_string(),
+                    local: false,
+                },
+            ]
+        );
+        assert!(HookRef::parse_list("ftp:c_x").is_err());
+        assert!(HookRef::parse_list("local:").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn local_hooks_wrap_an_action_and_block_when_configured() {
+        let db_path = std::env::temp_dir().join(format!(
+            "teradock-hooks-{}-{}.db",
+            std::process::id(),
+            crate::util::now_ms()
+        ));
+        let marker = db_path.with_extension("log");
+        let profiles = ProfileStore::new(db::init_connection_at(&db_path).unwrap());
+        let mut cmdsets = CmdSetStore::new(db::init_connection_at(&db_path).unwrap());
+        let profile = profiles
+            .insert(NewProfile {
+                profile_id: Some("p_web".to_string()),
+                name: "web".to_string(),
+                profile_type: ProfileType::Ssh,
+                host: "web.example.com".to_string(),
+                port: 22,
+                user: "ops".to_string(),
+                danger_level: DangerLevel::Normal,
+                group: None,
+                tags: Vec::new(),
+                note: None,
+                initial_send: None,
+                client_overrides: None,
+            })
+            .unwrap();
+        for (cmdset_id, cmd) in [
+            (
+                "c_note",
+                format!(
+                    "echo \"$TD_HOOK $TD_OP $TD_HOST $TD_CMDSET_ID $TD_OK\" >> {}",
+                    marker.display()
+                ),
+            ),
+            ("c_fail", "exit 3".to_string()),
+        ] {
+            cmdsets
+                .insert(NewCmdSet {
+                    cmdset_id: Some(cmdset_id.to_string()),
+                    name: cmdset_id.to_string(),
+                    vars: (cmdset_id == "c_fail")
+                        .then(|| serde_json::json!({ "hooks": { "on_failure": "local:c_note" } })),
+                    steps: vec![NewCmdStep {
+                        cmd,
+                        timeout_ms: None,
+                        on_error: StepOnError::Stop,
+                        parser_spec: ParserSpec::Raw,
+                    }],
+                })
+                .unwrap();
+        }
+        let conn = profiles.conn();
+        let scope = settings::SettingScope::Profile("p_web".to_string());
+        settings::set_setting_scoped(
+            conn,
+            &scope,
+            settings::HOOKS_PRE_CONNECT_KEY,
+            "local:c_note",
+        )
+        .unwrap();
+        let context = HookContext {
+            op: "run",
+            profile: &profile,
+            cmdset_id: Some("c_fail"),
+        };
+
+        let (value, failed) = around(
+            &profiles,
+            &cmdsets,
+            &context,
+            || Ok::<_, CoreError>(false),
+            |ok| *ok,
+        )
+        .unwrap();
+        assert!(!value);
+        assert!(failed.is_empty());
+        let written = std::fs::read_to_string(&marker).unwrap();
+        assert_eq!(
+            written.lines().collect::<Vec<_>>(),
+            vec![
+                "pre_connect run web.example.com c_fail ",
+                "on_failure run web.example.com c_fail false",
+            ]
+        );
+
+        settings::set_setting_scoped(conn, &scope, settings::HOOKS_POST_RUN_KEY, "local:c_fail")
+            .unwrap();
+        let (_, failed) = around(
+            &profiles,
+            &cmdsets,
+            &context,
+            || Ok::<_, CoreError>(true),
+            |ok| *ok,
+        )
+        .unwrap();
+        assert_eq!(failed.len(), 1);
+        assert!(failed[0]
+            .describe()
+            .starts_with("post_run hook local:c_fail failed"));
+
+        settings::set_setting_scoped(conn, &scope, settings::HOOKS_FAILURE_KEY, "blocking")
+            .unwrap();
+        settings::set_setting_scoped(
+            conn,
+            &scope,
+            settings::HOOKS_PRE_CONNECT_KEY,
+            "local:c_fail",
+        )
+        .unwrap();
+        let mut ran = false;
+        let blocked = around(
+            &profiles,
+            &cmdsets,
+            &context,
+            || {
+                ran = true;
+                Ok::<_, CoreError>(true)
+            },
+            |ok| *ok,
+        );
+        assert!(matches!(
+            blocked,
+            Err(CoreError::Run(RunError::CommandExecution(_)))
+        ));
+        assert!(!ran, "a blocking pre_connect failure stops the action");
+
+        drop((profiles, cmdsets));
+        let _ = std::fs::remove_file(&db_path);
+        let _ = std::fs::remove_file(&marker);
+    }
+
+    #[test]
+    fn remote_hooks_are_held_to_the_profile_policy_and_maintenance_windows() {
+        let db_path = std::env::temp_dir().join(format!(
+            "teradock-hooks-policy-{}-{}.db",
+            std::process::id(),
+            crate::util::now_ms()
+        ));
+        let profiles = ProfileStore::new(db::init_connection_at(&db_path).unwrap());
+        let mut cmdsets = CmdSetStore::new(db::init_connection_at(&db_path).unwrap());
+        let profile = profiles
+            .insert(NewProfile {
+                profile_id: Some("p_db".to_string()),
+                name: "db".to_string(),
+                profile_type: ProfileType::Ssh,
+                host: "db.example.com".to_string(),
+                port: 22,
+                user: "ops".to_string(),
+                danger_level: DangerLevel::Critical,
+                group: None,
+                tags: Vec::new(),
+                note: None,
+                initial_send: None,
+                client_overrides: None,
+            })
+            .unwrap();
+        for (cmdset_id, cmd) in [("c_health", "uptime"), ("c_cleanup", "rm -rf /var/tmp/x")] {
+            cmdsets
+                .insert(NewCmdSet {
+                    cmdset_id: Some(cmdset_id.to_string()),
+                    name: cmdset_id.to_string(),
+                    vars: None,
+                    steps: vec![NewCmdStep {
+                        cmd: cmd.to_string(),
+                        timeout_ms: None,
+                        on_error: StepOnError::Stop,
+                        parser_spec: ParserSpec::Raw,
+                    }],
+                })
+                .unwrap();
+        }
+        let conn = profiles.conn();
+        settings::set_setting(
+            conn,
+            settings::POLICY_DANGER_KEY,
+            r#"{"critical": {"cmdsets": ["c_health"], "allow_commands": ["uptime"]}}"#,
+        )
+        .unwrap();
+        settings::set_setting_scoped(
+            conn,
+            &settings::SettingScope::Profile("p_db".to_string()),
+            settings::HOOKS_PRE_CONNECT_KEY,
+            "c_cleanup",
+        )
+        .unwrap();
+        let context = HookContext {
+            op: "run",
+            profile: &profile,
+            cmdset_id: Some("c_health"),
+        };
+
+        let mut ran = false;
+        let refused = around(
+            &profiles,
+            &cmdsets,
+            &context,
+            || {
+                ran = true;
+                Ok::<_, CoreError>(true)
+            },
+            |ok| *ok,
+        );
+        assert!(
+            matches!(&refused, Err(CoreError::PolicyDenied(message)) if message.contains("c_cleanup")),
+            "{refused:?}"
+        );
+        assert!(!ran, "a refused hook stops the action even when advisory");
+        assert!(check_remote(&cmdsets, &profile, "c_health", conn).is_ok());
+
+        maintenance::add(
+            conn,
+            maintenance::NewMaintenanceWindow {
+                target: crate::schedule::ScheduleTarget::parse("p_db").unwrap(),
+                span: "2000-01-01..2100-01-01".to_string(),
+                mode: WindowMode::Block,
+                note: None,
+            },
+            0,
+        )
+        .unwrap();
+        let blocked = check_remote(&cmdsets, &profile, "c_health", conn).unwrap_err();
+        assert!(
+            blocked.to_string().contains("maintenance window"),
+            "{blocked}"
+        );
+
+        drop((profiles, cmdsets));
+        let _ = std::fs::remove_file(&db_path);
+    }
+}
@@ -0,0 +1,552 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::profile::{DangerLevel, NewProfile, ProfileStore, ProfileType};
+
+/// Values used for columns that a host list leaves empty.
+#[derive(Debug, Clone)]
+pub struct HostImportDefaults {
+    pub user: Option<String>,
+    pub port: u16,
+    pub profile_type: ProfileType,
+    pub danger_level: DangerLevel,
+    pub group: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// One data row of a host list; `line` is 1-based for error reporting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostRow {
+    pub line: usize,
+    pub host: String,
+    pub user: Option<String>,
+    pub port: Option<String>,
+    pub group: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedHost {
+    pub line: usize,
+    pub host: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HostImportReport {
+    pub created: Vec<String>,
+    pub skipped: Vec<SkippedHost>,
+}
+
+const COLUMNS: [&str; 5] = ["host", "user", "port", "group", "tags"];
+
+/// Parses `host,user,port,group,tags` CSV (header optional, columns may be reordered
+/// when a header is present) or a plain newline-separated host list. Blank lines and
+/// `#` comments are ignored; tags inside a cell are separated by `;`.
+pub fn parse_host_rows(raw: &str) -> Vec<HostRow> {
+    let mut columns: Option<Vec<Option<usize>>> = None;
+    let mut rows = Vec::new();
+    for (idx, line) in raw.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let fields = split_csv_line(trimmed);
+        if columns.is_none() && rows.is_empty() && is_header(&fields) {
+            columns = Some(
+                COLUMNS
+                    .iter()
+                    .map(|name| {
+                        fields
+                            .iter()
+                            .position(|field| field.trim().eq_ignore_ascii_case(name))
+                    })
+                    .collect(),
+            );
+            continue;
+        }
+        let field = |column: usize| -> Option<String> {
+            let position = match &columns {
+                Some(mapping) => mapping[column]?,
+                None => column,
+            };
+            fields
+                .get(position)
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        rows.push(HostRow {
+            line: idx + 1,
+            host: field(0).unwrap_or_default(),
+            user: field(1),
+            port: field(2),
+            group: field(3),
+            tags: field(4)
+                .map(|raw| {
+                    raw.split(';')
+                        .map(str::trim)
+                        .filter(|tag| !tag.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        });
+    }
+    rows
+}
+
+/// Creates one profile per row in a single transaction. Rows that are invalid or that
+/// duplicate an existing host/user/port are skipped and reported instead of aborting.
+pub fn import_hosts(
+    store: &ProfileStore,
+    rows: &[HostRow],
+    defaults: &HostImportDefaults,
+) -> Result<HostImportReport> {
+    let mut seen = store
+        .list()?
+        .into_iter()
+        .map(|profile| endpoint_key(&profile.host, &profile.user, profile.port))
+        .collect::<HashSet<_>>();
+    let mut report = HostImportReport::default();
+    let tx = store.conn().unchecked_transaction()?;
+    for row in rows {
+        let skip = |reason: String| SkippedHost {
+            line: row.line,
+            host: row.host.clone(),
+            reason,
+        };
+        if row.host.is_empty() {
+            report.skipped.push(skip("missing host".to_string()));
+            continue;
+        }
+        let Some(user) = row.user.clone().or_else(|| defaults.user.clone()) else {
+            report
+                .skipped
+                .push(skip("missing user (no default given)".to_string()));
+            continue;
+        };
+        let port = match row.port.as_deref() {
+            Some(raw) => match raw.parse::<u16>() {
+                Ok(port) if port > 0 => port,
+                _ => {
+                    report.skipped.push(skip(format!("invalid port: {raw}")));
+                    continue;
+                }
+            },
+            None => defaults.port,
+        };
+        if !seen.insert(endpoint_key(&row.host, &user, port)) {
+            report
+                .skipped
+                .push(skip(format!("duplicate of {user}@{}:{port}", row.host)));
+            continue;
+        }
+        let mut tags = defaults.tags.clone();
+        for tag in &row.tags {
+            if !tags
+                .iter()
+                .any(|existing| existing.eq_ignore_ascii_case(tag))
+            {
+                tags.push(tag.clone());
+            }
+        }
+        let created = store.insert(NewProfile {
+            profile_id: None,
+            name: row.host.clone(),
+            profile_type: defaults.profile_type,
+            host: row.host.clone(),
+            port,
+            user,
+            danger_level: defaults.danger_level,
+            group: row.group.clone().or_else(|| defaults.group.clone()),
+            tags,
+            note: None,
+            initial_send: None,
+            client_overrides: None,
+        })?;
+        report.created.push(created.profile_id);
+    }
+    tx.commit()?;
+    Ok(report)
+}
+
+/// An `ssh` destination found in shell history; `line` is where it first appears.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HistoryHost {
+    pub line: usize,
+    pub host: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub count: usize,
+}
+
+impl HistoryHost {
+    pub fn to_row(&self) -> HostRow {
+        HostRow {
+            line: self.line,
+            host: self.host.clone(),
+            user: self.user.clone(),
+            port: self.port.map(|port| port.to_string()),
+            group: None,
+            tags: Vec::new(),
+        }
+    }
+}
+
+/// ssh options that take a value, either attached (`-p2222`) or as the next argument.
+const SSH_VALUE_OPTIONS: &str = "BbcDEeFIiJLlmOoPpQRSWw";
+
+/// Extracts `ssh [-p N] [-l user] [user@]host` invocations from bash, zsh (extended
+/// history format included), or PowerShell history, deduplicated and ordered by use
+/// count. Destinations containing shell expansions are ignored.
+pub fn parse_ssh_history(raw: &str) -> Vec<HistoryHost> {
+    let mut hosts: Vec<HistoryHost> = Vec::new();
+    let mut index = HashMap::new();
+    for (idx, line) in raw.lines().enumerate() {
+        let line = strip_zsh_timestamp(line.trim());
+        let tokens = split_shell_words(line);
+        for command in tokens.split(|token| matches!(token.as_str(), ";" | "|" | "&&" | "||")) {
+            let Some(found) = parse_ssh_command(command) else {
+                continue;
+            };
+            let key = endpoint_key(
+                &found.host,
+                found.user.as_deref().unwrap_or(""),
+                found.port.unwrap_or(0),
+            );
+            match index.get(&key) {
+                Some(&position) => {
+                    let existing: &mut HistoryHost = &mut hosts[position];
+                    existing.count += 1;
+                }
+                None => {
+                    index.insert(key, hosts.len());
+                    hosts.push(HistoryHost {
+                        line: idx + 1,
+                        count: 1,
+                        ..found
+                    });
+                }
+            }
+        }
+    }
+    hosts.sort_by(|a, b| b.count.cmp(&a.count).then(a.line.cmp(&b.line)));
+    hosts
+}
+
+/// Drops history entries that already have a profile with the same host/user/port once
+/// `defaults` fill in the missing user and port.
+pub fn unknown_history_hosts(
+    store: &ProfileStore,
+    hosts: Vec<HistoryHost>,
+    defaults: &HostImportDefaults,
+) -> Result<Vec<HistoryHost>> {
+    let known = store
+        .list()?
+        .into_iter()
+        .map(|profile| endpoint_key(&profile.host, &profile.user, profile.port))
+        .collect::<HashSet<_>>();
+    Ok(hosts
+        .into_iter()
+        .filter(|entry| {
+            let user = entry.user.as_deref().or(defaults.user.as_deref());
+            match user {
+                Some(user) => !known.contains(&endpoint_key(
+                    &entry.host,
+                    user,
+                    entry.port.unwrap_or(defaults.port),
+                )),
+                None => true,
+            }
+        })
+        .collect())
+}
+
+/// History files scanned when none are given: bash, zsh, and PowerShell (PSReadLine).
+pub fn default_history_paths(home: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![home.join(".bash_history"), home.join(".zsh_history")];
+    let psreadline = ["Microsoft", "Windows", "PowerShell", "PSReadLine"];
+    let mut windows = home.join("AppData").join("Roaming");
+    let mut unix = home.join(".local").join("share").join("powershell");
+    for part in psreadline {
+        windows.push(part);
+    }
+    unix.push("PSReadLine");
+    paths.push(windows.join("ConsoleHost_history.txt"));
+    paths.push(unix.join("ConsoleHost_history.txt"));
+    paths
+}
+
+fn strip_zsh_timestamp(line: &str) -> &str {
+    // `: 1700000000:0;ssh host`
+    match line
+        .strip_prefix(": ")
+        .and_then(|rest| rest.split_once(';'))
+    {
+        Some((stamp, command)) if stamp.chars().all(|c| c.is_ascii_digit() || c == ':') => command,
+        _ => line,
+    }
+}
+
+fn parse_ssh_command(tokens: &[String]) -> Option<HistoryHost> {
+    let mut iter = tokens.iter().skip_while(|token| *token == "sudo");
+    let program = iter.next()?;
+    let name = program.rsplit(['/', '\\']).next().unwrap_or(program);
+    if !name.eq_ignore_ascii_case("ssh") && !name.eq_ignore_ascii_case("ssh.exe") {
+        return None;
+    }
+    let mut user = None;
+    let mut port = None;
+    let mut destination = None;
+    while let Some(token) = iter.next() {
+        if token == "--" {
+            destination = destination.or_else(|| iter.next().cloned());
+            break;
+        }
+        // Options may also follow the destination (`ssh web1 -p 2222`); the first
+        // other word after it starts the remote command.
+        let Some(flags) = token.strip_prefix('-') else {
+            if destination.is_some() {
+                break;
+            }
+            destination = Some(token.clone());
+            continue;
+        };
+        for (pos, flag) in flags.char_indices() {
+            if !SSH_VALUE_OPTIONS.contains(flag) {
+                continue;
+            }
+            let attached = &flags[pos + flag.len_utf8()..];
+            let value = if attached.is_empty() {
+                iter.next().cloned()?
+            } else {
+                attached.to_string()
+            };
+            match flag {
+                'p' => port = Some(value.parse::<u16>().ok()?),
+                'l' => user = Some(value),
+                'o' => {
+                    let (key, option_value) = value.split_once(['=', ' '])?;
+                    if key.eq_ignore_ascii_case("port") {
+                        port = Some(option_value.trim().parse::<u16>().ok()?);
+                    } else if key.eq_ignore_ascii_case("user") {
+                        user = Some(option_value.trim().to_string());
+                    }
+                }
+                _ => {}
+            }
+            break;
+        }
+    }
+    let mut destination = destination?;
+    if let Some(rest) = destination.strip_prefix("ssh://") {
+        destination = rest.trim_end_matches('/').to_string();
+        if let Some((host_part, raw_port)) = destination.rsplit_once(':') {
+            port = Some(raw_port.parse::<u16>().ok()?);
+            destination = host_part.to_string();
+        }
+    }
+    let (dest_user, host) = match destination.rsplit_once('@') {
+        Some((dest_user, host)) => (Some(dest_user.to_string()), host.to_string()),
+        None => (None, destination),
+    };
+    let valid_host = !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+    let valid_user = dest_user
+        .as_deref()
+        .or(user.as_deref())
+        .is_none_or(|user| !user.is_empty() && !user.contains(['$', '`', '*']));
+    if !valid_host || !valid_user || port == Some(0) {
+        return None;
+    }
+    Some(HistoryHost {
+        line: 0,
+        host,
+        user: dest_user.or(user),
+        port,
+        count: 0,
+    })
+}
+
+/// Splits a command line into words, honouring single and double quotes and treating
+/// `;`, `|`, `&&`, and `||` as separate tokens.
+fn split_shell_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match (quote, ch) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => quote = Some(ch),
+            (None, c) if c.is_whitespace() => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            (None, ';') => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+                words.push(";".to_string());
+            }
+            (None, '|' | '&') => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+                let doubled = chars.next_if_eq(&ch).is_some();
+                words.push(match (ch, doubled) {
+                    ('|', false) => "|".to_string(),
+                    ('|', true) => "||".to_string(),
+                    (_, true) => "&&".to_string(),
+                    // A lone `&` backgrounds the command; treat it as a separator too.
+                    (_, false) => ";".to_string(),
+                });
+            }
+            (None, c) => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn endpoint_key(host: &str, user: &str, port: u16) -> (String, String, u16) {
+    (host.to_ascii_lowercase(), user.to_string(), port)
+}
+
+fn is_header(fields: &[String]) -> bool {
+    fields
+        .iter()
+        .any(|field| field.trim().eq_ignore_ascii_case("host"))
+        && fields.iter().all(|field| {
+            COLUMNS
+                .iter()
+                .any(|name| field.trim().eq_ignore_ascii_case(name))
+        })
+}
+
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut current)),
+            _ => current.push(ch),
+        }
+    }
+    fields.push(current);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_in_memory;
+
+    fn defaults() -> HostImportDefaults {
+        HostImportDefaults {
+            user: Some("ops".into()),
+            port: 22,
+            profile_type: ProfileType::Ssh,
+            danger_level: DangerLevel::Normal,
+            group: None,
+            tags: vec!["imported".into()],
+        }
+    }
+
+    #[test]
+    fn parses_csv_with_header_and_plain_lists() {
+        let rows =
+            parse_host_rows("user,host,tags\nalice,web1,\"web;prod\"\n# comment\n\nbob,db1,\n");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].host, "web1");
+        assert_eq!(rows[0].user.as_deref(), Some("alice"));
+        assert_eq!(rows[0].tags, vec!["web", "prod"]);
+        assert_eq!(rows[1].line, 5);
+
+        let plain = parse_host_rows("a.example.com\nb.example.com\n");
+        assert_eq!(plain.len(), 2);
+        assert_eq!(plain[1].host, "b.example.com");
+        assert!(plain[1].user.is_none());
+    }
+
+    #[test]
+    fn imports_rows_and_skips_duplicates_and_invalid_ports() {
+        let store = ProfileStore::new(init_in_memory().unwrap());
+        let rows = parse_host_rows("web1,alice,2222,web,prod\nweb1,alice,2222\nweb2,,abc\nweb3\n");
+
+        let report = import_hosts(&store, &rows, &defaults()).unwrap();
+
+        assert_eq!(report.created.len(), 2);
+        assert_eq!(report.skipped.len(), 2);
+        assert_eq!(report.skipped[0].line, 2);
+        assert!(report.skipped[1].reason.contains("invalid port"));
+        let profiles = store.list().unwrap();
+        let web1 = profiles.iter().find(|p| p.host == "web1").unwrap();
+        assert_eq!(web1.port, 2222);
+        assert_eq!(web1.group.as_deref(), Some("web"));
+        assert_eq!(web1.tags, vec!["imported", "prod"]);
+        let web3 = profiles.iter().find(|p| p.host == "web3").unwrap();
+        assert_eq!(web3.user, "ops");
+
+        let again = import_hosts(&store, &rows[..1], &defaults()).unwrap();
+        assert!(again.created.is_empty());
+    }
+
+    #[test]
+    fn parses_ssh_invocations_from_history() {
+        let history = "\
+ls -la
+ssh alice@web1 -p 2222
+: 1700000000:0;ssh -p2222 alice@web1 uptime
+ssh -i ~/.ssh/id_ed25519 -l bob db1.example.com
+cd /tmp && ssh -o Port=2200 ssh://carol@edge:2201
+ssh.exe web2
+ssh $HOST
+sudo ssh -vA root@10.0.0.5 'tail -f /var/log/syslog'
+";
+        let hosts = parse_ssh_history(history);
+
+        assert_eq!(hosts.len(), 5);
+        assert_eq!(hosts[0].host, "web1");
+        assert_eq!(hosts[0].user.as_deref(), Some("alice"));
+        assert_eq!(hosts[0].port, Some(2222));
+        assert_eq!(hosts[0].count, 2);
+        assert_eq!(hosts[1].user.as_deref(), Some("bob"));
+        assert_eq!(hosts[1].port, None);
+        assert_eq!(hosts[2].host, "edge");
+        assert_eq!(hosts[2].port, Some(2201));
+        assert_eq!(hosts[3].host, "web2");
+        assert_eq!(hosts[4].user.as_deref(), Some("root"));
+        assert_eq!(hosts[4].host, "10.0.0.5");
+    }
+
+    #[test]
+    fn unknown_history_hosts_skips_existing_profiles() {
+        let store = ProfileStore::new(init_in_memory().unwrap());
+        import_hosts(&store, &parse_host_rows("web1,alice,2222\n"), &defaults()).unwrap();
+        let hosts = parse_ssh_history("ssh -p 2222 alice@web1\nssh web2\n");
+
+        let unknown = unknown_history_hosts(&store, hosts, &defaults()).unwrap();
+
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].host, "web2");
+        let report = import_hosts(&store, &[unknown[0].to_row()], &defaults()).unwrap();
+        assert_eq!(report.created.len(), 1);
+    }
+}
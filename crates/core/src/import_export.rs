@@ -6,7 +6,7 @@ use serde_json::Value;
 
 use crate::configset::ConfigFileWhen;
 use crate::crypto::{decrypt, encrypt, random_bytes, MasterKey};
-use crate::error::{CoreError, Result};
+use crate::error::{CoreError, Result, SecretError};
 use crate::profile::{DangerLevel, Profile, ProfileType};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -14,6 +14,10 @@ use crate::profile::{DangerLevel, Profile, ProfileType};
 pub enum ConflictStrategy {
     Reject,
     Rename,
+    /// Leave items whose id already exists alone; name collisions are renamed.
+    Skip,
+    /// Replace profiles whose id already exists; other existing items are skipped.
+    Overwrite,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -84,6 +88,12 @@ pub struct ImportReport {
     pub configs: usize,
     pub secrets: usize,
     pub secrets_skipped: usize,
+    /// Items left out because their id already exists (`skip`, `overwrite`).
+    #[serde(default)]
+    pub skipped: usize,
+    /// Profiles replaced in place (`overwrite`).
+    #[serde(default)]
+    pub overwritten: usize,
 }
 
 pub fn export_document(
@@ -113,6 +123,27 @@ pub fn export_document(
     })
 }
 
+/// An export document with only the given profiles, for sharing a selection. Command sets,
+/// configs, and secrets are left out.
+pub fn export_profiles(conn: &Connection, profile_ids: &[String]) -> Result<ExportDocument> {
+    let mut profiles = load_profiles(conn)?;
+    if let Some(missing) = profile_ids
+        .iter()
+        .find(|id| !profiles.iter().any(|profile| &profile.profile_id == *id))
+    {
+        return Err(CoreError::NotFound(format!("profile not found: {missing}")));
+    }
+    profiles.retain(|profile| profile_ids.contains(&profile.profile_id));
+    Ok(ExportDocument {
+        version: 1,
+        profiles,
+        cmdsets: Vec::new(),
+        parsers: Vec::new(),
+        configs: Vec::new(),
+        secrets: Vec::new(),
+    })
+}
+
 pub fn export_to_json(
     conn: &Connection,
     include_secrets: bool,
@@ -151,21 +182,29 @@ pub fn import_document(
     let existing_parser_ids = load_id_set(&tx, "parsers", "parser_id")?;
     let existing_secret_ids = load_id_set(&tx, "secrets", "secret_id")?;
 
+    let keep_existing = matches!(
+        strategy,
+        ConflictStrategy::Skip | ConflictStrategy::Overwrite
+    );
     ensure_no_id_conflicts(
         &existing_profile_ids,
         document.profiles.iter().map(|p| &p.profile_id),
+        keep_existing,
     )?;
     ensure_no_id_conflicts(
         &existing_cmdset_ids,
         document.cmdsets.iter().map(|c| &c.cmdset_id),
+        keep_existing,
     )?;
     ensure_no_id_conflicts(
         &existing_config_ids,
         document.configs.iter().map(|c| &c.config_id),
+        keep_existing,
     )?;
     ensure_no_id_conflicts(
         &existing_parser_ids,
         document.parsers.iter().map(|p| &p.parser_id),
+        keep_existing,
     )?;
     ensure_no_id_conflicts(
         &existing_secret_ids,
@@ -174,6 +213,7 @@ pub fn import_document(
             .iter()
             .filter(|secret| secret.value.is_some())
             .map(|secret| &secret.secret_id),
+        keep_existing,
     )?;
 
     let mut profile_names = load_name_set(&tx, "profiles")?;
@@ -182,22 +222,49 @@ pub fn import_document(
 
     let mut profiles = document.profiles;
     for profile in &mut profiles {
+        let exists = existing_profile_ids.contains(&profile.profile_id);
+        if exists && strategy == ConflictStrategy::Skip {
+            report.skipped += 1;
+            continue;
+        }
+        if exists {
+            let current: String = tx.query_row(
+                "SELECT name FROM profiles WHERE profile_id = ?1",
+                params![profile.profile_id],
+                |row| row.get(0),
+            )?;
+            profile_names.remove(&current);
+        }
         profile.name = resolve_name(
             &mut profile_names,
             profile.name.clone(),
             strategy,
             "profile",
         )?;
-        insert_profile(&tx, profile)?;
-        report.profiles += 1;
+        if exists {
+            overwrite_profile(&tx, profile)?;
+            report.overwritten += 1;
+        } else {
+            insert_profile(&tx, profile)?;
+            report.profiles += 1;
+        }
     }
 
     for parser in &document.parsers {
+        if existing_parser_ids.contains(&parser.parser_id) {
+            report.skipped += 1;
+            continue;
+        }
         insert_parser(&tx, parser)?;
         report.parsers += 1;
     }
 
     let mut cmdsets = document.cmdsets;
+    cmdsets.retain(|cmdset| {
+        let exists = existing_cmdset_ids.contains(&cmdset.cmdset_id);
+        report.skipped += usize::from(exists);
+        !exists
+    });
     for cmdset in &mut cmdsets {
         cmdset.name = resolve_name(&mut cmdset_names, cmdset.name.clone(), strategy, "cmdset")?;
         insert_cmdset(&tx, cmdset)?;
@@ -210,6 +277,11 @@ pub fn import_document(
     }
 
     let mut configs = document.configs;
+    configs.retain(|config| {
+        let exists = existing_config_ids.contains(&config.config_id);
+        report.skipped += usize::from(exists);
+        !exists
+    });
     for config in &mut configs {
         if let Some(hooks) = config.hooks_cmdset_id.as_deref() {
             if !available_cmdsets.contains(hooks) {
@@ -232,6 +304,9 @@ pub fn import_document(
     let mut secrets_skipped = 0usize;
     for secret in &document.secrets {
         match &secret.value {
+            Some(_) if existing_secret_ids.contains(&secret.secret_id) => {
+                report.skipped += 1;
+            }
             Some(_value) => {
                 let master = master.ok_or_else(|| {
                     CoreError::Import("master key required to import secrets".into())
@@ -260,11 +335,21 @@ pub fn import_from_json(
     import_document(conn, document, strategy, master)
 }
 
+/// Loads an export document into a scratch database for the read-only viewer. Secret
+/// values are dropped so no master key is needed; secret metadata is kept.
+pub fn load_snapshot(conn: &mut Connection, mut document: ExportDocument) -> Result<ImportReport> {
+    for secret in &mut document.secrets {
+        secret.value = None;
+    }
+    import_document(conn, document, ConflictStrategy::Rename, None)
+}
+
 fn load_profiles(conn: &Connection) -> Result<Vec<Profile>> {
     let mut stmt = conn.prepare(
         r#"
         SELECT profile_id, name, type, host, port, user, danger_level, "group",
-               tags_json, note, initial_send, client_overrides_json, created_at, updated_at, last_used_at
+               tags_json, note, initial_send, client_overrides_json, created_at, updated_at, last_used_at,
+               archived_at
         FROM profiles
         ORDER BY name ASC
         "#,
@@ -408,7 +493,8 @@ fn load_secrets(
             let nonce: Vec<u8> = row.get("nonce")?;
             let aad = secret_aad(&secret_id, &kind);
             let plaintext = decrypt(master.as_ref(), &nonce, aad.as_bytes(), &ciphertext)?;
-            let value = String::from_utf8(plaintext).map_err(|_| CoreError::DecryptionFailed)?;
+            let value = String::from_utf8(plaintext)
+                .map_err(|_| CoreError::Secret(SecretError::DecryptionFailed))?;
             Some(value)
         } else {
             None
@@ -451,6 +537,7 @@ fn deserialize_profile(row: &Row<'_>) -> Result<Profile> {
         created_at: row.get("created_at")?,
         updated_at: row.get("updated_at")?,
         last_used_at: row.get("last_used_at")?,
+        archived_at: row.get("archived_at")?,
     })
 }
 
@@ -478,7 +565,13 @@ fn load_name_set(conn: &Connection, table: &str) -> Result<HashSet<String>> {
     Ok(names)
 }
 
-fn ensure_no_id_conflicts<'a, I>(existing: &HashSet<String>, incoming: I) -> Result<()>
+/// Rejects ids that repeat within the import, and ids that already exist unless
+/// `keep_existing` (the caller then skips or overwrites those items).
+fn ensure_no_id_conflicts<'a, I>(
+    existing: &HashSet<String>,
+    incoming: I,
+    keep_existing: bool,
+) -> Result<()>
 where
     I: Iterator<Item = &'a String>,
 {
@@ -489,7 +582,7 @@ where
                 "import id appears multiple times: {id}"
             )));
         }
-        if existing.contains(id) {
+        if existing.contains(id) && !keep_existing {
             return Err(CoreError::Conflict(format!(
                 "import id already exists: {id}"
             )));
@@ -513,7 +606,7 @@ fn resolve_name(
         ConflictStrategy::Reject => Err(CoreError::Conflict(format!(
             "{kind} name already exists: {candidate}"
         ))),
-        ConflictStrategy::Rename => {
+        ConflictStrategy::Rename | ConflictStrategy::Skip | ConflictStrategy::Overwrite => {
             let mut suffix = 1usize;
             loop {
                 let next = if suffix == 1 {
@@ -531,7 +624,7 @@ fn resolve_name(
     }
 }
 
-fn insert_profile(tx: &Transaction<'_>, profile: &Profile) -> Result<()> {
+pub(crate) fn insert_profile(tx: &Transaction<'_>, profile: &Profile) -> Result<()> {
     let tags_json = serde_json::to_string(&profile.tags)?;
     let overrides_json = profile
         .client_overrides
@@ -543,8 +636,9 @@ fn insert_profile(tx: &Transaction<'_>, profile: &Profile) -> Result<()> {
         r#"
         INSERT INTO profiles (
             profile_id, name, type, host, port, user, danger_level, "group",
-            tags_json, note, initial_send, client_overrides_json, created_at, updated_at, last_used_at
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+            tags_json, note, initial_send, client_overrides_json, created_at, updated_at, last_used_at,
+            archived_at
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
         "#,
         params![
             profile.profile_id,
@@ -562,6 +656,44 @@ fn insert_profile(tx: &Transaction<'_>, profile: &Profile) -> Result<()> {
             profile.created_at,
             profile.updated_at,
             profile.last_used_at,
+            profile.archived_at,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Replaces an existing profile's fields with the imported ones; `created_at` and
+/// `last_used_at` stay as they are.
+fn overwrite_profile(tx: &Transaction<'_>, profile: &Profile) -> Result<()> {
+    let tags_json = serde_json::to_string(&profile.tags)?;
+    let overrides_json = profile
+        .client_overrides
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+    tx.execute(
+        r#"
+        UPDATE profiles
+        SET name = ?2, type = ?3, host = ?4, port = ?5, user = ?6, danger_level = ?7,
+            "group" = ?8, tags_json = ?9, note = ?10, initial_send = ?11,
+            client_overrides_json = ?12, updated_at = ?13, archived_at = ?14
+        WHERE profile_id = ?1
+        "#,
+        params![
+            profile.profile_id,
+            profile.name,
+            profile.profile_type.to_string(),
+            profile.host,
+            profile.port as i64,
+            profile.user,
+            profile.danger_level.to_string(),
+            profile.group,
+            tags_json,
+            profile.note,
+            profile.initial_send,
+            overrides_json,
+            profile.updated_at,
+            profile.archived_at,
         ],
     )?;
     Ok(())
@@ -667,3 +799,94 @@ fn insert_secret(tx: &Transaction<'_>, secret: &ExportSecret, master: &MasterKey
 fn secret_aad(secret_id: &str, kind: &str) -> String {
     format!("{secret_id}:{kind}")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_in_memory;
+    use crate::profile::{NewProfile, ProfileStore};
+    use common::id::seed_ids;
+
+    fn seeded_store(seed: u64) -> ProfileStore {
+        let store = ProfileStore::new(init_in_memory().unwrap());
+        let _ids = seed_ids(seed);
+        for host in ["web1", "db1"] {
+            store
+                .insert(NewProfile {
+                    profile_id: None,
+                    name: host.into(),
+                    profile_type: ProfileType::Ssh,
+                    host: host.into(),
+                    port: 22,
+                    user: "ops".into(),
+                    danger_level: DangerLevel::Normal,
+                    group: None,
+                    tags: Vec::new(),
+                    note: None,
+                    initial_send: None,
+                    client_overrides: None,
+                })
+                .unwrap();
+        }
+        store
+    }
+
+    fn profile_ids(document: &ExportDocument) -> Vec<String> {
+        let mut ids = document
+            .profiles
+            .iter()
+            .map(|profile| profile.profile_id.clone())
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn round_trip_keeps_seeded_ids_stable() {
+        let first = export_document(seeded_store(42).conn(), false, None).unwrap();
+        let second = export_document(seeded_store(42).conn(), false, None).unwrap();
+        assert_eq!(profile_ids(&first), profile_ids(&second));
+
+        let mut conn = init_in_memory().unwrap();
+        let report =
+            import_document(&mut conn, first.clone(), ConflictStrategy::Reject, None).unwrap();
+        assert_eq!(report.profiles, 2);
+        let imported = export_document(&conn, false, None).unwrap();
+        assert_eq!(profile_ids(&imported), profile_ids(&first));
+    }
+
+    #[test]
+    fn selected_profiles_import_with_skip_or_overwrite() {
+        let store = seeded_store(7);
+        let all = export_document(store.conn(), false, None).unwrap();
+        let web = all.profiles.iter().find(|p| p.name == "web1").unwrap();
+        let mut selection =
+            export_profiles(store.conn(), std::slice::from_ref(&web.profile_id)).unwrap();
+        assert_eq!(selection.profiles.len(), 1);
+        assert!(export_profiles(store.conn(), &["p_missing".to_string()]).is_err());
+
+        let mut conn = init_in_memory().unwrap();
+        import_document(&mut conn, all.clone(), ConflictStrategy::Reject, None).unwrap();
+        selection.profiles[0].host = "web1.new".to_string();
+        assert!(
+            import_document(&mut conn, selection.clone(), ConflictStrategy::Rename, None).is_err(),
+            "existing ids still conflict"
+        );
+
+        let report =
+            import_document(&mut conn, selection.clone(), ConflictStrategy::Skip, None).unwrap();
+        assert_eq!((report.profiles, report.skipped), (0, 1));
+        let report = import_document(
+            &mut conn,
+            selection.clone(),
+            ConflictStrategy::Overwrite,
+            None,
+        )
+        .unwrap();
+        assert_eq!((report.profiles, report.overwritten), (0, 1));
+        let after = export_document(&conn, false, None).unwrap();
+        let web = after.profiles.iter().find(|p| p.name == "web1").unwrap();
+        assert_eq!(web.host, "web1.new");
+        assert_eq!(after.profiles.len(), 2);
+    }
+}
@@ -1,26 +1,50 @@
+pub mod active_session;
 pub mod agent;
+pub mod audit;
+pub mod calendar;
+pub mod clipboard;
 pub mod cmdset;
 pub mod cmdset_runner;
 pub mod configset;
 #[cfg(windows)]
 pub mod conpty;
 pub mod crypto;
+pub mod daemon;
 pub mod db;
+pub mod db_context;
 pub mod doctor;
 pub mod error;
+pub mod forward_preset;
+pub mod guard;
+pub mod hooks;
+pub mod host_import;
 pub mod import_export;
+pub mod maintenance;
+pub mod notify;
 pub mod oplog;
 pub mod parser;
 pub mod paths;
+pub mod policy;
 pub mod profile;
+pub mod remote_script;
+pub mod retention;
+pub mod run_events;
+pub mod run_output;
+pub mod run_record;
+pub mod run_report;
+pub mod schedule;
 pub mod secret;
 pub mod session_log;
 pub mod settings;
 pub mod settings_registry;
 pub mod ssh;
+#[cfg(feature = "ssh-native")]
+pub mod ssh_native;
 pub mod tester;
+pub mod totp;
 pub mod transfer;
 pub mod tunnel;
+pub mod tunnel_manager;
 pub mod util;
 
 pub use common::id;
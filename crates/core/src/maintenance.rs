@@ -0,0 +1,293 @@
+//! Maintenance windows: time spans per group, tag, or profile during which connections to
+//! critical profiles are blocked, or need an override reason that is logged as a
+//! `maintenance_override` op log entry (and so reaches `audit.file`).
+//!
+//! Spans use the `policy.danger` freeze window syntax (`sat 22:00..sun 06:00` weekly, or
+//! `2026-12-24..2026-12-26`) read in `ui.timezone`. Profiles below critical are not
+//! affected; use a `policy.danger` freeze window for those.
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+
+use crate::error::{CoreError, Result};
+use crate::oplog::{self, OpLogEntry};
+use crate::policy::TimeWindow;
+use crate::profile::{DangerLevel, Profile};
+use crate::schedule::ScheduleTarget;
+use crate::settings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowMode {
+    /// No connections at all.
+    Block,
+    /// Connections go ahead once a reason is given.
+    Reason,
+}
+
+impl WindowMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Block => "block",
+            Self::Reason => "reason",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "block" => Ok(Self::Block),
+            "reason" => Ok(Self::Reason),
+            other => Err(CoreError::InvalidSetting(format!(
+                "unknown maintenance window mode '{other}' (expected block or reason)"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceWindow {
+    pub id: i64,
+    /// `<profile_id>`, `group:<name>`, or `tag:<a>,<b>`.
+    pub target: String,
+    pub span: String,
+    pub mode: WindowMode,
+    pub note: Option<String>,
+    pub created_at: i64,
+}
+
+impl MaintenanceWindow {
+    /// The error an action stops with when this window blocks it.
+    pub fn denied(&self, profile: &Profile, op: &str) -> CoreError {
+        CoreError::PolicyDenied(format!(
+            "{op} on critical profile {} blocked by maintenance window {} ({}{})",
+            profile.profile_id,
+            self.id,
+            self.span,
+            self.note
+                .as_deref()
+                .map(|note| format!(": {note}"))
+                .unwrap_or_default()
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewMaintenanceWindow {
+    pub target: ScheduleTarget,
+    pub span: String,
+    pub mode: WindowMode,
+    pub note: Option<String>,
+}
+
+pub fn add(conn: &Connection, new: NewMaintenanceWindow, now_ms: i64) -> Result<MaintenanceWindow> {
+    TimeWindow::parse(&new.span)?;
+    conn.execute(
+        "INSERT INTO maintenance_windows (target, span, mode, note, created_at) \
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            new.target.to_string(),
+            new.span.trim(),
+            new.mode.as_str(),
+            new.note,
+            now_ms
+        ],
+    )?;
+    let id = conn.last_insert_rowid();
+    get(conn, id)?.ok_or_else(|| CoreError::NotFound(format!("maintenance window not found: {id}")))
+}
+
+pub fn list(conn: &Connection) -> Result<Vec<MaintenanceWindow>> {
+    let mut stmt = conn.prepare(&format!("{SELECT_WINDOW} ORDER BY id"))?;
+    let mut rows = stmt.query([])?;
+    let mut windows = Vec::new();
+    while let Some(row) = rows.next()? {
+        windows.push(window_from_row(row)?);
+    }
+    Ok(windows)
+}
+
+pub fn get(conn: &Connection, id: i64) -> Result<Option<MaintenanceWindow>> {
+    conn.query_row(
+        &format!("{SELECT_WINDOW} WHERE id = ?1"),
+        params![id],
+        |row| Ok(window_from_row(row)),
+    )
+    .optional()?
+    .transpose()
+}
+
+pub fn remove(conn: &Connection, id: i64) -> Result<()> {
+    if conn.execute("DELETE FROM maintenance_windows WHERE id = ?1", params![id])? == 0 {
+        return Err(CoreError::NotFound(format!(
+            "maintenance window not found: {id}"
+        )));
+    }
+    Ok(())
+}
+
+/// The window in force for `profile` at `now_ms`, if it is critical and one covers it;
+/// a blocking window wins over one that asks for a reason.
+pub fn active_for(
+    conn: &Connection,
+    profile: &Profile,
+    now_ms: i64,
+) -> Result<Option<MaintenanceWindow>> {
+    if profile.danger_level != DangerLevel::Critical {
+        return Ok(None);
+    }
+    let offset = settings::get_display_offset(conn)?;
+    let mut active = None;
+    for window in list(conn)? {
+        if !ScheduleTarget::parse(&window.target)?.matches(profile)
+            || !TimeWindow::parse(&window.span)?.contains(now_ms, offset)
+        {
+            continue;
+        }
+        if window.mode == WindowMode::Block {
+            return Ok(Some(window));
+        }
+        active.get_or_insert(window);
+    }
+    Ok(active)
+}
+
+/// Logs that `op` on `profile` went ahead inside `window` for `reason`.
+pub fn record_override(
+    conn: &Connection,
+    profile: &Profile,
+    window: &MaintenanceWindow,
+    op: &str,
+    reason: &str,
+) -> Result<i64> {
+    oplog::log_operation(
+        conn,
+        OpLogEntry {
+            op: "maintenance_override".to_string(),
+            profile_id: Some(profile.profile_id.clone()),
+            client_used: None,
+            ok: true,
+            exit_code: None,
+            duration_ms: None,
+            meta_json: Some(serde_json::json!({
+                "window_id": window.id,
+                "span": window.span,
+                "action": op,
+                "reason": reason,
+            })),
+        },
+    )
+}
+
+const SELECT_WINDOW: &str =
+    "SELECT id, target, span, mode, note, created_at FROM maintenance_windows";
+
+fn window_from_row(row: &Row<'_>) -> Result<MaintenanceWindow> {
+    Ok(MaintenanceWindow {
+        id: row.get("id")?,
+        target: row.get("target")?,
+        span: row.get("span")?,
+        mode: WindowMode::parse(&row.get::<_, String>("mode")?)?,
+        note: row.get("note")?,
+        created_at: row.get("created_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_in_memory;
+    use crate::profile::{NewProfile, ProfileStore, ProfileType};
+
+    // 2026-10-17 (a Saturday) 23:00 UTC.
+    const SATURDAY_NIGHT_MS: i64 = 1_792_278_000_000;
+
+    #[test]
+    fn windows_cover_critical_profiles_in_their_target_and_block_wins() {
+        let store = ProfileStore::new(init_in_memory().unwrap());
+        let insert = |profile_id: &str, danger_level| {
+            store
+                .insert(NewProfile {
+                    profile_id: Some(profile_id.to_string()),
+                    name: profile_id.to_string(),
+                    profile_type: ProfileType::Ssh,
+                    host: format!("{profile_id}.example.com"),
+                    port: 22,
+                    user: "ops".to_string(),
+                    danger_level,
+                    group: Some("billing".to_string()),
+                    tags: vec!["db".to_string()],
+                    note: None,
+                    initial_send: None,
+                    client_overrides: None,
+                })
+                .unwrap()
+        };
+        let db = insert("p_db", DangerLevel::Critical);
+        let staging = insert("p_staging", DangerLevel::High);
+        let conn = store.conn();
+
+        let reason = add(
+            conn,
+            NewMaintenanceWindow {
+                target: ScheduleTarget::parse("group:Billing").unwrap(),
+                span: "sat 22:00..sun 06:00".to_string(),
+                mode: WindowMode::Reason,
+                note: None,
+            },
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            active_for(conn, &db, SATURDAY_NIGHT_MS)
+                .unwrap()
+                .unwrap()
+                .id,
+            reason.id
+        );
+        assert!(active_for(conn, &db, SATURDAY_NIGHT_MS - 2 * 3_600_000)
+            .unwrap()
+            .is_none());
+        assert!(
+            active_for(conn, &staging, SATURDAY_NIGHT_MS)
+                .unwrap()
+                .is_none(),
+            "only critical profiles"
+        );
+
+        let block = add(
+            conn,
+            NewMaintenanceWindow {
+                target: ScheduleTarget::parse("tag:db").unwrap(),
+                span: "2026-10-17..2026-10-18".to_string(),
+                mode: WindowMode::Block,
+                note: Some("storage migration".to_string()),
+            },
+            0,
+        )
+        .unwrap();
+        let active = active_for(conn, &db, SATURDAY_NIGHT_MS).unwrap().unwrap();
+        assert_eq!(active.id, block.id);
+        assert!(active
+            .denied(&db, "connect")
+            .to_string()
+            .ends_with("(2026-10-17..2026-10-18: storage migration)"));
+
+        record_override(conn, &db, &reason, "connect", "INC-7 replica lag").unwrap();
+        let logged = oplog::list_operations(conn, &oplog::OpLogQuery::default()).unwrap();
+        assert_eq!(logged[0].op, "maintenance_override");
+
+        remove(conn, block.id).unwrap();
+        assert!(remove(conn, block.id).is_err());
+        assert!(add(
+            conn,
+            NewMaintenanceWindow {
+                target: ScheduleTarget::parse("group:billing").unwrap(),
+                span: "whenever".to_string(),
+                mode: WindowMode::Block,
+                note: None,
+            },
+            0,
+        )
+        .is_err());
+    }
+}
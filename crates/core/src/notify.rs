@@ -0,0 +1,428 @@
+//! Notifications when runs finish: a desktop notification (Windows toast, macOS
+//! Notification Center, or `notify-send`), a JSON POST to `notify.webhook_url`, or a
+//! Slack-compatible `{"text": ...}` POST to `notify.slack_url`.
+//!
+//! `notify.success` and `notify.failure` list the channels for each outcome, per profile
+//! if needed; both are empty by default. Webhooks are sent with `curl`, reading the URL
+//! and body from stdin so webhook tokens stay out of process listings. Delivery failures
+//! are reported but never fail the run they describe.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use rusqlite::Connection;
+use serde::Serialize;
+use serde_json::{json, Value};
+use wait_timeout::ChildExt;
+
+use crate::cmdset_runner::CmdSetRunResult;
+use crate::error::{CoreError, Result};
+use crate::settings::{self, SettingScope};
+use crate::util;
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+/// PowerShell's app id; toasts from an unregistered id are dropped on some Windows builds.
+const WINDOWS_TOAST_APP_ID: &str =
+    r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyChannel {
+    Desktop,
+    Webhook,
+    Slack,
+}
+
+impl NotifyChannel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Desktop => "desktop",
+            Self::Webhook => "webhook",
+            Self::Slack => "slack",
+        }
+    }
+
+    /// Parses a comma-separated channel list; `none` is an explicit empty list, so a
+    /// profile can opt out of a global setting.
+    pub fn parse_list(raw: &str) -> Result<Vec<Self>> {
+        let mut channels = Vec::new();
+        for entry in raw
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let channel = match entry.to_ascii_lowercase().as_str() {
+                "none" => continue,
+                "desktop" => Self::Desktop,
+                "webhook" => Self::Webhook,
+                "slack" => Self::Slack,
+                _ => {
+                    return Err(CoreError::InvalidSetting(format!(
+                        "unknown notification channel '{entry}' (expected desktop, webhook, slack, or none)"
+                    )))
+                }
+            };
+            if !channels.contains(&channel) {
+                channels.push(channel);
+            }
+        }
+        Ok(channels)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifySeverity {
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    /// `run`, `bulk_run`, or `schedule`.
+    pub event: String,
+    pub severity: NotifySeverity,
+    pub title: String,
+    pub text: String,
+    pub profile_id: Option<String>,
+    pub cmdset_id: Option<String>,
+}
+
+impl Notification {
+    /// A single CommandSet run; `outcome` is the run or the error that stopped it.
+    pub fn run(
+        profile_id: &str,
+        cmdset_id: &str,
+        outcome: std::result::Result<&CmdSetRunResult, String>,
+    ) -> Self {
+        let (severity, text) = match outcome {
+            Ok(result) if result.ok => (
+                NotifySeverity::Success,
+                format!(
+                    "{} step(s) ok in {}ms",
+                    result.steps.len(),
+                    result.duration_ms
+                ),
+            ),
+            Ok(result) => (
+                NotifySeverity::Failure,
+                format!("failed with exit code {}", result.exit_code),
+            ),
+            Err(err) => (NotifySeverity::Failure, err),
+        };
+        Self {
+            event: "run".to_string(),
+            severity,
+            title: format!(
+                "td run {cmdset_id} on {profile_id}: {}",
+                severity_word(severity)
+            ),
+            text,
+            profile_id: Some(profile_id.to_string()),
+            cmdset_id: Some(cmdset_id.to_string()),
+        }
+    }
+
+    /// A run across several profiles.
+    pub fn summary(event: &str, cmdset_id: &str, ok_count: usize, failed: &[String]) -> Self {
+        let severity = if failed.is_empty() {
+            NotifySeverity::Success
+        } else {
+            NotifySeverity::Failure
+        };
+        let mut text = format!("{ok_count} ok, {} failed", failed.len());
+        if !failed.is_empty() {
+            text.push_str(&format!(": {}", failed.join(", ")));
+        }
+        Self {
+            event: event.to_string(),
+            severity,
+            title: format!("td {event} {cmdset_id}: {}", severity_word(severity)),
+            text,
+            profile_id: None,
+            cmdset_id: Some(cmdset_id.to_string()),
+        }
+    }
+
+    /// The generic webhook body.
+    pub fn webhook_payload(&self) -> Value {
+        json!({
+            "source": "teradock",
+            "ts": util::now_ms(),
+            "event": self.event,
+            "severity": self.severity,
+            "title": self.title,
+            "text": self.text,
+            "profile_id": self.profile_id,
+            "cmdset_id": self.cmdset_id,
+        })
+    }
+
+    /// A Slack incoming-webhook body; Mattermost and Teams' Slack-compatible hooks accept
+    /// it too.
+    pub fn slack_payload(&self) -> Value {
+        let icon = match self.severity {
+            NotifySeverity::Success => ":white_check_mark:",
+            NotifySeverity::Failure => ":x:",
+        };
+        json!({ "text": format!("{icon} *{}*\n{}", self.title, self.text) })
+    }
+}
+
+fn severity_word(severity: NotifySeverity) -> &'static str {
+    match severity {
+        NotifySeverity::Success => "ok",
+        NotifySeverity::Failure => "FAILED",
+    }
+}
+
+/// The channels and endpoints configured for one notification.
+#[derive(Debug, Clone, Default)]
+pub struct Notifier {
+    channels: Vec<NotifyChannel>,
+    webhook_url: Option<String>,
+    slack_url: Option<String>,
+}
+
+impl Notifier {
+    /// Resolves `notify.success` or `notify.failure` for the profile (global and env
+    /// scopes when `profile_id` is `None`).
+    pub fn for_event(
+        conn: &Connection,
+        profile_id: Option<&str>,
+        severity: NotifySeverity,
+    ) -> Result<Self> {
+        let scope = match profile_id {
+            Some(profile_id) => SettingScope::Profile(profile_id.to_string()),
+            None => SettingScope::Global,
+        };
+        let key = match severity {
+            NotifySeverity::Success => settings::NOTIFY_SUCCESS_KEY,
+            NotifySeverity::Failure => settings::NOTIFY_FAILURE_KEY,
+        };
+        let channels = settings::get_notify_channels(conn, &scope, key)?;
+        if channels.is_empty() {
+            return Ok(Self::default());
+        }
+        Ok(Self {
+            channels,
+            webhook_url: settings::get_setting_resolved(
+                conn,
+                &scope,
+                settings::NOTIFY_WEBHOOK_URL_KEY,
+            )?,
+            slack_url: settings::get_setting_resolved(
+                conn,
+                &scope,
+                settings::NOTIFY_SLACK_URL_KEY,
+            )?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// Sends `notification` on every channel; returns one message per channel that failed.
+    pub fn send(&self, notification: &Notification) -> Vec<String> {
+        let mut failures = Vec::new();
+        for channel in &self.channels {
+            let sent = match channel {
+                NotifyChannel::Desktop => send_desktop(notification),
+                NotifyChannel::Webhook => match &self.webhook_url {
+                    Some(url) => post_json(url, &notification.webhook_payload()),
+                    None => Err(missing_url(settings::NOTIFY_WEBHOOK_URL_KEY)),
+                },
+                NotifyChannel::Slack => match &self.slack_url {
+                    Some(url) => post_json(url, &notification.slack_payload()),
+                    None => Err(missing_url(settings::NOTIFY_SLACK_URL_KEY)),
+                },
+            };
+            if let Err(err) = sent {
+                tracing::warn!("{} notification failed: {err}", channel.as_str());
+                failures.push(format!("{} notification failed: {err}", channel.as_str()));
+            }
+        }
+        failures
+    }
+}
+
+/// Sends `notification` to the channels configured for its severity.
+pub fn dispatch(conn: &Connection, notification: &Notification) -> Result<Vec<String>> {
+    let notifier = Notifier::for_event(
+        conn,
+        notification.profile_id.as_deref(),
+        notification.severity,
+    )?;
+    Ok(notifier.send(notification))
+}
+
+fn missing_url(key: &str) -> CoreError {
+    CoreError::InvalidSetting(format!("{key} is not set"))
+}
+
+fn post_json(url: &str, payload: &Value) -> Result<()> {
+    let config = curl_config(url, &payload.to_string());
+    let mut command = Command::new("curl");
+    command
+        .args(["--silent", "--show-error", "--fail", "--config", "-"])
+        .arg("--max-time")
+        .arg(SEND_TIMEOUT.as_secs().to_string());
+    run_with_input(command, &config, "curl")
+}
+
+/// A curl config (read from stdin) that POSTs `body` as JSON to `url`.
+fn curl_config(url: &str, body: &str) -> String {
+    format!(
+        "url = \"{}\"\nrequest = \"POST\"\nheader = \"Content-Type: application/json\"\ndata-binary = \"{}\"\n",
+        curl_quote(url),
+        curl_quote(body)
+    )
+}
+
+/// Escapes a value for a double-quoted curl config parameter.
+fn curl_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted
+}
+
+/// Title and text go through the environment, never into a script or shell command.
+fn send_desktop(notification: &Notification) -> Result<()> {
+    let mut command = if cfg!(windows) {
+        let script = format!(
+            "[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null; \
+             $template = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+             $text = $template.GetElementsByTagName('text'); \
+             $text.Item(0).AppendChild($template.CreateTextNode($env:TD_NOTIFY_TITLE)) > $null; \
+             $text.Item(1).AppendChild($template.CreateTextNode($env:TD_NOTIFY_TEXT)) > $null; \
+             [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('{WINDOWS_TOAST_APP_ID}').Show([Windows.UI.Notifications.ToastNotification]::new($template))"
+        );
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-NonInteractive", "-Command", &script]);
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.args([
+            "-e",
+            "display notification (system attribute \"TD_NOTIFY_TEXT\") with title (system attribute \"TD_NOTIFY_TITLE\")",
+        ]);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command
+            .arg("--app-name=TeraDock")
+            .arg("--")
+            .arg(&notification.title)
+            .arg(&notification.text);
+        command
+    };
+    command
+        .env("TD_NOTIFY_TITLE", &notification.title)
+        .env("TD_NOTIFY_TEXT", &notification.text);
+    run_with_input(command, "", "desktop notifier")
+}
+
+fn run_with_input(mut command: Command, input: &str, label: &str) -> Result<()> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| CoreError::InvalidSetting(format!("cannot start {label}: {err}")))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let Some(status) = child.wait_timeout(SEND_TIMEOUT + Duration::from_secs(2))? else {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(CoreError::Io(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!("{label} timed out"),
+        )));
+    };
+    if status.success() {
+        return Ok(());
+    }
+    let output = child.wait_with_output()?;
+    Err(CoreError::Io(std::io::Error::other(format!(
+        "{label} exited with {}: {}",
+        status.code().unwrap_or(-1),
+        String::from_utf8_lossy(&output.stderr).trim()
+    ))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_in_memory;
+
+    #[test]
+    fn builds_payloads_and_resolves_channels_per_severity() {
+        let failed = Notification::summary("bulk_run", "c_check", 3, &["p_db".to_string()]);
+        assert_eq!(failed.severity, NotifySeverity::Failure);
+        assert_eq!(failed.text, "3 ok, 1 failed: p_db");
+        assert_eq!(
+            failed.slack_payload()["text"],
+            ":x: *td bulk_run c_check: FAILED*\n3 ok, 1 failed: p_db"
+        );
+        let payload = failed.webhook_payload();
+        assert_eq!(payload["severity"], "failure");
+        assert_eq!(payload["cmdset_id"], "c_check");
+
+        assert_eq!(
+            curl_config("https://hooks.example/x?a=\"b\"", "{\"t\":\"a\\nb\"}"),
+            "url = \"https://hooks.example/x?a=\\\"b\\\"\"\nrequest = \"POST\"\n\
+             header = \"Content-Type: application/json\"\n\
+             data-binary = \"{\\\"t\\\":\\\"a\\\\nb\\\"}\"\n"
+        );
+
+        let conn = init_in_memory().unwrap();
+        assert!(
+            Notifier::for_event(&conn, Some("p_web"), NotifySeverity::Failure)
+                .unwrap()
+                .is_empty(),
+            "off by default"
+        );
+        settings::set_setting(&conn, settings::NOTIFY_FAILURE_KEY, "slack, desktop").unwrap();
+        settings::set_setting_scoped(
+            &conn,
+            &SettingScope::Profile("p_lab".to_string()),
+            settings::NOTIFY_FAILURE_KEY,
+            "none",
+        )
+        .unwrap();
+        let notifier = Notifier::for_event(&conn, Some("p_web"), NotifySeverity::Failure).unwrap();
+        assert_eq!(
+            notifier.channels,
+            vec![NotifyChannel::Slack, NotifyChannel::Desktop]
+        );
+        assert!(
+            Notifier::for_event(&conn, Some("p_lab"), NotifySeverity::Failure)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(Notifier::for_event(&conn, None, NotifySeverity::Success)
+            .unwrap()
+            .is_empty());
+
+        let slack_only = Notifier {
+            channels: vec![NotifyChannel::Slack],
+            ..Notifier::default()
+        };
+        assert_eq!(
+            slack_only.send(&failed),
+            vec!["slack notification failed: invalid setting: notify.slack_url is not set"]
+        );
+        assert!(NotifyChannel::parse_list("pager").is_err());
+    }
+}
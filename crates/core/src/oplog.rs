@@ -1,11 +1,19 @@
-use crate::error::Result;
-use crate::util::now_ms;
+use crate::audit;
+use crate::db;
+use crate::error::{CoreError, Result};
+use crate::settings;
+use crate::util::{self, now_ms};
 use rusqlite::{params, Connection};
 use serde::Serialize;
 use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use tracing::warn;
 
 pub const SSH_SESSION_OP: &str = "ssh_session";
 
+/// Longest handoff note accepted by [`set_note`], in characters.
+pub const MAX_NOTE_CHARS: usize = 1000;
+
 #[derive(Debug, Clone)]
 pub struct OpLogEntry {
     pub op: String,
@@ -33,29 +41,55 @@ pub struct RecentSshSession {
     pub duration_ms: Option<i64>,
 }
 
-pub fn log_operation(conn: &Connection, entry: OpLogEntry) -> Result<()> {
+/// How often a profile shows up in `op_logs`. `frecency` weights every logged operation
+/// by its age: 100 within 4 days, 70 within 2 weeks, 50 within a month, 30 within three
+/// months, and 10 for anything older.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProfileUsage {
+    pub uses: u64,
+    pub frecency: u64,
+}
+
+const DAY_MS: i64 = 86_400_000;
+
+/// Records a finished operation, with the OS user and configured operator running it, and
+/// returns its id.
+pub fn log_operation(conn: &Connection, entry: OpLogEntry) -> Result<i64> {
     let meta = entry
         .meta_json
         .as_ref()
         .map(serde_json::to_string)
         .transpose()?;
-    conn.execute(
-        r#"
-        INSERT INTO op_logs (ts, op, profile_id, client_used, ok, exit_code, duration_ms, meta_json)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
-        "#,
-        params![
-            now_ms(),
-            entry.op,
-            entry.profile_id,
-            entry.client_used,
-            entry.ok as i32,
-            entry.exit_code,
-            entry.duration_ms,
-            meta
-        ],
-    )?;
-    Ok(())
+    let ts = now_ms();
+    let os_user = util::os_user();
+    let operator = settings::get_operator(conn)?;
+    // The operation already happened, so wait out a competing writer rather than lose it.
+    let id = db::retry_busy(|| {
+        conn.execute(
+            r#"
+            INSERT INTO op_logs (ts, op, profile_id, client_used, ok, exit_code, duration_ms, meta_json, os_user, operator)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            "#,
+            params![
+                ts,
+                entry.op,
+                entry.profile_id,
+                entry.client_used,
+                entry.ok as i32,
+                entry.exit_code,
+                entry.duration_ms,
+                meta,
+                os_user,
+                operator
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    })?;
+    // The operation is logged already; a broken audit file must not fail it.
+    if let Err(err) = audit::append(conn, id) {
+        warn!("audit.file append failed: {err}");
+    }
+    Ok(id)
 }
 
 pub fn recent_ssh_sessions(conn: &Connection, limit: usize) -> Result<Vec<RecentSshSession>> {
@@ -109,6 +143,244 @@ pub fn recent_ssh_sessions(conn: &Connection, limit: usize) -> Result<Vec<Recent
     Ok(sessions)
 }
 
+pub fn profile_usage(conn: &Connection, now: i64) -> Result<HashMap<String, ProfileUsage>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT profile_id,
+               COUNT(*) AS uses,
+               SUM(CASE
+                   WHEN ts >= ?1 - 4 * ?2 THEN 100
+                   WHEN ts >= ?1 - 14 * ?2 THEN 70
+                   WHEN ts >= ?1 - 31 * ?2 THEN 50
+                   WHEN ts >= ?1 - 90 * ?2 THEN 30
+                   ELSE 10
+               END) AS frecency
+        FROM op_logs
+        WHERE profile_id IS NOT NULL
+        GROUP BY profile_id
+        "#,
+    )?;
+    let mut rows = stmt.query(params![now, DAY_MS])?;
+    let mut usage = HashMap::new();
+    while let Some(row) = rows.next()? {
+        usage.insert(
+            row.get::<_, String>("profile_id")?,
+            ProfileUsage {
+                uses: row.get::<_, i64>("uses")? as u64,
+                frecency: row.get::<_, i64>("frecency")? as u64,
+            },
+        );
+    }
+    Ok(usage)
+}
+
+/// One `op_logs` row as stored.
+#[derive(Debug, Clone, Serialize)]
+pub struct OpLogRecord {
+    pub id: i64,
+    pub ts: i64,
+    pub op: String,
+    pub profile_id: Option<String>,
+    pub client_used: Option<String>,
+    pub ok: bool,
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<i64>,
+    pub meta: Option<Value>,
+    /// Handoff note added afterwards with `td log note`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// OS account that ran the operation; unset on entries logged before v16.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os_user: Option<String>,
+    /// `operator.name` (or `TD_OPERATOR`) when the operation was logged.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
+}
+
+impl OpLogRecord {
+    /// Who ran the operation: the configured operator, else the OS user.
+    pub fn who(&self) -> Option<&str> {
+        self.operator.as_deref().or(self.os_user.as_deref())
+    }
+}
+
+/// Filters for [`list_operations`]; unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct OpLogQuery {
+    pub profile_id: Option<String>,
+    pub op: Option<String>,
+    pub ok: Option<bool>,
+    pub since_ms: Option<i64>,
+    pub until_ms: Option<i64>,
+    /// Matches the operator name or the OS user.
+    pub operator: Option<String>,
+    /// 0 returns every match.
+    pub limit: usize,
+    /// Matches to skip first, for paging.
+    pub offset: usize,
+}
+
+/// Logged operations matching `query`, newest first.
+pub fn list_operations(conn: &Connection, query: &OpLogQuery) -> Result<Vec<OpLogRecord>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT id, ts, op, profile_id, client_used, ok, exit_code, duration_ms, meta_json, note,
+               os_user, operator
+        FROM op_logs
+        WHERE (?1 IS NULL OR profile_id = ?1)
+          AND (?2 IS NULL OR op = ?2)
+          AND (?3 IS NULL OR ok = ?3)
+          AND (?4 IS NULL OR ts >= ?4)
+          AND (?5 IS NULL OR ts < ?5)
+          AND (?7 IS NULL OR operator = ?7 OR os_user = ?7)
+        ORDER BY ts DESC, id DESC
+        LIMIT ?6 OFFSET ?8
+        "#,
+    )?;
+    let limit = if query.limit == 0 {
+        -1
+    } else {
+        query.limit as i64
+    };
+    let mut rows = stmt.query(params![
+        query.profile_id,
+        query.op,
+        query.ok.map(i32::from),
+        query.since_ms,
+        query.until_ms,
+        limit,
+        query.operator,
+        query.offset as i64
+    ])?;
+    let mut records = Vec::new();
+    while let Some(row) = rows.next()? {
+        records.push(deserialize_record(row)?);
+    }
+    Ok(records)
+}
+
+pub fn get_operation(conn: &Connection, id: i64) -> Result<Option<OpLogRecord>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT id, ts, op, profile_id, client_used, ok, exit_code, duration_ms, meta_json, note,
+               os_user, operator
+        FROM op_logs
+        WHERE id = ?1
+        "#,
+    )?;
+    let mut rows = stmt.query(params![id])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(deserialize_record(row)?)),
+        None => Ok(None),
+    }
+}
+
+fn deserialize_record(row: &rusqlite::Row<'_>) -> Result<OpLogRecord> {
+    let meta: Option<String> = row.get("meta_json")?;
+    Ok(OpLogRecord {
+        id: row.get("id")?,
+        ts: row.get("ts")?,
+        op: row.get("op")?,
+        profile_id: row.get("profile_id")?,
+        client_used: row.get("client_used")?,
+        ok: row.get::<_, i64>("ok")? != 0,
+        exit_code: row.get("exit_code")?,
+        duration_ms: row.get("duration_ms")?,
+        meta: meta.as_deref().map(serde_json::from_str).transpose()?,
+        note: row.get("note")?,
+        os_user: row.get("os_user")?,
+        operator: row.get("operator")?,
+    })
+}
+
+/// Attaches a handoff note ("restarted nginx, watch error rates") to an `op_logs` row,
+/// replacing any earlier note. An empty note removes it.
+pub fn set_note(conn: &Connection, id: i64, note: &str) -> Result<()> {
+    let note = note.trim();
+    if note.chars().count() > MAX_NOTE_CHARS {
+        return Err(CoreError::InvalidSetting(format!(
+            "note is longer than {MAX_NOTE_CHARS} characters"
+        )));
+    }
+    let note = (!note.is_empty()).then_some(note);
+    let updated = db::retry_busy(|| {
+        conn.execute(
+            "UPDATE op_logs SET note = ?1 WHERE id = ?2",
+            params![note, id],
+        )
+        .map_err(Into::into)
+    })?;
+    if updated == 0 {
+        return Err(CoreError::NotFound(format!("op log entry not found: {id}")));
+    }
+    Ok(())
+}
+
+/// Id of the newest `op_logs` row, optionally for one profile.
+pub fn latest_operation_id(conn: &Connection, profile_id: Option<&str>) -> Result<Option<i64>> {
+    let mut stmt = conn.prepare(
+        "SELECT id FROM op_logs WHERE (?1 IS NULL OR profile_id = ?1) ORDER BY ts DESC, id DESC LIMIT 1",
+    )?;
+    let mut rows = stmt.query(params![profile_id])?;
+    Ok(match rows.next()? {
+        Some(row) => Some(row.get(0)?),
+        None => None,
+    })
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct OpCounts {
+    pub ok: usize,
+    pub failed: usize,
+}
+
+/// Operations in one window, for `td log summary` shift handoffs.
+#[derive(Debug, Clone, Serialize)]
+pub struct OpLogSummary {
+    pub since_ms: i64,
+    pub until_ms: i64,
+    pub total: usize,
+    pub failed: usize,
+    pub by_op: BTreeMap<String, OpCounts>,
+    /// Entries with a note, oldest first.
+    pub notes: Vec<OpLogRecord>,
+}
+
+pub fn summarize(conn: &Connection, since_ms: i64, until_ms: i64) -> Result<OpLogSummary> {
+    let records = list_operations(
+        conn,
+        &OpLogQuery {
+            since_ms: Some(since_ms),
+            until_ms: Some(until_ms),
+            ..OpLogQuery::default()
+        },
+    )?;
+    let mut by_op = BTreeMap::<String, OpCounts>::new();
+    for record in &records {
+        let counts = by_op.entry(record.op.clone()).or_default();
+        if record.ok {
+            counts.ok += 1;
+        } else {
+            counts.failed += 1;
+        }
+    }
+    let failed = records.iter().filter(|record| !record.ok).count();
+    let mut notes = records
+        .iter()
+        .filter(|record| record.note.is_some())
+        .cloned()
+        .collect::<Vec<_>>();
+    notes.reverse();
+    Ok(OpLogSummary {
+        since_ms,
+        until_ms,
+        total: records.len(),
+        failed,
+        by_op,
+        notes,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -250,4 +522,214 @@ mod tests {
 
         assert_eq!(recent.len(), 1);
     }
+
+    #[test]
+    fn profile_usage_weights_recent_operations() {
+        let conn = init_in_memory().unwrap();
+        let store = ProfileStore::new(conn);
+        for profile_id in ["p_old", "p_new"] {
+            store
+                .insert(NewProfile {
+                    profile_id: Some(profile_id.into()),
+                    name: profile_id.into(),
+                    profile_type: ProfileType::Ssh,
+                    host: "localhost".into(),
+                    port: 22,
+                    user: "root".into(),
+                    danger_level: DangerLevel::Normal,
+                    group: None,
+                    tags: vec![],
+                    note: None,
+                    initial_send: None,
+                    client_overrides: None,
+                })
+                .unwrap();
+        }
+        let now = 1_000 * DAY_MS;
+        for (ts, profile_id) in [
+            (now - 200 * DAY_MS, "p_old"),
+            (now - 150 * DAY_MS, "p_old"),
+            (now - 100 * DAY_MS, "p_old"),
+            (now - DAY_MS, "p_new"),
+        ] {
+            store
+                .conn()
+                .execute(
+                    "INSERT INTO op_logs (ts, op, profile_id, ok) VALUES (?1, 'run', ?2, 1)",
+                    params![ts, profile_id],
+                )
+                .unwrap();
+        }
+
+        let usage = profile_usage(store.conn(), now).unwrap();
+
+        assert_eq!(
+            usage["p_old"],
+            ProfileUsage {
+                uses: 3,
+                frecency: 30
+            }
+        );
+        assert_eq!(
+            usage["p_new"],
+            ProfileUsage {
+                uses: 1,
+                frecency: 100
+            }
+        );
+    }
+
+    #[test]
+    fn notes_show_up_in_the_summary() {
+        let conn = init_in_memory().unwrap();
+        for (ts, op, ok) in [(100, "run", 1), (200, "push", 0), (300, "run", 1)] {
+            conn.execute(
+                "INSERT INTO op_logs (ts, op, ok) VALUES (?1, ?2, ?3)",
+                params![ts, op, ok],
+            )
+            .unwrap();
+        }
+        let latest = latest_operation_id(&conn, None).unwrap().unwrap();
+        set_note(&conn, latest, "  restarted nginx, watch error rates ").unwrap();
+        set_note(&conn, 1, "first").unwrap();
+        set_note(&conn, 1, "").unwrap();
+        set_note(&conn, 2, "push failed, retry after 18:00").unwrap();
+        assert!(matches!(
+            set_note(&conn, 99, "x"),
+            Err(CoreError::NotFound(_))
+        ));
+        assert!(set_note(&conn, 1, &"x".repeat(MAX_NOTE_CHARS + 1)).is_err());
+
+        let summary = summarize(&conn, 0, 1_000).unwrap();
+        assert_eq!((summary.total, summary.failed), (3, 1));
+        assert_eq!(summary.by_op["run"], OpCounts { ok: 2, failed: 0 });
+        assert_eq!(
+            summary
+                .notes
+                .iter()
+                .map(|record| record.note.as_deref().unwrap())
+                .collect::<Vec<_>>(),
+            vec![
+                "push failed, retry after 18:00",
+                "restarted nginx, watch error rates"
+            ]
+        );
+    }
+
+    #[test]
+    fn records_and_filters_by_operator_or_os_user() {
+        let conn = init_in_memory().unwrap();
+        for (ts, os_user, operator) in [
+            (100, Some("alice"), None),
+            (200, Some("bob"), Some("Bob Ops <bob@example.com>")),
+            (300, None, None),
+        ] {
+            conn.execute(
+                "INSERT INTO op_logs (ts, op, ok, os_user, operator) VALUES (?1, 'run', 1, ?2, ?3)",
+                params![ts, os_user, operator],
+            )
+            .unwrap();
+        }
+        let by = |operator: &str| {
+            list_operations(
+                &conn,
+                &OpLogQuery {
+                    operator: Some(operator.to_string()),
+                    ..OpLogQuery::default()
+                },
+            )
+            .unwrap()
+            .iter()
+            .map(|record| record.ts)
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(by("alice"), vec![100]);
+        assert_eq!(by("bob"), vec![200]);
+        assert_eq!(by("Bob Ops <bob@example.com>"), vec![200]);
+
+        let all = list_operations(&conn, &OpLogQuery::default()).unwrap();
+        assert_eq!(all[1].who(), Some("Bob Ops <bob@example.com>"));
+        assert_eq!(all[2].who(), Some("alice"));
+        assert_eq!(all[0].who(), None);
+
+        settings::set_setting(&conn, settings::OPERATOR_NAME_KEY, " jane@example.com ").unwrap();
+        let id = log_operation(
+            &conn,
+            OpLogEntry {
+                op: "exec".to_string(),
+                profile_id: None,
+                client_used: None,
+                ok: true,
+                exit_code: Some(0),
+                duration_ms: None,
+                meta_json: None,
+            },
+        )
+        .unwrap();
+        let logged = get_operation(&conn, id).unwrap().unwrap();
+        if std::env::var_os("TD_OPERATOR").is_none() {
+            assert_eq!(logged.operator.as_deref(), Some("jane@example.com"));
+        }
+        assert_eq!(logged.os_user, util::os_user());
+    }
+
+    #[test]
+    fn list_operations_filters_newest_first() {
+        let conn = init_in_memory().unwrap();
+        for (ts, op, ok) in [(100, "run", 1), (200, "push", 1), (300, "run", 0)] {
+            conn.execute(
+                "INSERT INTO op_logs (ts, op, ok, meta_json) VALUES (?1, ?2, ?3, '{\"n\":1}')",
+                params![ts, op, ok],
+            )
+            .unwrap();
+        }
+        let runs = list_operations(
+            &conn,
+            &OpLogQuery {
+                op: Some("run".into()),
+                ..OpLogQuery::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            runs.iter().map(|r| r.ts).collect::<Vec<_>>(),
+            vec![300, 100]
+        );
+        assert_eq!(runs[1].meta.as_ref().unwrap()["n"], 1);
+
+        let failed = list_operations(
+            &conn,
+            &OpLogQuery {
+                ok: Some(false),
+                ..OpLogQuery::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(failed.len(), 1);
+        let window = list_operations(
+            &conn,
+            &OpLogQuery {
+                since_ms: Some(150),
+                until_ms: Some(300),
+                limit: 5,
+                ..OpLogQuery::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(window.len(), 1);
+        assert_eq!(window[0].op, "push");
+        let second_page = list_operations(
+            &conn,
+            &OpLogQuery {
+                limit: 2,
+                offset: 2,
+                ..OpLogQuery::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            second_page.iter().map(|r| r.ts).collect::<Vec<_>>(),
+            vec![100]
+        );
+    }
 }
@@ -3,7 +3,7 @@ use std::fmt;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
-use crate::error::{CoreError, Result};
+use crate::error::{CoreError, Result, RunError};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ParserSpec {
@@ -20,15 +20,15 @@ impl ParserSpec {
             _ => {
                 if let Some(rest) = value.strip_prefix("regex:") {
                     if rest.is_empty() {
-                        return Err(CoreError::InvalidCommandSpec(
+                        return Err(CoreError::Run(RunError::InvalidCommandSpec(
                             "regex parser spec missing id".into(),
-                        ));
+                        )));
                     }
                     Ok(Self::Regex(rest.to_string()))
                 } else {
-                    Err(CoreError::InvalidCommandSpec(format!(
+                    Err(CoreError::Run(RunError::InvalidCommandSpec(format!(
                         "unknown parser spec: {value}"
-                    )))
+                    ))))
                 }
             }
         }
@@ -55,9 +55,9 @@ impl ParserType {
     pub fn parse(value: &str) -> Result<Self> {
         match value {
             "regex" => Ok(ParserType::Regex),
-            _ => Err(CoreError::InvalidCommandSpec(format!(
+            _ => Err(CoreError::Run(RunError::InvalidCommandSpec(format!(
                 "unknown parser type: {value}"
-            ))),
+            )))),
         }
     }
 }
@@ -83,11 +83,11 @@ pub fn parse_output(
         ParserSpec::Regex(parser_id) => {
             let definition = parser
                 .filter(|p| p.parser_id == *parser_id)
-                .ok_or_else(|| CoreError::ParserNotFound(parser_id.clone()))?;
+                .ok_or_else(|| CoreError::Run(RunError::ParserNotFound(parser_id.clone())))?;
             if definition.parser_type != ParserType::Regex {
-                return Err(CoreError::InvalidCommandSpec(format!(
+                return Err(CoreError::Run(RunError::InvalidCommandSpec(format!(
                     "parser {parser_id} is not regex"
-                )));
+                ))));
             }
             parse_regex_output(&definition.definition, stdout)
         }
@@ -95,7 +95,8 @@ pub fn parse_output(
 }
 
 fn parse_regex_output(pattern: &str, stdout: &str) -> Result<serde_json::Value> {
-    let regex = Regex::new(pattern).map_err(|err| CoreError::Regex(err.to_string()))?;
+    let regex =
+        Regex::new(pattern).map_err(|err| CoreError::Run(RunError::Regex(err.to_string())))?;
     let mut matches = Vec::new();
     for caps in regex.captures_iter(stdout) {
         let mut entry = serde_json::Map::new();
@@ -1,9 +1,15 @@
 use std::path::PathBuf;
+use std::sync::OnceLock;
 
 use directories::BaseDirs;
 
 use crate::error::{CoreError, Result};
 
+/// Environment variable naming the database file, below `td --db` and `td --context`.
+pub const DB_ENV: &str = "TD_DB";
+
+static DATABASE_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
 pub fn config_dir() -> Result<PathBuf> {
     let dirs = BaseDirs::new().ok_or(CoreError::DirectoryResolution)?;
     let base = if cfg!(windows) {
@@ -29,7 +35,35 @@ pub fn session_logs_dir() -> Result<PathBuf> {
     Ok(dir)
 }
 
+/// Directory for SSH ControlMaster sockets, readable only by the current user.
+pub fn ssh_control_dir() -> Result<PathBuf> {
+    let mut dir = config_dir()?;
+    dir.push("ssh-control");
+    std::fs::create_dir_all(&dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(dir)
+}
+
+/// Points this process at another database file (`td --db`, `td --context`); call once at
+/// startup, before the first connection. Logs, reports, and settings files stay in the
+/// config directory.
+pub fn set_database_path(path: PathBuf) {
+    let _ = DATABASE_OVERRIDE.set(path);
+}
+
+/// The database file: the startup override, else `TD_DB`, else `teradock.db` in the
+/// config directory.
 pub fn database_path() -> Result<PathBuf> {
+    if let Some(path) = DATABASE_OVERRIDE.get() {
+        return Ok(path.clone());
+    }
+    if let Some(path) = std::env::var_os(DB_ENV).filter(|path| !path.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
     let mut dir = config_dir()?;
     dir.push("teradock.db");
     Ok(dir)
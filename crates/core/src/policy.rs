@@ -0,0 +1,453 @@
+//! Per-danger-level policies from the `policy.danger` setting, checked before an action
+//! touches a profile. A policy can require typed confirmation or a recorded reason,
+//! limit which CommandSets run, allow only commands matching a regex list, and block
+//! everything during freeze windows:
+//!
+//! ```json
+//! { "critical": { "reason": true, "cmdsets": ["c_health"],
+//!                 "allow_commands": ["uptime", "df -h"],
+//!                 "freeze": ["fri 18:00..mon 08:00", "2026-12-24..2026-12-26"] } }
+//! ```
+//!
+//! Freeze windows are read in `ui.timezone`. A reason given for an action is logged as a
+//! `policy` op log entry so it shows up in `td log` and the audit export.
+
+use regex::Regex;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+use crate::error::{CoreError, Result};
+use crate::oplog::{self, OpLogEntry};
+use crate::profile::{DangerLevel, Profile};
+use crate::settings::{self, SettingScope};
+
+const MINUTES_PER_WEEK: u32 = 7 * 24 * 60;
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// What is about to happen to a profile.
+#[derive(Debug, Clone, Copy)]
+pub enum PolicyAction<'a> {
+    /// An interactive session, `td tail`, or a session test.
+    Connect,
+    Exec {
+        command: &'a str,
+    },
+    /// A local script piped to the remote shell (`td exec-script`).
+    Script,
+    Run {
+        cmdset_id: &'a str,
+        commands: &'a [String],
+    },
+    Transfer {
+        upload: bool,
+    },
+    Tunnel,
+    ConfigApply,
+}
+
+impl PolicyAction<'_> {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Connect => "connect",
+            Self::Exec { .. } => "exec",
+            Self::Script => "exec_script",
+            Self::Run { .. } => "run",
+            Self::Transfer { upload: true } => "push",
+            Self::Transfer { upload: false } => "pull",
+            Self::Tunnel => "tunnel",
+            Self::ConfigApply => "config_apply",
+        }
+    }
+
+    fn cmdset_id(&self) -> Option<&str> {
+        match self {
+            Self::Run { cmdset_id, .. } => Some(cmdset_id),
+            _ => None,
+        }
+    }
+}
+
+/// The `policy.danger` setting: one optional policy per danger level.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicySet {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normal: Option<DangerPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub high: Option<DangerPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub critical: Option<DangerPolicy>,
+}
+
+impl PolicySet {
+    /// Parses and validates the setting value, compiling every pattern and window.
+    pub fn parse(raw: &str) -> Result<Self> {
+        let set: Self = serde_json::from_str(raw)
+            .map_err(|err| CoreError::InvalidSetting(format!("invalid policy: {err}")))?;
+        for policy in [&set.normal, &set.high, &set.critical]
+            .into_iter()
+            .flatten()
+        {
+            policy.command_patterns()?;
+            policy.freeze_windows()?;
+        }
+        Ok(set)
+    }
+
+    pub fn for_level(&self, level: DangerLevel) -> Option<&DangerPolicy> {
+        match level {
+            DangerLevel::Normal => self.normal.as_ref(),
+            DangerLevel::High => self.high.as_ref(),
+            DangerLevel::Critical => self.critical.as_ref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DangerPolicy {
+    /// Ask for typed confirmation even below `critical`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub confirm: bool,
+    /// Require a reason, recorded in the op log.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub reason: bool,
+    /// The only CommandSets `td run` may use; `None` allows any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cmdsets: Option<Vec<String>>,
+    /// Regexes the whole command must match (each is anchored as `^(?:pattern)$`); `None`
+    /// allows any. Actions whose commands cannot be
+    /// checked (interactive sessions, scripts, uploads, config apply) are then denied.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_commands: Option<Vec<String>>,
+    /// `<day> HH:MM..<day> HH:MM` every week, or `YYYY-MM-DD[ HH:MM]..YYYY-MM-DD[ HH:MM]`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub freeze: Vec<String>,
+}
+
+impl DangerPolicy {
+    fn command_patterns(&self) -> Result<Option<Vec<Regex>>> {
+        self.allow_commands
+            .as_ref()
+            .map(|patterns| {
+                patterns
+                    .iter()
+                    .map(|pattern| {
+                        Regex::new(&format!("^(?:{pattern})$")).map_err(|err| {
+                            CoreError::InvalidSetting(format!(
+                                "invalid allow_commands pattern '{pattern}': {err}"
+                            ))
+                        })
+                    })
+                    .collect()
+            })
+            .transpose()
+    }
+
+    fn freeze_windows(&self) -> Result<Vec<TimeWindow>> {
+        self.freeze
+            .iter()
+            .map(|raw| TimeWindow::parse(raw))
+            .collect()
+    }
+}
+
+/// What the caller still has to do before going ahead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PolicyVerdict {
+    pub confirm: bool,
+    pub reason: bool,
+}
+
+/// The policy for `profile`'s danger level (env and global scopes apply).
+pub fn policy_for(conn: &Connection, profile: &Profile) -> Result<Option<DangerPolicy>> {
+    let scope = SettingScope::Profile(profile.profile_id.clone());
+    let Some(raw) = settings::get_setting_resolved(conn, &scope, settings::POLICY_DANGER_KEY)?
+    else {
+        return Ok(None);
+    };
+    Ok(PolicySet::parse(&raw)?
+        .for_level(profile.danger_level)
+        .cloned())
+}
+
+/// Checks `action` on `profile` at `now_ms`; a denied action is a
+/// [`CoreError::PolicyDenied`].
+pub fn check(
+    conn: &Connection,
+    profile: &Profile,
+    action: &PolicyAction<'_>,
+    now_ms: i64,
+) -> Result<PolicyVerdict> {
+    let Some(policy) = policy_for(conn, profile)? else {
+        return Ok(PolicyVerdict::default());
+    };
+    let denied = |why: String| {
+        Err(CoreError::PolicyDenied(format!(
+            "{} on {} profile {}: {why}",
+            action.as_str(),
+            profile.danger_level,
+            profile.profile_id
+        )))
+    };
+    let offset = settings::get_display_offset(conn)?;
+    for (raw, window) in policy.freeze.iter().zip(policy.freeze_windows()?) {
+        if window.contains(now_ms, offset) {
+            return denied(format!("freeze window {raw}"));
+        }
+    }
+    if let (Some(allowed), Some(cmdset_id)) = (&policy.cmdsets, action.cmdset_id()) {
+        if !allowed.iter().any(|allowed| allowed == cmdset_id) {
+            return denied(format!("CommandSet {cmdset_id} is not in the allowed list"));
+        }
+    }
+    if let Some(patterns) = policy.command_patterns()? {
+        let commands: Vec<&str> = match action {
+            PolicyAction::Exec { command } => vec![command],
+            PolicyAction::Run { commands, .. } => commands.iter().map(String::as_str).collect(),
+            PolicyAction::Transfer { upload: false } | PolicyAction::Tunnel => Vec::new(),
+            _ => return denied("only allowlisted commands may run".to_string()),
+        };
+        if let Some(command) = commands
+            .into_iter()
+            .find(|command| !patterns.iter().any(|pattern| pattern.is_match(command)))
+        {
+            return denied(format!("command not in allow_commands: {command}"));
+        }
+    }
+    Ok(PolicyVerdict {
+        confirm: policy.confirm,
+        reason: policy.reason,
+    })
+}
+
+/// Logs the reason given for `action` on `profile` as a `policy` op log entry.
+pub fn record_reason(
+    conn: &Connection,
+    profile: &Profile,
+    action: &PolicyAction<'_>,
+    reason: &str,
+) -> Result<i64> {
+    oplog::log_operation(
+        conn,
+        OpLogEntry {
+            op: "policy".to_string(),
+            profile_id: Some(profile.profile_id.clone()),
+            client_used: None,
+            ok: true,
+            exit_code: None,
+            duration_ms: None,
+            meta_json: Some(serde_json::json!({
+                "action": action.as_str(),
+                "cmdset_id": action.cmdset_id(),
+                "danger_level": profile.danger_level.to_string(),
+                "reason": reason,
+            })),
+        },
+    )
+}
+
+/// A weekly or dated time span, used by freeze windows and maintenance windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TimeWindow {
+    /// Minutes since Monday 00:00; `start > end` wraps over the weekend.
+    Weekly { start: u32, end: u32 },
+    Dates {
+        start: PrimitiveDateTime,
+        end: PrimitiveDateTime,
+    },
+}
+
+impl TimeWindow {
+    pub(crate) fn parse(raw: &str) -> Result<Self> {
+        let invalid = || {
+            CoreError::InvalidSetting(format!(
+                "invalid time window '{raw}' (expected e.g. fri 18:00..mon 08:00 or 2026-12-24..2026-12-26)"
+            ))
+        };
+        let (start, end) = raw.trim().split_once("..").ok_or_else(invalid)?;
+        if let (Some(start), Some(end)) = (week_minute(start), week_minute(end)) {
+            if start == end {
+                return Err(invalid());
+            }
+            return Ok(Self::Weekly { start, end });
+        }
+        let start = date_time(start, false).ok_or_else(invalid)?;
+        let end = date_time(end, true).ok_or_else(invalid)?;
+        if end <= start {
+            return Err(invalid());
+        }
+        Ok(Self::Dates { start, end })
+    }
+
+    pub(crate) fn contains(&self, now_ms: i64, offset: UtcOffset) -> bool {
+        let Ok(now) = OffsetDateTime::from_unix_timestamp_nanos(i128::from(now_ms) * 1_000_000)
+        else {
+            return false;
+        };
+        let now = now.to_offset(offset);
+        match *self {
+            Self::Weekly { start, end } => {
+                let minute = u32::from(now.weekday().number_days_from_monday()) * 24 * 60
+                    + u32::from(now.hour()) * 60
+                    + u32::from(now.minute());
+                if start <= end {
+                    (start..end).contains(&minute)
+                } else {
+                    minute >= start || minute < end
+                }
+            }
+            Self::Dates { start, end } => {
+                let now = PrimitiveDateTime::new(now.date(), now.time());
+                start <= now && now < end
+            }
+        }
+    }
+}
+
+/// `fri 18:00` as minutes since Monday 00:00.
+fn week_minute(raw: &str) -> Option<u32> {
+    let (day, time) = raw.trim().split_once(' ')?;
+    let day = WEEKDAYS
+        .iter()
+        .position(|name| day.trim().to_ascii_lowercase().starts_with(name))?;
+    let time = clock(time.trim())?;
+    Some(
+        (day as u32 * 24 * 60 + u32::from(time.hour()) * 60 + u32::from(time.minute()))
+            % MINUTES_PER_WEEK,
+    )
+}
+
+/// `YYYY-MM-DD` or `YYYY-MM-DD HH:MM`; a bare end date covers that whole day.
+fn date_time(raw: &str, end: bool) -> Option<PrimitiveDateTime> {
+    let raw = raw.trim();
+    let (date, time) = match raw.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(clock(time.trim())?)),
+        None => (raw, None),
+    };
+    let mut parts = date.splitn(3, '-');
+    let year = parts.next()?.parse().ok()?;
+    let month = Month::try_from(parts.next()?.parse::<u8>().ok()?).ok()?;
+    let day = parts.next()?.parse().ok()?;
+    let date = Date::from_calendar_date(year, month, day).ok()?;
+    match time {
+        Some(time) => Some(PrimitiveDateTime::new(date, time)),
+        None if end => Some(PrimitiveDateTime::new(date.next_day()?, Time::MIDNIGHT)),
+        None => Some(PrimitiveDateTime::new(date, Time::MIDNIGHT)),
+    }
+}
+
+fn clock(raw: &str) -> Option<Time> {
+    let (hour, minute) = raw.split_once(':')?;
+    Time::from_hms(hour.parse().ok()?, minute.parse().ok()?, 0).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_in_memory;
+    use crate::profile::{NewProfile, ProfileStore, ProfileType};
+
+    // 2026-10-16 (a Friday) 19:00 UTC.
+    const FRIDAY_EVENING_MS: i64 = 1_792_177_200_000;
+
+    #[test]
+    fn checks_freeze_windows_cmdsets_and_command_allowlist() {
+        let store = ProfileStore::new(init_in_memory().unwrap());
+        let profile = store
+            .insert(NewProfile {
+                profile_id: Some("p_db".to_string()),
+                name: "db".to_string(),
+                profile_type: ProfileType::Ssh,
+                host: "db.example.com".to_string(),
+                port: 22,
+                user: "ops".to_string(),
+                danger_level: DangerLevel::Critical,
+                group: None,
+                tags: Vec::new(),
+                note: None,
+                initial_send: None,
+                client_overrides: None,
+            })
+            .unwrap();
+        let conn = store.conn();
+        let exec = |command| PolicyAction::Exec { command };
+        assert_eq!(
+            check(conn, &profile, &exec("rm -rf /tmp/x"), FRIDAY_EVENING_MS).unwrap(),
+            PolicyVerdict::default(),
+            "no policy by default"
+        );
+
+        settings::set_setting(
+            conn,
+            settings::POLICY_DANGER_KEY,
+            r#"{"critical": {"reason": true, "cmdsets": ["c_health"],
+                "allow_commands": ["^uptime$", "^df -h$"],
+                "freeze": ["sat 00:00..mon 06:00", "2026-12-24..2026-12-26"]}}"#,
+        )
+        .unwrap();
+        let verdict = check(conn, &profile, &exec("uptime"), FRIDAY_EVENING_MS).unwrap();
+        assert!(verdict.reason && !verdict.confirm);
+        let denied = check(conn, &profile, &exec("rm -rf /tmp/x"), FRIDAY_EVENING_MS)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            denied.contains("not in allow_commands: rm -rf /tmp/x"),
+            "{denied}"
+        );
+        let commands = ["uptime".to_string(), "df -h".to_string()];
+        let run = |cmdset_id| PolicyAction::Run {
+            cmdset_id,
+            commands: &commands,
+        };
+        assert!(check(conn, &profile, &run("c_health"), FRIDAY_EVENING_MS).is_ok());
+        assert!(check(conn, &profile, &run("c_deploy"), FRIDAY_EVENING_MS).is_err());
+        assert!(check(conn, &profile, &PolicyAction::Connect, FRIDAY_EVENING_MS).is_err());
+        assert!(check(
+            conn,
+            &profile,
+            &PolicyAction::Transfer { upload: false },
+            FRIDAY_EVENING_MS
+        )
+        .is_ok());
+
+        let saturday = FRIDAY_EVENING_MS + 6 * 3_600_000;
+        let frozen = check(conn, &profile, &exec("uptime"), saturday)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            frozen.contains("freeze window sat 00:00..mon 06:00"),
+            "{frozen}"
+        );
+        settings::set_setting(conn, settings::DISPLAY_TIMEZONE_KEY, "+09:00").unwrap();
+        assert!(
+            check(conn, &profile, &exec("uptime"), FRIDAY_EVENING_MS).is_err(),
+            "already Saturday in +09:00"
+        );
+
+        let christmas = TimeWindow::parse("2026-12-24..2026-12-26").unwrap();
+        let day_ms = 86_400_000;
+        let dec_26_noon = 1_798_286_400_000;
+        assert!(christmas.contains(dec_26_noon, UtcOffset::UTC));
+        assert!(!christmas.contains(dec_26_noon + day_ms, UtcOffset::UTC));
+        assert!(TimeWindow::parse("2026-12-26..2026-12-24").is_err());
+        assert!(TimeWindow::parse("mon 00:00..mon 00:00").is_err());
+        assert!(PolicySet::parse(r#"{"critical": {"allow_commands": ["("]}}"#).is_err());
+        assert!(PolicySet::parse(r#"{"critical": {"block": true}}"#).is_err());
+
+        settings::set_setting(
+            conn,
+            settings::POLICY_DANGER_KEY,
+            r#"{"critical": {"allow_commands": ["uptime", "df -h"]}}"#,
+        )
+        .unwrap();
+        assert!(check(conn, &profile, &exec("uptime"), FRIDAY_EVENING_MS).is_ok());
+        for chained in ["uptime; rm -rf /", "rm -rf / # uptime", "echo df -h"] {
+            assert!(
+                check(conn, &profile, &exec(chained), FRIDAY_EVENING_MS).is_err(),
+                "{chained}"
+            );
+        }
+    }
+}
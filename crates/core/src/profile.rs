@@ -1,11 +1,11 @@
 use std::fmt;
 
 use common::id::{generate_id, normalize_id, validate_id};
-use rusqlite::{params, Connection, Row};
+use rusqlite::{params, Connection, OptionalExtension, Row};
 use serde::{Deserialize, Serialize};
 
 use crate::doctor::ClientOverrides;
-use crate::error::{CoreError, Result};
+use crate::error::{CoreError, ProfileError, Result};
 use crate::util::now_ms;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -22,7 +22,9 @@ impl ProfileType {
             "ssh" => Ok(Self::Ssh),
             "telnet" => Ok(Self::Telnet),
             "serial" => Ok(Self::Serial),
-            _ => Err(CoreError::NotFound(value.to_string())),
+            _ => Err(CoreError::Profile(ProfileError::InvalidType(
+                value.to_string(),
+            ))),
         }
     }
 }
@@ -37,7 +39,7 @@ impl fmt::Display for ProfileType {
     }
 }
 
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum DangerLevel {
     #[default]
@@ -62,7 +64,9 @@ impl DangerLevel {
             "normal" => Ok(DangerLevel::Normal),
             "high" => Ok(DangerLevel::High),
             "critical" => Ok(DangerLevel::Critical),
-            _ => Err(CoreError::NotFound(value.to_string())),
+            _ => Err(CoreError::Profile(ProfileError::InvalidDangerLevel(
+                value.to_string(),
+            ))),
         }
     }
 }
@@ -84,6 +88,9 @@ pub struct Profile {
     pub created_at: i64,
     pub updated_at: i64,
     pub last_used_at: Option<i64>,
+    /// Set when the profile is archived; archived profiles are hidden from default lists.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived_at: Option<i64>,
 }
 
 #[derive(Debug, Clone)]
@@ -113,6 +120,44 @@ impl NewProfile {
     }
 }
 
+/// A profile removed with `ProfileStore::delete_restorable`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeletedProfile {
+    pub id: i64,
+    pub deleted_at: i64,
+    pub profile: Profile,
+    pub related: DeletedRelations,
+}
+
+/// Rows that go with a profile and are removed by `delete_restorable`, kept so
+/// `restore_deleted` can put them back.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeletedRelations {
+    pub forwards: Vec<SavedForward>,
+    pub jump_profile_id: Option<String>,
+    /// Profiles that used this one as their jump host.
+    pub jump_for: Vec<String>,
+    /// Profile-scoped settings as `(key, value)`.
+    pub settings: Vec<(String, String)>,
+    pub secret_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedForward {
+    pub name: String,
+    pub kind: String,
+    pub listen: String,
+    pub dest: Option<String>,
+}
+
+/// Field overrides applied when cloning a profile; everything else is copied.
+#[derive(Debug, Clone, Default)]
+pub struct CloneProfile {
+    pub profile_id: Option<String>,
+    pub name: Option<String>,
+    pub host: Option<String>,
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct ProfileFilters {
     pub group: Option<String>,
@@ -120,6 +165,7 @@ pub struct ProfileFilters {
     pub profile_type: Option<ProfileType>,
     pub danger: Option<DangerLevel>,
     pub query: Option<String>,
+    pub include_archived: bool,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -137,6 +183,237 @@ pub struct UpdateProfile {
     pub client_overrides: Option<Option<ClientOverrides>>,
 }
 
+/// Applies `+tag` / `-tag` edits to a tag list; bare tags are treated as additions.
+/// Matching is case-insensitive and the original order is preserved.
+pub fn apply_tag_edits(current: &[String], edits: &[String]) -> Vec<String> {
+    let mut tags = current.to_vec();
+    for edit in edits {
+        let edit = edit.trim();
+        if let Some(tag) = edit.strip_prefix('-') {
+            let tag = tag.trim();
+            tags.retain(|existing| !existing.eq_ignore_ascii_case(tag));
+        } else {
+            let tag = edit.strip_prefix('+').unwrap_or(edit).trim();
+            if tag.is_empty()
+                || tags
+                    .iter()
+                    .any(|existing| existing.eq_ignore_ascii_case(tag))
+            {
+                continue;
+            }
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+const CLIENT_OVERRIDE_KEYS: [&str; 5] = ["ssh", "scp", "sftp", "ftp", "telnet"];
+
+/// Applies a JSON merge patch (RFC 7386) to client overrides. `null` removes a key;
+/// returns `None` once no override remains.
+pub fn patch_client_overrides(
+    current: Option<&ClientOverrides>,
+    patch: &serde_json::Value,
+) -> Result<Option<ClientOverrides>> {
+    let patch = patch.as_object().ok_or_else(|| {
+        CoreError::InvalidSetting("client override patch must be a JSON object".to_string())
+    })?;
+    let mut merged = match current {
+        Some(overrides) => match serde_json::to_value(overrides)? {
+            serde_json::Value::Object(map) => map,
+            _ => serde_json::Map::new(),
+        },
+        None => serde_json::Map::new(),
+    };
+    for (key, value) in patch {
+        if !CLIENT_OVERRIDE_KEYS.contains(&key.as_str()) {
+            return Err(CoreError::InvalidSetting(format!(
+                "unknown client override key: {key}"
+            )));
+        }
+        match value {
+            serde_json::Value::Null => {
+                merged.remove(key);
+            }
+            serde_json::Value::String(_) => {
+                merged.insert(key.clone(), value.clone());
+            }
+            _ => {
+                return Err(CoreError::InvalidSetting(format!(
+                    "client override {key} must be a string or null"
+                )))
+            }
+        }
+    }
+    let overrides: ClientOverrides = serde_json::from_value(serde_json::Value::Object(merged))?;
+    let empty = overrides.ssh.is_none()
+        && overrides.scp.is_none()
+        && overrides.sftp.is_none()
+        && overrides.ftp.is_none()
+        && overrides.telnet.is_none();
+    Ok(if empty { None } else { Some(overrides) })
+}
+
+/// The profile fields a bulk edit can change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkEditField {
+    Group,
+    AddTag,
+    RemoveTag,
+    Danger,
+    ClientOverride,
+}
+
+impl BulkEditField {
+    pub const ALL: [BulkEditField; 5] = [
+        BulkEditField::Group,
+        BulkEditField::AddTag,
+        BulkEditField::RemoveTag,
+        BulkEditField::Danger,
+        BulkEditField::ClientOverride,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BulkEditField::Group => "Set group",
+            BulkEditField::AddTag => "Add tag",
+            BulkEditField::RemoveTag => "Remove tag",
+            BulkEditField::Danger => "Set danger level",
+            BulkEditField::ClientOverride => "Set client override",
+        }
+    }
+
+    /// What the value input expects.
+    pub fn input_hint(self) -> &'static str {
+        match self {
+            BulkEditField::Group => "group name (empty clears the group)",
+            BulkEditField::AddTag | BulkEditField::RemoveTag => "tag",
+            BulkEditField::Danger => "normal, high, or critical",
+            BulkEditField::ClientOverride => {
+                "client=path, e.g. ssh=/usr/bin/ssh (client= removes the override)"
+            }
+        }
+    }
+}
+
+/// One change applied to every profile in a bulk edit.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BulkEdit {
+    SetGroup(Option<String>),
+    AddTag(String),
+    RemoveTag(String),
+    SetDanger(DangerLevel),
+    /// JSON merge patch, see [`patch_client_overrides`].
+    PatchClientOverrides(serde_json::Value),
+}
+
+impl BulkEdit {
+    pub fn parse(field: BulkEditField, raw: &str) -> Result<Self> {
+        let value = raw.trim();
+        let required = |what: &str| {
+            if value.is_empty() {
+                Err(CoreError::InvalidSetting(format!(
+                    "{what} must not be empty"
+                )))
+            } else {
+                Ok(value.to_string())
+            }
+        };
+        match field {
+            BulkEditField::Group => Ok(BulkEdit::SetGroup(
+                (!value.is_empty()).then(|| value.to_string()),
+            )),
+            BulkEditField::AddTag => Ok(BulkEdit::AddTag(required("tag")?)),
+            BulkEditField::RemoveTag => Ok(BulkEdit::RemoveTag(required("tag")?)),
+            BulkEditField::Danger => Ok(BulkEdit::SetDanger(DangerLevel::from_str(
+                &value.to_ascii_lowercase(),
+            )?)),
+            BulkEditField::ClientOverride => {
+                let (client, path) = value
+                    .split_once('=')
+                    .ok_or_else(|| CoreError::InvalidSetting("expected client=path".to_string()))?;
+                let client = client.trim().to_ascii_lowercase();
+                let path = path.trim();
+                let value = if path.is_empty() {
+                    serde_json::Value::Null
+                } else {
+                    serde_json::Value::String(path.to_string())
+                };
+                let patch = serde_json::json!({ client: value });
+                // Reject unknown clients before the preview.
+                patch_client_overrides(None, &patch)?;
+                Ok(BulkEdit::PatchClientOverrides(patch))
+            }
+        }
+    }
+
+    fn apply_to(&self, profile: &mut Profile) -> Result<()> {
+        match self {
+            BulkEdit::SetGroup(group) => profile.group = group.clone(),
+            BulkEdit::AddTag(tag) => {
+                profile.tags = apply_tag_edits(&profile.tags, &[format!("+{tag}")]);
+            }
+            BulkEdit::RemoveTag(tag) => {
+                profile.tags = apply_tag_edits(&profile.tags, &[format!("-{tag}")]);
+            }
+            BulkEdit::SetDanger(level) => profile.danger_level = *level,
+            BulkEdit::PatchClientOverrides(patch) => {
+                profile.client_overrides =
+                    patch_client_overrides(profile.client_overrides.as_ref(), patch)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The value of the field this edit touches, for previews.
+    fn describe(&self, profile: &Profile) -> String {
+        let or_none = |value: String| {
+            if value.is_empty() {
+                "(none)".to_string()
+            } else {
+                value
+            }
+        };
+        match self {
+            BulkEdit::SetGroup(_) => or_none(profile.group.clone().unwrap_or_default()),
+            BulkEdit::AddTag(_) | BulkEdit::RemoveTag(_) => or_none(profile.tags.join(",")),
+            BulkEdit::SetDanger(_) => profile.danger_level.to_string(),
+            BulkEdit::PatchClientOverrides(_) => or_none(
+                profile
+                    .client_overrides
+                    .as_ref()
+                    .and_then(|overrides| serde_json::to_string(overrides).ok())
+                    .unwrap_or_default(),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkEditPreview {
+    pub profile_id: String,
+    pub name: String,
+    pub before: String,
+    pub after: String,
+}
+
+impl BulkEditPreview {
+    pub fn changed(&self) -> bool {
+        self.before != self.after
+    }
+}
+
+/// Turns free text into an FTS5 expression: every word must match as a prefix.
+fn fts_match_expression(query: &str) -> Option<String> {
+    let terms = query
+        .split_whitespace()
+        .map(|word| word.replace('"', ""))
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{word}\"*"))
+        .collect::<Vec<_>>();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
 pub struct ProfileStore {
     conn: Connection,
 }
@@ -186,14 +463,15 @@ impl ProfileStore {
         )?;
 
         self.get(&profile_id)?
-            .ok_or_else(|| CoreError::NotFound(profile_id))
+            .ok_or_else(|| CoreError::Profile(ProfileError::NotFound(profile_id)))
     }
 
     pub fn get(&self, profile_id: &str) -> Result<Option<Profile>> {
         let mut stmt = self.conn.prepare(
             r#"
             SELECT profile_id, name, type, host, port, user, danger_level, "group",
-                   tags_json, note, initial_send, client_overrides_json, created_at, updated_at, last_used_at
+                   tags_json, note, initial_send, client_overrides_json, created_at, updated_at, last_used_at,
+                   archived_at
             FROM profiles
             WHERE profile_id = ?1
             "#,
@@ -210,7 +488,8 @@ impl ProfileStore {
         let mut stmt = self.conn.prepare(
             r#"
             SELECT profile_id, name, type, host, port, user, danger_level, "group",
-                   tags_json, note, initial_send, client_overrides_json, created_at, updated_at, last_used_at
+                   tags_json, note, initial_send, client_overrides_json, created_at, updated_at, last_used_at,
+                   archived_at
             FROM profiles
             ORDER BY name ASC
             "#,
@@ -225,6 +504,9 @@ impl ProfileStore {
 
     pub fn list_filtered(&self, filters: &ProfileFilters) -> Result<Vec<Profile>> {
         let mut profiles = self.list()?;
+        if !filters.include_archived {
+            profiles.retain(|p| p.archived_at.is_none());
+        }
         if let Some(group) = &filters.group {
             profiles.retain(|p| match &p.group {
                 Some(g) => g.eq_ignore_ascii_case(group),
@@ -257,10 +539,54 @@ impl ProfileStore {
         Ok(profiles)
     }
 
+    /// Ranked full-text search over name, host, tags, group, and note (FTS5, prefix
+    /// match per word). Profiles that only match as a substring of id/name/host/user are
+    /// appended after the ranked hits, so partial words still find something.
+    pub fn search(&self, filters: &ProfileFilters) -> Result<Vec<Profile>> {
+        let Some(query) = filters.query.as_deref().filter(|q| !q.trim().is_empty()) else {
+            return self.list_filtered(filters);
+        };
+        let ranked_ids = self.fts_ranked_ids(query)?;
+        let substring_matches = self.list_filtered(filters)?;
+        let mut candidates = self.list_filtered(&ProfileFilters {
+            query: None,
+            ..filters.clone()
+        })?;
+        let mut results = Vec::new();
+        for profile_id in &ranked_ids {
+            if let Some(pos) = candidates.iter().position(|p| &p.profile_id == profile_id) {
+                results.push(candidates.swap_remove(pos));
+            }
+        }
+        for profile in substring_matches {
+            if !results.iter().any(|p| p.profile_id == profile.profile_id) {
+                results.push(profile);
+            }
+        }
+        Ok(results)
+    }
+
+    fn fts_ranked_ids(&self, query: &str) -> Result<Vec<String>> {
+        let Some(match_expr) = fts_match_expression(query) else {
+            return Ok(Vec::new());
+        };
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT profile_id FROM profiles_fts
+            WHERE profiles_fts MATCH ?1
+            ORDER BY bm25(profiles_fts, 0.0, 10.0, 5.0, 3.0, 3.0, 1.0)
+            "#,
+        )?;
+        let ids = stmt
+            .query_map([match_expr], |row| row.get::<_, String>(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(ids)
+    }
+
     pub fn update(&self, profile_id: &str, changes: UpdateProfile) -> Result<Profile> {
         let mut profile = self
             .get(profile_id)?
-            .ok_or_else(|| CoreError::NotFound(profile_id.to_string()))?;
+            .ok_or_else(|| CoreError::Profile(ProfileError::NotFound(profile_id.to_string())))?;
 
         if let Some(name) = changes.name {
             profile.name = name;
@@ -339,7 +665,61 @@ impl ProfileStore {
         )?;
 
         self.get(profile_id)?
-            .ok_or_else(|| CoreError::NotFound(profile_id.to_string()))
+            .ok_or_else(|| CoreError::Profile(ProfileError::NotFound(profile_id.to_string())))
+    }
+
+    /// Shows what `edit` would change on each profile without writing anything.
+    pub fn preview_bulk_edit(
+        &self,
+        profile_ids: &[String],
+        edit: &BulkEdit,
+    ) -> Result<Vec<BulkEditPreview>> {
+        profile_ids
+            .iter()
+            .map(|profile_id| {
+                let mut profile = self.get(profile_id)?.ok_or_else(|| {
+                    CoreError::Profile(ProfileError::NotFound(profile_id.to_string()))
+                })?;
+                let before = edit.describe(&profile);
+                edit.apply_to(&mut profile)?;
+                Ok(BulkEditPreview {
+                    profile_id: profile.profile_id.clone(),
+                    name: profile.name.clone(),
+                    before,
+                    after: edit.describe(&profile),
+                })
+            })
+            .collect()
+    }
+
+    /// Applies `edit` to every profile in one transaction; nothing is written if any
+    /// profile fails. Returns how many profiles changed.
+    pub fn apply_bulk_edit(&self, profile_ids: &[String], edit: &BulkEdit) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut changed = 0;
+        for profile_id in profile_ids {
+            let mut profile = self.get(profile_id)?.ok_or_else(|| {
+                CoreError::Profile(ProfileError::NotFound(profile_id.to_string()))
+            })?;
+            let before = edit.describe(&profile);
+            edit.apply_to(&mut profile)?;
+            if edit.describe(&profile) == before {
+                continue;
+            }
+            self.update(
+                profile_id,
+                UpdateProfile {
+                    danger_level: Some(profile.danger_level),
+                    group: Some(profile.group),
+                    tags: Some(profile.tags),
+                    client_overrides: Some(profile.client_overrides),
+                    ..UpdateProfile::default()
+                },
+            )?;
+            changed += 1;
+        }
+        tx.commit()?;
+        Ok(changed)
     }
 
     pub fn delete(&self, profile_id: &str) -> Result<bool> {
@@ -349,6 +729,206 @@ impl ProfileStore {
         Ok(count > 0)
     }
 
+    /// Deletes the profile but keeps its fields in `deleted_profiles` for `restore_deleted`,
+    /// together with its forwards, jump host links, profile-scoped settings, and secret
+    /// links, which are removed in the same transaction.
+    pub fn delete_restorable(&self, profile_id: &str) -> Result<bool> {
+        let Some(profile) = self.get(profile_id)? else {
+            return Ok(false);
+        };
+        let tx = self.conn.unchecked_transaction()?;
+        let related = related_rows(&tx, profile_id)?;
+        tx.execute(
+            "INSERT INTO deleted_profiles (profile_id, deleted_at, profile_json, related_json) \
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                profile_id,
+                now_ms(),
+                serde_json::to_string(&profile)?,
+                serde_json::to_string(&related)?
+            ],
+        )?;
+        tx.execute(
+            "DELETE FROM settings WHERE scope = ?1",
+            [format!("profile:{profile_id}")],
+        )?;
+        tx.execute("DELETE FROM profiles WHERE profile_id = ?1", [profile_id])?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Profiles removed with `delete_restorable`, newest first.
+    pub fn list_deleted(&self) -> Result<Vec<DeletedProfile>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, deleted_at, profile_json, related_json FROM deleted_profiles \
+             ORDER BY deleted_at DESC, id DESC",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut deleted = Vec::new();
+        while let Some(row) = rows.next()? {
+            deleted.push(DeletedProfile {
+                id: row.get(0)?,
+                deleted_at: row.get(1)?,
+                profile: serde_json::from_str(&row.get::<_, String>(2)?)?,
+                related: match row.get::<_, Option<String>>(3)? {
+                    Some(raw) => serde_json::from_str(&raw)?,
+                    None => DeletedRelations::default(),
+                },
+            });
+        }
+        Ok(deleted)
+    }
+
+    /// Permanently drops restorable copies: those of `profile_id` when given, and only the
+    /// ones removed before `deleted_before` (ms) when given. Returns the dropped copies.
+    pub fn purge_deleted(
+        &self,
+        profile_id: Option<&str>,
+        deleted_before: Option<i64>,
+        dry_run: bool,
+    ) -> Result<Vec<DeletedProfile>> {
+        let matching = self
+            .list_deleted()?
+            .into_iter()
+            .filter(|deleted| profile_id.is_none_or(|id| deleted.profile.profile_id == id))
+            .filter(|deleted| deleted_before.is_none_or(|before| deleted.deleted_at < before))
+            .collect::<Vec<_>>();
+        if !dry_run {
+            let tx = self.conn.unchecked_transaction()?;
+            for deleted in &matching {
+                tx.execute("DELETE FROM deleted_profiles WHERE id = ?1", [deleted.id])?;
+            }
+            tx.commit()?;
+        }
+        Ok(matching)
+    }
+
+    /// Brings back the most recently deleted copy of `profile_id` and its related rows,
+    /// unless the id is in use. Links to jump host profiles or secrets that no longer exist
+    /// are skipped.
+    pub fn restore_deleted(&self, profile_id: &str) -> Result<Profile> {
+        let Some(deleted) = self
+            .list_deleted()?
+            .into_iter()
+            .find(|deleted| deleted.profile.profile_id == profile_id)
+        else {
+            return Err(CoreError::NotFound(format!(
+                "no deleted profile {profile_id} to restore"
+            )));
+        };
+        if self.get(profile_id)?.is_some() {
+            return Err(CoreError::Profile(ProfileError::Conflict(format!(
+                "profile already exists: {profile_id}"
+            ))));
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        crate::import_export::insert_profile(&tx, &deleted.profile)?;
+        restore_related_rows(&tx, profile_id, &deleted.related)?;
+        tx.execute("DELETE FROM deleted_profiles WHERE id = ?1", [deleted.id])?;
+        tx.commit()?;
+        Ok(deleted.profile)
+    }
+
+    /// Writes back the danger level, group, tags, and client overrides of `before`, the
+    /// profiles as they were ahead of a bulk edit, in one transaction.
+    pub fn revert_bulk_edit(&self, before: &[Profile]) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        for profile in before {
+            self.update(
+                &profile.profile_id,
+                UpdateProfile {
+                    danger_level: Some(profile.danger_level),
+                    group: Some(profile.group.clone()),
+                    tags: Some(profile.tags.clone()),
+                    client_overrides: Some(profile.client_overrides.clone()),
+                    ..UpdateProfile::default()
+                },
+            )?;
+        }
+        tx.commit()?;
+        Ok(before.len())
+    }
+
+    /// Copies a profile with its forwards, jump host, and profile-scoped settings under a
+    /// new id. Usage timestamps are not copied.
+    pub fn clone_profile(&self, source_id: &str, changes: CloneProfile) -> Result<Profile> {
+        let source = self
+            .get(source_id)?
+            .ok_or_else(|| CoreError::Profile(ProfileError::NotFound(source_id.to_string())))?;
+        let input = NewProfile {
+            profile_id: changes.profile_id,
+            name: changes
+                .name
+                .unwrap_or_else(|| format!("{} (copy)", source.name)),
+            profile_type: source.profile_type,
+            host: changes.host.unwrap_or(source.host),
+            port: source.port,
+            user: source.user,
+            danger_level: source.danger_level,
+            group: source.group,
+            tags: source.tags,
+            note: source.note,
+            initial_send: source.initial_send,
+            client_overrides: source.client_overrides,
+        };
+        let new_id = input.normalize_id()?;
+        if self.get(&new_id)?.is_some() {
+            return Err(CoreError::Profile(ProfileError::Conflict(format!(
+                "profile already exists: {new_id}"
+            ))));
+        }
+        let input = NewProfile {
+            profile_id: Some(new_id.clone()),
+            ..input
+        };
+
+        let tx = self.conn.unchecked_transaction()?;
+        self.insert(input)?;
+        tx.execute(
+            r#"
+            INSERT INTO ssh_forwards (profile_id, name, kind, listen, dest)
+            SELECT ?2, name, kind, listen, dest FROM ssh_forwards WHERE profile_id = ?1
+            ORDER BY id
+            "#,
+            params![source_id, new_id],
+        )?;
+        tx.execute(
+            r#"
+            INSERT INTO ssh_jump (profile_id, jump_profile_id)
+            SELECT ?2, jump_profile_id FROM ssh_jump WHERE profile_id = ?1
+            "#,
+            params![source_id, new_id],
+        )?;
+        tx.execute(
+            r#"
+            INSERT INTO settings (scope, key, value)
+            SELECT ?2, key, value FROM settings WHERE scope = ?1
+            "#,
+            params![format!("profile:{source_id}"), format!("profile:{new_id}")],
+        )?;
+        tx.commit()?;
+
+        self.get(&new_id)?
+            .ok_or_else(|| CoreError::Profile(ProfileError::NotFound(new_id)))
+    }
+
+    /// Archives or restores a profile. Archiving keeps forwards, history, and op_logs
+    /// intact; it only hides the profile from default lists.
+    pub fn set_archived(&self, profile_id: &str, archived: bool) -> Result<Profile> {
+        let archived_at = archived.then(now_ms);
+        let count = self.conn.execute(
+            "UPDATE profiles SET archived_at = ?1, updated_at = ?2 WHERE profile_id = ?3",
+            params![archived_at, now_ms(), profile_id],
+        )?;
+        if count == 0 {
+            return Err(CoreError::Profile(ProfileError::NotFound(
+                profile_id.to_string(),
+            )));
+        }
+        self.get(profile_id)?
+            .ok_or_else(|| CoreError::Profile(ProfileError::NotFound(profile_id.to_string())))
+    }
+
     pub fn touch_last_used(&self, profile_id: &str) -> Result<()> {
         let now = now_ms();
         self.conn.execute(
@@ -359,6 +939,102 @@ impl ProfileStore {
     }
 }
 
+fn related_rows(conn: &Connection, profile_id: &str) -> Result<DeletedRelations> {
+    let mut stmt = conn.prepare(
+        "SELECT name, kind, listen, dest FROM ssh_forwards WHERE profile_id = ?1 ORDER BY id",
+    )?;
+    let forwards = stmt
+        .query_map([profile_id], |row| {
+            Ok(SavedForward {
+                name: row.get(0)?,
+                kind: row.get(1)?,
+                listen: row.get(2)?,
+                dest: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let jump_profile_id = conn
+        .query_row(
+            "SELECT jump_profile_id FROM ssh_jump WHERE profile_id = ?1",
+            [profile_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let mut stmt = conn.prepare(
+        "SELECT profile_id FROM ssh_jump WHERE jump_profile_id = ?1 ORDER BY profile_id",
+    )?;
+    let jump_for = stmt
+        .query_map([profile_id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut stmt = conn.prepare("SELECT key, value FROM settings WHERE scope = ?1 ORDER BY key")?;
+    let settings = stmt
+        .query_map([format!("profile:{profile_id}")], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut stmt = conn.prepare(
+        "SELECT secret_id FROM profile_secrets WHERE profile_id = ?1 ORDER BY secret_id",
+    )?;
+    let secret_ids = stmt
+        .query_map([profile_id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(DeletedRelations {
+        forwards,
+        jump_profile_id,
+        jump_for,
+        settings,
+        secret_ids,
+    })
+}
+
+fn restore_related_rows(
+    conn: &Connection,
+    profile_id: &str,
+    related: &DeletedRelations,
+) -> Result<()> {
+    for forward in &related.forwards {
+        conn.execute(
+            "INSERT INTO ssh_forwards (profile_id, name, kind, listen, dest) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                profile_id,
+                forward.name,
+                forward.kind,
+                forward.listen,
+                forward.dest
+            ],
+        )?;
+    }
+    if let Some(jump_profile_id) = &related.jump_profile_id {
+        conn.execute(
+            "INSERT OR IGNORE INTO ssh_jump (profile_id, jump_profile_id) \
+             SELECT ?1, profile_id FROM profiles WHERE profile_id = ?2",
+            params![profile_id, jump_profile_id],
+        )?;
+    }
+    for jumping in &related.jump_for {
+        conn.execute(
+            "INSERT OR IGNORE INTO ssh_jump (profile_id, jump_profile_id) \
+             SELECT profile_id, ?2 FROM profiles WHERE profile_id = ?1",
+            params![jumping, profile_id],
+        )?;
+    }
+    for (key, value) in &related.settings {
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (scope, key, value) VALUES (?1, ?2, ?3)",
+            params![format!("profile:{profile_id}"), key, value],
+        )?;
+    }
+    for secret_id in &related.secret_ids {
+        conn.execute(
+            "INSERT OR IGNORE INTO profile_secrets (profile_id, secret_id, created_at) \
+             SELECT ?1, secret_id, ?3 FROM secrets WHERE secret_id = ?2",
+            params![profile_id, secret_id, now_ms()],
+        )?;
+    }
+    Ok(())
+}
+
 fn deserialize_profile(row: &Row<'_>) -> Result<Profile> {
     let profile_type: String = row.get("type")?;
     let danger: String = row.get("danger_level")?;
@@ -384,6 +1060,7 @@ fn deserialize_profile(row: &Row<'_>) -> Result<Profile> {
         created_at: row.get("created_at")?,
         updated_at: row.get("updated_at")?,
         last_used_at: row.get("last_used_at")?,
+        archived_at: row.get("archived_at")?,
     })
 }
 
@@ -443,6 +1120,123 @@ mod tests {
         assert!(store.get("p_test123").unwrap().is_none());
     }
 
+    #[test]
+    fn restorable_delete_can_be_undone_once() {
+        let store = ProfileStore::new(init_in_memory().unwrap());
+        let original = store.insert(base_profile()).unwrap();
+        assert!(store.delete_restorable("p_test123").unwrap());
+        assert!(!store.delete_restorable("p_test123").unwrap());
+        assert!(store.get("p_test123").unwrap().is_none());
+        let deleted = store.list_deleted().unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].profile.host, original.host);
+
+        let restored = store.restore_deleted("p_test123").unwrap();
+        assert_eq!(restored.created_at, original.created_at);
+        assert_eq!(store.get("p_test123").unwrap().unwrap().tags, original.tags);
+        assert!(store.list_deleted().unwrap().is_empty());
+        assert!(store.restore_deleted("p_test123").is_err());
+
+        store.delete_restorable("p_test123").unwrap();
+        store.insert(base_profile()).unwrap();
+        assert!(matches!(
+            store.restore_deleted("p_test123"),
+            Err(CoreError::Profile(ProfileError::Conflict(_)))
+        ));
+    }
+
+    #[test]
+    fn restorable_delete_brings_back_forwards_jump_settings_and_secrets() {
+        let store = ProfileStore::new(init_in_memory().unwrap());
+        store.insert(base_profile()).unwrap();
+        store
+            .insert(NewProfile {
+                profile_id: Some("p_bastion".to_string()),
+                ..base_profile()
+            })
+            .unwrap();
+        store
+            .insert(NewProfile {
+                profile_id: Some("p_inner".to_string()),
+                ..base_profile()
+            })
+            .unwrap();
+        store
+            .conn()
+            .execute_batch(
+                "INSERT INTO ssh_forwards (profile_id, name, kind, listen, dest) \
+                 VALUES ('p_test123', 'db', 'local', '127.0.0.1:5432', 'db:5432'); \
+                 INSERT INTO ssh_jump (profile_id, jump_profile_id) VALUES ('p_test123', 'p_bastion'); \
+                 INSERT INTO ssh_jump (profile_id, jump_profile_id) VALUES ('p_inner', 'p_test123'); \
+                 INSERT INTO settings (scope, key, value) VALUES ('profile:p_test123', 'ssh.use_agent', 'false'); \
+                 INSERT INTO secrets (secret_id, kind, label, ciphertext, nonce, created_at, updated_at) \
+                 VALUES ('s_pw', 'password', 'pw', x'00', x'00', 0, 0); \
+                 INSERT INTO profile_secrets (profile_id, secret_id, created_at) VALUES ('p_test123', 's_pw', 0);",
+            )
+            .unwrap();
+        let count =
+            |sql: &str| -> i64 { store.conn().query_row(sql, [], |row| row.get(0)).unwrap() };
+        let related = "SELECT (SELECT COUNT(*) FROM ssh_forwards WHERE profile_id = 'p_test123') \
+             + (SELECT COUNT(*) FROM ssh_jump WHERE 'p_test123' IN (profile_id, jump_profile_id)) \
+             + (SELECT COUNT(*) FROM settings WHERE scope = 'profile:p_test123') \
+             + (SELECT COUNT(*) FROM profile_secrets WHERE profile_id = 'p_test123')";
+        assert_eq!(count(related), 5);
+
+        store.delete_restorable("p_test123").unwrap();
+        assert_eq!(count(related), 0);
+        let deleted = store.list_deleted().unwrap();
+        assert_eq!(deleted[0].related.forwards[0].listen, "127.0.0.1:5432");
+        assert_eq!(deleted[0].related.jump_for, vec!["p_inner".to_string()]);
+
+        store.restore_deleted("p_test123").unwrap();
+        assert_eq!(count(related), 5);
+        assert_eq!(
+            count("SELECT COUNT(*) FROM ssh_jump WHERE profile_id = 'p_test123' AND jump_profile_id = 'p_bastion'"),
+            1
+        );
+        assert_eq!(
+            count("SELECT COUNT(*) FROM settings WHERE scope = 'profile:p_test123' AND value = 'false'"),
+            1
+        );
+    }
+
+    #[test]
+    fn purge_drops_restorable_copies_by_id_and_age() {
+        let store = ProfileStore::new(init_in_memory().unwrap());
+        for id in ["p_old", "p_new"] {
+            store
+                .insert(NewProfile {
+                    profile_id: Some(id.to_string()),
+                    ..base_profile()
+                })
+                .unwrap();
+            store.delete_restorable(id).unwrap();
+        }
+        store
+            .conn()
+            .execute(
+                "UPDATE deleted_profiles SET deleted_at = 1000 WHERE profile_id = 'p_old'",
+                [],
+            )
+            .unwrap();
+
+        let preview = store.purge_deleted(None, Some(2000), true).unwrap();
+        assert_eq!(preview.len(), 1);
+        assert_eq!(preview[0].profile.profile_id, "p_old");
+        assert_eq!(store.list_deleted().unwrap().len(), 2);
+
+        store.purge_deleted(None, Some(2000), false).unwrap();
+        assert_eq!(store.list_deleted().unwrap().len(), 1);
+        assert_eq!(
+            store
+                .purge_deleted(Some("p_new"), None, false)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(store.list_deleted().unwrap().is_empty());
+    }
+
     #[test]
     fn touch_last_used_sets_timestamp() {
         let conn = init_in_memory().unwrap();
@@ -478,6 +1272,7 @@ mod tests {
             profile_type: Some(ProfileType::Ssh),
             danger: Some(DangerLevel::High),
             query: Some("test".into()),
+            ..ProfileFilters::default()
         };
         let filtered = store.list_filtered(&filters).unwrap();
         assert_eq!(filtered.len(), 1);
@@ -516,4 +1311,238 @@ mod tests {
         assert!(updated.client_overrides.is_none());
         assert!(updated.updated_at >= updated.created_at);
     }
+
+    #[test]
+    fn applies_tag_edits() {
+        let current = vec!["prod".to_string(), "old".to_string()];
+        let edits = vec!["+new".to_string(), "-OLD".to_string(), "prod".to_string()];
+        assert_eq!(apply_tag_edits(&current, &edits), vec!["prod", "new"]);
+    }
+
+    #[test]
+    fn patches_client_overrides() {
+        let current = ClientOverrides {
+            ssh: Some("/usr/bin/ssh".into()),
+            scp: Some("/usr/bin/scp".into()),
+            ..ClientOverrides::default()
+        };
+        let patched = patch_client_overrides(
+            Some(&current),
+            &serde_json::json!({"scp": null, "sftp": "/opt/sftp"}),
+        )
+        .unwrap()
+        .expect("overrides remain");
+        assert_eq!(patched.ssh.as_deref(), Some("/usr/bin/ssh"));
+        assert_eq!(patched.scp, None);
+        assert_eq!(patched.sftp.as_deref(), Some("/opt/sftp"));
+
+        let cleared = patch_client_overrides(
+            Some(&patched),
+            &serde_json::json!({"ssh": null, "sftp": null}),
+        )
+        .unwrap();
+        assert!(cleared.is_none());
+
+        let err = patch_client_overrides(None, &serde_json::json!({"rdp": "x"})).unwrap_err();
+        assert!(matches!(err, CoreError::InvalidSetting(_)));
+    }
+
+    #[test]
+    fn bulk_edit_previews_and_applies_in_one_transaction() {
+        let store = ProfileStore::new(init_in_memory().unwrap());
+        let first = store.insert(base_profile()).unwrap();
+        let second = store
+            .insert(NewProfile {
+                profile_id: Some("p_second".into()),
+                tags: vec!["web".into()],
+                ..base_profile()
+            })
+            .unwrap();
+        let ids = vec![first.profile_id.clone(), second.profile_id.clone()];
+
+        let edit = BulkEdit::parse(BulkEditField::AddTag, " web ").unwrap();
+        let preview = store.preview_bulk_edit(&ids, &edit).unwrap();
+        assert!(preview[0].changed());
+        assert!(!preview[1].changed());
+        assert_eq!(store.apply_bulk_edit(&ids, &edit).unwrap(), 1);
+        assert!(store
+            .get(&first.profile_id)
+            .unwrap()
+            .unwrap()
+            .tags
+            .contains(&"web".into()));
+
+        let edit = BulkEdit::parse(BulkEditField::ClientOverride, "ssh=/opt/ssh").unwrap();
+        assert_eq!(store.apply_bulk_edit(&ids, &edit).unwrap(), 2);
+        let overrides = store.get("p_second").unwrap().unwrap().client_overrides;
+        assert_eq!(overrides.unwrap().ssh.as_deref(), Some("/opt/ssh"));
+
+        let missing = vec![first.profile_id.clone(), "p_missing".to_string()];
+        let edit = BulkEdit::parse(BulkEditField::Danger, "Critical").unwrap();
+        assert!(store.apply_bulk_edit(&missing, &edit).is_err());
+        let untouched = store.get(&first.profile_id).unwrap().unwrap();
+        assert_eq!(untouched.danger_level, DangerLevel::Normal);
+
+        assert!(BulkEdit::parse(BulkEditField::ClientOverride, "rdp=/x").is_err());
+        assert_eq!(
+            BulkEdit::parse(BulkEditField::Group, "  ").unwrap(),
+            BulkEdit::SetGroup(None)
+        );
+    }
+
+    #[test]
+    fn clones_profile_with_forwards_and_settings() {
+        use crate::settings::{self, SettingScope};
+
+        let conn = init_in_memory().unwrap();
+        let store = ProfileStore::new(conn);
+        let source = store.insert(base_profile()).unwrap();
+        store.touch_last_used(&source.profile_id).unwrap();
+        store
+            .conn()
+            .execute(
+                "INSERT INTO ssh_forwards (profile_id, name, kind, listen, dest) \
+                 VALUES (?1, 'web', 'local', '127.0.0.1:8080', 'localhost:80')",
+                [&source.profile_id],
+            )
+            .unwrap();
+        settings::set_setting_scoped(
+            store.conn(),
+            &SettingScope::profile(source.profile_id.clone()),
+            "ssh.use_agent",
+            "true",
+        )
+        .unwrap();
+
+        let cloned = store
+            .clone_profile(
+                &source.profile_id,
+                CloneProfile {
+                    profile_id: Some("p_clone".into()),
+                    host: Some("other.example.com".into()),
+                    ..CloneProfile::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(cloned.profile_id, "p_clone");
+        assert_eq!(cloned.name, "Test Profile (copy)");
+        assert_eq!(cloned.host, "other.example.com");
+        assert_eq!(cloned.tags, source.tags);
+        assert!(cloned.last_used_at.is_none());
+        let forward_count: i64 = store
+            .conn()
+            .query_row(
+                "SELECT COUNT(*) FROM ssh_forwards WHERE profile_id = 'p_clone'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(forward_count, 1);
+        assert_eq!(
+            settings::get_setting_scoped(
+                store.conn(),
+                &SettingScope::profile("p_clone"),
+                "ssh.use_agent"
+            )
+            .unwrap()
+            .as_deref(),
+            Some("true")
+        );
+
+        let err = store
+            .clone_profile(
+                &source.profile_id,
+                CloneProfile {
+                    profile_id: Some("p_clone".into()),
+                    ..CloneProfile::default()
+                },
+            )
+            .unwrap_err();
+        assert!(matches!(err, CoreError::Profile(ProfileError::Conflict(_))));
+    }
+
+    #[test]
+    fn archived_profiles_are_hidden_by_default() {
+        let conn = init_in_memory().unwrap();
+        let store = ProfileStore::new(conn);
+        store.insert(base_profile()).unwrap();
+
+        let archived = store.set_archived("p_test123", true).unwrap();
+        assert!(archived.archived_at.is_some());
+        assert!(store
+            .list_filtered(&ProfileFilters::default())
+            .unwrap()
+            .is_empty());
+        let all = store
+            .list_filtered(&ProfileFilters {
+                include_archived: true,
+                ..ProfileFilters::default()
+            })
+            .unwrap();
+        assert_eq!(all.len(), 1);
+
+        store.set_archived("p_test123", false).unwrap();
+        assert_eq!(
+            store
+                .list_filtered(&ProfileFilters::default())
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(matches!(
+            store.set_archived("p_missing", true).unwrap_err(),
+            CoreError::Profile(ProfileError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn search_ranks_full_text_hits_and_tracks_writes() {
+        let conn = init_in_memory().unwrap();
+        let store = ProfileStore::new(conn);
+        let mut db = base_profile();
+        db.profile_id = Some("p_db".into());
+        db.name = "Billing database".into();
+        db.host = "db1.internal".into();
+        db.note = Some("primary postgres".into());
+        store.insert(db).unwrap();
+        let mut web = base_profile();
+        web.profile_id = Some("p_web".into());
+        web.name = "Web frontend".into();
+        web.host = "web1.internal".into();
+        web.note = Some("talks to postgres".into());
+        store.insert(web).unwrap();
+
+        let search = |query: &str| {
+            store
+                .search(&ProfileFilters {
+                    query: Some(query.into()),
+                    ..ProfileFilters::default()
+                })
+                .unwrap()
+                .into_iter()
+                .map(|p| p.profile_id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(search("billing"), vec!["p_db"]);
+        let mut both = search("post");
+        both.sort();
+        assert_eq!(both, vec!["p_db", "p_web"]);
+        assert_eq!(search("database postgres"), vec!["p_db"]);
+        assert_eq!(search("eb1"), vec!["p_web"], "substring fallback");
+
+        store
+            .update(
+                "p_web",
+                UpdateProfile {
+                    note: Some(Some("cdn edge".into())),
+                    ..UpdateProfile::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(search("cdn"), vec!["p_web"]);
+        store.delete("p_db").unwrap();
+        assert!(search("billing").is_empty());
+    }
 }
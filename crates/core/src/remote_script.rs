@@ -0,0 +1,228 @@
+//! Runs a local script on a remote host: the script is staged to a private temp
+//! directory, made executable, run with output streamed to the terminal, and removed.
+//!
+//! The script is read once and those bytes are hashed and written to a private local
+//! copy, which is what scp uploads; edits to the original while the run starts cannot
+//! change what runs. The operation log records the SHA-256 of the exact bytes that were
+//! uploaded, so a later audit can tell which version of a script ran. Script arguments
+//! are not logged.
+
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::cmdset::StagedFile;
+use crate::cmdset_runner::{create_staging_dir, remove_staging_dir};
+use crate::crypto;
+use crate::error::{CoreError, ProfileError, Result, RunError};
+use crate::oplog::{self, OpLogEntry};
+use crate::profile::{ProfileStore, ProfileType};
+
+pub struct ScriptRunRequest<'a> {
+    pub profile_id: &'a str,
+    pub script: &'a Path,
+    /// Appended to the remote command line as-is, so the remote shell splits it.
+    pub args: Option<&'a str>,
+    pub ssh: &'a Path,
+    pub ssh_auth_args: &'a [OsString],
+    pub scp: &'a Path,
+    /// Allocate a remote pty (`ssh -t`) for scripts that prompt or draw progress.
+    pub tty: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptRunResult {
+    pub ok: bool,
+    pub exit_code: i32,
+    pub duration_ms: i64,
+    pub script_sha256: String,
+    pub remote_path: String,
+}
+
+pub fn run_script(store: &ProfileStore, request: ScriptRunRequest<'_>) -> Result<ScriptRunResult> {
+    let profile = store.get(request.profile_id)?.ok_or_else(|| {
+        CoreError::Profile(ProfileError::NotFound(request.profile_id.to_string()))
+    })?;
+    if profile.profile_type != ProfileType::Ssh {
+        return Err(CoreError::Run(RunError::InvalidCommandSpec(
+            "exec-script only supports SSH profiles".to_string(),
+        )));
+    }
+    let bytes = std::fs::read(request.script)?;
+    let checksum = sha256_hex(&bytes);
+    let name = remote_script_name(request.script);
+    let local_copy = LocalCopy::write(&bytes, &name)?;
+    let staged = [StagedFile {
+        local: local_copy.path.clone(),
+        name,
+    }];
+
+    let started = Instant::now();
+    let dir = create_staging_dir(
+        request.ssh,
+        request.ssh_auth_args,
+        &profile,
+        request.scp,
+        &staged,
+    )?;
+    let remote_path = format!("{dir}/{}", staged[0].name);
+    let mut remote_command = format!("chmod 700 '{remote_path}' && '{remote_path}'");
+    if let Some(args) = request.args.filter(|args| !args.trim().is_empty()) {
+        remote_command.push(' ');
+        remote_command.push_str(args);
+    }
+    let mut command = Command::new(request.ssh);
+    command.arg("-p").arg(profile.port.to_string());
+    if request.tty {
+        command.arg("-t");
+    }
+    command
+        .args(request.ssh_auth_args)
+        .arg(format!("{}@{}", profile.user, profile.host))
+        .arg(remote_command)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    let status = command.status();
+    remove_staging_dir(request.ssh, request.ssh_auth_args, &profile, &dir);
+    drop(local_copy);
+    let status = status?;
+    let duration_ms = started.elapsed().as_millis() as i64;
+    let exit_code = status.code().unwrap_or(-1);
+
+    store.touch_last_used(&profile.profile_id)?;
+    oplog::log_operation(
+        store.conn(),
+        OpLogEntry {
+            op: "exec_script".into(),
+            profile_id: Some(profile.profile_id),
+            client_used: Some(request.ssh.to_string_lossy().into_owned()),
+            ok: status.success(),
+            exit_code: Some(exit_code),
+            duration_ms: Some(duration_ms),
+            meta_json: Some(serde_json::json!({
+                "script": staged[0].name,
+                "sha256": checksum,
+                "bytes": bytes.len(),
+                "tty": request.tty,
+            })),
+        },
+    )?;
+
+    Ok(ScriptRunResult {
+        ok: status.success(),
+        exit_code,
+        duration_ms,
+        script_sha256: checksum,
+        remote_path,
+    })
+}
+
+/// A user-only temp directory holding the bytes that were hashed, removed on drop.
+struct LocalCopy {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl LocalCopy {
+    fn write(bytes: &[u8], name: &str) -> Result<Self> {
+        let token: String = crypto::random_bytes::<8>()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        let dir = std::env::temp_dir().join(format!("teradock-script-{token}"));
+        let mut builder = std::fs::DirBuilder::new();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        builder.create(&dir)?;
+        let copy = Self {
+            path: dir.join(name),
+            dir,
+        };
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(&copy.path)?.write_all(bytes)?;
+        Ok(copy)
+    }
+}
+
+impl Drop for LocalCopy {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// The local file name with anything outside `[A-Za-z0-9._-]` replaced, since the
+/// name is quoted into remote shell commands.
+fn remote_script_name(script: &Path) -> String {
+    let name = script
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    match name.trim_start_matches('.') {
+        "" => "script".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_and_names_scripts() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            remote_script_name(Path::new("./diag nose.sh")),
+            "diag_nose.sh"
+        );
+        assert_eq!(remote_script_name(Path::new("/tmp/.hidden")), "hidden");
+    }
+
+    #[test]
+    fn local_copy_holds_the_hashed_bytes_until_dropped() {
+        let copy = LocalCopy::write(b"echo hi\n", "diag.sh").unwrap();
+        assert_eq!(std::fs::read(&copy.path).unwrap(), b"echo hi\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&copy.dir).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        let dir = copy.dir.clone();
+        drop(copy);
+        assert!(!dir.exists());
+    }
+}
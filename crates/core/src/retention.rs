@@ -0,0 +1,238 @@
+//! Danger-aware retention for `op_logs` and stored run outputs. Rows are kept for the
+//! `retention.<level>_days` of the profile they belong to, so evidence from critical
+//! profiles outlives lab noise. Rows whose profile was deleted count as normal. Stored
+//! outputs go earlier when `retention.output_days` is set and sooner.
+//!
+//! `oplog.max_rows` additionally caps how many entries are kept, oldest going first.
+//!
+//! Nothing is pruned automatically unless `oplog.auto_prune` is turned on; otherwise
+//! `td log prune` applies the policy on request.
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::profile::DangerLevel;
+use crate::settings;
+
+const DAY_MS: i64 = 86_400_000;
+const LEVELS: [DangerLevel; 3] = [
+    DangerLevel::Normal,
+    DangerLevel::High,
+    DangerLevel::Critical,
+];
+
+/// Retention for one danger level and the rows older than it.
+#[derive(Debug, Clone, Serialize)]
+pub struct LevelRetention {
+    pub danger_level: DangerLevel,
+    /// 0 keeps rows forever.
+    pub days: u32,
+    pub cutoff_ms: Option<i64>,
+    pub op_logs: usize,
+    pub run_outputs: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    pub levels: Vec<LevelRetention>,
+    /// `oplog.max_rows`; 0 sets no cap.
+    pub max_rows: u64,
+    /// Entries past `max_rows` once the level retention has been applied.
+    pub over_max_rows: usize,
+}
+
+impl RetentionReport {
+    pub fn op_logs(&self) -> usize {
+        self.levels.iter().map(|level| level.op_logs).sum::<usize>() + self.over_max_rows
+    }
+
+    pub fn run_outputs(&self) -> usize {
+        self.levels.iter().map(|level| level.run_outputs).sum()
+    }
+}
+
+/// Counts (with `dry_run`) or deletes the rows past their danger level's retention.
+pub fn prune(conn: &Connection, now_ms: i64, dry_run: bool) -> Result<RetentionReport> {
+    let tx = conn.unchecked_transaction()?;
+    let output_days = settings::get_output_retention_days(&tx)?;
+    let output_cutoff_ms = (output_days > 0).then(|| now_ms - i64::from(output_days) * DAY_MS);
+    let mut levels = Vec::new();
+    for level in LEVELS {
+        let days = settings::get_retention_days(&tx, level)?;
+        let cutoff_ms = (days > 0).then(|| now_ms - i64::from(days) * DAY_MS);
+        let op_logs = match cutoff_ms {
+            Some(cutoff) => prune_table(&tx, "op_logs", level, cutoff, dry_run)?,
+            None => 0,
+        };
+        // Outputs go at whichever cutoff comes first.
+        let run_outputs = match cutoff_ms.max(output_cutoff_ms) {
+            Some(cutoff) => prune_table(&tx, "run_outputs", level, cutoff, dry_run)?,
+            None => 0,
+        };
+        levels.push(LevelRetention {
+            danger_level: level,
+            days,
+            cutoff_ms,
+            op_logs,
+            run_outputs,
+        });
+    }
+    let max_rows = settings::get_oplog_max_rows(&tx)?;
+    let over_max_rows = if max_rows == 0 {
+        0
+    } else {
+        let pruned = levels.iter().map(|level| level.op_logs).sum::<usize>();
+        prune_over_max_rows(&tx, max_rows, pruned, dry_run)?
+    };
+    tx.commit()?;
+    Ok(RetentionReport {
+        dry_run,
+        levels,
+        max_rows,
+        over_max_rows,
+    })
+}
+
+/// Applies [`prune`] when `oplog.auto_prune` is on; `None` when it is off.
+pub fn auto_prune(conn: &Connection, now_ms: i64) -> Result<Option<RetentionReport>> {
+    if !settings::get_oplog_auto_prune(conn)? {
+        return Ok(None);
+    }
+    prune(conn, now_ms, false).map(Some)
+}
+
+/// Deletes (or, with `dry_run`, counts) the oldest entries beyond `max_rows`. A dry run has
+/// not removed the `already_pruned` rows yet, so they are left out of the count.
+fn prune_over_max_rows(
+    conn: &Connection,
+    max_rows: u64,
+    already_pruned: usize,
+    dry_run: bool,
+) -> Result<usize> {
+    if dry_run {
+        let total: i64 = conn.query_row("SELECT COUNT(*) FROM op_logs", [], |row| row.get(0))?;
+        let remaining = (total as u64).saturating_sub(already_pruned as u64);
+        return Ok(remaining.saturating_sub(max_rows) as usize);
+    }
+    let keep = i64::try_from(max_rows).unwrap_or(i64::MAX);
+    Ok(conn.execute(
+        "DELETE FROM op_logs WHERE id IN \
+         (SELECT id FROM op_logs ORDER BY ts DESC, id DESC LIMIT -1 OFFSET ?1)",
+        params![keep],
+    )?)
+}
+
+fn prune_table(
+    conn: &Connection,
+    table: &str,
+    level: DangerLevel,
+    cutoff_ms: i64,
+    dry_run: bool,
+) -> Result<usize> {
+    let filter = format!(
+        "FROM {table} WHERE ts < ?1 AND COALESCE((SELECT p.danger_level FROM profiles p \
+         WHERE p.profile_id = {table}.profile_id), 'normal') = ?2"
+    );
+    let level = level.to_string();
+    if dry_run {
+        let count: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) {filter}"),
+            params![cutoff_ms, level],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    } else {
+        Ok(conn.execute(&format!("DELETE {filter}"), params![cutoff_ms, level])?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_in_memory;
+    use crate::profile::{NewProfile, ProfileStore, ProfileType};
+
+    #[test]
+    fn keeps_critical_rows_longer_than_normal_ones() {
+        let store = ProfileStore::new(init_in_memory().unwrap());
+        for (id, danger_level) in [
+            ("p_lab", DangerLevel::Normal),
+            ("p_prod", DangerLevel::Critical),
+        ] {
+            store
+                .insert(NewProfile {
+                    profile_id: Some(id.to_string()),
+                    name: id.to_string(),
+                    profile_type: ProfileType::Ssh,
+                    host: "localhost".to_string(),
+                    port: 22,
+                    user: "root".to_string(),
+                    danger_level,
+                    group: None,
+                    tags: Vec::new(),
+                    note: None,
+                    initial_send: None,
+                    client_overrides: None,
+                })
+                .unwrap();
+        }
+        let conn = store.conn();
+        let now = 400 * DAY_MS;
+        for (profile, age_days) in [
+            (Some("p_lab"), 10),
+            (Some("p_lab"), 40),
+            (Some("p_prod"), 40),
+            (Some("p_prod"), 380),
+            (None, 40),
+        ] {
+            conn.execute(
+                "INSERT INTO op_logs (ts, op, profile_id, ok) VALUES (?1, 'run', ?2, 1)",
+                params![now - age_days * DAY_MS, profile],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO run_outputs (ts, profile_id, source, cmd, stdout, stderr, op_log_id) \
+                 VALUES (?1, ?2, 'exec', 'uptime', 'up', '', last_insert_rowid())",
+                params![now - age_days * DAY_MS, profile],
+            )
+            .unwrap();
+        }
+
+        let report = prune(conn, now, true).unwrap();
+        assert_eq!((report.op_logs(), report.run_outputs()), (3, 3));
+        let remaining: i64 = conn
+            .query_row("SELECT COUNT(*) FROM op_logs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(remaining, 5);
+
+        settings::set_setting(conn, settings::RETENTION_NORMAL_DAYS_KEY, "0").unwrap();
+        let report = prune(conn, now, false).unwrap();
+        assert_eq!(report.levels[0].days, 0);
+        assert_eq!((report.op_logs(), report.run_outputs()), (1, 1));
+        let old_prod: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM run_outputs WHERE profile_id = 'p_prod'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(old_prod, 1);
+
+        settings::set_setting(conn, settings::RETENTION_OUTPUT_DAYS_KEY, "5").unwrap();
+        let report = prune(conn, now, false).unwrap();
+        assert_eq!((report.op_logs(), report.run_outputs()), (0, 4));
+
+        assert!(auto_prune(conn, now).unwrap().is_none(), "off by default");
+        settings::set_setting(conn, settings::OPLOG_MAX_ROWS_KEY, "3").unwrap();
+        settings::set_setting(conn, settings::OPLOG_AUTO_PRUNE_KEY, "true").unwrap();
+        assert_eq!(prune(conn, now, true).unwrap().over_max_rows, 1);
+        let report = auto_prune(conn, now).unwrap().unwrap();
+        assert_eq!((report.over_max_rows, report.op_logs()), (1, 1));
+        let oldest_left: i64 = conn
+            .query_row("SELECT MIN(ts) FROM op_logs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(oldest_left, now - 40 * DAY_MS);
+    }
+}
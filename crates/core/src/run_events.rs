@@ -0,0 +1,107 @@
+//! Lifecycle events for CommandSet runs. A run with `CmdSetRunRequest::events` set
+//! publishes them as it goes, so the TUI can show live output and `td api serve` can
+//! stream them as server-sent events; the final `CmdSetRunResult` is unchanged.
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RunEvent {
+    RunStarted {
+        profile_id: String,
+        cmdset_id: String,
+        steps: usize,
+    },
+    StepStarted {
+        ord: i64,
+        cmd: String,
+    },
+    /// Output as it arrives. The native backend sends each stream as one chunk when the
+    /// step ends.
+    Output {
+        ord: i64,
+        stream: OutputStream,
+        chunk: String,
+    },
+    StepFinished {
+        ord: i64,
+        ok: bool,
+        exit_code: i32,
+        duration_ms: i64,
+    },
+    StepParsed {
+        ord: i64,
+        parsed: serde_json::Value,
+    },
+    RunFinished {
+        ok: bool,
+        exit_code: i32,
+        duration_ms: i64,
+    },
+}
+
+impl RunEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::RunStarted { .. } => "run_started",
+            Self::StepStarted { .. } => "step_started",
+            Self::Output { .. } => "output",
+            Self::StepFinished { .. } => "step_finished",
+            Self::StepParsed { .. } => "step_parsed",
+            Self::RunFinished { .. } => "run_finished",
+        }
+    }
+}
+
+/// Fan-out of run events to any number of subscribers; dropped receivers are pruned on
+/// the next publish.
+#[derive(Debug, Default)]
+pub struct RunEventBus {
+    subscribers: Mutex<Vec<Sender<RunEvent>>>,
+    timed: Mutex<Vec<Sender<(Instant, RunEvent)>>>,
+}
+
+impl RunEventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&self) -> Receiver<RunEvent> {
+        let (tx, rx) = mpsc::channel();
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(tx);
+        }
+        rx
+    }
+
+    /// Like `subscribe`, with the time each event was published, for a subscriber that
+    /// reads the events only after the run (see `run_record`).
+    pub fn subscribe_timed(&self) -> Receiver<(Instant, RunEvent)> {
+        let (tx, rx) = mpsc::channel();
+        if let Ok(mut timed) = self.timed.lock() {
+            timed.push(tx);
+        }
+        rx
+    }
+
+    pub fn publish(&self, event: RunEvent) {
+        if let Ok(mut timed) = self.timed.lock() {
+            let now = Instant::now();
+            timed.retain(|tx| tx.send((now, event.clone())).is_ok());
+        }
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+        }
+    }
+}
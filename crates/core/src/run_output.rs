@@ -0,0 +1,408 @@
+//! Stored stdout/stderr of `td run` steps and `td exec` commands, indexed with FTS5 for
+//! fleet-wide searches such as "which hosts printed ORA-00600 this month", and linked to
+//! their op log entry so `td log show` and the TUI history can replay a past run.
+//!
+//! Outputs can contain anything a remote command prints, so storing them is opt-in via
+//! the `run.store_outputs` setting, each stream is cut at `run.output_max_bytes`, and
+//! nothing is pruned unless asked: `td log prune` (or `oplog.auto_prune`) removes them by
+//! danger-level retention or `retention.output_days` (see `retention`).
+
+use rusqlite::{params, Connection, Row};
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::oplog::OpLogRecord;
+use crate::settings;
+use crate::util::now_ms;
+
+#[derive(Debug, Clone)]
+pub struct NewRunOutput<'a> {
+    pub profile_id: &'a str,
+    /// `run:<cmdset_id>` for CommandSet steps, `exec` for ad-hoc commands.
+    pub source: &'a str,
+    pub ord: Option<i64>,
+    pub cmd: &'a str,
+    pub exit_code: Option<i32>,
+    pub stdout: &'a str,
+    pub stderr: &'a str,
+    /// The op log entry of the run or exec that printed it.
+    pub op_log_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputMatch {
+    pub id: i64,
+    pub ts: i64,
+    pub profile_id: Option<String>,
+    pub host: Option<String>,
+    pub source: String,
+    pub ord: Option<i64>,
+    pub cmd: String,
+    pub exit_code: Option<i32>,
+    /// Output lines containing the query (case-insensitive), stdout first.
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OutputSearch<'a> {
+    pub query: &'a str,
+    pub since_ms: Option<i64>,
+    pub profile_id: Option<&'a str>,
+    pub limit: usize,
+}
+
+/// One stored output in full.
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredOutput {
+    pub id: i64,
+    pub ts: i64,
+    pub source: String,
+    pub ord: Option<i64>,
+    pub cmd: String,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    /// Bytes cut from stdout and stderr by `run.output_max_bytes`.
+    pub truncated_bytes: usize,
+}
+
+/// Lines shown per match; the full output stays in the database.
+const MAX_MATCH_LINES: usize = 5;
+
+/// How far outside an operation's logged span outputs stored without an op log link
+/// (before schema v22) may have been written: `td run` stored them just before logging,
+/// `td exec` just after.
+const OPERATION_SLACK_MS: i64 = 2_000;
+
+/// Stores `output`, keeping at most `run.output_max_bytes` of each stream.
+pub fn record(conn: &Connection, output: &NewRunOutput<'_>) -> Result<()> {
+    let max_bytes = settings::get_output_max_bytes(conn)?;
+    let (stdout, stdout_cut) = truncate(output.stdout, max_bytes);
+    let (stderr, stderr_cut) = truncate(output.stderr, max_bytes);
+    conn.execute(
+        r#"
+        INSERT INTO run_outputs
+            (ts, profile_id, source, ord, cmd, exit_code, stdout, stderr, op_log_id,
+             truncated_bytes)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+        "#,
+        params![
+            now_ms(),
+            output.profile_id,
+            output.source,
+            output.ord,
+            output.cmd,
+            output.exit_code,
+            stdout,
+            stderr,
+            output.op_log_id,
+            (stdout_cut + stderr_cut) as i64
+        ],
+    )?;
+    Ok(())
+}
+
+/// The first `max_bytes` of `text` (on a char boundary) and how many bytes were dropped.
+fn truncate(text: &str, max_bytes: usize) -> (&str, usize) {
+    if text.len() <= max_bytes {
+        return (text, 0);
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    (&text[..end], text.len() - end)
+}
+
+/// Finds stored outputs containing `query` as a phrase, newest first.
+pub fn search(conn: &Connection, search: &OutputSearch<'_>) -> Result<Vec<OutputMatch>> {
+    let query = search.query.trim();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    // Quote the whole query so FTS5 treats punctuation such as `-` as part of a phrase.
+    let phrase = format!("\"{}\"", query.replace('"', "\"\""));
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT o.id, o.ts, o.profile_id, p.host, o.source, o.ord, o.cmd, o.exit_code,
+               o.stdout, o.stderr
+        FROM run_outputs_fts f
+        JOIN run_outputs o ON o.id = f.rowid
+        LEFT JOIN profiles p ON p.profile_id = o.profile_id
+        WHERE run_outputs_fts MATCH ?1
+          AND (?2 IS NULL OR o.ts >= ?2)
+          AND (?3 IS NULL OR o.profile_id = ?3)
+        ORDER BY o.ts DESC, o.id DESC
+        LIMIT ?4
+        "#,
+    )?;
+    let limit = if search.limit == 0 {
+        -1
+    } else {
+        search.limit as i64
+    };
+    let mut rows = stmt.query(params![phrase, search.since_ms, search.profile_id, limit])?;
+    let needle = query.to_lowercase();
+    let mut matches = Vec::new();
+    while let Some(row) = rows.next()? {
+        let stdout: String = row.get("stdout")?;
+        let stderr: String = row.get("stderr")?;
+        let lines = stdout
+            .lines()
+            .chain(stderr.lines())
+            .filter(|line| line.to_lowercase().contains(&needle))
+            .take(MAX_MATCH_LINES)
+            .map(|line| line.trim_end().to_string())
+            .collect();
+        matches.push(OutputMatch {
+            id: row.get("id")?,
+            ts: row.get("ts")?,
+            profile_id: row.get("profile_id")?,
+            host: row.get("host")?,
+            source: row.get("source")?,
+            ord: row.get("ord")?,
+            cmd: row.get("cmd")?,
+            exit_code: row.get("exit_code")?,
+            lines,
+        });
+    }
+    Ok(matches)
+}
+
+/// Outputs stored for a logged `run` or `exec` operation (`run.store_outputs`): the rows
+/// linked to it, or for older rows without a link, those with the same profile and source
+/// stored while it ran. Empty for other operations or when the setting was off.
+pub fn outputs_for_operation(conn: &Connection, record: &OpLogRecord) -> Result<Vec<StoredOutput>> {
+    let source = match record.op.as_str() {
+        "run" => match record
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.get("cmdset_id"))
+            .and_then(|id| id.as_str())
+        {
+            Some(cmdset_id) => format!("run:{cmdset_id}"),
+            None => return Ok(Vec::new()),
+        },
+        "exec" => "exec".to_string(),
+        _ => return Ok(Vec::new()),
+    };
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT id, ts, source, ord, cmd, exit_code, stdout, stderr, truncated_bytes
+        FROM run_outputs
+        WHERE op_log_id = ?1
+        ORDER BY ord, id
+        "#,
+    )?;
+    let linked = stmt
+        .query_map(params![record.id], stored_output)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    if !linked.is_empty() {
+        return Ok(linked);
+    }
+    let started = record.ts - record.duration_ms.unwrap_or(0) - OPERATION_SLACK_MS;
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT id, ts, source, ord, cmd, exit_code, stdout, stderr, truncated_bytes
+        FROM run_outputs
+        WHERE op_log_id IS NULL AND profile_id IS ?1 AND source = ?2 AND ts BETWEEN ?3 AND ?4
+        ORDER BY ord, id
+        "#,
+    )?;
+    let rows = stmt.query_map(
+        params![
+            record.profile_id,
+            source,
+            started,
+            record.ts + OPERATION_SLACK_MS
+        ],
+        stored_output,
+    )?;
+    Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+}
+
+fn stored_output(row: &Row<'_>) -> rusqlite::Result<StoredOutput> {
+    Ok(StoredOutput {
+        id: row.get("id")?,
+        ts: row.get("ts")?,
+        source: row.get("source")?,
+        ord: row.get("ord")?,
+        cmd: row.get("cmd")?,
+        exit_code: row.get("exit_code")?,
+        stdout: row.get("stdout")?,
+        stderr: row.get("stderr")?,
+        truncated_bytes: row.get::<_, i64>("truncated_bytes")? as usize,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_in_memory;
+    use crate::profile::{DangerLevel, NewProfile, ProfileStore, ProfileType};
+
+    fn store_with_profiles(ids: &[&str]) -> ProfileStore {
+        let store = ProfileStore::new(init_in_memory().unwrap());
+        for id in ids {
+            store
+                .insert(NewProfile {
+                    profile_id: Some(id.to_string()),
+                    name: id.to_string(),
+                    profile_type: ProfileType::Ssh,
+                    host: format!("{id}.example.com"),
+                    port: 22,
+                    user: "oracle".to_string(),
+                    danger_level: DangerLevel::Normal,
+                    group: None,
+                    tags: Vec::new(),
+                    note: None,
+                    initial_send: None,
+                    client_overrides: None,
+                })
+                .unwrap();
+        }
+        store
+    }
+
+    fn output<'a>(profile_id: &'a str, stdout: &'a str) -> NewRunOutput<'a> {
+        NewRunOutput {
+            profile_id,
+            source: "run:c_db",
+            ord: Some(1),
+            cmd: "tail alert.log",
+            exit_code: Some(0),
+            stdout,
+            stderr: "",
+            op_log_id: None,
+        }
+    }
+
+    #[test]
+    fn finds_outputs_by_phrase_and_filters() {
+        let store = store_with_profiles(&["db1", "db2"]);
+        let conn = store.conn();
+        record(conn, &output("db1", "ok\nORA-00600: internal error\n")).unwrap();
+        record(conn, &output("db2", "ORA-00601 only\n")).unwrap();
+        record(conn, &output("db2", "ora-00600 again\n")).unwrap();
+
+        let all = search(
+            conn,
+            &OutputSearch {
+                query: "ORA-00600",
+                ..OutputSearch::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].lines, vec!["ora-00600 again"]);
+        assert_eq!(all[1].lines, vec!["ORA-00600: internal error"]);
+        assert_eq!(all[1].host.as_deref(), Some("db1.example.com"));
+
+        let db1 = search(
+            conn,
+            &OutputSearch {
+                query: "ORA-00600",
+                profile_id: Some("db1"),
+                limit: 10,
+                ..OutputSearch::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(db1.len(), 1);
+        let future = search(
+            conn,
+            &OutputSearch {
+                query: "ORA-00600",
+                since_ms: Some(now_ms() + 60_000),
+                ..OutputSearch::default()
+            },
+        )
+        .unwrap();
+        assert!(future.is_empty());
+    }
+
+    #[test]
+    fn links_outputs_to_their_operation() {
+        let store = store_with_profiles(&["db1", "db2"]);
+        let conn = store.conn();
+        record(conn, &output("db1", "step one\n")).unwrap();
+        record(conn, &output("db2", "other host\n")).unwrap();
+        crate::oplog::log_operation(
+            conn,
+            crate::oplog::OpLogEntry {
+                op: "run".to_string(),
+                profile_id: Some("db1".to_string()),
+                client_used: Some("ssh".to_string()),
+                ok: true,
+                exit_code: Some(0),
+                duration_ms: Some(40),
+                meta_json: Some(serde_json::json!({ "cmdset_id": "c_db" })),
+            },
+        )
+        .unwrap();
+        let id = crate::oplog::latest_operation_id(conn, Some("db1"))
+            .unwrap()
+            .unwrap();
+        let op = crate::oplog::get_operation(conn, id).unwrap().unwrap();
+
+        let outputs = outputs_for_operation(conn, &op).unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].stdout, "step one\n");
+
+        let other_cmdset = OpLogRecord {
+            meta: Some(serde_json::json!({ "cmdset_id": "c_other" })),
+            ..op.clone()
+        };
+        assert!(outputs_for_operation(conn, &other_cmdset)
+            .unwrap()
+            .is_empty());
+        let connect = OpLogRecord {
+            op: "connect".to_string(),
+            ..op
+        };
+        assert!(outputs_for_operation(conn, &connect).unwrap().is_empty());
+    }
+
+    #[test]
+    fn keeps_capped_outputs_with_their_operation() {
+        let store = store_with_profiles(&["web"]);
+        let conn = store.conn();
+        let id = crate::oplog::log_operation(
+            conn,
+            crate::oplog::OpLogEntry {
+                op: "exec".to_string(),
+                profile_id: Some("web".to_string()),
+                client_used: None,
+                ok: true,
+                exit_code: Some(0),
+                duration_ms: Some(3),
+                meta_json: None,
+            },
+        )
+        .unwrap();
+        settings::set_setting(conn, settings::RUN_OUTPUT_MAX_BYTES_KEY, "2").unwrap();
+        record(
+            conn,
+            &NewRunOutput {
+                source: "exec",
+                ord: None,
+                cmd: "cat motd",
+                op_log_id: Some(id),
+                ..output("web", "héllo world")
+            },
+        )
+        .unwrap();
+
+        let op = crate::oplog::get_operation(conn, id).unwrap().unwrap();
+        let stored = outputs_for_operation(conn, &op).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].stdout, "h");
+        assert_eq!(stored[0].truncated_bytes, "héllo world".len() - 1);
+
+        let other = crate::oplog::OpLogRecord { id: id + 1, ..op };
+        assert!(
+            outputs_for_operation(conn, &other).unwrap().is_empty(),
+            "linked rows do not match other entries by time"
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! Recordings of CommandSet run output: with `run.record` set to `log` or `cast`, each
+//! `td run` and TUI run also writes what its steps printed, with timing, to a new file
+//! under `logs/runs/`. `log` is plain text with each line's offset from the start of the
+//! run; `cast` is an asciinema v2 cast for `asciinema play`.
+//!
+//! The op log entry keeps the path in its `recording` meta field, so `td log show <id>
+//! --output` finds the file. Recordings hold whatever remote commands print, so they are
+//! off by default and created readable only by the owner.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde_json::Value;
+use time::UtcOffset;
+
+use crate::error::Result;
+use crate::oplog::OpLogRecord;
+use crate::paths;
+use crate::run_events::{OutputStream, RunEvent};
+use crate::run_report::{file_safe, file_stamp, write_new_file};
+use crate::settings::RunRecordFormat;
+use crate::util::format_timestamp_ms;
+
+/// Terminal size written into cast headers; players rewrap to their own window.
+const CAST_WIDTH: u16 = 120;
+const CAST_HEIGHT: u16 = 40;
+
+/// `logs/runs/` in the config directory.
+pub fn runs_dir() -> Result<PathBuf> {
+    let dir = paths::logs_dir()?.join("runs");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Writes the recording of one run to a new `<profile>-<cmdset>-<UTC timestamp>` file in
+/// `dir` and returns its path; `Off` writes nothing. `events` are the run's events with the
+/// time each was published, oldest first.
+pub fn write(
+    dir: &Path,
+    format: RunRecordFormat,
+    profile_id: &str,
+    cmdset_id: &str,
+    started_at_ms: i64,
+    events: &[(Instant, RunEvent)],
+) -> Result<Option<PathBuf>> {
+    let (body, extension) = match format {
+        RunRecordFormat::Off => return Ok(None),
+        RunRecordFormat::Log => (
+            render_log(profile_id, cmdset_id, started_at_ms, events),
+            "log",
+        ),
+        RunRecordFormat::Cast => (
+            render_cast(profile_id, cmdset_id, started_at_ms, events)?,
+            "cast",
+        ),
+    };
+    let stem = format!(
+        "{}-{}-{}",
+        file_safe(profile_id),
+        file_safe(cmdset_id),
+        file_stamp(started_at_ms)
+    );
+    write_new_file(dir, &stem, extension, &body).map(Some)
+}
+
+/// The recording path stored with an op log entry, if the run was recorded.
+pub fn recording_path(record: &OpLogRecord) -> Option<PathBuf> {
+    record
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.get("recording"))
+        .and_then(Value::as_str)
+        .map(PathBuf::from)
+}
+
+/// Milliseconds from the first event to each event.
+fn offsets(events: &[(Instant, RunEvent)]) -> impl Iterator<Item = (i64, &RunEvent)> {
+    let start = events.first().map(|(at, _)| *at);
+    events.iter().map(move |(at, event)| {
+        let offset = start.map_or(0, |start| at.duration_since(start).as_millis() as i64);
+        (offset, event)
+    })
+}
+
+fn render_log(
+    profile_id: &str,
+    cmdset_id: &str,
+    started_at_ms: i64,
+    events: &[(Instant, RunEvent)],
+) -> String {
+    let mut out = format!(
+        "# td run {cmdset_id} on {profile_id} at {} UTC\n",
+        format_timestamp_ms(started_at_ms, UtcOffset::UTC)
+    );
+    for (offset_ms, event) in offsets(events) {
+        let stamp = format!("+{}.{:03}s", offset_ms / 1000, offset_ms % 1000);
+        match event {
+            RunEvent::StepStarted { ord, cmd } => {
+                let _ = writeln!(out, "{stamp} step {ord} $ {cmd}");
+            }
+            RunEvent::Output { ord, stream, chunk } => {
+                let label = match stream {
+                    OutputStream::Stdout => "out",
+                    OutputStream::Stderr => "err",
+                };
+                for line in chunk.lines() {
+                    let _ = writeln!(out, "{stamp} step {ord} {label}| {line}");
+                }
+            }
+            RunEvent::StepFinished {
+                ord,
+                exit_code,
+                duration_ms,
+                ..
+            } => {
+                let _ = writeln!(
+                    out,
+                    "{stamp} step {ord} exit {exit_code} ({duration_ms} ms)"
+                );
+            }
+            RunEvent::RunStarted { .. }
+            | RunEvent::StepParsed { .. }
+            | RunEvent::RunFinished { .. } => {}
+        }
+    }
+    out
+}
+
+fn render_cast(
+    profile_id: &str,
+    cmdset_id: &str,
+    started_at_ms: i64,
+    events: &[(Instant, RunEvent)],
+) -> Result<String> {
+    let header = serde_json::json!({
+        "version": 2,
+        "width": CAST_WIDTH,
+        "height": CAST_HEIGHT,
+        "timestamp": started_at_ms / 1000,
+        "title": format!("td run {cmdset_id} on {profile_id}"),
+    });
+    let mut out = format!("{header}\n");
+    for (offset_ms, event) in offsets(events) {
+        let data = match event {
+            RunEvent::StepStarted { cmd, .. } => format!("\u{1b}[1m$ {cmd}\u{1b}[0m\r\n"),
+            RunEvent::Output { stream, chunk, .. } => {
+                let chunk = chunk.replace('\n', "\r\n");
+                match stream {
+                    OutputStream::Stdout => chunk,
+                    OutputStream::Stderr => format!("\u{1b}[31m{chunk}\u{1b}[0m"),
+                }
+            }
+            RunEvent::StepFinished { exit_code, .. } if *exit_code != 0 => {
+                format!("\u{1b}[31m[exit {exit_code}]\u{1b}[0m\r\n")
+            }
+            _ => continue,
+        };
+        let line = serde_json::to_string(&(offset_ms as f64 / 1000.0, "o", data))?;
+        out.push_str(&line);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn sample_events() -> Vec<(Instant, RunEvent)> {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        vec![
+            (
+                at(0),
+                RunEvent::RunStarted {
+                    profile_id: "p_web".to_string(),
+                    cmdset_id: "c_check".to_string(),
+                    steps: 1,
+                },
+            ),
+            (
+                at(5),
+                RunEvent::StepStarted {
+                    ord: 1,
+                    cmd: "df -h".to_string(),
+                },
+            ),
+            (
+                at(1250),
+                RunEvent::Output {
+                    ord: 1,
+                    stream: OutputStream::Stdout,
+                    chunk: "/dev/sda1 90%\n".to_string(),
+                },
+            ),
+            (
+                at(1300),
+                RunEvent::StepFinished {
+                    ord: 1,
+                    ok: false,
+                    exit_code: 2,
+                    duration_ms: 1295,
+                },
+            ),
+        ]
+    }
+
+    #[test]
+    fn writes_timed_log_and_cast_recordings() {
+        let dir = std::env::temp_dir().join(format!("td-run-record-{}", std::process::id()));
+        let events = sample_events();
+        assert!(
+            write(&dir, RunRecordFormat::Off, "p_web", "c_check", 0, &events)
+                .unwrap()
+                .is_none()
+        );
+
+        let log = write(&dir, RunRecordFormat::Log, "p_web", "c_check", 0, &events)
+            .unwrap()
+            .unwrap();
+        assert!(log.ends_with("p_web-c_check-19700101-000000.log"));
+        let text = std::fs::read_to_string(&log).unwrap();
+        assert!(text.contains("+0.005s step 1 $ df -h\n"));
+        assert!(text.contains("+1.250s step 1 out| /dev/sda1 90%\n"));
+        assert!(text.contains("+1.300s step 1 exit 2 (1295 ms)\n"));
+
+        let cast = write(&dir, RunRecordFormat::Cast, "p_web", "c_check", 0, &events)
+            .unwrap()
+            .unwrap();
+        let text = std::fs::read_to_string(&cast).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        let header: Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(header["version"], 2);
+        let output: Value = serde_json::from_str(lines[2]).unwrap();
+        assert_eq!(output, serde_json::json!([1.25, "o", "/dev/sda1 90%\r\n"]));
+        assert_eq!(lines.len(), 4, "header, command, output, exit");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,389 @@
+//! Run reports: the result of a CommandSet run on one or more profiles, written to a
+//! timestamped JSON, plain text, or HTML file under `reports.dir` by `td run --report`
+//! and the TUI's `e` key. Reports include command output, so they are created with
+//! owner-only permissions where the platform supports it.
+
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use time::UtcOffset;
+
+use crate::cmdset_runner::CmdSetRunResult;
+use crate::error::Result;
+use crate::util::{format_timestamp_ms, now_ms};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Text,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Text => "txt",
+            Self::Html => "html",
+        }
+    }
+}
+
+/// One profile's part of a report. `result` is absent when the run could not start.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileRun {
+    pub profile_id: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<CmdSetRunResult>,
+}
+
+impl ProfileRun {
+    pub fn from_result(profile_id: &str, result: CmdSetRunResult) -> Self {
+        Self {
+            profile_id: profile_id.to_string(),
+            ok: result.ok,
+            exit_code: Some(result.exit_code),
+            error: None,
+            result: Some(result),
+        }
+    }
+
+    pub fn from_error(profile_id: &str, error: impl Into<String>) -> Self {
+        Self {
+            profile_id: profile_id.to_string(),
+            ok: false,
+            exit_code: None,
+            error: Some(error.into()),
+            result: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    pub cmdset_id: String,
+    pub generated_at: i64,
+    pub runs: Vec<ProfileRun>,
+}
+
+impl RunReport {
+    pub fn new(cmdset_id: &str, runs: Vec<ProfileRun>) -> Self {
+        Self {
+            cmdset_id: cmdset_id.to_string(),
+            generated_at: now_ms(),
+            runs,
+        }
+    }
+
+    pub fn ok_count(&self) -> usize {
+        self.runs.iter().filter(|run| run.ok).count()
+    }
+
+    pub fn render(&self, format: ReportFormat, offset: UtcOffset) -> Result<String> {
+        match format {
+            ReportFormat::Json => Ok(serde_json::to_string_pretty(self)? + "\n"),
+            ReportFormat::Text => Ok(self.render_text(offset)),
+            ReportFormat::Html => Ok(self.render_html(offset)),
+        }
+    }
+
+    /// Writes the report to a new file in `dir` and returns its path. Existing reports
+    /// are never overwritten.
+    pub fn write(&self, dir: &Path, format: ReportFormat, offset: UtcOffset) -> Result<PathBuf> {
+        let body = self.render(format, offset)?;
+        write_new_file(dir, &self.file_stem(), format.extension(), &body)
+    }
+
+    /// `<profile>-<cmdset>-<UTC timestamp>` for single runs, `bulk-<cmdset>-...` otherwise.
+    fn file_stem(&self) -> String {
+        let stamp = file_stamp(self.generated_at);
+        let subject = match self.runs.as_slice() {
+            [run] => run.profile_id.as_str(),
+            _ => "bulk",
+        };
+        format!(
+            "{}-{}-{stamp}",
+            file_safe(subject),
+            file_safe(&self.cmdset_id)
+        )
+    }
+
+    fn render_text(&self, offset: UtcOffset) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "CommandSet run report: {}", self.cmdset_id);
+        let _ = writeln!(
+            out,
+            "Generated: {}",
+            format_timestamp_ms(self.generated_at, offset)
+        );
+        let _ = writeln!(
+            out,
+            "Profiles: {} ({} ok, {} failed)",
+            self.runs.len(),
+            self.ok_count(),
+            self.runs.len() - self.ok_count()
+        );
+        for run in &self.runs {
+            let _ = writeln!(out, "\n== {}: {} ==", run.profile_id, run_status(run));
+            if let Some(error) = &run.error {
+                let _ = writeln!(out, "error: {error}");
+            }
+            for step in run.result.iter().flat_map(|result| &result.steps) {
+                let _ = writeln!(
+                    out,
+                    "\n-- step {}: {} ({}, exit {}, {} ms)",
+                    step.ord,
+                    step.cmd,
+                    if step.ok { "ok" } else { "failed" },
+                    step.exit_code,
+                    step.duration_ms
+                );
+                push_text_block(&mut out, "stdout", &step.stdout);
+                push_text_block(&mut out, "stderr", &step.stderr);
+                if !step.parsed.is_null() {
+                    let parsed = serde_json::to_string_pretty(&step.parsed).unwrap_or_default();
+                    push_text_block(&mut out, "parsed", &parsed);
+                }
+            }
+        }
+        out
+    }
+
+    fn render_html(&self, offset: UtcOffset) -> String {
+        let mut out = String::new();
+        let title = format!("CommandSet run report: {}", html_escape(&self.cmdset_id));
+        let _ = write!(
+            out,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+             <style>body{{font-family:sans-serif}}pre{{background:#f4f4f4;padding:.5em;overflow:auto}}\
+             .ok{{color:#1a7f37}}.failed{{color:#cf222e}}td,th{{padding:.2em .8em;text-align:left}}</style>\n\
+             </head>\n<body>\n<h1>{title}</h1>\n<p>Generated {} &middot; {} profile(s), {} ok, {} failed</p>\n",
+            html_escape(&format_timestamp_ms(self.generated_at, offset)),
+            self.runs.len(),
+            self.ok_count(),
+            self.runs.len() - self.ok_count()
+        );
+        out.push_str("<table>\n<tr><th>Profile</th><th>Status</th></tr>\n");
+        for run in &self.runs {
+            let _ = writeln!(
+                out,
+                "<tr><td><a href=\"#{id}\">{id}</a></td><td class=\"{class}\">{status}</td></tr>",
+                id = html_escape(&run.profile_id),
+                class = if run.ok { "ok" } else { "failed" },
+                status = html_escape(&run_status(run)),
+            );
+        }
+        out.push_str("</table>\n");
+        for run in &self.runs {
+            let _ = writeln!(
+                out,
+                "<h2 id=\"{id}\">{id}: <span class=\"{class}\">{status}</span></h2>",
+                id = html_escape(&run.profile_id),
+                class = if run.ok { "ok" } else { "failed" },
+                status = html_escape(&run_status(run)),
+            );
+            if let Some(error) = &run.error {
+                let _ = writeln!(out, "<p class=\"failed\">{}</p>", html_escape(error));
+            }
+            for step in run.result.iter().flat_map(|result| &result.steps) {
+                let _ = writeln!(
+                    out,
+                    "<h3>Step {}: <code>{}</code> &middot; {}, exit {}, {} ms</h3>",
+                    step.ord,
+                    html_escape(&step.cmd),
+                    if step.ok { "ok" } else { "failed" },
+                    step.exit_code,
+                    step.duration_ms
+                );
+                push_html_block(&mut out, "stdout", &step.stdout);
+                push_html_block(&mut out, "stderr", &step.stderr);
+                if !step.parsed.is_null() {
+                    let parsed = serde_json::to_string_pretty(&step.parsed).unwrap_or_default();
+                    push_html_block(&mut out, "parsed", &parsed);
+                }
+            }
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+fn run_status(run: &ProfileRun) -> String {
+    let label = if run.ok { "ok" } else { "failed" };
+    match (&run.result, run.exit_code) {
+        (Some(result), _) => format!(
+            "{label} (exit {}, {} ms)",
+            result.exit_code, result.duration_ms
+        ),
+        (None, Some(code)) => format!("{label} (exit {code})"),
+        (None, None) => label.to_string(),
+    }
+}
+
+fn push_text_block(out: &mut String, label: &str, body: &str) {
+    if body.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "[{label}]");
+    out.push_str(body);
+    if !body.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+fn push_html_block(out: &mut String, label: &str, body: &str) {
+    if body.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "<p>{label}</p>\n<pre>{}</pre>", html_escape(body));
+}
+
+fn html_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            ch => out.push(ch),
+        }
+    }
+    out
+}
+
+/// `<stem>.<extension>` in `dir`, or `<stem>-2.<extension>` and so on when that exists,
+/// created with owner-only permissions. Returns the path written.
+pub fn write_new_file(dir: &Path, stem: &str, extension: &str, body: &str) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let mut attempt = 1;
+    loop {
+        let name = if attempt == 1 {
+            format!("{stem}.{extension}")
+        } else {
+            format!("{stem}-{attempt}.{extension}")
+        };
+        let path = dir.join(name);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        match options.open(&path) {
+            Ok(mut file) => {
+                file.write_all(body.as_bytes())?;
+                return Ok(path);
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// `ts_ms` as a UTC `YYYYMMDD-HHMMSS` for file names.
+pub fn file_stamp(ts_ms: i64) -> String {
+    format_timestamp_ms(ts_ms, UtcOffset::UTC)
+        .chars()
+        .take(19)
+        .filter_map(|ch| match ch {
+            '-' | ':' => None,
+            ' ' => Some('-'),
+            ch => Some(ch),
+        })
+        .collect()
+}
+
+/// `value` with everything but ASCII letters, digits, `-`, and `_` replaced by `_`.
+pub fn file_safe(value: &str) -> String {
+    value
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmdset_runner::CmdStepRunResult;
+
+    fn sample_report() -> RunReport {
+        let result = CmdSetRunResult {
+            ok: true,
+            exit_code: 0,
+            duration_ms: 12,
+            stdout: "<up>\n".into(),
+            stderr: String::new(),
+            steps: vec![CmdStepRunResult {
+                ord: 1,
+                cmd: "uptime".into(),
+                ok: true,
+                exit_code: 0,
+                duration_ms: 12,
+                stdout: "<up>\n".into(),
+                stderr: String::new(),
+                parsed: serde_json::json!({"load": 0.5}),
+            }],
+        };
+        RunReport {
+            cmdset_id: "c_check".into(),
+            generated_at: 1_700_000_000_000,
+            runs: vec![
+                ProfileRun::from_result("p_web", result),
+                ProfileRun::from_error("p/db", "profile not found"),
+            ],
+        }
+    }
+
+    #[test]
+    fn renders_and_writes_reports_in_each_format() {
+        let report = sample_report();
+        let json: serde_json::Value =
+            serde_json::from_str(&report.render(ReportFormat::Json, UtcOffset::UTC).unwrap())
+                .unwrap();
+        assert_eq!(json["runs"][0]["result"]["steps"][0]["parsed"]["load"], 0.5);
+        assert_eq!(json["runs"][1]["error"], "profile not found");
+
+        let text = report.render(ReportFormat::Text, UtcOffset::UTC).unwrap();
+        assert!(text.contains("Profiles: 2 (1 ok, 1 failed)"));
+        assert!(text.contains("-- step 1: uptime (ok, exit 0, 12 ms)\n[stdout]\n<up>\n"));
+        assert!(text.contains("[parsed]\n{\n  \"load\": 0.5\n}\n"));
+
+        let html = report.render(ReportFormat::Html, UtcOffset::UTC).unwrap();
+        assert!(html.contains("<pre>&lt;up&gt;\n</pre>"));
+        assert!(!html.contains("<up>"));
+
+        let dir = std::env::temp_dir().join(format!("teradock-reports-{}", now_ms()));
+        let first = report
+            .write(&dir, ReportFormat::Text, UtcOffset::UTC)
+            .unwrap();
+        let second = report
+            .write(&dir, ReportFormat::Text, UtcOffset::UTC)
+            .unwrap();
+        assert_eq!(
+            first.file_name().unwrap(),
+            "bulk-c_check-20231114-221320.txt"
+        );
+        assert_eq!(
+            second.file_name().unwrap(),
+            "bulk-c_check-20231114-221320-2.txt"
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+}
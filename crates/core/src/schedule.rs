@@ -0,0 +1,723 @@
+//! Scheduled CommandSet runs: `td schedule add` stores a cmdset, a profile selector, and a
+//! five-field cron expression, and `td daemon` (or `td schedule run-due`) runs whatever is
+//! due. Each profile run is logged as a normal `run` entry, and every firing adds one
+//! `schedule_run` entry summarising which profiles passed, failed, or were skipped.
+//!
+//! Cron times are evaluated in the `ui.timezone` offset. Nobody is around to confirm a
+//! scheduled run, so critical profiles are skipped unless the schedule was created with
+//! `allow_critical`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Instant;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use time::{Date, Duration, Month, OffsetDateTime, UtcOffset};
+
+use crate::cmdset::CmdSetStore;
+use crate::cmdset_runner::run_cmdset_unattended;
+use crate::error::{CoreError, Result};
+use crate::hooks::{self, HookContext};
+use crate::maintenance;
+use crate::notify::{self, Notification};
+use crate::oplog::{self, OpLogEntry};
+use crate::policy::{self, PolicyAction};
+use crate::profile::{DangerLevel, Profile, ProfileFilters, ProfileStore, ProfileType};
+use crate::settings;
+
+/// Upper bound on the minutes [`CronExpr::next_after`] steps through; more than enough for
+/// any satisfiable expression (Feb 29 recurs within eight years).
+const MAX_SEARCH_STEPS: usize = 200_000;
+
+/// A parsed five-field cron expression: minute, hour, day of month, month, day of week.
+///
+/// Fields accept `*`, numbers, `a-b` ranges, `,` lists, and `/n` steps; day of week is
+/// 0-7 with both 0 and 7 meaning Sunday. `@hourly`, `@daily`, `@weekly`, `@monthly`, and
+/// `@yearly` are accepted as shorthands. As in Vixie cron, when both day fields are
+/// restricted a day matches if either does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl CronExpr {
+    pub fn parse(raw: &str) -> Result<Self> {
+        let raw = raw.trim();
+        let expanded = match raw.to_ascii_lowercase().as_str() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            _ => raw,
+        };
+        let fields = expanded.split_whitespace().collect::<Vec<_>>();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(invalid_cron(raw, "expected 5 fields"));
+        };
+        let mut weekdays = parse_field(raw, weekday, 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays & !(1 << 7)) | 1;
+        }
+        Ok(Self {
+            minutes: parse_field(raw, minute, 0, 59)?,
+            hours: parse_field(raw, hour, 0, 23)?,
+            days: parse_field(raw, day, 1, 31)?,
+            months: parse_field(raw, month, 1, 12)?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+
+    /// The first matching minute strictly after `after_ms`, evaluated at `offset`.
+    pub fn next_after(&self, after_ms: i64, offset: UtcOffset) -> Option<i64> {
+        let after = OffsetDateTime::from_unix_timestamp(after_ms.div_euclid(1000))
+            .ok()?
+            .to_offset(offset);
+        let mut at = after.replace_second(0).ok()?.replace_nanosecond(0).ok()? + Duration::MINUTE;
+        for _ in 0..MAX_SEARCH_STEPS {
+            if !has(self.months, u8::from(at.month())) {
+                at = first_of_next_month(at.date())?
+                    .midnight()
+                    .assume_offset(offset);
+            } else if !self.day_matches(at.date()) {
+                at = at.date().next_day()?.midnight().assume_offset(offset);
+            } else if !has(self.hours, at.hour()) {
+                at = at.replace_minute(0).ok()? + Duration::HOUR;
+            } else if !has(self.minutes, at.minute()) {
+                at += Duration::MINUTE;
+            } else {
+                return Some(at.unix_timestamp() * 1000);
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, date: Date) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().number_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+}
+
+fn has(mask: u64, value: u8) -> bool {
+    mask & (1 << value) != 0
+}
+
+fn first_of_next_month(date: Date) -> Option<Date> {
+    let (year, month) = match date.month() {
+        Month::December => (date.year() + 1, Month::January),
+        month => (date.year(), month.next()),
+    };
+    Date::from_calendar_date(year, month, 1).ok()
+}
+
+fn parse_field(raw: &str, field: &str, min: u8, max: u8) -> Result<u64> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step)),
+            None => (part, None),
+        };
+        let step = match step {
+            Some(step) => step
+                .parse::<u8>()
+                .ok()
+                .filter(|step| *step > 0)
+                .ok_or_else(|| invalid_cron(raw, &format!("invalid step in '{part}'")))?,
+            None => 1,
+        };
+        let value = |text: &str| {
+            text.parse::<u8>()
+                .ok()
+                .filter(|value| (min..=max).contains(value))
+                .ok_or_else(|| {
+                    invalid_cron(
+                        raw,
+                        &format!("'{text}' is outside {min}-{max} in '{field}'"),
+                    )
+                })
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (value(start)?, value(end)?),
+                None if step > 1 => (value(range)?, max),
+                None => {
+                    let single = value(range)?;
+                    (single, single)
+                }
+            },
+        };
+        if start > end {
+            return Err(invalid_cron(raw, &format!("empty range '{range}'")));
+        }
+        for value in (start..=end).step_by(usize::from(step)) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn invalid_cron(raw: &str, reason: &str) -> CoreError {
+    CoreError::InvalidSetting(format!("invalid cron expression '{raw}': {reason}"))
+}
+
+/// Which profiles a schedule runs on: `<profile_id>`, `group:<name>`, or `tag:<a>,<b>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleTarget {
+    Profile(String),
+    Group(String),
+    Tags(Vec<String>),
+}
+
+impl ScheduleTarget {
+    pub fn parse(raw: &str) -> Result<Self> {
+        let raw = raw.trim();
+        let target = if let Some(group) = raw.strip_prefix("group:") {
+            Self::Group(group.trim().to_string())
+        } else if let Some(tags) = raw.strip_prefix("tag:") {
+            Self::Tags(
+                tags.split(',')
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            )
+        } else {
+            Self::Profile(raw.to_string())
+        };
+        let empty = match &target {
+            Self::Profile(value) | Self::Group(value) => value.is_empty(),
+            Self::Tags(tags) => tags.is_empty(),
+        };
+        if empty {
+            return Err(CoreError::InvalidSetting(format!(
+                "invalid schedule target '{raw}': use <profile_id>, group:<name>, or tag:<a>,<b>"
+            )));
+        }
+        Ok(target)
+    }
+
+    /// Active SSH profiles the target selects right now.
+    pub fn resolve(&self, store: &ProfileStore) -> Result<Vec<Profile>> {
+        let profiles = match self {
+            Self::Profile(profile_id) => store
+                .get(profile_id)?
+                .filter(|profile| profile.archived_at.is_none())
+                .into_iter()
+                .collect(),
+            Self::Group(group) => store.list_filtered(&ProfileFilters {
+                group: Some(group.clone()),
+                ..ProfileFilters::default()
+            })?,
+            Self::Tags(tags) => store.list_filtered(&ProfileFilters {
+                tags: tags.clone(),
+                ..ProfileFilters::default()
+            })?,
+        };
+        Ok(profiles
+            .into_iter()
+            .filter(|profile| profile.profile_type == ProfileType::Ssh)
+            .collect())
+    }
+
+    /// Whether the target selects `profile`, by the same rules as [`Self::resolve`].
+    pub fn matches(&self, profile: &Profile) -> bool {
+        match self {
+            Self::Profile(profile_id) => profile.profile_id == *profile_id,
+            Self::Group(group) => profile
+                .group
+                .as_deref()
+                .is_some_and(|name| name.eq_ignore_ascii_case(group)),
+            Self::Tags(tags) => tags.iter().all(|tag| {
+                profile
+                    .tags
+                    .iter()
+                    .any(|have| have.eq_ignore_ascii_case(tag))
+            }),
+        }
+    }
+}
+
+impl fmt::Display for ScheduleTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Profile(profile_id) => write!(f, "{profile_id}"),
+            Self::Group(group) => write!(f, "group:{group}"),
+            Self::Tags(tags) => write!(f, "tag:{}", tags.join(",")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Schedule {
+    pub id: i64,
+    pub cmdset_id: String,
+    pub target: String,
+    pub cron: String,
+    pub enabled: bool,
+    pub allow_critical: bool,
+    pub next_run_at: Option<i64>,
+    pub last_run_at: Option<i64>,
+    pub last_ok: Option<bool>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewSchedule {
+    pub cmdset_id: String,
+    pub target: ScheduleTarget,
+    pub cron: String,
+    pub enabled: bool,
+    pub allow_critical: bool,
+}
+
+/// What one firing of a schedule did.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduleRun {
+    pub schedule_id: i64,
+    pub cmdset_id: String,
+    pub ok: Vec<String>,
+    pub failed: Vec<ScheduleFailure>,
+    /// Critical profiles left alone because the schedule does not allow them.
+    pub skipped: Vec<String>,
+    pub next_run_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduleFailure {
+    pub profile_id: String,
+    pub error: String,
+}
+
+pub fn add(conn: &Connection, new: NewSchedule, now_ms: i64) -> Result<Schedule> {
+    let cron = CronExpr::parse(&new.cron)?;
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM cmdsets WHERE cmdset_id = ?1",
+            params![new.cmdset_id],
+            |_| Ok(()),
+        )
+        .optional()?;
+    if exists.is_none() {
+        return Err(CoreError::NotFound(format!(
+            "cmdset not found: {}",
+            new.cmdset_id
+        )));
+    }
+    let next_run_at = if new.enabled {
+        cron.next_after(now_ms, settings::get_display_offset(conn)?)
+    } else {
+        None
+    };
+    conn.execute(
+        r#"
+        INSERT INTO schedules (cmdset_id, target, cron, enabled, allow_critical, next_run_at, created_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        "#,
+        params![
+            new.cmdset_id,
+            new.target.to_string(),
+            new.cron.trim(),
+            new.enabled,
+            new.allow_critical,
+            next_run_at,
+            now_ms
+        ],
+    )?;
+    find(conn, conn.last_insert_rowid())
+}
+
+pub fn list(conn: &Connection) -> Result<Vec<Schedule>> {
+    let mut stmt = conn.prepare(&format!("{SELECT_SCHEDULE} ORDER BY id"))?;
+    let rows = stmt.query_map([], schedule_from_row)?;
+    Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+}
+
+pub fn get(conn: &Connection, id: i64) -> Result<Option<Schedule>> {
+    Ok(conn
+        .query_row(
+            &format!("{SELECT_SCHEDULE} WHERE id = ?1"),
+            params![id],
+            schedule_from_row,
+        )
+        .optional()?)
+}
+
+fn find(conn: &Connection, id: i64) -> Result<Schedule> {
+    get(conn, id)?.ok_or_else(|| CoreError::NotFound(format!("schedule not found: {id}")))
+}
+
+pub fn remove(conn: &Connection, id: i64) -> Result<()> {
+    if conn.execute("DELETE FROM schedules WHERE id = ?1", params![id])? == 0 {
+        return Err(CoreError::NotFound(format!("schedule not found: {id}")));
+    }
+    Ok(())
+}
+
+/// Enables or disables schedule `id`. Enabling computes the next run from `now_ms`, so
+/// firings missed while it was off are not caught up.
+pub fn set_enabled(conn: &Connection, id: i64, enabled: bool, now_ms: i64) -> Result<Schedule> {
+    let schedule = find(conn, id)?;
+    let next_run_at = if enabled {
+        CronExpr::parse(&schedule.cron)?.next_after(now_ms, settings::get_display_offset(conn)?)
+    } else {
+        None
+    };
+    conn.execute(
+        "UPDATE schedules SET enabled = ?2, next_run_at = ?3 WHERE id = ?1",
+        params![id, enabled, next_run_at],
+    )?;
+    find(conn, id)
+}
+
+/// Enabled schedules whose next run is at or before `now_ms`.
+pub fn due(conn: &Connection, now_ms: i64) -> Result<Vec<Schedule>> {
+    let mut stmt = conn.prepare(&format!(
+        "{SELECT_SCHEDULE} WHERE enabled = 1 AND next_run_at IS NOT NULL AND next_run_at <= ?1 \
+         ORDER BY next_run_at, id"
+    ))?;
+    let rows = stmt.query_map(params![now_ms], schedule_from_row)?;
+    Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+}
+
+/// The earliest upcoming run per CommandSet across its enabled schedules.
+pub fn next_runs_by_cmdset(conn: &Connection) -> Result<HashMap<String, i64>> {
+    let mut stmt = conn.prepare(
+        "SELECT cmdset_id, MIN(next_run_at) FROM schedules \
+         WHERE enabled = 1 AND next_run_at IS NOT NULL GROUP BY cmdset_id",
+    )?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    Ok(rows.collect::<std::result::Result<HashMap<_, _>, _>>()?)
+}
+
+/// Runs every due schedule once. A schedule that missed several firings (the daemon was
+/// down) runs once and then waits for its next firing after `now_ms`.
+pub fn run_due(
+    profile_store: &ProfileStore,
+    cmdset_store: &CmdSetStore,
+    now_ms: i64,
+) -> Result<Vec<ScheduleRun>> {
+    let conn = profile_store.conn();
+    let offset = settings::get_display_offset(conn)?;
+    let mut runs = Vec::new();
+    for schedule in due(conn, now_ms)? {
+        // Advance first so a crash mid-run does not fire the same slot again.
+        let next_run_at = CronExpr::parse(&schedule.cron)
+            .ok()
+            .and_then(|cron| cron.next_after(now_ms, offset));
+        conn.execute(
+            "UPDATE schedules SET next_run_at = ?2, last_run_at = ?3 WHERE id = ?1",
+            params![schedule.id, next_run_at, now_ms],
+        )?;
+        let run = fire(profile_store, cmdset_store, &schedule, next_run_at, now_ms)?;
+        conn.execute(
+            "UPDATE schedules SET last_ok = ?2 WHERE id = ?1",
+            params![schedule.id, run.failed.is_empty()],
+        )?;
+        runs.push(run);
+    }
+    Ok(runs)
+}
+
+fn fire(
+    profile_store: &ProfileStore,
+    cmdset_store: &CmdSetStore,
+    schedule: &Schedule,
+    next_run_at: Option<i64>,
+    now_ms: i64,
+) -> Result<ScheduleRun> {
+    let started = Instant::now();
+    let mut run = ScheduleRun {
+        schedule_id: schedule.id,
+        cmdset_id: schedule.cmdset_id.clone(),
+        ok: Vec::new(),
+        failed: Vec::new(),
+        skipped: Vec::new(),
+        next_run_at,
+    };
+    let profiles = ScheduleTarget::parse(&schedule.target)?.resolve(profile_store)?;
+    let commands: Vec<String> = cmdset_store
+        .list_steps(&schedule.cmdset_id)?
+        .into_iter()
+        .map(|step| step.cmd)
+        .collect();
+    let action = PolicyAction::Run {
+        cmdset_id: &schedule.cmdset_id,
+        commands: &commands,
+    };
+    for profile in profiles {
+        if profile.danger_level == DangerLevel::Critical && !schedule.allow_critical {
+            run.skipped.push(profile.profile_id);
+            continue;
+        }
+        // Nobody can give a reason at firing time, so a policy or maintenance window that
+        // wants one fails too.
+        let allowed = maintenance::active_for(profile_store.conn(), &profile, now_ms)
+            .and_then(|window| match window {
+                Some(window) => Err(window.denied(&profile, "scheduled run")),
+                None => Ok(()),
+            })
+            .and_then(|()| policy::check(profile_store.conn(), &profile, &action, now_ms))
+            .and_then(|verdict| {
+                if verdict.reason {
+                    return Err(CoreError::PolicyDenied(format!(
+                        "run on {} profile {} requires a reason; scheduled runs cannot give one",
+                        profile.danger_level, profile.profile_id
+                    )));
+                }
+                Ok(())
+            });
+        if let Err(err) = allowed {
+            run.failed.push(ScheduleFailure {
+                profile_id: profile.profile_id,
+                error: err.to_string(),
+            });
+            continue;
+        }
+        let context = HookContext {
+            op: "schedule",
+            profile: &profile,
+            cmdset_id: Some(&schedule.cmdset_id),
+        };
+        let outcome = hooks::around(
+            profile_store,
+            cmdset_store,
+            &context,
+            || run_cmdset_unattended(profile_store, cmdset_store, &profile, &schedule.cmdset_id),
+            |result| result.ok,
+        );
+        match outcome.map(|(result, _)| result) {
+            Ok(result) if result.ok => run.ok.push(profile.profile_id),
+            Ok(_) => run.failed.push(ScheduleFailure {
+                profile_id: profile.profile_id,
+                error: "command failed".to_string(),
+            }),
+            Err(err) => run.failed.push(ScheduleFailure {
+                profile_id: profile.profile_id,
+                error: err.to_string(),
+            }),
+        }
+    }
+    oplog::log_operation(
+        profile_store.conn(),
+        OpLogEntry {
+            op: "schedule_run".into(),
+            profile_id: None,
+            client_used: None,
+            ok: run.failed.is_empty(),
+            exit_code: None,
+            duration_ms: Some(started.elapsed().as_millis() as i64),
+            meta_json: Some(serde_json::json!({
+                "schedule_id": schedule.id,
+                "cmdset_id": schedule.cmdset_id,
+                "target": schedule.target,
+                "profiles_ok": run.ok,
+                "profiles_failed": run.failed,
+                "profiles_skipped": run.skipped,
+            })),
+        },
+    )?;
+    let failed: Vec<String> = run
+        .failed
+        .iter()
+        .map(|failure| failure.profile_id.clone())
+        .collect();
+    // Delivery failures are logged by the notifier; none of them fail the schedule.
+    if let Err(err) = notify::dispatch(
+        profile_store.conn(),
+        &Notification::summary("schedule", &schedule.cmdset_id, run.ok.len(), &failed),
+    ) {
+        tracing::warn!("schedule {} notification skipped: {err}", schedule.id);
+    }
+    Ok(run)
+}
+
+const SELECT_SCHEDULE: &str = "SELECT id, cmdset_id, target, cron, enabled, allow_critical, \
+     next_run_at, last_run_at, last_ok, created_at FROM schedules";
+
+fn schedule_from_row(row: &Row<'_>) -> rusqlite::Result<Schedule> {
+    Ok(Schedule {
+        id: row.get("id")?,
+        cmdset_id: row.get("cmdset_id")?,
+        target: row.get("target")?,
+        cron: row.get("cron")?,
+        enabled: row.get::<_, i64>("enabled")? != 0,
+        allow_critical: row.get::<_, i64>("allow_critical")? != 0,
+        next_run_at: row.get("next_run_at")?,
+        last_run_at: row.get("last_run_at")?,
+        last_ok: row.get::<_, Option<i64>>("last_ok")?.map(|ok| ok != 0),
+        created_at: row.get("created_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn ms(at: OffsetDateTime) -> i64 {
+        at.unix_timestamp() * 1000
+    }
+
+    #[test]
+    fn computes_next_cron_runs() {
+        let utc = UtcOffset::UTC;
+        // Wednesday.
+        let now = ms(datetime!(2026-10-14 10:07:30 UTC));
+        let next = |expr: &str| CronExpr::parse(expr).unwrap().next_after(now, utc);
+
+        assert_eq!(
+            next("*/15 * * * *"),
+            Some(ms(datetime!(2026-10-14 10:15 UTC)))
+        );
+        assert_eq!(next("@daily"), Some(ms(datetime!(2026-10-15 0:00 UTC))));
+        assert_eq!(next("30 2 * * 7"), Some(ms(datetime!(2026-10-18 2:30 UTC))));
+        assert_eq!(
+            next("0 9 1-5 * *"),
+            Some(ms(datetime!(2026-11-01 9:00 UTC)))
+        );
+        // Day of month OR day of week when both are restricted: the 20th or a Friday.
+        assert_eq!(next("0 0 20 * 5"), Some(ms(datetime!(2026-10-16 0:00 UTC))));
+        assert_eq!(next("0 0 29 2 *"), Some(ms(datetime!(2028-02-29 0:00 UTC))));
+        assert_eq!(next("0 0 31 2 *"), None);
+
+        let tokyo = UtcOffset::from_hms(9, 0, 0).unwrap();
+        assert_eq!(
+            CronExpr::parse("0 0 * * *").unwrap().next_after(now, tokyo),
+            Some(ms(datetime!(2026-10-15 0:00 +9)))
+        );
+
+        for bad in [
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "x * * * *",
+        ] {
+            assert!(CronExpr::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn due_schedules_run_skip_critical_profiles_and_advance() {
+        use crate::cmdset::{NewCmdSet, NewCmdStep, StepOnError};
+        use crate::doctor::ClientOverrides;
+        use crate::parser::ParserSpec;
+        use crate::profile::NewProfile;
+        use std::os::unix::fs::PermissionsExt;
+
+        let base = std::env::temp_dir().join(format!(
+            "teradock-schedule-{}-{}",
+            std::process::id(),
+            crate::util::now_ms()
+        ));
+        std::fs::create_dir_all(&base).unwrap();
+        let fake_ssh = base.join("ssh");
+        std::fs::write(&fake_ssh, "#!/bin/sh\nexit 0\n").unwrap();
+        std::fs::set_permissions(&fake_ssh, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let db_path = base.join("td.db");
+        let profiles = ProfileStore::new(crate::db::init_connection_at(&db_path).unwrap());
+        let mut cmdsets = CmdSetStore::new(crate::db::init_connection_at(&db_path).unwrap());
+        for (profile_id, danger_level) in [
+            ("p_app", DangerLevel::Normal),
+            ("p_db", DangerLevel::Critical),
+        ] {
+            profiles
+                .insert(NewProfile {
+                    profile_id: Some(profile_id.to_string()),
+                    name: profile_id.to_string(),
+                    profile_type: ProfileType::Ssh,
+                    host: "example.com".to_string(),
+                    port: 22,
+                    user: "ops".to_string(),
+                    danger_level,
+                    group: None,
+                    tags: vec!["nightly".to_string()],
+                    note: None,
+                    initial_send: None,
+                    client_overrides: Some(ClientOverrides {
+                        ssh: Some(fake_ssh.to_string_lossy().into_owned()),
+                        ..ClientOverrides::default()
+                    }),
+                })
+                .unwrap();
+        }
+        cmdsets
+            .insert(NewCmdSet {
+                cmdset_id: Some("c_check".to_string()),
+                name: "check".to_string(),
+                vars: None,
+                steps: vec![NewCmdStep {
+                    cmd: "uptime".to_string(),
+                    timeout_ms: None,
+                    on_error: StepOnError::Stop,
+                    parser_spec: ParserSpec::Raw,
+                }],
+            })
+            .unwrap();
+        let conn = profiles.conn();
+        let schedule_for = |cmdset_id: &str| NewSchedule {
+            cmdset_id: cmdset_id.to_string(),
+            target: ScheduleTarget::parse("tag: nightly ").unwrap(),
+            cron: "@hourly".to_string(),
+            enabled: true,
+            allow_critical: false,
+        };
+        assert!(matches!(
+            add(conn, schedule_for("c_missing"), 0),
+            Err(CoreError::NotFound(_))
+        ));
+
+        let now = ms(datetime!(2026-10-14 10:07 UTC));
+        let schedule = add(conn, schedule_for("c_check"), now).unwrap();
+        assert_eq!(schedule.target, "tag:nightly");
+        let next = schedule.next_run_at.unwrap();
+        assert_eq!(next, ms(datetime!(2026-10-14 11:00 UTC)));
+        assert_eq!(next_runs_by_cmdset(conn).unwrap()["c_check"], next);
+        assert!(run_due(&profiles, &cmdsets, next - 1).unwrap().is_empty());
+
+        // Two hours late: runs once, then waits for the slot after now.
+        let late = next + 2 * 3_600_000 + 60_000;
+        let runs = run_due(&profiles, &cmdsets, late).unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].ok, vec!["p_app".to_string()]);
+        assert_eq!(runs[0].skipped, vec!["p_db".to_string()]);
+        assert!(runs[0].failed.is_empty());
+        let fired = get(conn, schedule.id).unwrap().unwrap();
+        assert_eq!(fired.last_run_at, Some(late));
+        assert_eq!(fired.last_ok, Some(true));
+        assert_eq!(fired.next_run_at, Some(ms(datetime!(2026-10-14 14:00 UTC))));
+        assert!(run_due(&profiles, &cmdsets, late).unwrap().is_empty());
+        let logged = oplog::get_operation(conn, conn.last_insert_rowid())
+            .unwrap()
+            .unwrap();
+        assert_eq!(logged.op, "schedule_run");
+
+        let disabled = set_enabled(conn, schedule.id, false, late).unwrap();
+        assert_eq!(disabled.next_run_at, None);
+        assert!(due(conn, i64::MAX).unwrap().is_empty());
+        remove(conn, schedule.id).unwrap();
+        assert!(matches!(
+            remove(conn, schedule.id),
+            Err(CoreError::NotFound(_))
+        ));
+        drop((profiles, cmdsets));
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
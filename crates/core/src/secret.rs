@@ -3,9 +3,14 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use zeroize::Zeroizing;
 
+use crate::clipboard::{self, Clipboard, ClipboardExpiry};
 use crate::crypto::{decrypt, derive_key, encrypt, random_bytes, KdfParams, MasterKey};
-use crate::error::{CoreError, Result};
-use crate::settings::{get_setting, set_setting};
+use crate::error::{CoreError, Result, SecretError};
+use crate::oplog::{self, OpLogEntry};
+use crate::settings::{
+    clear_setting_scoped, get_clipboard_clear_after, get_setting, set_setting, SettingScope,
+};
+use crate::totp;
 use crate::util::now_ms;
 use common::id::{generate_id, normalize_id, validate_id};
 use rusqlite::{params, Connection};
@@ -13,6 +18,8 @@ use rusqlite::{params, Connection};
 const KEY_SALT: &str = "master_salt";
 const KEY_KDF_PARAMS: &str = "master_kdf_params";
 const KEY_CHECK: &str = "master_check";
+const KEY_LOCK_PIN: &str = "lock_pin";
+const MIN_LOCK_PIN_LEN: usize = 4;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct CheckToken {
@@ -61,7 +68,7 @@ impl MasterState {
         let salt = match get_setting(conn, KEY_SALT)? {
             Some(s) => B64
                 .decode(s.as_bytes())
-                .map_err(|e| CoreError::Crypto(e.to_string()))?,
+                .map_err(|e| CoreError::Secret(SecretError::Crypto(e.to_string())))?,
             None => return Ok(None),
         };
         let params = match get_setting(conn, KEY_KDF_PARAMS)? {
@@ -83,22 +90,93 @@ impl MasterState {
         let key = derive_key(password, &self.salt, &self.params)?;
         let nonce_bytes = B64
             .decode(self.check.nonce.as_bytes())
-            .map_err(|e| CoreError::Crypto(e.to_string()))?;
+            .map_err(|e| CoreError::Secret(SecretError::Crypto(e.to_string())))?;
         let cipher_bytes = B64
             .decode(self.check.ciphertext.as_bytes())
-            .map_err(|e| CoreError::Crypto(e.to_string()))?;
+            .map_err(|e| CoreError::Secret(SecretError::Crypto(e.to_string())))?;
         let decrypted = decrypt(key.as_ref(), &nonce_bytes, b"master-check", &cipher_bytes)?;
         if decrypted.is_empty() {
-            return Err(CoreError::MasterVerificationFailed);
+            return Err(CoreError::Secret(SecretError::MasterVerificationFailed));
         }
         Ok(key)
     }
 }
 
+/// What a secret is for, which decides what td does with it beyond `reveal`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum SecretKind {
+    /// A login password; `td connect` offers a linked one at the login prompt.
+    Password,
+    /// Fed to ssh-add when `ssh.agent_autoload` loads the profile's key.
+    SshKeyPassphrase,
+    /// Exported as an environment variable into CommandSet runs (`td run --secret-env`).
+    ApiToken,
+    /// A base32 TOTP seed; `td secret totp` prints its current code.
+    OtpSeed,
+    /// Any other kind name, stored as given with no typed behavior.
+    Other(String),
+}
+
+impl SecretKind {
+    pub const TYPED: [SecretKind; 4] = [
+        Self::Password,
+        Self::SshKeyPassphrase,
+        Self::ApiToken,
+        Self::OtpSeed,
+    ];
+
+    pub fn parse(raw: &str) -> Self {
+        let raw = raw.trim();
+        match raw.to_ascii_lowercase().replace('-', "_").as_str() {
+            "password" => Self::Password,
+            "ssh_key_passphrase" => Self::SshKeyPassphrase,
+            "api_token" => Self::ApiToken,
+            "otp_seed" => Self::OtpSeed,
+            _ => Self::Other(raw.to_string()),
+        }
+    }
+
+    /// The stored name; it is also part of each secret's AAD, so it never changes.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Password => "password",
+            Self::SshKeyPassphrase => "ssh_key_passphrase",
+            Self::ApiToken => "api_token",
+            Self::OtpSeed => "otp_seed",
+            Self::Other(name) => name,
+        }
+    }
+}
+
+impl std::fmt::Display for SecretKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<String> for SecretKind {
+    fn from(raw: String) -> Self {
+        Self::parse(&raw)
+    }
+}
+
+impl From<&str> for SecretKind {
+    fn from(raw: &str) -> Self {
+        Self::parse(raw)
+    }
+}
+
+impl From<SecretKind> for String {
+    fn from(kind: SecretKind) -> Self {
+        kind.as_str().to_string()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct NewSecret {
     pub secret_id: Option<String>,
-    pub kind: String,
+    pub kind: SecretKind,
     pub label: String,
     pub value: Zeroizing<String>,
     pub meta: Option<Value>,
@@ -107,7 +185,7 @@ pub struct NewSecret {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct SecretMetadata {
     pub secret_id: String,
-    pub kind: String,
+    pub kind: SecretKind,
     pub label: String,
     pub created_at: i64,
     pub updated_at: i64,
@@ -128,7 +206,7 @@ impl SecretStore {
 
     pub fn set_master(&self, password: &str) -> Result<()> {
         if self.is_master_set()? {
-            return Err(CoreError::MasterAlreadySet);
+            return Err(CoreError::Secret(SecretError::MasterAlreadySet));
         }
         let (state, _key) = MasterState::create(password.as_bytes())?;
         state.store(&self.conn)?;
@@ -136,10 +214,63 @@ impl SecretStore {
     }
 
     pub fn load_master(&self, password: &str) -> Result<MasterKey> {
-        let state = MasterState::load(&self.conn)?.ok_or(CoreError::MasterNotSet)?;
+        let state =
+            MasterState::load(&self.conn)?.ok_or(CoreError::Secret(SecretError::MasterNotSet))?;
         state
             .load_and_verify(password.as_bytes())
-            .map_err(|_| CoreError::MasterVerificationFailed)
+            .map_err(|_| CoreError::Secret(SecretError::MasterVerificationFailed))
+    }
+
+    /// Changes the master password. Every secret is re-encrypted under a key derived from
+    /// `new_password` with a fresh salt, in one transaction; returns how many there were.
+    pub fn rotate_master(&self, current_password: &str, new_password: &str) -> Result<usize> {
+        let current = self.load_master(current_password)?;
+        self.reencrypt_all(&current, new_password)
+    }
+
+    /// Rotates the key secrets are encrypted with while keeping the password: a fresh salt
+    /// derives a new key and every secret is re-encrypted under it.
+    pub fn rekey(&self, password: &str) -> Result<usize> {
+        let current = self.load_master(password)?;
+        self.reencrypt_all(&current, password)
+    }
+
+    /// A secret that fails to decrypt rolls the whole rotation back.
+    fn reencrypt_all(&self, current: &MasterKey, password: &str) -> Result<usize> {
+        let (state, key) = MasterState::create(password.as_bytes())?;
+        let tx = self.conn.unchecked_transaction()?;
+        let rows = {
+            let mut stmt = tx.prepare("SELECT secret_id, kind, ciphertext, nonce FROM secrets")?;
+            let mut rows = stmt.query([])?;
+            let mut collected = Vec::new();
+            while let Some(row) = rows.next()? {
+                collected.push((
+                    row.get::<_, String>("secret_id")?,
+                    row.get::<_, String>("kind")?,
+                    row.get::<_, Vec<u8>>("ciphertext")?,
+                    row.get::<_, Vec<u8>>("nonce")?,
+                ));
+            }
+            collected
+        };
+        for (secret_id, kind, ciphertext, nonce) in &rows {
+            let aad = Self::aad(secret_id, kind);
+            let plaintext = Zeroizing::new(decrypt(
+                current.as_ref(),
+                nonce,
+                aad.as_bytes(),
+                ciphertext,
+            )?);
+            let nonce = random_bytes::<24>();
+            let ciphertext = encrypt(key.as_ref(), &nonce, aad.as_bytes(), &plaintext)?;
+            tx.execute(
+                "UPDATE secrets SET ciphertext = ?1, nonce = ?2 WHERE secret_id = ?3",
+                params![ciphertext, nonce.to_vec(), secret_id],
+            )?;
+        }
+        state.store(&tx)?;
+        tx.commit()?;
+        Ok(rows.len())
     }
 
     pub fn add(&self, master: &MasterKey, input: NewSecret) -> Result<SecretMetadata> {
@@ -148,7 +279,10 @@ impl SecretStore {
             None => generate_id("s_"),
         };
         validate_id(&secret_id).map_err(CoreError::InvalidId)?;
-        let aad = Self::aad(&secret_id, &input.kind);
+        if input.kind == SecretKind::OtpSeed {
+            totp::decode_seed(&input.value)?;
+        }
+        let aad = Self::aad(&secret_id, input.kind.as_str());
         let nonce = random_bytes::<24>();
         let ciphertext = encrypt(
             master.as_ref(),
@@ -167,7 +301,7 @@ impl SecretStore {
             "#,
             params![
                 secret_id,
-                input.kind,
+                input.kind.as_str(),
                 input.label,
                 ciphertext,
                 nonce.to_vec(),
@@ -197,17 +331,91 @@ impl SecretStore {
         let mut rows = stmt.query([])?;
         let mut secrets = Vec::new();
         while let Some(row) = rows.next()? {
-            secrets.push(SecretMetadata {
-                secret_id: row.get("secret_id")?,
-                kind: row.get("kind")?,
-                label: row.get("label")?,
-                created_at: row.get("created_at")?,
-                updated_at: row.get("updated_at")?,
-            });
+            secrets.push(metadata_from_row(row)?);
         }
         Ok(secrets)
     }
 
+    pub fn get(&self, secret_id: &str) -> Result<SecretMetadata> {
+        let mut stmt = self.conn.prepare(
+            "SELECT secret_id, kind, label, created_at, updated_at FROM secrets WHERE secret_id = ?1",
+        )?;
+        let mut rows = stmt.query([secret_id])?;
+        match rows.next()? {
+            Some(row) => metadata_from_row(row),
+            None => Err(CoreError::Secret(SecretError::NotFound(
+                secret_id.to_string(),
+            ))),
+        }
+    }
+
+    /// Reveals a secret after checking it is of `kind`, for callers with typed behavior.
+    pub fn reveal_kind(
+        &self,
+        master: &MasterKey,
+        secret_id: &str,
+        kind: SecretKind,
+    ) -> Result<Zeroizing<String>> {
+        let found = self.get(secret_id)?.kind;
+        if found != kind {
+            return Err(CoreError::Secret(SecretError::WrongKind {
+                secret_id: secret_id.to_string(),
+                expected: kind.to_string(),
+                found: found.to_string(),
+            }));
+        }
+        Ok(Zeroizing::new(self.reveal(master, secret_id)?))
+    }
+
+    /// The current TOTP code of an `otp_seed` secret and the seconds it stays valid.
+    pub fn totp(
+        &self,
+        master: &MasterKey,
+        secret_id: &str,
+        unix_secs: u64,
+    ) -> Result<(String, u64)> {
+        let seed = self.reveal_kind(master, secret_id, SecretKind::OtpSeed)?;
+        Ok((
+            totp::code(&seed, unix_secs)?,
+            totp::remaining_secs(unix_secs),
+        ))
+    }
+
+    /// Copies a secret to the clipboard without showing it, clears it again after
+    /// `clipboard.clear_after_secs`, and logs a `secret_copy` entry (never the value).
+    /// `source` names the caller, e.g. `cli` or `tui`.
+    pub fn copy_to_clipboard(
+        &self,
+        master: &MasterKey,
+        secret_id: &str,
+        clipboard: &Clipboard,
+        source: &str,
+        profile_id: Option<&str>,
+    ) -> Result<Option<ClipboardExpiry>> {
+        let secret = self.get(secret_id)?;
+        let value = Zeroizing::new(self.reveal(master, secret_id)?);
+        let clear_after = get_clipboard_clear_after(&self.conn)?;
+        let expiry = clipboard::copy_sensitive(clipboard, &value, clear_after)?;
+        oplog::log_operation(
+            &self.conn,
+            OpLogEntry {
+                op: "secret_copy".to_string(),
+                profile_id: profile_id.map(str::to_string),
+                client_used: None,
+                ok: true,
+                exit_code: None,
+                duration_ms: None,
+                meta_json: Some(serde_json::json!({
+                    "secret_id": secret.secret_id,
+                    "kind": secret.kind,
+                    "source": source,
+                    "clear_after_secs": clear_after.map(|after| after.as_secs()),
+                })),
+            },
+        )?;
+        Ok(expiry)
+    }
+
     pub fn reveal(&self, master: &MasterKey, secret_id: &str) -> Result<String> {
         let mut stmt = self.conn.prepare(
             r#"
@@ -219,14 +427,19 @@ impl SecretStore {
         let mut rows = stmt.query([secret_id])?;
         let row = match rows.next()? {
             Some(row) => row,
-            None => return Err(CoreError::NotFound(secret_id.to_string())),
+            None => {
+                return Err(CoreError::Secret(SecretError::NotFound(
+                    secret_id.to_string(),
+                )))
+            }
         };
         let kind: String = row.get("kind")?;
         let aad = Self::aad(secret_id, &kind);
         let ciphertext: Vec<u8> = row.get("ciphertext")?;
         let nonce: Vec<u8> = row.get("nonce")?;
         let plaintext = decrypt(master.as_ref(), &nonce, aad.as_bytes(), &ciphertext)?;
-        let value = String::from_utf8(plaintext).map_err(|_| CoreError::DecryptionFailed)?;
+        let value = String::from_utf8(plaintext)
+            .map_err(|_| CoreError::Secret(SecretError::DecryptionFailed))?;
         Ok(value)
     }
 
@@ -237,11 +450,127 @@ impl SecretStore {
         Ok(count > 0)
     }
 
+    /// Links a secret to a profile so `td connect` can use it for that host.
+    pub fn link(&self, profile_id: &str, secret_id: &str) -> Result<()> {
+        if !self.exists(secret_id)? {
+            return Err(CoreError::Secret(SecretError::NotFound(
+                secret_id.to_string(),
+            )));
+        }
+        self.conn.execute(
+            "INSERT OR IGNORE INTO profile_secrets (profile_id, secret_id, created_at) \
+             VALUES (?1, ?2, ?3)",
+            params![profile_id, secret_id, now_ms()],
+        )?;
+        Ok(())
+    }
+
+    pub fn unlink(&self, profile_id: &str, secret_id: &str) -> Result<bool> {
+        let count = self.conn.execute(
+            "DELETE FROM profile_secrets WHERE profile_id = ?1 AND secret_id = ?2",
+            params![profile_id, secret_id],
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Secrets linked to the profile, oldest link first.
+    pub fn list_for_profile(&self, profile_id: &str) -> Result<Vec<SecretMetadata>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT s.secret_id, s.kind, s.label, s.created_at, s.updated_at
+            FROM profile_secrets ps
+            JOIN secrets s ON s.secret_id = ps.secret_id
+            WHERE ps.profile_id = ?1
+            ORDER BY ps.created_at ASC, s.secret_id ASC
+            "#,
+        )?;
+        let mut rows = stmt.query([profile_id])?;
+        let mut secrets = Vec::new();
+        while let Some(row) = rows.next()? {
+            secrets.push(metadata_from_row(row)?);
+        }
+        Ok(secrets)
+    }
+
+    /// The first secret of `kind` linked to the profile.
+    pub fn linked_of_kind(
+        &self,
+        profile_id: &str,
+        kind: SecretKind,
+    ) -> Result<Option<SecretMetadata>> {
+        Ok(self
+            .list_for_profile(profile_id)?
+            .into_iter()
+            .find(|secret| secret.kind == kind))
+    }
+
+    /// The linked secret `td connect` logs in with: the first one of kind `password`.
+    pub fn login_secret_for(&self, profile_id: &str) -> Result<Option<SecretMetadata>> {
+        self.linked_of_kind(profile_id, SecretKind::Password)
+    }
+
+    fn exists(&self, secret_id: &str) -> Result<bool> {
+        Ok(self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM secrets WHERE secret_id = ?1)",
+            [secret_id],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Stores a PIN that can resume a locked UI instead of the master password. Only a
+    /// KDF-derived check token is kept, never the PIN itself.
+    pub fn set_lock_pin(&self, pin: &str) -> Result<()> {
+        if pin.chars().count() < MIN_LOCK_PIN_LEN {
+            return Err(CoreError::InvalidSetting(format!(
+                "lock PIN must be at least {MIN_LOCK_PIN_LEN} characters"
+            )));
+        }
+        let (state, _key) = MasterState::create(pin.as_bytes())?;
+        set_setting(&self.conn, KEY_LOCK_PIN, &serde_json::to_string(&state)?)
+    }
+
+    pub fn clear_lock_pin(&self) -> Result<bool> {
+        let existed = self.is_lock_pin_set()?;
+        clear_setting_scoped(&self.conn, &SettingScope::global(), KEY_LOCK_PIN)?;
+        Ok(existed)
+    }
+
+    pub fn is_lock_pin_set(&self) -> Result<bool> {
+        Ok(get_setting(&self.conn, KEY_LOCK_PIN)?.is_some())
+    }
+
+    /// Returns whether a locked UI may resume with `input`: it must match the master
+    /// password or the lock PIN. With neither configured any input is accepted.
+    pub fn verify_unlock(&self, input: &str) -> Result<bool> {
+        let master = MasterState::load(&self.conn)?;
+        let pin = match get_setting(&self.conn, KEY_LOCK_PIN)? {
+            Some(raw) => Some(serde_json::from_str::<MasterState>(&raw)?),
+            None => None,
+        };
+        if master.is_none() && pin.is_none() {
+            return Ok(true);
+        }
+        Ok([master, pin]
+            .iter()
+            .flatten()
+            .any(|state| state.load_and_verify(input.as_bytes()).is_ok()))
+    }
+
     fn aad(secret_id: &str, kind: &str) -> String {
         format!("{secret_id}:{kind}")
     }
 }
 
+fn metadata_from_row(row: &rusqlite::Row<'_>) -> Result<SecretMetadata> {
+    Ok(SecretMetadata {
+        secret_id: row.get("secret_id")?,
+        kind: SecretKind::parse(&row.get::<_, String>("kind")?),
+        label: row.get("label")?,
+        created_at: row.get("created_at")?,
+        updated_at: row.get("updated_at")?,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -253,7 +582,7 @@ mod tests {
         let store = SecretStore::new(conn);
         assert!(!store.is_master_set().unwrap());
         let err = store.load_master("bad").unwrap_err();
-        assert!(matches!(err, CoreError::MasterNotSet));
+        assert!(matches!(err, CoreError::Secret(SecretError::MasterNotSet)));
     }
 
     #[test]
@@ -298,10 +627,238 @@ mod tests {
             )
             .unwrap();
         let err = store.load_master("wrong").unwrap_err();
-        assert!(matches!(err, CoreError::MasterVerificationFailed));
+        assert!(matches!(
+            err,
+            CoreError::Secret(SecretError::MasterVerificationFailed)
+        ));
         let err = store
             .reveal(&Zeroizing::new([0u8; 32]), &secret.secret_id)
             .unwrap_err();
-        assert!(matches!(err, CoreError::DecryptionFailed));
+        assert!(matches!(
+            err,
+            CoreError::Secret(SecretError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn linked_secrets_follow_their_profile_and_secret() {
+        let conn = init_in_memory().unwrap();
+        conn.execute(
+            "INSERT INTO profiles (profile_id, name, type, host, port, user, danger_level, \
+             tags_json, created_at, updated_at) \
+             VALUES ('p_db', 'db', 'ssh', 'db.example.com', 22, 'ops', 'normal', '[]', 0, 0)",
+            [],
+        )
+        .unwrap();
+        let store = SecretStore::new(conn);
+        store.set_master("pw").unwrap();
+        let master = store.load_master("pw").unwrap();
+        for (id, kind) in [("s_token", "token"), ("s_login", "password")] {
+            store
+                .add(
+                    &master,
+                    NewSecret {
+                        secret_id: Some(id.into()),
+                        kind: kind.into(),
+                        label: id.into(),
+                        value: Zeroizing::new("value".into()),
+                        meta: None,
+                    },
+                )
+                .unwrap();
+            store.link("p_db", id).unwrap();
+        }
+        store.link("p_db", "s_login").unwrap();
+        assert!(matches!(
+            store.link("p_db", "s_missing").unwrap_err(),
+            CoreError::Secret(SecretError::NotFound(_))
+        ));
+
+        let linked = store.list_for_profile("p_db").unwrap();
+        assert_eq!(linked.len(), 2);
+        assert_eq!(
+            store.login_secret_for("p_db").unwrap().unwrap().secret_id,
+            "s_login"
+        );
+        assert!(store.list_for_profile("p_web").unwrap().is_empty());
+
+        store.delete("s_login").unwrap();
+        assert!(store.login_secret_for("p_db").unwrap().is_none());
+        assert!(store.unlink("p_db", "s_token").unwrap());
+        assert!(!store.unlink("p_db", "s_token").unwrap());
+    }
+
+    #[test]
+    fn typed_kinds_check_their_values_and_callers() {
+        let conn = init_in_memory().unwrap();
+        let store = SecretStore::new(conn);
+        store.set_master("pw").unwrap();
+        let master = store.load_master("pw").unwrap();
+        let add = |id: &str, kind: &str, value: &str| {
+            store.add(
+                &master,
+                NewSecret {
+                    secret_id: Some(id.into()),
+                    kind: kind.into(),
+                    label: id.into(),
+                    value: Zeroizing::new(value.into()),
+                    meta: None,
+                },
+            )
+        };
+        add("s_otp", "OTP-Seed", "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").unwrap();
+        add("s_token", "api_token", "tok-123").unwrap();
+        add("s_legacy", "token", "old").unwrap();
+        assert!(add("s_bad", "otp_seed", "not base32!").is_err());
+
+        assert_eq!(store.get("s_otp").unwrap().kind, SecretKind::OtpSeed);
+        assert_eq!(
+            store.get("s_legacy").unwrap().kind,
+            SecretKind::Other("token".into())
+        );
+        assert_eq!(
+            store.totp(&master, "s_otp", 59).unwrap(),
+            ("287082".to_string(), 1)
+        );
+        let err = store.totp(&master, "s_token", 59).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "secret s_token has kind api_token, expected otp_seed"
+        );
+        assert_eq!(
+            store
+                .reveal_kind(&master, "s_token", SecretKind::ApiToken)
+                .unwrap()
+                .as_str(),
+            "tok-123"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copying_to_the_clipboard_logs_the_event_but_not_the_value() {
+        let store = SecretStore::new(init_in_memory().unwrap());
+        store.set_master("pw").unwrap();
+        let master = store.load_master("pw").unwrap();
+        store
+            .add(
+                &master,
+                NewSecret {
+                    secret_id: Some("s_db".into()),
+                    kind: "password".into(),
+                    label: "db".into(),
+                    value: Zeroizing::new("hunter2".into()),
+                    meta: None,
+                },
+            )
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("td-secret-clip-{}", std::process::id()));
+        let clipboard = Clipboard::with_commands(
+            vec![
+                "sh".into(),
+                "-c".into(),
+                format!("cat > '{}'", path.display()).into(),
+            ],
+            Some(vec![
+                "sh".into(),
+                "-c".into(),
+                format!("cat '{}'", path.display()).into(),
+            ]),
+        );
+
+        let expiry = store
+            .copy_to_clipboard(&master, "s_db", &clipboard, "cli", None)
+            .unwrap()
+            .expect("cleared after the default 30s");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hunter2");
+        assert!(expiry.clear_now());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        assert!(store
+            .copy_to_clipboard(&master, "s_missing", &clipboard, "cli", None)
+            .is_err());
+
+        let logged = oplog::list_operations(&store.conn, &oplog::OpLogQuery::default()).unwrap();
+        assert_eq!(logged.len(), 1);
+        assert_eq!(logged[0].op, "secret_copy");
+        let meta = serde_json::to_string(&logged[0].meta).unwrap();
+        assert!(meta.contains("\"clear_after_secs\":30"), "{meta}");
+        assert!(!meta.contains("hunter2"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn rotating_the_master_reencrypts_every_secret() {
+        let conn = init_in_memory().unwrap();
+        let store = SecretStore::new(conn);
+        store.set_master("old").unwrap();
+        let master = store.load_master("old").unwrap();
+        for (id, value) in [("s_db", "hunter2"), ("s_api", "tok-123")] {
+            store
+                .add(
+                    &master,
+                    NewSecret {
+                        secret_id: Some(id.into()),
+                        kind: "password".into(),
+                        label: id.into(),
+                        value: Zeroizing::new(value.into()),
+                        meta: None,
+                    },
+                )
+                .unwrap();
+        }
+
+        assert!(store.rotate_master("wrong", "new").is_err());
+        assert_eq!(store.rotate_master("old", "new").unwrap(), 2);
+        assert!(store.load_master("old").is_err());
+        let rotated = store.load_master("new").unwrap();
+        assert_eq!(store.reveal(&rotated, "s_db").unwrap(), "hunter2");
+        assert!(store.reveal(&master, "s_db").is_err());
+
+        assert_eq!(store.rekey("new").unwrap(), 2);
+        let rekeyed = store.load_master("new").unwrap();
+        assert_ne!(rekeyed.as_ref(), rotated.as_ref());
+        assert_eq!(store.reveal(&rekeyed, "s_api").unwrap(), "tok-123");
+
+        // A secret the current key cannot open leaves everything as it was.
+        store
+            .add(
+                &Zeroizing::new([7u8; 32]),
+                NewSecret {
+                    secret_id: Some("s_stray".into()),
+                    kind: "token".into(),
+                    label: "stray".into(),
+                    value: Zeroizing::new("x".into()),
+                    meta: None,
+                },
+            )
+            .unwrap();
+        assert!(store.rotate_master("new", "newer").is_err());
+        assert_eq!(
+            store
+                .reveal(&store.load_master("new").unwrap(), "s_api")
+                .unwrap(),
+            "tok-123"
+        );
+    }
+
+    #[test]
+    fn unlock_accepts_master_or_pin() {
+        let conn = init_in_memory().unwrap();
+        let store = SecretStore::new(conn);
+        assert!(store.verify_unlock("").unwrap());
+
+        store.set_master("right").unwrap();
+        assert!(store.verify_unlock("right").unwrap());
+        assert!(!store.verify_unlock("1234").unwrap());
+
+        assert!(store.set_lock_pin("12").is_err());
+        store.set_lock_pin("1234").unwrap();
+        assert!(store.is_lock_pin_set().unwrap());
+        assert!(store.verify_unlock("1234").unwrap());
+        assert!(store.verify_unlock("right").unwrap());
+        assert!(!store.verify_unlock("wrong").unwrap());
+
+        assert!(store.clear_lock_pin().unwrap());
+        assert!(!store.verify_unlock("1234").unwrap());
     }
 }
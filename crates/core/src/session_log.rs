@@ -1498,7 +1498,9 @@ pub fn get_session_log_in_dir(dir: &Path, session_id: &str) -> Result<SessionLog
     validate_id(session_id).map_err(CoreError::InvalidId)?;
     let metadata_path = dir.join(format!("{session_id}.json"));
     if !metadata_path.is_file() {
-        return Err(CoreError::NotFound(format!("session log: {session_id}")));
+        return Err(CoreError::NotFound(format!(
+            "session log not found: {session_id}"
+        )));
     }
     let raw = fs::read_to_string(metadata_path)?;
     Ok(serde_json::from_str(&raw)?)
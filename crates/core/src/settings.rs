@@ -1,11 +1,16 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::Duration;
 
+use directories::BaseDirs;
 use rusqlite::{params, Connection};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use time::UtcOffset;
 
 use crate::doctor::ClientOverrides;
 use crate::error::{CoreError, Result};
+use crate::profile::{DangerLevel, Profile};
 use crate::settings_registry;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
@@ -13,13 +18,17 @@ use crate::settings_registry;
 pub enum SettingScopeKind {
     Global,
     Env,
+    Group,
     Profile,
 }
 
+/// Where a setting is stored. A profile inherits from its group, then the current env,
+/// then global.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SettingScope {
     Global,
     Env(String),
+    Group(String),
     Profile(String),
 }
 
@@ -36,6 +45,7 @@ impl SettingScope {
         match self {
             SettingScope::Global => SettingScopeKind::Global,
             SettingScope::Env(_) => SettingScopeKind::Env,
+            SettingScope::Group(_) => SettingScopeKind::Group,
             SettingScope::Profile(_) => SettingScopeKind::Profile,
         }
     }
@@ -44,6 +54,7 @@ impl SettingScope {
         match self {
             SettingScope::Global => Cow::Borrowed("global"),
             SettingScope::Env(name) => Cow::Owned(format!("env:{name}")),
+            SettingScope::Group(name) => Cow::Owned(format!("group:{name}")),
             SettingScope::Profile(profile_id) => Cow::Owned(format!("profile:{profile_id}")),
         }
     }
@@ -60,6 +71,14 @@ impl SettingScope {
             }
             return Ok(Self::Env(name.trim().to_string()));
         }
+        if let Some(group) = raw.strip_prefix("group:") {
+            if group.trim().is_empty() {
+                return Err(CoreError::InvalidSetting(
+                    "group scope requires a name (group:NAME)".to_string(),
+                ));
+            }
+            return Ok(Self::Group(group.trim().to_string()));
+        }
         if let Some(profile_id) = raw.strip_prefix("profile:") {
             if profile_id.trim().is_empty() {
                 return Err(CoreError::InvalidSetting(
@@ -69,7 +88,7 @@ impl SettingScope {
             return Ok(Self::Profile(profile_id.trim().to_string()));
         }
         Err(CoreError::InvalidSetting(format!(
-            "unknown scope '{raw}' (expected global, env:NAME, or profile:ID)"
+            "unknown scope '{raw}' (expected global, env:NAME, group:NAME, or profile:ID)"
         )))
     }
 }
@@ -136,7 +155,7 @@ pub fn get_setting_resolved_with_override(
     }
     match scope {
         SettingScope::Global => get_setting_scoped(conn, scope, key),
-        SettingScope::Env(_) => {
+        SettingScope::Env(_) | SettingScope::Group(_) => {
             let scoped = get_setting_scoped(conn, scope, key)?;
             if scoped.is_some() {
                 Ok(scoped)
@@ -144,11 +163,17 @@ pub fn get_setting_resolved_with_override(
                 get_setting_scoped(conn, &SettingScope::Global, key)
             }
         }
-        SettingScope::Profile(_) => {
+        SettingScope::Profile(profile_id) => {
             let scoped = get_setting_scoped(conn, scope, key)?;
             if scoped.is_some() {
                 return Ok(scoped);
             }
+            if let Some(group) = get_profile_group(conn, profile_id)? {
+                let group_value = get_setting_scoped(conn, &SettingScope::Group(group), key)?;
+                if group_value.is_some() {
+                    return Ok(group_value);
+                }
+            }
             if let Some(env_name) = get_current_env(conn)? {
                 let env_scope = SettingScope::Env(env_name);
                 let env_value = get_setting_scoped(conn, &env_scope, key)?;
@@ -166,6 +191,7 @@ pub fn get_setting_resolved_with_override(
 pub enum ResolvedSettingSource {
     Command,
     Profile,
+    Group,
     Env,
     Global,
 }
@@ -175,6 +201,7 @@ impl ResolvedSettingSource {
         match self {
             Self::Command => "command",
             Self::Profile => "profile",
+            Self::Group => "group",
             Self::Env => "env",
             Self::Global => "global",
         }
@@ -186,6 +213,9 @@ pub struct ResolvedSettingDetail {
     pub key: String,
     pub command_value: Option<String>,
     pub profile_value: Option<String>,
+    /// The profile's group, whose `group:NAME` values it inherits.
+    pub group: Option<String>,
+    pub group_value: Option<String>,
     pub env_value: Option<String>,
     pub global_value: Option<String>,
     pub resolved_value: Option<String>,
@@ -199,6 +229,8 @@ pub fn resolve_settings_for_profile(
 ) -> Result<Vec<ResolvedSettingDetail>> {
     let env_name = get_current_env(conn)?;
     let profile_scope = SettingScope::Profile(profile_id.to_string());
+    let group = get_profile_group(conn, profile_id)?;
+    let group_scope = group.as_ref().map(|name| SettingScope::Group(name.clone()));
     let env_scope = env_name
         .as_ref()
         .map(|name| SettingScope::Env(name.to_string()));
@@ -207,6 +239,10 @@ pub fn resolve_settings_for_profile(
     for key in settings_registry::list_keys() {
         let command_value = command_overrides.and_then(|map| map.get(key)).cloned();
         let profile_value = get_setting_scoped(conn, &profile_scope, key)?;
+        let group_value = match &group_scope {
+            Some(scope) => get_setting_scoped(conn, scope, key)?,
+            None => None,
+        };
         let env_value = match &env_scope {
             Some(scope) => get_setting_scoped(conn, scope, key)?,
             None => None,
@@ -217,6 +253,8 @@ pub fn resolve_settings_for_profile(
             (Some(value.clone()), Some(ResolvedSettingSource::Command))
         } else if let Some(value) = &profile_value {
             (Some(value.clone()), Some(ResolvedSettingSource::Profile))
+        } else if let Some(value) = &group_value {
+            (Some(value.clone()), Some(ResolvedSettingSource::Group))
         } else if let Some(value) = &env_value {
             (Some(value.clone()), Some(ResolvedSettingSource::Env))
         } else if let Some(value) = &global_value {
@@ -229,6 +267,8 @@ pub fn resolve_settings_for_profile(
             key: key.to_string(),
             command_value,
             profile_value,
+            group: group.clone(),
+            group_value,
             env_value,
             global_value,
             resolved_value,
@@ -239,6 +279,17 @@ pub fn resolve_settings_for_profile(
     Ok(resolved)
 }
 
+/// The group a profile belongs to, if it exists and has one.
+pub fn get_profile_group(conn: &Connection, profile_id: &str) -> Result<Option<String>> {
+    let mut stmt = conn.prepare("SELECT \"group\" FROM profiles WHERE profile_id = ?1")?;
+    let mut rows = stmt.query(params![profile_id])?;
+    let group: Option<String> = match rows.next()? {
+        Some(row) => row.get(0)?,
+        None => None,
+    };
+    Ok(group.filter(|group| !group.trim().is_empty()))
+}
+
 pub fn get_current_env(conn: &Connection) -> Result<Option<String>> {
     get_setting_scoped(conn, &SettingScope::Global, "env.current")
 }
@@ -251,20 +302,72 @@ pub fn clear_current_env(conn: &Connection) -> Result<()> {
     clear_setting_scoped(conn, &SettingScope::Global, "env.current")
 }
 
+/// Envs made with `td env create`, kept so an env exists before it has any settings.
+const ENV_PRESETS_KEY: &str = "env.presets";
+
+fn created_env_names(conn: &Connection) -> Result<Vec<String>> {
+    match get_setting(conn, ENV_PRESETS_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn set_created_env_names(conn: &Connection, names: &[String]) -> Result<()> {
+    if names.is_empty() {
+        return clear_setting_scoped(conn, &SettingScope::Global, ENV_PRESETS_KEY);
+    }
+    set_setting(conn, ENV_PRESETS_KEY, &serde_json::to_string(names)?)
+}
+
+/// Every env that was created or has settings, sorted by name.
 pub fn list_env_names(conn: &Connection) -> Result<Vec<String>> {
     let mut stmt = conn
         .prepare("SELECT DISTINCT scope FROM settings WHERE scope LIKE 'env:%' ORDER BY scope")?;
     let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
-    let mut envs = Vec::new();
+    let mut envs = created_env_names(conn)?;
     for row in rows {
         let scope = row?;
         if let Some(name) = scope.strip_prefix("env:") {
             envs.push(name.to_string());
         }
     }
+    envs.sort();
+    envs.dedup();
     Ok(envs)
 }
 
+pub fn env_exists(conn: &Connection, name: &str) -> Result<bool> {
+    Ok(list_env_names(conn)?.iter().any(|env| env == name))
+}
+
+/// Records an empty env; `false` when it already exists.
+pub fn create_env(conn: &Connection, name: &str) -> Result<bool> {
+    if env_exists(conn, name)? {
+        return Ok(false);
+    }
+    let mut names = created_env_names(conn)?;
+    names.push(name.to_string());
+    set_created_env_names(conn, &names)?;
+    Ok(true)
+}
+
+/// Deletes an env and its settings, and stops using it if it is current. Returns the
+/// number of settings deleted.
+pub fn remove_env(conn: &Connection, name: &str) -> Result<usize> {
+    let scope = SettingScope::Env(name.to_string());
+    let removed = conn.execute(
+        "DELETE FROM settings WHERE scope = ?1",
+        params![scope.as_db()],
+    )?;
+    let mut names = created_env_names(conn)?;
+    names.retain(|env| env != name);
+    set_created_env_names(conn, &names)?;
+    if get_current_env(conn)?.as_deref() == Some(name) {
+        clear_current_env(conn)?;
+    }
+    Ok(removed)
+}
+
 pub fn list_settings_scoped(
     conn: &Connection,
     scope: &SettingScope,
@@ -288,6 +391,18 @@ pub fn get_client_overrides(conn: &Connection) -> Result<Option<ClientOverrides>
     }
 }
 
+/// The `client_overrides` setting a profile sees: its group's, or else the global one.
+pub fn get_client_overrides_for(
+    conn: &Connection,
+    profile_id: &str,
+) -> Result<Option<ClientOverrides>> {
+    let scope = SettingScope::Profile(profile_id.to_string());
+    match get_setting_resolved(conn, &scope, "client_overrides")? {
+        Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+        None => Ok(None),
+    }
+}
+
 pub fn set_client_overrides(conn: &Connection, overrides: &ClientOverrides) -> Result<()> {
     let json = serde_json::to_string(overrides)?;
     set_setting(conn, "client_overrides", &json)
@@ -301,6 +416,12 @@ pub fn get_ssh_auth_order(conn: &Connection) -> Result<Option<String>> {
     get_setting(conn, "ssh_auth_order")
 }
 
+/// `ssh_auth_order` resolved for a profile through its group, the current env, and global.
+pub fn get_ssh_auth_order_for(conn: &Connection, profile_id: &str) -> Result<Option<String>> {
+    let scope = SettingScope::Profile(profile_id.to_string());
+    get_setting_resolved(conn, &scope, "ssh_auth_order")
+}
+
 pub fn set_ssh_auth_order(conn: &Connection, order: &str) -> Result<()> {
     set_setting(conn, "ssh_auth_order", order)
 }
@@ -317,6 +438,16 @@ pub fn get_allow_insecure_transfers(conn: &Connection) -> Result<bool> {
     }
 }
 
+/// `allow_insecure_transfers` resolved for a profile through its group, the current env,
+/// and global.
+pub fn get_allow_insecure_transfers_for(conn: &Connection, profile_id: &str) -> Result<bool> {
+    let scope = SettingScope::Profile(profile_id.to_string());
+    Ok(
+        get_setting_resolved(conn, &scope, "allow_insecure_transfers")?
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case("true")),
+    )
+}
+
 pub fn set_allow_insecure_transfers(conn: &Connection, allow: bool) -> Result<()> {
     set_setting(
         conn,
@@ -328,3 +459,844 @@ pub fn set_allow_insecure_transfers(conn: &Connection, allow: bool) -> Result<()
 pub fn clear_allow_insecure_transfers(conn: &Connection) -> Result<()> {
     clear_setting_scoped(conn, &SettingScope::Global, "allow_insecure_transfers")
 }
+
+pub const RESULT_TAB_LAYOUTS_KEY: &str = "tui.result_tabs";
+
+/// Display preferences for one result tab (stdout, stderr, or parsed).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResultTabLayout {
+    pub wrap: bool,
+    /// Prefix each output line with the elapsed time of the step that produced it.
+    pub timestamps: bool,
+    /// Used by graphical front-ends; terminal output is always monospace.
+    pub monospace: bool,
+    pub max_lines: Option<usize>,
+}
+
+impl Default for ResultTabLayout {
+    fn default() -> Self {
+        Self {
+            wrap: true,
+            timestamps: false,
+            monospace: true,
+            max_lines: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResultTabLayouts {
+    pub stdout: ResultTabLayout,
+    pub stderr: ResultTabLayout,
+    pub parsed: ResultTabLayout,
+}
+
+pub fn parse_result_tab_layouts(raw: &str) -> Result<ResultTabLayouts> {
+    serde_json::from_str(raw)
+        .map_err(|err| CoreError::InvalidSetting(format!("invalid result tab layouts: {err}")))
+}
+
+pub fn get_result_tab_layouts(conn: &Connection) -> Result<ResultTabLayouts> {
+    match get_setting(conn, RESULT_TAB_LAYOUTS_KEY)? {
+        Some(raw) => parse_result_tab_layouts(&raw),
+        None => Ok(ResultTabLayouts::default()),
+    }
+}
+
+pub fn set_result_tab_layouts(conn: &Connection, layouts: &ResultTabLayouts) -> Result<()> {
+    let json = serde_json::to_string(layouts)?;
+    set_setting(conn, RESULT_TAB_LAYOUTS_KEY, &json)
+}
+
+pub const PROFILE_LIST_LAYOUT_KEY: &str = "tui.profile_list";
+
+/// A field shown after the profile name in the TUI profile list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileColumn {
+    Id,
+    /// `user@host:port`.
+    Endpoint,
+    User,
+    Host,
+    Port,
+    Type,
+    Danger,
+    Group,
+    Tags,
+    LastUsed,
+    Note,
+    Archived,
+}
+
+/// What the TUI profile list is grouped under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileGrouping {
+    #[default]
+    None,
+    Group,
+    /// Under each of its tags; a profile with several tags is listed once per tag.
+    Tag,
+}
+
+/// Which fields the TUI profile list shows, in order, in normal and wide mode.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProfileListLayout {
+    pub columns: Vec<ProfileColumn>,
+    pub wide_columns: Vec<ProfileColumn>,
+    pub wide: bool,
+    /// Longest value shown per field outside wide mode; longer values are cut with `…`.
+    /// `0` disables truncation.
+    pub max_width: usize,
+    pub group_by: ProfileGrouping,
+    /// Collapsed headers, as `group:<name>` or `tag:<name>`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub collapsed: Vec<String>,
+    /// Protocol and danger badges before each name, with high and critical rows tinted.
+    pub badges: bool,
+}
+
+impl Default for ProfileListLayout {
+    fn default() -> Self {
+        use ProfileColumn::*;
+        Self {
+            columns: vec![Id, Endpoint, Type, Danger, Group, Tags, Archived],
+            wide_columns: vec![
+                Id, Endpoint, Type, Danger, Group, Tags, LastUsed, Note, Archived,
+            ],
+            wide: false,
+            max_width: 24,
+            group_by: ProfileGrouping::None,
+            collapsed: Vec::new(),
+            badges: true,
+        }
+    }
+}
+
+impl ProfileListLayout {
+    /// Columns for the current mode.
+    pub fn active_columns(&self) -> &[ProfileColumn] {
+        if self.wide {
+            &self.wide_columns
+        } else {
+            &self.columns
+        }
+    }
+
+    /// Truncation limit for the current mode; wide mode never truncates.
+    pub fn active_max_width(&self) -> Option<usize> {
+        (!self.wide && self.max_width > 0).then_some(self.max_width)
+    }
+}
+
+pub fn parse_profile_list_layout(raw: &str) -> Result<ProfileListLayout> {
+    serde_json::from_str(raw)
+        .map_err(|err| CoreError::InvalidSetting(format!("invalid profile list layout: {err}")))
+}
+
+pub fn get_profile_list_layout(conn: &Connection) -> Result<ProfileListLayout> {
+    match get_setting(conn, PROFILE_LIST_LAYOUT_KEY)? {
+        Some(raw) => parse_profile_list_layout(&raw),
+        None => Ok(ProfileListLayout::default()),
+    }
+}
+
+pub fn set_profile_list_layout(conn: &Connection, layout: &ProfileListLayout) -> Result<()> {
+    let json = serde_json::to_string(layout)?;
+    set_setting(conn, PROFILE_LIST_LAYOUT_KEY, &json)
+}
+
+pub const PANE_LAYOUT_KEY: &str = "tui.layout";
+/// Smallest and largest share a TUI pane may take of its split, in percent.
+pub const PANE_SPLIT_MIN: u16 = 20;
+pub const PANE_SPLIT_MAX: u16 = 80;
+
+/// A result tab, by name, for `tui.layout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultTabName {
+    #[default]
+    Stdout,
+    Stderr,
+    Parsed,
+    Summary,
+}
+
+/// TUI pane sizes and the result tab shown when the TUI starts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaneLayout {
+    /// Width of the profile list, in percent of the screen.
+    pub profiles_width: u16,
+    /// Height of the Action pane, in percent of the right column; results get the rest.
+    pub actions_height: u16,
+    pub result_tab: ResultTabName,
+}
+
+impl Default for PaneLayout {
+    fn default() -> Self {
+        Self {
+            profiles_width: 40,
+            actions_height: 45,
+            result_tab: ResultTabName::Stdout,
+        }
+    }
+}
+
+pub fn parse_pane_layout(raw: &str) -> Result<PaneLayout> {
+    let layout: PaneLayout = serde_json::from_str(raw)
+        .map_err(|err| CoreError::InvalidSetting(format!("invalid TUI layout: {err}")))?;
+    for (name, value) in [
+        ("profiles_width", layout.profiles_width),
+        ("actions_height", layout.actions_height),
+    ] {
+        if !(PANE_SPLIT_MIN..=PANE_SPLIT_MAX).contains(&value) {
+            return Err(CoreError::InvalidSetting(format!(
+                "{name} must be between {PANE_SPLIT_MIN} and {PANE_SPLIT_MAX} (got {value})"
+            )));
+        }
+    }
+    Ok(layout)
+}
+
+pub fn get_pane_layout(conn: &Connection) -> Result<PaneLayout> {
+    match get_setting(conn, PANE_LAYOUT_KEY)? {
+        Some(raw) => parse_pane_layout(&raw),
+        None => Ok(PaneLayout::default()),
+    }
+}
+
+pub fn set_pane_layout(conn: &Connection, layout: &PaneLayout) -> Result<()> {
+    let json = serde_json::to_string(layout)?;
+    set_setting(conn, PANE_LAYOUT_KEY, &json)
+}
+
+pub const DISPLAY_TIMEZONE_KEY: &str = "ui.timezone";
+
+/// Offset used when displaying timestamps; defaults to UTC when `ui.timezone` is unset.
+pub fn get_display_offset(conn: &Connection) -> Result<UtcOffset> {
+    match get_setting(conn, DISPLAY_TIMEZONE_KEY)? {
+        Some(raw) => crate::util::parse_utc_offset(&raw).ok_or_else(|| {
+            CoreError::InvalidSetting(format!("invalid {DISPLAY_TIMEZONE_KEY}: {raw}"))
+        }),
+        None => Ok(UtcOffset::UTC),
+    }
+}
+
+pub const IDLE_LOCK_MINUTES_KEY: &str = "tui.idle_lock_minutes";
+const MAX_IDLE_LOCK_MINUTES: u64 = 24 * 60;
+
+pub fn parse_idle_lock_minutes(raw: &str) -> Result<u64> {
+    match raw.trim().parse::<u64>() {
+        Ok(value) if value <= MAX_IDLE_LOCK_MINUTES => Ok(value),
+        _ => Err(CoreError::InvalidSetting(format!(
+            "{IDLE_LOCK_MINUTES_KEY} must be 0-{MAX_IDLE_LOCK_MINUTES} (got {raw})"
+        ))),
+    }
+}
+
+/// Idle period after which the UI locks; `None` when the lock is disabled (the default).
+pub fn get_idle_lock_after(conn: &Connection) -> Result<Option<Duration>> {
+    let minutes = match get_setting(conn, IDLE_LOCK_MINUTES_KEY)? {
+        Some(raw) => parse_idle_lock_minutes(&raw)?,
+        None => 0,
+    };
+    Ok((minutes > 0).then(|| Duration::from_secs(minutes * 60)))
+}
+
+pub const VAULT_AUTO_LOCK_MINUTES_KEY: &str = "vault.auto_lock_minutes";
+const DEFAULT_VAULT_AUTO_LOCK_MINUTES: u64 = 15;
+const MAX_VAULT_AUTO_LOCK_MINUTES: u64 = 24 * 60;
+
+pub fn parse_vault_auto_lock_minutes(raw: &str) -> Result<u64> {
+    match raw.trim().parse::<u64>() {
+        Ok(value) if value <= MAX_VAULT_AUTO_LOCK_MINUTES => Ok(value),
+        _ => Err(CoreError::InvalidSetting(format!(
+            "{VAULT_AUTO_LOCK_MINUTES_KEY} must be 0-{MAX_VAULT_AUTO_LOCK_MINUTES} (got {raw})"
+        ))),
+    }
+}
+
+/// How long the daemon keeps an unlocked master key (default 15 minutes); `None`, when
+/// the setting is explicitly 0, keeps it until `td secret lock`.
+pub fn get_vault_auto_lock_after(conn: &Connection) -> Result<Option<Duration>> {
+    let minutes = match get_setting(conn, VAULT_AUTO_LOCK_MINUTES_KEY)? {
+        Some(raw) => parse_vault_auto_lock_minutes(&raw)?,
+        None => DEFAULT_VAULT_AUTO_LOCK_MINUTES,
+    };
+    Ok((minutes > 0).then(|| Duration::from_secs(minutes * 60)))
+}
+
+pub const CLIPBOARD_CLEAR_AFTER_KEY: &str = "clipboard.clear_after_secs";
+const DEFAULT_CLIPBOARD_CLEAR_AFTER_SECS: u64 = 30;
+const MAX_CLIPBOARD_CLEAR_AFTER_SECS: u64 = 60 * 60;
+
+pub fn parse_clipboard_clear_after_secs(raw: &str) -> Result<u64> {
+    match raw.trim().parse::<u64>() {
+        Ok(value) if value <= MAX_CLIPBOARD_CLEAR_AFTER_SECS => Ok(value),
+        _ => Err(CoreError::InvalidSetting(format!(
+            "{CLIPBOARD_CLEAR_AFTER_KEY} must be 0-{MAX_CLIPBOARD_CLEAR_AFTER_SECS} (got {raw})"
+        ))),
+    }
+}
+
+/// How long a copied secret stays on the clipboard (default 30s); `None` when clearing
+/// is disabled with 0.
+pub fn get_clipboard_clear_after(conn: &Connection) -> Result<Option<Duration>> {
+    let secs = match get_setting(conn, CLIPBOARD_CLEAR_AFTER_KEY)? {
+        Some(raw) => parse_clipboard_clear_after_secs(&raw)?,
+        None => DEFAULT_CLIPBOARD_CLEAR_AFTER_SECS,
+    };
+    Ok((secs > 0).then(|| Duration::from_secs(secs)))
+}
+
+pub const CONNECT_PREFLIGHT_KEY: &str = "connect.preflight";
+
+/// Whether to probe DNS+TCP before launching an interactive session (default off).
+pub fn get_connect_preflight(conn: &Connection, profile_id: &str) -> Result<bool> {
+    let scope = SettingScope::Profile(profile_id.to_string());
+    Ok(get_setting_resolved(conn, &scope, CONNECT_PREFLIGHT_KEY)?
+        .is_some_and(|raw| raw.trim() == "true"))
+}
+
+pub const RUN_STORE_OUTPUTS_KEY: &str = "run.store_outputs";
+
+/// Whether `td run` / `td exec` output for this profile is stored for `td search-output`
+/// (default off).
+pub fn get_run_store_outputs(conn: &Connection, profile_id: &str) -> Result<bool> {
+    let scope = SettingScope::Profile(profile_id.to_string());
+    Ok(get_setting_resolved(conn, &scope, RUN_STORE_OUTPUTS_KEY)?
+        .is_some_and(|raw| raw.trim() == "true"))
+}
+
+pub const RUN_OUTPUT_MAX_BYTES_KEY: &str = "run.output_max_bytes";
+const DEFAULT_OUTPUT_MAX_BYTES: usize = 64 * 1024;
+const MAX_OUTPUT_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+pub fn parse_output_max_bytes(raw: &str) -> Result<usize> {
+    match raw.trim().parse::<usize>() {
+        Ok(value) if (1..=MAX_OUTPUT_MAX_BYTES).contains(&value) => Ok(value),
+        _ => Err(CoreError::InvalidSetting(format!(
+            "{RUN_OUTPUT_MAX_BYTES_KEY} must be 1-{MAX_OUTPUT_MAX_BYTES} (got {raw})"
+        ))),
+    }
+}
+
+/// Bytes of stdout and of stderr kept per stored step output (default 64 KiB).
+pub fn get_output_max_bytes(conn: &Connection) -> Result<usize> {
+    match get_setting(conn, RUN_OUTPUT_MAX_BYTES_KEY)? {
+        Some(raw) => parse_output_max_bytes(&raw),
+        None => Ok(DEFAULT_OUTPUT_MAX_BYTES),
+    }
+}
+
+pub const AUDIT_FILE_KEY: &str = "audit.file";
+
+/// JSON-lines file every logged operation is appended to; `None` (the default) writes none.
+pub fn get_audit_file(conn: &Connection) -> Result<Option<PathBuf>> {
+    Ok(get_setting(conn, AUDIT_FILE_KEY)?
+        .map(|raw| raw.trim().to_string())
+        .filter(|raw| !raw.is_empty())
+        .map(PathBuf::from))
+}
+
+pub const HOOKS_PRE_CONNECT_KEY: &str = "hooks.pre_connect";
+pub const HOOKS_POST_RUN_KEY: &str = "hooks.post_run";
+pub const HOOKS_ON_FAILURE_KEY: &str = "hooks.on_failure";
+pub const HOOKS_FAILURE_KEY: &str = "hooks.failure";
+
+/// Hook CommandSets in the `hooks.<point>` setting `key` for the profile.
+pub fn get_hooks(
+    conn: &Connection,
+    profile_id: &str,
+    key: &str,
+) -> Result<Vec<crate::hooks::HookRef>> {
+    let scope = SettingScope::Profile(profile_id.to_string());
+    match get_setting_resolved(conn, &scope, key)? {
+        Some(raw) => crate::hooks::HookRef::parse_list(&raw),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub fn get_hooks_failure(conn: &Connection, profile_id: &str) -> Result<crate::hooks::HookFailure> {
+    let scope = SettingScope::Profile(profile_id.to_string());
+    match get_setting_resolved(conn, &scope, HOOKS_FAILURE_KEY)?.as_deref() {
+        Some("blocking") => Ok(crate::hooks::HookFailure::Blocking),
+        Some("advisory") | None => Ok(crate::hooks::HookFailure::Advisory),
+        Some(other) => Err(CoreError::InvalidSetting(format!(
+            "invalid {HOOKS_FAILURE_KEY}: {other}"
+        ))),
+    }
+}
+
+pub const NOTIFY_SUCCESS_KEY: &str = "notify.success";
+pub const NOTIFY_FAILURE_KEY: &str = "notify.failure";
+pub const NOTIFY_WEBHOOK_URL_KEY: &str = "notify.webhook_url";
+pub const NOTIFY_SLACK_URL_KEY: &str = "notify.slack_url";
+
+/// Notification channels in the `notify.<severity>` setting `key` at `scope`; empty by default.
+pub fn get_notify_channels(
+    conn: &Connection,
+    scope: &SettingScope,
+    key: &str,
+) -> Result<Vec<crate::notify::NotifyChannel>> {
+    match get_setting_resolved(conn, scope, key)? {
+        Some(raw) => crate::notify::NotifyChannel::parse_list(&raw),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub const POLICY_DANGER_KEY: &str = "policy.danger";
+pub const GUARD_COMMANDS_KEY: &str = "guard.commands";
+
+pub const OPERATOR_NAME_KEY: &str = "operator.name";
+
+/// The operator recorded on op log entries: `TD_OPERATOR`, else `operator.name`, else
+/// `None` (entries then carry only the OS user).
+pub fn get_operator(conn: &Connection) -> Result<Option<String>> {
+    let configured = match std::env::var("TD_OPERATOR") {
+        Ok(name) => Some(name),
+        Err(_) => get_setting(conn, OPERATOR_NAME_KEY)?,
+    };
+    Ok(configured
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty()))
+}
+
+pub const OBSERVER_ENABLED_KEY: &str = "observer.enabled";
+
+/// Whether `td` starts in read-only observer mode (default off), read for the active
+/// environment.
+pub fn get_observer_enabled(conn: &Connection) -> Result<bool> {
+    let scope = match get_current_env(conn)? {
+        Some(name) => SettingScope::Env(name),
+        None => SettingScope::Global,
+    };
+    Ok(get_setting_resolved(conn, &scope, OBSERVER_ENABLED_KEY)?
+        .is_some_and(|raw| raw.trim() == "true"))
+}
+
+pub const REPORTS_DIR_KEY: &str = "reports.dir";
+
+/// Where run reports are written: `reports.dir`, or `reports/` in the config directory.
+pub fn get_reports_dir(conn: &Connection) -> Result<PathBuf> {
+    match get_setting(conn, REPORTS_DIR_KEY)? {
+        Some(raw) => Ok(PathBuf::from(raw.trim())),
+        None => Ok(crate::paths::config_dir()?.join("reports")),
+    }
+}
+
+pub const SSH_MULTIPLEX_KEY: &str = "ssh.multiplex";
+pub const SSH_CONTROL_PERSIST_KEY: &str = "ssh.control_persist";
+pub const DEFAULT_SSH_CONTROL_PERSIST: &str = "60s";
+
+/// The `ControlPersist` value to use when `ssh.multiplex` is on for the profile, or
+/// `None` when connection sharing is off (the default).
+pub fn get_ssh_multiplex(conn: &Connection, profile_id: &str) -> Result<Option<String>> {
+    let scope = SettingScope::Profile(profile_id.to_string());
+    let enabled = get_setting_resolved(conn, &scope, SSH_MULTIPLEX_KEY)?
+        .is_some_and(|raw| raw.trim() == "true");
+    if !enabled {
+        return Ok(None);
+    }
+    Ok(Some(
+        get_setting_resolved(conn, &scope, SSH_CONTROL_PERSIST_KEY)?
+            .unwrap_or_else(|| DEFAULT_SSH_CONTROL_PERSIST.to_string()),
+    ))
+}
+
+pub const SSH_AGENT_KEY_KEY: &str = "ssh.agent_key";
+pub const SSH_AGENT_AUTOLOAD_KEY: &str = "ssh.agent_autoload";
+
+/// The key to load into ssh-agent before connecting, when `ssh.agent_autoload` is on and
+/// `ssh.agent_key` is set for the profile (a leading `~/` is expanded).
+pub fn get_ssh_agent_autoload_key(conn: &Connection, profile_id: &str) -> Result<Option<PathBuf>> {
+    let scope = SettingScope::Profile(profile_id.to_string());
+    let enabled = get_setting_resolved(conn, &scope, SSH_AGENT_AUTOLOAD_KEY)?
+        .is_some_and(|raw| raw.trim() == "true");
+    if !enabled {
+        return Ok(None);
+    }
+    let Some(raw) = get_setting_resolved(conn, &scope, SSH_AGENT_KEY_KEY)? else {
+        return Ok(None);
+    };
+    let raw = raw.trim();
+    let path = match raw.strip_prefix("~/").or_else(|| raw.strip_prefix("~\\")) {
+        Some(rest) => BaseDirs::new()
+            .ok_or(CoreError::DirectoryResolution)?
+            .home_dir()
+            .join(rest),
+        None => PathBuf::from(raw),
+    };
+    Ok(Some(path))
+}
+
+pub const SSH_BACKEND_KEY: &str = "ssh.backend";
+pub const SSH_PASSWORD_SECRET_KEY: &str = "ssh.password_secret";
+
+/// How CommandSet steps reach the remote host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SshBackend {
+    /// Spawn the configured ssh client once per step.
+    #[default]
+    External,
+    /// Keep one libssh2 session for the whole run (`ssh-native` feature).
+    Native,
+}
+
+pub fn get_ssh_backend(conn: &Connection, profile_id: &str) -> Result<SshBackend> {
+    let scope = SettingScope::Profile(profile_id.to_string());
+    match get_setting_resolved(conn, &scope, SSH_BACKEND_KEY)?.as_deref() {
+        Some("native") => Ok(SshBackend::Native),
+        Some("external") | None => Ok(SshBackend::External),
+        Some(other) => Err(CoreError::InvalidSetting(format!(
+            "invalid {SSH_BACKEND_KEY}: {other}"
+        ))),
+    }
+}
+
+pub const RUN_ENV_MODE_KEY: &str = "run.env_mode";
+
+/// How CommandSet environment variables reach the remote shell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RunEnvMode {
+    /// `export NAME='value';` in front of each step command.
+    #[default]
+    Export,
+    /// Set on the local ssh process and forwarded with `-o SendEnv=NAME`; the server must
+    /// list the names in `AcceptEnv`. The native backend always exports.
+    SendEnv,
+}
+
+pub fn get_run_env_mode(conn: &Connection, profile_id: &str) -> Result<RunEnvMode> {
+    let scope = SettingScope::Profile(profile_id.to_string());
+    match get_setting_resolved(conn, &scope, RUN_ENV_MODE_KEY)?.as_deref() {
+        Some("send_env") => Ok(RunEnvMode::SendEnv),
+        Some("export") | None => Ok(RunEnvMode::Export),
+        Some(other) => Err(CoreError::InvalidSetting(format!(
+            "invalid {RUN_ENV_MODE_KEY}: {other}"
+        ))),
+    }
+}
+
+pub const RUN_RECORD_KEY: &str = "run.record";
+
+/// Whether each run's output is also written to a recording under `logs/runs/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RunRecordFormat {
+    #[default]
+    Off,
+    /// Plain text, each line prefixed with its offset from the start of the run.
+    Log,
+    /// An asciinema v2 cast, for `asciinema play`.
+    Cast,
+}
+
+pub fn get_run_record(conn: &Connection, profile_id: &str) -> Result<RunRecordFormat> {
+    let scope = SettingScope::Profile(profile_id.to_string());
+    match get_setting_resolved(conn, &scope, RUN_RECORD_KEY)?.as_deref() {
+        Some("off") | None => Ok(RunRecordFormat::Off),
+        Some("log") => Ok(RunRecordFormat::Log),
+        Some("cast") => Ok(RunRecordFormat::Cast),
+        Some(other) => Err(CoreError::InvalidSetting(format!(
+            "invalid {RUN_RECORD_KEY}: {other}"
+        ))),
+    }
+}
+
+/// The secret id used for password auth by the native backend, if the profile has one.
+pub fn get_ssh_password_secret(conn: &Connection, profile_id: &str) -> Result<Option<String>> {
+    let scope = SettingScope::Profile(profile_id.to_string());
+    get_setting_resolved(conn, &scope, SSH_PASSWORD_SECRET_KEY)
+}
+
+pub const TUNNEL_ENV_TEMPLATES_KEY: &str = "tunnel.env_templates";
+
+/// Parses `tunnel.env_templates`: a JSON object mapping forward names to
+/// `KEY=VALUE` templates (see `tunnel::forward_env_vars`).
+pub fn parse_tunnel_env_templates(raw: &str) -> Result<BTreeMap<String, String>> {
+    let templates: BTreeMap<String, String> = serde_json::from_str(raw).map_err(|err| {
+        CoreError::InvalidSetting(format!(
+            "{TUNNEL_ENV_TEMPLATES_KEY} must map forward names to templates: {err}"
+        ))
+    })?;
+    for template in templates.values() {
+        crate::tunnel::parse_env_template(template)?;
+    }
+    Ok(templates)
+}
+
+/// Env templates for a profile's forwards, resolved profile > env > global.
+pub fn get_tunnel_env_templates(
+    conn: &Connection,
+    profile_id: &str,
+) -> Result<BTreeMap<String, String>> {
+    let scope = SettingScope::Profile(profile_id.to_string());
+    match get_setting_resolved(conn, &scope, TUNNEL_ENV_TEMPLATES_KEY)? {
+        Some(raw) => parse_tunnel_env_templates(&raw),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+pub const CONFIRM_TOKEN_KEY: &str = "confirm.token";
+
+/// Whether confirming an action on `profile` asks for a random token: `confirm.token`
+/// names the lowest danger level that does, and `off` (the default) disables it.
+pub fn confirm_token_required(conn: &Connection, profile: &Profile) -> Result<bool> {
+    let scope = SettingScope::Profile(profile.profile_id.clone());
+    let threshold = match get_setting_resolved(conn, &scope, CONFIRM_TOKEN_KEY)?.as_deref() {
+        Some("off") | None => return Ok(false),
+        Some("high") => DangerLevel::High,
+        Some("critical") => DangerLevel::Critical,
+        Some(other) => {
+            return Err(CoreError::InvalidSetting(format!(
+                "invalid {CONFIRM_TOKEN_KEY}: {other}"
+            )))
+        }
+    };
+    Ok(profile.danger_level >= threshold)
+}
+
+pub const TUNNEL_RECONNECT_KEY: &str = "tunnel.reconnect";
+
+/// Parses `tunnel.reconnect`: a JSON object mapping forward names (or `*` for all other
+/// forwards) to reconnect policies.
+pub fn parse_tunnel_reconnect_policies(
+    raw: &str,
+) -> Result<BTreeMap<String, crate::tunnel_manager::ReconnectPolicy>> {
+    let policies: BTreeMap<String, crate::tunnel_manager::ReconnectPolicy> =
+        serde_json::from_str(raw).map_err(|err| {
+            CoreError::InvalidSetting(format!(
+                "{TUNNEL_RECONNECT_KEY} must map forward names to reconnect policies: {err}"
+            ))
+        })?;
+    for (name, policy) in &policies {
+        policy.validate().map_err(|err| {
+            CoreError::InvalidSetting(format!("{TUNNEL_RECONNECT_KEY} {name}: {err}"))
+        })?;
+    }
+    Ok(policies)
+}
+
+/// Reconnect policies for a profile's forwards, resolved profile > env > global.
+pub fn get_tunnel_reconnect_policies(
+    conn: &Connection,
+    profile_id: &str,
+) -> Result<BTreeMap<String, crate::tunnel_manager::ReconnectPolicy>> {
+    let scope = SettingScope::Profile(profile_id.to_string());
+    match get_setting_resolved(conn, &scope, TUNNEL_RECONNECT_KEY)? {
+        Some(raw) => parse_tunnel_reconnect_policies(&raw),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+pub const RETENTION_NORMAL_DAYS_KEY: &str = "retention.normal_days";
+pub const RETENTION_HIGH_DAYS_KEY: &str = "retention.high_days";
+pub const RETENTION_CRITICAL_DAYS_KEY: &str = "retention.critical_days";
+const MAX_RETENTION_DAYS: u32 = 36_500;
+
+/// The `retention.*_days` key for a danger level and its default in days.
+pub fn retention_days_key(level: DangerLevel) -> (&'static str, u32) {
+    match level {
+        DangerLevel::Normal => (RETENTION_NORMAL_DAYS_KEY, 30),
+        DangerLevel::High => (RETENTION_HIGH_DAYS_KEY, 90),
+        DangerLevel::Critical => (RETENTION_CRITICAL_DAYS_KEY, 365),
+    }
+}
+
+pub fn parse_retention_days(raw: &str) -> Result<u32> {
+    match raw.trim().parse::<u32>() {
+        Ok(value) if value <= MAX_RETENTION_DAYS => Ok(value),
+        _ => Err(CoreError::InvalidSetting(format!(
+            "retention days must be 0-{MAX_RETENTION_DAYS} (got {raw})"
+        ))),
+    }
+}
+
+/// Days `td log prune` keeps op logs and stored outputs for profiles at `level`
+/// (defaults: normal 30, high 90, critical 365); 0 keeps them forever.
+pub fn get_retention_days(conn: &Connection, level: DangerLevel) -> Result<u32> {
+    let (key, default) = retention_days_key(level);
+    match get_setting(conn, key)? {
+        Some(raw) => parse_retention_days(&raw),
+        None => Ok(default),
+    }
+}
+
+pub const OPLOG_MAX_ROWS_KEY: &str = "oplog.max_rows";
+const MAX_OPLOG_MAX_ROWS: u64 = 1_000_000_000;
+
+pub fn parse_oplog_max_rows(raw: &str) -> Result<u64> {
+    match raw.trim().parse::<u64>() {
+        Ok(value) if value <= MAX_OPLOG_MAX_ROWS => Ok(value),
+        _ => Err(CoreError::InvalidSetting(format!(
+            "{OPLOG_MAX_ROWS_KEY} must be 0-{MAX_OPLOG_MAX_ROWS} (got {raw})"
+        ))),
+    }
+}
+
+/// Op log entries `td log prune` keeps at most, newest first; 0 (the default) sets no cap.
+pub fn get_oplog_max_rows(conn: &Connection) -> Result<u64> {
+    match get_setting(conn, OPLOG_MAX_ROWS_KEY)? {
+        Some(raw) => parse_oplog_max_rows(&raw),
+        None => Ok(0),
+    }
+}
+
+pub const OPLOG_AUTO_PRUNE_KEY: &str = "oplog.auto_prune";
+
+/// Whether the TUI applies `td log prune --yes` when it starts (default off).
+pub fn get_oplog_auto_prune(conn: &Connection) -> Result<bool> {
+    Ok(get_setting(conn, OPLOG_AUTO_PRUNE_KEY)?.is_some_and(|raw| raw.trim() == "true"))
+}
+
+pub const RETENTION_OUTPUT_DAYS_KEY: &str = "retention.output_days";
+
+/// Days `td log prune` keeps stored run outputs, when that is sooner than the retention of
+/// their profile's danger level; 0 (the default) keeps them as long as that.
+pub fn get_output_retention_days(conn: &Connection) -> Result<u32> {
+    match get_setting(conn, RETENTION_OUTPUT_DAYS_KEY)? {
+        Some(raw) => parse_retention_days(&raw),
+        None => Ok(0),
+    }
+}
+
+pub const API_TOKEN_KEY: &str = "api.token";
+const API_TOKEN_HASH_PREFIX: &str = "sha256:";
+
+/// `api.token` is stored as `sha256:<hex>`; a plain value is hashed before it is saved.
+pub fn normalize_api_token(raw: &str) -> Result<String> {
+    let raw = raw.trim();
+    if let Some(hex) = raw.strip_prefix(API_TOKEN_HASH_PREFIX) {
+        if hex.len() == 64 && hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Ok(format!(
+                "{API_TOKEN_HASH_PREFIX}{}",
+                hex.to_ascii_lowercase()
+            ));
+        }
+    }
+    if raw.len() < 16 {
+        return Err(CoreError::InvalidSetting(format!(
+            "{API_TOKEN_KEY} must be at least 16 characters"
+        )));
+    }
+    Ok(format!(
+        "{API_TOKEN_HASH_PREFIX}{}",
+        crate::remote_script::sha256_hex(raw.as_bytes())
+    ))
+}
+
+/// Whether `presented` matches the stored `api.token`; always false when none is set.
+pub fn api_token_matches(conn: &Connection, presented: &str) -> Result<bool> {
+    let Some(stored) = get_setting(conn, API_TOKEN_KEY)? else {
+        return Ok(false);
+    };
+    let presented = format!(
+        "{API_TOKEN_HASH_PREFIX}{}",
+        crate::remote_script::sha256_hex(presented.as_bytes())
+    );
+    Ok(presented.len() == stored.len()
+        && presented
+            .bytes()
+            .zip(stored.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_in_memory;
+    use crate::profile::{NewProfile, ProfileStore, ProfileType};
+
+    #[test]
+    fn profiles_inherit_group_settings_before_env_and_global() {
+        let store = ProfileStore::new(init_in_memory().unwrap());
+        store
+            .insert(NewProfile {
+                profile_id: Some("p_web".to_string()),
+                name: "web".to_string(),
+                profile_type: ProfileType::Ssh,
+                host: "web.example.com".to_string(),
+                port: 22,
+                user: "ops".to_string(),
+                danger_level: DangerLevel::Normal,
+                group: Some("prod".to_string()),
+                tags: Vec::new(),
+                note: None,
+                initial_send: None,
+                client_overrides: None,
+            })
+            .unwrap();
+        let conn = store.conn();
+        let prod = SettingScope::parse("group:prod").unwrap();
+        assert_eq!(prod, SettingScope::Group("prod".to_string()));
+        assert_eq!(prod.as_db(), "group:prod");
+        assert!(SettingScope::parse("group: ").is_err());
+
+        set_setting(conn, "ssh_auth_order", "password").unwrap();
+        set_setting_scoped(
+            conn,
+            &SettingScope::Env("work".to_string()),
+            "ssh_auth_order",
+            "keys",
+        )
+        .unwrap();
+        set_current_env(conn, "work").unwrap();
+        assert_eq!(
+            get_ssh_auth_order_for(conn, "p_web").unwrap().as_deref(),
+            Some("keys")
+        );
+        set_setting_scoped(conn, &prod, "ssh_auth_order", "agent,keys").unwrap();
+        set_setting_scoped(conn, &prod, "allow_insecure_transfers", "true").unwrap();
+        set_setting_scoped(conn, &prod, "client_overrides", r#"{"ssh":"/opt/ssh"}"#).unwrap();
+        assert_eq!(
+            get_ssh_auth_order_for(conn, "p_web").unwrap().as_deref(),
+            Some("agent,keys")
+        );
+        assert!(get_allow_insecure_transfers_for(conn, "p_web").unwrap());
+        assert!(!get_allow_insecure_transfers_for(conn, "p_other").unwrap());
+        let overrides = get_client_overrides_for(conn, "p_web").unwrap().unwrap();
+        assert_eq!(overrides.ssh.as_deref(), Some("/opt/ssh"));
+        assert!(get_client_overrides(conn).unwrap().is_none());
+
+        let profile = SettingScope::profile("p_web");
+        set_setting_scoped(conn, &profile, "ssh_auth_order", "password").unwrap();
+        let details = resolve_settings_for_profile(conn, "p_web", None).unwrap();
+        let detail = details
+            .iter()
+            .find(|detail| detail.key == "ssh_auth_order")
+            .unwrap();
+        assert_eq!(detail.group.as_deref(), Some("prod"));
+        assert_eq!(detail.group_value.as_deref(), Some("agent,keys"));
+        assert_eq!(detail.resolved_source, Some(ResolvedSettingSource::Profile));
+        let detail = details
+            .iter()
+            .find(|detail| detail.key == "allow_insecure_transfers")
+            .unwrap();
+        assert_eq!(detail.resolved_source, Some(ResolvedSettingSource::Group));
+    }
+
+    #[test]
+    fn creates_lists_and_removes_envs() {
+        let conn = init_in_memory().unwrap();
+        assert!(create_env(&conn, "stage").unwrap());
+        assert!(!create_env(&conn, "stage").unwrap());
+        let work = SettingScope::Env("work".to_string());
+        set_setting_scoped(&conn, &work, "ssh_auth_order", "keys").unwrap();
+        set_setting_scoped(&conn, &work, "ssh.use_agent", "false").unwrap();
+        assert_eq!(list_env_names(&conn).unwrap(), ["stage", "work"]);
+        assert!(!create_env(&conn, "work").unwrap());
+
+        set_current_env(&conn, "work").unwrap();
+        assert_eq!(remove_env(&conn, "work").unwrap(), 2);
+        assert_eq!(get_current_env(&conn).unwrap(), None);
+        assert_eq!(remove_env(&conn, "stage").unwrap(), 0);
+        assert!(list_env_names(&conn).unwrap().is_empty());
+        assert_eq!(get_setting(&conn, ENV_PRESETS_KEY).unwrap(), None);
+    }
+}
@@ -12,6 +12,7 @@ pub enum SettingValueType {
     String,
     Json,
     CsvList,
+    Integer,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -50,10 +51,53 @@ const SESSION_LOG_BACKENDS: [&str; 5] = [
     "conpty",
     "no-log",
 ];
-const ALLOW_INSECURE_EXAMPLES: [&str; 2] = ["true", "false"];
+/// Allowed values and examples of every boolean setting.
+const BOOL_VALUES: [&str; 2] = ["true", "false"];
 const SSH_AUTH_EXAMPLES: [&str; 2] = ["agent,keys,password", "keys,password"];
 const CLIENT_OVERRIDE_EXAMPLES: [&str; 1] = [r#"{"ssh":"/usr/bin/ssh","scp":"/usr/bin/scp"}"#];
-const SSH_USE_AGENT_EXAMPLES: [&str; 2] = ["true", "false"];
+const RESULT_TAB_EXAMPLES: [&str; 1] =
+    [r#"{"parsed":{"wrap":false,"max_lines":200},"stdout":{"timestamps":true}}"#];
+const PROFILE_LIST_EXAMPLES: [&str; 1] = [
+    r#"{"columns":["group","last_used"],"wide_columns":["endpoint","group","tags","last_used","note"],"max_width":16}"#,
+];
+const PANE_LAYOUT_EXAMPLES: [&str; 1] =
+    [r#"{"profiles_width":30,"actions_height":40,"result_tab":"summary"}"#];
+const DB_BUSY_TIMEOUT_EXAMPLES: [&str; 2] = ["5000", "15000"];
+const DISPLAY_TIMEZONE_EXAMPLES: [&str; 2] = ["UTC", "+09:00"];
+const IDLE_LOCK_EXAMPLES: [&str; 2] = ["0", "15"];
+const CLIPBOARD_CLEAR_AFTER_EXAMPLES: [&str; 2] = ["30", "0"];
+const RETENTION_DAYS_EXAMPLES: [&str; 3] = ["30", "365", "0"];
+const OUTPUT_MAX_BYTES_EXAMPLES: [&str; 2] = ["65536", "1048576"];
+const AUDIT_FILE_EXAMPLES: [&str; 1] = ["/var/log/teradock/audit.jsonl"];
+const OPLOG_MAX_ROWS_EXAMPLES: [&str; 2] = ["100000", "0"];
+const SSH_AGENT_KEY_EXAMPLES: [&str; 2] =
+    ["~/.ssh/id_ed25519", "C:\\Users\\alice\\.ssh\\id_ed25519"];
+const CONTROL_PERSIST_EXAMPLES: [&str; 2] = ["60s", "10m"];
+const SSH_BACKENDS: [&str; 2] = ["external", "native"];
+const RUN_ENV_MODES: [&str; 2] = ["export", "send_env"];
+const RUN_RECORD_FORMATS: [&str; 3] = ["off", "log", "cast"];
+const CONFIRM_TOKEN_LEVELS: [&str; 3] = ["off", "high", "critical"];
+const SSH_PASSWORD_SECRET_EXAMPLES: [&str; 1] = ["s_db_root"];
+const HOOK_EXAMPLES: [&str; 2] = ["local:c_notify", "c_snapshot,local:c_notify"];
+const HOOK_FAILURE_MODES: [&str; 2] = ["advisory", "blocking"];
+const NOTIFY_CHANNEL_EXAMPLES: [&str; 3] = ["desktop", "slack,desktop", "none"];
+const POLICY_DANGER_EXAMPLES: [&str; 2] = [
+    r#"{"critical":{"reason":true,"freeze":["fri 18:00..mon 08:00"]}}"#,
+    r#"{"high":{"confirm":true},"critical":{"cmdsets":["c_health"],"allow_commands":["uptime","df -h"]}}"#,
+];
+const GUARD_COMMANDS_EXAMPLES: [&str; 2] = [
+    r#"{"deny":["rm\\s+-rf\\s+/","\\bshutdown\\b"]}"#,
+    r#"{"allow":["^(systemctl status|df|uptime)"],"deny":["\\breboot\\b"]}"#,
+];
+const OPERATOR_NAME_EXAMPLES: [&str; 2] = ["Jane Doe", "jane.doe@example.com"];
+const NOTIFY_URL_EXAMPLES: [&str; 1] = ["https://hooks.example.com/teradock"];
+const TUNNEL_ENV_TEMPLATE_EXAMPLES: [&str; 1] = [r#"{"pg":"PGHOST={host} PGPORT={port}"}"#];
+const TUNNEL_RECONNECT_EXAMPLES: [&str; 1] =
+    [r#"{"*":{"max_retries":5,"backoff_secs":2,"max_backoff_secs":60,"jitter":0.2}}"#];
+const REPORTS_DIR_EXAMPLES: [&str; 2] = [
+    "/home/alice/.config/teradock/reports",
+    "C:\\Users\\alice\\AppData\\Roaming\\TeraDock\\reports",
+];
 const SESSION_LOG_DIR_EXAMPLES: [&str; 2] = [
     "/home/alice/.config/teradock/session-logs",
     "C:\\Users\\alice\\AppData\\Roaming\\TeraDock\\session-logs",
@@ -62,87 +106,678 @@ const SESSION_LOG_DIR_EXAMPLES: [&str; 2] = [
 static SETTINGS: &[SettingDefinition] = &[
     SettingDefinition {
         schema: SettingSchema {
-            key: "allow_insecure_transfers",
-            description: "Allow insecure transfers when using FTP clients.",
-            value_type: SettingValueType::Boolean,
-            allowed_values: &ALLOW_INSECURE_EXAMPLES,
-            examples: &ALLOW_INSECURE_EXAMPLES,
+            key: "allow_insecure_transfers",
+            description: "Allow insecure transfers when using FTP clients.",
+            value_type: SettingValueType::Boolean,
+            allowed_values: &BOOL_VALUES,
+            examples: &BOOL_VALUES,
+            dangerous: true,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_bool,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "ssh_auth_order",
+            description: "Preferred SSH auth order (comma-delimited: agent,keys,password).",
+            value_type: SettingValueType::CsvList,
+            allowed_values: &SSH_AUTH_ALLOWED,
+            examples: &SSH_AUTH_EXAMPLES,
+            dangerous: false,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_ssh_auth_order,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "ssh.use_agent",
+            description: "Enable SSH agent usage for authentication.",
+            value_type: SettingValueType::Boolean,
+            allowed_values: &BOOL_VALUES,
+            examples: &BOOL_VALUES,
+            dangerous: false,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_bool,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "client_overrides",
+            description: "JSON overrides for client paths (ssh/scp/sftp/ftp/telnet).",
+            value_type: SettingValueType::Json,
+            allowed_values: &[],
+            examples: &CLIENT_OVERRIDE_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global, SettingScopeKind::Group],
+        },
+        validator: validate_json,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "session.log.enabled",
+            description: "Enable terminal transcript logging for interactive SSH sessions. Logs may contain secrets shown in the terminal.",
+            value_type: SettingValueType::Boolean,
+            allowed_values: &BOOL_VALUES,
+            examples: &BOOL_VALUES,
+            dangerous: true,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_bool,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "session.log.dir",
+            description: "Directory for interactive SSH session transcript logs and metadata.",
+            value_type: SettingValueType::String,
+            allowed_values: &[],
+            examples: &SESSION_LOG_DIR_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_non_empty,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "session.log.backend",
+            description: "Backend for interactive SSH session logging (auto, script, powershell-transcript, conpty experimental, or no-log).",
+            value_type: SettingValueType::String,
+            allowed_values: &SESSION_LOG_BACKENDS,
+            examples: &SESSION_LOG_BACKENDS,
+            dangerous: false,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_session_log_backend,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "db.busy_timeout_ms",
+            description: "Milliseconds to wait for a locked database before failing (applies to new connections).",
+            value_type: SettingValueType::Integer,
+            allowed_values: &[],
+            examples: &DB_BUSY_TIMEOUT_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_busy_timeout,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "tui.result_tabs",
+            description: "Per-tab result display (stdout/stderr/parsed): wrap, timestamps, monospace, max_lines.",
+            value_type: SettingValueType::Json,
+            allowed_values: &[],
+            examples: &RESULT_TAB_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_result_tab_layouts,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "tui.profile_list",
+            description: "TUI profile list fields: columns, wide_columns, wide, max_width (0 = no truncation), badges.",
+            value_type: SettingValueType::Json,
+            allowed_values: &[],
+            examples: &PROFILE_LIST_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_profile_list_layout,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "tui.layout",
+            description: "TUI pane sizes in percent (profiles_width, actions_height; 20-80) and the result tab shown at startup.",
+            value_type: SettingValueType::Json,
+            allowed_values: &[],
+            examples: &PANE_LAYOUT_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_pane_layout,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "ui.timezone",
+            description: "Timezone for displayed timestamps: UTC or a fixed offset such as +09:00.",
+            value_type: SettingValueType::String,
+            allowed_values: &[],
+            examples: &DISPLAY_TIMEZONE_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_display_timezone,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "tui.idle_lock_minutes",
+            description: "Lock the TUI after this many minutes without input (0 disables); resume with the master password or lock PIN.",
+            value_type: SettingValueType::Integer,
+            allowed_values: &[],
+            examples: &IDLE_LOCK_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_idle_lock_minutes,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "clipboard.clear_after_secs",
+            description: "Clear secrets copied to the clipboard after this many seconds unless something else was copied since (default 30, 0 disables).",
+            value_type: SettingValueType::Integer,
+            allowed_values: &[],
+            examples: &CLIPBOARD_CLEAR_AFTER_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_clipboard_clear_after_secs,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "connect.preflight",
+            description: "Probe DNS and TCP before opening an interactive session and ask before launching when the host is unreachable.",
+            value_type: SettingValueType::Boolean,
+            allowed_values: &BOOL_VALUES,
+            examples: &BOOL_VALUES,
+            dangerous: false,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_bool,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "run.store_outputs",
+            description: "Store td run/exec stdout and stderr in the database for td search-output. Outputs may contain secrets printed by remote commands.",
+            value_type: SettingValueType::Boolean,
+            allowed_values: &BOOL_VALUES,
+            examples: &BOOL_VALUES,
+            dangerous: true,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_bool,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "run.output_max_bytes",
+            description: "Bytes of stdout and of stderr kept per step when run.store_outputs is on (default 65536); the rest is dropped and counted.",
+            value_type: SettingValueType::Integer,
+            allowed_values: &[],
+            examples: &OUTPUT_MAX_BYTES_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_output_max_bytes,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "ssh.agent_key",
+            description: "Private key loaded into ssh-agent before td connect when ssh.agent_autoload is true.",
+            value_type: SettingValueType::String,
+            allowed_values: &[],
+            examples: &SSH_AGENT_KEY_EXAMPLES,
+            dangerous: false,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_non_empty,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "ssh.agent_autoload",
+            description: "Load ssh.agent_key into ssh-agent before td connect if it is not already loaded.",
+            value_type: SettingValueType::Boolean,
+            allowed_values: &BOOL_VALUES,
+            examples: &BOOL_VALUES,
+            dangerous: false,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_bool,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "ssh.multiplex",
+            description: "Share one SSH connection per host (ControlMaster) across td run steps, exec, and transfers. Not available on Windows.",
+            value_type: SettingValueType::Boolean,
+            allowed_values: &BOOL_VALUES,
+            examples: &BOOL_VALUES,
+            dangerous: false,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_bool,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "ssh.control_persist",
+            description: "How long a shared SSH connection stays open after its last use (seconds, or with an s/m/h suffix; default 60s).",
+            value_type: SettingValueType::String,
+            allowed_values: &[],
+            examples: &CONTROL_PERSIST_EXAMPLES,
+            dangerous: false,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_control_persist,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "ssh.backend",
+            description: "How td run executes CommandSet steps: external (spawn the ssh client per step) or native (one in-process libssh2 session; needs a build with the ssh-native feature).",
+            value_type: SettingValueType::String,
+            allowed_values: &SSH_BACKENDS,
+            examples: &SSH_BACKENDS,
+            dangerous: false,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_ssh_backend,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "ssh.password_secret",
+            description: "Secret id whose value the native SSH backend sends for password and keyboard-interactive auth (unlocked with the master password).",
+            value_type: SettingValueType::String,
+            allowed_values: &[],
+            examples: &SSH_PASSWORD_SECRET_EXAMPLES,
+            dangerous: false,
+            scopes: &[
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_non_empty,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "tunnel.env_templates",
+            description: "Per-forward KEY=VALUE templates for td tunnel env ({host}, {port}, {name} are filled in).",
+            value_type: SettingValueType::Json,
+            allowed_values: &[],
+            examples: &TUNNEL_ENV_TEMPLATE_EXAMPLES,
+            dangerous: false,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_tunnel_env_templates,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "confirm.token",
+            description: "Lowest danger level (high or critical) whose confirmations ask for a short random token instead of the profile id or 'yes'; off keeps the usual prompts.",
+            value_type: SettingValueType::String,
+            allowed_values: &CONFIRM_TOKEN_LEVELS,
+            examples: &CONFIRM_TOKEN_LEVELS,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global, SettingScopeKind::Env],
+        },
+        validator: validate_confirm_token,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "tunnel.reconnect",
+            description: "Per-forward restart policy (max_retries, backoff_secs, max_backoff_secs, jitter); \"*\" covers other forwards.",
+            value_type: SettingValueType::Json,
+            allowed_values: &[],
+            examples: &TUNNEL_RECONNECT_EXAMPLES,
+            dangerous: false,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_tunnel_reconnect,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "retention.normal_days",
+            description: "Days td log prune keeps op logs and stored outputs of normal profiles (default 30, 0 keeps them forever).",
+            value_type: SettingValueType::Integer,
+            allowed_values: &[],
+            examples: &RETENTION_DAYS_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_retention_days,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "retention.high_days",
+            description: "Days td log prune keeps op logs and stored outputs of high profiles (default 90, 0 keeps them forever).",
+            value_type: SettingValueType::Integer,
+            allowed_values: &[],
+            examples: &RETENTION_DAYS_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_retention_days,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "retention.critical_days",
+            description: "Days td log prune keeps op logs and stored outputs of critical profiles (default 365, 0 keeps them forever).",
+            value_type: SettingValueType::Integer,
+            allowed_values: &[],
+            examples: &RETENTION_DAYS_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_retention_days,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "oplog.max_rows",
+            description: "Op log entries td log prune keeps at most, deleting the oldest beyond it (default 0: no cap).",
+            value_type: SettingValueType::Integer,
+            allowed_values: &[],
+            examples: &OPLOG_MAX_ROWS_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_oplog_max_rows,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "oplog.auto_prune",
+            description: "Run td log prune --yes (retention.* and oplog.max_rows) each time the TUI starts. Deletes op logs and stored outputs without asking.",
+            value_type: SettingValueType::Boolean,
+            allowed_values: &BOOL_VALUES,
+            examples: &BOOL_VALUES,
+            dangerous: true,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_bool,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "retention.output_days",
+            description: "Days td log prune keeps stored run outputs when sooner than their danger level's retention (default 0: as long as that).",
+            value_type: SettingValueType::Integer,
+            allowed_values: &[],
+            examples: &RETENTION_DAYS_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_retention_days,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "api.token",
+            description: "Bearer token for td api serve; stored as a SHA-256 hash (plain values are hashed on save). Generate one with td api token.",
+            value_type: SettingValueType::String,
+            allowed_values: &[],
+            examples: &[],
+            dangerous: true,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_api_token,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "reports.dir",
+            description: "Directory for run reports written by td run --report and the TUI (default: reports/ in the config directory). Reports contain command output.",
+            value_type: SettingValueType::String,
+            allowed_values: &[],
+            examples: &REPORTS_DIR_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_non_empty,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "audit.file",
+            description: "JSON-lines file every logged operation is appended to for SIEM collection (user, host, profile, command hash, exit status). Unset writes none.",
+            value_type: SettingValueType::String,
+            allowed_values: &[],
+            examples: &AUDIT_FILE_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_non_empty,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "hooks.pre_connect",
+            description: "CommandSets run before td connect, td exec, and td run on the profile (comma-separated; local:<id> runs the steps on this machine).",
+            value_type: SettingValueType::String,
+            allowed_values: &[],
+            examples: &HOOK_EXAMPLES,
             dangerous: true,
             scopes: &[
                 SettingScopeKind::Global,
                 SettingScopeKind::Env,
+                SettingScopeKind::Group,
                 SettingScopeKind::Profile,
             ],
         },
-        validator: validate_bool,
+        validator: validate_hooks,
     },
     SettingDefinition {
         schema: SettingSchema {
-            key: "ssh_auth_order",
-            description: "Preferred SSH auth order (comma-delimited: agent,keys,password).",
-            value_type: SettingValueType::CsvList,
-            allowed_values: &SSH_AUTH_ALLOWED,
-            examples: &SSH_AUTH_EXAMPLES,
+            key: "hooks.post_run",
+            description: "CommandSets run after every td exec or td run on the profile (comma-separated; local:<id> runs the steps on this machine).",
+            value_type: SettingValueType::String,
+            allowed_values: &[],
+            examples: &HOOK_EXAMPLES,
+            dangerous: true,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_hooks,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "hooks.on_failure",
+            description: "CommandSets run after a failed td exec or td run on the profile (comma-separated; local:<id> runs the steps on this machine).",
+            value_type: SettingValueType::String,
+            allowed_values: &[],
+            examples: &HOOK_EXAMPLES,
+            dangerous: true,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_hooks,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "hooks.failure",
+            description: "What a failed hook does: advisory (report it and carry on) or blocking (abort the action, or fail it when the hook ran afterwards).",
+            value_type: SettingValueType::String,
+            allowed_values: &HOOK_FAILURE_MODES,
+            examples: &HOOK_FAILURE_MODES,
             dangerous: false,
             scopes: &[
                 SettingScopeKind::Global,
                 SettingScopeKind::Env,
+                SettingScopeKind::Group,
                 SettingScopeKind::Profile,
             ],
         },
-        validator: validate_ssh_auth_order,
+        validator: validate_hook_failure,
     },
     SettingDefinition {
         schema: SettingSchema {
-            key: "ssh.use_agent",
-            description: "Enable SSH agent usage for authentication.",
-            value_type: SettingValueType::Boolean,
-            allowed_values: &SSH_USE_AGENT_EXAMPLES,
-            examples: &SSH_USE_AGENT_EXAMPLES,
+            key: "notify.success",
+            description: "Channels notified when a run succeeds (comma-separated: desktop, webhook, slack; none turns a broader scope off). Unset sends nothing.",
+            value_type: SettingValueType::String,
+            allowed_values: &[],
+            examples: &NOTIFY_CHANNEL_EXAMPLES,
             dangerous: false,
             scopes: &[
                 SettingScopeKind::Global,
                 SettingScopeKind::Env,
+                SettingScopeKind::Group,
                 SettingScopeKind::Profile,
             ],
         },
-        validator: validate_bool,
+        validator: validate_notify_channels,
     },
     SettingDefinition {
         schema: SettingSchema {
-            key: "client_overrides",
-            description: "JSON overrides for client paths (ssh/scp/sftp/ftp/telnet).",
-            value_type: SettingValueType::Json,
+            key: "notify.failure",
+            description: "Channels notified when a run fails (comma-separated: desktop, webhook, slack; none turns a broader scope off). Unset sends nothing.",
+            value_type: SettingValueType::String,
             allowed_values: &[],
-            examples: &CLIENT_OVERRIDE_EXAMPLES,
+            examples: &NOTIFY_CHANNEL_EXAMPLES,
             dangerous: false,
-            scopes: &[SettingScopeKind::Global],
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
         },
-        validator: validate_json,
+        validator: validate_notify_channels,
     },
     SettingDefinition {
         schema: SettingSchema {
-            key: "session.log.enabled",
-            description: "Enable terminal transcript logging for interactive SSH sessions. Logs may contain secrets shown in the terminal.",
-            value_type: SettingValueType::Boolean,
-            allowed_values: &ALLOW_INSECURE_EXAMPLES,
-            examples: &ALLOW_INSECURE_EXAMPLES,
+            key: "notify.webhook_url",
+            description: "URL the webhook channel POSTs a JSON event to. Treat it as a secret when it embeds a token.",
+            value_type: SettingValueType::String,
+            allowed_values: &[],
+            examples: &NOTIFY_URL_EXAMPLES,
+            dangerous: false,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+            ],
+        },
+        validator: validate_notify_url,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "notify.slack_url",
+            description: "Slack-compatible incoming webhook URL the slack channel posts to. Treat it as a secret.",
+            value_type: SettingValueType::String,
+            allowed_values: &[],
+            examples: &NOTIFY_URL_EXAMPLES,
+            dangerous: false,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+            ],
+        },
+        validator: validate_notify_url,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "policy.danger",
+            description: "Policies per danger level (JSON keyed by normal, high, critical): confirm, reason, cmdsets, allow_commands (regexes matching the whole command), freeze (windows in ui.timezone).",
+            value_type: SettingValueType::Json,
+            allowed_values: &[],
+            examples: &POLICY_DANGER_EXAMPLES,
+            dangerous: true,
+            scopes: &[SettingScopeKind::Global, SettingScopeKind::Env],
+        },
+        validator: validate_policy,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "guard.commands",
+            description: "Regex allow and deny patterns (JSON with allow and deny lists) checked against every CommandSet step before a run starts; a deny match or, with allow set, no allow match blocks the run.",
+            value_type: SettingValueType::Json,
+            allowed_values: &[],
+            examples: &GUARD_COMMANDS_EXAMPLES,
             dangerous: true,
             scopes: &[
                 SettingScopeKind::Global,
                 SettingScopeKind::Env,
+                SettingScopeKind::Group,
                 SettingScopeKind::Profile,
             ],
         },
+        validator: validate_guard,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "observer.enabled",
+            description: "Start td and the TUI in read-only observer mode: browsing profiles, history, and doctor output works, while runs, sessions, transfers, and edits are refused.",
+            value_type: SettingValueType::Boolean,
+            allowed_values: &BOOL_VALUES,
+            examples: &BOOL_VALUES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global, SettingScopeKind::Env],
+        },
         validator: validate_bool,
     },
     SettingDefinition {
         schema: SettingSchema {
-            key: "session.log.dir",
-            description: "Directory for interactive SSH session transcript logs and metadata.",
+            key: "operator.name",
+            description: "Operator name or email recorded on every op log entry next to the OS user, for shared installs; TD_OPERATOR overrides it per shell.",
             value_type: SettingValueType::String,
             allowed_values: &[],
-            examples: &SESSION_LOG_DIR_EXAMPLES,
+            examples: &OPERATOR_NAME_EXAMPLES,
             dangerous: false,
             scopes: &[SettingScopeKind::Global],
         },
@@ -150,19 +785,49 @@ static SETTINGS: &[SettingDefinition] = &[
     },
     SettingDefinition {
         schema: SettingSchema {
-            key: "session.log.backend",
-            description: "Backend for interactive SSH session logging (auto, script, powershell-transcript, conpty experimental, or no-log).",
+            key: "vault.auto_lock_minutes",
+            description: "Lock the master key held by td daemon this many minutes after `td secret unlock` (default 15; 0 keeps it until `td secret lock`).",
+            value_type: SettingValueType::Integer,
+            allowed_values: &[],
+            examples: &IDLE_LOCK_EXAMPLES,
+            dangerous: false,
+            scopes: &[SettingScopeKind::Global],
+        },
+        validator: validate_vault_auto_lock_minutes,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "run.env_mode",
+            description: "How CommandSet env_vars reach the remote shell: export (an export prefix on each step) or send_env (ssh -o SendEnv; the server must AcceptEnv the names).",
             value_type: SettingValueType::String,
-            allowed_values: &SESSION_LOG_BACKENDS,
-            examples: &SESSION_LOG_BACKENDS,
+            allowed_values: &RUN_ENV_MODES,
+            examples: &RUN_ENV_MODES,
             dangerous: false,
             scopes: &[
                 SettingScopeKind::Global,
                 SettingScopeKind::Env,
+                SettingScopeKind::Group,
                 SettingScopeKind::Profile,
             ],
         },
-        validator: validate_session_log_backend,
+        validator: validate_run_env_mode,
+    },
+    SettingDefinition {
+        schema: SettingSchema {
+            key: "run.record",
+            description: "Also write each CommandSet run's output to a file under logs/runs: off, log (text with time offsets), or cast (asciinema v2). Recordings may contain secrets printed by remote commands; td log show --output prints a run's path.",
+            value_type: SettingValueType::String,
+            allowed_values: &RUN_RECORD_FORMATS,
+            examples: &RUN_RECORD_FORMATS,
+            dangerous: true,
+            scopes: &[
+                SettingScopeKind::Global,
+                SettingScopeKind::Env,
+                SettingScopeKind::Group,
+                SettingScopeKind::Profile,
+            ],
+        },
+        validator: validate_run_record,
     },
 ];
 
@@ -259,6 +924,19 @@ fn validate_non_empty(raw: &str) -> Result<String> {
     Ok(trimmed.to_string())
 }
 
+fn validate_control_persist(raw: &str) -> Result<String> {
+    let trimmed = raw.trim().to_ascii_lowercase();
+    let digits = trimmed.trim_end_matches(['s', 'm', 'h']);
+    let suffix_len = trimmed.len() - digits.len();
+    if suffix_len <= 1 && !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+        Ok(trimmed)
+    } else {
+        Err(CoreError::InvalidSetting(format!(
+            "invalid control persist '{raw}' (expected e.g. 60, 60s, 10m, 1h)"
+        )))
+    }
+}
+
 fn validate_session_log_backend(raw: &str) -> Result<String> {
     let normalized = raw.trim().to_ascii_lowercase();
     if SESSION_LOG_BACKENDS.contains(&normalized.as_str()) {
@@ -270,6 +948,175 @@ fn validate_session_log_backend(raw: &str) -> Result<String> {
     }
 }
 
+fn validate_hooks(raw: &str) -> Result<String> {
+    let hooks = crate::hooks::HookRef::parse_list(raw)?;
+    if hooks.is_empty() {
+        return Err(CoreError::InvalidSetting(
+            "value cannot be empty".to_string(),
+        ));
+    }
+    Ok(hooks
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(","))
+}
+
+fn validate_notify_channels(raw: &str) -> Result<String> {
+    let channels = crate::notify::NotifyChannel::parse_list(raw)?;
+    if channels.is_empty() {
+        return Ok("none".to_string());
+    }
+    Ok(channels
+        .iter()
+        .map(|channel| channel.as_str())
+        .collect::<Vec<_>>()
+        .join(","))
+}
+
+fn validate_notify_url(raw: &str) -> Result<String> {
+    let trimmed = raw.trim();
+    if trimmed.starts_with("https://") || trimmed.starts_with("http://") {
+        Ok(trimmed.to_string())
+    } else {
+        Err(CoreError::InvalidSetting(format!(
+            "notification URL must start with http:// or https:// (got '{trimmed}')"
+        )))
+    }
+}
+
+fn validate_policy(raw: &str) -> Result<String> {
+    let policy = crate::policy::PolicySet::parse(raw)?;
+    Ok(serde_json::to_string(&policy)?)
+}
+
+fn validate_guard(raw: &str) -> Result<String> {
+    let rules = crate::guard::GuardRules::parse(raw)?;
+    Ok(serde_json::to_string(&rules)?)
+}
+
+fn validate_hook_failure(raw: &str) -> Result<String> {
+    let normalized = raw.trim().to_ascii_lowercase();
+    if HOOK_FAILURE_MODES.contains(&normalized.as_str()) {
+        Ok(normalized)
+    } else {
+        Err(CoreError::InvalidSetting(format!(
+            "unknown hook failure mode '{raw}' (expected advisory or blocking)"
+        )))
+    }
+}
+
+fn validate_ssh_backend(raw: &str) -> Result<String> {
+    let normalized = raw.trim().to_ascii_lowercase();
+    if SSH_BACKENDS.contains(&normalized.as_str()) {
+        Ok(normalized)
+    } else {
+        Err(CoreError::InvalidSetting(format!(
+            "unknown ssh backend '{raw}' (expected external or native)"
+        )))
+    }
+}
+
+fn validate_busy_timeout(raw: &str) -> Result<String> {
+    crate::db::parse_busy_timeout(raw).map(|value| value.to_string())
+}
+
+fn validate_result_tab_layouts(raw: &str) -> Result<String> {
+    let layouts = crate::settings::parse_result_tab_layouts(raw)?;
+    Ok(serde_json::to_string(&layouts)?)
+}
+
+fn validate_profile_list_layout(raw: &str) -> Result<String> {
+    let layout = crate::settings::parse_profile_list_layout(raw)?;
+    Ok(serde_json::to_string(&layout)?)
+}
+
+fn validate_pane_layout(raw: &str) -> Result<String> {
+    let layout = crate::settings::parse_pane_layout(raw)?;
+    Ok(serde_json::to_string(&layout)?)
+}
+
+fn validate_display_timezone(raw: &str) -> Result<String> {
+    crate::util::parse_utc_offset(raw)
+        .map(|_| raw.trim().to_string())
+        .ok_or_else(|| {
+            CoreError::InvalidSetting(format!(
+                "ui.timezone must be UTC or an offset like +09:00 (got {raw})"
+            ))
+        })
+}
+
+fn validate_idle_lock_minutes(raw: &str) -> Result<String> {
+    crate::settings::parse_idle_lock_minutes(raw).map(|value| value.to_string())
+}
+
+fn validate_vault_auto_lock_minutes(raw: &str) -> Result<String> {
+    crate::settings::parse_vault_auto_lock_minutes(raw).map(|value| value.to_string())
+}
+
+fn validate_clipboard_clear_after_secs(raw: &str) -> Result<String> {
+    crate::settings::parse_clipboard_clear_after_secs(raw).map(|value| value.to_string())
+}
+
+fn validate_tunnel_env_templates(raw: &str) -> Result<String> {
+    let templates = crate::settings::parse_tunnel_env_templates(raw)?;
+    Ok(serde_json::to_string(&templates)?)
+}
+
+fn validate_api_token(raw: &str) -> Result<String> {
+    crate::settings::normalize_api_token(raw)
+}
+
+fn validate_retention_days(raw: &str) -> Result<String> {
+    crate::settings::parse_retention_days(raw).map(|value| value.to_string())
+}
+
+fn validate_output_max_bytes(raw: &str) -> Result<String> {
+    crate::settings::parse_output_max_bytes(raw).map(|value| value.to_string())
+}
+
+fn validate_oplog_max_rows(raw: &str) -> Result<String> {
+    crate::settings::parse_oplog_max_rows(raw).map(|value| value.to_string())
+}
+
+fn validate_run_record(raw: &str) -> Result<String> {
+    let normalized = raw.trim().to_ascii_lowercase();
+    if RUN_RECORD_FORMATS.contains(&normalized.as_str()) {
+        Ok(normalized)
+    } else {
+        Err(CoreError::InvalidSetting(format!(
+            "unknown run.record '{raw}' (expected off, log, or cast)"
+        )))
+    }
+}
+
+fn validate_run_env_mode(raw: &str) -> Result<String> {
+    let normalized = raw.trim().to_ascii_lowercase();
+    if RUN_ENV_MODES.contains(&normalized.as_str()) {
+        Ok(normalized)
+    } else {
+        Err(CoreError::InvalidSetting(format!(
+            "unknown run.env_mode '{raw}' (expected export or send_env)"
+        )))
+    }
+}
+
+fn validate_confirm_token(raw: &str) -> Result<String> {
+    let normalized = raw.trim().to_ascii_lowercase();
+    if CONFIRM_TOKEN_LEVELS.contains(&normalized.as_str()) {
+        Ok(normalized)
+    } else {
+        Err(CoreError::InvalidSetting(format!(
+            "unknown confirm.token level '{raw}' (expected off, high, or critical)"
+        )))
+    }
+}
+
+fn validate_tunnel_reconnect(raw: &str) -> Result<String> {
+    let policies = crate::settings::parse_tunnel_reconnect_policies(raw)?;
+    Ok(serde_json::to_string(&policies)?)
+}
+
 fn slice_is_empty<T>(slice: &[T]) -> bool {
     slice.is_empty()
 }
@@ -287,6 +1134,16 @@ mod tests {
         assert!(keys.contains(&"session.log.backend"));
     }
 
+    #[test]
+    fn boolean_settings_list_true_and_false() {
+        for schema in list_schemas() {
+            if matches!(schema.value_type, SettingValueType::Boolean) {
+                assert_eq!(schema.allowed_values, BOOL_VALUES, "{}", schema.key);
+                assert_eq!(schema.examples, BOOL_VALUES, "{}", schema.key);
+            }
+        }
+    }
+
     #[test]
     fn validates_session_log_settings() {
         assert_eq!(
@@ -308,4 +1165,65 @@ mod tests {
         assert!(validate_setting_value("session.log.backend", "pty").is_err());
         assert!(validate_setting_value("session.log.dir", " ").is_err());
     }
+
+    #[test]
+    fn validates_result_tab_layouts() {
+        let normalized =
+            validate_setting_value("tui.result_tabs", r#"{"parsed":{"wrap":false}}"#).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&normalized).unwrap();
+
+        assert_eq!(value["parsed"]["wrap"], false);
+        assert_eq!(value["stdout"]["wrap"], true);
+        assert!(
+            validate_setting_value("tui.result_tabs", r#"{"parsed":{"wrapp":false}}"#).is_err()
+        );
+        assert!(validate_setting_value("tui.result_tabs", r#"{"summary":{}}"#).is_err());
+    }
+
+    #[test]
+    fn validates_profile_list_layout() {
+        let normalized =
+            validate_setting_value("tui.profile_list", r#"{"columns":["group","last_used"]}"#)
+                .unwrap();
+        let layout = crate::settings::parse_profile_list_layout(&normalized).unwrap();
+
+        assert_eq!(
+            layout.active_columns(),
+            [
+                crate::settings::ProfileColumn::Group,
+                crate::settings::ProfileColumn::LastUsed
+            ]
+        );
+        assert_eq!(layout.active_max_width(), Some(24));
+        assert!(layout.badges);
+        let plain = validate_setting_value("tui.profile_list", r#"{"badges":false}"#).unwrap();
+        assert!(
+            !crate::settings::parse_profile_list_layout(&plain)
+                .unwrap()
+                .badges
+        );
+        assert!(validate_setting_value("tui.profile_list", r#"{"columns":["hostname"]}"#).is_err());
+        assert!(validate_setting_value("tui.profile_list", r#"{"width":10}"#).is_err());
+    }
+
+    #[test]
+    fn validates_pane_layout() {
+        let normalized = validate_setting_value("tui.layout", r#"{"profiles_width":30}"#).unwrap();
+        let layout = crate::settings::parse_pane_layout(&normalized).unwrap();
+
+        assert_eq!(layout.profiles_width, 30);
+        assert_eq!(layout.actions_height, 45);
+        assert_eq!(layout.result_tab, crate::settings::ResultTabName::Stdout);
+        assert!(validate_setting_value("tui.layout", r#"{"profiles_width":90}"#).is_err());
+        assert!(validate_setting_value("tui.layout", r#"{"result_tab":"raw"}"#).is_err());
+    }
+
+    #[test]
+    fn validates_confirm_token_level() {
+        assert_eq!(
+            validate_setting_value("confirm.token", " High ").unwrap(),
+            "high"
+        );
+        assert!(validate_setting_value("confirm.token", "normal").is_err());
+    }
 }
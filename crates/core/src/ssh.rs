@@ -1,15 +1,17 @@
 use std::collections::HashSet;
 use std::ffi::OsString;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use directories::BaseDirs;
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::doctor::{self, ClientKind, ClientOverrides};
+use crate::doctor::{self, ClientKind, ResolvedClient};
+use crate::paths;
 use crate::profile::{DangerLevel, Profile, ProfileStore, ProfileType};
 use crate::settings;
+use crate::util;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -114,12 +116,8 @@ pub fn build_ssh_invocation(
         .map_err(|err| SshBuildError::SettingsError(err.to_string()))?
         .ok_or_else(|| SshBuildError::ProfileNotFound(request.profile_id.to_string()))?;
     let target = ssh_target_from_profile(&profile)?;
-    let client_path = resolve_client_for(
-        ClientKind::Ssh,
-        profile.client_overrides.as_ref(),
-        store.conn(),
-    )?;
-    let auth = ssh_auth_context(store.conn())?;
+    let client_path = resolve_client_for(ClientKind::Ssh, &profile, store.conn())?;
+    let auth = ssh_profile_auth_context(store.conn(), &profile.profile_id)?;
     let args = build_ssh_args(&target, &auth.args);
     let safe_metadata = safe_ssh_metadata(&target, request.source, request.mode, None);
 
@@ -149,17 +147,39 @@ pub fn ssh_target_from_profile(profile: &Profile) -> SshBuildResult<SshTarget> {
     })
 }
 
+/// The client for `profile`: its own override, then the `client_overrides` setting of its
+/// group or the global one, then PATH.
 pub fn resolve_client_for(
     kind: ClientKind,
-    profile_overrides: Option<&ClientOverrides>,
+    profile: &Profile,
     conn: &Connection,
 ) -> SshBuildResult<PathBuf> {
-    let global_overrides = settings::get_client_overrides(conn)
+    let global_overrides = settings::get_client_overrides_for(conn, &profile.profile_id)
         .map_err(|err| SshBuildError::SettingsError(err.to_string()))?;
-    doctor::resolve_client_with_overrides(kind, profile_overrides, global_overrides.as_ref())
-        .ok_or_else(|| SshBuildError::ClientNotFound {
-            kind: kind.as_str(),
-        })
+    doctor::resolve_client_with_overrides(
+        kind,
+        profile.client_overrides.as_ref(),
+        global_overrides.as_ref(),
+    )
+    .ok_or_else(|| SshBuildError::ClientNotFound {
+        kind: kind.as_str(),
+    })
+}
+
+/// Like `resolve_client_for`, but also says whether a profile override, the group or
+/// global override, or PATH supplied the client; a missing client is not an error here.
+pub fn resolve_client_source_for(
+    kind: ClientKind,
+    profile: &Profile,
+    conn: &Connection,
+) -> SshBuildResult<ResolvedClient> {
+    let global_overrides = settings::get_client_overrides_for(conn, &profile.profile_id)
+        .map_err(|err| SshBuildError::SettingsError(err.to_string()))?;
+    Ok(doctor::resolve_client_with_source(
+        kind,
+        profile.client_overrides.as_ref(),
+        global_overrides.as_ref(),
+    ))
 }
 
 pub fn build_ssh_args(target: &SshTarget, auth_args: &[OsString]) -> Vec<OsString> {
@@ -210,6 +230,27 @@ pub fn format_ssh_invocation(
     parts.join(" ")
 }
 
+/// The command line `client_path args...` for display, as a shell would show it: password
+/// and token values are masked (`util::mask_sensitive_args`) and arguments with spaces or
+/// quotes are single-quoted.
+pub fn format_command_line(client_path: &Path, args: &[OsString]) -> String {
+    let parts: Vec<String> = std::iter::once(client_path.as_os_str())
+        .chain(args.iter().map(OsString::as_os_str))
+        .map(|part| part.to_string_lossy().into_owned())
+        .collect();
+    util::mask_sensitive_args(&parts)
+        .into_iter()
+        .map(|part| {
+            if part.is_empty() || part.contains(|c: char| c.is_whitespace() || "'\"".contains(c)) {
+                format!("'{}'", part.replace('\'', "'\\''"))
+            } else {
+                part
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 pub fn normalize_auth_order(order: Vec<SshAuthMethod>) -> SshBuildResult<Vec<SshAuthMethod>> {
     if order.is_empty() {
         return Err(SshBuildError::InvalidAuthOrder(
@@ -288,23 +329,43 @@ pub fn load_ssh_auth_order(conn: &Connection) -> SshBuildResult<Vec<SshAuthMetho
     }
 }
 
+/// [`load_ssh_auth_order`] for one profile, which may set its own order or inherit one
+/// from its group or the current env.
+pub fn load_ssh_auth_order_for(
+    conn: &Connection,
+    profile_id: &str,
+) -> SshBuildResult<Vec<SshAuthMethod>> {
+    match settings::get_ssh_auth_order_for(conn, profile_id)
+        .map_err(|err| SshBuildError::SettingsError(err.to_string()))?
+    {
+        Some(raw) => parse_auth_order_setting(&raw),
+        None => Ok(default_auth_order()),
+    }
+}
+
+/// Key files OpenSSH tries by default, relative to `~/.ssh`.
+pub(crate) const DEFAULT_IDENTITY_FILES: [&str; 7] = [
+    "id_ed25519",
+    "id_rsa",
+    "id_ecdsa",
+    "id_ed25519_sk",
+    "id_ecdsa_sk",
+    "id_dsa",
+    "identity",
+];
+
+/// Whether this build includes the `ssh-native` CommandSet backend.
+pub const NATIVE_BACKEND_AVAILABLE: bool = cfg!(feature = "ssh-native");
+
 pub fn detect_ssh_auth_availability() -> SshAuthAvailability {
     let agent = std::env::var_os("SSH_AUTH_SOCK")
         .map(|value| !value.is_empty())
         .unwrap_or(false);
     let keys = if let Some(dirs) = BaseDirs::new() {
         let ssh_dir = dirs.home_dir().join(".ssh");
-        [
-            "id_ed25519",
-            "id_rsa",
-            "id_ecdsa",
-            "id_ed25519_sk",
-            "id_ecdsa_sk",
-            "id_dsa",
-            "identity",
-        ]
-        .iter()
-        .any(|name| ssh_dir.join(name).exists())
+        DEFAULT_IDENTITY_FILES
+            .iter()
+            .any(|name| ssh_dir.join(name).exists())
     } else {
         false
     };
@@ -347,7 +408,10 @@ pub fn build_ssh_auth_args(
 }
 
 pub fn ssh_auth_context(conn: &Connection) -> SshBuildResult<SshAuthContext> {
-    let order = load_ssh_auth_order(conn)?;
+    Ok(auth_context_for_order(load_ssh_auth_order(conn)?))
+}
+
+fn auth_context_for_order(order: Vec<SshAuthMethod>) -> SshAuthContext {
     let availability = detect_ssh_auth_availability();
     let args = build_ssh_auth_args(&order, &availability);
     let hint = match order.first().copied() {
@@ -365,12 +429,58 @@ pub fn ssh_auth_context(conn: &Connection) -> SshBuildResult<SshAuthContext> {
         .find(|method| is_auth_method_available(*method, &availability));
     let warn_password_fallback = matches!(first_available, Some(SshAuthMethod::Password))
         && order.first().copied() != Some(SshAuthMethod::Password);
-    Ok(SshAuthContext {
+    SshAuthContext {
         order,
         args,
         hint,
         warn_password_fallback,
-    })
+    }
+}
+
+/// [`ssh_auth_context`] with the profile's own or inherited auth order, plus its
+/// connection-sharing options, for ssh, scp, and sftp invocations against one profile.
+pub fn ssh_profile_auth_context(
+    conn: &Connection,
+    profile_id: &str,
+) -> SshBuildResult<SshAuthContext> {
+    let mut auth = auth_context_for_order(load_ssh_auth_order_for(conn, profile_id)?);
+    auth.args.extend(multiplex_args(conn, profile_id)?);
+    Ok(auth)
+}
+
+/// `-o Control*` options when `ssh.multiplex` is on for the profile. Sockets live under
+/// [`paths::ssh_control_dir`], named by ssh's `%C` connection hash. Windows OpenSSH has
+/// no connection sharing, so nothing is added there.
+pub fn multiplex_args(conn: &Connection, profile_id: &str) -> SshBuildResult<Vec<OsString>> {
+    if cfg!(windows) {
+        return Ok(Vec::new());
+    }
+    let Some(persist) = settings::get_ssh_multiplex(conn, profile_id)
+        .map_err(|err| SshBuildError::SettingsError(err.to_string()))?
+    else {
+        return Ok(Vec::new());
+    };
+    let dir =
+        paths::ssh_control_dir().map_err(|err| SshBuildError::SettingsError(err.to_string()))?;
+    Ok(build_multiplex_args(&dir, &persist))
+}
+
+fn build_multiplex_args(control_dir: &Path, persist: &str) -> Vec<OsString> {
+    let control_path = control_dir.join("%C").to_string_lossy().into_owned();
+    // ssh splits option values on whitespace unless they are quoted.
+    let control_path = if control_path.contains(char::is_whitespace) {
+        format!("\"{control_path}\"")
+    } else {
+        control_path
+    };
+    [
+        "ControlMaster=auto".to_string(),
+        format!("ControlPath={control_path}"),
+        format!("ControlPersist={persist}"),
+    ]
+    .into_iter()
+    .flat_map(|option| [OsString::from("-o"), OsString::from(option)])
+    .collect()
 }
 
 fn is_auth_method_available(method: SshAuthMethod, availability: &SshAuthAvailability) -> bool {
@@ -388,6 +498,7 @@ mod tests {
     use std::fs;
 
     use crate::db;
+    use crate::doctor::ClientOverrides;
     use crate::profile::NewProfile;
 
     fn fake_ssh_path(name: &str) -> PathBuf {
@@ -453,6 +564,47 @@ mod tests {
         );
         assert_eq!(invocation.safe_metadata["source"], "tui");
         assert_eq!(invocation.safe_metadata["mode"], "interactive");
+        assert!(
+            format_command_line(&invocation.client_path, &invocation.args)
+                .ends_with(" alice@example.com")
+        );
+
+        let profile = store.get("p_test").unwrap().unwrap();
+        let resolved = resolve_client_source_for(ClientKind::Ssh, &profile, store.conn()).unwrap();
+        assert_eq!(resolved.path.as_deref(), Some(fake_ssh.as_path()));
+        assert_eq!(resolved.source, doctor::ClientSource::ProfileOverride);
+        let broken = ClientOverrides {
+            ssh: Some("/nonexistent/ssh".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            broken.invalid_paths(),
+            vec![(ClientKind::Ssh, "/nonexistent/ssh")]
+        );
+        let broken_profile = Profile {
+            client_overrides: Some(broken),
+            ..profile.clone()
+        };
+        assert_ne!(
+            resolve_client_source_for(ClientKind::Ssh, &broken_profile, store.conn())
+                .unwrap()
+                .source,
+            doctor::ClientSource::ProfileOverride
+        );
+        assert_eq!(
+            format_command_line(
+                Path::new("/usr/bin/ssh"),
+                &[OsString::from("-o"), OsString::from("ControlPath=/tmp/a b")]
+            ),
+            "/usr/bin/ssh -o 'ControlPath=/tmp/a b'"
+        );
+        assert_eq!(
+            format_command_line(
+                Path::new("plink"),
+                &[OsString::from("-pw"), OsString::from("hunter2")]
+            ),
+            "plink -pw ****"
+        );
 
         let _ = fs::remove_file(invocation.client_path);
     }
@@ -550,4 +702,25 @@ mod tests {
         let err = parse_auth_order_setting("agent,agent").unwrap_err();
         assert!(matches!(err, SshBuildError::InvalidAuthOrder(_)));
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn multiplex_args_quote_control_paths_with_spaces() {
+        let args = build_multiplex_args(Path::new("/home/a b/teradock/ssh-control"), "10m");
+        let args = args
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            args,
+            vec![
+                "-o",
+                "ControlMaster=auto",
+                "-o",
+                "ControlPath=\"/home/a b/teradock/ssh-control/%C\"",
+                "-o",
+                "ControlPersist=10m",
+            ]
+        );
+    }
 }
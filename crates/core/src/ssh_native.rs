@@ -0,0 +1,310 @@
+//! In-process SSH over libssh2 (`ssh-native` feature): one authenticated session per
+//! profile, reused for every CommandSet step, with exit codes read from the exec channel
+//! instead of inferred from the ssh client's own exit status.
+//!
+//! Host keys must already be in `~/.ssh/known_hosts`; unknown or changed keys are
+//! rejected rather than trusted on first use. Connect once with the ssh client to add one.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use directories::BaseDirs;
+use rusqlite::Connection;
+use ssh2::{CheckResult, KeyboardInteractivePrompt, KnownHostFileKind, Prompt, Session};
+use zeroize::Zeroizing;
+
+use crate::error::{CoreError, Result, RunError};
+use crate::profile::Profile;
+use crate::ssh::{self, SshAuthMethod, DEFAULT_IDENTITY_FILES};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Credentials tried in `ssh_auth_order` order.
+pub struct NativeAuth {
+    pub order: Vec<SshAuthMethod>,
+    pub identity_files: Vec<PathBuf>,
+    /// Sent for both password and keyboard-interactive prompts.
+    pub password: Option<Zeroizing<String>>,
+}
+
+impl NativeAuth {
+    /// The profile's auth order (its own, its group's, or the global one) and the default
+    /// identity files under `~/.ssh`.
+    pub fn from_settings(
+        conn: &Connection,
+        profile_id: &str,
+        password: Option<&str>,
+    ) -> Result<Self> {
+        let order = ssh::load_ssh_auth_order_for(conn, profile_id)
+            .map_err(|err| CoreError::InvalidSetting(err.to_string()))?;
+        let identity_files = BaseDirs::new()
+            .map(|dirs| {
+                let ssh_dir = dirs.home_dir().join(".ssh");
+                DEFAULT_IDENTITY_FILES
+                    .iter()
+                    .map(|name| ssh_dir.join(name))
+                    .filter(|path| path.is_file())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Self {
+            order,
+            identity_files,
+            password: password.map(|value| Zeroizing::new(value.to_string())),
+        })
+    }
+}
+
+/// What one remote command produced.
+pub struct NativeOutput {
+    pub exit_code: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+pub struct NativeSession {
+    session: Session,
+}
+
+impl NativeSession {
+    pub fn connect(profile: &Profile, auth: &NativeAuth) -> Result<Self> {
+        let stream = connect_tcp(&profile.host, profile.port)?;
+        let mut session = Session::new().map_err(ssh_error("start session"))?;
+        session.set_tcp_stream(stream);
+        session.set_timeout(CONNECT_TIMEOUT.as_millis() as u32);
+        session.handshake().map_err(ssh_error("handshake"))?;
+        verify_host_key(&session, &profile.host, profile.port)?;
+        authenticate(&session, &profile.user, auth)?;
+        // Steps may legitimately run for a long time; per-step timeouts are enforced in exec.
+        session.set_timeout(0);
+        Ok(Self { session })
+    }
+
+    /// Runs `cmd` on a new exec channel with `input` (or nothing) as its stdin. Stdout and
+    /// stderr are read together so a chatty stderr cannot stall the channel window.
+    pub fn exec(
+        &self,
+        cmd: &str,
+        input: Option<&[u8]>,
+        timeout: Option<Duration>,
+    ) -> Result<NativeOutput> {
+        let mut channel = self
+            .session
+            .channel_session()
+            .map_err(ssh_error("open channel"))?;
+        channel.exec(cmd).map_err(ssh_error("exec"))?;
+        if let Some(input) = input {
+            channel.write_all(input)?;
+        }
+        channel.send_eof().map_err(ssh_error("send eof"))?;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        self.session.set_blocking(false);
+        let drained = loop {
+            let progressed = match (
+                read_available(&mut channel, &mut stdout),
+                read_available(&mut channel.stderr(), &mut stderr),
+            ) {
+                (Ok(out), Ok(err)) => out || err,
+                (Err(err), _) | (_, Err(err)) => break Err(CoreError::Io(err)),
+            };
+            if channel.eof() {
+                break Ok(());
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break Err(CoreError::Io(std::io::Error::new(
+                    ErrorKind::TimedOut,
+                    format!(
+                        "timeout after {}ms",
+                        timeout.unwrap_or_default().as_millis()
+                    ),
+                )));
+            }
+            if !progressed {
+                thread::sleep(POLL_INTERVAL);
+            }
+        };
+        self.session.set_blocking(true);
+        if let Err(err) = drained {
+            let _ = channel.close();
+            return Err(err);
+        }
+        channel.read_to_end(&mut stdout)?;
+        channel.stderr().read_to_end(&mut stderr)?;
+        channel.wait_close().map_err(ssh_error("close channel"))?;
+        let exit_code = channel.exit_status().map_err(ssh_error("exit status"))?;
+        Ok(NativeOutput {
+            exit_code,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+impl Drop for NativeSession {
+    fn drop(&mut self) {
+        let _ = self.session.disconnect(None, "td run finished", None);
+    }
+}
+
+fn connect_tcp(host: &str, port: u16) -> Result<TcpStream> {
+    let mut last_error = None;
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_error = Some(err),
+        }
+    }
+    Err(CoreError::Run(RunError::CommandExecution(format!(
+        "failed to connect to {host}:{port}: {}",
+        last_error.map_or_else(|| "no addresses".to_string(), |err| err.to_string())
+    ))))
+}
+
+fn verify_host_key(session: &Session, host: &str, port: u16) -> Result<()> {
+    let (key, _) = session.host_key().ok_or_else(|| {
+        CoreError::Run(RunError::CommandExecution(format!(
+            "{host} did not send a host key"
+        )))
+    })?;
+    let mut known_hosts = session.known_hosts().map_err(ssh_error("known_hosts"))?;
+    if let Some(dirs) = BaseDirs::new() {
+        let path = dirs.home_dir().join(".ssh").join("known_hosts");
+        if path.is_file() {
+            known_hosts
+                .read_file(&path, KnownHostFileKind::OpenSSH)
+                .map_err(ssh_error("read known_hosts"))?;
+        }
+    }
+    let problem = match known_hosts.check_port(host, port, key) {
+        CheckResult::Match => return Ok(()),
+        CheckResult::Mismatch => "host key does not match ~/.ssh/known_hosts",
+        CheckResult::NotFound => {
+            "host key is not in ~/.ssh/known_hosts; connect once with ssh to verify it"
+        }
+        CheckResult::Failure => "failed to check the host key against ~/.ssh/known_hosts",
+    };
+    Err(CoreError::Run(RunError::CommandExecution(format!(
+        "{host}:{port}: {problem}"
+    ))))
+}
+
+fn authenticate(session: &Session, user: &str, auth: &NativeAuth) -> Result<()> {
+    let offered = session
+        .auth_methods(user)
+        .map(str::to_string)
+        .unwrap_or_default();
+    for method in &auth.order {
+        match method {
+            SshAuthMethod::Agent => {
+                let _ = session.userauth_agent(user);
+            }
+            SshAuthMethod::Keys => {
+                for identity in &auth.identity_files {
+                    if session
+                        .userauth_pubkey_file(user, None, identity, None)
+                        .is_ok()
+                    {
+                        break;
+                    }
+                }
+            }
+            SshAuthMethod::Password => {
+                let Some(password) = &auth.password else {
+                    continue;
+                };
+                if offered.contains("keyboard-interactive") {
+                    let mut prompt = PasswordPrompt(password);
+                    let _ = session.userauth_keyboard_interactive(user, &mut prompt);
+                }
+                if !session.authenticated() && offered.contains("password") {
+                    let _ = session.userauth_password(user, password);
+                }
+            }
+        }
+        if session.authenticated() {
+            return Ok(());
+        }
+    }
+    Err(CoreError::Run(RunError::CommandExecution(format!(
+        "native ssh authentication failed for {user} (server offers: {offered})"
+    ))))
+}
+
+/// Answers every keyboard-interactive prompt with the stored password.
+struct PasswordPrompt<'a>(&'a str);
+
+impl KeyboardInteractivePrompt for PasswordPrompt<'_> {
+    fn prompt<'b>(
+        &mut self,
+        _username: &str,
+        _instructions: &str,
+        prompts: &[Prompt<'b>],
+    ) -> Vec<String> {
+        prompts.iter().map(|_| self.0.to_string()).collect()
+    }
+}
+
+/// Reads whatever is buffered without blocking; returns whether anything was read.
+fn read_available(reader: &mut impl Read, into: &mut Vec<u8>) -> std::io::Result<bool> {
+    let mut buf = [0u8; 8192];
+    let mut progressed = false;
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(progressed),
+            Ok(read) => {
+                into.extend_from_slice(&buf[..read]);
+                progressed = true;
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(progressed),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn ssh_error(action: &'static str) -> impl Fn(ssh2::Error) -> CoreError {
+    move |err| {
+        CoreError::Run(RunError::CommandExecution(format!(
+            "native ssh {action}: {err}"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_until_would_block() {
+        struct Chunks(Vec<std::io::Result<Vec<u8>>>);
+        impl Read for Chunks {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                match self.0.pop() {
+                    Some(Ok(chunk)) => {
+                        buf[..chunk.len()].copy_from_slice(&chunk);
+                        Ok(chunk.len())
+                    }
+                    Some(Err(err)) => Err(err),
+                    None => Ok(0),
+                }
+            }
+        }
+        let mut reader = Chunks(vec![
+            Ok(b"tail".to_vec()),
+            Err(ErrorKind::WouldBlock.into()),
+            Ok(b"world\n".to_vec()),
+            Ok(b"hello ".to_vec()),
+        ]);
+        let mut out = Vec::new();
+        assert!(read_available(&mut reader, &mut out).unwrap());
+        assert_eq!(out, b"hello world\n");
+        assert!(read_available(&mut reader, &mut out).unwrap());
+        assert!(!read_available(&mut reader, &mut out).unwrap());
+        assert_eq!(out, b"hello world\ntail");
+    }
+}
@@ -2,9 +2,13 @@ use crate::profile::{Profile, ProfileType};
 use serde::Serialize;
 use serde_json::Value;
 use std::ffi::OsString;
+use std::io::Read;
 use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
 use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Serialize)]
@@ -30,6 +34,58 @@ pub struct TestReport {
     pub duration_ms: i64,
     pub ok: bool,
     pub checks: Vec<TestCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency: Option<LatencyStats>,
+}
+
+/// Round-trip times of repeated TCP connects; `*_ms` are `None` when every sample was lost.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencyStats {
+    pub samples: u32,
+    pub received: u32,
+    pub loss_pct: f64,
+    pub min_ms: Option<f64>,
+    pub avg_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+}
+
+impl LatencyStats {
+    fn from_samples(samples: &[Option<Duration>]) -> Self {
+        let rtts = samples
+            .iter()
+            .flatten()
+            .map(|rtt| rtt.as_secs_f64() * 1000.0)
+            .collect::<Vec<_>>();
+        let received = rtts.len() as u32;
+        let total = samples.len() as u32;
+        let loss_pct = if total == 0 {
+            0.0
+        } else {
+            f64::from(total - received) * 100.0 / f64::from(total)
+        };
+        let min_ms = rtts.iter().copied().reduce(f64::min);
+        let max_ms = rtts.iter().copied().reduce(f64::max);
+        let avg_ms = (!rtts.is_empty()).then(|| rtts.iter().sum::<f64>() / f64::from(received));
+        Self {
+            samples: total,
+            received,
+            loss_pct,
+            min_ms,
+            avg_ms,
+            max_ms,
+        }
+    }
+
+    /// e.g. `rtt min/avg/max 0.4/0.6/1.1 ms, 0% loss (5 samples)`.
+    pub fn summary(&self) -> String {
+        match (self.min_ms, self.avg_ms, self.max_ms) {
+            (Some(min), Some(avg), Some(max)) => format!(
+                "rtt min/avg/max {min:.1}/{avg:.1}/{max:.1} ms, {:.0}% loss ({} samples)",
+                self.loss_pct, self.samples
+            ),
+            _ => format!("100% loss ({} samples)", self.samples),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -46,11 +102,22 @@ pub struct SshBatchCommand {
 pub struct TestOptions {
     pub tcp_timeout: Duration,
     pub ssh: Option<SshBatchCommand>,
+    /// How long to wait for telnet negotiation bytes; `None` skips the banner check.
+    pub telnet_banner_timeout: Option<Duration>,
+    /// Number of extra TCP connects used to measure RTT and loss; `None` skips it.
+    pub latency_samples: Option<u32>,
 }
 
+/// Pause between latency samples so a burst of connects is not mistaken for a scan.
+const LATENCY_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
 pub fn run_profile_test(profile: &Profile, options: &TestOptions) -> TestReport {
     let started = Instant::now();
     let mut checks = Vec::new();
+    if profile.profile_type == ProfileType::Serial {
+        checks.push(check_serial_port(&profile.host, profile.port as u32));
+        return finish_report(profile, started, checks, None);
+    }
 
     let (dns_ok, addresses, dns_detail, dns_duration) = resolve_dns(&profile.host, profile.port);
     checks.push(TestCheck {
@@ -69,13 +136,14 @@ pub fn run_profile_test(profile: &Profile, options: &TestOptions) -> TestReport
         exit_code: None,
     });
 
-    let (tcp_ok, tcp_detail, tcp_duration, connected_addr) = if dns_ok && !addresses.is_empty() {
+    let (tcp_ok, tcp_detail, tcp_duration, connected) = if dns_ok && !addresses.is_empty() {
         connect_tcp(&addresses, options.tcp_timeout)
     } else if dns_ok {
         (false, Some("no addresses resolved".to_string()), 0, None)
     } else {
         (false, Some("skipped (dns failed)".to_string()), 0, None)
     };
+    let connected_addr = connected.as_ref().map(|(addr, _)| *addr);
 
     checks.push(TestCheck {
         name: "tcp".into(),
@@ -94,6 +162,53 @@ pub fn run_profile_test(profile: &Profile, options: &TestOptions) -> TestReport
         exit_code: None,
     });
 
+    if let (ProfileType::Telnet, Some(timeout)) =
+        (profile.profile_type, options.telnet_banner_timeout)
+    {
+        checks.push(match connected {
+            Some((_, stream)) => check_telnet_banner(stream, timeout),
+            None => TestCheck {
+                name: "telnet".into(),
+                ok: false,
+                skipped: true,
+                duration_ms: Some(0),
+                detail: Some("skipped (tcp failed)".to_string()),
+                data: None,
+                exit_code: None,
+            },
+        });
+    }
+
+    let mut latency = None;
+    if let Some(samples) = options.latency_samples.filter(|samples| *samples > 0) {
+        checks.push(match connected_addr {
+            Some(addr) => {
+                let started = Instant::now();
+                let stats = measure_latency(addr, samples, options.tcp_timeout);
+                let check = TestCheck {
+                    name: "latency".into(),
+                    ok: stats.received > 0,
+                    skipped: false,
+                    duration_ms: Some(started.elapsed().as_millis() as i64),
+                    detail: Some(stats.summary()),
+                    data: None,
+                    exit_code: None,
+                };
+                latency = Some(stats);
+                check
+            }
+            None => TestCheck {
+                name: "latency".into(),
+                ok: false,
+                skipped: true,
+                duration_ms: Some(0),
+                detail: Some("skipped (tcp failed)".to_string()),
+                data: None,
+                exit_code: None,
+            },
+        });
+    }
+
     if let Some(ssh) = options.ssh.as_ref() {
         let (ssh_ok, ssh_detail, ssh_duration, exit_code, stderr) = if tcp_ok {
             run_ssh_batch(ssh)
@@ -116,6 +231,15 @@ pub fn run_profile_test(profile: &Profile, options: &TestOptions) -> TestReport
         });
     }
 
+    finish_report(profile, started, checks, latency)
+}
+
+fn finish_report(
+    profile: &Profile,
+    started: Instant,
+    checks: Vec<TestCheck>,
+    latency: Option<LatencyStats>,
+) -> TestReport {
     let ok = checks.iter().all(|check| check.ok || check.skipped);
     let duration_ms = started.elapsed().as_millis() as i64;
 
@@ -127,6 +251,7 @@ pub fn run_profile_test(profile: &Profile, options: &TestOptions) -> TestReport
         duration_ms,
         ok,
         checks,
+        latency,
     }
 }
 
@@ -162,18 +287,18 @@ fn resolve_dns(host: &str, port: u16) -> (bool, Vec<SocketAddr>, Option<String>,
 fn connect_tcp(
     addresses: &[SocketAddr],
     timeout: Duration,
-) -> (bool, Option<String>, i64, Option<SocketAddr>) {
+) -> (bool, Option<String>, i64, Option<(SocketAddr, TcpStream)>) {
     let started = Instant::now();
     let mut last_error: Option<String> = None;
     for addr in addresses {
         match TcpStream::connect_timeout(addr, timeout) {
-            Ok(_) => {
+            Ok(stream) => {
                 let duration_ms = started.elapsed().as_millis() as i64;
                 return (
                     true,
                     Some(format!("connected to {addr}")),
                     duration_ms,
-                    Some(*addr),
+                    Some((*addr, stream)),
                 );
             }
             Err(err) => {
@@ -190,6 +315,176 @@ fn connect_tcp(
     )
 }
 
+fn measure_latency(addr: SocketAddr, samples: u32, timeout: Duration) -> LatencyStats {
+    let rtts = (0..samples)
+        .map(|index| {
+            if index > 0 {
+                thread::sleep(LATENCY_SAMPLE_INTERVAL);
+            }
+            let started = Instant::now();
+            TcpStream::connect_timeout(&addr, timeout)
+                .ok()
+                .map(|_| started.elapsed())
+        })
+        .collect::<Vec<_>>();
+    LatencyStats::from_samples(&rtts)
+}
+
+const IAC: u8 = 255;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+/// Reads whatever a telnet server sends first: option negotiation (IAC DO/WILL ...) and
+/// banner text. Any data counts as a live telnet service; silence or an immediate close
+/// does not.
+fn check_telnet_banner(mut stream: TcpStream, timeout: Duration) -> TestCheck {
+    let started = Instant::now();
+    let mut received = Vec::new();
+    let mut buf = [0u8; 512];
+    let _ = stream.set_read_timeout(Some(Duration::from_millis(200)));
+    let mut closed = false;
+    while started.elapsed() < timeout && received.len() < 4096 {
+        match stream.read(&mut buf) {
+            Ok(0) => {
+                closed = true;
+                break;
+            }
+            Ok(n) => {
+                received.extend_from_slice(&buf[..n]);
+                // Stop once a banner line (not only negotiation) has arrived.
+                if parse_telnet_stream(&received).1.contains('\n') {
+                    break;
+                }
+            }
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                if !received.is_empty() {
+                    break;
+                }
+            }
+            Err(err) => {
+                return TestCheck {
+                    name: "telnet".into(),
+                    ok: false,
+                    skipped: false,
+                    duration_ms: Some(started.elapsed().as_millis() as i64),
+                    detail: Some(err.to_string()),
+                    data: None,
+                    exit_code: None,
+                };
+            }
+        }
+    }
+    let duration_ms = started.elapsed().as_millis() as i64;
+    let (negotiation, banner) = parse_telnet_stream(&received);
+    let banner = banner.trim().to_string();
+    let (ok, detail) = if received.is_empty() {
+        let reason = if closed {
+            "connection closed without data".to_string()
+        } else {
+            format!("no data within {}ms", timeout.as_millis())
+        };
+        (false, reason)
+    } else if banner.is_empty() {
+        (true, format!("{} negotiation option(s)", negotiation.len()))
+    } else {
+        (
+            true,
+            format!("banner: {}", banner.lines().next().unwrap_or("")),
+        )
+    };
+    TestCheck {
+        name: "telnet".into(),
+        ok,
+        skipped: false,
+        duration_ms: Some(duration_ms),
+        detail: Some(detail),
+        data: (!received.is_empty()).then(|| {
+            serde_json::json!({
+                "negotiation": negotiation,
+                "banner": banner,
+            })
+        }),
+        exit_code: None,
+    }
+}
+
+/// Splits raw telnet bytes into negotiation commands (e.g. `DO 1`) and printable text.
+fn parse_telnet_stream(bytes: &[u8]) -> (Vec<String>, String) {
+    let mut negotiation = Vec::new();
+    let mut text = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != IAC {
+            text.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        match bytes.get(i + 1) {
+            Some(&IAC) => {
+                text.push(IAC);
+                i += 2;
+            }
+            Some(&SB) => {
+                let option = bytes.get(i + 2).copied().unwrap_or_default();
+                negotiation.push(format!("SB {option}"));
+                // Skip to IAC SE.
+                i += 3;
+                while i + 1 < bytes.len() && !(bytes[i] == IAC && bytes[i + 1] == SE) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            Some(&verb @ 251..=254) => {
+                let name = match verb {
+                    251 => "WILL",
+                    252 => "WONT",
+                    253 => "DO",
+                    _ => "DONT",
+                };
+                match bytes.get(i + 2) {
+                    Some(option) => negotiation.push(format!("{name} {option}")),
+                    None => negotiation.push(name.to_string()),
+                }
+                i += 3;
+            }
+            Some(_) => i += 2,
+            None => i += 1,
+        }
+    }
+    let text = String::from_utf8_lossy(&text)
+        .chars()
+        .filter(|c| !c.is_control() || *c == '\n')
+        .collect();
+    (negotiation, text)
+}
+
+/// Opens the serial device at the profile's baud rate and closes it again.
+fn check_serial_port(port_name: &str, baud_rate: u32) -> TestCheck {
+    let started = Instant::now();
+    let result = serialport::new(port_name, baud_rate)
+        .timeout(Duration::from_millis(100))
+        .open();
+    let duration_ms = started.elapsed().as_millis() as i64;
+    let (ok, detail) = match result {
+        Ok(_) => (true, format!("opened {port_name} at {baud_rate} baud")),
+        Err(err) => (false, format!("failed to open {port_name}: {err}")),
+    };
+    TestCheck {
+        name: "serial".into(),
+        ok,
+        skipped: false,
+        duration_ms: Some(duration_ms),
+        detail: Some(detail),
+        data: None,
+        exit_code: None,
+    }
+}
+
 fn run_ssh_batch(ssh: &SshBatchCommand) -> (bool, String, i64, Option<i32>, Option<String>) {
     let started = Instant::now();
     let timeout_secs = ssh.connect_timeout.as_secs().max(1);
@@ -248,10 +543,39 @@ impl Default for TestOptions {
         Self {
             tcp_timeout: Duration::from_secs(5),
             ssh: None,
+            telnet_banner_timeout: Some(Duration::from_secs(2)),
+            latency_samples: None,
         }
     }
 }
 
+/// Runs `run_profile_test` for many profiles on up to `workers` threads. Reports come
+/// back in the same order as `jobs`.
+pub fn run_profile_tests(jobs: &[(Profile, TestOptions)], workers: usize) -> Vec<TestReport> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(jobs.len()));
+    thread::scope(|scope| {
+        for _ in 0..workers.clamp(1, jobs.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some((profile, options)) = jobs.get(index) else {
+                    break;
+                };
+                let report = run_profile_test(profile, options);
+                results
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .push((index, report));
+            });
+        }
+    });
+    let mut results = results
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, report)| report).collect()
+}
+
 impl TestReport {
     pub fn ssh_exit_code(&self) -> Option<i32> {
         self.checks
@@ -259,6 +583,43 @@ impl TestReport {
             .find(|check| check.name == "ssh")
             .and_then(|check| check.exit_code)
     }
+
+    /// `name: detail` of the first failed check, e.g. `tcp: connection refused`.
+    pub fn failure_summary(&self) -> Option<String> {
+        self.checks
+            .iter()
+            .find(|check| check.is_failed())
+            .map(|check| {
+                format!(
+                    "{}: {}",
+                    check.name,
+                    check.detail.as_deref().unwrap_or("failed")
+                )
+            })
+    }
+}
+
+const PREFLIGHT_TCP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Fast DNS+TCP probe run before launching an interactive client (`connect.preflight`).
+/// Returns why the host looks unreachable, or `None` when it answered or the profile is
+/// not a network profile.
+pub fn preflight(profile: &Profile) -> Option<String> {
+    if !is_network_profile(profile) {
+        return None;
+    }
+    let options = TestOptions {
+        tcp_timeout: PREFLIGHT_TCP_TIMEOUT,
+        ssh: None,
+        telnet_banner_timeout: None,
+        latency_samples: None,
+    };
+    let report = run_profile_test(profile, &options);
+    if report.ok {
+        None
+    } else {
+        report.failure_summary()
+    }
 }
 
 impl TestOptions {
@@ -266,6 +627,11 @@ impl TestOptions {
         self.ssh = Some(ssh);
         self
     }
+
+    pub fn with_latency_samples(mut self, samples: Option<u32>) -> Self {
+        self.latency_samples = samples;
+        self
+    }
 }
 
 impl SshBatchCommand {
@@ -301,3 +667,96 @@ impl TestCheck {
 pub fn is_network_profile(profile: &Profile) -> bool {
     matches!(profile.profile_type, ProfileType::Ssh | ProfileType::Telnet)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn latency_stats_report_rtt_and_loss() {
+        let stats = LatencyStats::from_samples(&[
+            Some(Duration::from_millis(2)),
+            None,
+            Some(Duration::from_millis(4)),
+            None,
+        ]);
+        assert_eq!(stats.received, 2);
+        assert_eq!(stats.loss_pct, 50.0);
+        assert_eq!(stats.min_ms, Some(2.0));
+        assert_eq!(stats.avg_ms, Some(3.0));
+        assert_eq!(stats.max_ms, Some(4.0));
+        assert_eq!(
+            stats.summary(),
+            "rtt min/avg/max 2.0/3.0/4.0 ms, 50% loss (4 samples)"
+        );
+        assert_eq!(
+            LatencyStats::from_samples(&[None, None]).summary(),
+            "100% loss (2 samples)"
+        );
+    }
+
+    #[test]
+    fn latency_check_samples_open_port() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let profile = Profile {
+            profile_id: "p_lat".into(),
+            name: "p_lat".into(),
+            profile_type: ProfileType::Ssh,
+            host: "127.0.0.1".into(),
+            port,
+            user: "alice".into(),
+            danger_level: crate::profile::DangerLevel::Normal,
+            group: None,
+            tags: Vec::new(),
+            note: None,
+            initial_send: None,
+            client_overrides: None,
+            created_at: 0,
+            updated_at: 0,
+            last_used_at: None,
+            archived_at: None,
+        };
+
+        let report = run_profile_test(
+            &profile,
+            &TestOptions::default().with_latency_samples(Some(3)),
+        );
+
+        assert!(report.ok);
+        let latency = report.latency.expect("latency stats");
+        assert_eq!((latency.samples, latency.received), (3, 3));
+        assert!(report.checks.iter().any(|check| check.name == "latency"));
+    }
+
+    #[test]
+    fn parses_telnet_negotiation_and_banner() {
+        let bytes = [
+            IAC, 253, 24, IAC, 251, 1, IAC, SB, 24, 1, IAC, SE, b'l', b'o', b'g', b'i', b'n', b':',
+        ];
+        let (negotiation, banner) = parse_telnet_stream(&bytes);
+        assert_eq!(negotiation, vec!["DO 24", "WILL 1", "SB 24"]);
+        assert_eq!(banner, "login:");
+    }
+
+    #[test]
+    fn telnet_banner_check_reads_server_greeting() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            socket.write_all(&[IAC, 253, 1]).unwrap();
+            socket.write_all(b"router1 login: \n").unwrap();
+        });
+        let stream = TcpStream::connect(addr).unwrap();
+
+        let check = check_telnet_banner(stream, Duration::from_secs(2));
+        server.join().unwrap();
+
+        assert!(check.ok, "{:?}", check.detail);
+        assert_eq!(check.detail.as_deref(), Some("banner: router1 login:"));
+    }
+}
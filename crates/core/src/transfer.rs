@@ -1,9 +1,17 @@
 use std::env;
 use std::ffi::OsString;
+use std::fs::File;
 use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+use rusqlite::{params, Connection, Row};
+use serde::Serialize;
+use tracing::warn;
 
 use crate::doctor::ClientKind;
-use crate::error::{CoreError, Result};
+use crate::error::{CoreError, Result, TransferError};
+use crate::oplog::OpLogEntry;
 use crate::profile::Profile;
 use crate::util::now_ms;
 
@@ -13,6 +21,25 @@ pub enum TransferDirection {
     Pull,
 }
 
+impl TransferDirection {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "push" => Ok(Self::Push),
+            "pull" => Ok(Self::Pull),
+            _ => Err(CoreError::Transfer(TransferError::InvalidDirection(
+                value.to_string(),
+            ))),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Push => "push",
+            Self::Pull => "pull",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TransferVia {
     Scp,
@@ -26,8 +53,8 @@ impl TransferVia {
             "scp" => Ok(Self::Scp),
             "sftp" => Ok(Self::Sftp),
             "ftp" => Ok(Self::Ftp),
-            _ => Err(CoreError::InvalidCommandSpec(format!(
-                "invalid transfer client: {value}"
+            _ => Err(CoreError::Transfer(TransferError::InvalidClient(
+                value.to_string(),
             ))),
         }
     }
@@ -126,3 +153,441 @@ impl Drop for TransferTempDir {
         let _ = std::fs::remove_dir_all(&self.path);
     }
 }
+
+pub struct TransferOutcome {
+    pub ok: bool,
+    pub exit_code: i32,
+    pub duration_ms: i64,
+    pub client_used: PathBuf,
+    pub insecure: bool,
+}
+
+/// Runs one push or pull with `client` and waits for it. The client writes to the
+/// terminal, so scp and sftp show their own progress. FTP logs in with `TD_FTP_PASSWORD`;
+/// callers check `allow_insecure_transfers` before asking for an FTP transfer.
+pub fn execute(
+    profile: &Profile,
+    direction: TransferDirection,
+    local_path: &Path,
+    remote_path: &str,
+    via: TransferVia,
+    client: PathBuf,
+    auth_args: &[OsString],
+) -> Result<TransferOutcome> {
+    let mut cmd = Command::new(&client);
+    let mut stdin_file: Option<File> = None;
+    let _batch_guard: Option<TransferTempDir>;
+    let insecure = via.is_insecure();
+    if insecure {
+        warn!(
+            "insecure ftp transfer approved for {} -> {}",
+            profile.profile_id, remote_path
+        );
+    }
+    let args = match via {
+        TransferVia::Scp => {
+            _batch_guard = None;
+            build_scp_args(profile, direction, local_path, remote_path)
+        }
+        TransferVia::Sftp => {
+            let batch_dir = TransferTempDir::new("sftp-batch")?;
+            let batch_path = batch_dir.path().join("batch.txt");
+            let batch_contents = build_sftp_batch(direction, local_path, remote_path);
+            std::fs::write(&batch_path, batch_contents)?;
+            _batch_guard = Some(batch_dir);
+            build_sftp_args(profile, &batch_path)
+        }
+        TransferVia::Ftp => {
+            let batch_dir = TransferTempDir::new("ftp-batch")?;
+            let batch_path = batch_dir.path().join("batch.txt");
+            let password = env::var("TD_FTP_PASSWORD").unwrap_or_default();
+            if password.is_empty() {
+                warn!("TD_FTP_PASSWORD is not set; FTP login may fail unless anonymous access is enabled.");
+            }
+            let batch_contents =
+                build_ftp_batch(profile, &password, direction, local_path, remote_path);
+            std::fs::write(&batch_path, batch_contents)?;
+            stdin_file = Some(File::open(&batch_path)?);
+            _batch_guard = Some(batch_dir);
+            build_ftp_args(profile)
+        }
+    };
+
+    if matches!(via, TransferVia::Scp | TransferVia::Sftp) {
+        cmd.args(auth_args);
+    }
+    if let Some(file) = stdin_file {
+        cmd.stdin(file);
+    }
+    cmd.args(args)
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+
+    let started = Instant::now();
+    let status = cmd.status()?;
+    let duration_ms = started.elapsed().as_millis() as i64;
+    let exit_code = status.code().unwrap_or_default();
+    Ok(TransferOutcome {
+        ok: status.success(),
+        exit_code,
+        duration_ms,
+        client_used: client,
+        insecure,
+    })
+}
+
+/// The op log entry for a finished transfer; `op` is usually `push` or `pull`.
+pub fn log_entry(
+    profile: &Profile,
+    direction: TransferDirection,
+    local_path: &Path,
+    remote_path: &str,
+    via: TransferVia,
+    outcome: &TransferOutcome,
+    op: &str,
+) -> OpLogEntry {
+    OpLogEntry {
+        op: op.into(),
+        profile_id: Some(profile.profile_id.clone()),
+        client_used: Some(outcome.client_used.to_string_lossy().into_owned()),
+        ok: outcome.ok,
+        exit_code: Some(outcome.exit_code),
+        duration_ms: Some(outcome.duration_ms),
+        meta_json: Some(serde_json::json!({
+            "via": via.as_str(),
+            "direction": direction.as_str(),
+            "local_path": local_path.display().to_string(),
+            "remote_path": remote_path,
+            "insecure": outcome.insecure,
+        })),
+    }
+}
+
+fn build_ftp_args(profile: &Profile) -> Vec<OsString> {
+    vec![
+        OsString::from("-i"),
+        OsString::from("-n"),
+        OsString::from("-v"),
+        OsString::from(profile.host.clone()),
+        OsString::from(profile.port.to_string()),
+    ]
+}
+
+fn build_ftp_batch(
+    profile: &Profile,
+    password: &str,
+    direction: TransferDirection,
+    local_path: &Path,
+    remote_path: &str,
+) -> String {
+    let local = quote_ftp_arg(&local_path.to_string_lossy());
+    let remote = quote_ftp_arg(remote_path);
+    let user = quote_ftp_arg(&profile.user);
+    let pass = quote_ftp_arg(password);
+    let transfer = match direction {
+        TransferDirection::Push => format!("put {local} {remote}"),
+        TransferDirection::Pull => format!("get {remote} {local}"),
+    };
+    format!("user {user} {pass}\nbinary\n{transfer}\nquit\n",)
+}
+
+fn quote_ftp_arg(value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("\"{escaped}\"")
+}
+
+/// A saved local/remote path pair that can be re-run against its profile.
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferFavorite {
+    pub id: i64,
+    pub profile_id: String,
+    pub name: String,
+    #[serde(serialize_with = "serialize_direction")]
+    pub direction: TransferDirection,
+    pub local_path: String,
+    pub remote_path: String,
+    #[serde(serialize_with = "serialize_via")]
+    pub via: TransferVia,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewTransferFavorite {
+    pub profile_id: String,
+    pub name: String,
+    pub direction: TransferDirection,
+    pub local_path: String,
+    pub remote_path: String,
+    pub via: TransferVia,
+}
+
+pub struct TransferFavoriteStore {
+    conn: Connection,
+}
+
+impl TransferFavoriteStore {
+    pub fn new(conn: Connection) -> Self {
+        Self { conn }
+    }
+
+    pub fn conn(&self) -> &Connection {
+        &self.conn
+    }
+
+    pub fn insert(&self, input: NewTransferFavorite) -> Result<TransferFavorite> {
+        let name = input.name.trim();
+        if name.is_empty() {
+            return Err(CoreError::InvalidSetting(
+                "favorite name is required".into(),
+            ));
+        }
+        if input.local_path.trim().is_empty() || input.remote_path.trim().is_empty() {
+            return Err(CoreError::InvalidSetting(
+                "favorite local and remote paths are required".into(),
+            ));
+        }
+        if self.get_by_name(&input.profile_id, name)?.is_some() {
+            return Err(CoreError::Transfer(TransferError::FavoriteConflict(
+                name.to_string(),
+            )));
+        }
+        self.conn.execute(
+            r#"
+            INSERT INTO transfer_favorites
+                (profile_id, name, direction, local_path, remote_path, via, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            "#,
+            params![
+                input.profile_id,
+                name,
+                input.direction.as_str(),
+                input.local_path,
+                input.remote_path,
+                input.via.as_str(),
+                now_ms()
+            ],
+        )?;
+        let id = self.conn.last_insert_rowid();
+        self.get_by_id(id)?
+            .ok_or_else(|| CoreError::NotFound(format!("favorite not found: {id}")))
+    }
+
+    pub fn list(&self) -> Result<Vec<TransferFavorite>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT id, profile_id, name, direction, local_path, remote_path, via, created_at,
+                   last_used_at
+            FROM transfer_favorites
+            ORDER BY profile_id ASC, name ASC
+            "#,
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut out = Vec::new();
+        while let Some(row) = rows.next()? {
+            out.push(deserialize_favorite(row)?);
+        }
+        Ok(out)
+    }
+
+    pub fn list_for_profile(&self, profile_id: &str) -> Result<Vec<TransferFavorite>> {
+        favorites_for_profile(&self.conn, profile_id)
+    }
+
+    pub fn get_by_name(&self, profile_id: &str, name: &str) -> Result<Option<TransferFavorite>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT id, profile_id, name, direction, local_path, remote_path, via, created_at,
+                   last_used_at
+            FROM transfer_favorites
+            WHERE profile_id = ?1 AND name = ?2
+            "#,
+        )?;
+        let mut rows = stmt.query(params![profile_id, name])?;
+        let result = match rows.next()? {
+            Some(row) => Some(deserialize_favorite(row)?),
+            None => None,
+        };
+        Ok(result)
+    }
+
+    pub fn touch_last_used(&self, id: i64) -> Result<()> {
+        touch_favorite(&self.conn, id)
+    }
+
+    pub fn remove(&self, profile_id: &str, name: &str) -> Result<()> {
+        let affected = self.conn.execute(
+            "DELETE FROM transfer_favorites WHERE profile_id = ?1 AND name = ?2",
+            params![profile_id, name],
+        )?;
+        if affected == 0 {
+            return Err(CoreError::Transfer(TransferError::FavoriteNotFound(
+                name.to_string(),
+            )));
+        }
+        Ok(())
+    }
+
+    fn get_by_id(&self, id: i64) -> Result<Option<TransferFavorite>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT id, profile_id, name, direction, local_path, remote_path, via, created_at,
+                   last_used_at
+            FROM transfer_favorites
+            WHERE id = ?1
+            "#,
+        )?;
+        let mut rows = stmt.query([id])?;
+        let result = match rows.next()? {
+            Some(row) => Some(deserialize_favorite(row)?),
+            None => None,
+        };
+        Ok(result)
+    }
+}
+
+/// `profile_id`'s favorites by name, for callers that share a connection (the TUI).
+pub fn favorites_for_profile(conn: &Connection, profile_id: &str) -> Result<Vec<TransferFavorite>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT id, profile_id, name, direction, local_path, remote_path, via, created_at,
+               last_used_at
+        FROM transfer_favorites
+        WHERE profile_id = ?1
+        ORDER BY name ASC
+        "#,
+    )?;
+    let mut rows = stmt.query([profile_id])?;
+    let mut out = Vec::new();
+    while let Some(row) = rows.next()? {
+        out.push(deserialize_favorite(row)?);
+    }
+    Ok(out)
+}
+
+pub fn touch_favorite(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE transfer_favorites SET last_used_at = ?1 WHERE id = ?2",
+        params![now_ms(), id],
+    )?;
+    Ok(())
+}
+
+fn deserialize_favorite(row: &Row<'_>) -> Result<TransferFavorite> {
+    Ok(TransferFavorite {
+        id: row.get(0)?,
+        profile_id: row.get(1)?,
+        name: row.get(2)?,
+        direction: TransferDirection::parse(&row.get::<_, String>(3)?)?,
+        local_path: row.get(4)?,
+        remote_path: row.get(5)?,
+        via: TransferVia::parse(&row.get::<_, String>(6)?)?,
+        created_at: row.get(7)?,
+        last_used_at: row.get(8)?,
+    })
+}
+
+fn serialize_direction<S: serde::Serializer>(
+    value: &TransferDirection,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(value.as_str())
+}
+
+fn serialize_via<S: serde::Serializer>(
+    value: &TransferVia,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(value.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_connection_at;
+    use crate::profile::{DangerLevel, NewProfile, ProfileStore, ProfileType};
+    use std::fs;
+
+    fn temp_db_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!(
+            "teradock-transfer-{name}-{}-{}.db",
+            std::process::id(),
+            now_ms()
+        ))
+    }
+
+    fn store_with_profile(db_path: &Path) -> TransferFavoriteStore {
+        let profiles = ProfileStore::new(init_connection_at(db_path).unwrap());
+        profiles
+            .insert(NewProfile {
+                profile_id: Some("p_fav".into()),
+                name: "fav".into(),
+                profile_type: ProfileType::Ssh,
+                host: "localhost".into(),
+                port: 22,
+                user: "root".into(),
+                danger_level: DangerLevel::Normal,
+                group: None,
+                tags: vec![],
+                note: None,
+                initial_send: None,
+                client_overrides: None,
+            })
+            .unwrap();
+        TransferFavoriteStore::new(init_connection_at(db_path).unwrap())
+    }
+
+    fn deploy_bundle() -> NewTransferFavorite {
+        NewTransferFavorite {
+            profile_id: "p_fav".into(),
+            name: "deploy bundle".into(),
+            direction: TransferDirection::Push,
+            local_path: "./target/app.tar".into(),
+            remote_path: "/opt/app/".into(),
+            via: TransferVia::Scp,
+        }
+    }
+
+    #[test]
+    fn stores_and_lists_favorites_per_profile() {
+        let db_path = temp_db_path("favorites");
+        let store = store_with_profile(&db_path);
+        let favorite = store.insert(deploy_bundle()).unwrap();
+        assert_eq!(favorite.direction, TransferDirection::Push);
+        assert_eq!(favorite.via, TransferVia::Scp);
+        assert!(favorite.last_used_at.is_none());
+
+        store.touch_last_used(favorite.id).unwrap();
+        let listed = store.list_for_profile("p_fav").unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].remote_path, "/opt/app/");
+        assert!(listed[0].last_used_at.is_some());
+
+        store.remove("p_fav", "deploy bundle").unwrap();
+        assert!(store.list().unwrap().is_empty());
+        let _ = fs::remove_file(db_path);
+    }
+
+    #[test]
+    fn rejects_duplicate_favorite_names() {
+        let db_path = temp_db_path("duplicate");
+        let store = store_with_profile(&db_path);
+        store.insert(deploy_bundle()).unwrap();
+        let err = store.insert(deploy_bundle()).unwrap_err();
+        assert!(matches!(
+            err,
+            CoreError::Transfer(TransferError::FavoriteConflict(_))
+        ));
+        let _ = fs::remove_file(db_path);
+    }
+
+    #[test]
+    fn rejects_favorites_for_unknown_profiles() {
+        let db_path = temp_db_path("unknown");
+        let store = store_with_profile(&db_path);
+        let mut input = deploy_bundle();
+        input.profile_id = "p_missing".into();
+        assert!(store.insert(input).is_err());
+        let _ = fs::remove_file(db_path);
+    }
+}
@@ -1,11 +1,23 @@
 use rusqlite::{params, Connection, Row};
 use serde::{Deserialize, Serialize};
-use std::process::Command;
+use std::ffi::OsString;
+use std::io::Read;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use wait_timeout::ChildExt;
 
+use crate::doctor::ClientKind;
 use crate::error::{CoreError, Result};
+use crate::profile::{Profile, ProfileStore};
+use crate::ssh::{self, SshBuildError};
 use crate::util::now_ms;
 use common::id::generate_id;
 
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ForwardKind {
@@ -45,7 +57,7 @@ impl std::fmt::Display for ForwardKind {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Forward {
     pub id: i64,
     pub profile_id: String,
@@ -100,8 +112,9 @@ impl ForwardStore {
             ],
         )?;
         let id = self.conn.last_insert_rowid();
-        self.get_by_id(id)
-            .and_then(|forward| forward.ok_or_else(|| CoreError::NotFound(id.to_string())))
+        self.get_by_id(id).and_then(|forward| {
+            forward.ok_or_else(|| CoreError::NotFound(format!("forward not found: {id}")))
+        })
     }
 
     pub fn list_for_profile(&self, profile_id: &str) -> Result<Vec<Forward>> {
@@ -122,19 +135,7 @@ impl ForwardStore {
     }
 
     pub fn get_by_name(&self, profile_id: &str, name: &str) -> Result<Option<Forward>> {
-        let mut stmt = self.conn.prepare(
-            r#"
-            SELECT id, profile_id, name, kind, listen, dest
-            FROM ssh_forwards
-            WHERE profile_id = ?1 AND name = ?2
-            "#,
-        )?;
-        let mut rows = stmt.query(params![profile_id, name])?;
-        let result = match rows.next()? {
-            Some(row) => Some(deserialize_forward(row)?),
-            None => None,
-        };
-        Ok(result)
+        get_forward_by_name(&self.conn, profile_id, name)
     }
 
     pub fn remove(&self, profile_id: &str, name: &str) -> Result<()> {
@@ -165,6 +166,22 @@ impl ForwardStore {
     }
 }
 
+fn get_forward_by_name(conn: &Connection, profile_id: &str, name: &str) -> Result<Option<Forward>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT id, profile_id, name, kind, listen, dest
+        FROM ssh_forwards
+        WHERE profile_id = ?1 AND name = ?2
+        "#,
+    )?;
+    let mut rows = stmt.query(params![profile_id, name])?;
+    let result = match rows.next()? {
+        Some(row) => Some(deserialize_forward(row)?),
+        None => None,
+    };
+    Ok(result)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum SessionKind {
@@ -190,7 +207,7 @@ impl std::fmt::Display for SessionKind {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
     pub session_id: String,
     pub kind: SessionKind,
@@ -198,6 +215,44 @@ pub struct Session {
     pub pid: Option<u32>,
     pub started_at: i64,
     pub forwards: Vec<String>,
+    /// Result of the most recent health check, if one has run.
+    pub health: Option<SessionHealth>,
+    pub restarts: u32,
+    pub restarted_at: Option<i64>,
+    /// Earliest time the next restart may run, set from the reconnect policy's backoff.
+    pub retry_at: Option<i64>,
+}
+
+impl Session {
+    pub fn is_healthy(&self) -> bool {
+        self.health
+            .as_ref()
+            .is_some_and(|health| health.is_healthy())
+    }
+
+    /// `ok`, `unchecked`, or what is dead (`ssh exited` or the dead forward names).
+    pub fn health_summary(&self) -> String {
+        let Some(health) = &self.health else {
+            return "unchecked".to_string();
+        };
+        if !health.process_alive {
+            return "dead: ssh exited".to_string();
+        }
+        let dead = health
+            .dead_forwards()
+            .map(|forward| forward.name.as_str())
+            .collect::<Vec<_>>();
+        if dead.is_empty() {
+            "ok".to_string()
+        } else {
+            format!("dead: {}", dead.join(","))
+        }
+    }
+
+    /// Whether the backoff after the last restart has elapsed.
+    pub fn restart_due(&self, now: i64) -> bool {
+        self.retry_at.is_none_or(|at| now >= at)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -236,39 +291,19 @@ impl SessionStore {
             ],
         )?;
         self.get(&session_id)?
-            .ok_or_else(|| CoreError::NotFound(session_id))
+            .ok_or_else(|| CoreError::NotFound(format!("tunnel session not found: {session_id}")))
+    }
+
+    pub fn conn(&self) -> &Connection {
+        &self.conn
     }
 
     pub fn list(&self) -> Result<Vec<Session>> {
-        let mut stmt = self.conn.prepare(
-            r#"
-            SELECT session_id, kind, profile_id, pid, started_at, forwards_json
-            FROM sessions
-            ORDER BY started_at DESC
-            "#,
-        )?;
-        let mut rows = stmt.query([])?;
-        let mut out = Vec::new();
-        while let Some(row) = rows.next()? {
-            out.push(deserialize_session(row)?);
-        }
-        Ok(out)
+        list_sessions(&self.conn)
     }
 
     pub fn get(&self, session_id: &str) -> Result<Option<Session>> {
-        let mut stmt = self.conn.prepare(
-            r#"
-            SELECT session_id, kind, profile_id, pid, started_at, forwards_json
-            FROM sessions
-            WHERE session_id = ?1
-            "#,
-        )?;
-        let mut rows = stmt.query([session_id])?;
-        let result = match rows.next()? {
-            Some(row) => Some(deserialize_session(row)?),
-            None => None,
-        };
-        Ok(result)
+        get_session(&self.conn, session_id)
     }
 
     pub fn remove(&self, session_id: &str) -> Result<()> {
@@ -305,7 +340,472 @@ impl SessionStore {
     }
 }
 
-fn deserialize_forward(row: &Row<'_>) -> Result<Forward> {
+fn list_sessions(conn: &Connection) -> Result<Vec<Session>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT session_id, kind, profile_id, pid, started_at, forwards_json,
+               health_json, restarts, restarted_at, retry_at
+        FROM sessions
+        ORDER BY started_at DESC
+        "#,
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut out = Vec::new();
+    while let Some(row) = rows.next()? {
+        out.push(deserialize_session(row)?);
+    }
+    Ok(out)
+}
+
+fn get_session(conn: &Connection, session_id: &str) -> Result<Option<Session>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT session_id, kind, profile_id, pid, started_at, forwards_json,
+               health_json, restarts, restarted_at, retry_at
+        FROM sessions
+        WHERE session_id = ?1
+        "#,
+    )?;
+    let mut rows = stmt.query([session_id])?;
+    let result = match rows.next()? {
+        Some(row) => Some(deserialize_session(row)?),
+        None => None,
+    };
+    Ok(result)
+}
+
+/// How a forward looked at the last health check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForwardState {
+    /// The local listen address accepted a connection.
+    Up,
+    /// The listen address refused or timed out, or the forward no longer exists.
+    Down,
+    /// Remote forwards listen on the far side and cannot be probed locally.
+    Unchecked,
+}
+
+impl std::fmt::Display for ForwardState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ForwardState::Up => write!(f, "up"),
+            ForwardState::Down => write!(f, "down"),
+            ForwardState::Unchecked => write!(f, "unchecked"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForwardHealth {
+    pub name: String,
+    pub listen: Option<String>,
+    pub state: ForwardState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionHealth {
+    pub checked_at: i64,
+    pub process_alive: bool,
+    pub forwards: Vec<ForwardHealth>,
+}
+
+impl SessionHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.process_alive && self.dead_forwards().next().is_none()
+    }
+
+    pub fn dead_forwards(&self) -> impl Iterator<Item = &ForwardHealth> {
+        self.forwards
+            .iter()
+            .filter(|forward| forward.state == ForwardState::Down)
+    }
+}
+
+/// Probes every tunnel session's process and local listen addresses and stores the result
+/// on the session row. Dead sessions are kept so the caller can decide to restart them.
+pub fn check_tunnel_health(conn: &Connection) -> Result<Vec<Session>> {
+    let mut sessions = list_sessions(conn)?;
+    for session in &mut sessions {
+        let health = probe_session(conn, session)?;
+        conn.execute(
+            "UPDATE sessions SET health_json = ?1 WHERE session_id = ?2",
+            params![serde_json::to_string(&health)?, session.session_id],
+        )?;
+        session.health = Some(health);
+    }
+    Ok(sessions)
+}
+
+fn probe_session(conn: &Connection, session: &Session) -> Result<SessionHealth> {
+    let process_alive = session.pid.filter(|pid| *pid > 0).is_some_and(is_pid_alive);
+    let mut forwards = Vec::new();
+    for name in &session.forwards {
+        let health = match get_forward_by_name(conn, &session.profile_id, name)? {
+            Some(forward) => probe_forward(&forward),
+            None => ForwardHealth {
+                name: name.clone(),
+                listen: None,
+                state: ForwardState::Down,
+                error: Some("forward no longer exists".to_string()),
+            },
+        };
+        forwards.push(health);
+    }
+    Ok(SessionHealth {
+        checked_at: now_ms(),
+        process_alive,
+        forwards,
+    })
+}
+
+/// Connects to a local or dynamic forward's listen address; wildcard binds are probed
+/// on loopback.
+pub fn probe_forward(forward: &Forward) -> ForwardHealth {
+    let mut health = ForwardHealth {
+        name: forward.name.clone(),
+        listen: Some(forward.listen.clone()),
+        state: ForwardState::Unchecked,
+        error: None,
+    };
+    if forward.kind == ForwardKind::Remote {
+        return health;
+    }
+    match connect_listen(&forward.listen) {
+        Ok(()) => health.state = ForwardState::Up,
+        Err(err) => {
+            health.state = ForwardState::Down;
+            health.error = Some(err.to_string());
+        }
+    }
+    health
+}
+
+fn connect_listen(listen: &str) -> std::io::Result<()> {
+    let (host, port) = split_host_port(listen)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err.to_string()))?;
+    let host = match host.trim_start_matches('[').trim_end_matches(']') {
+        "" | "*" | "0.0.0.0" => "127.0.0.1",
+        "::" => "::1",
+        host => host,
+    };
+    let mut last_error = None;
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, PROBE_TIMEOUT) {
+            Ok(_) => return Ok(()),
+            Err(err) => last_error = Some(err),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, "no addresses for listen host")
+    }))
+}
+
+/// The `ssh -N` invocation that holds `forwards` open for `profile`.
+pub fn tunnel_command(
+    ssh: &Path,
+    profile: &Profile,
+    auth_args: &[OsString],
+    forwards: &[Forward],
+) -> Result<Command> {
+    let mut cmd = Command::new(ssh);
+    cmd.arg("-N")
+        .arg("-p")
+        .arg(profile.port.to_string())
+        .args(auth_args);
+    for forward in forwards {
+        let spec = match forward.kind {
+            ForwardKind::Dynamic => forward.listen.clone(),
+            ForwardKind::Local | ForwardKind::Remote => format!(
+                "{}:{}",
+                forward.listen,
+                forward
+                    .dest
+                    .as_ref()
+                    .ok_or_else(|| CoreError::InvalidSetting(format!(
+                        "forward {} missing destination",
+                        forward.name
+                    )))?
+            ),
+        };
+        cmd.arg(forward.kind.as_flag()).arg(spec);
+    }
+    cmd.arg(format!("{}@{}", profile.user, profile.host))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // Keep the tunnel out of td's process group so Ctrl-C on a watching td (or the TUI)
+    // does not take restarted tunnels down with it.
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        cmd.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+    Ok(cmd)
+}
+
+/// What the target server reported when asked to connect to a local forward's
+/// destination.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "result", rename_all = "lowercase")]
+pub enum DestProbe {
+    /// The server opened a connection to the destination.
+    Reachable,
+    /// The server could not connect; `error` is the channel failure ssh printed.
+    Unreachable { error: String },
+    /// ssh failed before the destination was tried (host down, auth refused).
+    Unknown { error: String },
+}
+
+/// Asks `profile`'s server to connect to `dest` with `ssh -W`, the same hop a local
+/// forward makes. A channel that is still open after `wait` counts as reachable.
+pub fn probe_forward_dest(
+    ssh: &Path,
+    profile: &Profile,
+    auth_args: &[OsString],
+    dest: &str,
+    wait: Duration,
+) -> Result<DestProbe> {
+    let (host, port) = split_host_port(dest)?;
+    let mut child = Command::new(ssh)
+        .arg("-p")
+        .arg(profile.port.to_string())
+        .args(auth_args)
+        .arg("-o")
+        .arg(format!("ConnectTimeout={}", wait.as_secs().max(1)))
+        .arg("-W")
+        .arg(format!("{host}:{port}"))
+        .arg(format!("{}@{}", profile.user, profile.host))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stderr = child.stderr.take();
+    let reader = thread::spawn(move || {
+        let mut text = String::new();
+        if let Some(stderr) = stderr.as_mut() {
+            let _ = stderr.read_to_string(&mut text);
+        }
+        text
+    });
+    let status = match child.wait_timeout(wait)? {
+        Some(status) => status,
+        None => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(DestProbe::Reachable);
+        }
+    };
+    let stderr = reader.join().unwrap_or_default();
+    Ok(classify_dest_probe(status.success(), &stderr))
+}
+
+fn classify_dest_probe(success: bool, stderr: &str) -> DestProbe {
+    let mut lines = stderr
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    if let Some(line) = lines.clone().find(|line| line.contains("open failed")) {
+        return DestProbe::Unreachable {
+            error: line.to_string(),
+        };
+    }
+    if success {
+        return DestProbe::Reachable;
+    }
+    DestProbe::Unknown {
+        error: lines
+            .next_back()
+            .unwrap_or("ssh exited without output")
+            .to_string(),
+    }
+}
+
+/// Stops what is left of a session's ssh process and launches a fresh one for the same
+/// forwards. Returns the ssh client used and the new pid; the caller records the restart.
+pub(crate) fn relaunch_tunnel(store: &ProfileStore, session: &Session) -> Result<(PathBuf, u32)> {
+    let conn = store.conn();
+    let profile = store
+        .get(&session.profile_id)?
+        .ok_or_else(|| CoreError::NotFound(format!("profile not found: {}", session.profile_id)))?;
+    let mut forwards = Vec::new();
+    for name in &session.forwards {
+        forwards.push(
+            get_forward_by_name(conn, &profile.profile_id, name)?
+                .ok_or_else(|| CoreError::NotFound(format!("forward not found: {name}")))?,
+        );
+    }
+    let ssh = ssh::resolve_client_for(ClientKind::Ssh, &profile, conn).map_err(ssh_build_error)?;
+    let auth = ssh::ssh_profile_auth_context(conn, &profile.profile_id).map_err(ssh_build_error)?;
+    if let Some(pid) = session.pid.filter(|pid| *pid > 0 && is_pid_alive(*pid)) {
+        terminate_pid(pid)?;
+    }
+    let mut child = tunnel_command(&ssh, &profile, &auth.args, &forwards)?.spawn()?;
+    let pid = child.id();
+    // Reap the process if it exits while this td is still running.
+    thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok((ssh, pid))
+}
+
+/// Counts a restart attempt. A new `pid` replaces the old one and clears the stale health
+/// result; a failed attempt (`None`) keeps both.
+pub(crate) fn record_restart(
+    conn: &Connection,
+    session_id: &str,
+    pid: Option<u32>,
+    retry_at: i64,
+) -> Result<()> {
+    conn.execute(
+        r#"
+        UPDATE sessions
+        SET pid = COALESCE(?1, pid),
+            health_json = CASE WHEN ?1 IS NULL THEN health_json ELSE NULL END,
+            restarts = restarts + 1,
+            restarted_at = ?2,
+            retry_at = ?3
+        WHERE session_id = ?4
+        "#,
+        params![pid.map(i64::from), now_ms(), retry_at, session_id],
+    )?;
+    Ok(())
+}
+
+/// Forgets earlier restarts once a session has stayed up.
+pub(crate) fn reset_restarts(conn: &Connection, session_id: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE sessions SET restarts = 0, retry_at = NULL WHERE session_id = ?1",
+        params![session_id],
+    )?;
+    Ok(())
+}
+
+pub(crate) fn find_session(conn: &Connection, session_id: &str) -> Result<Session> {
+    get_session(conn, session_id)?
+        .ok_or_else(|| CoreError::NotFound(format!("tunnel session not found: {session_id}")))
+}
+
+fn ssh_build_error(err: SshBuildError) -> CoreError {
+    match err {
+        SshBuildError::ClientNotFound { .. } | SshBuildError::ProfileNotFound(_) => {
+            CoreError::NotFound(err.to_string())
+        }
+        _ => CoreError::InvalidSetting(err.to_string()),
+    }
+}
+
+/// Shell syntax for `td tunnel env` output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvShell {
+    Bash,
+    Powershell,
+}
+
+/// Environment variables describing where a running forward can be reached locally.
+///
+/// `template` is a space-separated list of `KEY=VALUE` pairs where `{host}`, `{port}`, and
+/// `{name}` are replaced (for example `PGHOST={host} PGPORT={port}`). Without a template,
+/// local and dynamic forwards export `<NAME>_HOST` and `<NAME>_PORT` (plus `<NAME>_PROXY`
+/// for dynamic SOCKS forwards); remote forwards listen on the far side and export nothing.
+pub fn forward_env_vars(
+    forward: &Forward,
+    template: Option<&str>,
+) -> Result<Vec<(String, String)>> {
+    let (host, port) = split_host_port(&forward.listen)?;
+    let port = port.to_string();
+    let fill = |value: &str| {
+        value
+            .replace("{host}", &host)
+            .replace("{port}", &port)
+            .replace("{name}", &forward.name)
+    };
+    if let Some(template) = template {
+        return parse_env_template(template).map(|pairs| {
+            pairs
+                .into_iter()
+                .map(|(key, value)| (key, fill(&value)))
+                .collect()
+        });
+    }
+    let prefix = env_name_prefix(&forward.name);
+    let mut vars = match forward.kind {
+        ForwardKind::Remote => return Ok(Vec::new()),
+        ForwardKind::Local | ForwardKind::Dynamic => vec![
+            (format!("{prefix}_HOST"), host.clone()),
+            (format!("{prefix}_PORT"), port.clone()),
+        ],
+    };
+    if forward.kind == ForwardKind::Dynamic {
+        vars.push((
+            format!("{prefix}_PROXY"),
+            format!("socks5h://{host}:{port}"),
+        ));
+    }
+    Ok(vars)
+}
+
+/// Splits a `KEY=VALUE KEY=VALUE` template, checking that every key is a valid
+/// environment variable name.
+pub fn parse_env_template(template: &str) -> Result<Vec<(String, String)>> {
+    template
+        .split_whitespace()
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').ok_or_else(|| {
+                CoreError::InvalidSetting(format!("env template entry must be KEY=VALUE: {pair}"))
+            })?;
+            let valid_key = key
+                .chars()
+                .next()
+                .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid_key {
+                return Err(CoreError::InvalidSetting(format!(
+                    "invalid environment variable name: {key}"
+                )));
+            }
+            Ok((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn env_name_prefix(name: &str) -> String {
+    let prefix = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if prefix.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{prefix}")
+    } else {
+        prefix
+    }
+}
+
+/// One ready-to-eval export line per variable, single-quoted for the chosen shell.
+pub fn format_env_exports(vars: &[(String, String)], shell: EnvShell) -> String {
+    vars.iter()
+        .map(|(key, value)| match shell {
+            EnvShell::Bash => format!("export {key}='{}'\n", value.replace('\'', r"'\''")),
+            EnvShell::Powershell => format!("$env:{key} = '{}'\n", value.replace('\'', "''")),
+        })
+        .collect()
+}
+
+pub(crate) fn deserialize_forward(row: &Row<'_>) -> Result<Forward> {
     let dest_raw: String = row.get(5)?;
     let dest = if dest_raw.trim().is_empty() {
         None
@@ -326,6 +826,10 @@ fn deserialize_session(row: &Row<'_>) -> Result<Session> {
     let forwards_json: String = row.get(5)?;
     let forwards = serde_json::from_str(&forwards_json)?;
     let pid: Option<i64> = row.get(3)?;
+    let health = row
+        .get::<_, Option<String>>(6)?
+        .map(|value| serde_json::from_str(&value))
+        .transpose()?;
     Ok(Session {
         session_id: row.get(0)?,
         kind: SessionKind::from_str(&row.get::<_, String>(1)?)?,
@@ -333,6 +837,10 @@ fn deserialize_session(row: &Row<'_>) -> Result<Session> {
         pid: pid.map(|value| value as u32),
         started_at: row.get(4)?,
         forwards,
+        health,
+        restarts: row.get::<_, i64>(7)? as u32,
+        restarted_at: row.get(8)?,
+        retry_at: row.get(9)?,
     })
 }
 
@@ -390,12 +898,13 @@ fn parse_port(value: &str) -> Result<u16> {
         .map_err(|_| CoreError::InvalidSetting(format!("invalid port: {value}")))
 }
 
-fn is_pid_alive(pid: u32) -> bool {
+pub(crate) fn is_pid_alive(pid: u32) -> bool {
     #[cfg(unix)]
     {
         Command::new("kill")
             .arg("-0")
             .arg(pid.to_string())
+            .stderr(Stdio::null())
             .status()
             .map(|status| status.success())
             .unwrap_or(true)
@@ -416,6 +925,68 @@ fn is_pid_alive(pid: u32) -> bool {
     }
 }
 
+/// When `pid` started, as an opaque marker that differs once the pid has been reused;
+/// `None` when the process is gone or the platform does not say.
+pub(crate) fn process_start_marker(pid: u32) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        // Field 22 of /proc/<pid>/stat, ticks since boot. The command name before it is
+        // parenthesised and may contain spaces, so count from the closing parenthesis.
+        let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+        let fields = &stat[stat.rfind(')')? + 1..];
+        fields.split_whitespace().nth(19).map(str::to_string)
+    }
+    #[cfg(all(unix, not(target_os = "linux")))]
+    {
+        let output = Command::new("ps")
+            .args(["-o", "lstart=", "-p", &pid.to_string()])
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        let started = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (output.status.success() && !started.is_empty()).then_some(started)
+    }
+    #[cfg(windows)]
+    {
+        let output = Command::new("powershell")
+            .args([
+                "-NoProfile",
+                "-NonInteractive",
+                "-Command",
+                &format!("(Get-Process -Id {pid}).StartTime.ToFileTimeUtc()"),
+            ])
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        let started = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (output.status.success() && !started.is_empty()).then_some(started)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = pid;
+        None
+    }
+}
+
+/// Asks a process to exit (`kill` on Unix, `taskkill /T /F` on Windows).
+pub fn terminate_pid(pid: u32) -> Result<()> {
+    #[cfg(unix)]
+    let status = Command::new("kill").arg(pid.to_string()).status()?;
+    #[cfg(windows)]
+    let status = Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .status()?;
+    #[cfg(any(unix, windows))]
+    if !status.success() {
+        return Err(CoreError::Io(std::io::Error::other(format!(
+            "failed to terminate pid {pid}"
+        ))));
+    }
+    #[cfg(not(any(unix, windows)))]
+    let _ = pid;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -512,4 +1083,143 @@ mod tests {
         assert!(forward.dest.is_none());
         let _ = fs::remove_file(db_path);
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn probes_forward_dest_through_ssh() {
+        use std::os::unix::fs::PermissionsExt;
+        let db_path = temp_db_path("dest-probe");
+        let (store, _) = stores(&db_path);
+        let profile = store.get(&sample_profile(&store)).unwrap().unwrap();
+        let probe = |name: &str, script: &str| {
+            let path = std::env::temp_dir().join(format!(
+                "teradock-dest-probe-{name}-{}.sh",
+                std::process::id()
+            ));
+            fs::write(&path, script).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            let result =
+                probe_forward_dest(&path, &profile, &[], "db:5432", Duration::from_millis(300));
+            let _ = fs::remove_file(&path);
+            result.unwrap()
+        };
+
+        assert_eq!(probe("open", "#!/bin/sh\nsleep 5\n"), DestProbe::Reachable);
+        assert_eq!(
+            probe(
+                "refused",
+                "#!/bin/sh\necho 'channel 0: open failed: connect failed: Connection refused' >&2\n\
+                 echo 'stdio forwarding failed' >&2\nexit 255\n"
+            ),
+            DestProbe::Unreachable {
+                error: "channel 0: open failed: connect failed: Connection refused".into()
+            }
+        );
+        assert_eq!(
+            probe(
+                "auth",
+                "#!/bin/sh\necho 'root@localhost: Permission denied (publickey).' >&2\nexit 255\n"
+            ),
+            DestProbe::Unknown {
+                error: "root@localhost: Permission denied (publickey).".into()
+            }
+        );
+        let _ = fs::remove_file(db_path);
+    }
+
+    #[test]
+    fn exports_forward_env_with_default_and_template() {
+        let forward = Forward {
+            id: 1,
+            profile_id: "p_forward".into(),
+            name: "pg-main".into(),
+            kind: ForwardKind::Local,
+            listen: "127.0.0.1:15432".into(),
+            dest: Some("db:5432".into()),
+        };
+        let vars = forward_env_vars(&forward, None).unwrap();
+        assert_eq!(
+            format_env_exports(&vars, EnvShell::Bash),
+            "export PG_MAIN_HOST='127.0.0.1'\nexport PG_MAIN_PORT='15432'\n"
+        );
+        let vars = forward_env_vars(&forward, Some("PGHOST={host} PGPORT={port}")).unwrap();
+        assert_eq!(
+            format_env_exports(&vars, EnvShell::Powershell),
+            "$env:PGHOST = '127.0.0.1'\n$env:PGPORT = '15432'\n"
+        );
+        assert!(forward_env_vars(&forward, Some("1BAD={port}")).is_err());
+
+        let remote = Forward {
+            kind: ForwardKind::Remote,
+            ..forward
+        };
+        assert!(forward_env_vars(&remote, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn records_forward_health_for_sessions() {
+        let db_path = temp_db_path("health");
+        let (store, forward_store) = stores(&db_path);
+        let profile_id = sample_profile(&store);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        for (name, kind, listen) in [
+            ("up", ForwardKind::Dynamic, format!("0.0.0.0:{port}")),
+            ("far", ForwardKind::Remote, "127.0.0.1:9".to_string()),
+        ] {
+            forward_store
+                .insert(NewForward {
+                    profile_id: profile_id.clone(),
+                    name: name.into(),
+                    kind,
+                    listen,
+                    dest: (kind == ForwardKind::Remote).then(|| "localhost:22".into()),
+                })
+                .unwrap();
+        }
+        let session_store = SessionStore::new(init_connection_at(&db_path).unwrap());
+        let session = session_store
+            .insert(NewSession {
+                kind: SessionKind::Tunnel,
+                profile_id,
+                pid: None,
+                forwards: vec!["up".into(), "far".into(), "gone".into()],
+            })
+            .unwrap();
+        assert!(session.health.is_none());
+        assert!(session.restart_due(now_ms()));
+
+        check_tunnel_health(session_store.conn()).unwrap();
+        let health = session_store
+            .get(&session.session_id)
+            .unwrap()
+            .unwrap()
+            .health
+            .unwrap();
+        let states = health
+            .forwards
+            .iter()
+            .map(|forward| (forward.name.as_str(), forward.state))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            states,
+            vec![
+                ("up", ForwardState::Up),
+                ("far", ForwardState::Unchecked),
+                ("gone", ForwardState::Down),
+            ]
+        );
+        assert!(!health.process_alive);
+        assert!(!health.is_healthy());
+        let checked = session_store.get(&session.session_id).unwrap().unwrap();
+        assert_eq!(checked.health_summary(), "dead: ssh exited");
+
+        drop(listener);
+        let forward = forward_store
+            .get_by_name(&session.profile_id, "up")
+            .unwrap()
+            .unwrap();
+        assert_eq!(probe_forward(&forward).state, ForwardState::Down);
+        let _ = fs::remove_file(db_path);
+    }
 }
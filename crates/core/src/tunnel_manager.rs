@@ -0,0 +1,320 @@
+//! Supervision for running tunnels: probe each session, restart dead ones within their
+//! reconnect policy (`tunnel.reconnect`), and log every restart attempt to `op_logs`.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::error::Result;
+use crate::oplog::{self, OpLogEntry};
+use crate::profile::ProfileStore;
+use crate::settings;
+use crate::tunnel::{self, Session};
+use crate::util::now_ms;
+
+pub const TUNNEL_RESTART_OP: &str = "tunnel_restart";
+
+/// Policy key applied to forwards without an entry of their own.
+pub const DEFAULT_POLICY_KEY: &str = "*";
+
+/// Reconnect settings for one forward.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReconnectPolicy {
+    /// Restarts allowed before giving up; unset retries forever.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<u32>,
+    /// Delay after the first restart; doubles after each further restart.
+    pub backoff_secs: u64,
+    pub max_backoff_secs: u64,
+    /// Random spread applied to each delay, as a fraction (0.2 means +/-20%).
+    pub jitter: f64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: None,
+            backoff_secs: 2,
+            max_backoff_secs: 60,
+            jitter: 0.0,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Checks the values, returning the first problem found.
+    pub fn validate(&self) -> std::result::Result<(), &'static str> {
+        if self.backoff_secs == 0 {
+            return Err("backoff_secs must be at least 1");
+        }
+        if self.max_backoff_secs < self.backoff_secs {
+            return Err("max_backoff_secs must not be less than backoff_secs");
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err("jitter must be between 0 and 1");
+        }
+        Ok(())
+    }
+
+    /// Wait after restart number `attempt` (1-based) before the next one may run,
+    /// without jitter.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1).min(32));
+        Duration::from_secs(
+            self.backoff_secs
+                .saturating_mul(factor)
+                .min(self.max_backoff_secs),
+        )
+    }
+
+    fn jittered_delay(&self, attempt: u32, rng: &mut impl Rng) -> Duration {
+        let delay = self.delay(attempt);
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+        delay.mul_f64(1.0 + rng.gen_range(-self.jitter..=self.jitter))
+    }
+
+    pub fn exhausted(&self, restarts: u32) -> bool {
+        self.max_retries.is_some_and(|max| restarts >= max)
+    }
+
+    /// The most conservative of two policies: fewest retries, longest delays, most jitter.
+    fn strictest(self, other: &Self) -> Self {
+        Self {
+            max_retries: match (self.max_retries, other.max_retries) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+            backoff_secs: self.backoff_secs.max(other.backoff_secs),
+            max_backoff_secs: self.max_backoff_secs.max(other.max_backoff_secs),
+            jitter: self.jitter.max(other.jitter),
+        }
+    }
+}
+
+/// The policy for a session: each forward's own entry, else `*`, else the default. A
+/// session holding several forwards uses the strictest of their policies.
+pub fn session_policy(
+    policies: &BTreeMap<String, ReconnectPolicy>,
+    forwards: &[String],
+) -> ReconnectPolicy {
+    let fallback = policies
+        .get(DEFAULT_POLICY_KEY)
+        .cloned()
+        .unwrap_or_default();
+    forwards
+        .iter()
+        .map(|name| policies.get(name).unwrap_or(&fallback))
+        .fold(None, |merged: Option<ReconnectPolicy>, policy| {
+            Some(match merged {
+                Some(merged) => merged.strictest(policy),
+                None => policy.clone(),
+            })
+        })
+        .unwrap_or(fallback)
+}
+
+/// What one supervision pass saw and did.
+#[derive(Debug, Default)]
+pub struct TunnelReport {
+    /// Every session after the pass. Restarted sessions keep the health result that
+    /// triggered the restart; the new process is probed on the next pass.
+    pub sessions: Vec<Session>,
+    pub restarted: Vec<String>,
+    pub failed: Vec<(String, String)>,
+    /// Dead sessions left alone because their policy's `max_retries` is used up.
+    pub exhausted: Vec<String>,
+}
+
+pub struct TunnelManager<'a> {
+    store: &'a ProfileStore,
+}
+
+impl<'a> TunnelManager<'a> {
+    pub fn new(store: &'a ProfileStore) -> Self {
+        Self { store }
+    }
+
+    /// Probes every session and restarts dead ones whose backoff has elapsed. Sessions
+    /// that have stayed up for `max_backoff_secs` since their last restart start counting
+    /// retries from zero again.
+    pub fn supervise(&self) -> Result<TunnelReport> {
+        let conn = self.store.conn();
+        let mut report = TunnelReport {
+            sessions: tunnel::check_tunnel_health(conn)?,
+            ..TunnelReport::default()
+        };
+        let mut rng = rand::thread_rng();
+        for session in &mut report.sessions {
+            let now = now_ms();
+            let policies = settings::get_tunnel_reconnect_policies(conn, &session.profile_id)?;
+            let policy = session_policy(&policies, &session.forwards);
+            if session.is_healthy() {
+                let stable = session.restarted_at.is_some_and(|at| {
+                    now.saturating_sub(at) >= (policy.max_backoff_secs as i64) * 1000
+                });
+                if session.restarts > 0 && stable {
+                    tunnel::reset_restarts(conn, &session.session_id)?;
+                    session.restarts = 0;
+                    session.retry_at = None;
+                }
+                continue;
+            }
+            if policy.exhausted(session.restarts) {
+                report.exhausted.push(session.session_id.clone());
+                continue;
+            }
+            if !session.restart_due(now) {
+                continue;
+            }
+            let attempt = session.restarts + 1;
+            let retry_at = now + policy.jittered_delay(attempt, &mut rng).as_millis() as i64;
+            let reason = session.health_summary();
+            let started = now_ms();
+            let outcome = tunnel::relaunch_tunnel(self.store, session);
+            let (client_used, pid, error) = match &outcome {
+                Ok((client, pid)) => (
+                    Some(client.to_string_lossy().into_owned()),
+                    Some(*pid),
+                    None,
+                ),
+                Err(err) => (None, None, Some(err.to_string())),
+            };
+            tunnel::record_restart(conn, &session.session_id, pid, retry_at)?;
+            oplog::log_operation(
+                conn,
+                OpLogEntry {
+                    op: TUNNEL_RESTART_OP.into(),
+                    profile_id: Some(session.profile_id.clone()),
+                    client_used,
+                    ok: outcome.is_ok(),
+                    exit_code: None,
+                    duration_ms: Some(now_ms() - started),
+                    meta_json: Some(json!({
+                        "session_id": session.session_id,
+                        "attempt": attempt,
+                        "forwards": session.forwards,
+                        "reason": reason,
+                        "pid": pid,
+                        "retry_at": retry_at,
+                        "error": error,
+                    })),
+                },
+            )?;
+            let health = session.health.take();
+            *session = Session {
+                health,
+                ..tunnel::find_session(conn, &session.session_id)?
+            };
+            match error {
+                None => report.restarted.push(session.session_id.clone()),
+                Some(error) => report.failed.push((session.session_id.clone(), error)),
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::profile::{DangerLevel, NewProfile, ProfileType};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn delays_double_cap_and_jitter_within_bounds() {
+        let policy = ReconnectPolicy {
+            max_retries: Some(3),
+            backoff_secs: 5,
+            max_backoff_secs: 30,
+            jitter: 0.5,
+        };
+        assert_eq!(policy.delay(1), Duration::from_secs(5));
+        assert_eq!(policy.delay(3), Duration::from_secs(20));
+        assert_eq!(policy.delay(50), Duration::from_secs(30));
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..50 {
+            let delay = policy.jittered_delay(2, &mut rng);
+            assert!(delay >= Duration::from_secs(5) && delay <= Duration::from_secs(15));
+        }
+        assert!(!policy.exhausted(2));
+        assert!(policy.exhausted(3));
+    }
+
+    #[test]
+    fn session_policy_uses_strictest_forward_policy() {
+        let policies = settings::parse_tunnel_reconnect_policies(
+            r#"{"*":{"max_retries":10},"pg":{"max_retries":3,"backoff_secs":1},"web":{"backoff_secs":8,"jitter":0.2}}"#,
+        )
+        .unwrap();
+        let policy = session_policy(&policies, &["pg".into(), "web".into()]);
+        assert_eq!(policy.max_retries, Some(3));
+        assert_eq!(policy.backoff_secs, 8);
+        assert_eq!(policy.jitter, 0.2);
+        assert_eq!(
+            session_policy(&policies, &["other".into()]).max_retries,
+            Some(10)
+        );
+        assert!(settings::parse_tunnel_reconnect_policies(r#"{"*":{"jitter":2}}"#).is_err());
+    }
+
+    #[test]
+    fn supervise_logs_failed_restart_and_stops_at_max_retries() {
+        let store = ProfileStore::new(db::init_in_memory().unwrap());
+        store
+            .insert(NewProfile {
+                profile_id: Some("p_tunnel".into()),
+                name: "tunnel".into(),
+                profile_type: ProfileType::Ssh,
+                host: "localhost".into(),
+                port: 22,
+                user: "root".into(),
+                danger_level: DangerLevel::Normal,
+                group: None,
+                tags: vec![],
+                note: None,
+                initial_send: None,
+                client_overrides: None,
+            })
+            .unwrap();
+        let conn = store.conn();
+        conn.execute(
+            "INSERT INTO sessions (session_id, kind, profile_id, pid, started_at, forwards_json) \
+             VALUES ('s_dead', 'tunnel', 'p_tunnel', NULL, 0, '[\"gone\"]')",
+            [],
+        )
+        .unwrap();
+        settings::set_setting(
+            conn,
+            settings::TUNNEL_RECONNECT_KEY,
+            r#"{"*":{"max_retries":1}}"#,
+        )
+        .unwrap();
+        let manager = TunnelManager::new(&store);
+
+        let report = manager.supervise().unwrap();
+        assert_eq!(report.failed.len(), 1);
+        assert!(report.failed[0].1.contains("forward not found: gone"));
+        assert_eq!(report.sessions[0].restarts, 1);
+        let (ok, meta): (bool, String) = conn
+            .query_row(
+                "SELECT ok, meta_json FROM op_logs WHERE op = ?1",
+                [TUNNEL_RESTART_OP],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert!(!ok);
+        assert!(meta.contains("\"attempt\":1"));
+
+        let report = manager.supervise().unwrap();
+        assert_eq!(report.exhausted, vec!["s_dead".to_string()]);
+        assert!(report.failed.is_empty());
+    }
+}
@@ -1,7 +1,252 @@
-use time::OffsetDateTime;
+use rand::Rng;
+use time::{OffsetDateTime, UtcOffset};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 /// Returns the current UTC timestamp in milliseconds, clamping to i64::MAX on overflow.
 pub fn now_ms() -> i64 {
     let nanos = OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000;
     i64::try_from(nanos).unwrap_or(i64::MAX)
 }
+
+/// The OS account running this process ($USER, or %USERNAME% on Windows).
+pub fn os_user() -> Option<String> {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .ok()
+        .filter(|user| !user.trim().is_empty())
+}
+
+/// Formats the distance between `ts_ms` and `now_ms` as "just now", "5 min ago", "2 h ago", ...
+pub fn format_relative_ms(ts_ms: i64, now_ms: i64) -> String {
+    let delta_secs = now_ms.saturating_sub(ts_ms) / 1000;
+    if delta_secs < 0 {
+        return "in the future".to_string();
+    }
+    match delta_secs {
+        0..=59 => "just now".to_string(),
+        60..=3_599 => format!("{} min ago", delta_secs / 60),
+        3_600..=86_399 => format!("{} h ago", delta_secs / 3_600),
+        86_400..=2_591_999 => format!("{} d ago", delta_secs / 86_400),
+        2_592_000..=31_535_999 => format!("{} mo ago", delta_secs / 2_592_000),
+        _ => format!("{} y ago", delta_secs / 31_536_000),
+    }
+}
+
+/// Parses a display timezone: `UTC`/`Z` or a fixed offset such as `+09:00` / `-0530`.
+pub fn parse_utc_offset(raw: &str) -> Option<UtcOffset> {
+    let raw = raw.trim();
+    if raw.eq_ignore_ascii_case("utc") || raw.eq_ignore_ascii_case("z") {
+        return Some(UtcOffset::UTC);
+    }
+    let (sign, rest) = match raw.as_bytes().first()? {
+        b'+' => (1, &raw[1..]),
+        b'-' => (-1, &raw[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if rest.len() == 4 => (rest.get(..2)?, rest.get(2..)?),
+        None => (rest, "0"),
+    };
+    let hours: i8 = hours.parse().ok()?;
+    let minutes: i8 = minutes.parse().ok()?;
+    if !(0..=59).contains(&minutes) {
+        return None;
+    }
+    UtcOffset::from_hms(sign * hours, sign * minutes, 0).ok()
+}
+
+/// Formats a millisecond timestamp as `YYYY-MM-DD HH:MM:SS ±HH:MM` in the given offset.
+pub fn format_timestamp_ms(ts_ms: i64, offset: UtcOffset) -> String {
+    let Ok(datetime) = OffsetDateTime::from_unix_timestamp_nanos(i128::from(ts_ms) * 1_000_000)
+    else {
+        return ts_ms.to_string();
+    };
+    let datetime = datetime.to_offset(offset);
+    let (hours, minutes, _) = offset.as_hms();
+    let sign = if offset.is_negative() { '-' } else { '+' };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} {}{:02}:{:02}",
+        datetime.year(),
+        u8::from(datetime.month()),
+        datetime.day(),
+        datetime.hour(),
+        datetime.minute(),
+        datetime.second(),
+        sign,
+        hours.unsigned_abs(),
+        minutes.unsigned_abs()
+    )
+}
+
+/// Characters for confirmation tokens; look-alikes (0/O, 1/I/L) are left out.
+const TOKEN_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+
+/// A short random code the user must type to confirm a dangerous action. Unlike a
+/// profile id it changes every time, so it cannot be typed from muscle memory.
+pub fn confirmation_token() -> String {
+    let mut rng = rand::thread_rng();
+    (0..4)
+        .map(|_| TOKEN_ALPHABET[rng.gen_range(0..TOKEN_ALPHABET.len())] as char)
+        .collect()
+}
+
+/// Terminal columns `text` takes up: CJK and other wide characters count as two.
+pub fn display_width(text: &str) -> usize {
+    UnicodeWidthStr::width(text)
+}
+
+/// `text` cut to at most `max` columns, ending in `marker` when anything was cut.
+pub fn truncate_display(text: &str, max: usize, marker: char) -> String {
+    if display_width(text) <= max {
+        return text.to_string();
+    }
+    let budget = max.saturating_sub(marker.width().unwrap_or(1));
+    let mut cut = String::new();
+    let mut used = 0;
+    for ch in text.chars() {
+        let width = ch.width().unwrap_or(0);
+        if used + width > budget {
+            break;
+        }
+        cut.push(ch);
+        used += width;
+    }
+    if max > 0 {
+        cut.push(marker);
+    }
+    cut
+}
+
+/// `text` followed by spaces up to `width` columns, like `{:<width$}` for wide characters.
+pub fn pad_display(text: &str, width: usize) -> String {
+    let pad = width.saturating_sub(display_width(text));
+    format!("{text}{}", " ".repeat(pad))
+}
+
+/// `input` split on whitespace with password, token, and secret values replaced by `****`,
+/// for showing commands on screen.
+pub fn mask_sensitive_tokens(input: &str) -> String {
+    let tokens: Vec<String> = input.split_whitespace().map(str::to_string).collect();
+    mask_sensitive_args(&tokens).join(" ")
+}
+
+/// `args` with the value after a flag such as `--password` or `-pw`, and the value of a
+/// `password=...`, `/passwd=...`, or `token=...` argument, replaced by `****`.
+pub fn mask_sensitive_args(args: &[String]) -> Vec<String> {
+    let mut masked = args.to_vec();
+    let mut idx = 0;
+    while idx < masked.len() {
+        if is_sensitive_flag(&masked[idx]) && idx + 1 < masked.len() {
+            masked[idx + 1] = "****".to_string();
+            idx += 2;
+            continue;
+        }
+        if let Some(value) = mask_sensitive_kv(&masked[idx]) {
+            masked[idx] = value;
+        }
+        idx += 1;
+    }
+    masked
+}
+
+fn is_sensitive_flag(token: &str) -> bool {
+    matches!(
+        token.to_lowercase().as_str(),
+        "--password"
+            | "--pass"
+            | "--token"
+            | "--secret"
+            | "--api-key"
+            | "--apikey"
+            | "--key"
+            | "-pw"
+            | "/passwd"
+            | "/password"
+    )
+}
+
+fn mask_sensitive_kv(token: &str) -> Option<String> {
+    let (key, value) = token.split_once('=')?;
+    if value.is_empty() {
+        return None;
+    }
+    let lowered = key.to_lowercase();
+    ["passw", "secret", "token", "apikey", "api_key", "api-key"]
+        .iter()
+        .any(|word| lowered.contains(word))
+        .then(|| format!("{key}=****"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_and_fits_wide_characters() {
+        assert_eq!(display_width("本番DB"), 6);
+        assert_eq!(truncate_display("本番データベース", 7, '…'), "本番デ…");
+        assert_eq!(truncate_display("web-01", 8, '…'), "web-01");
+        assert_eq!(truncate_display("abc", 0, '~'), "");
+        assert_eq!(pad_display("東京", 6), "東京  ");
+        assert_eq!(pad_display("toolong", 3), "toolong");
+    }
+
+    #[test]
+    fn formats_relative_times() {
+        let now = 1_000_000_000_000;
+        assert_eq!(format_relative_ms(now - 10_000, now), "just now");
+        assert_eq!(format_relative_ms(now - 5 * 60_000, now), "5 min ago");
+        assert_eq!(format_relative_ms(now - 2 * 3_600_000, now), "2 h ago");
+        assert_eq!(format_relative_ms(now - 3 * 86_400_000, now), "3 d ago");
+        assert_eq!(format_relative_ms(now + 120_000, now), "in the future");
+    }
+
+    #[test]
+    fn rejects_multibyte_offsets_without_panicking() {
+        assert!(parse_utc_offset("+é05").is_none());
+        assert!(parse_utc_offset("-0é5").is_none());
+        assert!(parse_utc_offset("+０９００").is_none());
+    }
+
+    #[test]
+    fn parses_offsets_and_formats_timestamps() {
+        assert_eq!(parse_utc_offset("UTC"), Some(UtcOffset::UTC));
+        let tokyo = parse_utc_offset("+09:00").unwrap();
+        assert_eq!(tokyo.as_hms(), (9, 0, 0));
+        assert_eq!(parse_utc_offset("-0530").unwrap().as_hms(), (-5, -30, 0));
+        assert!(parse_utc_offset("Asia/Tokyo").is_none());
+        assert_eq!(
+            format_timestamp_ms(0, tokyo),
+            "1970-01-01 09:00:00 +09:00".to_string()
+        );
+    }
+
+    #[test]
+    fn confirmation_tokens_use_unambiguous_characters() {
+        let token = confirmation_token();
+        assert_eq!(token.len(), 4);
+        assert!(token.bytes().all(|byte| TOKEN_ALPHABET.contains(&byte)));
+        assert!(!token.contains(['0', 'O', '1', 'I', 'L']));
+    }
+
+    #[test]
+    fn masks_sensitive_arguments() {
+        assert_eq!(
+            mask_sensitive_tokens("deploy --password hunter2 API_TOKEN=abc user=ops"),
+            "deploy --password **** API_TOKEN=**** user=ops"
+        );
+        let args: Vec<String> = ["ttermpro.exe", "host", "/passwd=hunter2", "-pw", "s3cret"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert_eq!(
+            mask_sensitive_args(&args),
+            ["ttermpro.exe", "host", "/passwd=****", "-pw", "****"]
+        );
+        assert_eq!(
+            mask_sensitive_tokens("-o PreferredAuthentications=publickey,password"),
+            "-o PreferredAuthentications=publickey,password"
+        );
+    }
+}
@@ -6,46 +6,87 @@ use std::time::Instant;
 use anyhow::{anyhow, bail, Context, Result};
 use crossterm::event::{
     self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
-    KeyModifiers,
+    KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
 };
 use crossterm::execute;
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
 };
 use ratatui::backend::CrosstermBackend;
+use ratatui::layout::Rect;
 use ratatui::Terminal;
+use tdcore::active_session;
 use tdcore::cmdset::CmdSetStore;
 #[cfg(windows)]
 use tdcore::conpty::{run_conpty_ssh_child, ConptyRunOptions};
 use tdcore::db;
 use tdcore::profile::ProfileStore;
+use tdcore::retention;
+use tdcore::run_report::ReportFormat;
+use tdcore::secret::SecretStore;
 use tdcore::session_log::{
     self, SessionLogFiles, SessionLogPlan, SessionLogReference,
     SESSION_LOG_REASON_METADATA_WRITE_FAILED, SESSION_LOG_REASON_POWERSHELL_LAUNCH_FAILED,
     SESSION_LOG_REASON_SCRIPT_LAUNCH_FAILED,
 };
+use tdcore::settings;
+use tdcore::transfer::{self, TransferDirection};
 use tdcore::util::now_ms;
 
+use crate::cmdset_editor::InputTarget;
 use crate::settings_ui;
+use crate::startup::StartupTimer;
 use crate::state::{
-    ActivePane, AppState, ConfirmedAction, InputMode, ResultTab, SshSessionCommand,
+    ActivePane, AppState, BulkEditStage, ConfirmedAction, InputMode, PaneSplit, ResultTab,
+    SnapshotView, SshSessionCommand,
 };
 use crate::ui;
 
-pub fn run() -> Result<()> {
+/// Runs the TUI; with `profile_startup`, prints how long each startup step took on exit.
+/// `observer` (or the `observer.enabled` setting) starts it in observer mode.
+pub fn run(profile_startup: bool, observer: bool) -> Result<()> {
     ensure_interactive_tty()?;
-    let conn = db::init_connection()?;
+    let mut timer = StartupTimer::new(profile_startup);
+    let conn = timer.time("open database", db::init_connection)?;
+    let pruned = timer.time("auto prune", || retention::auto_prune(&conn, now_ms()));
     let store = ProfileStore::new(conn);
-    let cmdset_store = CmdSetStore::new(db::init_connection()?);
+    let cmdset_store = CmdSetStore::new(timer.time("open cmdset store", db::init_connection)?);
+    let observer = observer || settings::get_observer_enabled(store.conn())?;
+    let mut state = AppState::with_timer(store, cmdset_store, &mut timer)?;
+    if observer {
+        state.enter_observer_mode();
+    }
+    match pruned {
+        Ok(Some(report)) if report.op_logs() + report.run_outputs() > 0 => state
+            .set_status_message(format!(
+                "Auto prune deleted {} op logs, {} outputs.",
+                report.op_logs(),
+                report.run_outputs()
+            )),
+        Ok(_) => {}
+        Err(err) => state.set_status_message(format!("Auto prune failed: {err}")),
+    }
+    let res = run_state(&mut state, &mut timer);
+    timer.print();
+    res
+}
+
+/// Runs the TUI against stores loaded from an export document (see `td view`).
+pub fn run_snapshot(store: ProfileStore, cmdset_store: CmdSetStore, allow_run: bool) -> Result<()> {
+    ensure_interactive_tty()?;
     let mut state = AppState::new(store, cmdset_store)?;
+    state.enter_snapshot_view(SnapshotView { allow_run });
+    run_state(&mut state, &mut StartupTimer::new(false))
+}
 
+fn run_state(state: &mut AppState, timer: &mut StartupTimer) -> Result<()> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let res = run_loop(&mut terminal, &mut state);
+    let res = run_loop(&mut terminal, state, timer).and_then(|()| state.save_pane_layout());
 
     disable_raw_mode()?;
     execute!(
@@ -61,9 +102,18 @@ pub fn run() -> Result<()> {
 fn run_loop(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     state: &mut AppState,
+    timer: &mut StartupTimer,
 ) -> Result<()> {
     loop {
-        terminal.draw(|frame| ui::render(frame, state))?;
+        timer.time("health check", || state.tick_health());
+        timer.time("tunnel check", || state.tick_tunnels(Instant::now()));
+        state.tick_run();
+        state.tick_schedules(now_ms());
+        state.tick_idle_lock(Instant::now());
+        timer.time("first frame", || {
+            terminal.draw(|frame| ui::render(frame, state))
+        })?;
+        timer.finish();
         if event::poll(Duration::from_millis(250))? {
             match event::read()? {
                 Event::Key(key) => {
@@ -75,8 +125,39 @@ fn run_loop(
                     {
                         return Ok(());
                     }
+                    if state.is_locked() {
+                        handle_lock_key(state, key.code)?;
+                        continue;
+                    }
+                    state.note_input();
+                    if key.modifiers.contains(KeyModifiers::CONTROL)
+                        && key.code == KeyCode::Char('k')
+                    {
+                        if state.mode() == InputMode::Normal
+                            && state.profile_form().is_none()
+                            && state.cmdset_editor().is_none()
+                            && state.transfer_form().is_none()
+                        {
+                            state.toggle_palette();
+                        }
+                        continue;
+                    }
+                    if key.modifiers.contains(KeyModifiers::CONTROL)
+                        && key.code == KeyCode::Char('z')
+                    {
+                        if state.mode() == InputMode::Normal
+                            && state.bulk_edit_state().is_none()
+                            && state.profile_form().is_none()
+                            && state.cmdset_editor().is_none()
+                            && state.transfer_form().is_none()
+                        {
+                            state.undo_bulk_edit()?;
+                        }
+                        continue;
+                    }
                     match state.mode() {
                         InputMode::Search => handle_search_key(state, key.code)?,
+                        InputMode::ResultSearch => handle_result_search_key(state, key.code),
                         InputMode::Normal => match handle_normal_key(state, key.code)? {
                             UiAction::Continue => {}
                             UiAction::Quit => return Ok(()),
@@ -86,9 +167,20 @@ fn run_loop(
                             UiAction::OpenSettings => {
                                 handle_settings_request(terminal, state)?;
                             }
+                            UiAction::RunTransfer => {
+                                handle_transfer_request(terminal, state)?;
+                            }
                         },
                     }
                 }
+                Event::Mouse(mouse) => {
+                    if state.is_locked() {
+                        continue;
+                    }
+                    state.note_input();
+                    let size = terminal.size()?;
+                    handle_mouse(state, mouse, size)?;
+                }
                 Event::Resize(_, _) => {}
                 _ => {}
             }
@@ -96,6 +188,125 @@ fn run_loop(
     }
 }
 
+/// Clicks focus the pane under the pointer and select the profile, CommandSet, or result
+/// tab clicked; the wheel moves the selection or scrolls the results. Ignored while a
+/// popup or form is open or a search is being typed.
+fn handle_mouse(state: &mut AppState, mouse: MouseEvent, area: Rect) -> Result<()> {
+    if state.mode() != InputMode::Normal || state.overlay_open() {
+        return Ok(());
+    }
+    let panes = ui::pane_areas(area, state.details_open(), state.pane_layout());
+    let (x, y) = (mouse.column, mouse.row);
+    match (mouse.kind, state.dragging()) {
+        (MouseEventKind::Down(MouseButton::Left), _) => {
+            if let Some(split) = ui::pane_border_at(&panes, x, y) {
+                state.start_drag(split);
+                return Ok(());
+            }
+        }
+        (MouseEventKind::Drag(MouseButton::Left), Some(split)) => {
+            state.set_pane_split(split, ui::pane_split_percent(&panes, split, x, y));
+            return Ok(());
+        }
+        (MouseEventKind::Up(MouseButton::Left), Some(_)) => return state.finish_drag(),
+        _ => {}
+    }
+    let inside = |rect: Rect| {
+        (rect.x..rect.x + rect.width).contains(&x) && (rect.y..rect.y + rect.height).contains(&y)
+    };
+    let pane = if inside(panes.profiles) {
+        ActivePane::Profiles
+    } else if inside(panes.actions) {
+        ActivePane::Actions
+    } else if inside(panes.result_tabs) || inside(panes.results) {
+        ActivePane::Results
+    } else {
+        return Ok(());
+    };
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            state.focus_pane(pane);
+            match pane {
+                ActivePane::Profiles => {
+                    let rows = state.profile_row_count();
+                    if let Some(row) =
+                        ui::list_row_at(panes.profiles, state.profile_cursor(), rows, y)
+                    {
+                        state.select_profile_row(row)?;
+                    }
+                }
+                ActivePane::Actions => {
+                    if let Some(cmdsets) = panes.cmdsets.filter(|rect| inside(*rect)) {
+                        let len = state.cmdsets().len();
+                        if let Some(index) = ui::list_row_at(cmdsets, state.cmdset_cursor(), len, y)
+                        {
+                            state.select_cmdset(index);
+                        }
+                    }
+                }
+                ActivePane::Results => {
+                    if let Some(tab) = ui::result_tab_at(panes.result_tabs, x)
+                        .filter(|_| inside(panes.result_tabs))
+                    {
+                        state.set_result_tab(tab);
+                    }
+                }
+            }
+        }
+        MouseEventKind::ScrollDown => match pane {
+            ActivePane::Profiles => {
+                if state
+                    .profile_cursor()
+                    .is_some_and(|row| row + 1 < state.profile_row_count())
+                {
+                    state.next_profile()?;
+                }
+            }
+            ActivePane::Actions if state.details_open() => state.scroll_details_down(),
+            ActivePane::Actions => {
+                if state
+                    .cmdset_cursor()
+                    .is_some_and(|index| index + 1 < state.cmdsets().len())
+                {
+                    state.next_cmdset();
+                }
+            }
+            ActivePane::Results => state.scroll_results_down(false),
+        },
+        MouseEventKind::ScrollUp => match pane {
+            ActivePane::Profiles => {
+                if state.profile_cursor().is_some_and(|row| row > 0) {
+                    state.prev_profile()?;
+                }
+            }
+            ActivePane::Actions if state.details_open() => state.scroll_details_up(),
+            ActivePane::Actions => {
+                if state.cmdset_cursor().is_some_and(|index| index > 0) {
+                    state.prev_cmdset();
+                }
+            }
+            ActivePane::Results => state.scroll_results_up(false),
+        },
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_lock_key(state: &mut AppState, code: KeyCode) -> Result<()> {
+    match code {
+        KeyCode::Enter => {
+            let input = state.take_unlock_input();
+            let secrets = SecretStore::new(db::init_connection()?);
+            let verified = secrets.verify_unlock(&input)?;
+            state.finish_unlock(verified);
+        }
+        KeyCode::Backspace => state.pop_unlock_char(),
+        KeyCode::Char(ch) => state.push_unlock_char(ch),
+        _ => {}
+    }
+    Ok(())
+}
+
 fn should_handle_key_event(key: &KeyEvent) -> bool {
     matches!(key.kind, KeyEventKind::Press | KeyEventKind::Repeat)
 }
@@ -109,24 +320,159 @@ fn handle_search_key(state: &mut AppState, code: KeyCode) -> Result<()> {
     }
 }
 
+fn handle_result_search_key(state: &mut AppState, code: KeyCode) {
+    match code {
+        KeyCode::Enter => state.submit_result_search(),
+        KeyCode::Esc => state.cancel_result_search(),
+        KeyCode::Backspace => state.pop_result_search_char(),
+        KeyCode::Char(ch) => state.push_result_search_char(ch),
+        _ => {}
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum UiAction {
     Continue,
     Quit,
     OpenSshSession,
     OpenSettings,
+    RunTransfer,
 }
 
 fn handle_normal_key(state: &mut AppState, code: KeyCode) -> Result<UiAction> {
+    if state.palette_state().is_some() {
+        return handle_palette_key(state, code);
+    }
     if state.confirm_state().is_some() {
         return handle_confirm_key(state, code);
     }
+    if let Some(editor) = state.cmdset_editor_mut() {
+        if editor.input.is_some() {
+            match code {
+                KeyCode::Enter => state.commit_cmdset_editor_input(),
+                KeyCode::Esc => editor.cancel_input(),
+                KeyCode::Backspace => editor.pop_char(),
+                KeyCode::Char(ch) => editor.push_char(ch),
+                _ => {}
+            }
+            return Ok(UiAction::Continue);
+        }
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => state.close_cmdset_editor(),
+            KeyCode::Char('s') => state.save_cmdset_editor()?,
+            KeyCode::Up | KeyCode::Char('k') => editor.move_cursor(false),
+            KeyCode::Down | KeyCode::Char('j') => editor.move_cursor(true),
+            KeyCode::Char('K') => editor.move_step(false),
+            KeyCode::Char('J') => editor.move_step(true),
+            KeyCode::Char('a') => editor.begin_input(InputTarget::AddStep),
+            KeyCode::Enter | KeyCode::Char('e') => editor.begin_input(InputTarget::Command),
+            KeyCode::Char('t') => editor.begin_input(InputTarget::Timeout),
+            KeyCode::Char('p') => editor.begin_input(InputTarget::Parser),
+            KeyCode::Char('n') => editor.begin_input(InputTarget::Name),
+            KeyCode::Char('o') => editor.toggle_on_error(),
+            KeyCode::Char('d') | KeyCode::Delete => editor.remove_step(),
+            _ => {}
+        }
+        return Ok(UiAction::Continue);
+    }
+    if let Some(form) = state.transfer_form_mut() {
+        match code {
+            KeyCode::Enter => {
+                return Ok(if state.submit_transfer_form() {
+                    UiAction::RunTransfer
+                } else {
+                    UiAction::Continue
+                });
+            }
+            KeyCode::Esc => state.close_transfer_form(),
+            KeyCode::Up | KeyCode::BackTab => form.move_cursor(false),
+            KeyCode::Down => form.move_cursor(true),
+            KeyCode::Tab => form.tab(),
+            KeyCode::Left | KeyCode::Right | KeyCode::Char(' ') if form.field().is_choice() => {
+                form.cycle_choice()
+            }
+            KeyCode::Backspace => form.pop_char(),
+            KeyCode::Char(ch) => form.push_char(ch),
+            _ => {}
+        }
+        return Ok(UiAction::Continue);
+    }
+    if let Some(form) = state.profile_form_mut() {
+        match code {
+            KeyCode::Enter => state.save_profile_form()?,
+            KeyCode::Esc => state.close_profile_form(),
+            KeyCode::Up | KeyCode::BackTab => form.move_cursor(false),
+            KeyCode::Down | KeyCode::Tab => form.move_cursor(true),
+            KeyCode::Left => form.cycle_choice(false),
+            KeyCode::Right => form.cycle_choice(true),
+            KeyCode::Backspace => form.pop_char(),
+            KeyCode::Char(ch) => form.push_char(ch),
+            _ => {}
+        }
+        return Ok(UiAction::Continue);
+    }
+    if state.preset_picker_state().is_some() {
+        match code {
+            KeyCode::Enter => state.apply_preset_choice()?,
+            KeyCode::Esc | KeyCode::Char('q') => state.close_preset_picker(),
+            KeyCode::Up | KeyCode::Char('k') => state.preset_picker_move(false),
+            KeyCode::Down | KeyCode::Char('j') => state.preset_picker_move(true),
+            _ => {}
+        }
+        return Ok(UiAction::Continue);
+    }
+    if state.bulk_edit_state().is_some() {
+        handle_bulk_edit_key(state, code)?;
+        return Ok(UiAction::Continue);
+    }
+    if state.history_state().is_some() {
+        match code {
+            KeyCode::Enter => state.reopen_history_entry()?,
+            KeyCode::Esc | KeyCode::Char('L') | KeyCode::Char('q') => state.close_history(),
+            KeyCode::Up | KeyCode::Char('k') => state.history_move(false)?,
+            KeyCode::Down | KeyCode::Char('j') => state.history_move(true)?,
+            KeyCode::Char('f') => state.cycle_history_filter(false)?,
+            KeyCode::Char('t') => state.cycle_history_filter(true)?,
+            KeyCode::Char('x') => state.export_history(),
+            KeyCode::Char('r') => {
+                return Ok(if state.retry_history_entry()? {
+                    UiAction::OpenSshSession
+                } else {
+                    UiAction::Continue
+                });
+            }
+            _ => {}
+        }
+        return Ok(UiAction::Continue);
+    }
+    if state.export_prompt_open() {
+        state.export_report(match code {
+            KeyCode::Char('j') => Some(ReportFormat::Json),
+            KeyCode::Char('t') => Some(ReportFormat::Text),
+            KeyCode::Char('h') => Some(ReportFormat::Html),
+            _ => None,
+        });
+        return Ok(UiAction::Continue);
+    }
     match code {
         KeyCode::Char('q') => return Ok(UiAction::Quit),
+        KeyCode::Char('/') if state.active_pane() == ActivePane::Results => {
+            state.enter_result_search()
+        }
         KeyCode::Char('/') => state.enter_search(),
+        KeyCode::Char('n') => state.next_result_match(),
+        KeyCode::Char('y') => state.copy_result_buffer(),
+        KeyCode::Char('P') => state.copy_login_secret()?,
+        KeyCode::Char('e') => state.open_export_prompt(),
+        KeyCode::PageUp => state.scroll_results_up(true),
+        KeyCode::PageDown => state.scroll_results_down(true),
         KeyCode::Char('T') => state.cycle_profile_type()?,
         KeyCode::Char('g') => state.cycle_group()?,
         KeyCode::Char('D') => state.cycle_danger()?,
+        KeyCode::Char('A') => state.toggle_archived()?,
+        KeyCode::Char('o') => state.cycle_profile_sort()?,
+        KeyCode::Char('G') => state.cycle_profile_grouping()?,
+        KeyCode::Char('z') => state.toggle_group_collapse()?,
         KeyCode::Char('c') => return Ok(UiAction::OpenSettings),
         KeyCode::Char('C') => state.clear_filters()?,
         KeyCode::Char('[') => state.tag_cursor_prev(),
@@ -136,6 +482,10 @@ fn handle_normal_key(state: &mut AppState, code: KeyCode) -> Result<UiAction> {
         KeyCode::Tab => state.cycle_pane(),
         KeyCode::Char('d') => state.toggle_details()?,
         KeyCode::Char('?') => state.toggle_help(),
+        KeyCode::Char('H') => state.toggle_health_column(),
+        KeyCode::Char('u') => state.refresh_health()?,
+        KeyCode::Char('F') => state.toggle_tunnel_auto_restart(),
+        KeyCode::Char('K') => state.request_terminate_session()?,
         KeyCode::Up | KeyCode::Char('k') => match state.active_pane() {
             ActivePane::Profiles => state.prev_profile()?,
             ActivePane::Actions => {
@@ -145,7 +495,7 @@ fn handle_normal_key(state: &mut AppState, code: KeyCode) -> Result<UiAction> {
                     state.prev_cmdset();
                 }
             }
-            ActivePane::Results => {}
+            ActivePane::Results => state.scroll_results_up(false),
         },
         KeyCode::Down | KeyCode::Char('j') => match state.active_pane() {
             ActivePane::Profiles => state.next_profile()?,
@@ -156,7 +506,7 @@ fn handle_normal_key(state: &mut AppState, code: KeyCode) -> Result<UiAction> {
                     state.next_cmdset();
                 }
             }
-            ActivePane::Results => {}
+            ActivePane::Results => state.scroll_results_down(false),
         },
         KeyCode::Left | KeyCode::Char('h') => match state.active_pane() {
             ActivePane::Results => state.prev_result_tab(),
@@ -170,34 +520,116 @@ fn handle_normal_key(state: &mut AppState, code: KeyCode) -> Result<UiAction> {
         KeyCode::Char('2') => state.set_result_tab(ResultTab::Stderr),
         KeyCode::Char('3') => state.set_result_tab(ResultTab::Parsed),
         KeyCode::Char('4') => state.set_result_tab(ResultTab::Summary),
+        KeyCode::Char('w') => state.toggle_result_wrap()?,
+        KeyCode::Char('W') => state.toggle_profile_list_wide()?,
+        KeyCode::Char(',') => state.cycle_result_step(false),
+        KeyCode::Char('.') => state.cycle_result_step(true),
+        KeyCode::Char('<') => state.resize_pane(PaneSplit::Profiles, false)?,
+        KeyCode::Char('>') => state.resize_pane(PaneSplit::Profiles, true)?,
+        KeyCode::Char('{') => state.resize_pane(PaneSplit::Actions, false)?,
+        KeyCode::Char('}') => state.resize_pane(PaneSplit::Actions, true)?,
+        KeyCode::Char('=') => state.reset_pane_layout()?,
+        KeyCode::Char('t') => state.toggle_result_timestamps()?,
+        KeyCode::Enter
+            if state.active_pane() == ActivePane::Profiles && state.selected_header().is_some() =>
+        {
+            state.toggle_group_collapse()?
+        }
         KeyCode::Char('r') | KeyCode::Enter => state.request_run()?,
         KeyCode::Char('R') => state.request_bulk_run()?,
+        KeyCode::Char('E') => state.open_bulk_edit(),
+        KeyCode::Char('p') => state.open_preset_picker()?,
+        KeyCode::Char('a') => state.open_new_profile_form(),
+        KeyCode::Char('m') => state.open_edit_profile_form(),
+        KeyCode::Char('b') => state.open_cmdset_editor()?,
+        KeyCode::Char('N') => state.open_new_cmdset_editor(),
+        KeyCode::Char('U') => state.open_transfer_form()?,
+        KeyCode::Char('L') => state.open_history()?,
         KeyCode::Char('s') => return Ok(UiAction::OpenSshSession),
         _ => {}
     }
     Ok(UiAction::Continue)
 }
 
+fn handle_palette_key(state: &mut AppState, code: KeyCode) -> Result<UiAction> {
+    match code {
+        KeyCode::Esc => state.toggle_palette(),
+        KeyCode::Up => state.palette_move(false),
+        KeyCode::Down => state.palette_move(true),
+        KeyCode::Backspace => state.pop_palette_char(),
+        KeyCode::Char(ch) => state.push_palette_char(ch),
+        KeyCode::Enter => {
+            if let Some(key) = state.take_palette_choice() {
+                let code = if key == '\t' {
+                    KeyCode::Tab
+                } else {
+                    KeyCode::Char(key)
+                };
+                return handle_normal_key(state, code);
+            }
+        }
+        _ => {}
+    }
+    Ok(UiAction::Continue)
+}
+
 fn handle_settings_request(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     state: &mut AppState,
 ) -> Result<()> {
+    if state.blocked_read_only("the settings screen", false) {
+        return Ok(());
+    }
     let profile_id = state.selected_profile_id();
-    let outcome = settings_ui::run_in_terminal(terminal, profile_id)?;
+    let selection = if state.marked_profiles().is_empty() {
+        profile_id.iter().cloned().collect()
+    } else {
+        state.marked_profiles().iter().cloned().collect()
+    };
+    let outcome = settings_ui::run_in_terminal(terminal, profile_id, selection)?;
     state.refresh_after_settings(outcome.saved, outcome.session_log_enabled)?;
     Ok(())
 }
 
+fn handle_bulk_edit_key(state: &mut AppState, code: KeyCode) -> Result<()> {
+    let choosing = matches!(
+        state.bulk_edit_state().map(|edit| &edit.stage),
+        Some(BulkEditStage::ChooseField { .. })
+    );
+    let typing = matches!(
+        state.bulk_edit_state().map(|edit| &edit.stage),
+        Some(BulkEditStage::Input { .. })
+    );
+    match code {
+        KeyCode::Enter => state.submit_bulk_edit()?,
+        KeyCode::Esc => state.back_bulk_edit(),
+        KeyCode::Backspace if typing => state.pop_bulk_edit_char(),
+        KeyCode::Char(ch) if typing => state.push_bulk_edit_char(ch),
+        KeyCode::Up | KeyCode::Char('k') if choosing => state.bulk_edit_move(false),
+        KeyCode::Down | KeyCode::Char('j') if choosing => state.bulk_edit_move(true),
+        _ => {}
+    }
+    Ok(())
+}
+
 fn handle_confirm_key(state: &mut AppState, code: KeyCode) -> Result<UiAction> {
     match code {
         KeyCode::Enter => match state.confirm_action()? {
             ConfirmedAction::Continue => Ok(UiAction::Continue),
             ConfirmedAction::OpenSshSession => Ok(UiAction::OpenSshSession),
+            ConfirmedAction::RunTransfer => Ok(UiAction::RunTransfer),
         },
         KeyCode::Backspace => {
             state.pop_confirm_char();
             Ok(UiAction::Continue)
         }
+        KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown => {
+            state.scroll_confirm(
+                matches!(code, KeyCode::Down | KeyCode::PageDown),
+                matches!(code, KeyCode::PageUp | KeyCode::PageDown),
+            );
+            Ok(UiAction::Continue)
+        }
         KeyCode::Char(ch) => {
             state.push_confirm_char(ch);
             Ok(UiAction::Continue)
@@ -253,6 +685,45 @@ fn handle_ssh_session_request(
     Ok(())
 }
 
+fn handle_transfer_request(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    state: &mut AppState,
+) -> Result<()> {
+    let Some(command) = state.build_transfer_command()? else {
+        return Ok(());
+    };
+    suspend_tui_terminal(terminal)?;
+    let request = &command.request;
+    let (arrow, verb) = match request.direction {
+        TransferDirection::Push => ("->", "Pushing"),
+        TransferDirection::Pull => ("<-", "Pulling"),
+    };
+    println!(
+        "{verb} {} {arrow} {}:{} via {}",
+        request.local_path.display(),
+        command.profile.host,
+        request.remote_path,
+        request.via.as_str()
+    );
+    if let Some(hint) = &command.auth_hint {
+        println!("{hint}");
+    }
+    let _ = io::stdout().flush();
+    let outcome = transfer::execute(
+        &command.profile,
+        request.direction,
+        &request.local_path,
+        &request.remote_path,
+        request.via,
+        command.client.clone(),
+        &command.auth_args,
+    );
+    if let Err(err) = resume_tui_terminal(terminal) {
+        return Err(err).context("failed to restore TUI after transfer");
+    }
+    state.record_transfer_result(&command, outcome)
+}
+
 struct SshSessionOutcome {
     ok: bool,
     exit_code: Option<i32>,
@@ -338,13 +809,15 @@ fn run_plain_ssh_session(
     session_log: SessionLogReference,
 ) -> SshSessionRunResult {
     let started = Instant::now();
-    let status = Command::new(&session.executable)
-        .args(&session.args)
-        .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .status()
-        .context("failed to launch ssh");
+    let status = active_session::run_tracked(
+        Command::new(&session.executable)
+            .args(&session.args)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit()),
+        &session.profile_id,
+    )
+    .context("failed to launch ssh");
     let duration_ms = started.elapsed().as_millis() as i64;
 
     match status {
@@ -376,13 +849,15 @@ fn run_script_logged_ssh_session(
     );
     let log_started_at = now_ms();
     let started = Instant::now();
-    let status = Command::new(&invocation.executable)
-        .args(&invocation.args)
-        .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .status()
-        .context("failed to launch script");
+    let status = active_session::run_tracked(
+        Command::new(&invocation.executable)
+            .args(&invocation.args)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit()),
+        &session.profile_id,
+    )
+    .context("failed to launch script");
     let duration_ms = started.elapsed().as_millis() as i64;
 
     match status {
@@ -446,13 +921,15 @@ fn run_powershell_transcript_ssh_session(
     );
     let log_started_at = now_ms();
     let started = Instant::now();
-    let status = Command::new(&invocation.executable)
-        .args(&invocation.args)
-        .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .status()
-        .context("failed to launch PowerShell");
+    let status = active_session::run_tracked(
+        Command::new(&invocation.executable)
+            .args(&invocation.args)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit()),
+        &session.profile_id,
+    )
+    .context("failed to launch PowerShell");
     let duration_ms = started.elapsed().as_millis() as i64;
 
     match status {
@@ -659,13 +1136,17 @@ fn append_session_log_status(
 
 #[cfg(test)]
 mod tests {
-    use super::{handle_normal_key, should_handle_key_event, UiAction};
-    use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+    use super::{handle_mouse, handle_normal_key, should_handle_key_event, UiAction};
+    use crossterm::event::{
+        KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    };
+    use ratatui::layout::Rect;
     use tdcore::cmdset::CmdSetStore;
     use tdcore::db;
-    use tdcore::profile::ProfileStore;
+    use tdcore::profile::{DangerLevel, NewProfile, ProfileStore, ProfileType};
 
-    use crate::state::AppState;
+    use crate::state::{ActivePane, AppState, ResultTab};
+    use crate::ui;
 
     fn empty_state() -> AppState {
         AppState::new(
@@ -710,4 +1191,111 @@ mod tests {
 
         assert_eq!(action, UiAction::OpenSettings);
     }
+
+    #[test]
+    fn shift_h_toggles_health_column() {
+        let mut state = empty_state();
+
+        handle_normal_key(&mut state, KeyCode::Char('H')).unwrap();
+        assert!(state.health_column_enabled());
+
+        handle_normal_key(&mut state, KeyCode::Char('H')).unwrap();
+        assert!(!state.health_column_enabled());
+    }
+
+    #[test]
+    fn palette_filters_actions_and_runs_the_chosen_one() {
+        let mut state = empty_state();
+        state.toggle_palette();
+        for ch in "Settings".chars() {
+            handle_normal_key(&mut state, KeyCode::Char(ch)).unwrap();
+        }
+        assert_eq!(state.palette_state().unwrap().matches().len(), 1);
+        let action = handle_normal_key(&mut state, KeyCode::Enter).unwrap();
+        assert_eq!(action, UiAction::OpenSettings);
+        assert!(state.palette_state().is_none());
+
+        state.toggle_palette();
+        for ch in "health col".chars() {
+            handle_normal_key(&mut state, KeyCode::Char(ch)).unwrap();
+        }
+        handle_normal_key(&mut state, KeyCode::Enter).unwrap();
+        assert!(state.health_column_enabled());
+
+        state.toggle_palette();
+        handle_normal_key(&mut state, KeyCode::Char('~')).unwrap();
+        handle_normal_key(&mut state, KeyCode::Enter).unwrap();
+        assert_eq!(state.status_message(), Some("No action matches '~'."));
+    }
+
+    #[test]
+    fn mouse_focuses_panes_selects_rows_and_switches_tabs() {
+        let store = ProfileStore::new(db::init_in_memory().unwrap());
+        for profile_id in ["p_a", "p_b", "p_c"] {
+            store
+                .insert(NewProfile {
+                    profile_id: Some(profile_id.to_string()),
+                    name: profile_id.to_string(),
+                    profile_type: ProfileType::Ssh,
+                    host: "example.com".to_string(),
+                    port: 22,
+                    user: "ops".to_string(),
+                    danger_level: DangerLevel::Normal,
+                    group: None,
+                    tags: Vec::new(),
+                    note: None,
+                    initial_send: None,
+                    client_overrides: None,
+                })
+                .unwrap();
+        }
+        let mut state =
+            AppState::new(store, CmdSetStore::new(db::init_in_memory().unwrap())).unwrap();
+        let area = Rect::new(0, 0, 120, 40);
+        let panes = ui::pane_areas(area, false, state.pane_layout());
+        let mouse = |kind, column, row| MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        };
+        let click = MouseEventKind::Down(MouseButton::Left);
+        let (list_x, list_y) = (panes.profiles.x + 2, panes.profiles.y + 1);
+
+        handle_mouse(&mut state, mouse(click, list_x, list_y + 1), area).unwrap();
+        assert_eq!(state.selected_profile().unwrap().profile_id, "p_b");
+
+        // " stdout │ stderr ...": stderr starts ten columns into the tab bar.
+        let tabs = panes.result_tabs;
+        handle_mouse(&mut state, mouse(click, tabs.x + 11, tabs.y + 1), area).unwrap();
+        assert_eq!(state.active_pane(), ActivePane::Results);
+        assert_eq!(state.result_tab(), ResultTab::Stderr);
+
+        let wheel = MouseEventKind::ScrollDown;
+        handle_mouse(&mut state, mouse(wheel, list_x, list_y), area).unwrap();
+        handle_mouse(&mut state, mouse(wheel, list_x, list_y), area).unwrap();
+        assert_eq!(
+            state.profile_cursor(),
+            Some(2),
+            "the wheel stops at the end"
+        );
+
+        let border = panes.profiles.x + panes.profiles.width - 1;
+        handle_mouse(&mut state, mouse(click, border, list_y), area).unwrap();
+        let drag = MouseEventKind::Drag(MouseButton::Left);
+        handle_mouse(&mut state, mouse(drag, 60, list_y), area).unwrap();
+        assert_eq!(state.pane_layout().profiles_width, 50);
+        let release = MouseEventKind::Up(MouseButton::Left);
+        handle_mouse(&mut state, mouse(release, 60, list_y), area).unwrap();
+        assert!(state.dragging().is_none());
+
+        state.toggle_help();
+        handle_mouse(&mut state, mouse(click, list_x, list_y), area).unwrap();
+        assert_eq!(
+            state.profile_cursor(),
+            Some(2),
+            "ignored under the help screen"
+        );
+        assert_eq!(state.active_pane(), ActivePane::Results);
+    }
 }
@@ -0,0 +1,297 @@
+//! The CommandSet editor (`b` edits the selected CommandSet, `N` starts a new one): steps
+//! are changed in memory and written with one `CmdSetStore` call on `s`.
+
+use tdcore::cmdset::{CmdSet, CmdStep, NewCmdStep, StepOnError};
+use tdcore::parser::ParserSpec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputTarget {
+    Name,
+    /// A new step after the cursor.
+    AddStep,
+    Command,
+    Timeout,
+    Parser,
+}
+
+impl InputTarget {
+    pub fn prompt(self) -> &'static str {
+        match self {
+            InputTarget::Name => "Name",
+            InputTarget::AddStep => "New step command",
+            InputTarget::Command => "Command",
+            InputTarget::Timeout => "Timeout in ms (empty for none)",
+            InputTarget::Parser => "Parser (raw, json, or regex:<parser_id>)",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EditorInput {
+    pub target: InputTarget,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CmdSetEditor {
+    /// `None` for a new CommandSet, whose id is generated on save.
+    pub cmdset_id: Option<String>,
+    pub name: String,
+    pub steps: Vec<NewCmdStep>,
+    pub cursor: usize,
+    pub input: Option<EditorInput>,
+    pub dirty: bool,
+    /// Set by `Esc` with unsaved changes; a second `Esc` discards them.
+    pub discard_armed: bool,
+    pub error: Option<String>,
+}
+
+impl CmdSetEditor {
+    pub fn new_cmdset() -> Self {
+        Self {
+            cmdset_id: None,
+            name: String::new(),
+            steps: Vec::new(),
+            cursor: 0,
+            input: Some(EditorInput {
+                target: InputTarget::Name,
+                text: String::new(),
+            }),
+            dirty: true,
+            discard_armed: false,
+            error: None,
+        }
+    }
+
+    pub fn edit(cmdset: &CmdSet, steps: &[CmdStep]) -> Self {
+        Self {
+            cmdset_id: Some(cmdset.cmdset_id.clone()),
+            name: cmdset.name.clone(),
+            steps: steps.iter().map(NewCmdStep::from).collect(),
+            cursor: 0,
+            input: None,
+            dirty: false,
+            discard_armed: false,
+            error: None,
+        }
+    }
+
+    pub fn move_cursor(&mut self, down: bool) {
+        self.cursor = if down {
+            (self.cursor + 1).min(self.steps.len().saturating_sub(1))
+        } else {
+            self.cursor.saturating_sub(1)
+        };
+    }
+
+    /// `J`/`K`: swaps the selected step with the next or previous one.
+    pub fn move_step(&mut self, down: bool) {
+        let other = if down {
+            self.cursor + 1
+        } else {
+            match self.cursor.checked_sub(1) {
+                Some(other) => other,
+                None => return,
+            }
+        };
+        if other >= self.steps.len() {
+            return;
+        }
+        self.steps.swap(self.cursor, other);
+        self.cursor = other;
+        self.changed();
+    }
+
+    pub fn remove_step(&mut self) {
+        if self.cursor < self.steps.len() {
+            self.steps.remove(self.cursor);
+            self.cursor = self.cursor.min(self.steps.len().saturating_sub(1));
+            self.changed();
+        }
+    }
+
+    pub fn toggle_on_error(&mut self) {
+        if let Some(step) = self.steps.get_mut(self.cursor) {
+            step.on_error = match step.on_error {
+                StepOnError::Stop => StepOnError::Continue,
+                StepOnError::Continue => StepOnError::Stop,
+            };
+            self.changed();
+        }
+    }
+
+    /// Opens the input line for `target`, filled with the current value.
+    pub fn begin_input(&mut self, target: InputTarget) {
+        let step = self.steps.get(self.cursor);
+        let text = match target {
+            InputTarget::Name => self.name.clone(),
+            InputTarget::AddStep => String::new(),
+            InputTarget::Command => match step {
+                Some(step) => step.cmd.clone(),
+                None => return,
+            },
+            InputTarget::Timeout => match step {
+                Some(step) => step.timeout_ms.map(|ms| ms.to_string()).unwrap_or_default(),
+                None => return,
+            },
+            InputTarget::Parser => match step {
+                Some(step) => step.parser_spec.to_string(),
+                None => return,
+            },
+        };
+        self.error = None;
+        self.input = Some(EditorInput { target, text });
+    }
+
+    pub fn push_char(&mut self, ch: char) {
+        if let Some(input) = &mut self.input {
+            input.text.push(ch);
+        }
+    }
+
+    pub fn pop_char(&mut self) {
+        if let Some(input) = &mut self.input {
+            input.text.pop();
+        }
+    }
+
+    pub fn cancel_input(&mut self) {
+        self.input = None;
+    }
+
+    /// Applies the input line. A value that does not validate keeps the line open with an
+    /// error; `parser_exists` checks the id of a `regex:` parser.
+    pub fn commit_input(&mut self, parser_exists: impl Fn(&str) -> bool) {
+        let Some(input) = self.input.take() else {
+            return;
+        };
+        match self.apply_input(&input, parser_exists) {
+            Ok(()) => {
+                self.error = None;
+                self.changed();
+            }
+            Err(err) => {
+                self.error = Some(err);
+                self.input = Some(input);
+            }
+        }
+    }
+
+    fn apply_input(
+        &mut self,
+        input: &EditorInput,
+        parser_exists: impl Fn(&str) -> bool,
+    ) -> Result<(), String> {
+        let text = input.text.trim();
+        match input.target {
+            InputTarget::Name => {
+                if text.is_empty() {
+                    return Err("Name must not be empty.".to_string());
+                }
+                self.name = text.to_string();
+            }
+            InputTarget::AddStep | InputTarget::Command if text.is_empty() => {
+                return Err("Command must not be empty.".to_string());
+            }
+            InputTarget::AddStep => {
+                let at = if self.steps.is_empty() {
+                    0
+                } else {
+                    self.cursor + 1
+                };
+                self.steps.insert(
+                    at,
+                    NewCmdStep {
+                        cmd: text.to_string(),
+                        timeout_ms: None,
+                        on_error: StepOnError::Stop,
+                        parser_spec: ParserSpec::Raw,
+                    },
+                );
+                self.cursor = at;
+            }
+            InputTarget::Command => self.steps[self.cursor].cmd = text.to_string(),
+            InputTarget::Timeout => {
+                let timeout_ms = if text.is_empty() {
+                    None
+                } else {
+                    match text.parse::<u64>() {
+                        Ok(ms) if ms > 0 => Some(ms),
+                        _ => return Err("Timeout must be a positive number of ms.".to_string()),
+                    }
+                };
+                self.steps[self.cursor].timeout_ms = timeout_ms;
+            }
+            InputTarget::Parser => {
+                let spec = ParserSpec::parse(text).map_err(|err| err.to_string())?;
+                if let ParserSpec::Regex(id) = &spec {
+                    if !parser_exists(id) {
+                        return Err(format!("No parser named {id}."));
+                    }
+                }
+                self.steps[self.cursor].parser_spec = spec;
+            }
+        }
+        Ok(())
+    }
+
+    fn changed(&mut self) {
+        self.dirty = true;
+        self.discard_armed = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_line(editor: &mut CmdSetEditor, target: InputTarget, text: &str) {
+        editor.begin_input(target);
+        let input = editor.input.as_mut().unwrap();
+        input.text = text.to_string();
+        editor.commit_input(|id| id == "r_status");
+    }
+
+    #[test]
+    fn edits_reorders_and_validates_steps() {
+        let mut editor = CmdSetEditor::new_cmdset();
+        editor.commit_input(|_| true);
+        assert_eq!(editor.error.as_deref(), Some("Name must not be empty."));
+        editor.input.as_mut().unwrap().text = "Disk check".to_string();
+        editor.commit_input(|_| true);
+        assert_eq!(editor.name, "Disk check");
+
+        type_line(&mut editor, InputTarget::AddStep, "df -h");
+        type_line(&mut editor, InputTarget::AddStep, "uptime");
+        editor.move_cursor(false);
+        type_line(&mut editor, InputTarget::AddStep, "uname -a");
+        let cmds = |editor: &CmdSetEditor| {
+            editor
+                .steps
+                .iter()
+                .map(|step| step.cmd.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(cmds(&editor), ["df -h", "uname -a", "uptime"]);
+
+        editor.move_step(true);
+        assert_eq!(cmds(&editor), ["df -h", "uptime", "uname -a"]);
+        assert_eq!(editor.cursor, 2);
+        editor.remove_step();
+        assert_eq!(cmds(&editor), ["df -h", "uptime"]);
+
+        type_line(&mut editor, InputTarget::Timeout, "soon");
+        assert!(editor.input.is_some(), "bad value keeps the line open");
+        editor.cancel_input();
+        type_line(&mut editor, InputTarget::Timeout, "5000");
+        type_line(&mut editor, InputTarget::Parser, "regex:r_missing");
+        assert_eq!(editor.error.as_deref(), Some("No parser named r_missing."));
+        editor.cancel_input();
+        type_line(&mut editor, InputTarget::Parser, "regex:r_status");
+        editor.toggle_on_error();
+        let step = &editor.steps[1];
+        assert_eq!(step.timeout_ms, Some(5000));
+        assert_eq!(step.parser_spec, ParserSpec::Regex("r_status".to_string()));
+        assert_eq!(step.on_error, StepOnError::Continue);
+    }
+}
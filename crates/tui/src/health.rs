@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tdcore::profile::Profile;
+use tdcore::tester::{self, LatencyStats, TestOptions};
+
+const WORKER_COUNT: usize = 4;
+const TCP_TIMEOUT: Duration = Duration::from_secs(3);
+const LATENCY_SAMPLES: u32 = 3;
+pub const STALE_AFTER: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HealthStatus {
+    Pending,
+    Ok {
+        checked_at: Instant,
+        duration_ms: i64,
+        latency: Option<LatencyStats>,
+    },
+    Failed {
+        checked_at: Instant,
+        detail: String,
+    },
+    NotApplicable,
+}
+
+impl HealthStatus {
+    pub fn is_stale(&self, now: Instant) -> bool {
+        match self {
+            HealthStatus::Ok { checked_at, .. } | HealthStatus::Failed { checked_at, .. } => {
+                now.duration_since(*checked_at) >= STALE_AFTER
+            }
+            HealthStatus::Pending | HealthStatus::NotApplicable => false,
+        }
+    }
+
+    /// One-line description for the details pane, including RTT and loss when measured.
+    pub fn summary(&self, now: Instant) -> String {
+        let age = |checked_at: &Instant| now.duration_since(*checked_at).as_secs();
+        match self {
+            HealthStatus::Pending => "checking...".to_string(),
+            HealthStatus::NotApplicable => "n/a (serial profile)".to_string(),
+            HealthStatus::Ok {
+                checked_at,
+                latency: Some(latency),
+                ..
+            } => format!("up, {} ({}s ago)", latency.summary(), age(checked_at)),
+            HealthStatus::Ok {
+                checked_at,
+                duration_ms,
+                ..
+            } => format!("up in {duration_ms}ms ({}s ago)", age(checked_at)),
+            HealthStatus::Failed { checked_at, detail } => {
+                format!("down, {detail} ({}s ago)", age(checked_at))
+            }
+        }
+    }
+}
+
+struct HealthResult {
+    profile_id: String,
+    status: HealthStatus,
+}
+
+/// Background DNS/TCP checks (via `tester`) for the TUI status column.
+pub struct HealthMonitor {
+    jobs: Sender<Profile>,
+    results: Receiver<HealthResult>,
+    statuses: HashMap<String, HealthStatus>,
+}
+
+impl HealthMonitor {
+    pub fn new() -> Self {
+        let (job_tx, job_rx) = mpsc::channel::<Profile>();
+        let (result_tx, result_rx) = mpsc::channel();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+        for _ in 0..WORKER_COUNT {
+            let job_rx = Arc::clone(&job_rx);
+            let result_tx = result_tx.clone();
+            thread::spawn(move || loop {
+                let profile = match job_rx.lock() {
+                    Ok(rx) => match rx.recv() {
+                        Ok(profile) => profile,
+                        Err(_) => return,
+                    },
+                    Err(_) => return,
+                };
+                let status = check_profile(&profile);
+                if result_tx
+                    .send(HealthResult {
+                        profile_id: profile.profile_id,
+                        status,
+                    })
+                    .is_err()
+                {
+                    return;
+                }
+            });
+        }
+        Self {
+            jobs: job_tx,
+            results: result_rx,
+            statuses: HashMap::new(),
+        }
+    }
+
+    pub fn status(&self, profile_id: &str) -> Option<&HealthStatus> {
+        self.statuses.get(profile_id)
+    }
+
+    /// Queues checks for profiles that have never been checked; stale results are kept
+    /// until the user forces a refresh.
+    pub fn request_missing(&mut self, profiles: &[Profile]) -> usize {
+        let due = profiles
+            .iter()
+            .filter(|profile| !self.statuses.contains_key(&profile.profile_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.request(due)
+    }
+
+    /// Queues checks regardless of previous results; pending profiles are skipped.
+    pub fn request(&mut self, profiles: Vec<Profile>) -> usize {
+        let mut queued = 0;
+        for profile in profiles {
+            if self.statuses.get(&profile.profile_id) == Some(&HealthStatus::Pending) {
+                continue;
+            }
+            if !tester::is_network_profile(&profile) {
+                self.statuses
+                    .insert(profile.profile_id, HealthStatus::NotApplicable);
+                continue;
+            }
+            let profile_id = profile.profile_id.clone();
+            if self.jobs.send(profile).is_ok() {
+                self.statuses.insert(profile_id, HealthStatus::Pending);
+                queued += 1;
+            }
+        }
+        queued
+    }
+
+    /// Drains finished checks; returns true when any status changed.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok(result) = self.results.try_recv() {
+            self.statuses.insert(result.profile_id, result.status);
+            changed = true;
+        }
+        changed
+    }
+}
+
+fn check_profile(profile: &Profile) -> HealthStatus {
+    let report = tester::run_profile_test(
+        profile,
+        &TestOptions {
+            tcp_timeout: TCP_TIMEOUT,
+            ssh: None,
+            telnet_banner_timeout: None,
+            latency_samples: Some(LATENCY_SAMPLES),
+        },
+    );
+    let checked_at = Instant::now();
+    if report.ok {
+        HealthStatus::Ok {
+            checked_at,
+            duration_ms: report.duration_ms,
+            latency: report.latency,
+        }
+    } else {
+        let detail = report
+            .failure_summary()
+            .unwrap_or_else(|| "failed".to_string());
+        HealthStatus::Failed { checked_at, detail }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    use tdcore::profile::{DangerLevel, ProfileType};
+
+    fn profile(profile_id: &str, profile_type: ProfileType, port: u16) -> Profile {
+        Profile {
+            profile_id: profile_id.to_string(),
+            name: profile_id.to_string(),
+            profile_type,
+            host: "127.0.0.1".to_string(),
+            port,
+            user: "alice".to_string(),
+            danger_level: DangerLevel::Normal,
+            group: None,
+            tags: Vec::new(),
+            note: None,
+            initial_send: None,
+            client_overrides: None,
+            created_at: 0,
+            updated_at: 0,
+            last_used_at: None,
+            archived_at: None,
+        }
+    }
+
+    fn wait_for_results(monitor: &mut HealthMonitor, profile_id: &str) -> HealthStatus {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while Instant::now() < deadline {
+            monitor.poll();
+            match monitor.status(profile_id) {
+                Some(HealthStatus::Pending) | None => thread::sleep(Duration::from_millis(20)),
+                Some(status) => return status.clone(),
+            }
+        }
+        panic!("health check did not finish");
+    }
+
+    #[test]
+    fn reports_ok_for_listening_port() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut monitor = HealthMonitor::new();
+
+        let queued = monitor.request(vec![profile("p_up", ProfileType::Ssh, port)]);
+
+        assert_eq!(queued, 1);
+        assert_eq!(monitor.status("p_up"), Some(&HealthStatus::Pending));
+        let status = wait_for_results(&mut monitor, "p_up");
+        match &status {
+            HealthStatus::Ok {
+                latency: Some(latency),
+                ..
+            } => assert_eq!(latency.received, LATENCY_SAMPLES),
+            other => panic!("expected up with latency, got {other:?}"),
+        }
+        assert!(status
+            .summary(Instant::now())
+            .starts_with("up, rtt min/avg/max"));
+    }
+
+    #[test]
+    fn serial_profiles_are_not_checked() {
+        let mut monitor = HealthMonitor::new();
+
+        let queued = monitor.request_missing(&[profile("p_serial", ProfileType::Serial, 0)]);
+
+        assert_eq!(queued, 0);
+        assert_eq!(
+            monitor.status("p_serial"),
+            Some(&HealthStatus::NotApplicable)
+        );
+    }
+
+    #[test]
+    fn results_become_stale_after_threshold() {
+        let now = Instant::now();
+        let fresh = HealthStatus::Ok {
+            checked_at: now,
+            duration_ms: 1,
+            latency: None,
+        };
+        assert!(!fresh.is_stale(now));
+        assert!(fresh.is_stale(now + STALE_AFTER));
+        assert!(!HealthStatus::Pending.is_stale(now + STALE_AFTER));
+    }
+}
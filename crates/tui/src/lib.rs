@@ -1,9 +1,15 @@
 //! Ratatui-based TUI for TeraDock.
 
 mod app;
+mod cmdset_editor;
+mod health;
+mod live_run;
+mod profile_form;
 mod settings_ui;
+mod startup;
 mod state;
+mod transfer_form;
 mod ui;
 
-pub use app::run;
+pub use app::{run, run_snapshot};
 pub use settings_ui::{run as run_settings_ui, SettingsUiOutcome};
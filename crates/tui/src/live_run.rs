@@ -0,0 +1,343 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use tdcore::cmdset::CmdSetStore;
+use tdcore::cmdset_runner::{daemon_secret_env, run_cmdset_ssh, CmdSetRunRequest, CmdSetRunResult};
+use tdcore::db;
+use tdcore::hooks::{self, HookContext};
+use tdcore::profile::ProfileStore;
+use tdcore::run_events::{OutputStream, RunEvent, RunEventBus};
+
+/// Clients resolved on the UI thread before the worker starts.
+pub struct RunClients {
+    pub ssh: PathBuf,
+    pub scp: Option<PathBuf>,
+    pub auth_args: Vec<OsString>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepStatus {
+    Pending,
+    Running,
+    Ok,
+    Failed,
+}
+
+impl StepStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            StepStatus::Pending => "pending",
+            StepStatus::Running => "running",
+            StepStatus::Ok => "ok",
+            StepStatus::Failed => "fail",
+        }
+    }
+}
+
+/// One line of the step list above the results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepRow {
+    pub ord: i64,
+    pub cmd: String,
+    pub status: StepStatus,
+    /// Time the step took, or has taken so far while it runs; `None` before it starts or
+    /// when the source did not record it.
+    pub elapsed_ms: Option<i64>,
+}
+
+/// A step of a live run, with the output it has printed so far.
+#[derive(Debug, Clone)]
+pub struct LiveStep {
+    pub ord: i64,
+    pub cmd: String,
+    pub status: StepStatus,
+    pub started: Option<Instant>,
+    pub duration_ms: Option<i64>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl LiveStep {
+    fn pending(ord: i64, cmd: String) -> Self {
+        Self {
+            ord,
+            cmd,
+            status: StepStatus::Pending,
+            started: None,
+            duration_ms: None,
+            stdout: String::new(),
+            stderr: String::new(),
+        }
+    }
+}
+
+/// A CommandSet run on a worker thread, with output collected from its run events so
+/// the result pane can show it while the run is still going.
+pub struct LiveRun {
+    pub profile_id: String,
+    pub cmdset_id: String,
+    pub steps_total: usize,
+    pub current_step: Option<(i64, String)>,
+    pub steps_done: usize,
+    pub stdout: String,
+    pub stderr: String,
+    /// Every step of the CommandSet, listed as pending from the start; output is also
+    /// kept per step so the result pane can show one step at a time.
+    pub steps: Vec<LiveStep>,
+    events: Option<Receiver<RunEvent>>,
+    worker: Option<JoinHandle<Result<CmdSetRunResult>>>,
+}
+
+impl LiveRun {
+    /// `planned` lists the CommandSet's steps (ord and command) so they show as pending
+    /// before they run.
+    pub fn start(
+        profile_id: &str,
+        cmdset_id: &str,
+        planned: Vec<(i64, String)>,
+        clients: RunClients,
+    ) -> Self {
+        let bus = RunEventBus::new();
+        let events = bus.subscribe();
+        let (worker_profile, worker_cmdset) = (profile_id.to_string(), cmdset_id.to_string());
+        let worker = thread::spawn(move || {
+            let profile_store = ProfileStore::new(db::init_connection()?);
+            let cmdset_store = CmdSetStore::new(db::init_connection()?);
+            let profile = profile_store
+                .get(&worker_profile)?
+                .ok_or_else(|| anyhow!("profile not found: {worker_profile}"))?;
+            let context = HookContext {
+                op: "run",
+                profile: &profile,
+                cmdset_id: Some(&worker_cmdset),
+            };
+            let env = daemon_secret_env(&cmdset_store, &worker_cmdset)?;
+            let (mut result, failed_hooks) = hooks::around(
+                &profile_store,
+                &cmdset_store,
+                &context,
+                || {
+                    run_cmdset_ssh(
+                        &profile_store,
+                        &cmdset_store,
+                        CmdSetRunRequest {
+                            profile_id: &worker_profile,
+                            cmdset_id: &worker_cmdset,
+                            ssh: &clients.ssh,
+                            ssh_auth_args: &clients.auth_args,
+                            scp: clients.scp.as_deref(),
+                            ssh_password: None,
+                            events: Some(&bus),
+                            force_guard: false,
+                            env: &env,
+                        },
+                        |_| Ok(()),
+                    )
+                },
+                |result| result.ok,
+            )?;
+            for hook in failed_hooks {
+                result
+                    .stderr
+                    .push_str(&format!("warning: {}\n", hook.describe()));
+            }
+            Ok(result)
+        });
+        let mut run = Self::new(profile_id, cmdset_id);
+        run.steps = planned
+            .into_iter()
+            .map(|(ord, cmd)| LiveStep::pending(ord, cmd))
+            .collect();
+        run.events = Some(events);
+        run.worker = Some(worker);
+        run
+    }
+
+    fn new(profile_id: &str, cmdset_id: &str) -> Self {
+        Self {
+            profile_id: profile_id.to_string(),
+            cmdset_id: cmdset_id.to_string(),
+            steps_total: 0,
+            current_step: None,
+            steps_done: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            steps: Vec::new(),
+            events: None,
+            worker: None,
+        }
+    }
+
+    pub fn apply(&mut self, event: RunEvent) {
+        match event {
+            RunEvent::RunStarted { steps, .. } => self.steps_total = steps,
+            RunEvent::StepStarted { ord, cmd } => {
+                let step = self.step_mut(ord, &cmd);
+                step.status = StepStatus::Running;
+                step.started = Some(Instant::now());
+                self.current_step = Some((ord, cmd));
+            }
+            RunEvent::Output { ord, stream, chunk } => {
+                let step = self.steps.iter_mut().find(|step| step.ord == ord);
+                match stream {
+                    OutputStream::Stdout => {
+                        if let Some(step) = step {
+                            step.stdout.push_str(&chunk);
+                        }
+                        self.stdout.push_str(&chunk);
+                    }
+                    OutputStream::Stderr => {
+                        if let Some(step) = step {
+                            step.stderr.push_str(&chunk);
+                        }
+                        self.stderr.push_str(&chunk);
+                    }
+                }
+            }
+            RunEvent::StepFinished {
+                ord,
+                ok,
+                duration_ms,
+                ..
+            } => {
+                if let Some(step) = self.steps.iter_mut().find(|step| step.ord == ord) {
+                    step.status = if ok {
+                        StepStatus::Ok
+                    } else {
+                        StepStatus::Failed
+                    };
+                    step.duration_ms = Some(duration_ms);
+                }
+                self.steps_done += 1;
+            }
+            RunEvent::StepParsed { .. } | RunEvent::RunFinished { .. } => {}
+        }
+    }
+
+    /// The step with `ord`, added at the end when the plan did not list it.
+    fn step_mut(&mut self, ord: i64, cmd: &str) -> &mut LiveStep {
+        let index = match self.steps.iter().position(|step| step.ord == ord) {
+            Some(index) => index,
+            None => {
+                self.steps.push(LiveStep::pending(ord, cmd.to_string()));
+                self.steps.len() - 1
+            }
+        };
+        &mut self.steps[index]
+    }
+
+    pub fn step_rows(&self, now: Instant) -> Vec<StepRow> {
+        self.steps
+            .iter()
+            .map(|step| StepRow {
+                ord: step.ord,
+                cmd: step.cmd.clone(),
+                status: step.status,
+                elapsed_ms: step.duration_ms.or_else(|| {
+                    step.started
+                        .map(|started| now.duration_since(started).as_millis() as i64)
+                }),
+            })
+            .collect()
+    }
+
+    /// Applies pending events; returns the outcome once the worker has finished.
+    pub fn poll(&mut self) -> Option<Result<CmdSetRunResult>> {
+        if let Some(events) = self.events.take() {
+            for event in events.try_iter() {
+                self.apply(event);
+            }
+            self.events = Some(events);
+        }
+        if !self.worker.as_ref()?.is_finished() {
+            return None;
+        }
+        let worker = self.worker.take()?;
+        Some(
+            worker
+                .join()
+                .unwrap_or_else(|_| Err(anyhow!("run worker panicked"))),
+        )
+    }
+
+    pub fn progress(&self) -> String {
+        match &self.current_step {
+            Some((ord, cmd)) => format!(
+                "Running {} on {}: step {ord} ({}/{}) {cmd}",
+                self.cmdset_id, self.profile_id, self.steps_done, self.steps_total
+            ),
+            None => format!("Starting {} on {}...", self.cmdset_id, self.profile_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_output_and_progress_from_events() {
+        let mut run = LiveRun::new("p_web", "c_check");
+        run.steps = vec![
+            LiveStep::pending(1, "uptime".to_string()),
+            LiveStep::pending(2, "df -h".to_string()),
+            LiveStep::pending(3, "free -m".to_string()),
+        ];
+        for event in [
+            RunEvent::RunStarted {
+                profile_id: "p_web".to_string(),
+                cmdset_id: "c_check".to_string(),
+                steps: 2,
+            },
+            RunEvent::StepStarted {
+                ord: 1,
+                cmd: "uptime".to_string(),
+            },
+            RunEvent::Output {
+                ord: 1,
+                stream: OutputStream::Stdout,
+                chunk: "up 3 days\n".to_string(),
+            },
+            RunEvent::Output {
+                ord: 1,
+                stream: OutputStream::Stderr,
+                chunk: "warn\n".to_string(),
+            },
+            RunEvent::StepFinished {
+                ord: 1,
+                ok: true,
+                exit_code: 0,
+                duration_ms: 5,
+            },
+            RunEvent::StepStarted {
+                ord: 2,
+                cmd: "df -h".to_string(),
+            },
+        ] {
+            run.apply(event);
+        }
+        assert_eq!(run.stdout, "up 3 days\n");
+        assert_eq!(run.stderr, "warn\n");
+        assert_eq!(
+            run.progress(),
+            "Running c_check on p_web: step 2 (1/2) df -h"
+        );
+        assert!(run.poll().is_none());
+
+        let rows = run.step_rows(Instant::now());
+        let statuses = rows.iter().map(|row| row.status).collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            [StepStatus::Ok, StepStatus::Running, StepStatus::Pending]
+        );
+        assert_eq!(rows[0].elapsed_ms, Some(5));
+        assert!(rows[1].elapsed_ms.is_some());
+        assert_eq!(rows[2].elapsed_ms, None);
+        assert_eq!(run.steps[0].stdout, "up 3 days\n");
+        assert!(run.steps[1].stdout.is_empty());
+    }
+}
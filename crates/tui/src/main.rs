@@ -1,5 +1,7 @@
 use anyhow::Result;
 
 fn main() -> Result<()> {
-    tui::run()
+    let profile_startup = std::env::args().any(|arg| arg == "--profile-startup");
+    let observer = std::env::args().any(|arg| arg == "--observer");
+    tui::run(profile_startup, observer)
 }
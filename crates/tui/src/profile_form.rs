@@ -0,0 +1,411 @@
+//! The profile form (`a` adds, `m` edits the selected profile): one text value per field,
+//! checked field by field before anything reaches `ProfileStore`.
+
+use tdcore::doctor::ClientOverrides;
+use tdcore::profile::{
+    patch_client_overrides, DangerLevel, NewProfile, Profile, ProfileType, UpdateProfile,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormField {
+    Id,
+    Name,
+    Type,
+    Host,
+    Port,
+    User,
+    Group,
+    Tags,
+    Danger,
+    Note,
+    Clients,
+}
+
+impl FormField {
+    pub const ALL: [FormField; 11] = [
+        FormField::Id,
+        FormField::Name,
+        FormField::Type,
+        FormField::Host,
+        FormField::Port,
+        FormField::User,
+        FormField::Group,
+        FormField::Tags,
+        FormField::Danger,
+        FormField::Note,
+        FormField::Clients,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FormField::Id => "Profile ID",
+            FormField::Name => "Name",
+            FormField::Type => "Type",
+            FormField::Host => "Host",
+            FormField::Port => "Port",
+            FormField::User => "User",
+            FormField::Group => "Group",
+            FormField::Tags => "Tags",
+            FormField::Danger => "Danger",
+            FormField::Note => "Note",
+            FormField::Clients => "Clients",
+        }
+    }
+
+    /// Shown after an empty value.
+    pub fn hint(self) -> &'static str {
+        match self {
+            FormField::Id => "generated when empty",
+            FormField::Tags => "comma separated",
+            FormField::Clients => "ssh=/usr/bin/ssh, scp=/usr/bin/scp",
+            FormField::Group | FormField::Note => "optional",
+            _ => "required",
+        }
+    }
+
+    /// Type and danger cycle with Left/Right instead of taking typed text.
+    pub fn is_choice(self) -> bool {
+        matches!(self, FormField::Type | FormField::Danger)
+    }
+}
+
+const TYPES: [ProfileType; 3] = [ProfileType::Ssh, ProfileType::Telnet, ProfileType::Serial];
+const DANGER_LEVELS: [DangerLevel; 3] = [
+    DangerLevel::Normal,
+    DangerLevel::High,
+    DangerLevel::Critical,
+];
+
+#[derive(Debug, Clone)]
+pub struct ProfileForm {
+    /// The profile being edited; `None` while adding one.
+    pub editing: Option<String>,
+    values: Vec<String>,
+    pub cursor: usize,
+    /// Why the last save was refused, shown under the fields.
+    pub error: Option<String>,
+}
+
+impl ProfileForm {
+    pub fn new_profile() -> Self {
+        let mut form = Self {
+            editing: None,
+            values: vec![String::new(); FormField::ALL.len()],
+            cursor: 0,
+            error: None,
+        };
+        form.set(FormField::Type, ProfileType::Ssh.to_string());
+        form.set(FormField::Port, "22".to_string());
+        form.set(FormField::Danger, DangerLevel::Normal.to_string());
+        form
+    }
+
+    pub fn edit(profile: &Profile) -> Self {
+        let mut form = Self {
+            editing: Some(profile.profile_id.clone()),
+            values: vec![String::new(); FormField::ALL.len()],
+            cursor: 0,
+            error: None,
+        };
+        form.set(FormField::Id, profile.profile_id.clone());
+        form.set(FormField::Name, profile.name.clone());
+        form.set(FormField::Type, profile.profile_type.to_string());
+        form.set(FormField::Host, profile.host.clone());
+        form.set(FormField::Port, profile.port.to_string());
+        form.set(FormField::User, profile.user.clone());
+        form.set(FormField::Group, profile.group.clone().unwrap_or_default());
+        form.set(FormField::Tags, profile.tags.join(", "));
+        form.set(FormField::Danger, profile.danger_level.to_string());
+        form.set(FormField::Note, profile.note.clone().unwrap_or_default());
+        form.set(
+            FormField::Clients,
+            format_client_overrides(profile.client_overrides.as_ref()),
+        );
+        form
+    }
+
+    /// The fields in order; the id of an existing profile cannot change.
+    pub fn fields(&self) -> &'static [FormField] {
+        match self.editing {
+            Some(_) => &FormField::ALL[1..],
+            None => &FormField::ALL,
+        }
+    }
+
+    pub fn field(&self) -> FormField {
+        self.fields()[self.cursor]
+    }
+
+    pub fn value(&self, field: FormField) -> &str {
+        &self.values[field as usize]
+    }
+
+    fn set(&mut self, field: FormField, value: String) {
+        self.values[field as usize] = value;
+    }
+
+    pub fn move_cursor(&mut self, down: bool) {
+        let last = self.fields().len() - 1;
+        self.cursor = if down {
+            (self.cursor + 1).min(last)
+        } else {
+            self.cursor.saturating_sub(1)
+        };
+    }
+
+    pub fn push_char(&mut self, ch: char) {
+        let field = self.field();
+        if !field.is_choice() {
+            self.values[field as usize].push(ch);
+        }
+    }
+
+    pub fn pop_char(&mut self) {
+        let field = self.field();
+        if !field.is_choice() {
+            self.values[field as usize].pop();
+        }
+    }
+
+    /// Left/Right on the type or danger field.
+    pub fn cycle_choice(&mut self, forward: bool) {
+        let field = self.field();
+        let options: Vec<String> = match field {
+            FormField::Type => TYPES.iter().map(ToString::to_string).collect(),
+            FormField::Danger => DANGER_LEVELS.iter().map(ToString::to_string).collect(),
+            _ => return,
+        };
+        let current = options
+            .iter()
+            .position(|option| option == self.value(field))
+            .unwrap_or(0);
+        let next = if forward {
+            (current + 1) % options.len()
+        } else {
+            (current + options.len() - 1) % options.len()
+        };
+        self.set(field, options[next].clone());
+    }
+
+    /// The profile to insert, or the first field that is not valid.
+    pub fn to_new_profile(&self) -> Result<NewProfile, String> {
+        let fields = self.validate()?;
+        let id = self.value(FormField::Id).trim();
+        Ok(NewProfile {
+            profile_id: (!id.is_empty()).then(|| id.to_string()),
+            name: fields.name,
+            profile_type: fields.profile_type,
+            host: fields.host,
+            port: fields.port,
+            user: fields.user,
+            danger_level: fields.danger_level,
+            group: fields.group,
+            tags: fields.tags,
+            note: fields.note,
+            initial_send: None,
+            client_overrides: fields.client_overrides,
+        })
+    }
+
+    /// Every field of the form as an update; the initial send string is left alone.
+    pub fn to_update(&self) -> Result<UpdateProfile, String> {
+        let fields = self.validate()?;
+        Ok(UpdateProfile {
+            name: Some(fields.name),
+            profile_type: Some(fields.profile_type),
+            host: Some(fields.host),
+            port: Some(fields.port),
+            user: Some(fields.user),
+            danger_level: Some(fields.danger_level),
+            group: Some(fields.group),
+            tags: Some(fields.tags),
+            note: Some(fields.note),
+            initial_send: None,
+            client_overrides: Some(fields.client_overrides),
+        })
+    }
+
+    fn validate(&self) -> Result<ValidFields, String> {
+        let required = |field: FormField| {
+            let value = self.value(field).trim();
+            if value.is_empty() {
+                Err(format!("{} is required.", field.label()))
+            } else {
+                Ok(value.to_string())
+            }
+        };
+        let optional = |field: FormField| {
+            let value = self.value(field).trim();
+            (!value.is_empty()).then(|| value.to_string())
+        };
+        let name = required(FormField::Name)?;
+        let host = required(FormField::Host)?;
+        if host.chars().any(char::is_whitespace) {
+            return Err("Host must not contain spaces.".to_string());
+        }
+        let port = match self.value(FormField::Port).trim().parse::<u16>() {
+            Ok(port) if port > 0 => port,
+            _ => return Err("Port must be a number from 1 to 65535.".to_string()),
+        };
+        let user = required(FormField::User)?;
+        let profile_type = TYPES
+            .into_iter()
+            .find(|kind| kind.to_string() == self.value(FormField::Type))
+            .unwrap_or(ProfileType::Ssh);
+        let danger_level = DANGER_LEVELS
+            .into_iter()
+            .find(|level| level.to_string() == self.value(FormField::Danger))
+            .unwrap_or_default();
+        let tags = self
+            .value(FormField::Tags)
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect();
+        let client_overrides = parse_client_overrides(self.value(FormField::Clients))
+            .map_err(|err| format!("Clients: {err}"))?;
+        Ok(ValidFields {
+            name,
+            profile_type,
+            host,
+            port,
+            user,
+            danger_level,
+            group: optional(FormField::Group),
+            tags,
+            note: optional(FormField::Note),
+            client_overrides,
+        })
+    }
+}
+
+struct ValidFields {
+    name: String,
+    profile_type: ProfileType,
+    host: String,
+    port: u16,
+    user: String,
+    danger_level: DangerLevel,
+    group: Option<String>,
+    tags: Vec<String>,
+    note: Option<String>,
+    client_overrides: Option<ClientOverrides>,
+}
+
+fn format_client_overrides(overrides: Option<&ClientOverrides>) -> String {
+    let Some(overrides) = overrides else {
+        return String::new();
+    };
+    [
+        ("ssh", &overrides.ssh),
+        ("scp", &overrides.scp),
+        ("sftp", &overrides.sftp),
+        ("ftp", &overrides.ftp),
+        ("telnet", &overrides.telnet),
+    ]
+    .into_iter()
+    .filter_map(|(client, path)| path.as_ref().map(|path| format!("{client}={path}")))
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+/// `client=path` pairs separated by commas.
+fn parse_client_overrides(raw: &str) -> Result<Option<ClientOverrides>, String> {
+    let mut patch = serde_json::Map::new();
+    for pair in raw
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+    {
+        let (client, path) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("expected client=path, got '{pair}'"))?;
+        let path = path.trim();
+        if path.is_empty() {
+            return Err(format!("no path for {}", client.trim()));
+        }
+        patch.insert(
+            client.trim().to_ascii_lowercase(),
+            serde_json::Value::String(path.to_string()),
+        );
+    }
+    patch_client_overrides(None, &serde_json::Value::Object(patch)).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_text(form: &mut ProfileForm, field: FormField, text: &str) {
+        form.cursor = form.fields().iter().position(|f| *f == field).unwrap();
+        for ch in text.chars() {
+            form.push_char(ch);
+        }
+    }
+
+    #[test]
+    fn validates_fields_and_round_trips_an_existing_profile() {
+        let mut form = ProfileForm::new_profile();
+        assert_eq!(form.to_new_profile().unwrap_err(), "Name is required.");
+        type_text(&mut form, FormField::Name, "Billing DB");
+        type_text(&mut form, FormField::Host, "db01.example.com");
+        type_text(&mut form, FormField::User, "ops");
+        type_text(&mut form, FormField::Port, "x");
+        assert!(form.to_new_profile().unwrap_err().starts_with("Port"));
+        form.pop_char();
+        type_text(&mut form, FormField::Tags, "db, prod,");
+        type_text(&mut form, FormField::Clients, "ssh=/usr/bin/ssh, putty=/x");
+        assert!(form.to_new_profile().unwrap_err().starts_with("Clients:"));
+        for _ in 0..", putty=/x".len() {
+            form.pop_char();
+        }
+        form.cursor = form
+            .fields()
+            .iter()
+            .position(|f| *f == FormField::Danger)
+            .unwrap();
+        form.cycle_choice(false);
+        form.push_char('z');
+        assert_eq!(form.value(FormField::Danger), "critical");
+
+        let new = form.to_new_profile().unwrap();
+        assert_eq!(new.profile_id, None);
+        assert_eq!(new.port, 22);
+        assert_eq!(new.tags, ["db", "prod"]);
+        assert_eq!(new.danger_level, DangerLevel::Critical);
+        assert_eq!(
+            new.client_overrides.unwrap().ssh.as_deref(),
+            Some("/usr/bin/ssh")
+        );
+
+        let profile = Profile {
+            profile_id: "p_db".to_string(),
+            name: "Billing DB".to_string(),
+            profile_type: ProfileType::Telnet,
+            host: "db01".to_string(),
+            port: 23,
+            user: "ops".to_string(),
+            danger_level: DangerLevel::High,
+            group: Some("billing".to_string()),
+            tags: vec!["db".to_string()],
+            note: None,
+            initial_send: None,
+            client_overrides: Some(ClientOverrides {
+                telnet: Some("/usr/bin/telnet".to_string()),
+                ..ClientOverrides::default()
+            }),
+            created_at: 0,
+            updated_at: 0,
+            last_used_at: None,
+            archived_at: None,
+        };
+        let form = ProfileForm::edit(&profile);
+        assert_eq!(form.field(), FormField::Name, "the id is not editable");
+        assert_eq!(form.value(FormField::Clients), "telnet=/usr/bin/telnet");
+        let update = form.to_update().unwrap();
+        assert_eq!(update.profile_type, Some(ProfileType::Telnet));
+        assert_eq!(update.group, Some(Some("billing".to_string())));
+        assert_eq!(update.note, Some(None));
+    }
+}
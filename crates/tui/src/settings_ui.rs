@@ -18,9 +18,12 @@ use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragra
 use ratatui::{Frame, Terminal};
 use rusqlite::Connection;
 use tdcore::db;
+use tdcore::import_export::{self, ConflictStrategy, ExportDocument};
+use tdcore::run_report;
 use tdcore::session_log::{self, SessionLogBackendSetting, SessionLogConfig};
 use tdcore::settings::{self, SettingScope, SettingScopeKind};
 use tdcore::settings_registry::{self, SettingValueType};
+use tdcore::util;
 
 const SESSION_LOG_KEYS: [&str; 3] = [
     session_log::SESSION_LOG_ENABLED_KEY,
@@ -43,7 +46,7 @@ pub fn run() -> Result<SettingsUiOutcome> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let result = run_with_terminal_and_connection(&mut terminal, conn, None);
+    let result = run_with_terminal_and_connection(&mut terminal, conn, None, Vec::new());
 
     disable_raw_mode()?;
     execute!(
@@ -56,12 +59,15 @@ pub fn run() -> Result<SettingsUiOutcome> {
     result
 }
 
+/// `selection` is what `X` exports: the profiles marked in the main screen, or the
+/// selected one.
 pub(crate) fn run_in_terminal(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     profile_id: Option<String>,
+    selection: Vec<String>,
 ) -> Result<SettingsUiOutcome> {
     let conn = db::init_connection()?;
-    let outcome = run_with_terminal_and_connection(terminal, conn, profile_id)?;
+    let outcome = run_with_terminal_and_connection(terminal, conn, profile_id, selection)?;
     terminal.clear()?;
     Ok(outcome)
 }
@@ -70,8 +76,10 @@ fn run_with_terminal_and_connection(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     conn: Connection,
     profile_id: Option<String>,
+    selection: Vec<String>,
 ) -> Result<SettingsUiOutcome> {
     let mut state = SettingsUiState::new(conn, profile_id)?;
+    state.selection = selection;
     loop {
         terminal.draw(|frame| render(frame, &state))?;
         if event::poll(Duration::from_millis(250))? {
@@ -101,6 +109,7 @@ enum EffectiveSource {
     Default,
     Global,
     Env,
+    Group,
     Profile,
 }
 
@@ -110,12 +119,13 @@ impl EffectiveSource {
             Self::Default => "default",
             Self::Global => "global",
             Self::Env => "env",
+            Self::Group => "group",
             Self::Profile => "profile",
         }
     }
 
     fn overrides_global(self) -> bool {
-        matches!(self, Self::Env | Self::Profile)
+        matches!(self, Self::Env | Self::Group | Self::Profile)
     }
 }
 
@@ -157,6 +167,10 @@ impl SettingsItem {
 enum SettingsMode {
     Normal,
     Editing,
+    /// Typing the path of a `td export` file to import (`i`).
+    ImportPath,
+    /// Choosing how profiles that already exist are handled.
+    ImportConflict,
     ExitConfirm,
     Help,
 }
@@ -177,6 +191,8 @@ pub(crate) struct SettingsUiState {
     edit_buffer: String,
     status_message: String,
     saved: bool,
+    selection: Vec<String>,
+    pending_import: Option<ExportDocument>,
 }
 
 impl SettingsUiState {
@@ -195,6 +211,8 @@ impl SettingsUiState {
             edit_buffer: String::new(),
             status_message: "Ready.".to_string(),
             saved: false,
+            selection: Vec::new(),
+            pending_import: None,
         };
         state.reload()?;
         Ok(state)
@@ -204,6 +222,14 @@ impl SettingsUiState {
         match self.mode {
             SettingsMode::Normal => self.handle_normal_key(code),
             SettingsMode::Editing => self.handle_edit_key(code),
+            SettingsMode::ImportPath => {
+                self.handle_import_path_key(code);
+                Ok(SettingsAction::Continue)
+            }
+            SettingsMode::ImportConflict => {
+                self.handle_import_conflict_key(code);
+                Ok(SettingsAction::Continue)
+            }
             SettingsMode::ExitConfirm => Ok(self.handle_exit_confirm_key(code)),
             SettingsMode::Help => {
                 if matches!(code, KeyCode::Esc | KeyCode::Char('?') | KeyCode::Char('q')) {
@@ -231,6 +257,12 @@ impl SettingsUiState {
                 self.refresh_diagnostics()?;
                 self.status_message = "Diagnostics refreshed.".to_string();
             }
+            KeyCode::Char('x') => self.export_profiles(false),
+            KeyCode::Char('X') => self.export_profiles(true),
+            KeyCode::Char('i') => {
+                self.edit_buffer.clear();
+                self.mode = SettingsMode::ImportPath;
+            }
             KeyCode::Char('?') => self.mode = SettingsMode::Help,
             KeyCode::Esc | KeyCode::Char('q') => {
                 if self.dirty() {
@@ -272,6 +304,101 @@ impl SettingsUiState {
         Ok(SettingsAction::Continue)
     }
 
+    /// `x` writes the same document as `td export` (secret values left out); `X` writes only
+    /// the selected profiles. Files go under `reports.dir`.
+    fn export_profiles(&mut self, selected_only: bool) {
+        if selected_only && self.selection.is_empty() {
+            self.status_message =
+                "No profile selected; press x to export all profiles.".to_string();
+            return;
+        }
+        let stem = format!(
+            "profiles-{}-{}",
+            if selected_only { "selected" } else { "all" },
+            run_report::file_stamp(util::now_ms())
+        );
+        let written = if selected_only {
+            import_export::export_profiles(&self.conn, &self.selection)
+        } else {
+            import_export::export_document(&self.conn, false, None)
+        }
+        .and_then(|document| {
+            let body = serde_json::to_string_pretty(&document)? + "\n";
+            let dir = settings::get_reports_dir(&self.conn)?;
+            Ok((
+                document.profiles.len(),
+                run_report::write_new_file(&dir, &stem, "json", &body)?,
+            ))
+        });
+        self.status_message = match written {
+            Ok((count, path)) => format!("Exported {count} profiles to {}", path.display()),
+            Err(err) => format!("Export failed: {err}"),
+        };
+    }
+
+    fn handle_import_path_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Esc => {
+                self.mode = SettingsMode::Normal;
+                self.status_message = "Import cancelled.".to_string();
+            }
+            KeyCode::Enter => {
+                let path = PathBuf::from(self.edit_buffer.trim());
+                self.edit_buffer.clear();
+                self.mode = SettingsMode::Normal;
+                self.status_message = match read_export_file(&path) {
+                    Ok(document) if document.secrets.iter().any(|s| s.value.is_some()) => {
+                        "The file contains secret values; import it with `td import`, which asks for the master password.".to_string()
+                    }
+                    Ok(document) => {
+                        let message = format!(
+                            "{} profiles, {} command sets in {}. Existing ids: r rename names (ids still conflict), s skip, o overwrite profiles.",
+                            document.profiles.len(),
+                            document.cmdsets.len(),
+                            path.display()
+                        );
+                        self.pending_import = Some(document);
+                        self.mode = SettingsMode::ImportConflict;
+                        message
+                    }
+                    Err(err) => format!("Import failed: {err}"),
+                };
+            }
+            KeyCode::Backspace => {
+                self.edit_buffer.pop();
+            }
+            KeyCode::Char(ch) => self.edit_buffer.push(ch),
+            _ => {}
+        }
+    }
+
+    fn handle_import_conflict_key(&mut self, code: KeyCode) {
+        let strategy = match code {
+            KeyCode::Char('r') => ConflictStrategy::Rename,
+            KeyCode::Char('s') => ConflictStrategy::Skip,
+            KeyCode::Char('o') => ConflictStrategy::Overwrite,
+            KeyCode::Esc => {
+                self.pending_import = None;
+                self.mode = SettingsMode::Normal;
+                self.status_message = "Import cancelled.".to_string();
+                return;
+            }
+            _ => return,
+        };
+        self.mode = SettingsMode::Normal;
+        let Some(document) = self.pending_import.take() else {
+            return;
+        };
+        self.status_message =
+            match import_export::import_document(&mut self.conn, document, strategy, None) {
+                Ok(report) => format!(
+                    "Imported {} profiles, {} command sets; skipped {}, overwrote {}.",
+                    report.profiles, report.cmdsets, report.skipped, report.overwritten
+                ),
+                Err(err) => format!("Import failed: {err}"),
+            };
+    }
+
     fn handle_exit_confirm_key(&mut self, code: KeyCode) -> SettingsAction {
         match code {
             KeyCode::Char('y') | KeyCode::Char('Y') => SettingsAction::Exit,
@@ -457,6 +584,11 @@ impl SettingsUiState {
     }
 }
 
+fn read_export_file(path: &std::path::Path) -> Result<ExportDocument> {
+    let json = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
 fn load_items(conn: &Connection, profile_id: Option<&str>) -> Result<Vec<SettingsItem>> {
     let mut items = Vec::new();
     for key in SESSION_LOG_KEYS {
@@ -506,6 +638,14 @@ fn resolve_effective_value(
                 return Ok((value, EffectiveSource::Profile));
             }
         }
+        if settings_registry::scope_supported(key, SettingScopeKind::Group)? {
+            if let Some(group) = settings::get_profile_group(conn, profile_id)? {
+                let group_scope = SettingScope::Group(group);
+                if let Some(value) = settings::get_setting_scoped(conn, &group_scope, key)? {
+                    return Ok((value, EffectiveSource::Group));
+                }
+            }
+        }
         if settings_registry::scope_supported(key, SettingScopeKind::Env)? {
             if let Some(env_name) = settings::get_current_env(conn)? {
                 let env_scope = SettingScope::Env(env_name);
@@ -552,6 +692,8 @@ fn render(frame: &mut Frame<'_>, state: &SettingsUiState) {
 
     match state.mode {
         SettingsMode::Editing => render_edit_popup(frame, state),
+        SettingsMode::ImportPath => render_import_popup(frame, state),
+        SettingsMode::ImportConflict => render_import_conflict(frame, state),
         SettingsMode::ExitConfirm => render_exit_confirm(frame),
         SettingsMode::Help => render_help_popup(frame),
         SettingsMode::Normal => {}
@@ -789,7 +931,7 @@ fn diag_line(label: &str, value: &str) -> Line<'static> {
 fn footer_lines(state: &SettingsUiState) -> Text<'static> {
     let dirty = if state.dirty() { "dirty" } else { "clean" };
     Text::from(vec![
-        Line::from("Up/Down move | Left/Right change | Space toggle | Enter edit | s save | r reload | d diagnostics | ? help | q/Esc exit"),
+        Line::from("Up/Down move | Left/Right change | Space toggle | Enter edit | s save | r reload | d diagnostics | x/X export | i import | ? help | q/Esc exit"),
         Line::from(vec![
             Span::styled(format!("State: {dirty}"), Style::default().fg(Color::Yellow)),
             Span::raw("  "),
@@ -817,6 +959,41 @@ fn render_edit_popup(frame: &mut Frame<'_>, state: &SettingsUiState) {
     frame.render_widget(paragraph, area);
 }
 
+fn render_import_popup(frame: &mut Frame<'_>, state: &SettingsUiState) {
+    let area = centered_rect(72, 28, frame.size());
+    frame.render_widget(Clear, area);
+    let text = Text::from(vec![
+        Line::from("Path of a file written by td export or x/X:"),
+        Line::from(""),
+        Line::from(state.edit_buffer.clone()),
+        Line::from(""),
+        Line::from("Enter reads the file; Esc cancels."),
+    ]);
+    let paragraph = Paragraph::new(text)
+        .block(Block::default().borders(Borders::ALL).title("Import"))
+        .alignment(Alignment::Left)
+        .wrap(Wrap { trim: false });
+    frame.render_widget(paragraph, area);
+}
+
+fn render_import_conflict(frame: &mut Frame<'_>, state: &SettingsUiState) {
+    let area = centered_rect(72, 32, frame.size());
+    frame.render_widget(Clear, area);
+    let text = Text::from(vec![
+        Line::from(state.status_message.clone()),
+        Line::from(""),
+        Line::from("  r  rename colliding names (fails if an id already exists)"),
+        Line::from("  s  skip items whose id already exists"),
+        Line::from("  o  overwrite profiles whose id already exists"),
+        Line::from(""),
+        Line::from("Esc cancels."),
+    ]);
+    let paragraph = Paragraph::new(text)
+        .block(Block::default().borders(Borders::ALL).title("Import"))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(paragraph, area);
+}
+
 fn render_exit_confirm(frame: &mut Frame<'_>) {
     let area = centered_rect(64, 24, frame.size());
     frame.render_widget(Clear, area);
@@ -850,6 +1027,9 @@ fn render_help_popup(frame: &mut Frame<'_>) {
         Line::from("  s           save global settings"),
         Line::from("  r           reload and discard changes"),
         Line::from("  d           refresh diagnostics"),
+        Line::from("  x           export everything except secret values (like td export)"),
+        Line::from("  X           export the selected or marked profiles"),
+        Line::from("  i           import a td export file"),
         Line::from("  q/Esc       exit"),
         Line::from(""),
         Line::from("Only global scope is saved here. The source column shows when a profile or env override is currently winning."),
@@ -949,6 +1129,72 @@ mod tests {
         assert!(!state.current_item().dirty());
     }
 
+    #[test]
+    fn exports_the_selection_and_imports_with_a_conflict_choice() {
+        use tdcore::profile::{DangerLevel, NewProfile, ProfileStore, ProfileType};
+
+        let dir = std::env::temp_dir().join(format!("td-settings-export-{}", std::process::id()));
+        let source = ProfileStore::new(db::init_in_memory().unwrap());
+        source
+            .insert(NewProfile {
+                profile_id: Some("p_web".to_string()),
+                name: "web".to_string(),
+                profile_type: ProfileType::Ssh,
+                host: "web.example.com".to_string(),
+                port: 22,
+                user: "ops".to_string(),
+                danger_level: DangerLevel::Normal,
+                group: None,
+                tags: Vec::new(),
+                note: None,
+                initial_send: None,
+                client_overrides: None,
+            })
+            .unwrap();
+        let document =
+            import_export::export_profiles(source.conn(), &["p_web".to_string()]).unwrap();
+        let mut state = SettingsUiState::new(db::init_in_memory().unwrap(), None).unwrap();
+        import_export::import_document(&mut state.conn, document, ConflictStrategy::Reject, None)
+            .unwrap();
+        settings::set_setting(
+            state.conn(),
+            settings::REPORTS_DIR_KEY,
+            &dir.to_string_lossy(),
+        )
+        .unwrap();
+
+        state.handle_key(KeyCode::Char('X')).unwrap();
+        assert!(state.status_message.starts_with("No profile selected"));
+        state.selection = vec!["p_web".to_string()];
+        state.handle_key(KeyCode::Char('X')).unwrap();
+        let path = state
+            .status_message
+            .strip_prefix("Exported 1 profiles to ")
+            .map(PathBuf::from)
+            .expect("export status");
+
+        let mut import = |choice: char| {
+            state.handle_key(KeyCode::Char('i')).unwrap();
+            for ch in path.to_string_lossy().chars() {
+                state.handle_key(KeyCode::Char(ch)).unwrap();
+            }
+            state.handle_key(KeyCode::Enter).unwrap();
+            assert_eq!(state.mode, SettingsMode::ImportConflict);
+            state.handle_key(KeyCode::Char(choice)).unwrap();
+            state.status_message.clone()
+        };
+        assert_eq!(
+            import('s'),
+            "Imported 0 profiles, 0 command sets; skipped 1, overwrote 0."
+        );
+        assert_eq!(
+            import('o'),
+            "Imported 0 profiles, 0 command sets; skipped 0, overwrote 1."
+        );
+        assert!(import('r').starts_with("Import failed"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
     #[test]
     fn settings_ui_ignores_key_release_events() {
         let key = KeyEvent::new_with_kind(KeyCode::Down, KeyModifiers::NONE, KeyEventKind::Release);
@@ -0,0 +1,58 @@
+//! Wall-clock spans for `td ui --profile-startup`, printed to stderr after the TUI exits.
+
+use std::time::{Duration, Instant};
+
+pub struct StartupTimer {
+    enabled: bool,
+    started: Instant,
+    spans: Vec<(&'static str, Duration)>,
+    finished: bool,
+}
+
+impl StartupTimer {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            started: Instant::now(),
+            spans: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Runs `step`, recording how long it took until `finish` is called.
+    pub fn time<T>(&mut self, label: &'static str, step: impl FnOnce() -> T) -> T {
+        if !self.enabled || self.finished {
+            return step();
+        }
+        let started = Instant::now();
+        let value = step();
+        self.spans.push((label, started.elapsed()));
+        value
+    }
+
+    /// Stops recording; called once the first frame is on screen.
+    pub fn finish(&mut self) {
+        if self.enabled && !self.finished {
+            self.finished = true;
+            self.spans.push(("total", self.started.elapsed()));
+        }
+    }
+
+    pub fn report(&self) -> Vec<String> {
+        self.spans
+            .iter()
+            .map(|(label, elapsed)| {
+                format!("{label:<20} {:>9.1} ms", elapsed.as_secs_f64() * 1000.0)
+            })
+            .collect()
+    }
+
+    pub fn print(&self) {
+        if self.enabled {
+            eprintln!("startup profile:");
+            for line in self.report() {
+                eprintln!("  {line}");
+            }
+        }
+    }
+}
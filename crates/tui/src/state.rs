@@ -1,22 +1,106 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::ffi::OsString;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Result};
-
-use tdcore::cmdset::{CmdSet, CmdSetStore};
-use tdcore::cmdset_runner::{run_cmdset_ssh, CmdSetRunRequest, CmdSetRunResult};
-use tdcore::doctor::ClientKind;
-use tdcore::oplog::{self, OpLogEntry};
-use tdcore::profile::{DangerLevel, Profile, ProfileFilters, ProfileStore, ProfileType};
+use zeroize::Zeroizing;
+
+use tdcore::active_session::{self, ActiveSession};
+use tdcore::clipboard::{Clipboard, ClipboardExpiry};
+use tdcore::cmdset::{CmdSet, CmdSetStore, NewCmdSet};
+use tdcore::cmdset_runner::{daemon_secret_env, run_cmdset_ssh, CmdSetRunRequest, CmdSetRunResult};
+use tdcore::daemon::DaemonClient;
+use tdcore::db;
+use tdcore::doctor::{ClientKind, ClientOverrides};
+use tdcore::forward_preset::{self, ForwardPreset};
+use tdcore::hooks::{self, HookContext, HookPoint};
+use tdcore::maintenance::{self, WindowMode};
+use tdcore::notify::{Notification, Notifier};
+use tdcore::oplog::{self, OpLogEntry, OpLogQuery, OpLogRecord, ProfileUsage};
+use tdcore::policy::{self, PolicyAction, PolicyVerdict};
+use tdcore::profile::{
+    BulkEdit, BulkEditField, BulkEditPreview, DangerLevel, Profile, ProfileFilters, ProfileStore,
+    ProfileType,
+};
+use tdcore::run_output::{self, StoredOutput};
+use tdcore::run_report::{self, ProfileRun, ReportFormat, RunReport};
+use tdcore::schedule;
+use tdcore::secret::SecretStore;
 use tdcore::session_log::{self, SessionLogPlan, SessionLogReference};
-use tdcore::settings::{self, ResolvedSettingDetail, ResolvedSettingSource};
+use tdcore::settings::{
+    self, PaneLayout, ProfileGrouping, ProfileListLayout, ResolvedSettingDetail,
+    ResolvedSettingSource, ResultTabLayout, ResultTabLayouts, ResultTabName, PANE_SPLIT_MAX,
+    PANE_SPLIT_MIN,
+};
 use tdcore::ssh::{self, SshBuildError, SshInvocationMode, SshInvocationRequest};
+use tdcore::tester;
+use tdcore::transfer::{self, TransferDirection, TransferOutcome};
+use tdcore::tunnel::{self, Session as TunnelSession};
+use tdcore::tunnel_manager::TunnelManager;
+use tdcore::util;
+
+use crate::cmdset_editor::CmdSetEditor;
+use crate::health::{HealthMonitor, HealthStatus};
+use crate::live_run::{LiveRun, RunClients, StepRow, StepStatus};
+use crate::profile_form::ProfileForm;
+use crate::startup::StartupTimer;
+use crate::transfer_form::{TransferForm, TransferRequest};
+
+const TUNNEL_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// Lines moved by PageUp/PageDown in the Results pane.
+const RESULT_PAGE_LINES: usize = 10;
+/// Bulk edits `Ctrl+Z` can undo.
+const BULK_UNDO_LIMIT: usize = 20;
+/// Op log entries listed in the history overlay.
+const HISTORY_LIMIT: usize = 50;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InputMode {
     Normal,
     Search,
+    /// Typing a query for the Results pane (`/` while it is focused).
+    ResultSearch,
+}
+
+/// A row of the profile list: a group or tag header, or an index into the filtered profiles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfileRow {
+    Header {
+        /// `group:<name>` or `tag:<name>`, as stored in `tui.profile_list` `collapsed`.
+        key: String,
+        label: String,
+        count: usize,
+        collapsed: bool,
+    },
+    Profile(usize),
+}
+
+/// Order of the profile list when no search query is active (a query keeps its ranking).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProfileSort {
+    #[default]
+    Name,
+    LastUsed,
+    MostUsed,
+}
+
+impl ProfileSort {
+    pub fn label(self) -> &'static str {
+        match self {
+            ProfileSort::Name => "name",
+            ProfileSort::LastUsed => "last used",
+            ProfileSort::MostUsed => "most used",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            ProfileSort::Name => ProfileSort::LastUsed,
+            ProfileSort::LastUsed => ProfileSort::MostUsed,
+            ProfileSort::MostUsed => ProfileSort::Name,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -34,6 +118,40 @@ pub enum ResultTab {
     Summary,
 }
 
+impl From<ResultTabName> for ResultTab {
+    fn from(name: ResultTabName) -> Self {
+        match name {
+            ResultTabName::Stdout => ResultTab::Stdout,
+            ResultTabName::Stderr => ResultTab::Stderr,
+            ResultTabName::Parsed => ResultTab::Parsed,
+            ResultTabName::Summary => ResultTab::Summary,
+        }
+    }
+}
+
+impl From<ResultTab> for ResultTabName {
+    fn from(tab: ResultTab) -> Self {
+        match tab {
+            ResultTab::Stdout => ResultTabName::Stdout,
+            ResultTab::Stderr => ResultTabName::Stderr,
+            ResultTab::Parsed => ResultTabName::Parsed,
+            ResultTab::Summary => ResultTabName::Summary,
+        }
+    }
+}
+
+/// A pane border that `<`/`>`, `{`/`}`, or a mouse drag moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaneSplit {
+    /// Between the profile list and the right column.
+    Profiles,
+    /// Between the Action pane and the results.
+    Actions,
+}
+
+/// Percent a pane border moves per key press.
+const PANE_SPLIT_STEP: u16 = 5;
+
 #[derive(Debug, Clone)]
 pub struct RunSummaryItem {
     pub profile_id: String,
@@ -60,6 +178,26 @@ pub struct RunResult {
     pub stderr: String,
     pub parsed_pretty: String,
     pub error: Option<String>,
+    pub steps: Vec<StepOutput>,
+}
+
+/// Lines of the current result tab after `max_lines`; `hidden` counts the lines cut off.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResultBuffer {
+    pub lines: Vec<String>,
+    pub hidden: usize,
+}
+
+/// Output of one step with its start offset from the beginning of the run.
+#[derive(Debug, Clone)]
+pub struct StepOutput {
+    pub ord: i64,
+    pub cmd: String,
+    pub status: StepStatus,
+    pub duration_ms: Option<i64>,
+    pub offset_ms: i64,
+    pub stdout: String,
+    pub stderr: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -85,6 +223,7 @@ impl RunResult {
             stderr: String::new(),
             parsed_pretty: "{}".to_string(),
             error: Some(err.to_string()),
+            steps: Vec::new(),
         }
     }
 
@@ -108,6 +247,28 @@ impl RunResult {
         let parsed_json = serde_json::json!({ "steps": steps_json });
         let parsed_pretty =
             serde_json::to_string_pretty(&parsed_json).unwrap_or_else(|_| "{}".into());
+        let mut offset_ms = 0;
+        let steps = run
+            .steps
+            .iter()
+            .map(|step| {
+                let output = StepOutput {
+                    ord: step.ord,
+                    cmd: step.cmd.clone(),
+                    status: if step.ok {
+                        StepStatus::Ok
+                    } else {
+                        StepStatus::Failed
+                    },
+                    duration_ms: Some(step.duration_ms),
+                    offset_ms,
+                    stdout: step.stdout.clone(),
+                    stderr: step.stderr.clone(),
+                };
+                offset_ms += step.duration_ms;
+                output
+            })
+            .collect();
         Self {
             ok: run.ok,
             exit_code: run.exit_code,
@@ -116,6 +277,54 @@ impl RunResult {
             stderr: run.stderr,
             parsed_pretty,
             error: None,
+            steps,
+        }
+    }
+
+    /// A past run or exec rebuilt from its op log entry and the outputs stored with it.
+    fn from_op_log(record: &OpLogRecord, outputs: Vec<StoredOutput>) -> Self {
+        let steps_json = outputs
+            .iter()
+            .map(|stored| {
+                serde_json::json!({
+                    "ord": stored.ord,
+                    "cmd": stored.cmd,
+                    "exit_code": stored.exit_code,
+                    "stdout": stored.stdout,
+                    "stderr": stored.stderr,
+                })
+            })
+            .collect::<Vec<_>>();
+        let parsed_json = serde_json::json!({ "op_log": record, "steps": steps_json });
+        let parsed_pretty =
+            serde_json::to_string_pretty(&parsed_json).unwrap_or_else(|_| "{}".into());
+        let first_ts = outputs.first().map(|stored| stored.ts).unwrap_or_default();
+        let steps = outputs
+            .into_iter()
+            .zip(1..)
+            .map(|(stored, position)| StepOutput {
+                ord: stored.ord.unwrap_or(position),
+                cmd: stored.cmd,
+                status: if stored.exit_code.map_or(record.ok, |code| code == 0) {
+                    StepStatus::Ok
+                } else {
+                    StepStatus::Failed
+                },
+                duration_ms: None,
+                offset_ms: stored.ts - first_ts,
+                stdout: stored.stdout,
+                stderr: stored.stderr,
+            })
+            .collect::<Vec<_>>();
+        Self {
+            ok: record.ok,
+            exit_code: record.exit_code.unwrap_or(if record.ok { 0 } else { 1 }),
+            duration_ms: record.duration_ms.unwrap_or_default(),
+            stdout: steps.iter().map(|step| step.stdout.as_str()).collect(),
+            stderr: steps.iter().map(|step| step.stderr.as_str()).collect(),
+            parsed_pretty,
+            error: None,
+            steps,
         }
     }
 }
@@ -133,6 +342,53 @@ pub enum PendingAction {
     OpenSshSession {
         profile_id: String,
     },
+    /// Launch although the `connect.preflight` probe found the host unreachable.
+    OpenUnreachableSshSession {
+        profile_id: String,
+    },
+    /// Go ahead inside a `reason` maintenance window; the input is the logged reason.
+    OverrideMaintenance {
+        profile_id: String,
+        window_id: i64,
+        next: OverrideNext,
+    },
+    /// End a running interactive SSH session (`K`).
+    TerminateSession {
+        session_id: i64,
+    },
+    /// Start the submitted push or pull (`U`).
+    RunTransfer {
+        profile_id: String,
+    },
+}
+
+/// What goes ahead once a maintenance override reason is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverrideNext {
+    Run,
+    Connect,
+    Transfer(TransferDirection),
+}
+
+impl OverrideNext {
+    fn op(self) -> &'static str {
+        match self {
+            OverrideNext::Run => "run",
+            OverrideNext::Connect => "connect",
+            OverrideNext::Transfer(direction) => direction.as_str(),
+        }
+    }
+}
+
+/// A push or pull that passed its checks, run by the app with the TUI suspended so the
+/// client can draw its progress on the terminal.
+#[derive(Debug, Clone)]
+pub struct TransferCommand {
+    pub profile: Profile,
+    pub request: TransferRequest,
+    pub client: PathBuf,
+    pub auth_args: Vec<OsString>,
+    pub auth_hint: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -141,12 +397,251 @@ pub struct ConfirmState {
     pub required_input: String,
     pub input: String,
     pub action: PendingAction,
+    /// Every target of a bulk run, listed under the message.
+    pub blast_radius: Option<BlastRadius>,
+}
+
+impl ConfirmState {
+    /// Whether any non-empty input is accepted, as a reason, instead of `required_input`.
+    pub fn wants_reason(&self) -> bool {
+        matches!(self.action, PendingAction::OverrideMaintenance { .. })
+    }
+
+    /// Whether typing is accepted: a bulk run's target list must be scrolled to the end
+    /// first.
+    pub fn input_ready(&self) -> bool {
+        self.blast_radius.as_ref().is_none_or(BlastRadius::reviewed)
+    }
+}
+
+/// Target rows a bulk run confirmation shows at a time.
+pub const BLAST_RADIUS_ROWS: usize = 8;
+/// Commands of the CommandSet a bulk run confirmation lists.
+pub const BLAST_RADIUS_COMMANDS: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct BulkTarget {
+    pub profile_id: String,
+    pub name: String,
+    pub host: String,
+    pub group: Option<String>,
+    pub danger_level: DangerLevel,
+    /// Whether this profile is why the run asks for confirmation.
+    pub needs_confirm: bool,
+}
+
+/// What a bulk run would touch: every marked profile and the first commands it sends.
+#[derive(Debug, Clone)]
+pub struct BlastRadius {
+    pub targets: Vec<BulkTarget>,
+    /// The first `BLAST_RADIUS_COMMANDS` commands, with secrets masked.
+    pub commands: Vec<String>,
+    /// Commands left out of `commands`.
+    pub more_commands: usize,
+    /// First target row shown.
+    pub scroll: usize,
+}
+
+impl BlastRadius {
+    pub fn reviewed(&self) -> bool {
+        self.scroll + BLAST_RADIUS_ROWS >= self.targets.len()
+    }
+
+    fn scroll(&mut self, down: bool, page: bool) {
+        let step = if page { BLAST_RADIUS_ROWS } else { 1 };
+        let last = self.targets.len().saturating_sub(BLAST_RADIUS_ROWS);
+        self.scroll = if down {
+            (self.scroll + step).min(last)
+        } else {
+            self.scroll.saturating_sub(step)
+        };
+    }
+}
+
+/// Bulk edit of the marked profiles: pick a field, type a value, review the preview,
+/// then apply everything in one transaction.
+#[derive(Debug, Clone)]
+pub enum BulkEditStage {
+    ChooseField {
+        cursor: usize,
+    },
+    Input {
+        field: BulkEditField,
+        input: String,
+        error: Option<String>,
+    },
+    Preview {
+        field: BulkEditField,
+        input: String,
+        edit: BulkEdit,
+        rows: Vec<BulkEditPreview>,
+    },
+}
+
+/// Actions in the `Ctrl+K` palette with the key each stands for; choosing one acts as
+/// if the key had been pressed (`\t` is Tab).
+pub const PALETTE_ENTRIES: &[(char, &str)] = &[
+    ('s', "Connect: open an SSH session to the selected profile"),
+    ('r', "Run the selected CommandSet on the selected profile"),
+    ('R', "Run the selected CommandSet on marked profiles"),
+    ('L', "History of the selected profile"),
+    ('P', "Copy the profile's login secret to the clipboard"),
+    ('K', "End the selected profile's running SSH session"),
+    ('E', "Bulk edit marked profiles"),
+    ('a', "Add a profile"),
+    ('m', "Edit the selected profile"),
+    ('b', "Edit the selected CommandSet's steps"),
+    ('N', "New CommandSet"),
+    ('p', "Apply a forwarding preset to marked profiles"),
+    ('U', "Transfer a file: push or pull with scp or sftp"),
+    (' ', "Mark or unmark the selected profile"),
+    ('/', "Search profiles"),
+    ('C', "Clear filters"),
+    ('g', "Filter by next group"),
+    ('T', "Filter by next profile type"),
+    ('D', "Filter by next danger level"),
+    ('A', "Show or hide archived profiles"),
+    ('o', "Sort profiles by next order"),
+    ('G', "Group profiles by group, tag, or nothing"),
+    ('W', "Toggle the wide profile list"),
+    ('<', "Narrow the profile list"),
+    ('>', "Widen the profile list"),
+    ('{', "Shrink the Action pane"),
+    ('}', "Grow the Action pane"),
+    ('=', "Reset pane sizes"),
+    ('H', "Show or hide the health column"),
+    ('u', "Refresh health checks"),
+    ('F', "Toggle tunnel auto-restart"),
+    ('d', "Show or hide profile details"),
+    ('\t', "Switch pane"),
+    ('1', "Results: stdout tab"),
+    ('2', "Results: stderr tab"),
+    ('3', "Results: parsed tab"),
+    ('4', "Results: summary tab"),
+    (',', "Results: previous step's output"),
+    ('.', "Results: next step's output"),
+    ('y', "Copy the current result tab"),
+    ('e', "Export the last run report"),
+    ('c', "Open settings"),
+    ('?', "Help"),
+    ('q', "Quit"),
+];
+
+/// The `Ctrl+K` command palette: typed words filter `PALETTE_ENTRIES`.
+#[derive(Debug, Clone, Default)]
+pub struct PaletteState {
+    pub input: String,
+    pub cursor: usize,
+}
+
+impl PaletteState {
+    /// Entries whose label contains every typed word, ignoring case.
+    pub fn matches(&self) -> Vec<(char, &'static str)> {
+        let input = self.input.to_lowercase();
+        PALETTE_ENTRIES
+            .iter()
+            .copied()
+            .filter(|(_, label)| {
+                let label = label.to_lowercase();
+                input.split_whitespace().all(|word| label.contains(word))
+            })
+            .collect()
+    }
+}
+
+/// The preset picker (`p`): adds a forwarding preset's forwards to the target profiles.
+#[derive(Debug, Clone)]
+pub struct PresetPickerState {
+    pub presets: Vec<ForwardPreset>,
+    pub cursor: usize,
+    /// The marked profiles, or the selected one when none are marked.
+    pub profile_ids: Vec<String>,
+}
+
+/// The history overlay (`L`): the selected profile's recent op log entries.
+#[derive(Debug, Clone)]
+pub struct HistoryState {
+    pub profile_id: String,
+    pub entries: Vec<HistoryEntry>,
+    pub cursor: usize,
+    /// `f`: all entries, failures only, or successes only.
+    pub result: Option<bool>,
+    /// `t`: how far back entries go.
+    pub period: HistoryPeriod,
+    /// No older entries match; paging stops.
+    pub exhausted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryPeriod {
+    All,
+    Day,
+    Week,
+    Month,
+}
+
+impl HistoryPeriod {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::All => "all time",
+            Self::Day => "last 24h",
+            Self::Week => "last 7 days",
+            Self::Month => "last 30 days",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Self::All => Self::Day,
+            Self::Day => Self::Week,
+            Self::Week => Self::Month,
+            Self::Month => Self::All,
+        }
+    }
+
+    fn since_ms(self, now_ms: i64) -> Option<i64> {
+        const DAY_MS: i64 = 24 * 3_600_000;
+        match self {
+            Self::All => None,
+            Self::Day => Some(now_ms - DAY_MS),
+            Self::Week => Some(now_ms - 7 * DAY_MS),
+            Self::Month => Some(now_ms - 30 * DAY_MS),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub record: OpLogRecord,
+    /// `ts` in the display timezone.
+    pub when: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct BulkEditState {
+    pub profile_ids: Vec<String>,
+    pub stage: BulkEditStage,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConfirmedAction {
     Continue,
     OpenSshSession,
+    RunTransfer,
+}
+
+/// Set when browsing an exported document with `td view`; the database is in-memory and
+/// runs stay disabled unless `--allow-run` was given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotView {
+    pub allow_run: bool,
+}
+
+/// Input collected while the idle lock screen is shown.
+#[derive(Debug, Clone, Default)]
+pub struct LockPrompt {
+    pub input: Zeroizing<String>,
+    pub error: Option<String>,
 }
 
 pub struct AppState {
@@ -154,18 +649,35 @@ pub struct AppState {
     cmdset_store: CmdSetStore,
     filters: ProfileFilters,
     filtered: Vec<Profile>,
+    profile_sort: ProfileSort,
     groups: Vec<String>,
     tags: Vec<String>,
     tag_cursor: usize,
     mode: InputMode,
     search_input: String,
     profile_cursor: usize,
-    cmdsets: Vec<CmdSet>,
+    /// Loaded when the Actions pane or a run first needs them.
+    cmdsets: Option<Vec<CmdSet>>,
     cmdset_cursor: usize,
+    /// Next scheduled run per CommandSet, formatted for the Actions pane.
+    scheduled_runs: HashMap<String, String>,
+    /// When the earliest of `scheduled_runs` passes and the times need reloading.
+    schedules_stale_at: Option<i64>,
     active_pane: ActivePane,
     result_tab: ResultTab,
     confirm: Option<ConfirmState>,
     last_result: Option<RunResult>,
+    /// First line of the result buffer shown in the Results pane.
+    result_scroll: usize,
+    /// The step whose output the stdout and stderr tabs show; `None` shows every step.
+    result_step: Option<usize>,
+    /// Highlighted in the Results pane; `n` jumps to the next line containing it.
+    result_query: Option<String>,
+    result_search_input: String,
+    /// The last single or bulk run, kept for `e` (export report).
+    last_report: Option<RunReport>,
+    export_prompt: bool,
+    live_run: Option<LiveRun>,
     last_summary: Option<RunSummary>,
     marked_profiles: BTreeSet<String>,
     details_open: bool,
@@ -174,33 +686,97 @@ pub struct AppState {
     help_open: bool,
     status_message: Option<String>,
     confirmed_ssh_session_profile_id: Option<String>,
+    preflight_waived_profile_id: Option<String>,
+    maintenance_override_profile_id: Option<String>,
+    confirmed_transfer_profile_id: Option<String>,
+    health: Option<HealthMonitor>,
+    /// Read when a result is first shown or a layout toggled.
+    result_layouts: Option<ResultTabLayouts>,
+    profile_list: ProfileListLayout,
+    pane_layout: PaneLayout,
+    /// The border being dragged with the mouse.
+    dragging: Option<PaneSplit>,
+    idle_lock_after: Option<Duration>,
+    last_input_at: Instant,
+    lock: Option<LockPrompt>,
+    snapshot: Option<SnapshotView>,
+    observer: bool,
+    bulk_edit: Option<BulkEditState>,
+    history: Option<HistoryState>,
+    palette: Option<PaletteState>,
+    preset_picker: Option<PresetPickerState>,
+    profile_form: Option<ProfileForm>,
+    cmdset_editor: Option<CmdSetEditor>,
+    transfer_form: Option<TransferForm>,
+    /// Submitted from the transfer form, waiting for its checks and confirmation.
+    transfer_request: Option<TransferRequest>,
+    /// Forwarding presets each profile's forwards match, for the list badge.
+    preset_matches: BTreeMap<String, Vec<String>>,
+    /// Profiles as they were before each bulk edit this session, newest last (`Ctrl+Z`).
+    bulk_undo: Vec<Vec<Profile>>,
+    tunnels: Vec<TunnelSession>,
+    tunnels_checked_at: Option<Instant>,
+    /// Interactive SSH sessions running in any td, refreshed with the tunnel check.
+    active_sessions: Vec<ActiveSession>,
+    tunnel_auto_restart: bool,
+    /// The env preset in use (`td env use`), re-read with the tunnel check.
+    current_env: Option<String>,
+    /// The last copied secret; dropping it (on exit) clears the clipboard.
+    copied_secret: Option<ClipboardExpiry>,
 }
 
 impl AppState {
     pub fn new(store: ProfileStore, cmdset_store: CmdSetStore) -> Result<Self> {
-        let profiles = store.list()?;
-        let groups = collect_groups(&profiles);
-        let tags = collect_tags(&profiles);
+        Self::with_timer(store, cmdset_store, &mut StartupTimer::new(false))
+    }
+
+    /// Loads only what the first frame shows: the profile list and its settings.
+    /// CommandSets and result layouts are read on first use.
+    pub(crate) fn with_timer(
+        store: ProfileStore,
+        cmdset_store: CmdSetStore,
+        timer: &mut StartupTimer,
+    ) -> Result<Self> {
         let filters = ProfileFilters::default();
-        let filtered = store.list_filtered(&filters)?;
-        let cmdsets = cmdset_store.list()?;
+        let filtered = timer.time("load profiles", || store.list_filtered(&filters))?;
+        let groups = collect_groups(&filtered);
+        let tags = collect_tags(&filtered);
+        let (profile_list, pane_layout, idle_lock_after, current_env) =
+            timer.time("load settings", || {
+                Ok::<_, tdcore::error::CoreError>((
+                    settings::get_profile_list_layout(store.conn()).unwrap_or_default(),
+                    settings::get_pane_layout(store.conn()).unwrap_or_default(),
+                    settings::get_idle_lock_after(store.conn())?,
+                    settings::get_current_env(store.conn())?,
+                ))
+            })?;
         Ok(Self {
             store,
             cmdset_store,
             filters,
             filtered,
+            profile_sort: ProfileSort::Name,
             groups,
             tags,
             tag_cursor: 0,
             mode: InputMode::Normal,
             search_input: String::new(),
             profile_cursor: 0,
-            cmdsets,
+            cmdsets: None,
             cmdset_cursor: 0,
+            scheduled_runs: HashMap::new(),
+            schedules_stale_at: None,
             active_pane: ActivePane::Profiles,
-            result_tab: ResultTab::Stdout,
+            result_tab: pane_layout.result_tab.into(),
             confirm: None,
             last_result: None,
+            result_scroll: 0,
+            result_step: None,
+            result_query: None,
+            result_search_input: String::new(),
+            last_report: None,
+            export_prompt: false,
+            live_run: None,
             last_summary: None,
             marked_profiles: BTreeSet::new(),
             details_open: false,
@@ -209,6 +785,35 @@ impl AppState {
             help_open: false,
             status_message: None,
             confirmed_ssh_session_profile_id: None,
+            preflight_waived_profile_id: None,
+            maintenance_override_profile_id: None,
+            confirmed_transfer_profile_id: None,
+            health: None,
+            result_layouts: None,
+            profile_list,
+            pane_layout,
+            dragging: None,
+            idle_lock_after,
+            last_input_at: Instant::now(),
+            lock: None,
+            snapshot: None,
+            observer: false,
+            bulk_edit: None,
+            history: None,
+            palette: None,
+            preset_picker: None,
+            profile_form: None,
+            cmdset_editor: None,
+            transfer_form: None,
+            transfer_request: None,
+            preset_matches: BTreeMap::new(),
+            bulk_undo: Vec::new(),
+            tunnels: Vec::new(),
+            tunnels_checked_at: None,
+            active_sessions: Vec::new(),
+            tunnel_auto_restart: false,
+            current_env,
+            copied_secret: None,
         })
     }
 
@@ -244,6 +849,21 @@ impl AppState {
         self.help_open
     }
 
+    /// Whether a popup, form, or the help screen covers the panes, so mouse events on
+    /// them are ignored.
+    pub fn overlay_open(&self) -> bool {
+        self.help_open
+            || self.export_prompt
+            || self.confirm.is_some()
+            || self.palette.is_some()
+            || self.history.is_some()
+            || self.bulk_edit.is_some()
+            || self.preset_picker.is_some()
+            || self.profile_form.is_some()
+            || self.cmdset_editor.is_some()
+            || self.transfer_form.is_some()
+    }
+
     pub fn filters(&self) -> &ProfileFilters {
         &self.filters
     }
@@ -265,23 +885,83 @@ impl AppState {
     }
 
     pub fn profile_cursor(&self) -> Option<usize> {
-        if self.filtered.is_empty() {
-            None
-        } else {
-            Some(self.profile_cursor.min(self.filtered.len() - 1))
+        let rows = self.profile_row_count();
+        (rows > 0).then(|| self.profile_cursor.min(rows - 1))
+    }
+
+    /// The profile list as shown: the filtered profiles, or with `G` the same profiles under
+    /// group or tag headers, where a collapsed header hides its profiles.
+    pub fn profile_rows(&self) -> Vec<ProfileRow> {
+        let (prefix, missing) = match self.profile_list.group_by {
+            ProfileGrouping::None => {
+                return (0..self.filtered.len()).map(ProfileRow::Profile).collect()
+            }
+            ProfileGrouping::Group => ("group", "(no group)"),
+            ProfileGrouping::Tag => ("tag", "(untagged)"),
+        };
+        // Keyed by (has no header name, name) so the catch-all section comes last.
+        let mut sections: BTreeMap<(bool, &str), Vec<usize>> = BTreeMap::new();
+        for (index, profile) in self.filtered.iter().enumerate() {
+            let names = match self.profile_list.group_by {
+                ProfileGrouping::Tag => profile.tags.iter().map(String::as_str).collect(),
+                _ => profile.group.as_deref().into_iter().collect::<Vec<_>>(),
+            };
+            if names.is_empty() {
+                sections.entry((true, "")).or_default().push(index);
+            }
+            for name in names {
+                sections.entry((false, name)).or_default().push(index);
+            }
+        }
+        let mut rows = Vec::new();
+        for ((ungrouped, name), indexes) in sections {
+            let key = format!("{prefix}:{name}");
+            let collapsed = self.profile_list.collapsed.contains(&key);
+            rows.push(ProfileRow::Header {
+                key,
+                label: if ungrouped { missing } else { name }.to_string(),
+                count: indexes.len(),
+                collapsed,
+            });
+            if !collapsed {
+                rows.extend(indexes.into_iter().map(ProfileRow::Profile));
+            }
+        }
+        rows
+    }
+
+    pub fn profile_row_count(&self) -> usize {
+        match self.profile_list.group_by {
+            ProfileGrouping::None => self.filtered.len(),
+            _ => self.profile_rows().len(),
+        }
+    }
+
+    /// The header under the cursor, if the cursor is on one.
+    pub fn selected_header(&self) -> Option<String> {
+        match self.profile_rows().get(self.profile_cursor()?)? {
+            ProfileRow::Header { key, .. } => Some(key.clone()),
+            ProfileRow::Profile(_) => None,
         }
     }
 
     pub fn cmdset_cursor(&self) -> Option<usize> {
-        if self.cmdsets.is_empty() {
+        if self.cmdsets().is_empty() {
             None
         } else {
-            Some(self.cmdset_cursor.min(self.cmdsets.len() - 1))
+            Some(self.cmdset_cursor.min(self.cmdsets().len() - 1))
         }
     }
 
     pub fn selected_profile(&self) -> Option<&Profile> {
-        self.profile_cursor().and_then(|idx| self.filtered.get(idx))
+        let cursor = self.profile_cursor()?;
+        match self.profile_list.group_by {
+            ProfileGrouping::None => self.filtered.get(cursor),
+            _ => match self.profile_rows().get(cursor)? {
+                ProfileRow::Profile(index) => self.filtered.get(*index),
+                ProfileRow::Header { .. } => None,
+            },
+        }
     }
 
     pub fn selected_profile_id(&self) -> Option<String> {
@@ -290,90 +970,1621 @@ impl AppState {
     }
 
     pub fn selected_cmdset(&self) -> Option<&CmdSet> {
-        self.cmdset_cursor().and_then(|idx| self.cmdsets.get(idx))
+        self.cmdset_cursor().and_then(|idx| self.cmdsets().get(idx))
     }
 
     pub fn confirm_state(&self) -> Option<&ConfirmState> {
         self.confirm.as_ref()
     }
 
-    pub fn last_result(&self) -> Option<&RunResult> {
-        self.last_result.as_ref()
-    }
-
-    pub fn status_message(&self) -> Option<&str> {
-        self.status_message.as_deref()
+    pub fn bulk_edit_state(&self) -> Option<&BulkEditState> {
+        self.bulk_edit.as_ref()
     }
 
-    pub fn action_hint(&self) -> String {
-        if self.filtered.is_empty() {
-            return "No profiles match filters; press C to clear filters or c for settings."
-                .to_string();
-        }
-        let Some(profile) = self.selected_profile() else {
-            return "No profile selected.".to_string();
-        };
-        if profile.profile_type != ProfileType::Ssh {
-            return format!(
-                "Selected profile is {}; s and CommandSet run require SSH.",
-                profile.profile_type
-            );
-        }
-        if self.cmdsets.is_empty() {
-            return format!(
-                "Ready: s opens SSH session for '{}'; c opens settings; no CommandSets available.",
-                profile.profile_id
-            );
+    pub fn open_bulk_edit(&mut self) {
+        if self.blocked_read_only("bulk edit", false) {
+            return;
         }
-        let Some(cmdset) = self.selected_cmdset() else {
-            return format!(
-                "Ready: s opens SSH session for '{}'; c opens settings; no CommandSet selected.",
-                profile.profile_id
-            );
-        };
         if self.marked_profiles.is_empty() {
-            format!(
-                "Ready: s opens SSH session; c settings; r runs '{}' on '{}'; Space marks profiles for bulk R.",
-                cmdset.cmdset_id, profile.profile_id
-            )
-        } else {
-            format!(
-                "Ready: s opens SSH session; c settings; r runs selected; R runs '{}' on {} marked profiles.",
-                cmdset.cmdset_id,
-                self.marked_profiles.len()
-            )
+            self.status_message = Some("Mark profiles with Space to bulk edit them.".to_string());
+            return;
         }
+        self.bulk_edit = Some(BulkEditState {
+            profile_ids: self.marked_profiles.iter().cloned().collect(),
+            stage: BulkEditStage::ChooseField { cursor: 0 },
+        });
     }
 
-    pub fn cmdsets(&self) -> &[CmdSet] {
-        &self.cmdsets
-    }
-
-    pub fn marked_profiles(&self) -> &BTreeSet<String> {
-        &self.marked_profiles
+    pub fn bulk_edit_move(&mut self, down: bool) {
+        if let Some(BulkEditState {
+            stage: BulkEditStage::ChooseField { cursor },
+            ..
+        }) = &mut self.bulk_edit
+        {
+            let last = BulkEditField::ALL.len() - 1;
+            *cursor = if down {
+                (*cursor + 1).min(last)
+            } else {
+                cursor.saturating_sub(1)
+            };
+        }
     }
 
-    pub fn enter_search(&mut self) {
-        self.mode = InputMode::Search;
-        self.search_input = self.filters.query.clone().unwrap_or_default();
+    pub fn push_bulk_edit_char(&mut self, ch: char) {
+        if let Some(BulkEditState {
+            stage: BulkEditStage::Input { input, error, .. },
+            ..
+        }) = &mut self.bulk_edit
+        {
+            input.push(ch);
+            *error = None;
+        }
     }
 
-    pub fn exit_search(&mut self) -> Result<()> {
-        self.mode = InputMode::Normal;
-        self.update_query()
+    pub fn pop_bulk_edit_char(&mut self) {
+        if let Some(BulkEditState {
+            stage: BulkEditStage::Input { input, error, .. },
+            ..
+        }) = &mut self.bulk_edit
+        {
+            input.pop();
+            *error = None;
+        }
     }
 
-    pub fn push_search_char(&mut self, ch: char) -> Result<()> {
-        self.search_input.push(ch);
-        self.update_query()
+    /// Enter: choose the field, build the preview, or apply the previewed edit.
+    pub fn submit_bulk_edit(&mut self) -> Result<()> {
+        let Some(mut state) = self.bulk_edit.take() else {
+            return Ok(());
+        };
+        state.stage = match state.stage {
+            BulkEditStage::ChooseField { cursor } => BulkEditStage::Input {
+                field: BulkEditField::ALL[cursor],
+                input: String::new(),
+                error: None,
+            },
+            BulkEditStage::Input { field, input, .. } => {
+                let preview = BulkEdit::parse(field, &input).and_then(|edit| {
+                    let rows = self.store.preview_bulk_edit(&state.profile_ids, &edit)?;
+                    Ok((edit, rows))
+                });
+                match preview {
+                    Ok((edit, rows)) => BulkEditStage::Preview {
+                        field,
+                        input,
+                        edit,
+                        rows,
+                    },
+                    Err(err) => BulkEditStage::Input {
+                        field,
+                        input,
+                        error: Some(err.to_string()),
+                    },
+                }
+            }
+            BulkEditStage::Preview { field, edit, .. } => {
+                let mut before = Vec::new();
+                for profile_id in &state.profile_ids {
+                    before.extend(self.store.get(profile_id)?);
+                }
+                let changed = match self.store.apply_bulk_edit(&state.profile_ids, &edit) {
+                    Ok(changed) => changed,
+                    Err(err) => {
+                        self.status_message = Some(format!("Bulk edit failed: {err}"));
+                        return Ok(());
+                    }
+                };
+                if self.bulk_undo.len() == BULK_UNDO_LIMIT {
+                    self.bulk_undo.remove(0);
+                }
+                self.bulk_undo.push(before);
+                self.refresh()?;
+                self.status_message = Some(format!(
+                    "{}: updated {changed} of {} profile(s). Ctrl+Z undoes it.",
+                    field.label(),
+                    state.profile_ids.len()
+                ));
+                return Ok(());
+            }
+        };
+        self.bulk_edit = Some(state);
+        Ok(())
     }
 
-    pub fn pop_search_char(&mut self) -> Result<()> {
-        self.search_input.pop();
-        self.update_query()
+    /// `Ctrl+Z`: puts back the group, tags, danger level, and client overrides the
+    /// profiles had before the most recent bulk edit of this session.
+    pub fn undo_bulk_edit(&mut self) -> Result<()> {
+        let Some(before) = self.bulk_undo.pop() else {
+            self.status_message = Some("Nothing to undo.".to_string());
+            return Ok(());
+        };
+        let reverted = self.store.revert_bulk_edit(&before);
+        self.refresh()?;
+        self.status_message = Some(match reverted {
+            Ok(count) => format!("Undid the last bulk edit on {count} profile(s)."),
+            Err(err) => format!("Undo failed: {err}"),
+        });
+        Ok(())
     }
 
-    pub fn clear_filters(&mut self) -> Result<()> {
+    /// Esc: step back one stage, closing the dialog from the field list.
+    pub fn back_bulk_edit(&mut self) {
+        let Some(mut state) = self.bulk_edit.take() else {
+            return;
+        };
+        state.stage = match state.stage {
+            BulkEditStage::ChooseField { .. } => {
+                self.status_message = Some("Bulk edit cancelled.".to_string());
+                return;
+            }
+            BulkEditStage::Input { field, .. } => BulkEditStage::ChooseField {
+                cursor: BulkEditField::ALL
+                    .iter()
+                    .position(|candidate| *candidate == field)
+                    .unwrap_or_default(),
+            },
+            BulkEditStage::Preview { field, input, .. } => BulkEditStage::Input {
+                field,
+                input,
+                error: None,
+            },
+        };
+        self.bulk_edit = Some(state);
+    }
+
+    pub fn last_result(&self) -> Option<&RunResult> {
+        self.last_result.as_ref()
+    }
+
+    /// Lines of the current stdout/stderr/parsed tab, or `None` while the tab shows a
+    /// placeholder (no result, an error, a live run, or the summary).
+    pub fn result_buffer(&self) -> Option<ResultBuffer> {
+        if self.live_run.is_some() {
+            return None;
+        }
+        let result = self.last_result.as_ref()?;
+        if result.error.is_some() {
+            return None;
+        }
+        let default_layout = ResultTabLayout::default();
+        let layout = self
+            .result_layout(self.result_tab)
+            .unwrap_or(&default_layout);
+        let mut lines = match self.result_tab {
+            ResultTab::Stdout => output_lines(
+                result,
+                layout,
+                self.result_step,
+                |step| &step.stdout,
+                &result.stdout,
+            ),
+            ResultTab::Stderr => output_lines(
+                result,
+                layout,
+                self.result_step,
+                |step| &step.stderr,
+                &result.stderr,
+            ),
+            ResultTab::Parsed => result
+                .parsed_pretty
+                .lines()
+                .map(str::to_string)
+                .collect::<Vec<_>>(),
+            ResultTab::Summary => return None,
+        };
+        let total = lines.len();
+        lines.truncate(layout.max_lines.unwrap_or(total));
+        Some(ResultBuffer {
+            hidden: total - lines.len(),
+            lines,
+        })
+    }
+
+    pub fn result_scroll(&self) -> usize {
+        self.result_scroll
+    }
+
+    pub fn result_query(&self) -> Option<&str> {
+        self.result_query.as_deref()
+    }
+
+    pub fn result_search_input(&self) -> &str {
+        &self.result_search_input
+    }
+
+    pub fn scroll_results_down(&mut self, page: bool) {
+        let Some(buffer) = self.result_buffer() else {
+            return;
+        };
+        let step = if page { RESULT_PAGE_LINES } else { 1 };
+        self.result_scroll = (self.result_scroll + step).min(buffer.lines.len().saturating_sub(1));
+    }
+
+    pub fn scroll_results_up(&mut self, page: bool) {
+        let step = if page { RESULT_PAGE_LINES } else { 1 };
+        self.result_scroll = self.result_scroll.saturating_sub(step);
+    }
+
+    pub fn enter_result_search(&mut self) {
+        self.mode = InputMode::ResultSearch;
+        self.result_search_input = self.result_query.clone().unwrap_or_default();
+    }
+
+    pub fn push_result_search_char(&mut self, ch: char) {
+        self.result_search_input.push(ch);
+    }
+
+    pub fn pop_result_search_char(&mut self) {
+        self.result_search_input.pop();
+    }
+
+    /// Esc: leaves the prompt and clears the highlight.
+    pub fn cancel_result_search(&mut self) {
+        self.mode = InputMode::Normal;
+        self.result_search_input.clear();
+        self.result_query = None;
+    }
+
+    /// Enter: applies the query and jumps to the first match at or below the top line.
+    pub fn submit_result_search(&mut self) {
+        self.mode = InputMode::Normal;
+        let query = self.result_search_input.trim().to_string();
+        if query.is_empty() {
+            self.result_query = None;
+            return;
+        }
+        self.result_query = Some(query);
+        self.jump_to_result_match(self.result_scroll);
+    }
+
+    /// `n`: moves to the next line containing the query, wrapping to the top.
+    pub fn next_result_match(&mut self) {
+        if self.result_query.is_some() {
+            self.jump_to_result_match(self.result_scroll + 1);
+        }
+    }
+
+    fn jump_to_result_match(&mut self, from: usize) {
+        let Some(query) = self.result_query.clone() else {
+            return;
+        };
+        let Some(buffer) = self.result_buffer() else {
+            self.status_message = Some("No output to search.".to_string());
+            return;
+        };
+        let matches = buffer
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| contains_ignore_case(line, &query))
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let Some(position) = matches
+            .iter()
+            .position(|index| *index >= from)
+            .or((!matches.is_empty()).then_some(0))
+        else {
+            self.status_message = Some(format!("No matches for '{query}'."));
+            return;
+        };
+        self.result_scroll = matches[position];
+        self.status_message = Some(format!(
+            "Match {}/{} for '{query}' (line {}).",
+            position + 1,
+            matches.len(),
+            matches[position] + 1
+        ));
+    }
+
+    /// `P`: copies the selected profile's linked `password` secret with the key of an
+    /// unlocked `td daemon`. It is cleared after `clipboard.clear_after_secs` or on exit.
+    pub fn copy_login_secret(&mut self) -> Result<()> {
+        if self.blocked_read_only("copying secrets", false) {
+            return Ok(());
+        }
+        let Some(profile_id) = self.selected_profile_id() else {
+            self.status_message = Some("Select a profile to copy its password.".to_string());
+            return Ok(());
+        };
+        let secrets = SecretStore::new(db::init_connection()?);
+        let Some(secret) = secrets.login_secret_for(&profile_id)? else {
+            self.status_message = Some(format!(
+                "No password secret is linked to {profile_id} (td secret link)."
+            ));
+            return Ok(());
+        };
+        let Some(master) = DaemonClient::connect()
+            .ok()
+            .flatten()
+            .and_then(|client| client.master_key().ok().flatten())
+        else {
+            self.status_message =
+                Some("The vault is locked; run td secret unlock first.".to_string());
+            return Ok(());
+        };
+        let Some(clipboard) = Clipboard::detect() else {
+            self.status_message =
+                Some("No clipboard tool found (wl-copy, xclip, xsel, pbcopy, clip).".to_string());
+            return Ok(());
+        };
+        self.copied_secret = secrets.copy_to_clipboard(
+            &master,
+            &secret.secret_id,
+            &clipboard,
+            "tui",
+            Some(&profile_id),
+        )?;
+        self.status_message = Some(format!(
+            "Copied secret {} to the clipboard{}.",
+            secret.secret_id,
+            self.copied_secret
+                .as_ref()
+                .map(|expiry| format!("; it is cleared in {}s", expiry.remaining().as_secs() + 1))
+                .unwrap_or_default()
+        ));
+        Ok(())
+    }
+
+    /// `y`: copies the current tab's lines with the platform clipboard tool.
+    pub fn copy_result_buffer(&mut self) {
+        let Some(buffer) = self.result_buffer() else {
+            self.status_message = Some("No output to copy.".to_string());
+            return;
+        };
+        let Some(clipboard) = Clipboard::detect() else {
+            self.status_message =
+                Some("No clipboard tool found (wl-copy, xclip, xsel, pbcopy, clip).".to_string());
+            return;
+        };
+        self.status_message = Some(match clipboard.copy(&buffer.lines.join("\n")) {
+            Ok(()) => format!("Copied {} line(s) to the clipboard.", buffer.lines.len()),
+            Err(err) => format!("Copy failed: {err}"),
+        });
+    }
+
+    pub fn palette_state(&self) -> Option<&PaletteState> {
+        self.palette.as_ref()
+    }
+
+    /// `Ctrl+K`: opens the command palette, or closes it when open.
+    pub fn toggle_palette(&mut self) {
+        self.palette = match self.palette {
+            Some(_) => None,
+            None => Some(PaletteState::default()),
+        };
+    }
+
+    pub fn push_palette_char(&mut self, ch: char) {
+        if let Some(palette) = &mut self.palette {
+            palette.input.push(ch);
+            palette.cursor = 0;
+        }
+    }
+
+    pub fn pop_palette_char(&mut self) {
+        if let Some(palette) = &mut self.palette {
+            palette.input.pop();
+            palette.cursor = 0;
+        }
+    }
+
+    pub fn palette_move(&mut self, down: bool) {
+        let Some(palette) = &mut self.palette else {
+            return;
+        };
+        let count = palette.matches().len();
+        palette.cursor = if down {
+            (palette.cursor + 1).min(count.saturating_sub(1))
+        } else {
+            palette.cursor.saturating_sub(1)
+        };
+    }
+
+    /// Closes the palette and returns the key of the highlighted entry, if any matched.
+    pub fn take_palette_choice(&mut self) -> Option<char> {
+        let palette = self.palette.take()?;
+        let choice = palette.matches().get(palette.cursor).map(|(key, _)| *key);
+        if choice.is_none() {
+            self.status_message = Some(format!("No action matches '{}'.", palette.input));
+        }
+        choice
+    }
+
+    pub fn profile_form(&self) -> Option<&ProfileForm> {
+        self.profile_form.as_ref()
+    }
+
+    pub fn profile_form_mut(&mut self) -> Option<&mut ProfileForm> {
+        self.profile_form.as_mut()
+    }
+
+    /// `a`: opens an empty profile form.
+    pub fn open_new_profile_form(&mut self) {
+        if self.blocked_read_only("adding profiles", false) {
+            return;
+        }
+        self.profile_form = Some(ProfileForm::new_profile());
+    }
+
+    /// `m`: opens the form on the selected profile.
+    pub fn open_edit_profile_form(&mut self) {
+        if self.blocked_read_only("editing profiles", false) {
+            return;
+        }
+        let Some(profile) = self.selected_profile() else {
+            self.status_message = Some("Select a profile to edit it.".to_string());
+            return;
+        };
+        self.profile_form = Some(ProfileForm::edit(profile));
+    }
+
+    pub fn close_profile_form(&mut self) {
+        self.profile_form = None;
+    }
+
+    /// Enter in the form: saves through `ProfileStore` and selects the profile. A field
+    /// that does not validate, or a store error such as a taken id, keeps the form open.
+    pub fn save_profile_form(&mut self) -> Result<()> {
+        let Some(form) = &mut self.profile_form else {
+            return Ok(());
+        };
+        let saved = match &form.editing {
+            None => form
+                .to_new_profile()
+                .and_then(|new| self.store.insert(new).map_err(|err| err.to_string())),
+            Some(profile_id) => form.to_update().and_then(|update| {
+                self.store
+                    .update(profile_id, update)
+                    .map_err(|err| err.to_string())
+            }),
+        };
+        let profile = match saved {
+            Ok(profile) => profile,
+            Err(err) => {
+                form.error = Some(err);
+                return Ok(());
+            }
+        };
+        let action = if form.editing.is_some() {
+            "Updated"
+        } else {
+            "Added"
+        };
+        self.profile_form = None;
+        self.status_message = Some(format!("{action} profile {}.", profile.profile_id));
+        self.refresh()?;
+        if let Some(at) = self.profile_rows().iter().position(|row| {
+            matches!(row, ProfileRow::Profile(index)
+                if self.filtered[*index].profile_id == profile.profile_id)
+        }) {
+            self.profile_cursor = at;
+        }
+        Ok(())
+    }
+
+    pub fn cmdset_editor(&self) -> Option<&CmdSetEditor> {
+        self.cmdset_editor.as_ref()
+    }
+
+    pub fn cmdset_editor_mut(&mut self) -> Option<&mut CmdSetEditor> {
+        self.cmdset_editor.as_mut()
+    }
+
+    /// `b`: opens the editor on the selected CommandSet.
+    pub fn open_cmdset_editor(&mut self) -> Result<()> {
+        if self.blocked_read_only("editing CommandSets", false) {
+            return Ok(());
+        }
+        self.ensure_cmdsets();
+        let Some(cmdset) = self.selected_cmdset().cloned() else {
+            self.status_message =
+                Some("Select a CommandSet in the actions pane to edit it.".to_string());
+            return Ok(());
+        };
+        let steps = self.cmdset_store.list_steps(&cmdset.cmdset_id)?;
+        self.cmdset_editor = Some(CmdSetEditor::edit(&cmdset, &steps));
+        Ok(())
+    }
+
+    /// `N`: opens the editor on a new, empty CommandSet.
+    pub fn open_new_cmdset_editor(&mut self) {
+        if self.blocked_read_only("editing CommandSets", false) {
+            return;
+        }
+        self.cmdset_editor = Some(CmdSetEditor::new_cmdset());
+    }
+
+    /// Enter in the editor's input line.
+    pub fn commit_cmdset_editor_input(&mut self) {
+        let store = &self.cmdset_store;
+        if let Some(editor) = &mut self.cmdset_editor {
+            editor.commit_input(|id| matches!(store.get_parser(id), Ok(Some(_))));
+        }
+    }
+
+    /// `Esc`: closes the editor, asking for a second `Esc` when there are unsaved changes.
+    pub fn close_cmdset_editor(&mut self) {
+        if let Some(editor) = &mut self.cmdset_editor {
+            if editor.dirty && !editor.discard_armed {
+                editor.discard_armed = true;
+                editor.error =
+                    Some("Unsaved changes: press Esc again to discard them.".to_string());
+                return;
+            }
+        }
+        self.cmdset_editor = None;
+    }
+
+    /// `s` in the editor: writes the name and steps in one transaction and selects the set.
+    pub fn save_cmdset_editor(&mut self) -> Result<()> {
+        let Some(editor) = &mut self.cmdset_editor else {
+            return Ok(());
+        };
+        let steps = editor.steps.clone();
+        let saved = match &editor.cmdset_id {
+            Some(cmdset_id) => self.cmdset_store.update(cmdset_id, &editor.name, steps),
+            None => self.cmdset_store.insert(NewCmdSet {
+                cmdset_id: None,
+                name: editor.name.clone(),
+                vars: None,
+                steps,
+            }),
+        };
+        let cmdset = match saved {
+            Ok(cmdset) => cmdset,
+            Err(err) => {
+                editor.error = Some(err.to_string());
+                return Ok(());
+            }
+        };
+        self.status_message = Some(format!(
+            "Saved CommandSet {} ({} step(s)).",
+            cmdset.cmdset_id,
+            editor.steps.len()
+        ));
+        self.cmdset_editor = None;
+        self.cmdsets = None;
+        self.ensure_cmdsets();
+        if let Some(index) = self
+            .cmdsets()
+            .iter()
+            .position(|set| set.cmdset_id == cmdset.cmdset_id)
+        {
+            self.cmdset_cursor = index;
+        }
+        Ok(())
+    }
+
+    pub fn transfer_form(&self) -> Option<&TransferForm> {
+        self.transfer_form.as_ref()
+    }
+
+    pub fn transfer_form_mut(&mut self) -> Option<&mut TransferForm> {
+        self.transfer_form.as_mut()
+    }
+
+    /// `U`: opens the transfer form for the selected SSH profile, with its favorites.
+    pub fn open_transfer_form(&mut self) -> Result<()> {
+        if self.blocked_read_only("file transfers", true) {
+            return Ok(());
+        }
+        let Some(profile) = self.selected_profile() else {
+            self.status_message =
+                Some("No profile selected; clear filters or add a profile.".to_string());
+            return Ok(());
+        };
+        if profile.profile_type != ProfileType::Ssh {
+            self.status_message = Some(format!(
+                "Selected profile is {}; transfers require an SSH profile.",
+                profile.profile_type
+            ));
+            return Ok(());
+        }
+        let favorites = transfer::favorites_for_profile(self.store.conn(), &profile.profile_id)?;
+        self.transfer_form = Some(TransferForm::new(&profile.profile_id, favorites));
+        Ok(())
+    }
+
+    pub fn close_transfer_form(&mut self) {
+        self.transfer_form = None;
+    }
+
+    /// Enter in the form: `true` when the values check out and the transfer may start.
+    pub fn submit_transfer_form(&mut self) -> bool {
+        let Some(form) = &mut self.transfer_form else {
+            return false;
+        };
+        match form.validate() {
+            Ok(request) => {
+                self.transfer_request = Some(request);
+                self.transfer_form = None;
+                true
+            }
+            Err(err) => {
+                form.error = Some(err);
+                false
+            }
+        }
+    }
+
+    /// Takes the submitted transfer through maintenance windows, `policy.danger`, and the
+    /// danger confirmation, then resolves the client. `None` means it stops here: blocked,
+    /// or waiting in a confirmation that comes back through `ConfirmedAction::RunTransfer`.
+    pub fn build_transfer_command(&mut self) -> Result<Option<TransferCommand>> {
+        let confirmed_profile_id = self.confirmed_transfer_profile_id.take();
+        let Some(request) = self.transfer_request.clone() else {
+            return Ok(None);
+        };
+        let Some(profile) = self.store.get(&request.profile_id)? else {
+            self.transfer_request = None;
+            self.status_message = Some(format!("Profile not found: {}", request.profile_id));
+            return Ok(None);
+        };
+        let op = request.direction.as_str();
+        if confirmed_profile_id.as_deref() != Some(profile.profile_id.as_str()) {
+            if !self.maintenance_clear(&profile, OverrideNext::Transfer(request.direction))? {
+                return Ok(None);
+            }
+            let upload = request.direction == TransferDirection::Push;
+            let Some(verdict) = self.policy_verdict(&profile, PolicyAction::Transfer { upload })?
+            else {
+                return Ok(None);
+            };
+            if let Some(required_input) = self.confirmation_input(&profile, verdict.confirm)? {
+                let (from, to) = match request.direction {
+                    TransferDirection::Push => (
+                        request.local_path.display().to_string(),
+                        format!("{}:{}", profile.host, request.remote_path),
+                    ),
+                    TransferDirection::Pull => (
+                        format!("{}:{}", profile.host, request.remote_path),
+                        request.local_path.display().to_string(),
+                    ),
+                };
+                self.confirm = Some(ConfirmState {
+                    message: format!(
+                        "{} profile '{}'. {} to {op} {from} to {to}.",
+                        danger_title(profile.danger_level),
+                        profile.profile_id,
+                        confirmation_ask(&profile, &required_input),
+                    ),
+                    required_input,
+                    input: String::new(),
+                    blast_radius: None,
+                    action: PendingAction::RunTransfer {
+                        profile_id: profile.profile_id,
+                    },
+                });
+                return Ok(None);
+            }
+        }
+        self.transfer_request = None;
+        let auth = ssh::ssh_profile_auth_context(self.store.conn(), &profile.profile_id);
+        let client =
+            ssh::resolve_client_for(request.via.client_kind(), &profile, self.store.conn());
+        let (auth, client) = match (auth, client) {
+            (Ok(auth), Ok(client)) => (auth, client),
+            (Err(err), _) | (_, Err(err)) => {
+                self.status_message = Some(format!("Cannot start {op}: {err}"));
+                return Ok(None);
+            }
+        };
+        Ok(Some(TransferCommand {
+            profile,
+            request,
+            client,
+            auth_args: auth.args,
+            auth_hint: auth.hint,
+        }))
+    }
+
+    /// Logs a finished transfer to `op_logs` the way `td push`/`td pull` do. A client that
+    /// could not be started is only reported, as the CLI does.
+    pub fn record_transfer_result(
+        &mut self,
+        command: &TransferCommand,
+        outcome: Result<TransferOutcome, tdcore::error::CoreError>,
+    ) -> Result<()> {
+        let request = &command.request;
+        let (name, done) = match request.direction {
+            TransferDirection::Push => ("Push", "Pushed"),
+            TransferDirection::Pull => ("Pull", "Pulled"),
+        };
+        let outcome = match outcome {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                self.status_message = Some(format!("{name} failed to start: {err}"));
+                return Ok(());
+            }
+        };
+        self.store.touch_last_used(&command.profile.profile_id)?;
+        if let Some(favorite_id) = request.favorite_id {
+            transfer::touch_favorite(self.store.conn(), favorite_id)?;
+        }
+        oplog::log_operation(
+            self.store.conn(),
+            transfer::log_entry(
+                &command.profile,
+                request.direction,
+                &request.local_path,
+                &request.remote_path,
+                request.via,
+                &outcome,
+                request.direction.as_str(),
+            ),
+        )?;
+        self.status_message = Some(if outcome.ok {
+            format!(
+                "{done} {} via {} in {} ms.",
+                request.local_path.display(),
+                request.via.as_str(),
+                outcome.duration_ms
+            )
+        } else {
+            format!("{name} failed with exit code {}.", outcome.exit_code)
+        });
+        Ok(())
+    }
+
+    pub fn preset_picker_state(&self) -> Option<&PresetPickerState> {
+        self.preset_picker.as_ref()
+    }
+
+    /// Names of the forwarding presets `profile_id` matches.
+    pub fn preset_matches(&self, profile_id: &str) -> &[String] {
+        self.preset_matches
+            .get(profile_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// `p`: picks a forwarding preset to add to the marked profiles (or the selected one).
+    pub fn open_preset_picker(&mut self) -> Result<()> {
+        if self.blocked_read_only("applying forwarding presets", false) {
+            return Ok(());
+        }
+        let profile_ids: Vec<String> = if self.marked_profiles.is_empty() {
+            self.selected_profile_id().into_iter().collect()
+        } else {
+            self.marked_profiles.iter().cloned().collect()
+        };
+        if profile_ids.is_empty() {
+            self.status_message = Some("Select or mark profiles to apply a preset.".to_string());
+            return Ok(());
+        }
+        let presets = forward_preset::list(self.store.conn())?;
+        if presets.is_empty() {
+            self.status_message = Some(
+                "No forwarding presets yet (td tunnel preset save <name> --from <profile>)."
+                    .to_string(),
+            );
+            return Ok(());
+        }
+        self.preset_picker = Some(PresetPickerState {
+            presets,
+            cursor: 0,
+            profile_ids,
+        });
+        Ok(())
+    }
+
+    pub fn close_preset_picker(&mut self) {
+        self.preset_picker = None;
+    }
+
+    pub fn preset_picker_move(&mut self, down: bool) {
+        if let Some(picker) = &mut self.preset_picker {
+            picker.cursor = if down {
+                (picker.cursor + 1).min(picker.presets.len().saturating_sub(1))
+            } else {
+                picker.cursor.saturating_sub(1)
+            };
+        }
+    }
+
+    /// Adds the highlighted preset to the picker's profiles; a clash writes nothing.
+    pub fn apply_preset_choice(&mut self) -> Result<()> {
+        let Some(picker) = self.preset_picker.take() else {
+            return Ok(());
+        };
+        let Some(preset) = picker.presets.get(picker.cursor) else {
+            return Ok(());
+        };
+        self.status_message = Some(
+            match forward_preset::apply(self.store.conn(), &preset.name, &picker.profile_ids) {
+                Ok(report) => format!(
+                    "Preset {} applied to {} profile(s): {} forward(s) added, {} already present.",
+                    preset.name,
+                    picker.profile_ids.len(),
+                    report.added,
+                    report.unchanged
+                ),
+                Err(err) => format!("Preset {} not applied: {err}", preset.name),
+            },
+        );
+        self.refresh()
+    }
+
+    pub fn history_state(&self) -> Option<&HistoryState> {
+        self.history.as_ref()
+    }
+
+    /// `L`: lists the selected profile's recent op log entries.
+    pub fn open_history(&mut self) -> Result<()> {
+        let Some(profile_id) = self.selected_profile_id() else {
+            self.status_message = Some("Select a profile to browse its history.".to_string());
+            return Ok(());
+        };
+        let mut history = HistoryState {
+            profile_id,
+            entries: Vec::new(),
+            cursor: 0,
+            result: None,
+            period: HistoryPeriod::All,
+            exhausted: false,
+        };
+        self.load_history_page(&mut history)?;
+        if history.entries.is_empty() {
+            self.status_message =
+                Some(format!("No op log entries for {} yet.", history.profile_id));
+            return Ok(());
+        }
+        self.history = Some(history);
+        Ok(())
+    }
+
+    /// Appends the next `HISTORY_LIMIT` entries that match the overlay's filters.
+    fn load_history_page(&self, history: &mut HistoryState) -> Result<()> {
+        let records = oplog::list_operations(
+            self.store.conn(),
+            &OpLogQuery {
+                profile_id: Some(history.profile_id.clone()),
+                ok: history.result,
+                since_ms: history.period.since_ms(util::now_ms()),
+                limit: HISTORY_LIMIT,
+                offset: history.entries.len(),
+                ..OpLogQuery::default()
+            },
+        )?;
+        history.exhausted = records.len() < HISTORY_LIMIT;
+        let offset = settings::get_display_offset(self.store.conn())?;
+        history
+            .entries
+            .extend(records.into_iter().map(|record| HistoryEntry {
+                when: util::format_timestamp_ms(record.ts, offset),
+                record,
+            }));
+        Ok(())
+    }
+
+    /// Moves the cursor; moving past the last entry loads the next page.
+    pub fn history_move(&mut self, down: bool) -> Result<()> {
+        let Some(mut history) = self.history.take() else {
+            return Ok(());
+        };
+        let loaded = if down && history.cursor + 1 >= history.entries.len() && !history.exhausted {
+            self.load_history_page(&mut history)
+        } else {
+            Ok(())
+        };
+        history.cursor = if down {
+            (history.cursor + 1).min(history.entries.len().saturating_sub(1))
+        } else {
+            history.cursor.saturating_sub(1)
+        };
+        self.history = Some(history);
+        loaded
+    }
+
+    /// `f` cycles all, failed, and ok entries; `t` cycles the period. Both reload the list.
+    pub fn cycle_history_filter(&mut self, period: bool) -> Result<()> {
+        let Some(mut history) = self.history.take() else {
+            return Ok(());
+        };
+        if period {
+            history.period = history.period.next();
+        } else {
+            history.result = match history.result {
+                None => Some(false),
+                Some(false) => Some(true),
+                Some(true) => None,
+            };
+        }
+        history.entries.clear();
+        history.cursor = 0;
+        let loaded = self.load_history_page(&mut history);
+        self.history = Some(history);
+        loaded
+    }
+
+    /// `r`: runs the highlighted entry's CommandSet again on the profile. Returns true for
+    /// a `connect` entry, which the caller reopens as an SSH session.
+    pub fn retry_history_entry(&mut self) -> Result<bool> {
+        let Some(record) = self
+            .history
+            .as_ref()
+            .and_then(|history| history.entries.get(history.cursor))
+            .map(|entry| entry.record.clone())
+        else {
+            return Ok(false);
+        };
+        if record.op == "connect" {
+            self.history = None;
+            return Ok(true);
+        }
+        let cmdset_id = record
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.get("cmdset_id"))
+            .and_then(|id| id.as_str());
+        let Some(cmdset_id) = cmdset_id.filter(|_| record.op == "run") else {
+            self.status_message = Some(format!(
+                "Only run and connect entries can be retried; #{} is {}.",
+                record.id, record.op
+            ));
+            return Ok(false);
+        };
+        self.ensure_cmdsets();
+        let Some(index) = self
+            .cmdsets()
+            .iter()
+            .position(|cmdset| cmdset.cmdset_id == cmdset_id)
+        else {
+            self.status_message = Some(format!("CommandSet {cmdset_id} no longer exists."));
+            return Ok(false);
+        };
+        self.cmdset_cursor = index;
+        self.history = None;
+        self.request_run()?;
+        Ok(false)
+    }
+
+    /// `x`: writes the listed entries as JSON under `reports.dir`.
+    pub fn export_history(&mut self) {
+        let Some(history) = &self.history else {
+            return;
+        };
+        let records = history
+            .entries
+            .iter()
+            .map(|entry| &entry.record)
+            .collect::<Vec<_>>();
+        let stem = format!(
+            "history-{}-{}",
+            run_report::file_safe(&history.profile_id),
+            run_report::file_stamp(util::now_ms())
+        );
+        let written = settings::get_reports_dir(self.store.conn()).and_then(|dir| {
+            let body = serde_json::to_string_pretty(&records)? + "\n";
+            run_report::write_new_file(&dir, &stem, "json", &body)
+        });
+        self.status_message = Some(match written {
+            Ok(path) => format!(
+                "Wrote {} history entries to {}",
+                records.len(),
+                path.display()
+            ),
+            Err(err) => format!("History export failed: {err}"),
+        });
+    }
+
+    pub fn close_history(&mut self) {
+        self.history = None;
+    }
+
+    /// Shows the highlighted entry's stored output in the Results pane.
+    pub fn reopen_history_entry(&mut self) -> Result<()> {
+        let Some(history) = self.history.take() else {
+            return Ok(());
+        };
+        let Some(HistoryEntry { record, when }) = history.entries.get(history.cursor) else {
+            return Ok(());
+        };
+        let outputs = run_output::outputs_for_operation(self.store.conn(), record)?;
+        self.status_message = Some(if outputs.is_empty() {
+            format!(
+                "Op log #{} ({} at {when}) has no stored output; set run.store_outputs to keep it.",
+                record.id, record.op
+            )
+        } else {
+            format!(
+                "Reopened op log #{} ({} at {when}, {} step(s)).",
+                record.id,
+                record.op,
+                outputs.len()
+            )
+        });
+        // The report of the last live run no longer matches what the pane shows.
+        self.last_report = None;
+        self.last_summary = None;
+        self.set_last_result(RunResult::from_op_log(record, outputs));
+        Ok(())
+    }
+
+    pub fn export_prompt_open(&self) -> bool {
+        self.export_prompt
+    }
+
+    /// `e`: asks for a report format; the next key picks one.
+    pub fn open_export_prompt(&mut self) {
+        if self.snapshot.is_some() {
+            self.status_message = Some("Reports are not written from a snapshot.".to_string());
+            return;
+        }
+        if self.last_report.is_none() {
+            self.status_message = Some("No run to export yet.".to_string());
+            return;
+        }
+        self.export_prompt = true;
+        self.status_message =
+            Some("Export report: j json, t text, h html (any other key cancels)".to_string());
+    }
+
+    /// Writes the last run's report under `reports.dir`; `None` cancels the prompt.
+    pub fn export_report(&mut self, format: Option<ReportFormat>) {
+        self.export_prompt = false;
+        let (Some(format), Some(report)) = (format, self.last_report.as_ref()) else {
+            self.status_message = Some("Export cancelled.".to_string());
+            return;
+        };
+        let conn = self.store.conn();
+        let written = settings::get_reports_dir(conn)
+            .and_then(|dir| report.write(&dir, format, settings::get_display_offset(conn)?));
+        self.status_message = Some(match written {
+            Ok(path) => format!("Report written to {}", path.display()),
+            Err(err) => format!("Export failed: {err}"),
+        });
+    }
+
+    fn set_last_result(&mut self, result: RunResult) {
+        self.result_step = self.result_step.filter(|step| *step < result.steps.len());
+        self.last_result = Some(result);
+        self.result_scroll = 0;
+        self.result_layouts_mut();
+    }
+
+    pub fn live_run(&self) -> Option<&LiveRun> {
+        self.live_run.as_ref()
+    }
+
+    /// Collects output from the background run and shows its result once it ends.
+    pub fn tick_run(&mut self) {
+        let Some(run) = &mut self.live_run else {
+            return;
+        };
+        match run.poll() {
+            Some(outcome) => {
+                let (profile_id, cmdset_id) = (run.profile_id.clone(), run.cmdset_id.clone());
+                self.live_run = None;
+                self.finish_cmdset_run(&profile_id, &cmdset_id, outcome);
+            }
+            None => self.status_message = Some(run.progress()),
+        }
+    }
+
+    pub fn status_message(&self) -> Option<&str> {
+        self.status_message.as_deref()
+    }
+
+    pub fn action_hint(&self) -> String {
+        if self.filtered.is_empty() {
+            return "No profiles match filters; press C to clear filters or c for settings."
+                .to_string();
+        }
+        let Some(profile) = self.selected_profile() else {
+            return "No profile selected.".to_string();
+        };
+        if profile.profile_type != ProfileType::Ssh {
+            return format!(
+                "Selected profile is {}; s and CommandSet run require SSH.",
+                profile.profile_type
+            );
+        }
+        let Some(cmdsets) = &self.cmdsets else {
+            return format!(
+                "Ready: s opens SSH session for '{}'; c opens settings; Tab to Actions loads CommandSets.",
+                profile.profile_id
+            );
+        };
+        if cmdsets.is_empty() {
+            return format!(
+                "Ready: s opens SSH session for '{}'; c opens settings; no CommandSets available.",
+                profile.profile_id
+            );
+        }
+        let Some(cmdset) = self.selected_cmdset() else {
+            return format!(
+                "Ready: s opens SSH session for '{}'; c opens settings; no CommandSet selected.",
+                profile.profile_id
+            );
+        };
+        if self.marked_profiles.is_empty() {
+            format!(
+                "Ready: s opens SSH session; c settings; r runs '{}' on '{}'; Space marks profiles for bulk R.",
+                cmdset.cmdset_id, profile.profile_id
+            )
+        } else {
+            format!(
+                "Ready: s opens SSH session; c settings; r runs selected; R runs '{}' on {} marked profiles.",
+                cmdset.cmdset_id,
+                self.marked_profiles.len()
+            )
+        }
+    }
+
+    pub fn profile_list_layout(&self) -> &ProfileListLayout {
+        &self.profile_list
+    }
+
+    pub fn pane_layout(&self) -> &PaneLayout {
+        &self.pane_layout
+    }
+
+    /// `<`/`>` and `{`/`}`: moves a pane border by one step and saves the layout.
+    pub fn resize_pane(&mut self, split: PaneSplit, grow: bool) -> Result<()> {
+        let current = match split {
+            PaneSplit::Profiles => self.pane_layout.profiles_width,
+            PaneSplit::Actions => self.pane_layout.actions_height,
+        };
+        let percent = if grow {
+            current + PANE_SPLIT_STEP
+        } else {
+            current.saturating_sub(PANE_SPLIT_STEP)
+        };
+        self.set_pane_split(split, percent);
+        self.save_pane_layout()?;
+        self.status_message = Some(format!(
+            "Profile list {}%, Action pane {}%.",
+            self.pane_layout.profiles_width, self.pane_layout.actions_height
+        ));
+        Ok(())
+    }
+
+    /// Moves a pane border to `percent`, kept within 20-80%; saved by `save_pane_layout`.
+    pub fn set_pane_split(&mut self, split: PaneSplit, percent: u16) {
+        let percent = percent.clamp(PANE_SPLIT_MIN, PANE_SPLIT_MAX);
+        match split {
+            PaneSplit::Profiles => self.pane_layout.profiles_width = percent,
+            PaneSplit::Actions => self.pane_layout.actions_height = percent,
+        }
+    }
+
+    /// `=`: puts the pane borders back where they start by default.
+    pub fn reset_pane_layout(&mut self) -> Result<()> {
+        let defaults = PaneLayout::default();
+        self.pane_layout.profiles_width = defaults.profiles_width;
+        self.pane_layout.actions_height = defaults.actions_height;
+        self.save_pane_layout()?;
+        self.status_message = Some("Pane sizes reset.".to_string());
+        Ok(())
+    }
+
+    pub fn dragging(&self) -> Option<PaneSplit> {
+        self.dragging
+    }
+
+    pub fn start_drag(&mut self, split: PaneSplit) {
+        self.dragging = Some(split);
+    }
+
+    /// Ends a border drag and saves where the border ended up.
+    pub fn finish_drag(&mut self) -> Result<()> {
+        if self.dragging.take().is_some() {
+            self.save_pane_layout()?;
+        }
+        Ok(())
+    }
+
+    /// Writes `tui.layout`, including the result tab shown now, so the next `td ui`
+    /// opens the same way. Snapshot views leave it alone.
+    pub fn save_pane_layout(&mut self) -> Result<()> {
+        self.pane_layout.result_tab = self.result_tab.into();
+        if self.snapshot.is_none() {
+            settings::set_pane_layout(self.store.conn(), &self.pane_layout)?;
+        }
+        Ok(())
+    }
+
+    pub fn toggle_profile_list_wide(&mut self) -> Result<()> {
+        self.profile_list.wide = !self.profile_list.wide;
+        self.save_profile_list()?;
+        self.status_message = Some(format!(
+            "Wide profile list {}.",
+            if self.profile_list.wide { "on" } else { "off" }
+        ));
+        Ok(())
+    }
+
+    /// `G`: lists profiles flat, under their group, or under each of their tags.
+    pub fn cycle_profile_grouping(&mut self) -> Result<()> {
+        self.profile_list.group_by = match self.profile_list.group_by {
+            ProfileGrouping::None => ProfileGrouping::Group,
+            ProfileGrouping::Group => ProfileGrouping::Tag,
+            ProfileGrouping::Tag => ProfileGrouping::None,
+        };
+        self.profile_cursor = 0;
+        self.save_profile_list()?;
+        self.status_message = Some(
+            match self.profile_list.group_by {
+                ProfileGrouping::None => "Profile list ungrouped.",
+                ProfileGrouping::Group => "Profiles grouped by group (z collapses).",
+                ProfileGrouping::Tag => "Profiles grouped by tag (z collapses).",
+            }
+            .to_string(),
+        );
+        if self.details_open {
+            self.refresh_details()?;
+        }
+        Ok(())
+    }
+
+    /// `z`, or Enter on a header: collapses or expands the header under the cursor, or the
+    /// one the selected profile is listed under. The cursor lands on the header.
+    pub fn toggle_group_collapse(&mut self) -> Result<()> {
+        let Some(cursor) = self.profile_cursor() else {
+            return Ok(());
+        };
+        let rows = self.profile_rows();
+        let Some((at, key)) = rows[..=cursor]
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(at, row)| match row {
+                ProfileRow::Header { key, .. } => Some((at, key.clone())),
+                ProfileRow::Profile(_) => None,
+            })
+        else {
+            self.status_message = Some("Press G to group the profile list first.".to_string());
+            return Ok(());
+        };
+        let collapsed = &mut self.profile_list.collapsed;
+        match collapsed.iter().position(|existing| *existing == key) {
+            Some(position) => {
+                collapsed.remove(position);
+            }
+            None => collapsed.push(key),
+        }
+        self.profile_cursor = at;
+        self.save_profile_list()
+    }
+
+    /// Profiles under a `group:<name>` or `tag:<name>` header, collapsed or not.
+    fn header_profile_ids(&self, key: &str) -> Vec<String> {
+        let (kind, name) = key.split_once(':').unwrap_or((key, ""));
+        self.filtered
+            .iter()
+            .filter(|profile| match kind {
+                "tag" if name.is_empty() => profile.tags.is_empty(),
+                "tag" => profile.tags.iter().any(|tag| tag == name),
+                _ => profile.group.as_deref().unwrap_or_default() == name,
+            })
+            .map(|profile| profile.profile_id.clone())
+            .collect()
+    }
+
+    fn save_profile_list(&self) -> Result<()> {
+        if self.snapshot.is_none() {
+            settings::set_profile_list_layout(self.store.conn(), &self.profile_list)?;
+        }
+        Ok(())
+    }
+
+    pub fn health_column_enabled(&self) -> bool {
+        self.health.is_some()
+    }
+
+    pub fn health_status(&self, profile_id: &str) -> Option<&HealthStatus> {
+        self.health
+            .as_ref()
+            .and_then(|health| health.status(profile_id))
+    }
+
+    pub fn toggle_health_column(&mut self) {
+        if self.health.take().is_some() {
+            self.status_message = Some("Health column hidden.".to_string());
+            return;
+        }
+        let mut health = HealthMonitor::new();
+        let queued = health.request_missing(&self.filtered);
+        self.health = Some(health);
+        self.status_message = Some(format!(
+            "Health column shown; checking {queued} profile(s) in the background."
+        ));
+    }
+
+    /// Re-checks marked profiles, or the selected profile when nothing is marked.
+    pub fn refresh_health(&mut self) -> Result<()> {
+        let targets = if self.marked_profiles.is_empty() {
+            self.selected_profile().cloned().into_iter().collect()
+        } else {
+            let mut targets = Vec::new();
+            for profile_id in &self.marked_profiles {
+                if let Some(profile) = self.store.get(profile_id)? {
+                    targets.push(profile);
+                }
+            }
+            targets
+        };
+        if targets.is_empty() {
+            self.status_message = Some("No profile selected for health check.".to_string());
+            return Ok(());
+        }
+        let health = self.health.get_or_insert_with(HealthMonitor::new);
+        let queued = health.request(targets);
+        self.status_message = Some(format!("Health check queued for {queued} profile(s)."));
+        Ok(())
+    }
+
+    /// Collects finished background checks and queues newly visible profiles.
+    pub fn tick_health(&mut self) {
+        if let Some(health) = &mut self.health {
+            health.poll();
+            health.request_missing(&self.filtered);
+        }
+    }
+
+    pub fn tunnel_sessions(&self, profile_id: &str) -> impl Iterator<Item = &TunnelSession> {
+        let profile_id = profile_id.to_string();
+        self.tunnels
+            .iter()
+            .filter(move |session| session.profile_id == profile_id)
+    }
+
+    pub fn active_sessions(&self, profile_id: &str) -> impl Iterator<Item = &ActiveSession> {
+        let profile_id = profile_id.to_string();
+        self.active_sessions
+            .iter()
+            .filter(move |session| session.profile_id == profile_id)
+    }
+
+    /// `K`: asks before ending the selected profile's oldest running SSH session.
+    pub fn request_terminate_session(&mut self) -> Result<()> {
+        if self.blocked_read_only("ending sessions", false) {
+            return Ok(());
+        }
+        let Some(profile_id) = self.selected_profile_id() else {
+            return Ok(());
+        };
+        self.active_sessions = active_session::list(self.store.conn())?;
+        let Some(session) = self.active_sessions(&profile_id).next().cloned() else {
+            self.status_message = Some(format!("No active SSH session on {profile_id}."));
+            return Ok(());
+        };
+        self.confirm = Some(ConfirmState {
+            message: format!(
+                "End SSH session {} on {profile_id} (pid {}, started {})?",
+                session.id,
+                session.pid,
+                util::format_relative_ms(session.started_at, util::now_ms())
+            ),
+            required_input: "yes".to_string(),
+            input: String::new(),
+            blast_radius: None,
+            action: PendingAction::TerminateSession {
+                session_id: session.id,
+            },
+        });
+        Ok(())
+    }
+
+    pub fn tunnel_auto_restart(&self) -> bool {
+        self.tunnel_auto_restart
+    }
+
+    pub fn toggle_tunnel_auto_restart(&mut self) {
+        if self.blocked_read_only("tunnel restart", false) {
+            return;
+        }
+        self.tunnel_auto_restart = !self.tunnel_auto_restart;
+        self.tunnels_checked_at = None;
+        self.status_message = Some(if self.tunnel_auto_restart {
+            "Tunnel auto-restart on: dead tunnels restart per tunnel.reconnect.".to_string()
+        } else {
+            "Tunnel auto-restart off.".to_string()
+        });
+    }
+
+    /// Probes running tunnel sessions every few seconds and flags newly dead ones. With
+    /// auto-restart on, the tunnel manager restarts them per `tunnel.reconnect`.
+    pub fn tick_tunnels(&mut self, now: Instant) {
+        if self.snapshot.is_some()
+            || self
+                .tunnels_checked_at
+                .is_some_and(|at| now.duration_since(at) < TUNNEL_CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.tunnels_checked_at = Some(now);
+        if let Ok(sessions) = active_session::list(self.store.conn()) {
+            self.active_sessions = sessions;
+        }
+        if let Ok(current_env) = settings::get_current_env(self.store.conn()) {
+            self.current_env = current_env;
+        }
+        let mut messages = Vec::new();
+        // A running `td daemon` already checks (and maybe restarts) tunnels; show its view.
+        let daemon_sessions = DaemonClient::connect()
+            .ok()
+            .flatten()
+            .and_then(|client| client.sessions(false).ok());
+        let sessions =
+            if self.tunnel_auto_restart && daemon_sessions.is_none() {
+                let Ok(report) = TunnelManager::new(&self.store).supervise() else {
+                    return;
+                };
+                messages.extend(
+                    report
+                        .restarted
+                        .iter()
+                        .map(|session_id| format!("Restarted tunnel {session_id}.")),
+                );
+                messages.extend(report.failed.iter().map(|(session_id, error)| {
+                    format!("Tunnel {session_id} restart failed: {error}")
+                }));
+                report.sessions
+            } else {
+                let sessions = match daemon_sessions {
+                    Some(sessions) => sessions,
+                    None => {
+                        let Ok(sessions) = tunnel::check_tunnel_health(self.store.conn()) else {
+                            return;
+                        };
+                        sessions
+                    }
+                };
+                for session in sessions.iter().filter(|session| !session.is_healthy()) {
+                    let was_healthy = self
+                        .tunnels
+                        .iter()
+                        .find(|previous| previous.session_id == session.session_id)
+                        .is_none_or(|previous| previous.is_healthy());
+                    if was_healthy {
+                        messages.push(format!(
+                            "Tunnel {} ({}) {}; press F to restart dead tunnels automatically.",
+                            session.session_id,
+                            session.profile_id,
+                            session.health_summary()
+                        ));
+                    }
+                }
+                sessions
+            };
+        self.tunnels = sessions;
+        if !messages.is_empty() {
+            self.status_message = Some(messages.join(" "));
+        }
+    }
+
+    pub fn enter_snapshot_view(&mut self, view: SnapshotView) {
+        self.snapshot = Some(view);
+        self.status_message = Some(if view.allow_run {
+            "Snapshot view: changes are discarded on exit.".to_string()
+        } else {
+            "Snapshot view (read-only): runs and sessions are disabled.".to_string()
+        });
+    }
+
+    pub fn snapshot_view(&self) -> Option<SnapshotView> {
+        self.snapshot
+    }
+
+    /// Observer mode (`td --observer` or `observer.enabled`): browsing only, with runs,
+    /// sessions, edits, and the settings screen disabled.
+    pub fn enter_observer_mode(&mut self) {
+        self.observer = true;
+        self.status_message =
+            Some("Observer mode: runs, sessions, and edits are disabled.".to_string());
+    }
+
+    pub fn observer_mode(&self) -> bool {
+        self.observer
+    }
+
+    pub fn current_env(&self) -> Option<&str> {
+        self.current_env.as_deref()
+    }
+
+    /// Returns true (and explains why) when observer mode or the snapshot viewer forbids
+    /// `action`.
+    pub fn blocked_read_only(&mut self, action: &str, needs_run: bool) -> bool {
+        if self.observer {
+            self.status_message = Some(format!("Observer mode: {action} is disabled."));
+            return true;
+        }
+        let blocked = match self.snapshot {
+            Some(view) => !needs_run || !view.allow_run,
+            None => false,
+        };
+        if blocked {
+            self.status_message = Some(if needs_run {
+                format!("Snapshot view: {action} is disabled (start td view with --allow-run).")
+            } else {
+                format!("Snapshot view: {action} is not available.")
+            });
+        }
+        blocked
+    }
+
+    pub fn note_input(&mut self) {
+        self.last_input_at = Instant::now();
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.lock.is_some()
+    }
+
+    pub fn lock_prompt(&self) -> Option<&LockPrompt> {
+        self.lock.as_ref()
+    }
+
+    /// Locks the UI once `tui.idle_lock_minutes` have passed since the last key press.
+    pub fn tick_idle_lock(&mut self, now: Instant) {
+        let Some(after) = self.idle_lock_after else {
+            return;
+        };
+        if self.lock.is_none() && now.duration_since(self.last_input_at) >= after {
+            self.lock = Some(LockPrompt::default());
+        }
+    }
+
+    pub fn push_unlock_char(&mut self, ch: char) {
+        if let Some(lock) = &mut self.lock {
+            lock.input.push(ch);
+            lock.error = None;
+        }
+    }
+
+    pub fn pop_unlock_char(&mut self) {
+        if let Some(lock) = &mut self.lock {
+            lock.input.pop();
+        }
+    }
+
+    pub fn take_unlock_input(&mut self) -> Zeroizing<String> {
+        self.lock
+            .as_mut()
+            .map(|lock| std::mem::take(&mut lock.input))
+            .unwrap_or_default()
+    }
+
+    pub fn finish_unlock(&mut self, verified: bool) {
+        if verified {
+            self.lock = None;
+            self.last_input_at = Instant::now();
+        } else if let Some(lock) = &mut self.lock {
+            lock.error = Some("Incorrect password or PIN.".to_string());
+        }
+    }
+
+    pub fn cmdsets(&self) -> &[CmdSet] {
+        self.cmdsets.as_deref().unwrap_or_default()
+    }
+
+    pub fn marked_profiles(&self) -> &BTreeSet<String> {
+        &self.marked_profiles
+    }
+
+    pub fn enter_search(&mut self) {
+        self.mode = InputMode::Search;
+        self.search_input = self.filters.query.clone().unwrap_or_default();
+    }
+
+    pub fn exit_search(&mut self) -> Result<()> {
+        self.mode = InputMode::Normal;
+        self.update_query()
+    }
+
+    pub fn push_search_char(&mut self, ch: char) -> Result<()> {
+        self.search_input.push(ch);
+        self.update_query()
+    }
+
+    pub fn pop_search_char(&mut self) -> Result<()> {
+        self.search_input.pop();
+        self.update_query()
+    }
+
+    pub fn clear_filters(&mut self) -> Result<()> {
         self.filters = ProfileFilters::default();
         self.search_input.clear();
         self.refresh()
@@ -399,6 +2610,32 @@ impl AppState {
         self.refresh()
     }
 
+    pub fn profile_sort(&self) -> ProfileSort {
+        self.profile_sort
+    }
+
+    /// Cycles name / last used / most used. Usage counts come from `op_logs` and are
+    /// reloaded on every refresh so new sessions and runs move profiles up.
+    pub fn cycle_profile_sort(&mut self) -> Result<()> {
+        self.profile_sort = self.profile_sort.next();
+        self.status_message = Some(format!("Sorted by {}.", self.profile_sort.label()));
+        self.refresh()
+    }
+
+    fn sort_filtered(&mut self) -> Result<()> {
+        if self.profile_sort == ProfileSort::Name || self.filters.query.is_some() {
+            return Ok(());
+        }
+        let usage = oplog::profile_usage(self.store.conn(), util::now_ms())?;
+        sort_by_usage(&mut self.filtered, self.profile_sort, &usage);
+        Ok(())
+    }
+
+    pub fn toggle_archived(&mut self) -> Result<()> {
+        self.filters.include_archived = !self.filters.include_archived;
+        self.refresh()
+    }
+
     pub fn cycle_group(&mut self) -> Result<()> {
         if self.groups.is_empty() {
             self.filters.group = None;
@@ -452,20 +2689,80 @@ impl AppState {
         } else {
             self.filters.tags.push(tag.clone());
         }
-        self.refresh()
+        self.refresh()
+    }
+
+    pub fn cycle_pane(&mut self) {
+        self.focus_pane(match self.active_pane {
+            ActivePane::Profiles => ActivePane::Actions,
+            ActivePane::Actions => ActivePane::Results,
+            ActivePane::Results => ActivePane::Profiles,
+        });
+    }
+
+    /// Focuses `pane`, as `Tab` or a click on it does.
+    pub fn focus_pane(&mut self, pane: ActivePane) {
+        self.active_pane = pane;
+        match self.active_pane {
+            ActivePane::Actions => self.ensure_cmdsets(),
+            ActivePane::Results => {
+                self.result_layouts_mut();
+            }
+            ActivePane::Profiles => {}
+        }
+    }
+
+    pub fn cmdsets_loaded(&self) -> bool {
+        self.cmdsets.is_some()
+    }
+
+    fn ensure_cmdsets(&mut self) {
+        if self.cmdsets.is_some() {
+            return;
+        }
+        match self.cmdset_store.list() {
+            Ok(cmdsets) => self.cmdsets = Some(cmdsets),
+            Err(err) => self.status_message = Some(format!("Failed to load CommandSets: {err}")),
+        }
+        self.load_scheduled_runs();
+    }
+
+    pub fn scheduled_run(&self, cmdset_id: &str) -> Option<&str> {
+        self.scheduled_runs.get(cmdset_id).map(String::as_str)
+    }
+
+    /// Reloads next-run times once the earliest shown one has passed.
+    pub fn tick_schedules(&mut self, now_ms: i64) {
+        if self.schedules_stale_at.is_some_and(|at| at <= now_ms) {
+            self.load_scheduled_runs();
+        }
     }
 
-    pub fn cycle_pane(&mut self) {
-        self.active_pane = match self.active_pane {
-            ActivePane::Profiles => ActivePane::Actions,
-            ActivePane::Actions => ActivePane::Results,
-            ActivePane::Results => ActivePane::Profiles,
+    fn load_scheduled_runs(&mut self) {
+        let conn = self.store.conn();
+        let (Ok(next_runs), Ok(offset)) = (
+            schedule::next_runs_by_cmdset(conn),
+            settings::get_display_offset(conn),
+        ) else {
+            return;
         };
+        self.schedules_stale_at = next_runs.values().min().copied();
+        self.scheduled_runs = next_runs
+            .into_iter()
+            .map(|(cmdset_id, ts)| (cmdset_id, util::format_timestamp_ms(ts, offset)))
+            .collect();
+    }
+
+    fn result_layouts_mut(&mut self) -> &mut ResultTabLayouts {
+        let conn = self.store.conn();
+        self.result_layouts
+            .get_or_insert_with(|| settings::get_result_tab_layouts(conn).unwrap_or_default())
     }
 
     pub fn next_profile(&mut self) -> Result<()> {
-        if !self.filtered.is_empty() {
-            self.profile_cursor = (self.profile_cursor + 1) % self.filtered.len();
+        let rows = self.profile_row_count();
+        if rows > 0 {
+            self.profile_cursor = (self.profile_cursor + 1) % rows;
         }
         if self.details_open {
             self.refresh_details()?;
@@ -474,11 +2771,12 @@ impl AppState {
     }
 
     pub fn prev_profile(&mut self) -> Result<()> {
-        if self.filtered.is_empty() {
+        let rows = self.profile_row_count();
+        if rows == 0 {
             return Ok(());
         }
         if self.profile_cursor == 0 {
-            self.profile_cursor = self.filtered.len() - 1;
+            self.profile_cursor = rows - 1;
         } else {
             self.profile_cursor -= 1;
         }
@@ -488,24 +2786,46 @@ impl AppState {
         Ok(())
     }
 
+    /// Selects row `row` of the profile list (a click); rows past the end are ignored.
+    pub fn select_profile_row(&mut self, row: usize) -> Result<()> {
+        if row >= self.profile_row_count() {
+            return Ok(());
+        }
+        self.profile_cursor = row;
+        if self.details_open {
+            self.refresh_details()?;
+        }
+        Ok(())
+    }
+
+    pub fn select_cmdset(&mut self, index: usize) {
+        self.ensure_cmdsets();
+        if index < self.cmdsets().len() {
+            self.cmdset_cursor = index;
+        }
+    }
+
     pub fn next_cmdset(&mut self) {
-        if !self.cmdsets.is_empty() {
-            self.cmdset_cursor = (self.cmdset_cursor + 1) % self.cmdsets.len();
+        self.ensure_cmdsets();
+        if !self.cmdsets().is_empty() {
+            self.cmdset_cursor = (self.cmdset_cursor + 1) % self.cmdsets().len();
         }
     }
 
     pub fn prev_cmdset(&mut self) {
-        if self.cmdsets.is_empty() {
+        self.ensure_cmdsets();
+        if self.cmdsets().is_empty() {
             return;
         }
         if self.cmdset_cursor == 0 {
-            self.cmdset_cursor = self.cmdsets.len() - 1;
+            self.cmdset_cursor = self.cmdsets().len() - 1;
         } else {
             self.cmdset_cursor -= 1;
         }
     }
 
     pub fn next_result_tab(&mut self) {
+        self.result_scroll = 0;
         self.result_tab = match self.result_tab {
             ResultTab::Stdout => ResultTab::Stderr,
             ResultTab::Stderr => ResultTab::Parsed,
@@ -515,6 +2835,7 @@ impl AppState {
     }
 
     pub fn prev_result_tab(&mut self) {
+        self.result_scroll = 0;
         self.result_tab = match self.result_tab {
             ResultTab::Stdout => ResultTab::Summary,
             ResultTab::Stderr => ResultTab::Stdout,
@@ -525,6 +2846,103 @@ impl AppState {
 
     pub fn set_result_tab(&mut self, tab: ResultTab) {
         self.result_tab = tab;
+        self.result_scroll = 0;
+    }
+
+    /// Steps of the running CommandSet, or of the last result, with their status.
+    pub fn result_steps(&self, now: Instant) -> Vec<StepRow> {
+        if let Some(run) = &self.live_run {
+            return run.step_rows(now);
+        }
+        let Some(result) = &self.last_result else {
+            return Vec::new();
+        };
+        result
+            .steps
+            .iter()
+            .map(|step| StepRow {
+                ord: step.ord,
+                cmd: step.cmd.clone(),
+                status: step.status,
+                elapsed_ms: step.duration_ms,
+            })
+            .collect()
+    }
+
+    pub fn result_step(&self) -> Option<usize> {
+        self.result_step
+    }
+
+    /// `,`/`.`: shows the output of the previous or next step, going through "all steps"
+    /// between the last step and the first.
+    pub fn cycle_result_step(&mut self, forward: bool) {
+        let len = self.result_steps(Instant::now()).len();
+        if len == 0 {
+            return;
+        }
+        self.result_step = match (self.result_step, forward) {
+            (None, true) => Some(0),
+            (None, false) => Some(len - 1),
+            (Some(step), true) if step + 1 < len => Some(step + 1),
+            (Some(step), false) if step > 0 => Some(step - 1),
+            (Some(_), _) => None,
+        };
+        self.result_scroll = 0;
+    }
+
+    /// Display preferences for `tab`; the summary tab has none.
+    /// `None` until the layouts are loaded; callers fall back to the defaults.
+    pub fn result_layout(&self, tab: ResultTab) -> Option<&ResultTabLayout> {
+        let layouts = self.result_layouts.as_ref()?;
+        match tab {
+            ResultTab::Stdout => Some(&layouts.stdout),
+            ResultTab::Stderr => Some(&layouts.stderr),
+            ResultTab::Parsed => Some(&layouts.parsed),
+            ResultTab::Summary => None,
+        }
+    }
+
+    pub fn toggle_result_wrap(&mut self) -> Result<()> {
+        self.update_result_layout("wrap", |layout| {
+            layout.wrap = !layout.wrap;
+            layout.wrap
+        })
+    }
+
+    pub fn toggle_result_timestamps(&mut self) -> Result<()> {
+        self.update_result_layout("timestamps", |layout| {
+            layout.timestamps = !layout.timestamps;
+            layout.timestamps
+        })
+    }
+
+    fn update_result_layout(
+        &mut self,
+        label: &str,
+        update: impl FnOnce(&mut ResultTabLayout) -> bool,
+    ) -> Result<()> {
+        let result_tab = self.result_tab;
+        let layouts = self.result_layouts_mut();
+        let (tab_name, layout) = match result_tab {
+            ResultTab::Stdout => ("stdout", &mut layouts.stdout),
+            ResultTab::Stderr => ("stderr", &mut layouts.stderr),
+            ResultTab::Parsed => ("parsed", &mut layouts.parsed),
+            ResultTab::Summary => {
+                self.status_message = Some("Summary tab has no layout options.".to_string());
+                return Ok(());
+            }
+        };
+        let enabled = update(layout);
+        if self.snapshot.is_none() {
+            if let Some(layouts) = &self.result_layouts {
+                settings::set_result_tab_layouts(self.store.conn(), layouts)?;
+            }
+        }
+        self.status_message = Some(format!(
+            "{tab_name} {label} {}.",
+            if enabled { "on" } else { "off" }
+        ));
+        Ok(())
     }
 
     pub fn cancel_confirm(&mut self) {
@@ -536,7 +2954,17 @@ impl AppState {
         let Some(confirm) = self.confirm.as_ref() else {
             return Ok(ConfirmedAction::Continue);
         };
-        if confirm.input != confirm.required_input {
+        if !confirm.input_ready() {
+            self.status_message =
+                Some("Scroll to the end of the target list before confirming.".to_string());
+            return Ok(ConfirmedAction::Continue);
+        }
+        if confirm.wants_reason() {
+            if confirm.input.trim().is_empty() {
+                self.status_message = Some("Type a reason to continue.".to_string());
+                return Ok(ConfirmedAction::Continue);
+            }
+        } else if confirm.input != confirm.required_input {
             self.status_message = Some(format!("Type '{}' to confirm.", confirm.required_input));
             return Ok(ConfirmedAction::Continue);
         }
@@ -560,15 +2988,164 @@ impl AppState {
                 self.confirmed_ssh_session_profile_id = Some(profile_id);
                 Ok(ConfirmedAction::OpenSshSession)
             }
+            PendingAction::OpenUnreachableSshSession { profile_id } => {
+                self.confirmed_ssh_session_profile_id = Some(profile_id.clone());
+                self.preflight_waived_profile_id = Some(profile_id);
+                Ok(ConfirmedAction::OpenSshSession)
+            }
+            PendingAction::OverrideMaintenance {
+                profile_id,
+                window_id,
+                next,
+            } => {
+                let profile = self
+                    .store
+                    .get(&profile_id)?
+                    .ok_or_else(|| anyhow!("profile not found: {profile_id}"))?;
+                let window = maintenance::get(self.store.conn(), window_id)?
+                    .ok_or_else(|| anyhow!("maintenance window not found: {window_id}"))?;
+                maintenance::record_override(
+                    self.store.conn(),
+                    &profile,
+                    &window,
+                    next.op(),
+                    confirm.input.trim(),
+                )?;
+                self.maintenance_override_profile_id = Some(profile_id);
+                match next {
+                    OverrideNext::Connect => Ok(ConfirmedAction::OpenSshSession),
+                    OverrideNext::Transfer(_) => Ok(ConfirmedAction::RunTransfer),
+                    OverrideNext::Run => {
+                        self.request_run()?;
+                        Ok(ConfirmedAction::Continue)
+                    }
+                }
+            }
+            PendingAction::TerminateSession { session_id } => {
+                self.status_message = Some(
+                    match active_session::terminate(self.store.conn(), session_id) {
+                        Ok(session) => {
+                            format!("Ended SSH session {session_id} on {}.", session.profile_id)
+                        }
+                        Err(err) => format!("Could not end session {session_id}: {err}"),
+                    },
+                );
+                self.active_sessions = active_session::list(self.store.conn())?;
+                Ok(ConfirmedAction::Continue)
+            }
+            PendingAction::RunTransfer { profile_id } => {
+                self.confirmed_transfer_profile_id = Some(profile_id);
+                Ok(ConfirmedAction::RunTransfer)
+            }
+        }
+    }
+
+    /// What confirming an action on `profile` asks to type: a fresh random token when
+    /// `confirm.token` covers its danger level, the profile id for other critical
+    /// profiles or when a `policy.danger` `confirm` rule sets `always`, or `None` when no
+    /// confirmation is needed.
+    fn confirmation_input(&self, profile: &Profile, always: bool) -> Result<Option<String>> {
+        if settings::confirm_token_required(self.store.conn(), profile)? {
+            return Ok(Some(util::confirmation_token()));
+        }
+        Ok((always || profile.danger_level == DangerLevel::Critical)
+            .then(|| profile.profile_id.clone()))
+    }
+
+    /// Applies `policy.danger` to `action` on `profile`. `None` means it may not go ahead
+    /// here, with the reason in the status line; the TUI cannot take a policy reason, so
+    /// those actions need `td --reason`.
+    fn policy_verdict(
+        &mut self,
+        profile: &Profile,
+        action: PolicyAction<'_>,
+    ) -> Result<Option<PolicyVerdict>> {
+        match self.policy_check(profile, &action) {
+            Ok(verdict) => Ok(Some(verdict)),
+            Err(err) => {
+                self.status_message = Some(err.to_string());
+                Ok(None)
+            }
+        }
+    }
+
+    fn policy_check(&self, profile: &Profile, action: &PolicyAction<'_>) -> Result<PolicyVerdict> {
+        let verdict = policy::check(self.store.conn(), profile, action, util::now_ms())?;
+        if verdict.reason {
+            return Err(anyhow!(
+                "Policy requires a reason for {} on {}; use td {} --reason.",
+                action.as_str(),
+                profile.profile_id,
+                action.as_str()
+            ));
         }
+        Ok(verdict)
+    }
+
+    /// Applies maintenance windows to `next` on `profile`. `false` means it stops here:
+    /// blocked, with the reason in the status line, or waiting for an override reason.
+    fn maintenance_clear(&mut self, profile: &Profile, next: OverrideNext) -> Result<bool> {
+        if self.maintenance_override_profile_id.take().as_deref()
+            == Some(profile.profile_id.as_str())
+        {
+            return Ok(true);
+        }
+        let Some(window) = maintenance::active_for(self.store.conn(), profile, util::now_ms())?
+        else {
+            return Ok(true);
+        };
+        if window.mode == WindowMode::Block {
+            self.status_message = Some(window.denied(profile, next.op()).to_string());
+            return Ok(false);
+        }
+        self.confirm = Some(ConfirmState {
+            message: format!(
+                "Maintenance window {} ({}) covers critical profile '{}'. Give a reason to {} anyway; it is logged.",
+                window.id, window.span, profile.profile_id, next.op()
+            ),
+            required_input: String::new(),
+            input: String::new(),
+            blast_radius: None,
+            action: PendingAction::OverrideMaintenance {
+                profile_id: profile.profile_id.clone(),
+                window_id: window.id,
+                next,
+            },
+        });
+        Ok(false)
+    }
+
+    fn cmdset_commands(&self, cmdset_id: &str) -> Result<Vec<String>> {
+        Ok(self
+            .cmdset_store
+            .list_steps(cmdset_id)?
+            .into_iter()
+            .map(|step| step.cmd)
+            .collect())
     }
 
     pub fn push_confirm_char(&mut self, ch: char) {
         if let Some(confirm) = &mut self.confirm {
+            if !confirm.input_ready() {
+                self.status_message =
+                    Some("Scroll to the end of the target list before typing.".to_string());
+                return;
+            }
             confirm.input.push(ch);
         }
     }
 
+    /// `Up`/`Down` and `PgUp`/`PgDn` in a bulk run confirmation: scrolls the target list.
+    pub fn scroll_confirm(&mut self, down: bool, page: bool) {
+        if let Some(radius) = self
+            .confirm
+            .as_mut()
+            .and_then(|confirm| confirm.blast_radius.as_mut())
+        {
+            radius.scroll(down, page);
+        }
+    }
+
     pub fn pop_confirm_char(&mut self) {
         if let Some(confirm) = &mut self.confirm {
             confirm.input.pop();
@@ -576,7 +3153,11 @@ impl AppState {
     }
 
     pub fn request_run(&mut self) -> Result<()> {
-        let (profile_id, cmdset_id, danger_level, profile_label) = {
+        if self.blocked_read_only("running CommandSets", true) {
+            return Ok(());
+        }
+        self.ensure_cmdsets();
+        let (profile, cmdset_id) = {
             let Some(profile) = self.selected_profile() else {
                 self.status_message =
                     Some("No profile selected; clear filters or add a profile.".to_string());
@@ -588,32 +3169,52 @@ impl AppState {
                 );
                 return Ok(());
             };
-            (
-                profile.profile_id.clone(),
-                cmdset.cmdset_id.clone(),
-                profile.danger_level,
-                format!("{}@{}:{}", profile.user, profile.host, profile.port),
-            )
+            (profile.clone(), cmdset.cmdset_id.clone())
+        };
+        if !self.maintenance_clear(&profile, OverrideNext::Run)? {
+            return Ok(());
+        }
+        let commands = self.cmdset_commands(&cmdset_id)?;
+        let Some(verdict) = self.policy_verdict(
+            &profile,
+            PolicyAction::Run {
+                cmdset_id: &cmdset_id,
+                commands: &commands,
+            },
+        )?
+        else {
+            return Ok(());
         };
-        if danger_level == DangerLevel::Critical {
+        if let Some(required_input) = self.confirmation_input(&profile, verdict.confirm)? {
             self.confirm = Some(ConfirmState {
                 message: format!(
-                    "Critical profile '{}'. Type the profile id to run CommandSet '{}' on {}.",
-                    profile_id, cmdset_id, profile_label
+                    "{} profile '{}'. {} to run CommandSet '{}' on {}@{}:{}.",
+                    danger_title(profile.danger_level),
+                    profile.profile_id,
+                    confirmation_ask(&profile, &required_input),
+                    cmdset_id,
+                    profile.user,
+                    profile.host,
+                    profile.port
                 ),
-                required_input: profile_id.clone(),
+                required_input,
                 input: String::new(),
+                blast_radius: None,
                 action: PendingAction::RunCmdSet {
-                    profile_id,
+                    profile_id: profile.profile_id,
                     cmdset_id,
                 },
             });
             return Ok(());
         }
-        self.execute_cmdset_run(&profile_id, &cmdset_id)
+        self.execute_cmdset_run(&profile.profile_id, &cmdset_id)
     }
 
     pub fn request_bulk_run(&mut self) -> Result<()> {
+        if self.blocked_read_only("running CommandSets", true) {
+            return Ok(());
+        }
+        self.ensure_cmdsets();
         if self.marked_profiles.is_empty() {
             self.status_message =
                 Some("No profiles marked; press Space on profiles before bulk run.".to_string());
@@ -630,23 +3231,70 @@ impl AppState {
         };
         let mut profile_ids: Vec<String> = self.marked_profiles.iter().cloned().collect();
         profile_ids.sort();
-        let mut critical_ids = Vec::new();
+        let mut confirm_ids = Vec::new();
+        let mut targets = Vec::new();
+        let mut token_required = false;
+        let commands = self.cmdset_commands(&cmdset_id)?;
         for profile_id in &profile_ids {
             if let Some(profile) = self.store.get(profile_id)? {
-                if profile.danger_level == DangerLevel::Critical {
-                    critical_ids.push(profile.profile_id);
+                let confirm_count = confirm_ids.len();
+                // Denied profiles fail in the summary; only the confirmation is asked here.
+                let policy_confirm = self
+                    .policy_check(
+                        &profile,
+                        &PolicyAction::Run {
+                            cmdset_id: &cmdset_id,
+                            commands: &commands,
+                        },
+                    )
+                    .is_ok_and(|verdict| verdict.confirm);
+                if settings::confirm_token_required(self.store.conn(), &profile)? {
+                    token_required = true;
+                    confirm_ids.push(profile.profile_id.clone());
+                } else if policy_confirm || profile.danger_level == DangerLevel::Critical {
+                    confirm_ids.push(profile.profile_id.clone());
                 }
+                targets.push(BulkTarget {
+                    needs_confirm: confirm_ids.len() > confirm_count,
+                    profile_id: profile.profile_id,
+                    name: profile.name,
+                    host: profile.host,
+                    group: profile.group,
+                    danger_level: profile.danger_level,
+                });
             }
         }
-        if !critical_ids.is_empty() {
-            let required = critical_ids.join(",");
+        if !confirm_ids.is_empty() {
+            let ids = confirm_ids.join(",");
+            let (message, required_input) = if token_required {
+                (
+                    format!(
+                        "Dangerous profiles in bulk run: {ids}. Type the code below to continue."
+                    ),
+                    util::confirmation_token(),
+                )
+            } else {
+                (
+                    format!(
+                        "Critical profiles in bulk run: {ids}. Type the comma-separated IDs exactly to continue."
+                    ),
+                    ids,
+                )
+            };
             self.confirm = Some(ConfirmState {
-                message: format!(
-                    "Critical profiles in bulk run: {}. Type the comma-separated IDs exactly to continue.",
-                    required
-                ),
-                required_input: required,
+                message,
+                required_input,
                 input: String::new(),
+                blast_radius: Some(BlastRadius {
+                    targets,
+                    commands: commands
+                        .iter()
+                        .take(BLAST_RADIUS_COMMANDS)
+                        .map(|cmd| util::mask_sensitive_tokens(cmd))
+                        .collect(),
+                    more_commands: commands.len().saturating_sub(BLAST_RADIUS_COMMANDS),
+                    scroll: 0,
+                }),
                 action: PendingAction::RunCmdSetBulk {
                     profile_ids,
                     cmdset_id: cmdset_id.clone(),
@@ -658,7 +3306,11 @@ impl AppState {
     }
 
     pub fn build_ssh_session_command(&mut self) -> Result<Option<SshSessionCommand>> {
+        if self.blocked_read_only("opening SSH sessions", true) {
+            return Ok(None);
+        }
         let confirmed_profile_id = self.confirmed_ssh_session_profile_id.take();
+        let preflight_waived_id = self.preflight_waived_profile_id.take();
         let Some(profile) = self.selected_profile().cloned() else {
             self.status_message =
                 Some("No profile selected; clear filters or add a profile.".to_string());
@@ -671,22 +3323,83 @@ impl AppState {
             ));
             return Ok(None);
         }
-        if profile.danger_level == DangerLevel::Critical
-            && confirmed_profile_id.as_deref() != Some(profile.profile_id.as_str())
+        let required_input = if confirmed_profile_id.as_deref() == Some(profile.profile_id.as_str())
         {
+            None
+        } else {
+            if !self.maintenance_clear(&profile, OverrideNext::Connect)? {
+                return Ok(None);
+            }
+            let Some(verdict) = self.policy_verdict(&profile, PolicyAction::Connect)? else {
+                return Ok(None);
+            };
+            self.confirmation_input(&profile, verdict.confirm)?
+        };
+        if let Some(required_input) = required_input {
+            let mut message = format!(
+                "{} profile '{}'. {} to open SSH session to {}@{}:{}.",
+                danger_title(profile.danger_level),
+                profile.profile_id,
+                confirmation_ask(&profile, &required_input),
+                profile.user,
+                profile.host,
+                profile.port
+            );
+            if let Ok(command) = self.ssh_command_line(&profile) {
+                message.push_str(&format!("\nCommand: {command}"));
+            }
             self.confirm = Some(ConfirmState {
-                message: format!(
-                    "Critical profile '{}'. Type the profile id to open SSH session to {}@{}:{}.",
-                    profile.profile_id, profile.user, profile.host, profile.port
-                ),
-                required_input: profile.profile_id.clone(),
+                message,
+                required_input,
                 input: String::new(),
+                blast_radius: None,
                 action: PendingAction::OpenSshSession {
                     profile_id: profile.profile_id,
                 },
             });
             return Ok(None);
         }
+        if preflight_waived_id.as_deref() != Some(profile.profile_id.as_str())
+            && settings::get_connect_preflight(self.store.conn(), &profile.profile_id)?
+        {
+            if let Some(reason) = tester::preflight(&profile) {
+                self.confirm = Some(ConfirmState {
+                    message: format!(
+                        "{}:{} looks unreachable ({reason}). Launch anyway?",
+                        profile.host, profile.port
+                    ),
+                    required_input: "yes".to_string(),
+                    input: String::new(),
+                    blast_radius: None,
+                    action: PendingAction::OpenUnreachableSshSession {
+                        profile_id: profile.profile_id,
+                    },
+                });
+                return Ok(None);
+            }
+        }
+        let context = HookContext {
+            op: "connect",
+            profile: &profile,
+            cmdset_id: None,
+        };
+        match hooks::run(
+            &self.store,
+            &self.cmdset_store,
+            HookPoint::PreConnect,
+            &context,
+            None,
+        ) {
+            Ok(failed) => {
+                if let Some(hook) = failed.first() {
+                    self.status_message = Some(format!("Warning: {}", hook.describe()));
+                }
+            }
+            Err(err) => {
+                self.status_message = Some(err.to_string());
+                return Ok(None);
+            }
+        }
         let invocation = match ssh::build_ssh_invocation(
             &self.store,
             SshInvocationRequest {
@@ -773,6 +3486,11 @@ impl AppState {
 
     pub fn refresh_after_settings(&mut self, saved: bool, session_log_enabled: bool) -> Result<()> {
         self.refresh()?;
+        if self.result_layouts.is_some() {
+            self.result_layouts = Some(settings::get_result_tab_layouts(self.store.conn())?);
+        }
+        self.profile_list = settings::get_profile_list_layout(self.store.conn())?;
+        self.idle_lock_after = settings::get_idle_lock_after(self.store.conn())?;
         let session_status = if session_log_enabled {
             "Session logging enabled."
         } else {
@@ -787,8 +3505,54 @@ impl AppState {
     }
 
     fn execute_cmdset_run(&mut self, profile_id: &str, cmdset_id: &str) -> Result<()> {
-        let result = self.try_execute_cmdset_run(profile_id, cmdset_id);
-        match result {
+        if self.live_run.is_some() {
+            self.status_message = Some("A CommandSet run is already in progress.".to_string());
+            return Ok(());
+        }
+        self.result_step = None;
+        // Snapshot stores live in shared memory the worker cannot reopen, so those runs
+        // stay on the UI thread.
+        if self.snapshot.is_some() {
+            let result = self.try_execute_cmdset_run(profile_id, cmdset_id);
+            self.finish_cmdset_run(profile_id, cmdset_id, result);
+            return Ok(());
+        }
+        match self.run_clients(profile_id) {
+            Ok(clients) => {
+                let planned = self
+                    .cmdset_store
+                    .list_steps(cmdset_id)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|step| (step.ord, step.cmd))
+                    .collect();
+                let run = LiveRun::start(profile_id, cmdset_id, planned, clients);
+                self.status_message = Some(run.progress());
+                self.live_run = Some(run);
+                self.last_summary = None;
+            }
+            Err(err) => self.finish_cmdset_run(profile_id, cmdset_id, Err(err)),
+        }
+        Ok(())
+    }
+
+    fn finish_cmdset_run(
+        &mut self,
+        profile_id: &str,
+        cmdset_id: &str,
+        result: Result<CmdSetRunResult>,
+    ) {
+        let report_run = match &result {
+            Ok(run) => ProfileRun::from_result(profile_id, run.clone()),
+            Err(err) => ProfileRun::from_error(profile_id, err.to_string()),
+        };
+        self.last_report = Some(RunReport::new(cmdset_id, vec![report_run]));
+        let notification = Notification::run(
+            profile_id,
+            cmdset_id,
+            result.as_ref().map_err(ToString::to_string),
+        );
+        match result.map(RunResult::from_cmdset_run) {
             Ok(run) => {
                 self.status_message = Some(format!(
                     "Run {} in {}ms (exit {}).",
@@ -796,23 +3560,48 @@ impl AppState {
                     run.duration_ms,
                     run.exit_code
                 ));
-                self.last_result = Some(run);
+                self.set_last_result(run);
                 self.last_summary = None;
             }
             Err(err) => {
                 self.status_message = Some(format!("Run failed: {err}"));
-                self.last_result = Some(RunResult::from_error(err));
+                self.set_last_result(RunResult::from_error(err));
                 self.last_summary = None;
             }
         }
-        Ok(())
+        self.notify(notification);
+    }
+
+    /// Sends `notification` on its configured channels from a background thread, so a
+    /// slow webhook never stalls the UI; delivery failures are only logged.
+    fn notify(&mut self, notification: Notification) {
+        match Notifier::for_event(
+            self.store.conn(),
+            notification.profile_id.as_deref(),
+            notification.severity,
+        ) {
+            Ok(notifier) if !notifier.is_empty() => {
+                std::thread::spawn(move || notifier.send(&notification));
+            }
+            Ok(_) => {}
+            Err(err) => {
+                let status = self.status_message.take().unwrap_or_default();
+                self.status_message = Some(format!("{status} Notification skipped: {err}"));
+            }
+        }
     }
 
     fn execute_cmdset_run_bulk(&mut self, profile_ids: &[String], cmdset_id: &str) -> Result<()> {
+        if self.live_run.is_some() {
+            self.status_message = Some("A CommandSet run is already in progress.".to_string());
+            return Ok(());
+        }
         let mut items = Vec::new();
+        let mut report_runs = Vec::new();
         for profile_id in profile_ids {
             let profile = self.store.get(profile_id)?;
             let Some(profile) = profile else {
+                report_runs.push(ProfileRun::from_error(profile_id, "profile not found"));
                 items.push(RunSummaryItem {
                     profile_id: profile_id.clone(),
                     profile_name: "(missing)".to_string(),
@@ -825,6 +3614,8 @@ impl AppState {
             let result = self.try_execute_cmdset_run(&profile.profile_id, cmdset_id);
             match result {
                 Ok(run) => {
+                    report_runs.push(ProfileRun::from_result(&profile.profile_id, run.clone()));
+                    let run = RunResult::from_cmdset_run(run);
                     items.push(RunSummaryItem {
                         profile_id: profile.profile_id.clone(),
                         profile_name: profile.name.clone(),
@@ -832,9 +3623,10 @@ impl AppState {
                         exit_code: Some(run.exit_code),
                         error: run.error.clone(),
                     });
-                    self.last_result = Some(run);
+                    self.set_last_result(run);
                 }
                 Err(err) => {
+                    report_runs.push(ProfileRun::from_error(&profile.profile_id, err.to_string()));
                     items.push(RunSummaryItem {
                         profile_id: profile.profile_id.clone(),
                         profile_name: profile.name.clone(),
@@ -845,9 +3637,15 @@ impl AppState {
                 }
             }
         }
+        self.last_report = Some(RunReport::new(cmdset_id, report_runs));
         let ok_count = items.iter().filter(|item| item.ok).count();
         let total = items.len();
         let fail_count = total - ok_count;
+        let failed: Vec<String> = items
+            .iter()
+            .filter(|item| !item.ok)
+            .map(|item| item.profile_id.clone())
+            .collect();
         self.last_summary = Some(RunSummary {
             total,
             ok_count,
@@ -858,35 +3656,84 @@ impl AppState {
         self.status_message = Some(format!(
             "Bulk run finished: {ok_count} ok, {fail_count} failed."
         ));
+        self.notify(Notification::summary(
+            "bulk_run", cmdset_id, ok_count, &failed,
+        ));
         Ok(())
     }
 
-    fn try_execute_cmdset_run(&mut self, profile_id: &str, cmdset_id: &str) -> Result<RunResult> {
+    fn try_execute_cmdset_run(
+        &mut self,
+        profile_id: &str,
+        cmdset_id: &str,
+    ) -> Result<CmdSetRunResult> {
+        let clients = self.run_clients(profile_id)?;
         let profile = self
             .store
             .get(profile_id)?
             .ok_or_else(|| anyhow!("profile not found: {profile_id}"))?;
-        if profile.profile_type != ProfileType::Ssh {
-            return Err(anyhow!("run only supports SSH profiles for now"));
-        }
-        let ssh = ssh::resolve_client_for(
-            ClientKind::Ssh,
-            profile.client_overrides.as_ref(),
-            self.store.conn(),
+        let commands = self.cmdset_commands(cmdset_id)?;
+        self.policy_check(
+            &profile,
+            &PolicyAction::Run {
+                cmdset_id,
+                commands: &commands,
+            },
         )?;
-        let auth = ssh::ssh_auth_context(self.store.conn())?;
-        let run = run_cmdset_ssh(
+        let context = HookContext {
+            op: "run",
+            profile: &profile,
+            cmdset_id: Some(cmdset_id),
+        };
+        let env = daemon_secret_env(&self.cmdset_store, cmdset_id)?;
+        let (mut run, failed_hooks) = hooks::around(
             &self.store,
             &self.cmdset_store,
-            CmdSetRunRequest {
-                profile_id,
-                cmdset_id,
-                ssh: &ssh,
-                ssh_auth_args: &auth.args,
+            &context,
+            || {
+                run_cmdset_ssh(
+                    &self.store,
+                    &self.cmdset_store,
+                    CmdSetRunRequest {
+                        profile_id,
+                        cmdset_id,
+                        ssh: &clients.ssh,
+                        ssh_auth_args: &clients.auth_args,
+                        scp: clients.scp.as_deref(),
+                        ssh_password: None,
+                        events: None,
+                        force_guard: false,
+                        env: &env,
+                    },
+                    |_| Ok(()),
+                )
             },
-            |_| Ok(()),
+            |run| run.ok,
         )?;
-        Ok(RunResult::from_cmdset_run(run))
+        for hook in failed_hooks {
+            run.stderr
+                .push_str(&format!("warning: {}\n", hook.describe()));
+        }
+        Ok(run)
+    }
+
+    fn run_clients(&self, profile_id: &str) -> Result<RunClients> {
+        let profile = self
+            .store
+            .get(profile_id)?
+            .ok_or_else(|| anyhow!("profile not found: {profile_id}"))?;
+        if profile.profile_type != ProfileType::Ssh {
+            return Err(anyhow!("run only supports SSH profiles for now"));
+        }
+        let ssh = ssh::resolve_client_for(ClientKind::Ssh, &profile, self.store.conn())?;
+        // Only needed when the CommandSet stages files; the runner reports a missing client.
+        let scp = ssh::resolve_client_for(ClientKind::Scp, &profile, self.store.conn()).ok();
+        let auth = ssh::ssh_profile_auth_context(self.store.conn(), profile_id)?;
+        Ok(RunClients {
+            ssh,
+            scp,
+            auth_args: auth.args,
+        })
     }
 
     pub fn command_preview(&self, limit: usize) -> Vec<String> {
@@ -900,11 +3747,7 @@ impl AppState {
         let Ok(steps) = steps else {
             return vec!["Failed to load command steps.".to_string()];
         };
-        let ssh = ssh::resolve_client_for(
-            ClientKind::Ssh,
-            profile.client_overrides.as_ref(),
-            self.store.conn(),
-        );
+        let ssh = ssh::resolve_client_for(ClientKind::Ssh, profile, self.store.conn());
         let Ok(ssh) = ssh else {
             return vec!["SSH client not found.".to_string()];
         };
@@ -914,7 +3757,7 @@ impl AppState {
             .into_iter()
             .take(limit)
             .map(|step| {
-                let cmd = mask_sensitive_tokens(&step.cmd);
+                let cmd = util::mask_sensitive_tokens(&step.cmd);
                 format!(
                     "{} {}@{} {}",
                     ssh::format_ssh_invocation(&ssh, profile.port, &auth_args),
@@ -937,11 +3780,15 @@ impl AppState {
     }
 
     fn refresh(&mut self) -> Result<()> {
-        self.filtered = self.store.list_filtered(&self.filters)?;
-        if self.filtered.is_empty() {
+        self.filtered = self.store.search(&self.filters)?;
+        self.preset_matches = forward_preset::matches_by_profile(self.store.conn())?;
+        self.update_group_tag_cache();
+        self.sort_filtered()?;
+        let rows = self.profile_row_count();
+        if rows == 0 {
             self.profile_cursor = 0;
-        } else if self.profile_cursor >= self.filtered.len() {
-            self.profile_cursor = self.filtered.len() - 1;
+        } else if self.profile_cursor >= rows {
+            self.profile_cursor = rows - 1;
         }
         if self.details_open {
             self.refresh_details()?;
@@ -949,7 +3796,40 @@ impl AppState {
         Ok(())
     }
 
+    /// Adds groups and tags of the listed profiles to the cached lists without reading
+    /// every profile again. An unfiltered listing replaces the lists outright, which also
+    /// drops values no profile uses any more.
+    fn update_group_tag_cache(&mut self) {
+        let filters = &self.filters;
+        let unfiltered = filters.group.is_none()
+            && filters.tags.is_empty()
+            && filters.profile_type.is_none()
+            && filters.danger.is_none()
+            && filters.query.is_none();
+        let groups = collect_groups(&self.filtered);
+        let tags = collect_tags(&self.filtered);
+        if unfiltered {
+            self.groups = groups;
+            self.tags = tags;
+        } else {
+            self.groups = merge_sorted(&self.groups, groups);
+            self.tags = merge_sorted(&self.tags, tags);
+        }
+    }
+
     pub fn toggle_mark(&mut self) {
+        if let Some(key) = self.selected_header() {
+            // A header marks (or, when all are marked, unmarks) every profile under it.
+            let ids = self.header_profile_ids(&key);
+            if ids.iter().all(|id| self.marked_profiles.contains(id)) {
+                for id in &ids {
+                    self.marked_profiles.remove(id);
+                }
+            } else {
+                self.marked_profiles.extend(ids);
+            }
+            return;
+        }
         let Some(profile_id) = self
             .selected_profile()
             .map(|profile| profile.profile_id.clone())
@@ -987,22 +3867,93 @@ impl AppState {
         }
     }
 
+    /// The masked SSH command line `s` starts for `profile`.
+    fn ssh_command_line(&self, profile: &Profile) -> Result<String, SshBuildError> {
+        ssh::build_ssh_invocation(
+            &self.store,
+            SshInvocationRequest {
+                profile_id: &profile.profile_id,
+                source: "tui",
+                mode: SshInvocationMode::Interactive,
+            },
+        )
+        .map(|invocation| ssh::format_command_line(&invocation.client_path, &invocation.args))
+    }
+
+    /// The client `s` would launch for `profile`, where it came from, and its command line.
+    fn connect_preview(&self, profile: &Profile) -> Vec<String> {
+        let overrides = profile.client_overrides.as_ref();
+        let mut lines: Vec<String> = overrides
+            .map(ClientOverrides::invalid_paths)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(kind, path)| format!("! {} override {path} is not a file", kind.as_str()))
+            .collect();
+        let kind = match profile.profile_type {
+            ProfileType::Ssh => ClientKind::Ssh,
+            ProfileType::Telnet => ClientKind::Telnet,
+            ProfileType::Serial => {
+                lines.push(format!(
+                    "Client: serial port {} at {} baud",
+                    profile.host, profile.port
+                ));
+                return lines;
+            }
+        };
+        let resolved = ssh::resolve_client_source_for(kind, profile, self.store.conn());
+        let Some(client) = resolved.as_ref().ok().and_then(|r| r.path.clone()) else {
+            lines.push(format!("Client: {} not found", kind.as_str()));
+            return lines;
+        };
+        if let Ok(resolved) = &resolved {
+            lines.push(format!(
+                "Client: {} ({})",
+                client.display(),
+                resolved.source
+            ));
+        }
+        let command = if kind == ClientKind::Ssh {
+            self.ssh_command_line(profile)
+                .unwrap_or_else(|err| ssh_build_status_message(&err))
+        } else {
+            ssh::format_command_line(
+                &client,
+                &[profile.host.clone().into(), profile.port.to_string().into()],
+            )
+        };
+        lines.push(format!("Command: {command}"));
+        lines
+    }
+
     fn refresh_details(&mut self) -> Result<()> {
         let Some(profile) = self.selected_profile() else {
             self.details_lines = vec!["No profile selected.".to_string()];
             self.details_scroll = 0;
             return Ok(());
         };
-        let env_name =
-            settings::get_current_env(self.store.conn())?.unwrap_or_else(|| "none".to_string());
+        let env_name = settings::get_current_env(self.store.conn())?;
         let details =
             settings::resolve_settings_for_profile(self.store.conn(), &profile.profile_id, None)?;
-        self.details_lines = format_resolved_details(
+        let offset = settings::get_display_offset(self.store.conn())?;
+        let last_connected = match profile.last_used_at {
+            Some(ts) => format!(
+                "{} ({})",
+                util::format_relative_ms(ts, util::now_ms()),
+                util::format_timestamp_ms(ts, offset)
+            ),
+            None => "never".to_string(),
+        };
+        let mut lines = format_resolved_details(
             profile.profile_id.as_str(),
             profile.name.as_str(),
-            &env_name,
+            env_name.as_deref(),
+            profile.group.as_deref(),
             &details,
         );
+        lines.insert(1, format!("Last connected: {last_connected}"));
+        let preview = self.connect_preview(profile);
+        lines.splice(2..2, preview);
+        self.details_lines = lines;
         self.details_scroll = 0;
         Ok(())
     }
@@ -1011,12 +3962,18 @@ impl AppState {
 fn format_resolved_details(
     profile_id: &str,
     profile_name: &str,
-    env_name: &str,
+    env_name: Option<&str>,
+    group: Option<&str>,
     details: &[ResolvedSettingDetail],
 ) -> Vec<String> {
     let mut lines = Vec::new();
     lines.push(format!("Profile: {profile_name} ({profile_id})"));
-    lines.push(format!("Current env: {env_name}"));
+    lines.push(format!("Current env: {}", env_name.unwrap_or("none")));
+    let mut order = vec!["command".to_string(), "profile".to_string()];
+    order.extend(group.map(|group| format!("group:{group}")));
+    order.extend(env_name.map(|env| format!("env:{env}")));
+    order.push("global".to_string());
+    lines.push(format!("Resolution: {}", order.join(" > ")));
     lines.push(String::new());
     for detail in details {
         let resolved = detail.resolved_value.as_deref().unwrap_or("(unset)");
@@ -1027,9 +3984,10 @@ fn format_resolved_details(
             .unwrap_or("none");
         lines.push(format!("{} = {} ({})", detail.key, resolved, source));
         lines.push(format!(
-            "  command={} profile={} env={} global={}",
+            "  command={} profile={} group={} env={} global={}",
             display_opt(detail.command_value.as_deref()),
             display_opt(detail.profile_value.as_deref()),
+            display_opt(detail.group_value.as_deref()),
             display_opt(detail.env_value.as_deref()),
             display_opt(detail.global_value.as_deref())
         ));
@@ -1090,6 +4048,45 @@ fn ssh_session_meta_json(
     meta
 }
 
+fn sort_by_usage(
+    profiles: &mut [Profile],
+    sort: ProfileSort,
+    usage: &HashMap<String, ProfileUsage>,
+) {
+    let usage_of = |profile: &Profile| usage.get(&profile.profile_id).copied().unwrap_or_default();
+    match sort {
+        ProfileSort::Name => {}
+        ProfileSort::LastUsed => profiles.sort_by(|a, b| {
+            b.last_used_at
+                .cmp(&a.last_used_at)
+                .then_with(|| usage_of(b).frecency.cmp(&usage_of(a).frecency))
+        }),
+        ProfileSort::MostUsed => profiles.sort_by(|a, b| {
+            usage_of(b)
+                .frecency
+                .cmp(&usage_of(a).frecency)
+                .then_with(|| usage_of(b).uses.cmp(&usage_of(a).uses))
+                .then_with(|| b.last_used_at.cmp(&a.last_used_at))
+        }),
+    }
+}
+
+fn danger_title(level: DangerLevel) -> &'static str {
+    match level {
+        DangerLevel::Normal => "Normal",
+        DangerLevel::High => "High-risk",
+        DangerLevel::Critical => "Critical",
+    }
+}
+
+fn confirmation_ask(profile: &Profile, required_input: &str) -> &'static str {
+    if required_input == profile.profile_id {
+        "Type the profile id"
+    } else {
+        "Type the code below"
+    }
+}
+
 fn collect_groups(profiles: &[Profile]) -> Vec<String> {
     let mut set = BTreeSet::new();
     for profile in profiles {
@@ -1100,6 +4097,12 @@ fn collect_groups(profiles: &[Profile]) -> Vec<String> {
     set.into_iter().collect()
 }
 
+fn merge_sorted(cached: &[String], seen: Vec<String>) -> Vec<String> {
+    let mut set = cached.iter().cloned().collect::<BTreeSet<_>>();
+    set.extend(seen);
+    set.into_iter().collect()
+}
+
 fn collect_tags(profiles: &[Profile]) -> Vec<String> {
     let mut set = BTreeSet::new();
     for profile in profiles {
@@ -1110,50 +4113,45 @@ fn collect_tags(profiles: &[Profile]) -> Vec<String> {
     set.into_iter().collect()
 }
 
-fn mask_sensitive_tokens(input: &str) -> String {
-    let mut tokens: Vec<String> = input
-        .split_whitespace()
-        .map(|token| token.to_string())
-        .collect();
-    let mut idx = 0;
-    while idx < tokens.len() {
-        let token = tokens[idx].clone();
-        if is_sensitive_flag(&token) && idx + 1 < tokens.len() {
-            tokens[idx + 1] = "****".to_string();
-            idx += 2;
-            continue;
-        }
-        if let Some(masked) = mask_sensitive_kv(&token) {
-            tokens[idx] = masked;
-        }
-        idx += 1;
-    }
-    tokens.join(" ")
+/// Lines of one output stream: every step's, or only `only`'s when a step is selected.
+fn output_lines(
+    result: &RunResult,
+    layout: &ResultTabLayout,
+    only: Option<usize>,
+    select: impl Fn(&StepOutput) -> &String,
+    combined: &str,
+) -> Vec<String> {
+    let steps = match only {
+        Some(index) => result.steps.get(index..=index).unwrap_or_default(),
+        None if !layout.timestamps || result.steps.is_empty() => {
+            return combined.lines().map(str::to_string).collect();
+        }
+        None => &result.steps,
+    };
+    steps
+        .iter()
+        .flat_map(|step| {
+            let stamp = format_offset(step.offset_ms);
+            select(step)
+                .lines()
+                .map(|line| {
+                    if layout.timestamps {
+                        format!("[{stamp}] {line}")
+                    } else {
+                        line.to_string()
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
 }
 
-fn is_sensitive_flag(token: &str) -> bool {
-    matches!(
-        token,
-        "--password" | "--pass" | "--token" | "--secret" | "--api-key" | "--apikey" | "--key"
-    )
+fn format_offset(offset_ms: i64) -> String {
+    format!("+{}.{:03}s", offset_ms / 1000, offset_ms % 1000)
 }
 
-fn mask_sensitive_kv(token: &str) -> Option<String> {
-    let (key, value) = token.split_once('=')?;
-    if value.is_empty() {
-        return None;
-    }
-    let lowered = key.to_lowercase();
-    if lowered.contains("password")
-        || lowered.contains("secret")
-        || lowered.contains("token")
-        || lowered.contains("apikey")
-        || lowered.contains("api_key")
-    {
-        Some(format!("{key}=****"))
-    } else {
-        None
-    }
+fn contains_ignore_case(line: &str, query: &str) -> bool {
+    line.to_lowercase().contains(&query.to_lowercase())
 }
 
 #[cfg(test)]
@@ -1162,9 +4160,10 @@ mod tests {
     use std::ffi::OsStr;
     use std::fs;
 
-    use tdcore::cmdset::CmdSetStore;
+    use tdcore::cmdset::{CmdSetStore, NewCmdStep, StepOnError};
     use tdcore::db;
     use tdcore::doctor::ClientOverrides;
+    use tdcore::parser::ParserSpec;
     use tdcore::profile::{NewProfile, ProfileStore};
     use tdcore::settings;
 
@@ -1229,161 +4228,862 @@ mod tests {
     }
 
     #[test]
-    fn builds_selected_ssh_session_command() {
-        let fake_ssh = fake_ssh_path("build");
-        let mut profile = base_profile(ProfileType::Ssh);
-        profile.client_overrides = Some(ClientOverrides {
-            ssh: Some(fake_ssh.to_string_lossy().into_owned()),
-            ..Default::default()
-        });
-        let mut state = state_with_profiles(vec![profile]);
+    fn builds_selected_ssh_session_command() {
+        let fake_ssh = fake_ssh_path("build");
+        let mut profile = base_profile(ProfileType::Ssh);
+        profile.client_overrides = Some(ClientOverrides {
+            ssh: Some(fake_ssh.to_string_lossy().into_owned()),
+            ..Default::default()
+        });
+        let mut state = state_with_profiles(vec![profile]);
+
+        let command = state
+            .build_ssh_session_command()
+            .unwrap()
+            .expect("ssh session command");
+
+        assert_eq!(command.profile_id, "p_test");
+        assert_eq!(command.host, "example.com");
+        assert_eq!(command.port, 2222);
+        assert_eq!(command.user, "alice");
+        assert_eq!(command.profile_type, ProfileType::Ssh);
+        assert_eq!(command.executable, fake_ssh);
+        assert_eq!(command.args[0], OsStr::new("-p"));
+        assert_eq!(command.args[1], OsStr::new("2222"));
+        assert_eq!(
+            command.args.last().unwrap(),
+            OsStr::new("alice@example.com")
+        );
+        let _ = fs::remove_file(command.executable);
+    }
+
+    #[test]
+    fn details_show_the_client_and_connect_command() {
+        let fake_ssh = fake_ssh_path("details");
+        let mut profile = base_profile(ProfileType::Ssh);
+        profile.client_overrides = Some(ClientOverrides {
+            ssh: Some(fake_ssh.to_string_lossy().into_owned()),
+            scp: Some("/nonexistent/scp".to_string()),
+            ..Default::default()
+        });
+        let mut state = state_with_profiles(vec![profile]);
+        state.refresh_details().unwrap();
+
+        let lines = state.details_lines();
+        assert!(lines.contains(&"! scp override /nonexistent/scp is not a file".to_string()));
+        assert!(lines.contains(&format!(
+            "Client: {} (profile override)",
+            fake_ssh.display()
+        )));
+        assert!(lines.iter().any(|line| line.starts_with("Command: ")
+            && line.contains(" -p 2222 ")
+            && line.ends_with(" alice@example.com")));
+        let _ = fs::remove_file(fake_ssh);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn explicit_conpty_backend_flows_into_tui_ssh_session_plan() {
+        let fake_ssh = fake_ssh_path("conpty-plan");
+        let mut profile = base_profile(ProfileType::Ssh);
+        profile.client_overrides = Some(ClientOverrides {
+            ssh: Some(fake_ssh.to_string_lossy().into_owned()),
+            ..Default::default()
+        });
+        let mut state = state_with_profiles(vec![profile]);
+        settings::set_setting(
+            state.store.conn(),
+            session_log::SESSION_LOG_ENABLED_KEY,
+            "true",
+        )
+        .unwrap();
+        settings::set_setting(
+            state.store.conn(),
+            session_log::SESSION_LOG_BACKEND_KEY,
+            session_log::SESSION_LOG_BACKEND_CONPTY,
+        )
+        .unwrap();
+
+        let command = state
+            .build_ssh_session_command()
+            .unwrap()
+            .expect("ssh session command");
+
+        assert!(matches!(
+            command.session_log_plan,
+            SessionLogPlan::Conpty { .. }
+        ));
+        let _ = fs::remove_file(command.executable);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_auto_backend_does_not_flow_into_tui_conpty_plan() {
+        let fake_ssh = fake_ssh_path("auto-plan");
+        let mut profile = base_profile(ProfileType::Ssh);
+        profile.client_overrides = Some(ClientOverrides {
+            ssh: Some(fake_ssh.to_string_lossy().into_owned()),
+            ..Default::default()
+        });
+        let mut state = state_with_profiles(vec![profile]);
+        settings::set_setting(
+            state.store.conn(),
+            session_log::SESSION_LOG_ENABLED_KEY,
+            "true",
+        )
+        .unwrap();
+        settings::set_setting(
+            state.store.conn(),
+            session_log::SESSION_LOG_BACKEND_KEY,
+            session_log::SESSION_LOG_BACKEND_AUTO,
+        )
+        .unwrap();
+
+        let command = state
+            .build_ssh_session_command()
+            .unwrap()
+            .expect("ssh session command");
+
+        assert!(matches!(
+            command.session_log_plan,
+            SessionLogPlan::NoLog { .. }
+        ));
+        let _ = fs::remove_file(command.executable);
+    }
+
+    #[test]
+    fn rejects_ssh_session_when_no_profile_is_selected() {
+        let mut state = state_with_profiles(Vec::new());
+
+        let command = state.build_ssh_session_command().unwrap();
+
+        assert!(command.is_none());
+        assert_eq!(
+            state.status_message(),
+            Some("No profile selected; clear filters or add a profile.")
+        );
+    }
+
+    #[test]
+    fn rejects_ssh_session_for_non_ssh_profile() {
+        let mut state = state_with_profiles(vec![base_profile(ProfileType::Telnet)]);
+
+        let command = state.build_ssh_session_command().unwrap();
+
+        assert!(command.is_none());
+        assert_eq!(
+            state.status_message(),
+            Some("Selected profile is telnet; SSH session requires an SSH profile.")
+        );
+    }
+
+    #[test]
+    fn critical_ssh_session_requires_confirmation() {
+        let fake_ssh = fake_ssh_path("critical");
+        let mut profile = base_profile(ProfileType::Ssh);
+        profile.danger_level = DangerLevel::Critical;
+        profile.client_overrides = Some(ClientOverrides {
+            ssh: Some(fake_ssh.to_string_lossy().into_owned()),
+            ..Default::default()
+        });
+        let mut state = state_with_profiles(vec![profile]);
+
+        let command = state.build_ssh_session_command().unwrap();
+
+        assert!(command.is_none());
+        let message = &state.confirm_state().unwrap().message;
+        assert!(message.contains(&format!("\nCommand: {} -p 2222 ", fake_ssh.display())));
+        assert!(message.ends_with(" alice@example.com"));
+        for ch in "p_test".chars() {
+            state.push_confirm_char(ch);
+        }
+        assert_eq!(
+            state.confirm_action().unwrap(),
+            ConfirmedAction::OpenSshSession
+        );
+
+        let command = state
+            .build_ssh_session_command()
+            .unwrap()
+            .expect("confirmed ssh session command");
+
+        assert_eq!(command.profile_id, "p_test");
+        let _ = fs::remove_file(command.executable);
+    }
+
+    #[test]
+    fn confirm_token_setting_replaces_profile_id_with_random_code() {
+        let mut profile = base_profile(ProfileType::Ssh);
+        profile.danger_level = DangerLevel::High;
+        let mut state = state_with_profiles(vec![profile]);
+        settings::set_setting(state.store.conn(), settings::CONFIRM_TOKEN_KEY, "high").unwrap();
+
+        state.build_ssh_session_command().unwrap();
+
+        let confirm = state
+            .confirm_state()
+            .expect("high profile now asks for a token");
+        let token = confirm.required_input.clone();
+        assert_eq!(token.len(), 4);
+        assert!(confirm.message.contains("Type the code below"));
+        for ch in "p_test".chars() {
+            state.push_confirm_char(ch);
+        }
+        assert_eq!(state.confirm_action().unwrap(), ConfirmedAction::Continue);
+        assert!(state.confirm_state().is_some());
+        for _ in 0.."p_test".len() {
+            state.pop_confirm_char();
+        }
+        for ch in token.chars() {
+            state.push_confirm_char(ch);
+        }
+        assert_eq!(
+            state.confirm_action().unwrap(),
+            ConfirmedAction::OpenSshSession
+        );
+    }
+
+    #[test]
+    fn bulk_run_confirmation_lists_every_target_before_typing() {
+        let profiles = (1..=10)
+            .map(|n| NewProfile {
+                profile_id: Some(format!("p_{n:02}")),
+                name: format!("web {n}"),
+                host: format!("web{n}.example.com"),
+                danger_level: if n == 3 {
+                    DangerLevel::Critical
+                } else {
+                    DangerLevel::Normal
+                },
+                ..base_profile(ProfileType::Ssh)
+            })
+            .collect();
+        let mut state = state_with_profiles(profiles);
+        state
+            .cmdset_store
+            .insert(NewCmdSet {
+                cmdset_id: Some("c_deploy".to_string()),
+                name: "Deploy".to_string(),
+                vars: None,
+                steps: (1..=7)
+                    .map(|n| NewCmdStep {
+                        cmd: format!("step{n} --token=abc"),
+                        timeout_ms: None,
+                        on_error: StepOnError::Stop,
+                        parser_spec: ParserSpec::Raw,
+                    })
+                    .collect(),
+            })
+            .unwrap();
+        state.marked_profiles = state
+            .filtered
+            .iter()
+            .map(|p| p.profile_id.clone())
+            .collect();
+
+        state.request_bulk_run().unwrap();
+        let radius = state.confirm_state().unwrap().blast_radius.clone().unwrap();
+        assert_eq!(radius.targets.len(), 10);
+        let flagged = radius.targets.iter().filter(|t| t.needs_confirm);
+        assert_eq!(
+            flagged.map(|t| t.profile_id.as_str()).collect::<Vec<_>>(),
+            ["p_03"]
+        );
+        assert_eq!(radius.targets[0].host, "web1.example.com");
+        assert_eq!(radius.commands.len(), BLAST_RADIUS_COMMANDS);
+        assert_eq!(radius.commands[0], "step1 --token=****");
+        assert_eq!(radius.more_commands, 2);
+
+        state.push_confirm_char('p');
+        assert!(
+            state.confirm_state().unwrap().input.is_empty(),
+            "not reviewed yet"
+        );
+        assert_eq!(state.confirm_action().unwrap(), ConfirmedAction::Continue);
+        state.scroll_confirm(true, true);
+        assert!(state.confirm_state().unwrap().input_ready());
+        for ch in "p_03".chars() {
+            state.push_confirm_char(ch);
+        }
+        assert_eq!(state.confirm_state().unwrap().input, "p_03");
+    }
+
+    #[test]
+    fn toggling_result_wrap_persists_per_tab() {
+        let mut state = state_with_profiles(Vec::new());
+        state.set_result_tab(ResultTab::Parsed);
+
+        state.toggle_result_wrap().unwrap();
+
+        assert!(!state.result_layout(ResultTab::Parsed).unwrap().wrap);
+        assert!(state.result_layout(ResultTab::Stdout).unwrap().wrap);
+        let stored = settings::get_result_tab_layouts(state.store.conn()).unwrap();
+        assert!(!stored.parsed.wrap);
+        assert!(stored.stdout.wrap);
+    }
+
+    #[test]
+    fn scrolls_and_searches_result_output() {
+        let mut state = state_with_profiles(Vec::new());
+        let stdout = (1..=30)
+            .map(|n| {
+                if n % 10 == 0 {
+                    format!("ERROR {n}")
+                } else {
+                    format!("line {n}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        state.set_last_result(RunResult {
+            ok: true,
+            exit_code: 0,
+            duration_ms: 1,
+            stdout,
+            stderr: String::new(),
+            parsed_pretty: String::new(),
+            error: None,
+            steps: Vec::new(),
+        });
+
+        state.scroll_results_down(true);
+        state.scroll_results_down(false);
+        assert_eq!(state.result_scroll(), 11);
+        for _ in 0..5 {
+            state.scroll_results_down(true);
+        }
+        assert_eq!(state.result_scroll(), 29, "clamped to the last line");
+        state.scroll_results_up(true);
+        assert_eq!(state.result_scroll(), 19);
+
+        state.enter_result_search();
+        for ch in "error".chars() {
+            state.push_result_search_char(ch);
+        }
+        state.submit_result_search();
+        assert_eq!(state.mode(), InputMode::Normal);
+        assert_eq!(state.result_scroll(), 19);
+        assert_eq!(
+            state.status_message(),
+            Some("Match 2/3 for 'error' (line 20).")
+        );
+        state.next_result_match();
+        assert_eq!(state.result_scroll(), 29);
+        state.next_result_match();
+        assert_eq!(state.result_scroll(), 9, "wraps to the first match");
+
+        state.set_result_tab(ResultTab::Stderr);
+        assert_eq!(state.result_scroll(), 0);
+        assert_eq!(state.result_buffer().unwrap().lines, Vec::<String>::new());
+        state.cancel_result_search();
+        assert_eq!(state.result_query(), None);
+    }
+
+    #[test]
+    fn step_list_shows_status_and_one_step_output_at_a_time() {
+        let mut state = state_with_profiles(Vec::new());
+        state.cycle_result_step(true);
+        assert_eq!(state.result_step(), None, "nothing to pick before a run");
+        let step =
+            |ord: i64, cmd: &str, ok: bool, stdout: &str| tdcore::cmdset_runner::CmdStepRunResult {
+                ord,
+                cmd: cmd.to_string(),
+                ok,
+                exit_code: if ok { 0 } else { 1 },
+                duration_ms: 1200,
+                stdout: stdout.to_string(),
+                stderr: String::new(),
+                parsed: serde_json::Value::Null,
+            };
+        state.set_last_result(RunResult::from_cmdset_run(CmdSetRunResult {
+            ok: false,
+            exit_code: 1,
+            duration_ms: 2400,
+            stdout: "up 3 days\nno space\n".to_string(),
+            stderr: String::new(),
+            steps: vec![
+                step(1, "uptime", true, "up 3 days\n"),
+                step(2, "df -h", false, "no space\n"),
+            ],
+        }));
+
+        let rows = state.result_steps(Instant::now());
+        assert_eq!(
+            rows.iter()
+                .map(|row| (row.cmd.as_str(), row.status, row.elapsed_ms))
+                .collect::<Vec<_>>(),
+            [
+                ("uptime", StepStatus::Ok, Some(1200)),
+                ("df -h", StepStatus::Failed, Some(1200)),
+            ]
+        );
+        assert_eq!(state.result_buffer().unwrap().lines.len(), 2);
+        state.cycle_result_step(false);
+        assert_eq!(state.result_step(), Some(1));
+        assert_eq!(state.result_buffer().unwrap().lines, ["no space"]);
+        state.cycle_result_step(true);
+        assert_eq!(state.result_step(), None, "back to every step");
+        state.cycle_result_step(true);
+        assert_eq!(state.result_buffer().unwrap().lines, ["up 3 days"]);
+    }
+
+    #[test]
+    fn bulk_edit_previews_then_applies_to_marked_profiles() {
+        let mut state = state_with_profiles(vec![
+            base_profile(ProfileType::Ssh),
+            NewProfile {
+                profile_id: Some("p_other".to_string()),
+                name: "Other".to_string(),
+                ..base_profile(ProfileType::Ssh)
+            },
+        ]);
+        state.open_bulk_edit();
+        assert!(state.bulk_edit_state().is_none(), "needs marked profiles");
+
+        state.toggle_mark();
+        state.next_profile().unwrap();
+        state.toggle_mark();
+        state.open_bulk_edit();
+        state.submit_bulk_edit().unwrap();
+        for ch in "ops".chars() {
+            state.push_bulk_edit_char(ch);
+        }
+        state.submit_bulk_edit().unwrap();
+        let Some(BulkEditStage::Preview { rows, .. }) =
+            state.bulk_edit_state().map(|edit| &edit.stage)
+        else {
+            panic!("expected preview");
+        };
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|row| row.after == "ops"));
+
+        state.submit_bulk_edit().unwrap();
+        assert!(state.bulk_edit_state().is_none());
+        for profile_id in ["p_test", "p_other"] {
+            let profile = state.store.get(profile_id).unwrap().unwrap();
+            assert_eq!(profile.group.as_deref(), Some("ops"));
+        }
+        assert_eq!(state.groups, vec!["ops".to_string()]);
+
+        state.undo_bulk_edit().unwrap();
+        for profile_id in ["p_test", "p_other"] {
+            let profile = state.store.get(profile_id).unwrap().unwrap();
+            assert_eq!(profile.group, None);
+        }
+        assert!(state.groups.is_empty());
+        state.undo_bulk_edit().unwrap();
+        assert_eq!(state.status_message(), Some("Nothing to undo."));
+    }
+
+    #[test]
+    fn history_reopens_stored_run_output() {
+        let mut state = state_with_profiles(vec![base_profile(ProfileType::Ssh)]);
+        state.open_history().unwrap();
+        assert!(state.history_state().is_none(), "no entries yet");
+
+        let conn = state.store.conn();
+        run_output::record(
+            conn,
+            &run_output::NewRunOutput {
+                profile_id: "p_test",
+                source: "run:c_check",
+                ord: Some(1),
+                cmd: "uptime",
+                exit_code: Some(0),
+                stdout: "up 3 days\n",
+                stderr: "",
+                op_log_id: None,
+            },
+        )
+        .unwrap();
+        for (op, meta) in [
+            ("run", serde_json::json!({ "cmdset_id": "c_check" })),
+            ("connect", serde_json::json!({})),
+        ] {
+            oplog::log_operation(
+                conn,
+                OpLogEntry {
+                    op: op.to_string(),
+                    profile_id: Some("p_test".to_string()),
+                    client_used: None,
+                    ok: true,
+                    exit_code: Some(0),
+                    duration_ms: Some(10),
+                    meta_json: Some(meta),
+                },
+            )
+            .unwrap();
+        }
+
+        state.open_history().unwrap();
+        assert_eq!(state.history_state().unwrap().entries.len(), 2);
+        state.history_move(true).unwrap();
+        state.history_move(true).unwrap();
+        assert_eq!(state.history_state().unwrap().cursor, 1);
+        let picked = &state.history_state().unwrap().entries[1].record;
+        assert_eq!(picked.op, "run");
+
+        state.reopen_history_entry().unwrap();
+        assert!(state.history_state().is_none());
+        let result = state.last_result().unwrap();
+        assert_eq!(result.stdout, "up 3 days\n");
+        assert_eq!(result.steps.len(), 1);
+        assert!(result.parsed_pretty.contains("c_check"));
+    }
+
+    #[test]
+    fn history_pages_filters_and_retries_entries() {
+        let mut state = state_with_profiles(vec![base_profile(ProfileType::Ssh)]);
+        let conn = state.store.conn();
+        for index in 0..HISTORY_LIMIT + 10 {
+            let (op, meta) = if index == HISTORY_LIMIT + 9 {
+                ("connect", serde_json::json!({}))
+            } else {
+                ("run", serde_json::json!({ "cmdset_id": "c_gone" }))
+            };
+            oplog::log_operation(
+                conn,
+                OpLogEntry {
+                    op: op.to_string(),
+                    profile_id: Some("p_test".to_string()),
+                    client_used: None,
+                    ok: index % 10 != 0,
+                    exit_code: None,
+                    duration_ms: None,
+                    meta_json: Some(meta),
+                },
+            )
+            .unwrap();
+        }
 
-        let command = state
-            .build_ssh_session_command()
+        state.open_history().unwrap();
+        assert_eq!(state.history_state().unwrap().entries.len(), HISTORY_LIMIT);
+        for _ in 0..HISTORY_LIMIT {
+            state.history_move(true).unwrap();
+        }
+        let history = state.history_state().unwrap();
+        assert_eq!(history.entries.len(), HISTORY_LIMIT + 10);
+        assert!(history.exhausted);
+        assert_eq!(history.cursor, HISTORY_LIMIT);
+
+        state.cycle_history_filter(false).unwrap();
+        let history = state.history_state().unwrap();
+        assert_eq!(history.result, Some(false));
+        assert_eq!(history.entries.len(), 6);
+        assert!(history.entries.iter().all(|entry| !entry.record.ok));
+        state.cycle_history_filter(true).unwrap();
+        assert_eq!(state.history_state().unwrap().period, HistoryPeriod::Day);
+        assert_eq!(state.history_state().unwrap().entries.len(), 6);
+
+        assert!(!state.retry_history_entry().unwrap());
+        assert!(state
+            .status_message()
             .unwrap()
-            .expect("ssh session command");
-
-        assert_eq!(command.profile_id, "p_test");
-        assert_eq!(command.host, "example.com");
-        assert_eq!(command.port, 2222);
-        assert_eq!(command.user, "alice");
-        assert_eq!(command.profile_type, ProfileType::Ssh);
-        assert_eq!(command.executable, fake_ssh);
-        assert_eq!(command.args[0], OsStr::new("-p"));
-        assert_eq!(command.args[1], OsStr::new("2222"));
+            .contains("CommandSet c_gone no longer exists"));
+        state.cycle_history_filter(false).unwrap();
+        state.cycle_history_filter(false).unwrap();
         assert_eq!(
-            command.args.last().unwrap(),
-            OsStr::new("alice@example.com")
+            state.history_state().unwrap().entries[0].record.op,
+            "connect"
         );
-        let _ = fs::remove_file(command.executable);
+        assert!(state.retry_history_entry().unwrap());
+        assert!(state.history_state().is_none());
     }
 
-    #[cfg(windows)]
     #[test]
-    fn explicit_conpty_backend_flows_into_tui_ssh_session_plan() {
-        let fake_ssh = fake_ssh_path("conpty-plan");
-        let mut profile = base_profile(ProfileType::Ssh);
-        profile.client_overrides = Some(ClientOverrides {
-            ssh: Some(fake_ssh.to_string_lossy().into_owned()),
-            ..Default::default()
-        });
-        let mut state = state_with_profiles(vec![profile]);
-        settings::set_setting(
-            state.store.conn(),
-            session_log::SESSION_LOG_ENABLED_KEY,
-            "true",
-        )
-        .unwrap();
+    fn toggling_wide_profile_list_keeps_configured_columns() {
+        let mut state = state_with_profiles(Vec::new());
         settings::set_setting(
             state.store.conn(),
-            session_log::SESSION_LOG_BACKEND_KEY,
-            session_log::SESSION_LOG_BACKEND_CONPTY,
+            settings::PROFILE_LIST_LAYOUT_KEY,
+            r#"{"columns":["group"],"max_width":8}"#,
         )
         .unwrap();
+        state.refresh_after_settings(true, false).unwrap();
+        assert_eq!(state.profile_list_layout().active_max_width(), Some(8));
 
-        let command = state
-            .build_ssh_session_command()
-            .unwrap()
-            .expect("ssh session command");
+        state.toggle_profile_list_wide().unwrap();
 
-        assert!(matches!(
-            command.session_log_plan,
-            SessionLogPlan::Conpty { .. }
-        ));
-        let _ = fs::remove_file(command.executable);
+        let stored = settings::get_profile_list_layout(state.store.conn()).unwrap();
+        assert!(stored.wide);
+        assert_eq!(stored.columns, vec![settings::ProfileColumn::Group]);
+        assert_eq!(state.profile_list_layout().active_max_width(), None);
     }
 
-    #[cfg(windows)]
     #[test]
-    fn windows_auto_backend_does_not_flow_into_tui_conpty_plan() {
-        let fake_ssh = fake_ssh_path("auto-plan");
-        let mut profile = base_profile(ProfileType::Ssh);
-        profile.client_overrides = Some(ClientOverrides {
-            ssh: Some(fake_ssh.to_string_lossy().into_owned()),
-            ..Default::default()
-        });
-        let mut state = state_with_profiles(vec![profile]);
-        settings::set_setting(
-            state.store.conn(),
-            session_log::SESSION_LOG_ENABLED_KEY,
-            "true",
-        )
-        .unwrap();
+    fn pane_layout_resizes_within_bounds_and_restores_on_launch() {
+        let conn = db::init_in_memory().unwrap();
         settings::set_setting(
-            state.store.conn(),
-            session_log::SESSION_LOG_BACKEND_KEY,
-            session_log::SESSION_LOG_BACKEND_AUTO,
+            &conn,
+            settings::PANE_LAYOUT_KEY,
+            r#"{"profiles_width":25,"result_tab":"summary"}"#,
         )
         .unwrap();
+        let mut state = AppState::new(ProfileStore::new(conn), empty_cmdset_store()).unwrap();
+        assert_eq!(state.pane_layout().profiles_width, 25);
+        assert_eq!(state.pane_layout().actions_height, 45);
+        assert_eq!(state.result_tab(), ResultTab::Summary);
 
-        let command = state
-            .build_ssh_session_command()
-            .unwrap()
-            .expect("ssh session command");
+        state.resize_pane(PaneSplit::Profiles, false).unwrap();
+        state.resize_pane(PaneSplit::Profiles, false).unwrap();
+        assert_eq!(
+            state.pane_layout().profiles_width,
+            20,
+            "kept at the minimum"
+        );
+        state.set_pane_split(PaneSplit::Actions, 95);
+        assert_eq!(state.pane_layout().actions_height, 80);
+        state.set_result_tab(ResultTab::Stderr);
+        state.save_pane_layout().unwrap();
+        let stored = settings::get_pane_layout(state.store.conn()).unwrap();
+        assert_eq!((stored.profiles_width, stored.actions_height), (20, 80));
+        assert_eq!(stored.result_tab, ResultTabName::Stderr);
+
+        state.reset_pane_layout().unwrap();
+        let stored = settings::get_pane_layout(state.store.conn()).unwrap();
+        assert_eq!((stored.profiles_width, stored.actions_height), (40, 45));
+    }
 
-        assert!(matches!(
-            command.session_log_plan,
-            SessionLogPlan::NoLog { .. }
-        ));
-        let _ = fs::remove_file(command.executable);
+    #[test]
+    fn grouped_profile_list_collapses_headers_and_remembers_them() {
+        let profile = |id: &str, group: Option<&str>, tags: &[&str]| NewProfile {
+            profile_id: Some(id.to_string()),
+            name: id.to_string(),
+            group: group.map(str::to_string),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..base_profile(ProfileType::Ssh)
+        };
+        let mut state = state_with_profiles(vec![
+            profile("p_web", Some("prod"), &["web"]),
+            profile("p_db", Some("prod"), &["db", "web"]),
+            profile("p_lab", None, &[]),
+        ]);
+        let headers = |state: &AppState| {
+            state
+                .profile_rows()
+                .into_iter()
+                .filter_map(|row| match row {
+                    ProfileRow::Header { label, count, .. } => Some(format!("{label} ({count})")),
+                    ProfileRow::Profile(_) => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        state.cycle_profile_grouping().unwrap();
+        assert_eq!(headers(&state), ["prod (2)", "(no group) (1)"]);
+        assert_eq!(state.profile_rows().len(), 5);
+        assert!(state.selected_profile().is_none());
+        state.toggle_mark();
+        assert_eq!(
+            state.marked_profiles().iter().collect::<Vec<_>>(),
+            ["p_db", "p_web"]
+        );
+
+        state.next_profile().unwrap();
+        assert!(state.selected_profile().is_some());
+        state.toggle_group_collapse().unwrap();
+        assert_eq!(state.selected_header().as_deref(), Some("group:prod"));
+        assert_eq!(state.profile_rows().len(), 3);
+        let stored = settings::get_profile_list_layout(state.store.conn()).unwrap();
+        assert_eq!(stored.group_by, ProfileGrouping::Group);
+        assert_eq!(stored.collapsed, ["group:prod"]);
+
+        state.cycle_profile_grouping().unwrap();
+        assert_eq!(headers(&state), ["db (1)", "web (2)", "(untagged) (1)"]);
+        state.cycle_profile_grouping().unwrap();
+        assert_eq!(state.profile_rows().len(), 3);
+        assert!(state.selected_profile().is_some());
     }
 
     #[test]
-    fn rejects_ssh_session_when_no_profile_is_selected() {
+    fn idle_lock_engages_after_timeout_and_requires_verification() {
         let mut state = state_with_profiles(Vec::new());
+        let start = Instant::now();
+        state.tick_idle_lock(start + Duration::from_secs(3600));
+        assert!(!state.is_locked(), "lock is disabled by default");
+
+        settings::set_setting(state.store.conn(), settings::IDLE_LOCK_MINUTES_KEY, "5").unwrap();
+        state.refresh_after_settings(true, false).unwrap();
+        state.tick_idle_lock(state.last_input_at + Duration::from_secs(299));
+        assert!(!state.is_locked());
+        state.tick_idle_lock(state.last_input_at + Duration::from_secs(300));
+        assert!(state.is_locked());
+
+        state.push_unlock_char('x');
+        assert_eq!(state.take_unlock_input().as_str(), "x");
+        state.finish_unlock(false);
+        assert!(state.lock_prompt().unwrap().error.is_some());
+        state.finish_unlock(true);
+        assert!(!state.is_locked());
+    }
 
-        let command = state.build_ssh_session_command().unwrap();
+    #[test]
+    fn snapshot_view_blocks_runs_and_skips_persistence() {
+        let mut state = state_with_profiles(vec![base_profile(ProfileType::Ssh)]);
+        state.enter_snapshot_view(SnapshotView { allow_run: false });
 
-        assert!(command.is_none());
-        assert_eq!(
-            state.status_message(),
-            Some("No profile selected; clear filters or add a profile.")
+        state.request_run().unwrap();
+        assert!(state.confirm_state().is_none());
+        assert!(state.status_message().unwrap().contains("--allow-run"));
+
+        state.toggle_result_wrap().unwrap();
+        assert!(!state.result_layout(ResultTab::Stdout).unwrap().wrap);
+        assert!(
+            settings::get_setting(state.store.conn(), settings::RESULT_TAB_LAYOUTS_KEY)
+                .unwrap()
+                .is_none()
         );
     }
 
     #[test]
-    fn rejects_ssh_session_for_non_ssh_profile() {
-        let mut state = state_with_profiles(vec![base_profile(ProfileType::Telnet)]);
+    fn archived_profiles_hidden_until_toggled() {
+        let mut state = state_with_profiles(vec![base_profile(ProfileType::Ssh)]);
+        state.store.set_archived("p_test", true).unwrap();
+        state.refresh().unwrap();
+        assert!(state.filtered().is_empty());
 
-        let command = state.build_ssh_session_command().unwrap();
+        state.toggle_archived().unwrap();
+        assert_eq!(state.filtered().len(), 1);
+    }
 
-        assert!(command.is_none());
+    #[test]
+    fn profile_sort_cycles_name_last_used_most_used() {
+        let mut profiles = Vec::new();
+        for (profile_id, name) in [("p_a", "alpha"), ("p_b", "bravo"), ("p_c", "charlie")] {
+            let mut profile = base_profile(ProfileType::Ssh);
+            profile.profile_id = Some(profile_id.to_string());
+            profile.name = name.to_string();
+            profiles.push(profile);
+        }
+        let mut state = state_with_profiles(profiles);
+        let conn = state.store.conn();
+        conn.execute(
+            "UPDATE profiles SET last_used_at = 5 WHERE profile_id = 'p_b'",
+            [],
+        )
+        .unwrap();
+        for profile_id in ["p_c", "p_c", "p_b"] {
+            oplog::log_operation(
+                conn,
+                OpLogEntry {
+                    op: "run".to_string(),
+                    profile_id: Some(profile_id.to_string()),
+                    client_used: None,
+                    ok: true,
+                    exit_code: Some(0),
+                    duration_ms: None,
+                    meta_json: None,
+                },
+            )
+            .unwrap();
+        }
+        let order = |state: &AppState| {
+            state
+                .filtered()
+                .iter()
+                .map(|p| p.profile_id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(order(&state), vec!["p_a", "p_b", "p_c"]);
+        state.cycle_profile_sort().unwrap();
+        assert_eq!(state.profile_sort(), ProfileSort::LastUsed);
+        assert_eq!(order(&state), vec!["p_b", "p_c", "p_a"]);
+        state.cycle_profile_sort().unwrap();
+        assert_eq!(order(&state), vec!["p_c", "p_b", "p_a"]);
+        state.cycle_profile_sort().unwrap();
+        assert_eq!(order(&state), vec!["p_a", "p_b", "p_c"]);
+    }
+
+    #[test]
+    fn preflight_asks_before_launching_to_unreachable_host() {
+        let mut profile = base_profile(ProfileType::Ssh);
+        profile.host = "127.0.0.1".to_string();
+        profile.port = 1;
+        let mut state = state_with_profiles(vec![profile]);
+        settings::set_setting(state.store.conn(), settings::CONNECT_PREFLIGHT_KEY, "true").unwrap();
+
+        assert!(state.build_ssh_session_command().unwrap().is_none());
+        let confirm = state.confirm_state().expect("unreachable confirmation");
+        assert!(confirm.message.contains("looks unreachable"));
+        assert_eq!(confirm.required_input, "yes");
+
+        for ch in "yes".chars() {
+            state.push_confirm_char(ch);
+        }
         assert_eq!(
-            state.status_message(),
-            Some("Selected profile is telnet; SSH session requires an SSH profile.")
+            state.confirm_action().unwrap(),
+            ConfirmedAction::OpenSshSession
         );
+        assert_eq!(state.preflight_waived_profile_id.as_deref(), Some("p_test"));
     }
 
     #[test]
-    fn critical_ssh_session_requires_confirmation() {
-        let fake_ssh = fake_ssh_path("critical");
+    fn maintenance_window_asks_for_a_logged_reason_before_connecting() {
         let mut profile = base_profile(ProfileType::Ssh);
         profile.danger_level = DangerLevel::Critical;
-        profile.client_overrides = Some(ClientOverrides {
-            ssh: Some(fake_ssh.to_string_lossy().into_owned()),
-            ..Default::default()
-        });
+        profile.group = Some("billing".to_string());
         let mut state = state_with_profiles(vec![profile]);
+        maintenance::add(
+            state.store.conn(),
+            maintenance::NewMaintenanceWindow {
+                target: schedule::ScheduleTarget::parse("group:billing").unwrap(),
+                span: "2000-01-01..2999-12-31".to_string(),
+                mode: WindowMode::Reason,
+                note: None,
+            },
+            0,
+        )
+        .unwrap();
 
-        let command = state.build_ssh_session_command().unwrap();
-
-        assert!(command.is_none());
-        assert!(state.confirm_state().is_some());
-        for ch in "p_test".chars() {
+        assert!(state.build_ssh_session_command().unwrap().is_none());
+        assert!(state.confirm_state().unwrap().wants_reason());
+        assert_eq!(
+            state.confirm_action().unwrap(),
+            ConfirmedAction::Continue,
+            "an empty reason is refused"
+        );
+        for ch in "INC-42".chars() {
             state.push_confirm_char(ch);
         }
         assert_eq!(
             state.confirm_action().unwrap(),
             ConfirmedAction::OpenSshSession
         );
+        let logged = oplog::list_operations(state.store.conn(), &OpLogQuery::default()).unwrap();
+        assert_eq!(logged[0].op, "maintenance_override");
+
+        assert!(state.build_ssh_session_command().unwrap().is_none());
+        let confirm = state
+            .confirm_state()
+            .expect("critical confirmation follows");
+        assert!(!confirm.wants_reason());
+        assert_eq!(confirm.required_input, "p_test");
+    }
 
-        let command = state
-            .build_ssh_session_command()
-            .unwrap()
-            .expect("confirmed ssh session command");
+    #[test]
+    fn observer_mode_refuses_runs_and_sessions() {
+        let mut state = state_with_profiles(vec![base_profile(ProfileType::Ssh)]);
+        state.enter_observer_mode();
 
-        assert_eq!(command.profile_id, "p_test");
-        let _ = fs::remove_file(command.executable);
+        assert!(state.build_ssh_session_command().unwrap().is_none());
+        assert!(state.confirm_state().is_none());
+        assert_eq!(
+            state.status_message.as_deref(),
+            Some("Observer mode: opening SSH sessions is disabled.")
+        );
+        state.request_run().unwrap();
+        assert!(state.confirm_state().is_none());
+        assert_eq!(
+            state.status_message.as_deref(),
+            Some("Observer mode: running CommandSets is disabled.")
+        );
+        state.copy_login_secret().unwrap();
+        assert!(state.copied_secret.is_none());
+        assert_eq!(
+            state.status_message.as_deref(),
+            Some("Observer mode: copying secrets is disabled.")
+        );
     }
 
     #[test]
@@ -1396,6 +5096,7 @@ mod tests {
             action: PendingAction::OpenSshSession {
                 profile_id: "p_test".to_string(),
             },
+            blast_radius: None,
         });
 
         state.cancel_confirm();
@@ -1514,4 +5215,249 @@ mod tests {
         assert_eq!(meta["session_log_id"], "sl_abc123");
         assert!(meta.get("log_path").is_none());
     }
+
+    #[test]
+    fn flags_dead_tunnels_and_restarts_them_when_enabled() {
+        let mut state = state_with_profiles(vec![base_profile(ProfileType::Ssh)]);
+        state
+            .store
+            .conn()
+            .execute(
+                "INSERT INTO sessions (session_id, kind, profile_id, pid, started_at, forwards_json) \
+                 VALUES ('s_dead', 'tunnel', 'p_test', NULL, 0, '[\"gone\"]')",
+                [],
+            )
+            .unwrap();
+        let now = Instant::now();
+
+        state.tick_tunnels(now);
+        let sessions = state.tunnel_sessions("p_test").collect::<Vec<_>>();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].health_summary(), "dead: ssh exited");
+        assert!(state.status_message().unwrap().contains("press F"));
+
+        state.toggle_tunnel_auto_restart();
+        assert!(state.tunnel_auto_restart());
+        state.tick_tunnels(now);
+        assert!(state
+            .status_message()
+            .unwrap()
+            .contains("restart failed: forward not found: gone"));
+    }
+
+    #[test]
+    fn running_ssh_sessions_are_listed_and_ending_one_asks_first() {
+        let mut state = state_with_profiles(vec![base_profile(ProfileType::Ssh)]);
+        state.request_terminate_session().unwrap();
+        assert!(state.confirm_state().is_none());
+        assert_eq!(
+            state.status_message(),
+            Some("No active SSH session on p_test.")
+        );
+
+        let id =
+            active_session::register(state.store.conn(), "p_test", std::process::id(), 0).unwrap();
+        state.tick_tunnels(Instant::now());
+        assert_eq!(state.active_sessions("p_test").count(), 1);
+        state.request_terminate_session().unwrap();
+        let confirm = state.confirm_state().unwrap();
+        assert_eq!(confirm.required_input, "yes");
+        assert!(matches!(
+            confirm.action,
+            PendingAction::TerminateSession { session_id } if session_id == id
+        ));
+        state.cancel_confirm();
+    }
+
+    #[test]
+    fn cmdset_editor_saves_new_and_edited_steps() {
+        let mut state = state_with_profiles(Vec::new());
+        state.open_new_cmdset_editor();
+        let editor = state.cmdset_editor_mut().unwrap();
+        editor.input.as_mut().unwrap().text = "Disk".to_string();
+        state.commit_cmdset_editor_input();
+        state.save_cmdset_editor().unwrap();
+        assert!(state
+            .cmdset_editor()
+            .unwrap()
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("at least one step"));
+
+        let editor = state.cmdset_editor_mut().unwrap();
+        editor.begin_input(crate::cmdset_editor::InputTarget::AddStep);
+        editor.input.as_mut().unwrap().text = "df -h".to_string();
+        state.commit_cmdset_editor_input();
+        state.save_cmdset_editor().unwrap();
+        assert!(state.cmdset_editor().is_none());
+        let cmdset = state.selected_cmdset().unwrap().clone();
+        assert_eq!(cmdset.name, "Disk");
+
+        state.open_cmdset_editor().unwrap();
+        let editor = state.cmdset_editor_mut().unwrap();
+        editor.toggle_on_error();
+        state.close_cmdset_editor();
+        assert!(state.cmdset_editor().is_some(), "first Esc only warns");
+        state.save_cmdset_editor().unwrap();
+        let steps = state.cmdset_store.list_steps(&cmdset.cmdset_id).unwrap();
+        assert_eq!(steps[0].on_error, tdcore::cmdset::StepOnError::Continue);
+    }
+
+    #[test]
+    fn profile_form_adds_and_edits_profiles_through_the_store() {
+        let mut state = state_with_profiles(vec![base_profile(ProfileType::Ssh)]);
+        state.open_new_profile_form();
+        let form = state.profile_form_mut().unwrap();
+        for ch in "p_new".chars() {
+            form.push_char(ch);
+        }
+        state.save_profile_form().unwrap();
+        assert_eq!(
+            state.profile_form().unwrap().error.as_deref(),
+            Some("Name is required.")
+        );
+        state.close_profile_form();
+
+        state.open_edit_profile_form();
+        let form = state.profile_form_mut().unwrap();
+        form.move_cursor(true);
+        form.move_cursor(true);
+        for ch in ".org".chars() {
+            form.push_char(ch);
+        }
+        state.save_profile_form().unwrap();
+        assert!(state.profile_form().is_none());
+        assert_eq!(state.status_message(), Some("Updated profile p_test."));
+        assert_eq!(state.selected_profile().unwrap().host, "example.com.org");
+
+        state.observer = true;
+        state.open_edit_profile_form();
+        assert!(state.profile_form().is_none());
+    }
+
+    #[test]
+    fn transfer_form_confirms_critical_profiles_and_logs_the_push() {
+        let fake_scp = fake_ssh_path("scp");
+        let mut profile = base_profile(ProfileType::Ssh);
+        profile.danger_level = DangerLevel::Critical;
+        profile.client_overrides = Some(ClientOverrides {
+            scp: Some(fake_scp.to_string_lossy().into_owned()),
+            ..Default::default()
+        });
+        let mut state = state_with_profiles(vec![profile]);
+        state.open_transfer_form().unwrap();
+        let form = state.transfer_form_mut().unwrap();
+        form.move_cursor(true);
+        form.move_cursor(true);
+        for ch in fake_scp.to_string_lossy().chars() {
+            form.push_char(ch);
+        }
+        assert!(!state.submit_transfer_form());
+        assert_eq!(
+            state.transfer_form().unwrap().error.as_deref(),
+            Some("Remote path is required.")
+        );
+        let form = state.transfer_form_mut().unwrap();
+        form.move_cursor(true);
+        for ch in "/tmp/upload.bin".chars() {
+            form.push_char(ch);
+        }
+        assert!(state.submit_transfer_form());
+
+        assert!(state.build_transfer_command().unwrap().is_none());
+        assert_eq!(state.confirm_state().unwrap().required_input, "p_test");
+        for ch in "p_test".chars() {
+            state.push_confirm_char(ch);
+        }
+        assert_eq!(
+            state.confirm_action().unwrap(),
+            ConfirmedAction::RunTransfer
+        );
+        let command = state.build_transfer_command().unwrap().unwrap();
+        assert_eq!(command.client, fake_scp);
+        assert_eq!(command.request.remote_path, "/tmp/upload.bin");
+
+        state
+            .record_transfer_result(
+                &command,
+                Ok(TransferOutcome {
+                    ok: true,
+                    exit_code: 0,
+                    duration_ms: 12,
+                    client_used: fake_scp.clone(),
+                    insecure: false,
+                }),
+            )
+            .unwrap();
+        assert_eq!(
+            state.status_message(),
+            Some(format!("Pushed {} via scp in 12 ms.", fake_scp.display()).as_str())
+        );
+        let logged = oplog::list_operations(state.store.conn(), &OpLogQuery::default()).unwrap();
+        assert_eq!(logged[0].op, "push");
+        assert_eq!(
+            logged[0].meta.as_ref().unwrap()["remote_path"],
+            "/tmp/upload.bin"
+        );
+        fs::remove_file(fake_scp).unwrap();
+    }
+
+    #[test]
+    fn preset_picker_applies_to_marked_profiles_and_badges_matches() {
+        let profile = |id: &str| NewProfile {
+            profile_id: Some(id.to_string()),
+            ..base_profile(ProfileType::Ssh)
+        };
+        let mut state = state_with_profiles(vec![profile("p_db"), profile("p_web")]);
+        state.open_preset_picker().unwrap();
+        assert!(state.preset_picker_state().is_none(), "no presets yet");
+
+        state
+            .store
+            .conn()
+            .execute(
+                "INSERT INTO ssh_forwards (profile_id, name, kind, listen, dest) \
+                 VALUES ('p_db', 'pg', 'local', '127.0.0.1:5432', 'db.internal:5432')",
+                [],
+            )
+            .unwrap();
+        forward_preset::save_from_profile(state.store.conn(), "pg", "p_db").unwrap();
+        state.marked_profiles = ["p_db".to_string(), "p_web".to_string()].into();
+        state.open_preset_picker().unwrap();
+        assert_eq!(state.preset_picker_state().unwrap().profile_ids.len(), 2);
+        state.apply_preset_choice().unwrap();
+        assert!(state.preset_picker_state().is_none());
+        assert!(state
+            .status_message()
+            .unwrap()
+            .contains("1 forward(s) added, 1 already present"));
+        assert_eq!(state.preset_matches("p_web"), ["pg"]);
+    }
+
+    #[test]
+    fn startup_defers_cmdsets_and_caches_groups_incrementally() {
+        let profile = |id: &str, group: &str| NewProfile {
+            profile_id: Some(id.to_string()),
+            group: Some(group.to_string()),
+            ..base_profile(ProfileType::Ssh)
+        };
+        let mut state = state_with_profiles(vec![profile("p_db", "db"), profile("p_web", "web")]);
+        assert!(!state.cmdsets_loaded());
+        assert!(state.result_layout(ResultTab::Stdout).is_none());
+        state.cycle_pane();
+        assert!(state.cmdsets_loaded());
+        state.cycle_pane();
+        assert!(state.result_layout(ResultTab::Stdout).is_some());
+
+        state.store.insert(profile("p_cache", "cache")).unwrap();
+        state.filters.query = Some("cache".to_string());
+        state.refresh().unwrap();
+        assert_eq!(state.groups, vec!["cache", "db", "web"]);
+
+        state.store.delete("p_web").unwrap();
+        state.filters.query = None;
+        state.refresh().unwrap();
+        assert_eq!(state.groups, vec!["cache", "db"]);
+    }
 }
@@ -0,0 +1,389 @@
+//! The transfer form (`U` on an SSH profile): push or pull one path with scp or sftp.
+//! The local path lists matching entries of its directory as it is typed, and `Tab`
+//! completes it, so the form doubles as a small file browser. A profile with saved
+//! favorites (`td fav add`) gets a Favorite field first that fills in the other four.
+
+use std::path::{Path, PathBuf};
+
+use tdcore::transfer::{TransferDirection, TransferFavorite, TransferVia};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferField {
+    Favorite,
+    Direction,
+    Via,
+    Local,
+    Remote,
+}
+
+impl TransferField {
+    pub const ALL: [TransferField; 5] = [
+        TransferField::Favorite,
+        TransferField::Direction,
+        TransferField::Via,
+        TransferField::Local,
+        TransferField::Remote,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TransferField::Favorite => "Favorite",
+            TransferField::Direction => "Direction",
+            TransferField::Via => "Via",
+            TransferField::Local => "Local path",
+            TransferField::Remote => "Remote path",
+        }
+    }
+
+    pub fn is_choice(self) -> bool {
+        matches!(
+            self,
+            TransferField::Favorite | TransferField::Direction | TransferField::Via
+        )
+    }
+}
+
+/// A checked form, ready for the maintenance, policy, and confirmation checks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferRequest {
+    pub profile_id: String,
+    pub direction: TransferDirection,
+    pub via: TransferVia,
+    pub local_path: PathBuf,
+    pub remote_path: String,
+    /// The favorite the form was filled from and left unchanged, to mark it used.
+    pub favorite_id: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TransferForm {
+    pub profile_id: String,
+    pub direction: TransferDirection,
+    /// scp or sftp; FTP stays with `td push --via ftp`, which needs its own flag.
+    pub via: TransferVia,
+    pub local: String,
+    pub remote: String,
+    pub cursor: usize,
+    /// Names in the local path's directory that start with what is typed after the last
+    /// separator; directories end with `/`.
+    pub entries: Vec<String>,
+    /// The profile's scp and sftp favorites; FTP ones stay with `td fav run`.
+    pub favorites: Vec<TransferFavorite>,
+    /// The favorite last picked, until another field is changed.
+    pub favorite: Option<usize>,
+    pub error: Option<String>,
+}
+
+impl TransferForm {
+    pub fn new(profile_id: &str, favorites: Vec<TransferFavorite>) -> Self {
+        let favorites: Vec<TransferFavorite> = favorites
+            .into_iter()
+            .filter(|favorite| !favorite.via.is_insecure())
+            .collect();
+        let mut form = Self {
+            profile_id: profile_id.to_string(),
+            direction: TransferDirection::Push,
+            via: TransferVia::Scp,
+            local: String::new(),
+            remote: String::new(),
+            cursor: 0,
+            entries: Vec::new(),
+            favorites,
+            favorite: None,
+            error: None,
+        };
+        form.refresh_entries();
+        form
+    }
+
+    /// The fields shown; Favorite only when there is one to pick.
+    pub fn fields(&self) -> &'static [TransferField] {
+        if self.favorites.is_empty() {
+            &TransferField::ALL[1..]
+        } else {
+            &TransferField::ALL
+        }
+    }
+
+    pub fn field(&self) -> TransferField {
+        self.fields()[self.cursor]
+    }
+
+    pub fn value(&self, field: TransferField) -> &str {
+        match field {
+            TransferField::Favorite => self
+                .favorite
+                .and_then(|index| self.favorites.get(index))
+                .map_or("(none)", |favorite| favorite.name.as_str()),
+            TransferField::Direction => self.direction.as_str(),
+            TransferField::Via => self.via.as_str(),
+            TransferField::Local => &self.local,
+            TransferField::Remote => &self.remote,
+        }
+    }
+
+    pub fn move_cursor(&mut self, down: bool) {
+        let count = self.fields().len();
+        self.cursor = if down {
+            (self.cursor + 1) % count
+        } else {
+            (self.cursor + count - 1) % count
+        };
+    }
+
+    pub fn cycle_choice(&mut self) {
+        match self.field() {
+            TransferField::Favorite => {
+                let next = self.favorite.map_or(0, |index| index + 1);
+                self.favorite = (next < self.favorites.len()).then_some(next);
+                if let Some(favorite) = self.favorite.map(|index| self.favorites[index].clone()) {
+                    self.direction = favorite.direction;
+                    self.via = favorite.via;
+                    self.local = favorite.local_path;
+                    self.remote = favorite.remote_path;
+                    self.refresh_entries();
+                }
+                self.error = None;
+                return;
+            }
+            TransferField::Direction => {
+                self.direction = match self.direction {
+                    TransferDirection::Push => TransferDirection::Pull,
+                    TransferDirection::Pull => TransferDirection::Push,
+                };
+            }
+            TransferField::Via => {
+                self.via = match self.via {
+                    TransferVia::Scp => TransferVia::Sftp,
+                    _ => TransferVia::Scp,
+                };
+            }
+            TransferField::Local | TransferField::Remote => return,
+        }
+        self.favorite = None;
+        self.error = None;
+    }
+
+    pub fn push_char(&mut self, ch: char) {
+        match self.field() {
+            TransferField::Local => self.local.push(ch),
+            TransferField::Remote => self.remote.push(ch),
+            _ => return,
+        }
+        self.edited();
+    }
+
+    pub fn pop_char(&mut self) {
+        match self.field() {
+            TransferField::Local => self.local.pop(),
+            TransferField::Remote => self.remote.pop(),
+            _ => return,
+        };
+        self.edited();
+    }
+
+    /// `Tab`: completes the local path, or moves to the next field when there is nothing
+    /// to complete.
+    pub fn tab(&mut self) {
+        if !self.complete_local() {
+            self.move_cursor(true);
+        }
+    }
+
+    /// Extends the local path to the longest prefix the listed entries share; `false`
+    /// when that adds nothing.
+    pub fn complete_local(&mut self) -> bool {
+        if self.field() != TransferField::Local || self.entries.is_empty() {
+            return false;
+        }
+        let (_, typed) = split_local(&self.local);
+        let mut common = self.entries[0].clone();
+        for entry in &self.entries[1..] {
+            let shared = common
+                .chars()
+                .zip(entry.chars())
+                .take_while(|(a, b)| a == b)
+                .count();
+            common = common.chars().take(shared).collect();
+        }
+        if common.chars().count() <= typed.chars().count() {
+            return false;
+        }
+        let suffix: String = common.chars().skip(typed.chars().count()).collect();
+        self.local.push_str(&suffix);
+        self.edited();
+        true
+    }
+
+    pub fn validate(&self) -> Result<TransferRequest, String> {
+        let local = self.local.trim();
+        let remote = self.remote.trim();
+        if local.is_empty() {
+            return Err("Local path is required.".to_string());
+        }
+        if remote.is_empty() {
+            return Err("Remote path is required.".to_string());
+        }
+        let local_path = PathBuf::from(local);
+        match self.direction {
+            TransferDirection::Push if !local_path.is_file() => {
+                return Err(format!("{local} is not a file."));
+            }
+            TransferDirection::Pull => {
+                let parent = local_path
+                    .parent()
+                    .filter(|parent| !parent.as_os_str().is_empty())
+                    .unwrap_or(Path::new("."));
+                if !parent.is_dir() {
+                    return Err(format!("{} is not a directory.", parent.display()));
+                }
+            }
+            TransferDirection::Push => {}
+        }
+        Ok(TransferRequest {
+            profile_id: self.profile_id.clone(),
+            direction: self.direction,
+            via: self.via,
+            local_path,
+            remote_path: remote.to_string(),
+            favorite_id: self
+                .favorite
+                .and_then(|index| self.favorites.get(index))
+                .map(|favorite| favorite.id),
+        })
+    }
+
+    fn edited(&mut self) {
+        self.favorite = None;
+        self.error = None;
+        self.refresh_entries();
+    }
+
+    fn refresh_entries(&mut self) {
+        let (dir, typed) = split_local(&self.local);
+        let dir = if dir.is_empty() { "." } else { dir };
+        let mut entries: Vec<String> = std::fs::read_dir(dir)
+            .map(|read| {
+                read.filter_map(|entry| entry.ok())
+                    .filter_map(|entry| {
+                        let name = entry.file_name().to_string_lossy().into_owned();
+                        if !name.starts_with(typed) || (typed.is_empty() && name.starts_with('.')) {
+                            return None;
+                        }
+                        let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+                        Some(if is_dir { format!("{name}/") } else { name })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        entries.sort();
+        self.entries = entries;
+    }
+}
+
+/// The directory part of a typed local path, with its trailing separator, and the name
+/// being typed after it.
+fn split_local(local: &str) -> (&str, &str) {
+    match local.rfind(['/', std::path::MAIN_SEPARATOR]) {
+        Some(at) => local.split_at(at + 1),
+        None => ("", local),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn browses_completes_and_validates_paths() {
+        let dir = std::env::temp_dir().join(format!("td-transfer-form-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("backups")).unwrap();
+        std::fs::write(dir.join("backup.tar"), b"data").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"notes").unwrap();
+        let base = format!("{}/", dir.display());
+
+        let mut form = TransferForm::new("p_web", Vec::new());
+        form.move_cursor(true);
+        form.cycle_choice();
+        assert_eq!(form.via, TransferVia::Sftp);
+        form.move_cursor(true);
+        for ch in format!("{base}ba").chars() {
+            form.push_char(ch);
+        }
+        assert_eq!(form.entries, ["backup.tar", "backups/"]);
+        assert!(form.complete_local());
+        assert!(form.local.ends_with("/backup"));
+        assert!(!form.complete_local(), "two entries left");
+        form.push_char('.');
+        assert!(form.complete_local());
+        assert!(form.local.ends_with("/backup.tar"));
+
+        assert_eq!(form.validate(), Err("Remote path is required.".to_string()));
+        form.move_cursor(true);
+        for ch in "/tmp/backup.tar".chars() {
+            form.push_char(ch);
+        }
+        let request = form.validate().unwrap();
+        assert_eq!(request.direction, TransferDirection::Push);
+        assert_eq!(request.local_path, dir.join("backup.tar"));
+
+        form.local = format!("{base}missing/out.tar");
+        assert!(form.validate().is_err(), "push needs an existing file");
+        form.direction = TransferDirection::Pull;
+        assert!(form.validate().is_err(), "pull needs an existing directory");
+        form.local = format!("{base}out.tar");
+        assert!(form.validate().is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn favorite(id: i64, name: &str, via: TransferVia) -> TransferFavorite {
+        TransferFavorite {
+            id,
+            profile_id: "p_web".to_string(),
+            name: name.to_string(),
+            direction: TransferDirection::Pull,
+            local_path: format!("{name}.log"),
+            remote_path: format!("/var/log/{name}.log"),
+            via,
+            created_at: 0,
+            last_used_at: None,
+        }
+    }
+
+    #[test]
+    fn picking_a_favorite_fills_the_form_until_a_field_changes() {
+        let mut form = TransferForm::new(
+            "p_web",
+            vec![
+                favorite(1, "app", TransferVia::Sftp),
+                favorite(2, "legacy", TransferVia::Ftp),
+                favorite(3, "nginx", TransferVia::Scp),
+            ],
+        );
+        assert_eq!(form.favorites.len(), 2, "FTP favorites are left out");
+        assert_eq!(form.field(), TransferField::Favorite);
+        assert_eq!(form.value(TransferField::Favorite), "(none)");
+
+        form.cycle_choice();
+        form.cycle_choice();
+        assert_eq!(form.value(TransferField::Favorite), "nginx");
+        assert_eq!(form.direction, TransferDirection::Pull);
+        assert_eq!(form.via, TransferVia::Scp);
+        assert_eq!(form.local, "nginx.log");
+        assert_eq!(form.remote, "/var/log/nginx.log");
+        assert_eq!(form.validate().unwrap().favorite_id, Some(3));
+
+        form.move_cursor(false);
+        assert_eq!(form.field(), TransferField::Remote);
+        form.push_char('.');
+        assert_eq!(form.value(TransferField::Favorite), "(none)");
+        assert_eq!(form.validate().unwrap().favorite_id, None);
+
+        form.move_cursor(true);
+        form.cycle_choice();
+        form.cycle_choice();
+        form.cycle_choice();
+        assert_eq!(form.favorite, None, "cycles back to none");
+        assert_eq!(form.remote, "/var/log/nginx.log", "none keeps the values");
+    }
+}
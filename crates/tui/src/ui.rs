@@ -3,18 +3,30 @@ use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span, Text};
 use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Tabs, Wrap};
 use ratatui::Frame;
+use std::rc::Rc;
+use std::time::Instant;
 
-use crate::state::{ActivePane, AppState, InputMode, ResultTab};
+use tdcore::profile::{BulkEditField, DangerLevel, Profile, ProfileType};
+use tdcore::settings::{PaneLayout, ProfileColumn, ProfileListLayout};
+use tdcore::util;
+
+use crate::cmdset_editor::CmdSetEditor;
+use crate::health::HealthStatus;
+use crate::live_run::{LiveRun, StepRow, StepStatus};
+use crate::profile_form::ProfileForm;
+use crate::state::{
+    ActivePane, AppState, BlastRadius, BulkEditStage, BulkEditState, BulkTarget, HistoryState,
+    InputMode, LockPrompt, PaletteState, PaneSplit, PresetPickerState, ProfileRow, ProfileSort,
+    ResultTab, BLAST_RADIUS_ROWS,
+};
+use crate::transfer_form::{TransferField, TransferForm};
 
 pub fn render(frame: &mut Frame<'_>, state: &AppState) {
-    let layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(2),
-            Constraint::Length(1),
-            Constraint::Min(0),
-        ])
-        .split(frame.size());
+    if let Some(lock) = state.lock_prompt() {
+        render_lock_screen(frame, lock);
+        return;
+    }
+    let layout = screen_rows(frame.size());
 
     let filter_line = Paragraph::new(filters_line(state)).wrap(Wrap { trim: true });
     frame.render_widget(filter_line, layout[0]);
@@ -22,36 +34,172 @@ pub fn render(frame: &mut Frame<'_>, state: &AppState) {
     let hint_line = Paragraph::new(hints_line(state));
     frame.render_widget(hint_line, layout[1]);
 
-    let body = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
-        .split(layout[2]);
-
-    render_profiles(frame, state, body[0]);
-    render_right(frame, state, body[1]);
+    let panes = pane_areas(frame.size(), state.details_open(), state.pane_layout());
+    render_profiles(frame, state, panes.profiles);
+    if state.details_open() {
+        render_details_pane(frame, state, panes.actions);
+    } else {
+        render_action_pane(frame, state, panes.actions);
+    }
+    render_results_pane(frame, state, panes.result_tabs, panes.results);
 
     if let Some(confirm) = state.confirm_state() {
-        let area = centered_rect(70, 30, frame.size());
+        let height = if confirm.blast_radius.is_some() {
+            80
+        } else {
+            30
+        };
+        let area = centered_rect(70, height, frame.size());
         frame.render_widget(Clear, area);
         let block = Block::default()
             .title("Confirm")
             .borders(Borders::ALL)
             .border_style(Style::default().fg(Color::Red));
-        let text = Text::from(vec![
-            Line::from(confirm.message.clone()),
-            Line::from(""),
-            Line::from(format!("Type '{}' to confirm.", confirm.required_input)),
-            Line::from(format!("Input: {}", confirm.input)),
+        let mut lines: Vec<Line> = confirm.message.lines().map(Line::from).collect();
+        lines.push(Line::from(""));
+        if let Some(radius) = &confirm.blast_radius {
+            lines.extend(blast_radius_lines(radius));
+            lines.push(Line::from(""));
+        }
+        if confirm.input_ready() {
+            lines.extend([
+                Line::from(if confirm.wants_reason() {
+                    "Type a reason.".to_string()
+                } else {
+                    format!("Type '{}' to confirm.", confirm.required_input)
+                }),
+                Line::from(format!("Input: {}", confirm.input)),
+            ]);
+        } else {
+            lines.push(Line::styled(
+                "Scroll through every target with Down or PgDn to enable typing.",
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+        lines.extend([
             Line::from(""),
             Line::from("Press Enter to confirm, Esc to cancel."),
         ]);
-        let paragraph = Paragraph::new(text)
+        let paragraph = Paragraph::new(Text::from(lines))
             .block(block)
             .alignment(Alignment::Center)
             .wrap(Wrap { trim: true });
         frame.render_widget(paragraph, area);
     }
 
+    if let Some(bulk_edit) = state.bulk_edit_state() {
+        let area = centered_rect(70, 60, frame.size());
+        frame.render_widget(Clear, area);
+        let block = Block::default()
+            .title(format!(
+                "Bulk edit ({} marked profiles)",
+                bulk_edit.profile_ids.len()
+            ))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow));
+        let paragraph = Paragraph::new(Text::from(bulk_edit_lines(bulk_edit)))
+            .block(block)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(paragraph, area);
+    }
+
+    if let Some(history) = state.history_state() {
+        let area = centered_rect(70, 60, frame.size());
+        frame.render_widget(Clear, area);
+        let block = Block::default()
+            .title(format!(
+                "History ({}, {}, {})",
+                history.profile_id,
+                match history.result {
+                    None => "all results",
+                    Some(false) => "failed",
+                    Some(true) => "ok",
+                },
+                history.period.label()
+            ))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan));
+        let paragraph = Paragraph::new(Text::from(history_lines(history, area.height)))
+            .block(block)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(paragraph, area);
+    }
+
+    if let Some(editor) = state.cmdset_editor() {
+        let area = centered_rect(80, 70, frame.size());
+        frame.render_widget(Clear, area);
+        let title = match &editor.cmdset_id {
+            Some(cmdset_id) => format!("Edit CommandSet {cmdset_id}"),
+            None => "New CommandSet".to_string(),
+        };
+        let block = Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan));
+        let paragraph = Paragraph::new(Text::from(cmdset_editor_lines(editor, area.height)))
+            .block(block)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(paragraph, area);
+    }
+
+    if let Some(form) = state.profile_form() {
+        let area = centered_rect(70, 70, frame.size());
+        frame.render_widget(Clear, area);
+        let title = match &form.editing {
+            Some(profile_id) => format!("Edit profile {profile_id}"),
+            None => "Add profile".to_string(),
+        };
+        let block = Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan));
+        let paragraph = Paragraph::new(Text::from(profile_form_lines(form)))
+            .block(block)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(paragraph, area);
+    }
+
+    if let Some(form) = state.transfer_form() {
+        let area = centered_rect(70, 60, frame.size());
+        frame.render_widget(Clear, area);
+        let block = Block::default()
+            .title(format!("Transfer: {}", form.profile_id))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan));
+        let paragraph = Paragraph::new(Text::from(transfer_form_lines(form)))
+            .block(block)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(paragraph, area);
+    }
+
+    if let Some(picker) = state.preset_picker_state() {
+        let area = centered_rect(60, 50, frame.size());
+        frame.render_widget(Clear, area);
+        let block = Block::default()
+            .title(format!(
+                "Apply forwarding preset to {} profile(s)",
+                picker.profile_ids.len()
+            ))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan));
+        let paragraph = Paragraph::new(Text::from(preset_picker_lines(picker)))
+            .block(block)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(paragraph, area);
+    }
+
+    if let Some(palette) = state.palette_state() {
+        let area = centered_rect(60, 50, frame.size());
+        frame.render_widget(Clear, area);
+        let block = Block::default()
+            .title("Command palette (Ctrl+K)")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan));
+        let paragraph =
+            Paragraph::new(Text::from(palette_lines(palette, area.height))).block(block);
+        frame.render_widget(paragraph, area);
+    }
+
     if state.help_open() {
         let area = centered_rect(70, 60, frame.size());
         frame.render_widget(Clear, area);
@@ -68,18 +216,447 @@ pub fn render(frame: &mut Frame<'_>, state: &AppState) {
     }
 }
 
+/// The typed filter, then the matching actions around the cursor with their keys.
+fn palette_lines(palette: &PaletteState, height: u16) -> Vec<Line<'static>> {
+    let mut lines = vec![Line::from(format!("> {}_", palette.input)), Line::from("")];
+    let rows = usize::from(height.saturating_sub(6)).max(1);
+    let first = palette.cursor.saturating_sub(rows - 1);
+    let matches = palette.matches();
+    for (index, (key, label)) in matches.iter().enumerate().skip(first).take(rows) {
+        let key = match key {
+            '\t' => "Tab".to_string(),
+            ' ' => "Space".to_string(),
+            key => key.to_string(),
+        };
+        let style = if index == palette.cursor {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        lines.push(Line::from(Span::styled(
+            format!("  {key:<6} {label}"),
+            style,
+        )));
+    }
+    if matches.is_empty() {
+        lines.push(Line::from("  No action matches."));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(
+        "Type to filter, Up/Down choose, Enter run, Esc close.",
+    ));
+    lines
+}
+
+/// The name, the steps around the cursor with their settings, then the input line or the
+/// key hint.
+fn cmdset_editor_lines(editor: &CmdSetEditor, height: u16) -> Vec<Line<'static>> {
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled("Name: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(editor.name.clone()),
+            Span::styled(
+                if editor.dirty { "  (modified)" } else { "" },
+                Style::default().fg(Color::Yellow),
+            ),
+        ]),
+        Line::from(""),
+    ];
+    let rows = usize::from(height.saturating_sub(9)).max(1);
+    let first = editor.cursor.saturating_sub(rows - 1);
+    for (index, step) in editor.steps.iter().enumerate().skip(first).take(rows) {
+        let style = if index == editor.cursor {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        let timeout = step
+            .timeout_ms
+            .map(|ms| format!("{ms}ms"))
+            .unwrap_or_else(|| "-".to_string());
+        lines.push(Line::from(vec![
+            Span::styled(format!("{:>3}. {}", index + 1, step.cmd), style),
+            Span::styled(
+                format!(
+                    "  timeout:{timeout} on_error:{} parser:{}",
+                    step.on_error.as_str(),
+                    step.parser_spec
+                ),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]));
+    }
+    if editor.steps.is_empty() {
+        lines.push(Line::from("  No steps yet; press a to add one."));
+    }
+    lines.push(Line::from(""));
+    if let Some(error) = &editor.error {
+        lines.push(Line::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    match &editor.input {
+        Some(input) => {
+            lines.push(Line::from(format!("{}: {}_", input.target.prompt(), input.text)));
+            lines.push(Line::from("Enter apply, Esc cancel."));
+        }
+        None => lines.push(Line::from(
+            "a add, Enter edit, d delete, J/K move, t timeout, o on_error, p parser, n rename, s save, Esc close.",
+        )),
+    }
+    lines
+}
+
+/// One line per field with the current one highlighted, then any error and the key hint.
+fn profile_form_lines(form: &ProfileForm) -> Vec<Line<'static>> {
+    let current = form.field();
+    let mut lines = Vec::new();
+    for field in form.fields() {
+        let value = form.value(*field);
+        let selected = *field == current;
+        let shown = if field.is_choice() {
+            format!("< {value} >")
+        } else if selected {
+            format!("{value}_")
+        } else {
+            value.to_string()
+        };
+        let label_style = if selected {
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        let mut spans = vec![
+            Span::styled(format!("{:<11}", field.label()), label_style),
+            Span::raw(shown),
+        ];
+        if value.is_empty() {
+            spans.push(Span::styled(
+                format!("  ({})", field.hint()),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        lines.push(Line::from(spans));
+    }
+    lines.push(Line::from(""));
+    if let Some(error) = &form.error {
+        lines.push(Line::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    lines.push(Line::from(
+        "Up/Down or Tab move, type to edit, Left/Right change type and danger, Enter save, Esc cancel.",
+    ));
+    lines
+}
+
+/// Local path entries listed under the transfer form at most.
+const TRANSFER_ENTRIES_SHOWN: usize = 8;
+
+/// The fields, the entries matching the local path while it is edited, then any
+/// error and the key hint.
+fn transfer_form_lines(form: &TransferForm) -> Vec<Line<'static>> {
+    let current = form.field();
+    let mut lines = Vec::new();
+    for &field in form.fields() {
+        let value = form.value(field);
+        let selected = field == current;
+        let shown = if field.is_choice() {
+            format!("< {value} >")
+        } else if selected {
+            format!("{value}_")
+        } else {
+            value.to_string()
+        };
+        let label_style = if selected {
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{:<12}", field.label()), label_style),
+            Span::raw(shown),
+        ]));
+    }
+    if current == TransferField::Local && !form.entries.is_empty() {
+        lines.push(Line::from(""));
+        for entry in form.entries.iter().take(TRANSFER_ENTRIES_SHOWN) {
+            lines.push(Line::from(Span::styled(
+                format!("  {entry}"),
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
+        if form.entries.len() > TRANSFER_ENTRIES_SHOWN {
+            lines.push(Line::from(Span::styled(
+                format!("  ... {} more", form.entries.len() - TRANSFER_ENTRIES_SHOWN),
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
+    }
+    lines.push(Line::from(""));
+    if let Some(error) = &form.error {
+        lines.push(Line::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    lines.push(Line::from(if form.favorites.is_empty() {
+        "Up/Down move, Tab completes the local path, Left/Right change direction and client, Enter start, Esc cancel."
+    } else {
+        "Up/Down move, Left/Right pick a favorite or change direction and client, Tab completes the local path, Enter start, Esc cancel."
+    }));
+    lines
+}
+
+/// Each preset with its forwards, the highlighted one reversed, plus the key hint.
+fn preset_picker_lines(picker: &PresetPickerState) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    for (index, preset) in picker.presets.iter().enumerate() {
+        let style = if index == picker.cursor {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        let forwards = preset
+            .forwards
+            .iter()
+            .map(|forward| format!("{} {} {}", forward.name, forward.kind, forward.listen))
+            .collect::<Vec<_>>()
+            .join(", ");
+        lines.push(Line::from(Span::styled(
+            format!("  {}  ({forwards})", preset.name),
+            style,
+        )));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(
+        "Up/Down choose, Enter add its forwards, Esc close.",
+    ));
+    lines
+}
+
+/// Entries around the cursor that fit in `height` rows, plus the key hint.
+fn history_lines(history: &HistoryState, height: u16) -> Vec<Line<'static>> {
+    let rows = usize::from(height.saturating_sub(5)).max(1);
+    let first = history.cursor.saturating_sub(rows - 1);
+    let mut lines = Vec::new();
+    for (index, entry) in history.entries.iter().enumerate().skip(first).take(rows) {
+        let record = &entry.record;
+        let result = match (record.ok, record.exit_code) {
+            (true, _) => "ok".to_string(),
+            (false, Some(code)) => format!("exit {code}"),
+            (false, None) => "failed".to_string(),
+        };
+        let target = record
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.get("cmdset_id"))
+            .and_then(|id| id.as_str())
+            .map(|id| format!(" {id}"))
+            .unwrap_or_default();
+        let who = record
+            .who()
+            .map(|who| format!("  by {who}"))
+            .unwrap_or_default();
+        let line = format!(
+            "  #{:<5} {}  {}{target}  {result}{who}{}",
+            record.id,
+            entry.when,
+            record.op,
+            if record.note.is_some() {
+                "  [note]"
+            } else {
+                ""
+            }
+        );
+        let style = if index == history.cursor {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else if record.ok {
+            Style::default()
+        } else {
+            Style::default().fg(Color::Red)
+        };
+        lines.push(Line::from(Span::styled(line, style)));
+    }
+    if history.entries.is_empty() {
+        lines.push(Line::from("  No entries match these filters."));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(
+        "Up/Down choose (older pages load at the end), Enter reopen output, r run again/reconnect,",
+    ));
+    lines.push(Line::from(
+        "f all/failed/ok, t period, x export JSON, Esc close. Purge with td log prune.",
+    ));
+    lines
+}
+
+fn bulk_edit_lines(bulk_edit: &BulkEditState) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    match &bulk_edit.stage {
+        BulkEditStage::ChooseField { cursor } => {
+            for (index, field) in BulkEditField::ALL.iter().enumerate() {
+                let line = format!("  {}", field.label());
+                lines.push(if index == *cursor {
+                    Line::from(Span::styled(
+                        line,
+                        Style::default().add_modifier(Modifier::REVERSED),
+                    ))
+                } else {
+                    Line::from(line)
+                });
+            }
+            lines.push(Line::from(""));
+            lines.push(Line::from("Up/Down choose, Enter select, Esc cancel."));
+        }
+        BulkEditStage::Input {
+            field,
+            input,
+            error,
+        } => {
+            lines.push(Line::from(format!(
+                "{}: {}",
+                field.label(),
+                field.input_hint()
+            )));
+            lines.push(Line::from(format!("Value: {input}")));
+            if let Some(error) = error {
+                lines.push(Line::from(Span::styled(
+                    error.clone(),
+                    Style::default().fg(Color::Red),
+                )));
+            }
+            lines.push(Line::from(""));
+            lines.push(Line::from("Enter preview, Esc back."));
+        }
+        BulkEditStage::Preview {
+            field, input, rows, ..
+        } => {
+            let changed = rows.iter().filter(|row| row.changed()).count();
+            lines.push(Line::from(format!(
+                "{} '{}': {changed} of {} profile(s) change.",
+                field.label(),
+                input.trim(),
+                rows.len()
+            )));
+            lines.push(Line::from(""));
+            for row in rows {
+                let text = if row.changed() {
+                    format!(
+                        "{} ({}): {} -> {}",
+                        row.name, row.profile_id, row.before, row.after
+                    )
+                } else {
+                    format!("{} ({}): unchanged", row.name, row.profile_id)
+                };
+                let style = if row.changed() {
+                    Style::default()
+                } else {
+                    Style::default().fg(Color::DarkGray)
+                };
+                lines.push(Line::from(Span::styled(text, style)));
+            }
+            lines.push(Line::from(""));
+            lines.push(Line::from(
+                "Enter apply all in one transaction, Esc edit value.",
+            ));
+        }
+    }
+    lines
+}
+
+/// Replaces the whole screen so hostnames and output stay hidden while locked.
+fn render_lock_screen(frame: &mut Frame<'_>, lock: &LockPrompt) {
+    frame.render_widget(Clear, frame.size());
+    let area = centered_rect(50, 30, frame.size());
+    let block = Block::default()
+        .title("Locked")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+    let mut lines = vec![
+        Line::from("TeraDock is locked after inactivity."),
+        Line::from(""),
+        Line::from("Enter the master password or lock PIN, then press Enter."),
+        Line::from(format!("Input: {}", "*".repeat(lock.input.chars().count()))),
+    ];
+    if let Some(error) = &lock.error {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    let paragraph = Paragraph::new(Text::from(lines))
+        .block(block)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+    frame.render_widget(paragraph, area);
+}
+
 fn render_profiles(frame: &mut Frame<'_>, state: &AppState, area: Rect) {
+    let now = Instant::now();
+    let now_ms = util::now_ms();
     let items = state
-        .filtered()
-        .iter()
-        .map(|profile| profile_item(profile, state.marked_profiles()))
+        .profile_rows()
+        .into_iter()
+        .map(|row| {
+            let profile = match row {
+                ProfileRow::Profile(index) => &state.filtered()[index],
+                ProfileRow::Header {
+                    label,
+                    count,
+                    collapsed,
+                    ..
+                } => {
+                    return ListItem::new(Line::from(Span::styled(
+                        format!("{} {label} ({count})", if collapsed { "▸" } else { "▾" }),
+                        Style::default()
+                            .fg(Color::Cyan)
+                            .add_modifier(Modifier::BOLD),
+                    )))
+                }
+            };
+            let health = state
+                .health_column_enabled()
+                .then(|| health_span(state.health_status(&profile.profile_id), now));
+            profile_item(
+                profile,
+                state.marked_profiles(),
+                health,
+                tunnel_span(state, &profile.profile_id)
+                    .into_iter()
+                    .chain(session_span(state, &profile.profile_id))
+                    .chain(preset_span(state, &profile.profile_id))
+                    .collect(),
+                state.profile_list_layout(),
+                now_ms,
+            )
+        })
         .collect::<Vec<_>>();
     let mut list_state = ListState::default();
     list_state.select(state.profile_cursor());
     let title = format!(
-        "Profiles ({}) marked:{}",
+        "{}Profiles ({}) marked:{}{}",
+        if state.snapshot_view().is_some() {
+            "[snapshot] "
+        } else if state.observer_mode() {
+            "[observer] "
+        } else {
+            ""
+        },
         state.filtered().len(),
-        state.marked_profiles().len()
+        state.marked_profiles().len(),
+        if state.tunnel_auto_restart() {
+            " fwd:auto-restart"
+        } else {
+            ""
+        }
     );
     let list = List::new(items)
         .block(pane_block(
@@ -90,28 +667,145 @@ fn render_profiles(frame: &mut Frame<'_>, state: &AppState, area: Rect) {
     frame.render_stateful_widget(list, area, &mut list_state);
 }
 
-fn render_right(frame: &mut Frame<'_>, state: &AppState, area: Rect) {
-    let columns = Layout::default()
+/// Where the panes of the main screen go in a frame of `area`; `render` draws into these
+/// and mouse clicks are matched against them.
+#[derive(Debug, Clone, Copy)]
+pub struct PaneAreas {
+    pub profiles: Rect,
+    /// The Action pane, or the resolved details while they are open.
+    pub actions: Rect,
+    /// The CommandSet list inside the Action pane; `None` while details are open.
+    pub cmdsets: Option<Rect>,
+    pub result_tabs: Rect,
+    pub results: Rect,
+}
+
+pub fn pane_areas(area: Rect, details_open: bool, layout: &PaneLayout) -> PaneAreas {
+    let body = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(layout.profiles_width),
+            Constraint::Percentage(100 - layout.profiles_width),
+        ])
+        .split(screen_rows(area)[2]);
+    let right = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
-        .split(area);
-    if state.details_open() {
-        render_details_pane(frame, state, columns[0]);
-    } else {
-        render_action_pane(frame, state, columns[0]);
+        .constraints([
+            Constraint::Percentage(layout.actions_height),
+            Constraint::Percentage(100 - layout.actions_height),
+        ])
+        .split(body[1]);
+    let results = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .split(right[1]);
+    PaneAreas {
+        profiles: body[0],
+        actions: right[0],
+        cmdsets: (!details_open).then(|| action_sections(right[0])[1]),
+        result_tabs: results[0],
+        results: results[1],
     }
-    render_results_pane(frame, state, columns[1]);
 }
 
-fn render_action_pane(frame: &mut Frame<'_>, state: &AppState, area: Rect) {
-    let sections = Layout::default()
+/// The pane border at column `x`, row `y`, which a mouse press there starts dragging:
+/// the edges either side of the profile list's right border, or either side of the Action
+/// pane's bottom border.
+pub fn pane_border_at(panes: &PaneAreas, x: u16, y: u16) -> Option<PaneSplit> {
+    let right_edge = panes.profiles.x + panes.profiles.width;
+    let body_rows = panes.profiles.y..panes.profiles.y + panes.profiles.height;
+    if (right_edge.saturating_sub(1)..=right_edge).contains(&x) && body_rows.contains(&y) {
+        return Some(PaneSplit::Profiles);
+    }
+    let bottom_edge = panes.actions.y + panes.actions.height;
+    let right_cols = panes.actions.x..panes.actions.x + panes.actions.width;
+    if (bottom_edge.saturating_sub(1)..=bottom_edge).contains(&y) && right_cols.contains(&x) {
+        return Some(PaneSplit::Actions);
+    }
+    None
+}
+
+/// The split, in percent, that puts `split`'s border under column `x` or row `y`.
+pub fn pane_split_percent(panes: &PaneAreas, split: PaneSplit, x: u16, y: u16) -> u16 {
+    let (start, len, at) = match split {
+        PaneSplit::Profiles => (
+            panes.profiles.x,
+            panes.profiles.width + panes.actions.width,
+            x,
+        ),
+        PaneSplit::Actions => (
+            panes.actions.y,
+            panes.actions.height + panes.result_tabs.height + panes.results.height,
+            y,
+        ),
+    };
+    if len == 0 {
+        return 0;
+    }
+    let offset = u32::from(at.saturating_sub(start)) * 100 / u32::from(len);
+    offset.min(100) as u16
+}
+
+/// The row of a bordered list drawn in `area` under terminal row `y`. The lists are drawn
+/// with a fresh `ListState`, so ratatui scrolls just far enough to keep `selected` in view.
+pub fn list_row_at(area: Rect, selected: Option<usize>, len: usize, y: u16) -> Option<usize> {
+    let top = area.y + 1;
+    let height = usize::from(area.height.saturating_sub(2));
+    if y < top || usize::from(y - top) >= height {
+        return None;
+    }
+    let offset = selected.map_or(0, |selected| (selected + 1).saturating_sub(height));
+    let row = offset + usize::from(y - top);
+    (row < len).then_some(row)
+}
+
+/// The result tab whose title is at column `x` of the tab bar drawn in `area`.
+pub fn result_tab_at(area: Rect, x: u16) -> Option<ResultTab> {
+    let mut left = area.x + 1;
+    for (tab, title) in RESULT_TABS {
+        // Tabs pads each title with a space on both sides and puts a divider between them.
+        let right = left + title.len() as u16 + 2;
+        if (left..right).contains(&x) {
+            return Some(tab);
+        }
+        left = right + 1;
+    }
+    None
+}
+
+const RESULT_TABS: [(ResultTab, &str); 4] = [
+    (ResultTab::Stdout, "stdout"),
+    (ResultTab::Stderr, "stderr"),
+    (ResultTab::Parsed, "parsed"),
+    (ResultTab::Summary, "summary"),
+];
+
+/// Filter pills, the key hint line, and the panes below them.
+fn screen_rows(area: Rect) -> Rc<[Rect]> {
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2),
+            Constraint::Length(1),
+            Constraint::Min(0),
+        ])
+        .split(area)
+}
+
+/// Action info, the CommandSet list, and the command preview.
+fn action_sections(area: Rect) -> Rc<[Rect]> {
+    Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(5),
             Constraint::Length(7),
             Constraint::Min(0),
         ])
-        .split(area);
+        .split(area)
+}
+
+fn render_action_pane(frame: &mut Frame<'_>, state: &AppState, area: Rect) {
+    let sections = action_sections(area);
 
     let info = Paragraph::new(action_info(state))
         .block(pane_block(
@@ -123,9 +817,21 @@ fn render_action_pane(frame: &mut Frame<'_>, state: &AppState, area: Rect) {
 
     let mut cmdset_state = ListState::default();
     cmdset_state.select(state.cmdset_cursor());
-    let cmdset_items = state.cmdsets().iter().map(cmdset_item).collect::<Vec<_>>();
+    let cmdset_items = state
+        .cmdsets()
+        .iter()
+        .map(|cmdset| cmdset_item(cmdset, state.scheduled_run(&cmdset.cmdset_id)))
+        .collect::<Vec<_>>();
     let cmdset_list = List::new(cmdset_items)
-        .block(Block::default().borders(Borders::ALL).title("CommandSets"))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(if state.cmdsets_loaded() {
+                    "CommandSets"
+                } else {
+                    "CommandSets (Tab to load)"
+                }),
+        )
         .highlight_style(Style::default().add_modifier(Modifier::BOLD));
     frame.render_stateful_widget(cmdset_list, sections[1], &mut cmdset_state);
 
@@ -140,40 +846,55 @@ fn render_action_pane(frame: &mut Frame<'_>, state: &AppState, area: Rect) {
     frame.render_widget(preview, sections[2]);
 }
 
-fn render_results_pane(frame: &mut Frame<'_>, state: &AppState, area: Rect) {
-    let sections = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Length(2), Constraint::Min(0)])
-        .split(area);
-
-    let titles = [
-        Span::raw("stdout"),
-        Span::raw("stderr"),
-        Span::raw("parsed"),
-        Span::raw("summary"),
-    ];
-    let selected = match state.result_tab() {
-        ResultTab::Stdout => 0,
-        ResultTab::Stderr => 1,
-        ResultTab::Parsed => 2,
-        ResultTab::Summary => 3,
+fn render_results_pane(frame: &mut Frame<'_>, state: &AppState, tabs_area: Rect, area: Rect) {
+    let selected = RESULT_TABS
+        .iter()
+        .position(|(tab, _)| *tab == state.result_tab())
+        .unwrap_or_default();
+    let tabs = Tabs::new(
+        RESULT_TABS
+            .iter()
+            .map(|(_, title)| *title)
+            .collect::<Vec<_>>(),
+    )
+    .select(selected)
+    .block(pane_block(
+        "Results",
+        state.active_pane() == ActivePane::Results,
+    ))
+    .highlight_style(
+        Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD),
+    );
+    frame.render_widget(tabs, tabs_area);
+
+    let steps = state.result_steps(Instant::now());
+    let area = if steps.len() > 1 && state.result_tab() != ResultTab::Summary {
+        let list_height = (steps.len() as u16 + 1).min((area.height / 3).max(2));
+        let sections = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(list_height), Constraint::Min(0)])
+            .split(area);
+        render_step_list(frame, state, &steps, sections[0]);
+        sections[1]
+    } else {
+        area
     };
-    let tabs = Tabs::new(titles.to_vec())
-        .select(selected)
-        .block(pane_block(
-            "Results",
-            state.active_pane() == ActivePane::Results,
-        ))
-        .highlight_style(
-            Style::default()
-                .fg(Color::Yellow)
-                .add_modifier(Modifier::BOLD),
-        );
-    frame.render_widget(tabs, sections[0]);
 
-    let content = result_content(state);
-    let paragraph = Paragraph::new(content).wrap(Wrap { trim: false });
-    frame.render_widget(paragraph, sections[1]);
+    let content = match state.live_run() {
+        Some(run) => live_content(state, run, usize::from(area.height)),
+        None => result_content(state),
+    };
+    let wrap = state
+        .result_layout(state.result_tab())
+        .map(|layout| layout.wrap)
+        .unwrap_or(true);
+    let mut paragraph = Paragraph::new(content);
+    if wrap {
+        paragraph = paragraph.wrap(Wrap { trim: false });
+    }
+    frame.render_widget(paragraph, area);
 }
 
 fn filters_line(state: &AppState) -> Line<'static> {
@@ -205,6 +926,8 @@ fn filters_line(state: &AppState) -> Line<'static> {
     let tag_focus = state.tag_cursor().unwrap_or("none");
 
     Line::from(vec![
+        env_pill(state.current_env()),
+        spacer(),
         pill("Type", &type_value, state.filters().profile_type.is_some()),
         spacer(),
         pill("Group", &group_value, state.filters().group.is_some()),
@@ -215,6 +938,22 @@ fn filters_line(state: &AppState) -> Line<'static> {
         spacer(),
         pill("Query", &query_value, state.filters().query.is_some()),
         spacer(),
+        pill(
+            "Archived",
+            if state.filters().include_archived {
+                "shown"
+            } else {
+                "hidden"
+            },
+            state.filters().include_archived,
+        ),
+        spacer(),
+        pill(
+            "Sort",
+            state.profile_sort().label(),
+            state.profile_sort() != ProfileSort::Name,
+        ),
+        spacer(),
         pill("Tag Focus", tag_focus, !state.tags().is_empty()),
     ])
 }
@@ -230,10 +969,19 @@ fn hints_line(state: &AppState) -> Line<'static> {
             ),
             Span::raw("  (Enter/Esc to stop)"),
         ]),
+        InputMode::ResultSearch => Line::from(vec![
+            Span::styled(
+                format!("Find in results: {}", state.result_search_input()),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("  (Enter to find, Esc to clear)"),
+        ]),
         InputMode::Normal => Line::from(vec![
             Span::styled(state.action_hint(), Style::default().fg(Color::Yellow)),
             Span::raw(
-                " | / search, s ssh, c settings, r run, R bulk, Space mark, d details, ? help, q quit",
+                " | / search, s ssh, c settings, r run, R bulk, E edit, Space mark, d details, H health, ? help, q quit",
             ),
         ]),
     }
@@ -293,6 +1041,137 @@ fn command_preview_lines(state: &AppState) -> Vec<Line<'static>> {
     lines
 }
 
+/// The targets of a bulk run, `BLAST_RADIUS_ROWS` at a time, and the first commands it
+/// sends; profiles that made the run ask for confirmation are marked with `!`.
+fn blast_radius_lines(radius: &BlastRadius) -> Vec<Line<'static>> {
+    let total = radius.targets.len();
+    let shown = &radius.targets[radius.scroll.min(total)..];
+    let shown = &shown[..shown.len().min(BLAST_RADIUS_ROWS)];
+    let mut lines = vec![Line::styled(
+        format!(
+            "Targets {}-{} of {total} (Up/Down to scroll):",
+            radius.scroll + 1,
+            radius.scroll + shown.len()
+        ),
+        Style::default().add_modifier(Modifier::BOLD),
+    )];
+    let width = |text: fn(&BulkTarget) -> &str| {
+        shown
+            .iter()
+            .map(|target| util::display_width(text(target)).min(24))
+            .max()
+            .unwrap_or_default()
+    };
+    let (id_width, name_width) = (width(|t| &t.profile_id), width(|t| &t.name));
+    let cell =
+        |text: &str, width: usize| util::pad_display(&util::truncate_display(text, 24, '~'), width);
+    for target in shown {
+        lines.push(Line::from(vec![
+            Span::styled(
+                if target.needs_confirm { "! " } else { "  " },
+                Style::default().fg(Color::Red),
+            ),
+            Span::raw(format!(
+                "{}  {}  {}  {}  ",
+                cell(&target.profile_id, id_width),
+                cell(&target.name, name_width),
+                target.host,
+                target.group.as_deref().unwrap_or("-")
+            )),
+            Span::styled(
+                target.danger_level.to_string(),
+                danger_tint(target.danger_level)
+                    .map_or_else(Style::default, |color| Style::default().fg(color)),
+            ),
+        ]));
+    }
+    if radius.reviewed() {
+        lines.push(Line::from("(end of list)"));
+    } else {
+        lines.push(Line::from(format!(
+            "... {} more",
+            total - radius.scroll - shown.len()
+        )));
+    }
+    lines.push(Line::styled(
+        "Commands:",
+        Style::default().add_modifier(Modifier::BOLD),
+    ));
+    lines.extend(
+        radius
+            .commands
+            .iter()
+            .map(|cmd| Line::from(format!("  $ {cmd}"))),
+    );
+    if radius.more_commands > 0 {
+        lines.push(Line::from(format!(
+            "  ... and {} more",
+            radius.more_commands
+        )));
+    }
+    lines
+}
+
+/// The steps of a multi-step run with their status and time; the highlighted row is the
+/// step whose output is shown below (`,`/`.` move it).
+fn render_step_list(frame: &mut Frame<'_>, state: &AppState, steps: &[StepRow], area: Rect) {
+    let mut items = vec![ListItem::new(Line::from(Span::styled(
+        format!("  all {} steps", steps.len()),
+        Style::default().fg(Color::DarkGray),
+    )))];
+    items.extend(steps.iter().map(|step| {
+        let color = match step.status {
+            StepStatus::Pending => Color::DarkGray,
+            StepStatus::Running => Color::Yellow,
+            StepStatus::Ok => Color::Green,
+            StepStatus::Failed => Color::Red,
+        };
+        let elapsed = step
+            .elapsed_ms
+            .map(|ms| format!("{}.{}s", ms / 1000, ms % 1000 / 100))
+            .unwrap_or_default();
+        ListItem::new(Line::from(vec![
+            Span::raw(format!("{:>3} ", step.ord)),
+            Span::styled(
+                format!("{:<8}", step.status.label()),
+                Style::default().fg(color),
+            ),
+            Span::raw(format!("{elapsed:>7}  {}", step.cmd)),
+        ]))
+    }));
+    let mut list_state = ListState::default();
+    list_state.select(Some(state.result_step().map_or(0, |step| step + 1)));
+    let list = List::new(items)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol(">");
+    frame.render_stateful_widget(list, area, &mut list_state);
+}
+
+/// Tail of the output collected so far, so the newest lines stay visible.
+fn live_content(state: &AppState, run: &LiveRun, height: usize) -> Text<'static> {
+    let step = state.result_step().and_then(|index| run.steps.get(index));
+    let output = match (state.result_tab(), step) {
+        (ResultTab::Stdout, Some(step)) => &step.stdout,
+        (ResultTab::Stderr, Some(step)) => &step.stderr,
+        (ResultTab::Stdout, None) => &run.stdout,
+        (ResultTab::Stderr, None) => &run.stderr,
+        (ResultTab::Parsed | ResultTab::Summary, _) => {
+            return Text::from(format!(
+                "{}\nResults appear when the run finishes.",
+                run.progress()
+            ));
+        }
+    };
+    let lines = output.lines().collect::<Vec<_>>();
+    let skip = lines.len().saturating_sub(height);
+    Text::from(
+        lines[skip..]
+            .iter()
+            .map(|line| Line::from(line.to_string()))
+            .collect::<Vec<_>>(),
+    )
+}
+
 fn result_content(state: &AppState) -> Text<'static> {
     if let ResultTab::Summary = state.result_tab() {
         return summary_content(state);
@@ -303,25 +1182,78 @@ fn result_content(state: &AppState) -> Text<'static> {
     if let Some(error) = &result.error {
         return Text::from(format!("Error: {error}"));
     }
-    let content = match state.result_tab() {
-        ResultTab::Stdout => {
-            if result.stdout.is_empty() {
-                "(stdout empty)".to_string()
-            } else {
-                result.stdout.clone()
-            }
+    let buffer = state.result_buffer().unwrap_or_default();
+    if buffer.lines.is_empty() {
+        let empty = match state.result_tab() {
+            ResultTab::Stderr => "(stderr empty)",
+            _ => "(stdout empty)",
+        };
+        return Text::from(empty.to_string());
+    }
+    let shown = buffer.lines.len();
+    let mut lines = buffer
+        .lines
+        .into_iter()
+        .skip(state.result_scroll())
+        .map(|line| highlight_matches(line, state.result_query()))
+        .collect::<Vec<_>>();
+    if buffer.hidden > 0 {
+        lines.push(Line::styled(
+            format!("... {} more line(s) (max_lines={shown})", buffer.hidden),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    Text::from(lines)
+}
+
+/// Styles every case-insensitive occurrence of `query` in `line`.
+fn highlight_matches(line: String, query: Option<&str>) -> Line<'static> {
+    let Some(query) = query.filter(|query| !query.is_empty()) else {
+        return Line::from(line);
+    };
+    // Compared char by char so every span ends on a boundary of `line` itself, even where
+    // lowercasing changes a character's length (`ẞ`, `İ`).
+    let needle: Vec<char> = query.chars().collect();
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let style = Style::default()
+        .fg(Color::Black)
+        .bg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+    let mut spans = Vec::new();
+    let mut rest = 0;
+    let mut at = 0;
+    while at + needle.len() <= chars.len() {
+        let window = &chars[at..at + needle.len()];
+        if !window
+            .iter()
+            .zip(&needle)
+            .all(|(&(_, ch), &wanted)| chars_match(ch, wanted))
+        {
+            at += 1;
+            continue;
         }
-        ResultTab::Stderr => {
-            if result.stderr.is_empty() {
-                "(stderr empty)".to_string()
-            } else {
-                result.stderr.clone()
-            }
+        let start = window[0].0;
+        let end = chars
+            .get(at + needle.len())
+            .map_or(line.len(), |&(index, _)| index);
+        if start > rest {
+            spans.push(Span::raw(line[rest..start].to_string()));
         }
-        ResultTab::Parsed => result.parsed_pretty.clone(),
-        ResultTab::Summary => String::new(),
-    };
-    Text::from(content)
+        spans.push(Span::styled(line[start..end].to_string(), style));
+        rest = end;
+        at += needle.len();
+    }
+    if spans.is_empty() {
+        return Line::from(line);
+    }
+    if rest < line.len() {
+        spans.push(Span::raw(line[rest..].to_string()));
+    }
+    Line::from(spans)
+}
+
+fn chars_match(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
 }
 
 fn summary_content(state: &AppState) -> Text<'static> {
@@ -361,44 +1293,204 @@ fn summary_content(state: &AppState) -> Text<'static> {
     Text::from(lines)
 }
 
-fn cmdset_item(cmdset: &tdcore::cmdset::CmdSet) -> ListItem<'static> {
-    ListItem::new(Line::from(vec![
+fn cmdset_item(cmdset: &tdcore::cmdset::CmdSet, next_run: Option<&str>) -> ListItem<'static> {
+    let mut spans = vec![
         Span::styled(
             cmdset.name.clone(),
             Style::default().add_modifier(Modifier::BOLD),
         ),
         Span::raw(format!(" ({})", cmdset.cmdset_id)),
-    ]))
+    ];
+    if let Some(next_run) = next_run {
+        spans.push(Span::styled(
+            format!("  next {next_run}"),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    ListItem::new(Line::from(spans))
 }
 
 fn profile_item(
-    profile: &tdcore::profile::Profile,
+    profile: &Profile,
     marked: &std::collections::BTreeSet<String>,
+    health: Option<Span<'static>>,
+    badges: Vec<Span<'static>>,
+    layout: &ProfileListLayout,
+    now_ms: i64,
 ) -> ListItem<'static> {
-    let mut meta = format!(
-        "{}@{}:{} [{}] danger:{}",
-        profile.user, profile.host, profile.port, profile.profile_type, profile.danger_level
-    );
-    if let Some(group) = &profile.group {
-        meta.push_str(&format!(" group:{}", group));
-    }
-    if !profile.tags.is_empty() {
-        meta.push_str(&format!(" tags:{}", profile.tags.join(",")));
+    let max_width = layout.active_max_width();
+    let mut id = None;
+    let mut meta = Vec::new();
+    for column in layout.active_columns() {
+        let Some(text) = profile_column_text(profile, *column, now_ms) else {
+            continue;
+        };
+        let text = match max_width {
+            Some(max) => truncate_field(&text, max),
+            None => text,
+        };
+        if *column == ProfileColumn::Id {
+            id = Some(text);
+        } else {
+            meta.push(text);
+        }
     }
     let mark = if marked.contains(&profile.profile_id) {
         Span::styled("[*] ", Style::default().fg(Color::Yellow))
     } else {
         Span::raw("[ ] ")
     };
-    ListItem::new(Line::from(vec![
-        mark,
-        Span::styled(
-            format!("{} ", profile.name),
-            Style::default().add_modifier(Modifier::BOLD),
+    let mut spans = vec![mark];
+    spans.extend(health);
+    spans.extend(badges);
+    let tint = layout
+        .badges
+        .then(|| danger_tint(profile.danger_level))
+        .flatten();
+    if layout.badges {
+        spans.push(protocol_badge(profile.profile_type));
+        spans.push(danger_badge(profile.danger_level));
+    }
+    let name_style = Style::default().add_modifier(Modifier::BOLD);
+    spans.push(Span::styled(
+        format!("{} ", profile.name),
+        tint.map_or(name_style, |color| name_style.fg(color)),
+    ));
+    if let Some(id) = id {
+        spans.push(Span::styled(
+            format!("({id}) "),
+            tint.map_or(Style::default(), |color| Style::default().fg(color)),
+        ));
+    }
+    spans.push(Span::styled(
+        meta.join(" "),
+        Style::default().fg(Color::DarkGray),
+    ));
+    ListItem::new(Line::from(spans))
+}
+
+/// Fixed-width protocol label: `SSH`, `TEL`, or `SER`.
+fn protocol_badge(profile_type: ProfileType) -> Span<'static> {
+    let (label, color) = match profile_type {
+        ProfileType::Ssh => ("SSH ", Color::Blue),
+        ProfileType::Telnet => ("TEL ", Color::Yellow),
+        ProfileType::Serial => ("SER ", Color::Magenta),
+    };
+    Span::styled(label, Style::default().fg(color))
+}
+
+/// Blank for normal profiles, a yellow `!` for high, and a red bold `!!` for critical.
+fn danger_badge(danger: DangerLevel) -> Span<'static> {
+    match danger {
+        DangerLevel::Normal => Span::raw("   "),
+        DangerLevel::High => Span::styled("!  ", Style::default().fg(Color::Yellow)),
+        DangerLevel::Critical => Span::styled(
+            "!! ",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
         ),
-        Span::raw(format!("({}) ", profile.profile_id)),
-        Span::styled(meta, Style::default().fg(Color::DarkGray)),
-    ]))
+    }
+}
+
+fn danger_tint(danger: DangerLevel) -> Option<Color> {
+    match danger {
+        DangerLevel::Normal => None,
+        DangerLevel::High => Some(Color::Yellow),
+        DangerLevel::Critical => Some(Color::Red),
+    }
+}
+
+/// Text for one profile list field, or `None` when the profile has no value for it.
+fn profile_column_text(profile: &Profile, column: ProfileColumn, now_ms: i64) -> Option<String> {
+    let text = match column {
+        ProfileColumn::Id => profile.profile_id.clone(),
+        ProfileColumn::Endpoint => format!("{}@{}:{}", profile.user, profile.host, profile.port),
+        ProfileColumn::User => profile.user.clone(),
+        ProfileColumn::Host => profile.host.clone(),
+        ProfileColumn::Port => format!(":{}", profile.port),
+        ProfileColumn::Type => format!("[{}]", profile.profile_type),
+        ProfileColumn::Danger => format!("danger:{}", profile.danger_level),
+        ProfileColumn::Group => format!("group:{}", profile.group.as_ref()?),
+        ProfileColumn::Tags if profile.tags.is_empty() => return None,
+        ProfileColumn::Tags => format!("tags:{}", profile.tags.join(",")),
+        ProfileColumn::LastUsed => {
+            format!(
+                "used {}",
+                util::format_relative_ms(profile.last_used_at?, now_ms)
+            )
+        }
+        ProfileColumn::Note => {
+            let note = profile.note.as_deref()?.lines().next()?.trim();
+            (!note.is_empty()).then(|| format!("note:{note}"))?
+        }
+        ProfileColumn::Archived => {
+            profile.archived_at?;
+            "archived".to_string()
+        }
+    };
+    Some(text)
+}
+
+fn truncate_field(text: &str, max: usize) -> String {
+    util::truncate_display(text, max, '…')
+}
+
+fn health_span(status: Option<&HealthStatus>, now: Instant) -> Span<'static> {
+    let (label, color) = match status {
+        None => ("----", Color::DarkGray),
+        Some(HealthStatus::Pending) => ("....", Color::Yellow),
+        Some(HealthStatus::NotApplicable) => ("n/a ", Color::DarkGray),
+        Some(HealthStatus::Ok { .. }) => ("up  ", Color::Green),
+        Some(HealthStatus::Failed { .. }) => ("down", Color::Red),
+    };
+    // Stale results keep their label but are dimmed and marked with '~'.
+    let stale = status.is_some_and(|status| status.is_stale(now));
+    let style = if stale {
+        Style::default().fg(Color::DarkGray)
+    } else {
+        Style::default().fg(color)
+    };
+    let marker = if stale { '~' } else { ' ' };
+    Span::styled(format!("{label}{marker} "), style)
+}
+
+/// `fwd` when the profile has tunnel sessions: red with `!` if any is dead, dim until
+/// the first check.
+fn tunnel_span(state: &AppState, profile_id: &str) -> Option<Span<'static>> {
+    let sessions = state.tunnel_sessions(profile_id).collect::<Vec<_>>();
+    if sessions.is_empty() {
+        return None;
+    }
+    let (label, color) = if sessions.iter().any(|session| session.health.is_none()) {
+        ("fwd  ", Color::DarkGray)
+    } else if sessions.iter().all(|session| session.is_healthy()) {
+        ("fwd  ", Color::Green)
+    } else {
+        ("fwd! ", Color::Red)
+    };
+    Some(Span::styled(label, Style::default().fg(color)))
+}
+
+/// `ssh` (with a count above one) while interactive sessions to the profile are running.
+fn session_span(state: &AppState, profile_id: &str) -> Option<Span<'static>> {
+    let count = state.active_sessions(profile_id).count();
+    let label = match count {
+        0 => return None,
+        1 => "ssh  ".to_string(),
+        count => format!("ssh{count:<2}"),
+    };
+    Some(Span::styled(label, Style::default().fg(Color::Green)))
+}
+
+/// `[name]` for each forwarding preset whose forwards the profile has.
+fn preset_span(state: &AppState, profile_id: &str) -> Option<Span<'static>> {
+    let names = state.preset_matches(profile_id);
+    if names.is_empty() {
+        return None;
+    }
+    Some(Span::styled(
+        format!("[{}] ", names.join(",")),
+        Style::default().fg(Color::Magenta),
+    ))
 }
 
 fn pane_block(title: &str, active: bool) -> Block<'static> {
@@ -433,10 +1525,52 @@ fn detail_lines(state: &AppState) -> Vec<Line<'static>> {
         return vec![Line::from("No details available.".to_string())];
     }
     let start = state.details_scroll().min(lines.len());
-    lines
-        .iter()
-        .skip(start)
-        .map(|line| Line::from(line.clone()))
+    let health = state
+        .selected_profile()
+        .filter(|_| state.health_column_enabled())
+        .and_then(|profile| state.health_status(&profile.profile_id))
+        .map(|status| Line::from(format!("Health: {}", status.summary(Instant::now()))));
+    let tunnels = state
+        .selected_profile()
+        .into_iter()
+        .flat_map(|profile| state.tunnel_sessions(&profile.profile_id))
+        .map(|session| {
+            let restarts = if session.restarts > 0 {
+                format!(" (restarts: {})", session.restarts)
+            } else {
+                String::new()
+            };
+            Line::from(format!(
+                "Tunnel {} {:?}: {}{restarts}",
+                session.session_id,
+                session.forwards,
+                session.health_summary()
+            ))
+        })
+        .collect::<Vec<_>>();
+    let sessions = state
+        .selected_profile()
+        .into_iter()
+        .flat_map(|profile| state.active_sessions(&profile.profile_id))
+        .map(|session| {
+            Line::from(format!(
+                "SSH session {} (pid {}) started {}; K ends it",
+                session.id,
+                session.pid,
+                util::format_relative_ms(session.started_at, util::now_ms())
+            ))
+        })
+        .collect::<Vec<_>>();
+    health
+        .into_iter()
+        .chain(tunnels)
+        .chain(sessions)
+        .chain(
+            lines
+                .iter()
+                .skip(start)
+                .map(|line| Line::from(line.clone())),
+        )
         .collect()
 }
 
@@ -447,25 +1581,65 @@ fn help_lines() -> Vec<Line<'static>> {
         Line::from("  Tab         cycle panes"),
         Line::from("  Up/Down     move selection"),
         Line::from(""),
+        Line::from("Profile list badges"),
+        Line::from("  SSH/TEL/SER protocol; ! high danger (yellow row), !! critical (red row)"),
+        Line::from("  fwd tunnels, ssh open sessions, [name] matching forwarding presets"),
+        Line::from(""),
         Line::from("Actions"),
         Line::from("  s           open interactive SSH session"),
         Line::from("  c           open settings"),
         Line::from("  r / Enter   run CommandSet"),
         Line::from("  R           run CommandSet on marked profiles"),
+        Line::from("  E           bulk edit marked profiles (group, tags, danger, client)"),
+        Line::from("  Ctrl+Z      undo the last bulk edit"),
+        Line::from("  a / m       add a profile / edit the selected profile in a form"),
+        Line::from("  b / N       edit the selected CommandSet's steps / new CommandSet"),
+        Line::from("  p           add a forwarding preset to marked (or the selected) profiles"),
+        Line::from(
+            "  U           push or pull a file with scp/sftp (Tab completes, favorites fill)",
+        ),
+        Line::from("  Ctrl+K      command palette: type to find any action, Enter runs it"),
+        Line::from(
+            "  L           history of the selected profile; Enter reopens, r re-runs, f/t filter",
+        ),
+        Line::from("  P           copy the profile's linked password (needs td secret unlock)"),
         Line::from("  d           toggle resolved details"),
-        Line::from("  Space       mark/unmark profile"),
-        Line::from("  critical    type shown profile id(s), Enter confirms, Esc cancels"),
+        Line::from("  H           toggle health status column (~ marks stale results)"),
+        Line::from("              details show RTT and loss of the last check"),
+        Line::from("  u           re-check health of marked or selected profiles"),
+        Line::from("  F           toggle auto-restart of dead tunnels (fwd! in the list)"),
+        Line::from(
+            "  K           end the selected profile's running SSH session (ssh in the list)",
+        ),
+        Line::from("  Space       mark/unmark profile (on a header: every profile under it)"),
+        Line::from("  critical    type shown profile id(s) or code, Enter confirms, Esc cancels"),
         Line::from(""),
         Line::from("Filters"),
         Line::from("  T           cycle profile type filter"),
         Line::from("  g           cycle group filter"),
         Line::from("  D           cycle danger filter"),
+        Line::from("  A           show/hide archived profiles"),
+        Line::from("  o           sort profiles: name / last used / most used"),
+        Line::from("  G           group the list: none / by group / by tag"),
+        Line::from("  z           collapse/expand the current header (Enter on a header too)"),
         Line::from("  [ / ]       tag cursor"),
         Line::from("  x           toggle tag filter"),
         Line::from("  C           clear filters"),
         Line::from(""),
         Line::from("Results"),
         Line::from("  1/2/3/4     stdout/stderr/parsed/summary tabs"),
+        Line::from("  , / .       show the previous / next step's output (multi-step runs)"),
+        Line::from("  w           toggle line wrap for the current tab"),
+        Line::from("  W           toggle wide profile list (tui.profile_list columns)"),
+        Line::from("  < / >       narrow / widen the profile list (or drag its border)"),
+        Line::from("  { / }       shrink / grow the Action pane (or drag its border)"),
+        Line::from("  =           reset pane sizes (saved in tui.layout)"),
+        Line::from("  t           toggle per-line step timestamps for the current tab"),
+        Line::from("  j/k PgUp/PgDn  scroll (Results pane focused; PgUp/PgDn anywhere)"),
+        Line::from("  /           find in results when the Results pane is focused"),
+        Line::from("  n           jump to the next match"),
+        Line::from("  y           copy the current tab's output to the clipboard"),
+        Line::from("  e           export the last run or bulk run as a json/text/html report"),
         Line::from(""),
         Line::from("Other"),
         Line::from("  ?           toggle help"),
@@ -485,6 +1659,20 @@ fn pill(label: &str, value: &str, active: bool) -> Span<'static> {
     Span::styled(format!("[{}: {}]", label, value), style)
 }
 
+/// The env preset in use, kept in view because it changes what every action resolves to.
+fn env_pill(env: Option<&str>) -> Span<'static> {
+    match env {
+        Some(env) => Span::styled(
+            format!("[Env: {env}]"),
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Magenta)
+                .add_modifier(Modifier::BOLD),
+        ),
+        None => pill("Env", "none", false),
+    }
+}
+
 fn spacer() -> Span<'static> {
     Span::raw(" ")
 }
@@ -508,3 +1696,35 @@ fn centered_rect(percent_x: u16, percent_y: u16, rect: Rect) -> Rect {
         ])
         .split(popup_layout[1])[1]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highlighted(line: &Line<'_>) -> Vec<String> {
+        line.spans
+            .iter()
+            .filter(|span| span.style.bg == Some(Color::Yellow))
+            .map(|span| span.content.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn highlights_matches_without_splitting_characters() {
+        let line = highlight_matches("ẞİ… Error: ERROR".to_string(), Some("error"));
+        assert_eq!(highlighted(&line), ["Error", "ERROR"]);
+        let text: String = line
+            .spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect();
+        assert_eq!(text, "ẞİ… Error: ERROR");
+
+        let line = highlight_matches("Straße ẞİ…".to_string(), Some("ß"));
+        assert_eq!(highlighted(&line), ["ß", "ẞ"]);
+        let line = highlight_matches("Straße ẞİ…".to_string(), Some("ẞİ"));
+        assert_eq!(highlighted(&line), ["ẞİ"]);
+        let line = highlight_matches("ẞİ…".to_string(), Some("…"));
+        assert_eq!(highlighted(&line), ["…"]);
+    }
+}